@@ -0,0 +1,30 @@
+//! Criterion suite over the stages `tripwired bench` times (see
+//! `bench::cases`): `cargo bench --bench pipeline`
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use tripwire_kernel::audit::Durability;
+use tripwire_kernel::bench::{self, HostOptions};
+use tripwire_kernel::filter::FilterConfig;
+
+fn pipeline(c: &mut Criterion) {
+    let dir = tempfile::tempdir().expect("scratch directory");
+    let options = HostOptions {
+        lines: 10_000,
+        suspicious_pct: 5,
+        records: 1_000,
+        durability: Durability::Buffered,
+    };
+    let scratch = dir.path().join("audit.jsonl");
+    let cases = bench::cases(&FilterConfig::default(), &options, &scratch).expect("bench cases");
+    for mut case in cases {
+        let mut group = c.benchmark_group(case.group);
+        group.throughput(Throughput::Elements(1));
+        group.bench_function(&case.name, |b| {
+            b.iter_custom(|n| (case.time)(n).expect(&case.name))
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, pipeline);
+criterion_main!(benches);
@@ -0,0 +1,34 @@
+//! Fuzz the model verdict parser: no panics, and the precedence rules of
+//! `verdict` hold for any answer.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[path = "../../src/verdict.rs"]
+#[allow(dead_code)]
+mod verdict;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    let strict = verdict::parse(text, true);
+    let loose = verdict::parse(text, false);
+
+    // A strict verdict reads the same without the schema, and fenced
+    if let Some(ref v) = strict {
+        assert_eq!(loose.as_ref().map(|l| l.action), Some(v.action));
+        let fenced = format!("```json\n{}\n```", text);
+        assert_eq!(verdict::parse(&fenced, true).as_ref(), Some(v));
+    }
+
+    // A truncated answer reads the same action or none
+    if let Some(ref v) = loose {
+        for cut in (0..text.len()).filter(|&i| text.is_char_boundary(i)) {
+            if let Some(t) = verdict::parse(&text[..cut], false) {
+                assert_eq!(t.action, v.action);
+            }
+        }
+    }
+});
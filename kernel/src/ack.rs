@@ -0,0 +1,139 @@
+//! Acknowledgements - Each Line's Verdict, Written Back
+//!
+//! The protocol is fire-and-forget unless the kernel runs with `--ack`:
+//! then every line read on a TCP, Unix socket or named pipe connection is
+//! answered once judged, so an agent framework can submit the action it
+//! is about to take and wait for the kill-switch before executing it:
+//!
+//! ```text
+//! {"id":1,"action":"SUSTAIN"}
+//! {"id":2,"action":"KILL"}
+//! ```
+//!
+//! `id` counts the connection's lines from 1, in order. `action` is what
+//! was done (`SUSTAIN`, `KILL`, `PAUSE`; the most severe when a rate or
+//! flood escalation fired on the same line), or why the line was not
+//! judged: `DROPPED` (flow control), `REJECTED` (a frame that is not
+//! valid UTF-8), `PIPELINE_ERROR`, `HELLO` (the connection's introduction,
+//! see `agents`). Acks are JSON lines, or frames on a
+//! framed connection (see `line`). Clients that don't read them are not
+//! hurt: acks are written by a task of their own, and past `QUEUE`
+//! unread acks the next ones are dropped (and counted) instead of
+//! stalling the connection. Guard answers (see `guard`) take the same
+//! way, with or without `--ack`; MCP sessions (see `mcp`) are always
+//! acknowledged.
+
+use serde::Serialize;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tokio_util::task::TaskTracker;
+
+/// Acks waiting for a slow reader before new ones are dropped
+pub const QUEUE: usize = 1024;
+
+/// The write side of one connection
+pub struct Acks {
+    tx: mpsc::Sender<Vec<u8>>,
+    dropped: u64,
+    /// Answer every line, whatever `--ack` says
+    every_line: bool,
+}
+
+impl Acks {
+    /// Write acks to `writer` from a task on `tracker`
+    pub fn spawn<W>(tracker: &TaskTracker, mut writer: W) -> Self
+    where
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        let (tx, mut rx) = mpsc::channel::<Vec<u8>>(QUEUE);
+        tracker.spawn(async move {
+            while let Some(ack) = rx.recv().await {
+                if writer.write_all(&ack).await.is_err() {
+                    break;
+                }
+            }
+            let _ = writer.shutdown().await;
+        });
+        Self {
+            tx,
+            dropped: 0,
+            every_line: false,
+        }
+    }
+
+    /// Answer every line of the connection, even without `--ack`
+    pub fn every_line(mut self) -> Self {
+        self.every_line = true;
+        self
+    }
+
+    /// Every line is answered, even without `--ack`
+    pub fn answers_every_line(&self) -> bool {
+        self.every_line
+    }
+
+    /// Answer line `id`; `false` if the ack was dropped
+    pub fn send(&mut self, id: u64, action: &str, framed: bool) -> bool {
+        let sent = self.tx.try_send(encode(id, action, framed)).is_ok();
+        if !sent {
+            self.dropped += 1;
+        }
+        sent
+    }
+
+    /// Acks dropped so far
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+#[derive(Serialize)]
+struct Ack<'a> {
+    id: u64,
+    action: &'a str,
+}
+
+/// One ack as written: a JSON line, or a length-prefixed frame
+fn encode(id: u64, action: &str, framed: bool) -> Vec<u8> {
+    let json = serde_json::to_string(&Ack { id, action }).expect("ack serializes");
+    match framed {
+        true => [&(json.len() as u32).to_be_bytes()[..], json.as_bytes()].concat(),
+        false => format!("{}\n", json).into_bytes(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(
+            encode(3, "KILL", false),
+            b"{\"id\":3,\"action\":\"KILL\"}\n"
+        );
+        let frame = encode(1, "SUSTAIN", true);
+        assert_eq!(frame[..4], [0, 0, 0, 27]);
+        assert_eq!(&frame[4..], b"{\"id\":1,\"action\":\"SUSTAIN\"}");
+    }
+
+    #[tokio::test]
+    async fn test_acks() {
+        let (client, mut server) = tokio::io::duplex(64);
+        let tracker = TaskTracker::new();
+        let mut acks = Acks::spawn(&tracker, client);
+        assert!(acks.send(1, "SUSTAIN", false));
+        assert!(acks.send(2, "DROPPED", false));
+        drop(acks);
+        tracker.close();
+        tracker.wait().await;
+
+        let mut out = String::new();
+        server.read_to_string(&mut out).await.unwrap();
+        assert_eq!(
+            out,
+            "{\"id\":1,\"action\":\"SUSTAIN\"}\n{\"id\":2,\"action\":\"DROPPED\"}\n"
+        );
+    }
+}
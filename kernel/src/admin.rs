@@ -0,0 +1,547 @@
+//! Admin API - Runtime Stats, Metrics and Operator Overrides
+//!
+//! Loopback-only HTTP endpoint for operators and scrapers:
+//! - `GET /stats`   - JSON counters with per-rule match counts
+//! - `GET /metrics` - Prometheus text exposition
+//! - `GET /decisions?after=ID` - recent decision records (newest 256)
+//! - `GET /agents`  - connected agents
+//! - `GET /templates?limit=N` - today's most frequent line templates
+//!   (default 50, see `normalize`)
+//! - `GET /events`  - WebSocket stream of every decision record as JSON
+//!   (`?actions=KILL,FAIL` to filter, `?after=ID` to replay recent
+//!   records first)
+//! - `POST /disarm` - stop kill actions from signaling the target
+//!   (`for_ms` for a timed hold, otherwise until `POST /arm`)
+//! - `POST /arm` - lift a hold; also overrides the canary gate
+//! - `POST /emergency-kill` - kill the target now, armed or not
+//! - `POST /decisions/{id}/feedback` - label decision `id` a
+//!   `false-positive` or `false-negative` (`{"operator": "...", "label":
+//!   "false-positive", "reason": "..."}`), written to the audit trail as a
+//!   `correction` event linked by `decision_id` (see `dataset`)
+//! - `GET /healthz`, `GET /readyz` - liveness and readiness (see `probe`)
+//!
+//! The override endpoints take `{"operator": "...", "reason": "..."}` and
+//! are written to the audit trail as `operator` events. They, and feedback,
+//! need `Authorization: Bearer <token>` with the token in
+//! `--admin-token-file`; without one configured they are refused (any local
+//! user can reach a loopback port).
+
+use crate::agents::AgentStatus;
+use crate::audit::{self, CorrectionEvent, DecisionRecord, Label, OperatorEvent};
+use crate::normalize::TemplateReport;
+use crate::probe::{self, Probes};
+use crate::stats::StatsSnapshot;
+use crate::Kernel;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{error, info, warn};
+
+/// Serve the admin API until shutdown
+pub async fn serve(port: u16, kernel: Arc<Kernel>, probes: Probes) -> std::io::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port)).await?;
+    info!("📊 Admin API on http://127.0.0.1:{}", port);
+
+    let shutdown = kernel.shutdown.clone();
+    axum::serve(listener, router(kernel).merge(probe::routes(probes)))
+        .with_graceful_shutdown(shutdown.cancelled_owned())
+        .await
+}
+
+fn router(kernel: Arc<Kernel>) -> Router {
+    Router::new()
+        .route("/stats", get(stats))
+        .route("/metrics", get(metrics))
+        .route("/decisions", get(decisions))
+        .route("/agents", get(agents))
+        .route("/templates", get(templates))
+        .route("/events", get(events))
+        .merge(overrides(Arc::clone(&kernel)))
+        .with_state(kernel)
+}
+
+/// Endpoints that change what the kernel does, behind [`authorize`]
+fn overrides(kernel: Arc<Kernel>) -> Router<Arc<Kernel>> {
+    Router::new()
+        .route("/disarm", post(disarm))
+        .route("/arm", post(arm))
+        .route("/emergency-kill", post(emergency_kill))
+        .route("/decisions/{id}/feedback", post(feedback))
+        .route_layer(middleware::from_fn_with_state(kernel, authorize))
+}
+
+/// Let through requests bearing the `--admin-token-file` token
+async fn authorize(State(kernel): State<Arc<Kernel>>, request: Request, next: Next) -> Response {
+    let Some(ref digest) = kernel.config.admin_token else {
+        warn!("🔐 Admin override refused: no --admin-token-file configured");
+        return (StatusCode::FORBIDDEN, "overrides need --admin-token-file").into_response();
+    };
+    let token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if token.map(|token| audit::sha256_hex(token.trim())).as_ref() != Some(digest) {
+        warn!("🔐 Admin override refused: missing or wrong bearer token");
+        return (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+            "admin token required",
+        )
+            .into_response();
+    }
+    next.run(request).await
+}
+
+async fn stats(State(kernel): State<Arc<Kernel>>) -> Json<StatsSnapshot> {
+    Json(kernel.snapshot().await)
+}
+
+async fn metrics(State(kernel): State<Arc<Kernel>>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        kernel.snapshot().await.to_prometheus(),
+    )
+}
+
+#[derive(Deserialize)]
+struct DecisionsQuery {
+    /// Only records with a higher ID
+    #[serde(default)]
+    after: u64,
+}
+
+async fn decisions(
+    State(kernel): State<Arc<Kernel>>,
+    Query(query): Query<DecisionsQuery>,
+) -> Json<Vec<DecisionRecord>> {
+    Json(kernel.audit_trail.recent(query.after))
+}
+
+async fn agents(State(kernel): State<Arc<Kernel>>) -> Json<Vec<AgentStatus>> {
+    Json(kernel.agents.snapshot())
+}
+
+#[derive(Deserialize)]
+struct TemplatesQuery {
+    #[serde(default = "default_templates")]
+    limit: usize,
+}
+
+fn default_templates() -> usize {
+    50
+}
+
+async fn templates(
+    State(kernel): State<Arc<Kernel>>,
+    Query(query): Query<TemplatesQuery>,
+) -> Json<TemplateReport> {
+    Json(kernel.audit_trail.templates(query.limit))
+}
+
+#[derive(Deserialize)]
+struct EventsQuery {
+    /// Comma-separated actions to forward (default: all)
+    actions: Option<String>,
+    /// Replay buffered records above this ID before streaming
+    after: Option<u64>,
+}
+
+impl EventsQuery {
+    fn wants(&self, record: &DecisionRecord) -> bool {
+        self.actions.as_deref().is_none_or(|actions| {
+            actions
+                .split(',')
+                .any(|a| a.trim().eq_ignore_ascii_case(&record.action))
+        })
+    }
+}
+
+async fn events(
+    State(kernel): State<Arc<Kernel>>,
+    Query(query): Query<EventsQuery>,
+    ws: WebSocketUpgrade,
+) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| stream_events(socket, kernel, query))
+}
+
+/// Forward decision records until the client leaves or the kernel shuts down
+async fn stream_events(mut socket: WebSocket, kernel: Arc<Kernel>, query: EventsQuery) {
+    // Subscribe before replaying so nothing falls in between
+    let mut live = kernel.audit_trail.subscribe();
+    let mut last_id = 0;
+    if let Some(after) = query.after {
+        for record in kernel.audit_trail.recent(after) {
+            last_id = record.id;
+            if query.wants(&record) && send(&mut socket, &record).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    loop {
+        let record = tokio::select! {
+            received = live.recv() => match received {
+                Ok(record) => record,
+                Err(RecvError::Lagged(missed)) => {
+                    warn!("📡 Event subscriber lagged, {} records dropped", missed);
+                    let notice = serde_json::json!({ "event": "lagged", "missed": missed });
+                    if send(&mut socket, &notice).await.is_err() {
+                        return;
+                    }
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            },
+            incoming = socket.recv() => match incoming {
+                // Client messages are ignored; close or error ends the stream
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => continue,
+            },
+            _ = kernel.shutdown.cancelled() => break,
+        };
+        if record.id <= last_id || !query.wants(&record) {
+            continue;
+        }
+        if send(&mut socket, &record).await.is_err() {
+            return;
+        }
+    }
+    let _ = socket.send(Message::Close(None)).await;
+}
+
+async fn send<T: serde::Serialize>(socket: &mut WebSocket, value: &T) -> Result<(), axum::Error> {
+    let json = serde_json::to_string(value).expect("event serializes");
+    socket.send(Message::Text(json.into())).await
+}
+
+/// Body of the operator override endpoints
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OperatorRequest {
+    pub operator: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// `/disarm` only: hold length (milliseconds); absent = until re-armed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub for_ms: Option<u64>,
+}
+
+/// Arming state after an override
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArmState {
+    pub armed: bool,
+    /// End of an operator hold (Unix ms; `u64::MAX` = until re-armed)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disarmed_until_ms: Option<u64>,
+    /// PID signaled by `/emergency-kill`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub killed_pid: Option<u32>,
+    /// Container killed by `/emergency-kill`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub killed_container: Option<String>,
+    /// VM powered off by `/emergency-kill`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub killed_vm: Option<String>,
+}
+
+type Rejection = (StatusCode, String);
+
+impl OperatorRequest {
+    /// Write `event` to the audit trail
+    fn audit(&self, kernel: &Kernel, event: OperatorEvent) -> Result<(), Rejection> {
+        kernel
+            .audit_trail
+            .record_operator(&event)
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+    }
+
+    /// Audit event for `action`; rejects anonymous requests
+    fn event(&self, action: &str) -> Result<OperatorEvent, Rejection> {
+        if self.operator.trim().is_empty() {
+            return Err((StatusCode::BAD_REQUEST, "operator is required".to_string()));
+        }
+        Ok(OperatorEvent::new(
+            action,
+            &self.operator,
+            self.reason.as_deref(),
+        ))
+    }
+}
+
+fn arm_state(kernel: &Kernel) -> ArmState {
+    ArmState {
+        armed: kernel.health.armed(),
+        disarmed_until_ms: kernel.health.held_until(),
+        killed_pid: None,
+        killed_container: None,
+        killed_vm: None,
+    }
+}
+
+async fn disarm(
+    State(kernel): State<Arc<Kernel>>,
+    Json(request): Json<OperatorRequest>,
+) -> Result<Json<ArmState>, Rejection> {
+    let mut event = request.event("disarm")?;
+    let until = match request.for_ms {
+        Some(ms) => {
+            let until = event.timestamp_ms.saturating_add(ms);
+            event.until_ms = Some(until);
+            until
+        }
+        None => u64::MAX,
+    };
+    request.audit(&kernel, event)?;
+    kernel.health.disarm(until);
+    match request.for_ms {
+        Some(ms) => warn!(
+            "🔒 Kill actions disarmed by {} for {}s",
+            request.operator,
+            ms / 1000
+        ),
+        None => warn!(
+            "🔒 Kill actions disarmed by {} until re-armed",
+            request.operator
+        ),
+    }
+    Ok(Json(arm_state(&kernel)))
+}
+
+async fn arm(
+    State(kernel): State<Arc<Kernel>>,
+    Json(request): Json<OperatorRequest>,
+) -> Result<Json<ArmState>, Rejection> {
+    request.audit(&kernel, request.event("arm")?)?;
+    kernel.health.arm();
+    warn!("🔫 Kill actions armed by {}", request.operator);
+    Ok(Json(arm_state(&kernel)))
+}
+
+async fn emergency_kill(
+    State(kernel): State<Arc<Kernel>>,
+    Json(request): Json<OperatorRequest>,
+) -> Result<Json<ArmState>, Rejection> {
+    let mut event = request.event("emergency_kill")?;
+    let pid = kernel.config.target_pid;
+    let container = kernel.config.target_container.clone();
+    let vm = kernel.config.target_vm.clone();
+    if pid.is_none() && container.is_none() && vm.is_none() {
+        return Err((
+            StatusCode::CONFLICT,
+            "no target (start the kernel with --target-pid, --watch-container or --target-vm)"
+                .to_string(),
+        ));
+    }
+    event.target_pid = pid;
+    event.target_container = container.clone();
+    event.target_vm = vm.as_ref().map(ToString::to_string);
+    request.audit(&kernel, event)?;
+    error!(
+        "🚨 EMERGENCY KILL by {}: {}",
+        request.operator,
+        request.reason.as_deref().unwrap_or("no reason given")
+    );
+    if let Some(pid) = pid {
+        crate::kill_process(pid);
+    }
+    if let Some(ref container) = container {
+        crate::kill_container(container);
+    }
+    if let Some(ref vm) = vm {
+        crate::kill_vm(vm);
+    }
+    Ok(Json(ArmState {
+        killed_pid: pid,
+        killed_container: container,
+        killed_vm: vm.as_ref().map(ToString::to_string),
+        ..arm_state(&kernel)
+    }))
+}
+
+/// Body of `POST /decisions/{id}/feedback`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FeedbackRequest {
+    pub operator: String,
+    pub label: Label,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+async fn feedback(
+    State(kernel): State<Arc<Kernel>>,
+    Path(id): Path<u64>,
+    Json(request): Json<FeedbackRequest>,
+) -> Result<Json<CorrectionEvent>, Rejection> {
+    if request.operator.trim().is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "operator is required".to_string()));
+    }
+    if id == 0 || id > kernel.audit_trail.last_id() {
+        return Err((StatusCode::NOT_FOUND, format!("no decision {}", id)));
+    }
+    let mut event = CorrectionEvent::new(
+        id,
+        request.label,
+        &request.operator,
+        request.reason.as_deref(),
+    );
+    // Older decisions are linked by ID alone
+    if let Some(record) = kernel.audit_trail.get(id) {
+        if record.action == event.action {
+            return Err((
+                StatusCode::CONFLICT,
+                format!("decision {} was {} already", id, record.action),
+            ));
+        }
+        event.original = Some(record.action);
+        event.input_hash = Some(record.input_hash);
+    }
+    kernel
+        .audit_trail
+        .record_correction(&event)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    info!(
+        "✍️ Decision {} marked {} by {}",
+        id,
+        request.label.as_str(),
+        request.operator
+    );
+    Ok(Json(event))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_feedback() {
+        let dir = tempfile::tempdir().unwrap();
+        let token = dir.path().join("admin.token");
+        std::fs::write(&token, "s3cret\n").unwrap();
+        let flags = ["--admin-token-file", token.to_str().unwrap()];
+        let kernel = crate::tests::kernel(&flags, Default::default(), dir.path());
+        // Decision 1: a filtered SUSTAIN
+        crate::tests::connect(&kernel, "ls\n", true).await;
+        let input_hash = kernel.audit_trail.get(1).unwrap().input_hash;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router(kernel)).await });
+        let client = reqwest::Client::new();
+        let mark = |id: u64, operator: &str, label: &str| {
+            let body = serde_json::json!({"operator": operator, "label": label, "reason": "drill"});
+            let request = client
+                .post(format!("{}/decisions/{}/feedback", base, id))
+                .bearer_auth("s3cret")
+                .json(&body);
+            async move { request.send().await.unwrap() }
+        };
+
+        let status = |id, operator, label| async move { mark(id, operator, label).await.status() };
+        assert_eq!(
+            status(1, " ", "false-negative").await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(0, "ana", "false-negative").await,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            status(2, "ana", "false-negative").await,
+            StatusCode::NOT_FOUND
+        );
+        // A false positive should have been a SUSTAIN, which decision 1 was
+        assert_eq!(
+            status(1, "ana", "false-positive").await,
+            StatusCode::CONFLICT
+        );
+
+        let response = mark(1, "ana", "false-negative").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let event: CorrectionEvent = response.json().await.unwrap();
+        assert_eq!((event.decision_id, event.action.as_str()), (1, "KILL"));
+        // Appended to the trail, linked to the decision
+        let trail = std::fs::read_to_string(dir.path().join("audit.jsonl")).unwrap();
+        let corrections: Vec<CorrectionEvent> = trail
+            .lines()
+            .filter(|line| line.contains(r#""event":"correction""#))
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(corrections.len(), 1);
+        let correction = &corrections[0];
+        assert_eq!(correction.decision_id, 1);
+        assert_eq!(correction.operator, "ana");
+        assert_eq!(correction.original.as_deref(), Some("SUSTAIN"));
+        assert_eq!(correction.input_hash.as_deref(), Some(input_hash.as_str()));
+    }
+
+    #[tokio::test]
+    async fn test_overrides_need_token() {
+        let dir = tempfile::tempdir().unwrap();
+        let token = dir.path().join("admin.token");
+        std::fs::write(&token, "s3cret\n").unwrap();
+        let flags = ["--admin-token-file", token.to_str().unwrap()];
+        let kernel = crate::tests::kernel(&flags, Default::default(), dir.path());
+        let open_dir = tempfile::tempdir().unwrap();
+        let open = crate::tests::kernel(&[], Default::default(), open_dir.path());
+        let serve = |kernel| async move {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let base = format!("http://{}", listener.local_addr().unwrap());
+            tokio::spawn(async move { axum::serve(listener, router(kernel)).await });
+            base
+        };
+        let (base, open_base) = (serve(Arc::clone(&kernel)).await, serve(open).await);
+        let client = reqwest::Client::new();
+        let body = serde_json::json!({"operator": "ana", "reason": "drill"});
+        let post = |url: String, token: Option<&str>| {
+            let request = client.post(url).json(&body);
+            let request = match token {
+                Some(token) => request.bearer_auth(token),
+                None => request,
+            };
+            async move { request.send().await.unwrap().status() }
+        };
+
+        for path in ["disarm", "arm", "emergency-kill", "decisions/1/feedback"] {
+            let url = format!("{}/{}", base, path);
+            assert_eq!(post(url.clone(), None).await, StatusCode::UNAUTHORIZED);
+            assert_eq!(post(url, Some("wrong")).await, StatusCode::UNAUTHORIZED);
+            // No token configured: refused outright
+            let url = format!("{}/{}", open_base, path);
+            assert_eq!(post(url, Some("s3cret")).await, StatusCode::FORBIDDEN);
+        }
+        assert!(kernel.health.held_until().is_none());
+
+        let url = format!("{}/disarm", base);
+        assert_eq!(post(url, Some("s3cret")).await, StatusCode::OK);
+        assert!(kernel.health.held_until().is_some());
+        // Read-only endpoints stay open
+        let stats = client.get(format!("{}/stats", base)).send().await.unwrap();
+        assert_eq!(stats.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_events_action_filter() {
+        let record: DecisionRecord = serde_json::from_value(serde_json::json!({
+            "id": 1, "timestamp_ms": 0, "input_log": "x", "input_hash": "",
+            "action": "KILL", "confidence": 100, "filtered": true, "latency_ms": 0,
+            "model_fingerprint": "", "prompt_hash": "", "raw_response": null,
+        }))
+        .unwrap();
+        let query = |actions: Option<&str>| EventsQuery {
+            actions: actions.map(str::to_string),
+            after: None,
+        };
+
+        assert!(query(None).wants(&record));
+        assert!(query(Some("kill, fail")).wants(&record));
+        assert!(!query(Some("SUSTAIN")).wants(&record));
+    }
+}
@@ -0,0 +1,290 @@
+//! Agent Registry - Live Per-Connection Status
+//!
+//! One connection is one agent. The registry tracks each connected agent's
+//! line count, escalation verdicts and last activity for the admin API
+//! (`GET /agents`) and `tripwired top`.
+//!
+//! Each connection carries three correlation IDs, stamped on its decision
+//! records, session summaries, denials, notifications and per-agent
+//! metrics:
+//!
+//! - `connection_id` - the registry ID (1-based, in connect order)
+//! - `session_id` - 16 hex chars generated at connect time
+//! - `agent_id` - the peer (address, or the transport and its source:
+//!   `container:api`, `otlp:checkout`, ...)
+//!
+//! An agent names itself with a `HELLO` first line (not judged; acked as
+//! `HELLO` with `--ack`), e.g. to keep one `session_id` across reconnects:
+//!
+//! ```text
+//! HELLO agent=trader-7 session=run-2026-10-15
+//! ```
+//!
+//! With `--agent-token-file`, a TCP agent must open with a `HELLO` carrying
+//! the token (`HELLO agent=trader-7 token=<token>`); any other first line
+//! closes the connection.
+
+use crate::audit::sha256_hex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Marks a connection's first line as its introduction
+pub const HELLO: &str = "HELLO ";
+
+/// Longest `agent` / `session` a client may supply
+const MAX_ID_LEN: usize = 128;
+
+/// Status of one connected agent
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AgentStatus {
+    /// Connection number (1-based, in connect order)
+    pub id: u64,
+    /// Peer address, or the transport name for local sockets
+    pub peer: String,
+    /// Agent the connection speaks for (`HELLO agent=`, default the peer)
+    #[serde(default)]
+    pub agent_id: String,
+    /// `HELLO session=`, or generated at connect time
+    #[serde(default)]
+    pub session_id: String,
+    pub connected_ms: u64,
+    pub last_seen_ms: u64,
+    pub lines: u64,
+    /// Lines skipped by flow control
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub dropped: u64,
+    /// Lines escalated to a rule or the LLM
+    pub escalations: u64,
+    pub kills: u64,
+    /// Most recent escalation verdict
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_action: Option<String>,
+    /// Judged in degraded mode after a pipeline error on this connection
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub degraded: bool,
+}
+
+/// Correlation IDs of one connection, as stamped on its records
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AgentIds {
+    pub agent_id: String,
+    pub session_id: String,
+    pub connection_id: u64,
+}
+
+impl AgentStatus {
+    pub fn ids(&self) -> AgentIds {
+        AgentIds {
+            agent_id: self.agent_id.clone(),
+            session_id: self.session_id.clone(),
+            connection_id: self.id,
+        }
+    }
+}
+
+/// IDs a client supplies with `HELLO`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Hello {
+    pub agent: Option<String>,
+    pub session: Option<String>,
+    /// `--agent-token-file` token, checked before anything else is read
+    pub token: Option<String>,
+}
+
+/// The IDs (and token) of a `HELLO agent=... session=...` line; `None` if `line` is
+/// not one (unknown keys, empty or overlong values)
+pub fn hello(line: &str) -> Option<Hello> {
+    let mut hello = Hello::default();
+    for pair in line.strip_prefix(HELLO)?.split_whitespace() {
+        let (key, value) = pair.split_once('=')?;
+        if value.is_empty() || value.len() > MAX_ID_LEN {
+            return None;
+        }
+        match key {
+            "agent" => hello.agent = Some(value.to_string()),
+            "session" => hello.session = Some(value.to_string()),
+            "token" => hello.token = Some(value.to_string()),
+            _ => return None,
+        }
+    }
+    (hello != Hello::default()).then_some(hello)
+}
+
+/// Fresh `session_id`: unique per process, connection and instant
+fn session_id(id: u64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    sha256_hex(&format!("{}|{}|{}", std::process::id(), id, now))[..16].to_string()
+}
+
+/// Connected agents by ID
+#[derive(Default)]
+pub struct Registry {
+    next_id: AtomicU64,
+    agents: Mutex<BTreeMap<u64, AgentStatus>>,
+}
+
+impl Registry {
+    /// Register a new connection; returns its ID
+    pub fn connect(&self, peer: &str) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let now = now_ms();
+        self.agents.lock().unwrap().insert(
+            id,
+            AgentStatus {
+                id,
+                peer: peer.to_string(),
+                agent_id: peer.to_string(),
+                session_id: session_id(id),
+                connected_ms: now,
+                last_seen_ms: now,
+                ..Default::default()
+            },
+        );
+        id
+    }
+
+    /// Apply a connection's `HELLO`; returns its IDs
+    pub fn identify(&self, id: u64, hello: Hello) -> Option<AgentIds> {
+        let mut agents = self.agents.lock().unwrap();
+        let agent = agents.get_mut(&id)?;
+        if let Some(name) = hello.agent {
+            agent.agent_id = name;
+        }
+        if let Some(session) = hello.session {
+            agent.session_id = session;
+        }
+        Some(agent.ids())
+    }
+
+    pub fn disconnect(&self, id: u64) {
+        self.agents.lock().unwrap().remove(&id);
+    }
+
+    /// Count a received line
+    pub fn line(&self, id: u64) {
+        if let Some(agent) = self.agents.lock().unwrap().get_mut(&id) {
+            agent.lines += 1;
+            agent.last_seen_ms = now_ms();
+        }
+    }
+
+    /// Count a line skipped by flow control
+    pub fn dropped(&self, id: u64) {
+        if let Some(agent) = self.agents.lock().unwrap().get_mut(&id) {
+            agent.dropped += 1;
+        }
+    }
+
+    /// Count an escalation verdict
+    pub fn decision(&self, id: u64, action: &str) {
+        if let Some(agent) = self.agents.lock().unwrap().get_mut(&id) {
+            agent.escalations += 1;
+            if action == "KILL" {
+                agent.kills += 1;
+            }
+            agent.last_action = Some(action.to_string());
+        }
+    }
+
+    /// Mark an agent as judged in degraded mode
+    pub fn degrade(&self, id: u64) {
+        if let Some(agent) = self.agents.lock().unwrap().get_mut(&id) {
+            agent.degraded = true;
+        }
+    }
+
+    /// One connected agent
+    pub fn get(&self, id: u64) -> Option<AgentStatus> {
+        self.agents.lock().unwrap().get(&id).cloned()
+    }
+
+    /// Connected agents, oldest connection first
+    pub fn snapshot(&self) -> Vec<AgentStatus> {
+        self.agents.lock().unwrap().values().cloned().collect()
+    }
+}
+
+fn is_zero(n: &u64) -> bool {
+    *n == 0
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry() {
+        let registry = Registry::default();
+        let a = registry.connect("127.0.0.1:5000");
+        let b = registry.connect("unix");
+        registry.line(a);
+        registry.line(a);
+        registry.decision(a, "KILL");
+        registry.decision(b, "SUSTAIN");
+
+        let agents = registry.snapshot();
+        assert_eq!(agents.len(), 2);
+        assert_eq!((agents[0].lines, agents[0].kills), (2, 1));
+        assert_eq!(agents[1].last_action.as_deref(), Some("SUSTAIN"));
+        registry.degrade(b);
+        assert!(registry.get(b).unwrap().degraded);
+
+        registry.disconnect(a);
+        assert_eq!(registry.snapshot()[0].id, b);
+    }
+
+    #[test]
+    fn test_ids() {
+        let registry = Registry::default();
+        let a = registry.connect("unix");
+        let b = registry.connect("unix");
+        let (a_ids, b_ids) = (
+            registry.get(a).unwrap().ids(),
+            registry.get(b).unwrap().ids(),
+        );
+        assert_eq!((a_ids.agent_id.as_str(), a_ids.connection_id), ("unix", a));
+        assert_eq!(a_ids.session_id.len(), 16);
+        assert_ne!(a_ids.session_id, b_ids.session_id);
+
+        let ids = registry
+            .identify(b, hello("HELLO agent=trader-7").unwrap())
+            .unwrap();
+        assert_eq!(ids.agent_id, "trader-7");
+        assert_eq!(ids.session_id, b_ids.session_id);
+        let ids = registry
+            .identify(b, hello("HELLO  session=run-1 agent=trader-8 ").unwrap())
+            .unwrap();
+        assert_eq!(
+            (ids.agent_id.as_str(), ids.session_id.as_str()),
+            ("trader-8", "run-1")
+        );
+
+        let with_token = hello("HELLO agent=trader-9 token=s3cret").unwrap();
+        assert_eq!(with_token.token.as_deref(), Some("s3cret"));
+        let ids = registry.identify(b, with_token).unwrap();
+        assert_eq!(
+            (ids.agent_id.as_str(), ids.session_id.as_str()),
+            ("trader-9", "run-1")
+        );
+        assert!(hello("HELLO token=s3cret").is_some());
+
+        assert_eq!(hello("HELLO"), None);
+        assert_eq!(hello("HELLO world"), None);
+        assert_eq!(hello("HELLO agent="), None);
+        assert_eq!(hello("HELLO agent=x user=root"), None);
+        assert_eq!(hello(&format!("HELLO agent={}", "x".repeat(129))), None);
+    }
+}
@@ -3,38 +3,196 @@
 //! Every decision is logged with full context for compliance and forensics.
 //! Append-only, tamper-evident structure.
 
+use crate::agents::{AgentIds, AgentStatus};
+use crate::backup::{Backup, BackupMonitor, BackupStatus, Mirrored};
+use crate::checkpoint::{leaf_hash, merkle_root, to_hex};
+use crate::clock::{Sequencer, SystemClock};
+use crate::encrypt::Encryptor;
+use crate::error::ErrorCounts;
+use crate::llm::Sampling;
+use crate::normalize::{Normalizer, TemplateReport, Templates};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::fs::{File, OpenOptions};
-use std::io::{BufWriter, Write};
-use std::path::PathBuf;
-use std::sync::Mutex;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{broadcast, mpsc};
+
+/// Records kept in memory for the admin API decision feed
+const RECENT_RECORDS: usize = 256;
+
+/// Interval between summaries of a connection that stays open
+pub const SESSION_SUMMARY_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Records buffered per event-stream subscriber before it starts lagging
+const EVENT_BUFFER: usize = 1024;
+
+/// How far each write is pushed towards the disk (`--audit-durability`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Durability {
+    /// Flushed to the OS per record: survives a kernel crash, not
+    /// necessarily a host crash
+    #[default]
+    Buffered,
+    /// Every line on disk before the write returns (a write-through handle
+    /// on Windows, `sync_data` elsewhere)
+    Record,
+    /// `sync_data` after each KILL record, so a host crash right after a
+    /// kill cannot lose why it happened (and everything before it)
+    Kill,
+}
 
 /// A single decision record in the audit trail
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DecisionRecord {
     /// Unique decision ID (monotonic)
     pub id: u64,
     /// Unix timestamp (milliseconds)
     pub timestamp_ms: u64,
+    /// Monotonic milliseconds since the trail was opened (see `clock`):
+    /// grows with `id` whatever the wall clock does
+    #[serde(default)]
+    pub monotonic_ms: u64,
+    /// `timestamp_ms` is behind an earlier record's by this much (the wall
+    /// clock was stepped back)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clock_behind_ms: Option<u64>,
     /// Input log that triggered analysis
     pub input_log: String,
-    /// SHA-256 hash of input log
+    /// SHA-256 hash of the input as received (before redaction)
     pub input_hash: String,
+    /// SHA-256 of the normalized `input_log` (see `normalize`): equal for
+    /// lines differing only in timestamps, numbers, hex ids and UUIDs
+    #[serde(default)]
+    pub template_hash: String,
+    /// `input_log` had PII redacted (it no longer hashes to `input_hash`)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub redacted: bool,
     /// Decision action (KILL or SUSTAIN)
     pub action: String,
-    /// Confidence percentage
+    /// Confidence percentage (model-reported for LLM decisions, or
+    /// calibrated from logprobs)
     pub confidence: u32,
+    /// What the model wrote when `confidence` is calibrated from logprobs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model_confidence: Option<u32>,
     /// Was this pre-filtered (no LLM call)?
     pub filtered: bool,
     /// Latency in milliseconds
     pub latency_ms: u64,
-    /// Model fingerprint (name + config hash)
+    /// Fingerprint (name + config hash) of the model that answered
     pub model_fingerprint: String,
     /// Prompt version hash
     pub prompt_hash: String,
     /// Raw LLM response (for replay verification)
     pub raw_response: Option<String>,
+    /// SHA-256 of the conversation sent to the model (template filled with
+    /// the line and context, few-shot turns included)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rendered_prompt_hash: Option<String>,
+    /// That conversation as JSON messages, with `--audit-prompts`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rendered_prompt: Option<String>,
+    /// Category of the LLM failure a degraded-mode decision was made for
+    /// (`llm.timeout`, `llm.deadline`, ...)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Name of the filter rule that decided the line was suspicious
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+    /// Model's one-line rationale
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// SHA-256 of the agent history sent with the prompt (`{context}`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_hash: Option<String>,
+    /// Lines judged in the same LLM request, when batched
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub batch_size: Option<usize>,
+    /// Why a KILL did not signal the target (kill cooldown / hourly cap)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suppressed: Option<String>,
+    /// Policy rule that changed the verdict (`on-uncertain`: `--on-uncertain`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<String>,
+    /// Original verdict when `policy` changed the action
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verdict: Option<String>,
+    /// Schedule profile active when the decision was made
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    /// HA instance that made the decision
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    /// Agent, session and connection the line came from (see `agents`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connection_id: Option<u64>,
+    /// Retrospective verdict for a line first judged while the LLM was
+    /// down (never executed)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub backfilled: bool,
+    /// ID of the degraded-mode decision this one backfills
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backfill_of: Option<u64>,
+    /// Line accepted but not judged before a restart, replayed from the
+    /// persisted queue
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub recovered: bool,
+    /// `input_log`, `reason`, `raw_response` and `rendered_prompt` are
+    /// age-encrypted (see `encrypt`)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub encrypted: bool,
+    /// Follow-up justification of a KILL (see `explain`); set on the
+    /// notification only, the trail keeps it as an `explanation` event
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub explanation: Option<String>,
+}
+
+/// Fields supplied by the caller for one decision record
+#[derive(Debug, Default)]
+pub struct RecordInput<'a> {
+    pub input_log: &'a str,
+    /// Unredacted input: hashed into `input_hash`, never written
+    pub raw_input: Option<&'a str>,
+    pub reason: Option<&'a str>,
+    /// Context sent with the prompt: hashed into `context_hash`
+    pub context: Option<&'a str>,
+    /// Model that answered, if not the trail's primary (`name@hash`)
+    pub model_fingerprint: Option<&'a str>,
+    pub action: &'a str,
+    pub confidence: u32,
+    /// Model-reported confidence, when `confidence` is calibrated
+    pub model_confidence: Option<u32>,
+    pub filtered: bool,
+    pub latency_ms: u64,
+    pub raw_response: Option<String>,
+    /// Conversation sent to the model: hashed into `rendered_prompt_hash`,
+    /// kept as `rendered_prompt` with `--audit-prompts`
+    pub rendered_prompt: Option<&'a str>,
+    /// LLM failure category (degraded mode)
+    pub error: Option<&'a str>,
+    pub rule: Option<&'a str>,
+    pub batch_size: Option<usize>,
+    pub suppressed: Option<&'a str>,
+    pub policy: Option<&'a str>,
+    pub verdict: Option<&'a str>,
+    pub profile: Option<&'a str>,
+    /// Precomputed `input_hash`, when the raw input is gone (backfill)
+    pub input_hash: Option<&'a str>,
+    /// Degraded-mode decision this record backfills
+    pub backfill_of: Option<u64>,
+    /// Replayed from the persisted queue after a restart
+    pub recovered: bool,
+    /// Agent connection the decision belongs to (stamped on the record,
+    /// counted in its session summary)
+    pub agent: Option<&'a AgentIds>,
 }
 
 /// Model configuration fingerprint
@@ -43,21 +201,29 @@ pub struct ModelFingerprint {
     pub model_name: String,
     pub llm_url: String,
     pub max_tokens: u32,
-    pub temperature: f32,
+    /// Generation parameters (temperature, top_p, seed, stop, timeout)
+    #[serde(flatten)]
+    pub sampling: Sampling,
     /// SHA-256 of serialized config
     pub config_hash: String,
 }
 
 impl ModelFingerprint {
-    pub fn new(model_name: &str, llm_url: &str, max_tokens: u32, temperature: f32) -> Self {
-        let config_str = format!("{}|{}|{}|{}", model_name, llm_url, max_tokens, temperature);
+    pub fn new(model_name: &str, llm_url: &str, max_tokens: u32, sampling: &Sampling) -> Self {
+        let config_str = format!(
+            "{}|{}|{}|{}",
+            model_name,
+            llm_url,
+            max_tokens,
+            serde_json::to_string(sampling).expect("sampling serializes")
+        );
         let config_hash = sha256_hex(&config_str);
 
         Self {
             model_name: model_name.to_string(),
             llm_url: llm_url.to_string(),
             max_tokens,
-            temperature,
+            sampling: sampling.clone(),
             config_hash,
         }
     }
@@ -67,45 +233,939 @@ impl ModelFingerprint {
     }
 }
 
+/// Shutdown footer - final record written on graceful exit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShutdownFooter {
+    /// Always "shutdown"
+    pub event: String,
+    /// Unix timestamp (milliseconds)
+    pub timestamp_ms: u64,
+    /// What triggered the shutdown (SIGTERM, SIGINT, ctrl-c, ...)
+    pub reason: String,
+    /// ID of the last decision record written
+    pub last_id: u64,
+    /// Kernel uptime (milliseconds)
+    pub uptime_ms: u64,
+    /// Did all in-flight analyses finish before the drain timeout?
+    pub drained: bool,
+    /// Final kernel statistics
+    pub stats: serde_json::Value,
+    /// "hmac-sha256:<hex>" with --audit-key-file, otherwise "sha256:<hex>"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+impl ShutdownFooter {
+    /// Check `signature` against the rest of the footer (`None`: signed
+    /// with a key that was not given)
+    pub fn verify(&self, key: Option<&[u8]>) -> Option<bool> {
+        let unsigned = ShutdownFooter {
+            signature: None,
+            ..self.clone()
+        };
+        verify(&unsigned, self.signature.as_deref(), key)
+    }
+}
+
+/// Tombstone of an audit segment removed by the retention policy (see
+/// `retention`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PruneEvent {
+    /// Always "prune"
+    pub event: String,
+    /// Unix timestamp (milliseconds)
+    pub timestamp_ms: u64,
+    /// File name of the segment
+    pub segment: String,
+    /// Why it was removed (`retention 90d`)
+    pub reason: String,
+    /// `deleted`, or `archived to <path>`
+    pub action: String,
+    /// Decision records it held, and their ID / time range
+    pub records: u64,
+    pub first_id: Option<u64>,
+    pub last_id: Option<u64>,
+    pub from_ms: Option<u64>,
+    pub to_ms: Option<u64>,
+    /// SHA-256 of the whole file
+    pub sha256: String,
+    /// RFC 6962 tree hash over its decision records (see `checkpoint`)
+    pub root: String,
+    /// Root of the last checkpoint it held
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checkpoint: Option<String>,
+    /// As for the shutdown footer
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+impl PruneEvent {
+    /// Check `signature` (`None`: signed with a key that was not given)
+    pub fn verify(&self, key: Option<&[u8]>) -> Option<bool> {
+        let unsigned = PruneEvent {
+            signature: None,
+            ..self.clone()
+        };
+        verify(&unsigned, self.signature.as_deref(), key)
+    }
+}
+
+/// Last line of a segment closed by rotation
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RotateEvent {
+    /// Always "rotate"
+    pub event: String,
+    /// Unix timestamp (milliseconds)
+    pub timestamp_ms: u64,
+    /// ID of the last decision record written
+    pub last_id: u64,
+    /// File name the segment was renamed to
+    pub segment: String,
+}
+
+/// Recovery event - written when the previous session did not end cleanly
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RecoveryEvent {
+    /// Always "recovery"
+    pub event: String,
+    /// Unix timestamp (milliseconds)
+    pub timestamp_ms: u64,
+    /// Highest decision ID found in the existing file
+    pub previous_last_id: u64,
+    /// First ID this session will assign
+    pub resume_id: u64,
+    /// Did the previous session write a shutdown footer?
+    pub clean_shutdown: bool,
+    /// Size of the torn (unterminated) final line, if any
+    pub torn_line_bytes: Option<u64>,
+    /// ID claimed by the torn line - its record is lost
+    pub lost_id: Option<u64>,
+    /// Complete lines that failed to parse
+    pub corrupt_lines: u64,
+}
+
+/// Operator override - arm, disarm or emergency kill via the admin API
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OperatorEvent {
+    /// Always "operator"
+    pub event: String,
+    /// Unix timestamp (milliseconds)
+    pub timestamp_ms: u64,
+    /// "arm", "disarm" or "emergency_kill"
+    pub action: String,
+    /// Who asked, as given by the caller
+    pub operator: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// End of a timed disarm (Unix ms); absent = until re-armed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until_ms: Option<u64>,
+    /// PID signaled by an emergency kill
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_pid: Option<u32>,
+    /// Container killed by an emergency kill
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_container: Option<String>,
+    /// VM powered off by an emergency kill
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_vm: Option<String>,
+}
+
+/// Follow-up justification of a KILL decision (see `explain`)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExplanationEvent {
+    /// Always "explanation"
+    pub event: String,
+    /// Unix timestamp (milliseconds)
+    pub timestamp_ms: u64,
+    /// The KILL decision explained
+    pub decision_id: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub explanation: Option<String>,
+    /// Why there is no explanation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Fingerprint of the model that answered
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    pub latency_ms: u64,
+    /// `explanation` is age-encrypted (see `encrypt`)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub encrypted: bool,
+}
+
+impl ExplanationEvent {
+    pub fn new(decision_id: u64, latency_ms: u64) -> Self {
+        Self {
+            event: "explanation".to_string(),
+            timestamp_ms: now_ms(),
+            decision_id,
+            explanation: None,
+            error: None,
+            model: None,
+            latency_ms,
+            encrypted: false,
+        }
+    }
+}
+
+/// The countersignature exchange for a KILL (see `authorize`)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuthorizationEvent {
+    /// Always "authorization"
+    pub event: String,
+    /// Unix timestamp (milliseconds)
+    pub timestamp_ms: u64,
+    /// The KILL decision held for approval
+    pub decision_id: u64,
+    /// Authorizer endpoint
+    pub url: String,
+    /// Nonce the approval token is bound to
+    pub challenge: String,
+    /// Request body as sent
+    pub request: String,
+    /// Response body as received
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<String>,
+    pub approved: bool,
+    /// Who countersigned, as the authorizer reports it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approver: Option<String>,
+    /// Why the KILL was not approved
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Executed: KILL if approved, otherwise PAUSE
+    pub action: String,
+    pub latency_ms: u64,
+}
+
+impl AuthorizationEvent {
+    pub fn new(decision_id: u64, url: &str, challenge: &str) -> Self {
+        Self {
+            event: "authorization".to_string(),
+            timestamp_ms: now_ms(),
+            decision_id,
+            url: url.to_string(),
+            challenge: challenge.to_string(),
+            request: String::new(),
+            response: None,
+            approved: false,
+            approver: None,
+            error: None,
+            action: "PAUSE".to_string(),
+            latency_ms: 0,
+        }
+    }
+}
+
+/// A KILL target's state saved before the kill (see `snapshot`)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SnapshotEvent {
+    /// Always "snapshot"
+    pub event: String,
+    /// Unix timestamp (milliseconds)
+    pub timestamp_ms: u64,
+    /// The KILL decision
+    pub decision_id: u64,
+    /// `pid <n>`, `container <name>` or `vm <target>`
+    pub target: String,
+    /// Where the snapshot is
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    /// Why there is none (the target was killed regardless)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub latency_ms: u64,
+}
+
+impl SnapshotEvent {
+    pub fn new(
+        decision_id: u64,
+        target: &str,
+        result: Result<String, String>,
+        latency_ms: u64,
+    ) -> Self {
+        let (location, error) = match result {
+            Ok(location) => (Some(location), None),
+            Err(e) => (None, Some(e)),
+        };
+        Self {
+            event: "snapshot".to_string(),
+            timestamp_ms: now_ms(),
+            decision_id,
+            target: target.to_string(),
+            location,
+            error,
+            latency_ms,
+        }
+    }
+}
+
+/// What a human says a decision got wrong
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Label {
+    /// Acted on a harmless line: should have been SUSTAIN
+    FalsePositive,
+    /// Let a harmful line through: should have been KILL
+    FalseNegative,
+}
+
+impl Label {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::FalsePositive => "false-positive",
+            Self::FalseNegative => "false-negative",
+        }
+    }
+
+    /// The verdict the decision should have had
+    pub fn action(self) -> &'static str {
+        match self {
+            Self::FalsePositive => "SUSTAIN",
+            Self::FalseNegative => "KILL",
+        }
+    }
+}
+
+/// Human feedback on a decision (`POST /decisions/{id}/feedback`), linked
+/// by `decision_id`; read by `tripwired dataset` as a label override
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CorrectionEvent {
+    /// Always "correction"
+    pub event: String,
+    /// Unix timestamp (milliseconds)
+    pub timestamp_ms: u64,
+    /// The decision corrected
+    pub decision_id: u64,
+    pub label: Label,
+    /// The verdict it should have had (KILL or SUSTAIN)
+    pub action: String,
+    /// Who said so, as given by the caller
+    pub operator: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// The decision's action and `input_hash`, if it is still among the
+    /// recent ones
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_hash: Option<String>,
+}
+
+impl CorrectionEvent {
+    pub fn new(decision_id: u64, label: Label, operator: &str, reason: Option<&str>) -> Self {
+        Self {
+            event: "correction".to_string(),
+            timestamp_ms: now_ms(),
+            decision_id,
+            label,
+            action: label.action().to_string(),
+            operator: operator.to_string(),
+            reason: reason.map(str::to_string),
+            original: None,
+            input_hash: None,
+        }
+    }
+}
+
+/// Access to a decoy (see `honeypot`), ahead of its fast-path KILL
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HoneypotEvent {
+    /// Always "honeypot"
+    pub event: String,
+    /// Unix timestamp (milliseconds)
+    pub timestamp_ms: u64,
+    /// Decoy file or registry key
+    pub decoy: String,
+    /// `opened`, `modified`, `deleted`, ...
+    pub access: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_pid: Option<u32>,
+}
+
+impl HoneypotEvent {
+    pub fn new(decoy: &str, access: &str, target_pid: Option<u32>) -> Self {
+        Self {
+            event: "honeypot".to_string(),
+            timestamp_ms: now_ms(),
+            decoy: decoy.to_string(),
+            access: access.to_string(),
+            target_pid,
+        }
+    }
+}
+
+/// Verdict of the shadow analyzer on an escalated line (see `shadow`)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ShadowEvent {
+    /// Always "shadow"
+    pub event: String,
+    /// Unix timestamp (milliseconds)
+    pub timestamp_ms: u64,
+    /// The live decision on the same line
+    pub decision_id: u64,
+    /// Fingerprint of the shadow model
+    pub model: String,
+    /// First 8 hex chars of the shadow prompt's SHA-256
+    pub prompt_hash: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Why there is no shadow verdict
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub latency_ms: u64,
+    /// Live LLM verdict before the decision policy (absent in degraded mode)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub live_action: Option<String>,
+    /// Shadow and live verdicts match (absent if either is missing)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agree: Option<bool>,
+    /// `reason` is age-encrypted (see `encrypt`)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub encrypted: bool,
+}
+
+impl ShadowEvent {
+    pub fn new(model: &str, prompt_hash: &str) -> Self {
+        Self {
+            event: "shadow".to_string(),
+            timestamp_ms: now_ms(),
+            decision_id: 0,
+            model: model.to_string(),
+            prompt_hash: prompt_hash.to_string(),
+            action: None,
+            confidence: None,
+            reason: None,
+            error: None,
+            latency_ms: 0,
+            live_action: None,
+            agree: None,
+            encrypted: false,
+        }
+    }
+}
+
+/// Merkle root over the decision records written since the previous
+/// checkpoint (see `checkpoint`)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CheckpointEvent {
+    /// Always "checkpoint"
+    pub event: String,
+    /// Unix timestamp (milliseconds)
+    pub timestamp_ms: u64,
+    /// IDs of the first and last record covered, in file order
+    pub first_id: u64,
+    pub last_id: u64,
+    pub records: usize,
+    /// RFC 6962 tree hash over the records' lines (hex)
+    pub root: String,
+    /// Root of the checkpoint before this one in the file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous: Option<String>,
+}
+
+/// A checkpoint root sent to an external witness
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AnchorEvent {
+    /// Always "anchor"
+    pub event: String,
+    /// Unix timestamp (milliseconds)
+    pub timestamp_ms: u64,
+    pub root: String,
+    /// Anchor endpoint or time-stamping authority
+    pub url: String,
+    pub ok: bool,
+    /// Anchor response body, or RFC 3161 response (DER, hex)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub receipt: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl AnchorEvent {
+    pub fn new(root: &str, url: &str) -> Self {
+        Self {
+            event: "anchor".to_string(),
+            timestamp_ms: now_ms(),
+            root: root.to_string(),
+            url: url.to_string(),
+            ok: false,
+            receipt: None,
+            error: None,
+        }
+    }
+}
+
+/// A `CHECK` request the guard denied (see `guard`)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DenialEvent {
+    /// Always "denial"
+    pub event: String,
+    /// Unix timestamp (milliseconds)
+    pub timestamp_ms: u64,
+    /// Agent connection that asked
+    pub session: u64,
+    #[serde(default)]
+    pub agent_id: String,
+    #[serde(default)]
+    pub session_id: String,
+    /// The intended action, redacted (absent when payloads are encrypted)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_log: Option<String>,
+    /// SHA-256 of the unredacted action
+    pub input_hash: String,
+    /// Filter rule that matched the action
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+    /// LLM verdict and confidence (absent for rule denials and errors)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verdict: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Fingerprint of the model that answered
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Why the LLM gave no verdict
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub latency_ms: u64,
+    /// `reason` is age-encrypted (see `encrypt`)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub encrypted: bool,
+}
+
+impl DenialEvent {
+    pub fn new(agent: &AgentIds, input_log: &str, raw_input: &str) -> Self {
+        Self {
+            event: "denial".to_string(),
+            timestamp_ms: now_ms(),
+            session: agent.connection_id,
+            agent_id: agent.agent_id.clone(),
+            session_id: agent.session_id.clone(),
+            input_log: Some(input_log.to_string()),
+            input_hash: sha256_hex(raw_input),
+            rule: None,
+            verdict: None,
+            confidence: None,
+            reason: None,
+            model: None,
+            error: None,
+            latency_ms: 0,
+            encrypted: false,
+        }
+    }
+}
+
+/// Decision records awaiting the next checkpoint
+#[derive(Default)]
+struct Checkpoints {
+    /// Records per checkpoint (0: none)
+    every: usize,
+    leaves: Vec<[u8; 32]>,
+    first_id: u64,
+    last_id: u64,
+    /// Root of the last checkpoint written
+    previous: Option<String>,
+    /// Checkpoints to anchor (dropped once closed)
+    anchor: Option<mpsc::UnboundedSender<CheckpointEvent>>,
+}
+
+/// Startup arming checklist (see `selftest`)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SelfTestEvent {
+    /// Always "selftest"
+    pub event: String,
+    /// Unix timestamp (milliseconds)
+    pub timestamp_ms: u64,
+    /// Every check but `audit`, which is this line being read back
+    pub checks: Vec<SelfCheck>,
+}
+
+/// One checklist item
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SelfCheck {
+    pub name: String,
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl SelfTestEvent {
+    pub fn new(checks: Vec<SelfCheck>) -> Self {
+        Self {
+            event: "selftest".to_string(),
+            timestamp_ms: now_ms(),
+            checks,
+        }
+    }
+}
+
+impl OperatorEvent {
+    pub fn new(action: &str, operator: &str, reason: Option<&str>) -> Self {
+        Self {
+            event: "operator".to_string(),
+            timestamp_ms: now_ms(),
+            action: action.to_string(),
+            operator: operator.to_string(),
+            reason: reason.map(str::to_string),
+            until_ms: None,
+            target_pid: None,
+            target_container: None,
+            target_vm: None,
+        }
+    }
+}
+
+/// Per-connection totals - written when the connection closes and every
+/// hour while it stays open (totals since the connection opened)
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SessionSummary {
+    /// Always "session"
+    pub event: String,
+    /// Unix timestamp (milliseconds)
+    pub timestamp_ms: u64,
+    /// Agent connection ID (as in `/agents`)
+    pub session: u64,
+    pub peer: String,
+    #[serde(default)]
+    pub agent_id: String,
+    #[serde(default)]
+    pub session_id: String,
+    pub connected_ms: u64,
+    /// "closed" or "hourly"
+    pub reason: String,
+    /// Lines received
+    pub lines: u64,
+    /// Lines skipped by flow control
+    #[serde(default)]
+    pub dropped: u64,
+    /// Decision records written
+    pub decisions: u64,
+    /// Decisions made without the LLM (pre-filter and fast-path rules)
+    pub filtered: u64,
+    /// Decisions per action
+    pub actions: BTreeMap<String, u64>,
+    /// LLM decision latency
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<LatencySummary>,
+    /// ID of the session's last decision record
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_id: Option<u64>,
+    /// SHA-256 of the last decision record's line, as written
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_hash: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LatencySummary {
+    pub min: u64,
+    pub avg: u64,
+    pub p99: u64,
+}
+
+/// Running totals of one session's decisions
+#[derive(Debug, Default)]
+struct SessionTotals {
+    decisions: u64,
+    filtered: u64,
+    actions: BTreeMap<String, u64>,
+    /// LLM latency (ms) -> decisions
+    latencies: BTreeMap<u64, u64>,
+    last: Option<(u64, String)>,
+}
+
+impl SessionTotals {
+    fn observe(&mut self, record: &DecisionRecord, hash: String) {
+        self.decisions += 1;
+        *self.actions.entry(record.action.clone()).or_default() += 1;
+        match record.filtered {
+            true => self.filtered += 1,
+            false => *self.latencies.entry(record.latency_ms).or_default() += 1,
+        }
+        self.last = Some((record.id, hash));
+    }
+
+    fn latency(&self) -> Option<LatencySummary> {
+        let count: u64 = self.latencies.values().sum();
+        let min = *self.latencies.keys().next()?;
+        let sum: u64 = self.latencies.iter().map(|(ms, n)| ms * n).sum();
+        // Smallest latency at or above 99% of the decisions
+        let rank = (count * 99).div_ceil(100);
+        let mut seen = 0;
+        let p99 = self
+            .latencies
+            .iter()
+            .find(|(_, n)| {
+                seen += *n;
+                seen >= rank
+            })
+            .map_or(min, |(ms, _)| *ms);
+        Some(LatencySummary {
+            min,
+            avg: sum / count,
+            p99,
+        })
+    }
+}
+
+/// Why an audit trail write failed
+#[derive(Debug, thiserror::Error)]
+pub enum AuditError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("cannot serialize audit event: {0}")]
+    Serialize(#[from] serde_json::Error),
+    /// Sealing a record's payload (`[encrypt]`)
+    #[error("cannot encrypt record: {0}")]
+    Encrypt(#[source] std::io::Error),
+    /// A self-test event did not read back as written
+    #[error("event not found on read-back")]
+    ReadBack,
+}
+
+impl AuditError {
+    pub fn category(&self) -> &'static str {
+        match self {
+            AuditError::Io(_) => "audit.io",
+            AuditError::Serialize(_) => "audit.serialize",
+            AuditError::Encrypt(_) => "audit.encrypt",
+            AuditError::ReadBack => "audit.read_back",
+        }
+    }
+}
+
 /// Audit trail writer (append-only JSONL)
 pub struct AuditTrail {
-    writer: Mutex<BufWriter<File>>,
+    path: PathBuf,
+    writer: Mutex<Mirrored>,
+    /// Health of the backup sink (`--audit-backup`)
+    backup: Option<BackupMonitor>,
+    /// Unix ms the write in progress started (0 when idle)
+    busy_since: Arc<AtomicU64>,
     next_id: Mutex<u64>,
+    /// Stamps decision records, in ID order
+    sequencer: Mutex<Sequencer>,
     model_fingerprint: ModelFingerprint,
     prompt_hash: String,
+    /// Agent endpoint the pipeline serves, written to every header
+    endpoint: Option<String>,
+    signing_key: Option<Vec<u8>>,
+    /// HA instance id stamped on every decision
+    instance: Option<String>,
+    started_at: u64,
+    /// Unix ms of the oldest line in the current file
+    segment_started: AtomicU64,
+    recovery: Option<RecoveryEvent>,
+    /// Last `RECENT_RECORDS` decisions, oldest first
+    recent: Mutex<VecDeque<DecisionRecord>>,
+    /// Live feed of every record written
+    events: broadcast::Sender<DecisionRecord>,
+    normalizer: Normalizer,
+    /// Records per template today
+    templates: Templates,
+    /// Decision totals per open session
+    sessions: Mutex<HashMap<u64, SessionTotals>>,
+    checkpoints: Mutex<Checkpoints>,
+    /// Encrypts record payloads in the file
+    encryptor: Option<Encryptor>,
+    /// Keep the full rendered prompt, not just its hash
+    record_prompts: bool,
+    durability: Durability,
+    /// Failed writes by category
+    errors: ErrorCounts,
 }
 
 impl AuditTrail {
     /// Create a new audit trail
+    ///
+    /// An existing file is scanned first so IDs continue where the previous
+    /// run stopped; a crashed run gets a `recovery` event.
     pub fn new(
         path: PathBuf,
         model_fingerprint: ModelFingerprint,
         prompt_template: &str,
-    ) -> std::io::Result<Self> {
-        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+    ) -> Result<Self, AuditError> {
+        Self::open(path, model_fingerprint, prompt_template, None)
+    }
+
+    /// Create the audit trail of a pipeline serving `endpoint` (e.g.
+    /// `unix:/tmp/tripwired.sock`), which every header records
+    pub fn open(
+        path: PathBuf,
+        model_fingerprint: ModelFingerprint,
+        prompt_template: &str,
+        endpoint: Option<&str>,
+    ) -> Result<Self, AuditError> {
+        let scan = scan_existing(&path)?;
+        let file = open_append(&path, Durability::Buffered)?;
 
         let prompt_hash = sha256_hex(prompt_template);
+        let mut writer = Mirrored::new(file);
+
+        // Terminate a torn final line so our first record starts fresh
+        if scan.torn_line_bytes.is_some() {
+            writeln!(writer)?;
+        }
 
-        // Write header record
-        let mut writer = BufWriter::new(file);
-        let header = AuditHeader {
-            version: "1.0.0".to_string(),
-            created_at: now_ms(),
-            model_fingerprint: model_fingerprint.clone(),
-            prompt_hash: prompt_hash.clone(),
+        let resume_id = scan.last_id.max(scan.lost_id.unwrap_or(0)) + 1;
+        let recovery = if scan.needs_recovery() {
+            let event = RecoveryEvent {
+                event: "recovery".to_string(),
+                timestamp_ms: now_ms(),
+                previous_last_id: scan.last_id,
+                resume_id,
+                clean_shutdown: scan.clean_shutdown,
+                torn_line_bytes: scan.torn_line_bytes,
+                lost_id: scan.lost_id,
+                corrupt_lines: scan.corrupt_lines,
+            };
+            writeln!(writer, "{}", serde_json::to_string(&event)?)?;
+            Some(event)
+        } else {
+            None
         };
-        writeln!(writer, "{}", serde_json::to_string(&header)?)?;
-        writer.flush()?;
+
+        let endpoint = endpoint.map(str::to_string);
+        write_header(
+            &mut writer,
+            &model_fingerprint,
+            &prompt_hash,
+            endpoint.as_deref(),
+            None,
+            None,
+        )?;
 
         Ok(Self {
+            path,
             writer: Mutex::new(writer),
-            next_id: Mutex::new(1),
+            backup: None,
+            busy_since: Arc::new(AtomicU64::new(0)),
+            next_id: Mutex::new(resume_id),
+            sequencer: Mutex::new(Sequencer::new(Arc::new(SystemClock::new()))),
             model_fingerprint,
             prompt_hash,
+            endpoint,
+            signing_key: None,
+            instance: None,
+            started_at: now_ms(),
+            segment_started: AtomicU64::new(scan.first_ms.unwrap_or_else(now_ms)),
+            recovery,
+            recent: Mutex::new(VecDeque::with_capacity(RECENT_RECORDS)),
+            events: broadcast::channel(EVENT_BUFFER).0,
+            normalizer: Normalizer::new(),
+            templates: Templates::default(),
+            sessions: Mutex::default(),
+            checkpoints: Mutex::new(Checkpoints {
+                previous: scan.checkpoint,
+                ..Default::default()
+            }),
+            encryptor: None,
+            record_prompts: false,
+            durability: Durability::Buffered,
+            errors: ErrorCounts::default(),
         })
     }
 
-    /// Record a decision
+    /// Failed writes by category since startup
+    pub fn errors(&self) -> BTreeMap<String, u64> {
+        self.errors.snapshot()
+    }
+
+    /// Recovery event written at startup, if the previous run crashed
+    pub fn recovery(&self) -> Option<&RecoveryEvent> {
+        self.recovery.as_ref()
+    }
+
+    /// Sign epilogue records with HMAC-SHA256 instead of a bare digest
+    pub fn with_signing_key(mut self, key: Vec<u8>) -> Self {
+        self.signing_key = Some(key);
+        self
+    }
+
+    /// Stamp decisions with the HA instance id
+    /// Write a checkpoint every `records` decision records, and send it to
+    /// `anchor` if given
+    pub fn with_checkpoints(
+        self,
+        records: usize,
+        anchor: Option<mpsc::UnboundedSender<CheckpointEvent>>,
+    ) -> Self {
+        {
+            let mut checkpoints = self.checkpoints.lock().unwrap();
+            checkpoints.every = records;
+            checkpoints.anchor = anchor;
+        }
+        self
+    }
+
+    /// Write `input_log`, `raw_response` and `rendered_prompt` encrypted
+    pub fn with_encryptor(mut self, encryptor: Encryptor) -> Self {
+        self.encryptor = Some(encryptor);
+        self
+    }
+
+    /// Sync writes to disk as `durability` asks (on Windows, `Record`
+    /// reopens the file write-through)
+    pub fn with_durability(mut self, durability: Durability) -> Result<Self, AuditError> {
+        self.durability = durability;
+        #[cfg(windows)]
+        if durability == Durability::Record {
+            let writer = self.writer.get_mut().unwrap();
+            writer.flush()?;
+            writer.reopen(open_append(&self.path, durability)?);
+        }
+        Ok(self)
+    }
+
+    /// Write everything to a second file as well (`--audit-backup`), which
+    /// starts with its own header
+    pub fn with_backup(mut self, path: PathBuf) -> Result<Self, AuditError> {
+        let mut backup = Backup::open(path)?;
+        write_header(
+            &mut backup,
+            &self.model_fingerprint,
+            &self.prompt_hash,
+            self.endpoint.as_deref(),
+            None,
+            None,
+        )?;
+        self.backup = Some(backup.monitor());
+        self.writer.get_mut().unwrap().set_backup(backup);
+        Ok(self)
+    }
+
+    /// Backup sink health, if there is one
+    pub fn backup_status(&self) -> Option<BackupStatus> {
+        self.backup.as_ref().map(BackupMonitor::status)
+    }
+
+    /// Keep each decision's rendered prompt in full (`--audit-prompts`)
+    pub fn with_rendered_prompts(mut self) -> Self {
+        self.record_prompts = true;
+        self
+    }
+
+    /// Stamp decision records from `clock` instead of the system clocks
+    #[cfg(test)]
+    pub fn with_clock(mut self, clock: Arc<dyn crate::clock::Clock>) -> Self {
+        self.sequencer = Mutex::new(Sequencer::new(clock));
+        self
+    }
+
+    pub fn with_instance(mut self, instance: &str) -> Self {
+        self.instance = Some(instance.to_string());
+        self
+    }
+
+    /// Record a decision (unredacted, no rule)
+    #[cfg(test)]
     pub fn record(
         &self,
         input_log: &str,
@@ -114,31 +1174,490 @@ impl AuditTrail {
         filtered: bool,
         latency_ms: u64,
         raw_response: Option<String>,
-    ) -> std::io::Result<u64> {
-        let mut id_guard = self.next_id.lock().unwrap();
-        let id = *id_guard;
-        *id_guard += 1;
-        drop(id_guard);
-
-        let record = DecisionRecord {
-            id,
-            timestamp_ms: now_ms(),
-            input_log: input_log.to_string(),
-            input_hash: sha256_hex(input_log),
-            action: action.to_string(),
+    ) -> Result<u64, AuditError> {
+        self.record_entry(RecordInput {
+            input_log,
+            action,
             confidence,
             filtered,
             latency_ms,
-            model_fingerprint: self.model_fingerprint.fingerprint(),
-            prompt_hash: self.prompt_hash[..8].to_string(),
             raw_response,
+            ..Default::default()
+        })
+    }
+
+    /// Record a decision with full metadata
+    pub fn record_entry(&self, input: RecordInput) -> Result<u64, AuditError> {
+        self.counted(|| {
+            let mut id_guard = self.next_id.lock().unwrap();
+            let id = *id_guard;
+            *id_guard += 1;
+            // Stamped under the ID lock so time follows ID order
+            let stamp = self.sequencer.lock().unwrap().stamp();
+            drop(id_guard);
+
+            let raw = input.raw_input.unwrap_or(input.input_log);
+            let (input_hash, redacted) = match input.input_hash {
+                Some(hash) => (hash.to_string(), sha256_hex(input.input_log) != hash),
+                None => (sha256_hex(raw), raw != input.input_log),
+            };
+            let template = self.normalizer.template(input.input_log);
+            let record = DecisionRecord {
+                id,
+                timestamp_ms: stamp.timestamp_ms,
+                monotonic_ms: stamp.monotonic_ms,
+                clock_behind_ms: stamp.behind_ms,
+                input_log: input.input_log.to_string(),
+                input_hash,
+                template_hash: sha256_hex(&template),
+                redacted,
+                action: input.action.to_string(),
+                confidence: input.confidence,
+                model_confidence: input.model_confidence,
+                filtered: input.filtered,
+                latency_ms: input.latency_ms,
+                model_fingerprint: input
+                    .model_fingerprint
+                    .map_or_else(|| self.model_fingerprint.fingerprint(), str::to_string),
+                prompt_hash: self.prompt_hash[..8].to_string(),
+                raw_response: input.raw_response,
+                rendered_prompt_hash: input.rendered_prompt.map(sha256_hex),
+                rendered_prompt: input
+                    .rendered_prompt
+                    .filter(|_| self.record_prompts)
+                    .map(str::to_string),
+                error: input.error.map(str::to_string),
+                rule: input.rule.map(str::to_string),
+                reason: input.reason.map(str::to_string),
+                context_hash: input.context.map(sha256_hex),
+                batch_size: input.batch_size,
+                suppressed: input.suppressed.map(str::to_string),
+                policy: input.policy.map(str::to_string),
+                verdict: input.verdict.map(str::to_string),
+                profile: input.profile.map(str::to_string),
+                instance: self.instance.clone(),
+                agent_id: input.agent.map(|a| a.agent_id.clone()),
+                session_id: input.agent.map(|a| a.session_id.clone()),
+                connection_id: input.agent.map(|a| a.connection_id),
+                backfilled: input.backfill_of.is_some(),
+                backfill_of: input.backfill_of,
+                recovered: input.recovered,
+                encrypted: false,
+                explanation: None,
+            };
+
+            let line = match self.encryptor {
+                Some(ref encryptor) => {
+                    let sealed = encryptor.seal(&record).map_err(AuditError::Encrypt)?;
+                    serde_json::to_string(&sealed)?
+                }
+                None => serde_json::to_string(&record)?,
+            };
+            self.append_record(id, &line, record.action == "KILL")?;
+            self.templates.observe(&record, &template);
+            if let Some(agent) = input.agent {
+                let mut sessions = self.sessions.lock().unwrap();
+                sessions
+                    .entry(agent.connection_id)
+                    .or_default()
+                    .observe(&record, sha256_hex(&line));
+            }
+
+            // No subscribers is not an error
+            let _ = self.events.send(record.clone());
+            let mut recent = self.recent.lock().unwrap();
+            if recent.len() == RECENT_RECORDS {
+                recent.pop_front();
+            }
+            recent.push_back(record);
+
+            Ok(id)
+        })
+    }
+
+    /// Receive every decision recorded from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<DecisionRecord> {
+        self.events.subscribe()
+    }
+
+    /// Decision `id`, if it is among the recent ones
+    pub fn get(&self, id: u64) -> Option<DecisionRecord> {
+        self.recent
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|r| r.id == id)
+            .cloned()
+    }
+
+    /// ID of the last decision recorded (0 before the first)
+    pub fn last_id(&self) -> u64 {
+        *self.next_id.lock().unwrap() - 1
+    }
+
+    /// Recent decisions with an ID above `after`, oldest first
+    pub fn recent(&self, after: u64) -> Vec<DecisionRecord> {
+        self.recent
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|r| r.id > after)
+            .cloned()
+            .collect()
+    }
+
+    /// The `limit` most frequent templates recorded today
+    pub fn templates(&self, limit: usize) -> TemplateReport {
+        self.templates.report(limit)
+    }
+
+    /// Append the signed shutdown footer with final stats
+    pub fn record_shutdown<S: Serialize>(
+        &self,
+        reason: &str,
+        drained: bool,
+        stats: &S,
+    ) -> Result<(), AuditError> {
+        self.counted(|| {
+            let last_id = *self.next_id.lock().unwrap() - 1;
+            let now = now_ms();
+
+            let mut footer = ShutdownFooter {
+                event: "shutdown".to_string(),
+                timestamp_ms: now,
+                reason: reason.to_string(),
+                last_id,
+                uptime_ms: now.saturating_sub(self.started_at),
+                drained,
+                stats: serde_json::to_value(stats)?,
+                signature: None,
+            };
+            // Signature covers the footer serialized without the signature field
+            footer.signature = Some(self.sign(&serde_json::to_string(&footer)?));
+
+            self.append(&serde_json::to_string(&footer)?)
+        })
+    }
+
+    /// Append an operator override event (flushed immediately)
+    pub fn record_operator(&self, event: &OperatorEvent) -> Result<(), AuditError> {
+        self.append_event(event)
+    }
+
+    /// Append a KILL explanation event (flushed immediately)
+    pub fn record_explanation(&self, event: &ExplanationEvent) -> Result<(), AuditError> {
+        self.counted(|| match self.encryptor {
+            Some(ref encryptor) => {
+                let mut event = event.clone();
+                event.explanation = encryptor
+                    .seal_text(event.explanation.as_deref())
+                    .map_err(AuditError::Encrypt)?;
+                event.encrypted = true;
+                self.append(&serde_json::to_string(&event)?)
+            }
+            None => self.append(&serde_json::to_string(event)?),
+        })
+    }
+
+    /// Append a KILL authorization exchange (flushed immediately)
+    pub fn record_authorization(&self, event: &AuthorizationEvent) -> Result<(), AuditError> {
+        self.append_event(event)
+    }
+
+    /// Append a pre-KILL snapshot event (flushed immediately)
+    pub fn record_snapshot(&self, event: &SnapshotEvent) -> Result<(), AuditError> {
+        self.append_event(event)
+    }
+
+    /// Append a human correction of a decision (flushed immediately)
+    pub fn record_correction(&self, event: &CorrectionEvent) -> Result<(), AuditError> {
+        self.append_event(event)
+    }
+
+    /// Append a shadow verdict event (flushed immediately)
+    pub fn record_shadow(&self, event: &ShadowEvent) -> Result<(), AuditError> {
+        self.counted(|| match self.encryptor {
+            Some(ref encryptor) => {
+                let mut event = event.clone();
+                event.reason = encryptor
+                    .seal_text(event.reason.as_deref())
+                    .map_err(AuditError::Encrypt)?;
+                event.encrypted = true;
+                self.append(&serde_json::to_string(&event)?)
+            }
+            None => self.append(&serde_json::to_string(event)?),
+        })
+    }
+
+    /// Append a honeypot trip event (flushed immediately)
+    pub fn record_honeypot(&self, event: &HoneypotEvent) -> Result<(), AuditError> {
+        self.append_event(event)
+    }
+
+    /// Append a guard denial event (flushed immediately)
+    pub fn record_denial(&self, event: &DenialEvent) -> Result<(), AuditError> {
+        self.counted(|| match self.encryptor {
+            Some(ref encryptor) => {
+                let mut event = event.clone();
+                event.input_log = None;
+                event.reason = encryptor
+                    .seal_text(event.reason.as_deref())
+                    .map_err(AuditError::Encrypt)?;
+                event.encrypted = true;
+                self.append(&serde_json::to_string(&event)?)
+            }
+            None => self.append(&serde_json::to_string(event)?),
+        })
+    }
+
+    /// Append a session summary for `agent` (`closed`: the connection
+    /// ended, its totals are dropped; otherwise an hourly summary)
+    pub fn record_session(&self, agent: &AgentStatus, closed: bool) -> Result<(), AuditError> {
+        self.counted(|| {
+            let mut sessions = self.sessions.lock().unwrap();
+            let totals = sessions.entry(agent.id).or_default();
+            let summary = SessionSummary {
+                event: "session".to_string(),
+                timestamp_ms: now_ms(),
+                session: agent.id,
+                peer: agent.peer.clone(),
+                agent_id: agent.agent_id.clone(),
+                session_id: agent.session_id.clone(),
+                connected_ms: agent.connected_ms,
+                reason: if closed { "closed" } else { "hourly" }.to_string(),
+                lines: agent.lines,
+                dropped: agent.dropped,
+                decisions: totals.decisions,
+                filtered: totals.filtered,
+                actions: totals.actions.clone(),
+                latency_ms: totals.latency(),
+                last_id: totals.last.as_ref().map(|(id, _)| *id),
+                last_hash: totals.last.as_ref().map(|(_, hash)| hash.clone()),
+            };
+            if closed {
+                sessions.remove(&agent.id);
+            }
+            drop(sessions);
+            self.append(&serde_json::to_string(&summary)?)
+        })
+    }
+
+    /// Append an anchoring attempt (flushed immediately)
+    pub fn record_anchor(&self, event: &AnchorEvent) -> Result<(), AuditError> {
+        self.append_event(event)
+    }
+
+    /// Unix ms of the oldest line in the current file
+    pub fn segment_started(&self) -> u64 {
+        self.segment_started.load(Ordering::Relaxed)
+    }
+
+    /// Close the current file (checkpointed, ending with a `rotate` event),
+    /// rename it to `segment` and continue in a fresh file
+    pub fn rotate(&self, segment: &Path) -> Result<(), AuditError> {
+        self.counted(|| {
+            let mut writer = self.writer.lock().unwrap();
+            let mut checkpoints = self.checkpoints.lock().unwrap();
+            if checkpoints.every > 0 && !checkpoints.leaves.is_empty() {
+                self.checkpoint(&mut writer, &mut checkpoints)?;
+            }
+            let previous = checkpoints.previous.clone();
+            drop(checkpoints);
+            let last_id = *self.next_id.lock().unwrap() - 1;
+            let event = RotateEvent {
+                event: "rotate".to_string(),
+                timestamp_ms: now_ms(),
+                last_id,
+                segment: segment
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+            };
+            writeln!(writer, "{}", serde_json::to_string(&event)?)?;
+            writer.flush()?;
+
+            std::fs::rename(&self.path, segment)?;
+            let file = open_append(&self.path, self.durability)?;
+            writer.rotate(file, segment);
+            self.segment_started.store(now_ms(), Ordering::Relaxed);
+            // A restart before the next record still continues the IDs and chain
+            write_header(
+                &mut *writer,
+                &self.model_fingerprint,
+                &self.prompt_hash,
+                self.endpoint.as_deref(),
+                Some(last_id),
+                previous,
+            )
+        })
+    }
+
+    /// Append a signed segment tombstone; with checkpoints, it is covered
+    /// by a checkpoint at once (and anchored)
+    pub fn record_prune(&self, event: &PruneEvent) -> Result<(), AuditError> {
+        self.counted(|| {
+            let mut event = event.clone();
+            event.signature = None;
+            event.signature = Some(self.sign(&serde_json::to_string(&event)?));
+            let line = serde_json::to_string(&event)?;
+
+            let mut writer = self.writer.lock().unwrap();
+            writeln!(writer, "{}", line)?;
+            writer.flush()?;
+            let mut checkpoints = self.checkpoints.lock().unwrap();
+            if checkpoints.every == 0 {
+                return Ok(());
+            }
+            if checkpoints.leaves.is_empty() {
+                checkpoints.first_id = checkpoints.last_id;
+            }
+            checkpoints.leaves.push(leaf_hash(line.as_bytes()));
+            self.checkpoint(&mut writer, &mut checkpoints)
+        })
+    }
+
+    /// Checkpoint the records written since the last checkpoint, and stop
+    /// anchoring (shutdown)
+    pub fn close_checkpoints(&self) -> Result<(), AuditError> {
+        self.counted(|| {
+            let mut writer = self.writer.lock().unwrap();
+            let mut checkpoints = self.checkpoints.lock().unwrap();
+            let result = match checkpoints.every > 0 && !checkpoints.leaves.is_empty() {
+                true => self.checkpoint(&mut writer, &mut checkpoints),
+                false => Ok(()),
+            };
+            checkpoints.anchor = None;
+            result
+        })
+    }
+
+    /// Append a self-test event and read it back from the file
+    pub fn record_selftest(&self, event: &SelfTestEvent) -> Result<(), AuditError> {
+        self.counted(|| {
+            let line = serde_json::to_string(event)?;
+            // Hold the writer so no record lands between the write and the read
+            let mut writer = self.writer.lock().unwrap();
+            self.busy_since.store(now_ms().max(1), Ordering::Relaxed);
+            let result = writeln!(writer, "{}", line)
+                .and_then(|_| writer.flush())
+                .and_then(|_| read_tail(&self.path, line.len() as u64 + 1));
+            self.busy_since.store(0, Ordering::Relaxed);
+            match result? == format!("{}\n", line) {
+                true => Ok(()),
+                false => Err(AuditError::ReadBack),
+            }
+        })
+    }
+
+    /// Flush any buffered records to disk (and sync them, unless the
+    /// durability level is `Buffered`)
+    pub fn flush(&self) -> Result<(), AuditError> {
+        self.counted(|| {
+            let mut writer = self.writer.lock().unwrap();
+            writer.flush()?;
+            Ok(self.sync(&writer, true)?)
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Start time of the write in progress (Unix ms, 0 when idle), for the
+    /// watchdog
+    pub fn busy_since(&self) -> Arc<AtomicU64> {
+        Arc::clone(&self.busy_since)
+    }
+
+    /// Write one line and flush it
+    fn append(&self, line: &str) -> Result<(), AuditError> {
+        let mut writer = self.writer.lock().unwrap();
+        self.busy_since.store(now_ms().max(1), Ordering::Relaxed);
+        let result = writeln!(writer, "{}", line)
+            .and_then(|_| writer.flush())
+            .and_then(|_| self.sync(&writer, false));
+        self.busy_since.store(0, Ordering::Relaxed);
+        Ok(result?)
+    }
+
+    /// Serialize one event and append it
+    fn append_event(&self, event: &impl Serialize) -> Result<(), AuditError> {
+        self.counted(|| self.append(&serde_json::to_string(event)?))
+    }
+
+    /// Count the failure of `write` by category
+    fn counted<T>(&self, write: impl FnOnce() -> Result<T, AuditError>) -> Result<T, AuditError> {
+        write().inspect_err(|e| self.errors.count(e.category()))
+    }
+
+    /// Sync the file if the durability level asks for it after this write
+    fn sync(&self, writer: &Mirrored, kill: bool) -> std::io::Result<()> {
+        let sync = match self.durability {
+            Durability::Buffered => false,
+            // The write-through handle already put it on disk
+            Durability::Record => cfg!(not(windows)),
+            Durability::Kill => kill,
         };
+        match sync {
+            true => writer.sync_data(),
+            false => Ok(()),
+        }
+    }
 
+    /// Write a decision record and, once `every` records are pending, the
+    /// checkpoint covering them (both in file order, under the writer lock)
+    fn append_record(&self, id: u64, line: &str, kill: bool) -> Result<(), AuditError> {
         let mut writer = self.writer.lock().unwrap();
-        writeln!(writer, "{}", serde_json::to_string(&record)?)?;
+        self.busy_since.store(now_ms().max(1), Ordering::Relaxed);
+        let mut result = writeln!(writer, "{}", line)
+            .and_then(|_| writer.flush())
+            .and_then(|_| self.sync(&writer, kill))
+            .map_err(AuditError::from);
+        let mut checkpoints = self.checkpoints.lock().unwrap();
+        if result.is_ok() && checkpoints.every > 0 {
+            if checkpoints.leaves.is_empty() {
+                checkpoints.first_id = id;
+            }
+            checkpoints.last_id = id;
+            checkpoints.leaves.push(leaf_hash(line.as_bytes()));
+            if checkpoints.leaves.len() >= checkpoints.every {
+                result = self.checkpoint(&mut writer, &mut checkpoints);
+            }
+        }
+        self.busy_since.store(0, Ordering::Relaxed);
+        result
+    }
+
+    /// Write the checkpoint over the pending records and hand it to the
+    /// anchorer
+    fn checkpoint(
+        &self,
+        writer: &mut Mirrored,
+        checkpoints: &mut Checkpoints,
+    ) -> Result<(), AuditError> {
+        let root = to_hex(&merkle_root(&checkpoints.leaves));
+        let event = CheckpointEvent {
+            event: "checkpoint".to_string(),
+            timestamp_ms: now_ms(),
+            first_id: checkpoints.first_id,
+            last_id: checkpoints.last_id,
+            records: checkpoints.leaves.len(),
+            root: root.clone(),
+            previous: checkpoints.previous.replace(root),
+        };
+        checkpoints.leaves.clear();
+        writeln!(writer, "{}", serde_json::to_string(&event)?)?;
         writer.flush()?;
+        if let Some(ref anchor) = checkpoints.anchor {
+            // The anchorer stopped early: nothing to do but keep writing
+            let _ = anchor.send(event);
+        }
+        Ok(())
+    }
 
-        Ok(id)
+    fn sign(&self, payload: &str) -> String {
+        sign(self.signing_key.as_deref(), payload)
     }
 }
 
@@ -148,6 +1667,158 @@ struct AuditHeader {
     created_at: u64,
     model_fingerprint: ModelFingerprint,
     prompt_hash: String,
+    /// Agent endpoint (`unix:..`, `pipe:..`, `tcp:..`) telling apart the
+    /// trails of kernels sharing a host
+    #[serde(skip_serializing_if = "Option::is_none")]
+    endpoint: Option<String>,
+    /// Carried over from the previous segment after a rotation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    checkpoint: Option<String>,
+}
+
+/// Open the trail for appending (write-through for `Record` on Windows)
+fn open_append(path: &Path, durability: Durability) -> std::io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(windows)]
+    if durability == Durability::Record {
+        use std::os::windows::fs::OpenOptionsExt;
+        use windows_sys::Win32::Storage::FileSystem::FILE_FLAG_WRITE_THROUGH;
+        options.custom_flags(FILE_FLAG_WRITE_THROUGH);
+    }
+    #[cfg(not(windows))]
+    let _ = durability;
+    options.open(path)
+}
+
+/// First line of every run and segment
+fn write_header(
+    writer: &mut impl Write,
+    model_fingerprint: &ModelFingerprint,
+    prompt_hash: &str,
+    endpoint: Option<&str>,
+    last_id: Option<u64>,
+    checkpoint: Option<String>,
+) -> Result<(), AuditError> {
+    let header = AuditHeader {
+        version: "1.0.0".to_string(),
+        created_at: now_ms(),
+        model_fingerprint: model_fingerprint.clone(),
+        prompt_hash: prompt_hash.to_string(),
+        endpoint: endpoint.map(str::to_string),
+        last_id,
+        checkpoint,
+    };
+    writeln!(writer, "{}", serde_json::to_string(&header)?)?;
+    Ok(writer.flush()?)
+}
+
+/// What we learn from an existing audit file before appending to it
+#[derive(Debug, Default)]
+struct ScanResult {
+    has_records: bool,
+    last_id: u64,
+    clean_shutdown: bool,
+    torn_line_bytes: Option<u64>,
+    lost_id: Option<u64>,
+    corrupt_lines: u64,
+    /// Root of the last checkpoint, chained from the next one
+    checkpoint: Option<String>,
+    /// Timestamp of the first line
+    first_ms: Option<u64>,
+}
+
+impl ScanResult {
+    fn needs_recovery(&self) -> bool {
+        self.has_records && (!self.clean_shutdown || self.torn_line_bytes.is_some())
+    }
+}
+
+/// Fields shared by every line type (header, decision, event)
+#[derive(Deserialize)]
+struct LineProbe {
+    id: Option<u64>,
+    event: Option<String>,
+    last_id: Option<u64>,
+    previous_last_id: Option<u64>,
+    root: Option<String>,
+    checkpoint: Option<String>,
+    timestamp_ms: Option<u64>,
+    created_at: Option<u64>,
+}
+
+fn scan_existing(path: &Path) -> std::io::Result<ScanResult> {
+    let mut scan = ScanResult::default();
+    let file = match File::open(path) {
+        Ok(f) => f,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(scan),
+        Err(e) => return Err(e),
+    };
+
+    let mut reader = BufReader::new(file);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        scan.has_records = true;
+
+        // A final line without newline was cut off mid-write
+        if line.last() != Some(&b'\n') {
+            scan.torn_line_bytes = Some(line.len() as u64);
+            scan.lost_id = torn_line_id(&line).filter(|&id| id > scan.last_id);
+            scan.clean_shutdown = false;
+            break;
+        }
+
+        match serde_json::from_slice::<LineProbe>(&line) {
+            Ok(probe) => {
+                if scan.first_ms.is_none() {
+                    scan.first_ms = probe.timestamp_ms.or(probe.created_at);
+                }
+                let ids = [probe.id, probe.last_id, probe.previous_last_id];
+                for id in ids.into_iter().flatten() {
+                    scan.last_id = scan.last_id.max(id);
+                }
+                // Only a footer as the very last event counts as clean
+                match probe.event.as_deref() {
+                    Some("shutdown") => scan.clean_shutdown = true,
+                    Some("checkpoint") => scan.checkpoint = probe.root,
+                    // Header of a rotated-to segment
+                    None if probe.checkpoint.is_some() => scan.checkpoint = probe.checkpoint,
+                    _ if probe.id.is_some() => scan.clean_shutdown = false,
+                    _ => {}
+                }
+            }
+            Err(_) => {
+                scan.corrupt_lines += 1;
+                scan.clean_shutdown = false;
+            }
+        }
+    }
+
+    Ok(scan)
+}
+
+/// The last `len` bytes of a file
+fn read_tail(path: &Path, len: u64) -> std::io::Result<String> {
+    let mut file = File::open(path)?;
+    let len = len.min(file.metadata()?.len());
+    file.seek(SeekFrom::End(-(len as i64)))?;
+    let mut tail = String::new();
+    file.read_to_string(&mut tail)?;
+    Ok(tail)
+}
+
+/// Best-effort `"id":N` extraction from a truncated record
+fn torn_line_id(line: &[u8]) -> Option<u64> {
+    let text = String::from_utf8_lossy(line);
+    let rest = text.strip_prefix("{\"id\":")?;
+    let digits: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok()
 }
 
 fn now_ms() -> u64 {
@@ -157,7 +1828,7 @@ fn now_ms() -> u64 {
         .as_millis() as u64
 }
 
-fn sha256_hex(input: &str) -> String {
+pub fn sha256_hex(input: &str) -> String {
     use sha2::{Digest, Sha256};
 
     let mut hasher = Sha256::new();
@@ -165,17 +1836,61 @@ fn sha256_hex(input: &str) -> String {
     format!("{:x}", hasher.finalize())
 }
 
+/// Check a `signature` made over `unsigned` (its serialization without the
+/// signature field)
+fn verify<T: Serialize>(unsigned: &T, signature: Option<&str>, key: Option<&[u8]>) -> Option<bool> {
+    let Some(signature) = signature else {
+        return Some(false);
+    };
+    let payload = serde_json::to_string(unsigned).ok()?;
+    let keyed = signature.starts_with("hmac-sha256:");
+    if keyed && key.is_none() {
+        return None;
+    }
+    Some(signature == sign(key.filter(|_| keyed), &payload))
+}
+
+/// Footer signature: keyed HMAC, or a plain digest without a key
+fn sign(key: Option<&[u8]>, payload: &str) -> String {
+    match key {
+        Some(key) => format!("hmac-sha256:{}", hmac_sha256_hex(key, payload)),
+        None => format!("sha256:{}", sha256_hex(payload)),
+    }
+}
+
+fn hmac_sha256_hex(key: &[u8], input: &str) -> String {
+    use hmac::{Hmac, Mac};
+
+    let mut mac = Hmac::<sha2::Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(input.as_bytes());
+    format!("{:x}", mac.finalize().into_bytes())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clock::MockClock;
     use std::fs;
     use tempfile::tempdir;
 
     #[test]
     fn test_model_fingerprint() {
-        let fp = ModelFingerprint::new("llama-3.2", "http://localhost:1234/v1", 30, 0.0);
+        let fp = ModelFingerprint::new(
+            "llama-3.2",
+            "http://localhost:1234/v1",
+            30,
+            &Sampling::default(),
+        );
         assert!(fp.fingerprint().starts_with("llama-3.2@"));
         assert_eq!(fp.config_hash.len(), 64); // SHA-256 = 64 hex chars
+
+        // Any generation parameter changes the fingerprint
+        let seeded = Sampling {
+            seed: Some(7),
+            ..Sampling::default()
+        };
+        let fp2 = ModelFingerprint::new("llama-3.2", "http://localhost:1234/v1", 30, &seeded);
+        assert_ne!(fp.config_hash, fp2.config_hash);
     }
 
     #[test]
@@ -183,7 +1898,7 @@ mod tests {
         let dir = tempdir().unwrap();
         let path = dir.path().join("audit.jsonl");
 
-        let fp = ModelFingerprint::new("test-model", "http://localhost", 30, 0.0);
+        let fp = ModelFingerprint::new("test-model", "http://localhost", 30, &Sampling::default());
         let trail = AuditTrail::new(path.clone(), fp, "test prompt").unwrap();
 
         trail
@@ -196,5 +1911,356 @@ mod tests {
         let content = fs::read_to_string(&path).unwrap();
         let lines: Vec<&str> = content.lines().collect();
         assert_eq!(lines.len(), 3); // header + 2 records
+
+        let mut events = trail.subscribe();
+        trail
+            .record("live log", "KILL", 95, false, 120, None)
+            .unwrap();
+        assert_eq!(events.try_recv().unwrap().input_log, "live log");
+
+        let recent = trail.recent(0);
+        assert_eq!(recent.len(), 3);
+        assert_eq!(recent[0].input_log, "test log");
+        assert_eq!(trail.recent(recent[0].id).len(), 2);
+    }
+
+    #[test]
+    fn test_endpoint_header() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let fp = ModelFingerprint::new("test-model", "http://localhost", 30, &Sampling::default());
+        let header = |line: &str| serde_json::from_str::<serde_json::Value>(line).unwrap();
+
+        let trail = AuditTrail::new(path.clone(), fp.clone(), "test prompt").unwrap();
+        drop(trail);
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(header(content.lines().next().unwrap())
+            .get("endpoint")
+            .is_none());
+        fs::remove_file(&path).unwrap();
+
+        let trail = AuditTrail::open(
+            path.clone(),
+            fp,
+            "test prompt",
+            Some("unix:/run/staging.sock"),
+        )
+        .unwrap();
+        trail.record("log", "SUSTAIN", 100, true, 0, None).unwrap();
+        trail.rotate(&dir.path().join("segment.jsonl")).unwrap();
+        for file in [dir.path().join("segment.jsonl"), path] {
+            let content = fs::read_to_string(file).unwrap();
+            let first = header(content.lines().next().unwrap());
+            assert_eq!(first["endpoint"], "unix:/run/staging.sock");
+        }
+    }
+
+    #[test]
+    fn test_redacted_record_keeps_raw_hash() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let fp = ModelFingerprint::new("test-model", "http://localhost", 30, &Sampling::default());
+        let trail = AuditTrail::new(path.clone(), fp, "test prompt").unwrap();
+
+        let raw = "mail bob@example.com";
+        trail
+            .record_entry(RecordInput {
+                input_log: "mail [REDACTED:email]",
+                raw_input: Some(raw),
+                action: "SUSTAIN",
+                ..Default::default()
+            })
+            .unwrap();
+        trail
+            .record("clean", "SUSTAIN", 100, true, 0, None)
+            .unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        let redacted: DecisionRecord = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(redacted.input_log, "mail [REDACTED:email]");
+        assert_eq!(redacted.input_hash, sha256_hex(raw));
+        assert!(redacted.redacted);
+        assert!(!lines[1].contains("bob@"));
+        assert!(!lines[2].contains("redacted"));
+    }
+
+    #[test]
+    fn test_rendered_prompt() {
+        let dir = tempdir().unwrap();
+        let fp = ModelFingerprint::new("test-model", "http://localhost", 30, &Sampling::default());
+        let prompt = r#"[{"role":"user","content":"Log: \"x\""}]"#;
+        let input = || RecordInput {
+            input_log: "x",
+            action: "SUSTAIN",
+            rendered_prompt: Some(prompt),
+            ..Default::default()
+        };
+
+        let trail = AuditTrail::new(dir.path().join("a.jsonl"), fp.clone(), "p").unwrap();
+        let id = trail.record_entry(input()).unwrap();
+        let record = trail.get(id).unwrap();
+        assert_eq!(record.rendered_prompt_hash, Some(sha256_hex(prompt)));
+        assert!(record.rendered_prompt.is_none());
+
+        let trail = AuditTrail::new(dir.path().join("b.jsonl"), fp, "p")
+            .unwrap()
+            .with_rendered_prompts();
+        let id = trail.record_entry(input()).unwrap();
+        assert_eq!(
+            trail.get(id).unwrap().rendered_prompt.as_deref(),
+            Some(prompt)
+        );
+    }
+
+    #[test]
+    fn test_durability_levels() {
+        let dir = tempdir().unwrap();
+        let fp = ModelFingerprint::new("test-model", "http://localhost", 30, &Sampling::default());
+        for durability in [Durability::Record, Durability::Kill] {
+            let path = dir.path().join(format!("{:?}.jsonl", durability));
+            let trail = AuditTrail::new(path.clone(), fp.clone(), "p")
+                .unwrap()
+                .with_durability(durability)
+                .unwrap();
+            trail.record("a", "SUSTAIN", 90, true, 0, None).unwrap();
+            trail.record("b", "KILL", 95, true, 0, None).unwrap();
+            trail.rotate(&dir.path().join("segment.jsonl")).unwrap();
+            let id = trail.record("c", "KILL", 95, true, 0, None).unwrap();
+            trail.flush().unwrap();
+
+            let content = fs::read_to_string(&path).unwrap();
+            assert!(content.contains(r#""input_log":"c""#));
+            assert_eq!(trail.get(id).unwrap().action, "KILL");
+        }
+    }
+
+    #[test]
+    fn test_clock_step_annotated() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let fp = ModelFingerprint::new("test-model", "http://localhost", 30, &Sampling::default());
+        let clock = MockClock::at(1_792_000_000_000);
+        let trail = AuditTrail::new(path.clone(), fp, "test prompt")
+            .unwrap()
+            .with_clock(clock.clone());
+
+        trail.record("a", "SUSTAIN", 90, true, 0, None).unwrap();
+        clock.advance(40);
+        clock.set_wall(1_791_999_999_000);
+        trail.record("b", "SUSTAIN", 90, true, 0, None).unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        let records: Vec<DecisionRecord> = content
+            .lines()
+            .skip(1)
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(
+            (records[0].monotonic_ms, records[0].clock_behind_ms),
+            (0, None)
+        );
+        assert_eq!(records[1].timestamp_ms, 1_791_999_999_000);
+        assert_eq!(
+            (records[1].monotonic_ms, records[1].clock_behind_ms),
+            (40, Some(1_000))
+        );
+    }
+
+    #[test]
+    fn test_id_continuity_after_clean_shutdown() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let fp = ModelFingerprint::new("test-model", "http://localhost", 30, &Sampling::default());
+
+        let trail = AuditTrail::new(path.clone(), fp.clone(), "test prompt").unwrap();
+        trail.record("a", "SUSTAIN", 100, true, 0, None).unwrap();
+        trail.record("b", "SUSTAIN", 100, true, 0, None).unwrap();
+        trail
+            .record_shutdown("SIGTERM", true, &serde_json::json!({}))
+            .unwrap();
+        drop(trail);
+
+        let trail = AuditTrail::new(path.clone(), fp, "test prompt").unwrap();
+        assert!(trail.recovery().is_none());
+        assert_eq!(trail.record("c", "SUSTAIN", 100, true, 0, None).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_recovery_after_torn_line() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let fp = ModelFingerprint::new("test-model", "http://localhost", 30, &Sampling::default());
+
+        let trail = AuditTrail::new(path.clone(), fp.clone(), "test prompt").unwrap();
+        trail.record("a", "SUSTAIN", 100, true, 0, None).unwrap();
+        drop(trail);
+
+        // Simulate a crash halfway through record #2
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        write!(file, "{{\"id\":2,\"timestamp_ms\":17").unwrap();
+        drop(file);
+
+        let trail = AuditTrail::new(path.clone(), fp, "test prompt").unwrap();
+        let recovery = trail.recovery().unwrap().clone();
+        assert!(!recovery.clean_shutdown);
+        assert_eq!(recovery.previous_last_id, 1);
+        assert_eq!(recovery.lost_id, Some(2));
+        assert_eq!(recovery.resume_id, 3);
+        assert_eq!(trail.record("b", "KILL", 90, false, 0, None).unwrap(), 3);
+
+        // Every line after the torn fragment is intact JSON
+        let content = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert!(serde_json::from_str::<serde_json::Value>(lines[2]).is_err());
+        for line in &lines[3..] {
+            serde_json::from_str::<serde_json::Value>(line).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_shutdown_footer_signed() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+
+        let fp = ModelFingerprint::new("test-model", "http://localhost", 30, &Sampling::default());
+        let trail = AuditTrail::new(path.clone(), fp, "test prompt")
+            .unwrap()
+            .with_signing_key(b"secret".to_vec());
+
+        trail
+            .record("test log", "KILL", 90, false, 100, None)
+            .unwrap();
+        trail
+            .record_shutdown("SIGTERM", true, &serde_json::json!({"kills": 1}))
+            .unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        let last = content.lines().last().unwrap();
+        let mut footer: ShutdownFooter = serde_json::from_str(last).unwrap();
+        assert_eq!(footer.event, "shutdown");
+        assert_eq!(footer.last_id, 1);
+        assert!(footer.drained);
+        assert_eq!(footer.verify(Some(b"secret")), Some(true));
+        assert_eq!(footer.verify(Some(b"wrong")), Some(false));
+        assert_eq!(footer.verify(None), None);
+
+        // Signature verifies against the footer without its signature field
+        let signature = footer.signature.take().unwrap();
+        let payload = serde_json::to_string(&footer).unwrap();
+        assert_eq!(
+            signature,
+            format!("hmac-sha256:{}", hmac_sha256_hex(b"secret", &payload))
+        );
+    }
+
+    #[test]
+    fn test_selftest_read_back() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let fp = ModelFingerprint::new("test-model", "http://localhost", 30, &Sampling::default());
+        let trail = AuditTrail::new(path.clone(), fp, "test prompt").unwrap();
+
+        let check = SelfCheck {
+            name: "filters".to_string(),
+            ok: true,
+            detail: None,
+        };
+        trail
+            .record_selftest(&SelfTestEvent::new(vec![check.clone()]))
+            .unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        let event: SelfTestEvent = serde_json::from_str(content.lines().last().unwrap()).unwrap();
+        assert_eq!(event.event, "selftest");
+        assert_eq!(event.checks, [check]);
+
+        // Decision IDs are not consumed
+        assert_eq!(trail.record("x", "SUSTAIN", 90, true, 0, None).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_session_summary() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let fp = ModelFingerprint::new("test-model", "http://localhost", 30, &Sampling::default());
+        let trail = AuditTrail::new(path.clone(), fp, "test prompt").unwrap();
+
+        let decide = |connection_id, action, filtered, latency_ms| {
+            let ids = AgentIds {
+                agent_id: "trader-7".to_string(),
+                session_id: "run-1".to_string(),
+                connection_id,
+            };
+            trail
+                .record_entry(RecordInput {
+                    input_log: "line",
+                    action,
+                    filtered,
+                    latency_ms,
+                    agent: Some(&ids),
+                    ..Default::default()
+                })
+                .unwrap()
+        };
+        decide(1, "SUSTAIN", true, 12);
+        for ms in 1..=100 {
+            decide(1, "SUSTAIN", false, ms);
+        }
+        decide(2, "SUSTAIN", false, 7);
+        let last = decide(1, "KILL", false, 500);
+
+        let agent = AgentStatus {
+            id: 1,
+            peer: "127.0.0.1:5000".to_string(),
+            agent_id: "trader-7".to_string(),
+            session_id: "run-1".to_string(),
+            lines: 105,
+            ..Default::default()
+        };
+        let record = trail.get(last).unwrap();
+        assert_eq!(record.agent_id.as_deref(), Some("trader-7"));
+        assert_eq!(record.session_id.as_deref(), Some("run-1"));
+        assert_eq!(record.connection_id, Some(1));
+        trail.record_session(&agent, false).unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        let summary: SessionSummary = serde_json::from_str(lines[lines.len() - 1]).unwrap();
+        assert_eq!((summary.reason.as_str(), summary.lines), ("hourly", 105));
+        assert_eq!(
+            (summary.agent_id.as_str(), summary.session_id.as_str()),
+            ("trader-7", "run-1")
+        );
+        assert_eq!((summary.decisions, summary.filtered), (102, 1));
+        assert_eq!(summary.actions["SUSTAIN"], 101);
+        assert_eq!(summary.actions["KILL"], 1);
+        assert_eq!(
+            summary.latency_ms,
+            Some(LatencySummary {
+                min: 1,
+                avg: 54,
+                p99: 100
+            })
+        );
+        assert_eq!(summary.last_id, Some(last));
+        assert_eq!(summary.last_hash, Some(sha256_hex(lines[lines.len() - 2])));
+
+        // Totals run until the session closes
+        trail.record_session(&agent, true).unwrap();
+        trail.record_session(&agent, true).unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        let closed: Vec<SessionSummary> = content
+            .lines()
+            .rev()
+            .take(2)
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(
+            (closed[1].reason.as_str(), closed[1].decisions),
+            ("closed", 102)
+        );
+        assert_eq!(
+            (closed[0].decisions, closed[0].last_hash.as_deref()),
+            (0, None)
+        );
     }
 }
@@ -0,0 +1,466 @@
+//! Kill Authorization - A Second System Countersigns Every KILL
+//!
+//! Some deployments may not let one automated system stop an agent on its
+//! own word. With `[authorizer] url` set, a KILL that would signal the
+//! target is held while the kernel asks an external authorizer for a
+//! countersignature:
+//!
+//! - the challenge (decision ID, line, verdict, targets, a fresh
+//!   `challenge` nonce and the `deadline_ms` it expires at) is POSTed as
+//!   JSON, signed in `X-Tripwired-Signature: hmac-sha256=<hex>` over the body
+//! - the authorizer approves with `{"approved": true, "token": "<hex>"}`,
+//!   the token being HMAC-SHA256 of `approve:<challenge>` under the same
+//!   shared key; `approver` (optional) names who signed
+//!
+//! Only a valid token within `deadline_ms` executes the KILL. A refusal, a
+//! bad token, an error or the deadline passing fall back to PAUSE (the
+//! target is suspended for a human to decide). Every exchange - request,
+//! response, outcome - is audited as an `authorization` event linked by
+//! `decision_id`. Verdicts, the kill valve, dry runs and arming are
+//! unchanged: only the execution of a KILL waits.
+//!
+//! ```toml
+//! [authorizer]
+//! url = "https://risk.example.com/tripwired/authorize"
+//! key_env = "TRIPWIRED_AUTHORIZER_KEY"
+//! deadline_ms = 5000
+//! ```
+
+use crate::audit::{AuditTrail, AuthorizationEvent, DecisionRecord};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio_util::task::TaskTracker;
+use tracing::{error, info, warn};
+
+/// Longest authorizer response kept in the audit event (characters)
+const MAX_RESPONSE_CHARS: usize = 4096;
+
+/// `[authorizer]` table
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AuthorizerConfig {
+    /// Endpoint the challenge is POSTed to; unset = KILLs need no approval
+    pub url: Option<String>,
+    /// File holding the shared HMAC key
+    pub key_file: Option<PathBuf>,
+    /// Environment variable holding the shared HMAC key
+    pub key_env: Option<String>,
+    /// How long a KILL waits for its approval before PAUSE (milliseconds)
+    pub deadline_ms: u64,
+}
+
+impl Default for AuthorizerConfig {
+    fn default() -> Self {
+        Self {
+            url: None,
+            key_file: None,
+            key_env: None,
+            deadline_ms: 5000,
+        }
+    }
+}
+
+impl AuthorizerConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        let Some(ref url) = self.url else {
+            return Ok(());
+        };
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(format!("authorizer: {} is not an http(s) URL", url));
+        }
+        if self.key_file.is_some() == self.key_env.is_some() {
+            return Err("authorizer: set one of key_file or key_env".to_string());
+        }
+        if self.deadline_ms == 0 {
+            return Err("authorizer: deadline_ms must be at least 1".to_string());
+        }
+        Ok(())
+    }
+
+    /// The shared key, read from `key_file` or `key_env`
+    fn key(&self) -> Result<Vec<u8>, String> {
+        let key = match (&self.key_file, &self.key_env) {
+            (Some(path), _) => {
+                std::fs::read(path).map_err(|e| format!("authorizer: {}: {}", path.display(), e))?
+            }
+            (None, Some(var)) => std::env::var(var)
+                .map_err(|_| format!("authorizer: environment variable {} not set", var))?
+                .into_bytes(),
+            (None, None) => Vec::new(),
+        };
+        match key.trim_ascii() {
+            [] => Err("authorizer: the key is empty".to_string()),
+            key => Ok(key.to_vec()),
+        }
+    }
+}
+
+/// What the authorizer is asked to countersign (the request body)
+#[derive(Debug, Serialize)]
+pub struct Challenge {
+    /// Nonce the approval token is bound to
+    pub challenge: String,
+    pub decision_id: u64,
+    /// Unix ms the request was sent / the approval expires
+    pub issued_ms: u64,
+    pub deadline_ms: u64,
+    pub input_log: String,
+    pub input_hash: String,
+    pub confidence: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// What the KILL would stop
+    pub targets: Vec<String>,
+}
+
+/// The authorizer's answer
+#[derive(Debug, Deserialize)]
+struct Answer {
+    #[serde(default)]
+    approved: bool,
+    #[serde(default)]
+    token: Option<String>,
+    #[serde(default)]
+    approver: Option<String>,
+}
+
+/// Holds KILLs until an external authorizer countersigns them
+pub struct Authorizer {
+    url: String,
+    key: Vec<u8>,
+    deadline: Duration,
+    http: reqwest::Client,
+    audit: Arc<AuditTrail>,
+    /// Makes every challenge unique within the process
+    sequence: AtomicU64,
+    /// In-flight authorizations
+    tasks: TaskTracker,
+}
+
+impl Authorizer {
+    /// `None` without `url`; an error if the key cannot be read
+    pub fn new(
+        config: &AuthorizerConfig,
+        http: reqwest::Client,
+        audit: Arc<AuditTrail>,
+    ) -> Result<Option<Self>, String> {
+        let Some(ref url) = config.url else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            url: url.clone(),
+            key: config.key()?,
+            deadline: Duration::from_millis(config.deadline_ms),
+            http,
+            audit,
+            sequence: AtomicU64::new(0),
+            tasks: TaskTracker::new(),
+        }))
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Ask for approval of the KILL `record` against `targets`, audit the
+    /// exchange and call `execute` with whether it was approved in time
+    pub fn authorize(
+        &self,
+        record: &DecisionRecord,
+        targets: Vec<String>,
+        execute: impl FnOnce(bool) + Send + 'static,
+    ) {
+        let issued_ms = now_ms();
+        let sequence = self.sequence.fetch_add(1, Ordering::Relaxed);
+        let nonce = format!(
+            "{}:{}:{}:{}",
+            std::process::id(),
+            record.id,
+            issued_ms,
+            sequence
+        );
+        let challenge = Challenge {
+            challenge: hmac_hex(&self.key, nonce.as_bytes())[..32].to_string(),
+            decision_id: record.id,
+            issued_ms,
+            deadline_ms: issued_ms + self.deadline.as_millis() as u64,
+            input_log: record.input_log.clone(),
+            input_hash: record.input_hash.clone(),
+            confidence: record.confidence,
+            rule: record.rule.clone(),
+            reason: record.reason.clone(),
+            targets,
+        };
+        let body = serde_json::to_string(&challenge).expect("Challenge serializes");
+        let request = self
+            .http
+            .post(&self.url)
+            .header("Content-Type", "application/json")
+            .header(
+                "X-Tripwired-Signature",
+                format!("hmac-sha256={}", hmac_hex(&self.key, body.as_bytes())),
+            )
+            .body(body.clone());
+        let key = self.key.clone();
+        let deadline = self.deadline;
+        let url = self.url.clone();
+        let audit = Arc::clone(&self.audit);
+        info!(
+            "🖋️ [AUTHORIZE] ID:{} KILL held for approval ({}ms)",
+            record.id,
+            deadline.as_millis()
+        );
+        self.tasks.spawn(async move {
+            let start = Instant::now();
+            let exchange = async {
+                let response = request.send().await.map_err(|e| e.to_string())?;
+                let status = response.status();
+                let text = response.text().await.map_err(|e| e.to_string())?;
+                Ok::<_, String>((status, text))
+            };
+            let mut event =
+                AuthorizationEvent::new(challenge.decision_id, &url, &challenge.challenge);
+            event.request = body;
+            let result = match tokio::time::timeout(deadline, exchange).await {
+                Ok(Ok((status, text))) => {
+                    event.response =
+                        Some(crate::line::preview(&text, MAX_RESPONSE_CHARS).into_owned());
+                    match status.is_success() {
+                        true => verify(&key, &challenge.challenge, &text),
+                        false => Err(format!("HTTP {}", status)),
+                    }
+                }
+                Ok(Err(e)) => Err(e),
+                Err(_) => Err(format!("no approval within {}ms", deadline.as_millis())),
+            };
+            event.latency_ms = start.elapsed().as_millis() as u64;
+            let approved = match result {
+                Ok(approver) => {
+                    info!(
+                        "🖋️ [AUTHORIZE] ID:{} KILL approved{} in {}ms",
+                        challenge.decision_id,
+                        approver
+                            .as_deref()
+                            .map_or(String::new(), |a| format!(" by {}", a)),
+                        event.latency_ms
+                    );
+                    event.approver = approver;
+                    true
+                }
+                Err(e) => {
+                    warn!(
+                        "🖋️ [AUTHORIZE] ID:{} KILL not approved ({}) - falling back to PAUSE",
+                        challenge.decision_id, e
+                    );
+                    event.error = Some(e);
+                    false
+                }
+            };
+            event.approved = approved;
+            event.action = if approved { "KILL" } else { "PAUSE" }.to_string();
+            if let Err(e) = audit.record_authorization(&event) {
+                error!("Audit log write failed: {}", e);
+            }
+            execute(approved);
+        });
+    }
+
+    /// Wait (up to `timeout`) for KILLs still awaiting approval; returns
+    /// false on timeout
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        self.tasks.close();
+        tokio::time::timeout(timeout, self.tasks.wait())
+            .await
+            .is_ok()
+    }
+}
+
+/// The approver if `answer` approves `challenge` with a valid token
+fn verify(key: &[u8], challenge: &str, answer: &str) -> Result<Option<String>, String> {
+    let answer: Answer =
+        serde_json::from_str(answer).map_err(|e| format!("invalid response: {}", e))?;
+    if !answer.approved {
+        return Err(match answer.approver {
+            Some(approver) => format!("refused by {}", approver),
+            None => "refused".to_string(),
+        });
+    }
+    let token = answer
+        .token
+        .as_deref()
+        .and_then(from_hex)
+        .ok_or("approval without a token")?;
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(format!("approve:{}", challenge).as_bytes());
+    mac.verify_slice(&token)
+        .map_err(|_| "invalid approval token".to_string())?;
+    Ok(answer.approver)
+}
+
+fn hmac_hex(key: &[u8], input: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(input);
+    crate::checkpoint::to_hex(&mac.finalize().into_bytes())
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::ModelFingerprint;
+    use crate::llm::Sampling;
+    use axum::http::HeaderMap;
+
+    const KEY: &[u8] = b"countersign";
+
+    /// The approval token an authorizer returns for `challenge`
+    fn token(challenge: &str) -> String {
+        hmac_hex(KEY, format!("approve:{}", challenge).as_bytes())
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = |toml: &str| toml::from_str::<AuthorizerConfig>(toml).unwrap().validate();
+        assert!(config("").is_ok());
+        assert!(config(
+            "url = 'https://risk.example.com'
+key_env = 'KEY'"
+        )
+        .is_ok());
+        assert!(config("url = 'https://risk.example.com'").is_err());
+        assert!(config(
+            "url = 'https://risk.example.com'
+key_env = 'KEY'
+key_file = '/k'"
+        )
+        .is_err());
+        assert!(config(
+            "url = 'risk.example.com'
+key_env = 'KEY'"
+        )
+        .is_err());
+        assert!(config(
+            "url = 'https://risk.example.com'
+key_env = 'KEY'
+deadline_ms = 0"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_verify() {
+        let approval = |token: &str| {
+            serde_json::json!({"approved": true, "token": token, "approver": "risk-desk"})
+                .to_string()
+        };
+        assert_eq!(
+            verify(KEY, "c0ffee", &approval(&token("c0ffee"))),
+            Ok(Some("risk-desk".to_string()))
+        );
+        // Bound to the challenge and the key
+        assert!(verify(KEY, "c0ffee", &approval(&token("decade"))).is_err());
+        assert!(verify(b"other", "c0ffee", &approval(&token("c0ffee"))).is_err());
+        assert!(verify(KEY, "c0ffee", &approval("zz")).is_err());
+        assert!(verify(KEY, "c0ffee", r#"{"approved": true}"#).is_err());
+        assert_eq!(
+            verify(KEY, "c0ffee", r#"{"approved": false, "approver": "alice"}"#),
+            Err("refused by alice".to_string())
+        );
+        assert!(verify(KEY, "c0ffee", "<html>").is_err());
+    }
+
+    /// An authorizer approving every correctly signed challenge
+    async fn approve(headers: HeaderMap, body: String) -> String {
+        let signature = headers["x-tripwired-signature"].to_str().unwrap();
+        assert_eq!(
+            signature,
+            format!("hmac-sha256={}", hmac_hex(KEY, body.as_bytes()))
+        );
+        let challenge: serde_json::Value = serde_json::from_str(&body).unwrap();
+        let token = token(challenge["challenge"].as_str().unwrap());
+        serde_json::json!({"approved": true, "token": token}).to_string()
+    }
+
+    #[tokio::test]
+    async fn test_authorize() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let approving = listener.local_addr().unwrap();
+        let router = axum::Router::new().route("/", axum::routing::post(approve));
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        // Accepts connections, never answers
+        let silent = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("key"), b"countersign\n").unwrap();
+        let fp = ModelFingerprint::new("test-model", "http://localhost", 30, &Sampling::default());
+        let audit_path = dir.path().join("audit.jsonl");
+        let audit = Arc::new(AuditTrail::new(audit_path.clone(), fp, "test prompt").unwrap());
+        let id = audit
+            .record("rm -rf /srv", "KILL", 97, true, 0, None)
+            .unwrap();
+        let record = audit.get(id).unwrap();
+
+        let mut outcomes = Vec::new();
+        for addr in [approving, silent.local_addr().unwrap()] {
+            let config: AuthorizerConfig = toml::from_str(&format!(
+                "url = 'http://{}/'\nkey_file = '{}'\ndeadline_ms = 500",
+                addr,
+                dir.path().join("key").display()
+            ))
+            .unwrap();
+            let authorizer = Authorizer::new(&config, reqwest::Client::new(), Arc::clone(&audit))
+                .unwrap()
+                .unwrap();
+            let (tx, rx) = tokio::sync::oneshot::channel();
+            authorizer.authorize(&record, vec!["pid 42".to_string()], move |approved| {
+                let _ = tx.send(approved);
+            });
+            outcomes.push(rx.await.unwrap());
+            assert!(authorizer.drain(Duration::from_secs(1)).await);
+        }
+        assert_eq!(outcomes, [true, false]);
+
+        audit.flush().unwrap();
+        let events: Vec<AuthorizationEvent> = std::fs::read_to_string(&audit_path)
+            .unwrap()
+            .lines()
+            .filter(|l| l.contains(r#""event":"authorization""#))
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().all(|e| e.decision_id == id));
+        assert_eq!(
+            (events[0].approved, events[0].action.as_str()),
+            (true, "KILL")
+        );
+        assert!(events[0].request.contains(r#""targets":["pid 42"]"#));
+        assert_eq!(
+            (events[1].approved, events[1].action.as_str()),
+            (false, "PAUSE")
+        );
+        assert_eq!(events[1].error.as_deref(), Some("no approval within 500ms"));
+    }
+}
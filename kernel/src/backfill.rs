@@ -0,0 +1,222 @@
+//! Backfill - Retrospective Verdicts for the LLM's Blind Window
+//!
+//! While the LLM is down, escalated lines follow the degraded-mode policy
+//! and nobody learns what the model would have said. With a spill file,
+//! those lines are appended (already redacted) to a JSONL file that
+//! survives restarts. Once the canary probe passes again they are
+//! re-analyzed and audited with `backfilled: true` and `backfill_of` (the
+//! degraded decision's ID). Backfilled verdicts are never executed; one
+//! that would have been a KILL is logged and notified as a `kill` event.
+//!
+//! Recovery is detected by the canary probe, so `[health] interval_ms`
+//! must not be 0. Channels sharing the main config get the channel name
+//! appended to the spill file name.
+//!
+//! ```toml
+//! [backfill]
+//! spill_file = "tripwired-spill.jsonl"
+//! max_lines = 10000
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::warn;
+
+/// `[backfill]` table
+#[derive(Debug, Clone, Deserialize)]
+pub struct BackfillConfig {
+    /// JSONL file lines are spilled to (backfill disabled if unset)
+    #[serde(default)]
+    pub spill_file: Option<PathBuf>,
+    /// Lines kept at most; later ones are not spilled
+    #[serde(default = "default_max_lines")]
+    pub max_lines: usize,
+}
+
+fn default_max_lines() -> usize {
+    10_000
+}
+
+impl Default for BackfillConfig {
+    fn default() -> Self {
+        Self {
+            spill_file: None,
+            max_lines: default_max_lines(),
+        }
+    }
+}
+
+impl BackfillConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.spill_file.is_some() && self.max_lines == 0 {
+            return Err("backfill: max_lines must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// `tripwired-spill.jsonl` -> `tripwired-spill-<channel>.jsonl`
+pub fn channel_path(path: &Path, channel: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{}-{}.{}", stem, channel, ext.to_string_lossy()),
+        None => format!("{}-{}", stem, channel),
+    };
+    path.with_file_name(name)
+}
+
+/// One line awaiting a retrospective verdict
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpillEntry {
+    /// ID of the degraded-mode decision
+    pub record_id: u64,
+    /// Redacted input, as audited
+    pub input_log: String,
+    /// `input_hash` of the degraded decision
+    pub input_hash: String,
+    /// Redacted prompt rendering of the input
+    pub prompt_log: String,
+    /// Redacted agent history sent with the prompt
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub context: String,
+    pub rule: String,
+}
+
+struct SpillState {
+    file: File,
+    lines: usize,
+    /// The full-file warning was logged since the last take
+    warned: bool,
+}
+
+/// Append-only spill file
+pub struct Spill {
+    path: PathBuf,
+    max_lines: usize,
+    state: Mutex<SpillState>,
+}
+
+impl Spill {
+    /// Open (or create) the spill file; entries left by a previous run are kept
+    pub fn open(path: &Path, max_lines: usize) -> std::io::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)?;
+        let lines = BufReader::new(&file).lines().count();
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_lines,
+            state: Mutex::new(SpillState {
+                file,
+                lines,
+                warned: false,
+            }),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Entries awaiting backfill
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().lines
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Append an entry; returns false when the file is full
+    pub fn push(&self, entry: &SpillEntry) -> std::io::Result<bool> {
+        let mut state = self.state.lock().unwrap();
+        if state.lines >= self.max_lines {
+            if !state.warned {
+                state.warned = true;
+                warn!(
+                    "🕰️ Spill file {} full ({} lines) - later lines will not be backfilled",
+                    self.path.display(),
+                    self.max_lines
+                );
+            }
+            return Ok(false);
+        }
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        state.file.write_all(line.as_bytes())?;
+        state.lines += 1;
+        Ok(true)
+    }
+
+    /// Every entry, oldest first; empties the file
+    pub fn take(&self) -> std::io::Result<Vec<SpillEntry>> {
+        let mut state = self.state.lock().unwrap();
+        state.file.rewind()?;
+        let mut entries = Vec::with_capacity(state.lines);
+        for line in BufReader::new(&state.file).lines() {
+            let line = line?;
+            match serde_json::from_str(&line) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => warn!("🕰️ Skipping corrupt spill line: {}", e),
+            }
+        }
+        state.file.set_len(0)?;
+        state.lines = 0;
+        state.warned = false;
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(record_id: u64) -> SpillEntry {
+        SpillEntry {
+            record_id,
+            input_log: format!("withdraw {} BTC", record_id),
+            input_hash: "ab".to_string(),
+            prompt_log: format!("withdraw {} BTC", record_id),
+            context: String::new(),
+            rule: "trading#1".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_spill_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("spill.jsonl");
+
+        let spill = Spill::open(&path, 2).unwrap();
+        assert!(spill.push(&entry(1)).unwrap());
+        assert!(spill.push(&entry(2)).unwrap());
+        assert!(!spill.push(&entry(3)).unwrap());
+        drop(spill);
+
+        // Survives a restart, and is empty once taken
+        let spill = Spill::open(&path, 2).unwrap();
+        assert_eq!(spill.len(), 2);
+        assert_eq!(spill.take().unwrap(), vec![entry(1), entry(2)]);
+        assert!(spill.is_empty());
+        assert!(spill.push(&entry(4)).unwrap());
+        assert_eq!(spill.take().unwrap(), vec![entry(4)]);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+    }
+
+    #[test]
+    fn test_channel_path() {
+        assert_eq!(
+            channel_path(Path::new("/var/lib/tw/spill.jsonl"), "ops"),
+            PathBuf::from("/var/lib/tw/spill-ops.jsonl")
+        );
+        assert_eq!(
+            channel_path(Path::new("spill"), "ops"),
+            PathBuf::from("spill-ops")
+        );
+    }
+}
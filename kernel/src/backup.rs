@@ -0,0 +1,288 @@
+//! Backup Audit Sink - A Second Copy of the Trail
+//!
+//! With `--audit-backup <path>` every byte written to the audit trail is
+//! also written to a second file, typically on another disk, a network
+//! share or a mounted bucket, so losing or tampering with one copy does
+//! not destroy the evidence: the backup verifies on its own, and a
+//! difference between the two copies shows which one was touched.
+//!
+//! The copies fail independently. A primary write error is reported as
+//! before, but the backup still gets the line. A backup error never fails
+//! the primary: the kernel logs it once, counts the bytes the backup
+//! missed and reopens the backup on the next write. From the first missed
+//! byte until the next rotation the copies have *diverged*, shown in
+//! `/stats` (`audit_backup`) and `/metrics`
+//! (`tripwired_audit_backup_diverged`,
+//! `tripwired_audit_backup_missed_bytes_total`).
+//!
+//! Rotation rotates the backup too: it is renamed to the segment's file
+//! name in its own directory, and a fresh backup starts in sync.
+
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+/// Backup sink health, for `/stats` and `/metrics`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BackupStatus {
+    pub path: PathBuf,
+    /// Bytes written to the backup
+    pub bytes: u64,
+    /// Bytes the backup missed (written to the primary only)
+    pub missed_bytes: u64,
+    /// Failed backup writes, opens and renames
+    pub failures: u64,
+    /// The current backup file is missing bytes of the current segment
+    pub diverged: bool,
+}
+
+/// Counters shared with the stats snapshot, so it never waits on a write
+#[derive(Debug, Default)]
+struct Counters {
+    bytes: AtomicU64,
+    missed_bytes: AtomicU64,
+    failures: AtomicU64,
+    diverged: AtomicBool,
+}
+
+/// The second copy of the trail
+pub struct Backup {
+    path: PathBuf,
+    /// Closed after a failure, reopened on the next write
+    writer: Option<BufWriter<File>>,
+    counters: Arc<Counters>,
+}
+
+impl Backup {
+    /// Open (or create) the backup for appending
+    pub fn open(path: PathBuf) -> io::Result<Self> {
+        let writer = Some(BufWriter::new(open(&path)?));
+        Ok(Self {
+            path,
+            writer,
+            counters: Arc::default(),
+        })
+    }
+
+    /// Handle for reading the sink's health
+    pub fn monitor(&self) -> BackupMonitor {
+        BackupMonitor {
+            path: self.path.clone(),
+            counters: self.counters.clone(),
+        }
+    }
+
+    /// Write `buf` to the backup, recording a miss instead of failing
+    fn mirror(&mut self, buf: &[u8]) {
+        if self.writer.is_none() {
+            match open(&self.path) {
+                Ok(file) => self.writer = Some(BufWriter::new(file)),
+                Err(_) => return self.missed(buf.len()),
+            }
+        }
+        let writer = self.writer.as_mut().unwrap();
+        match writer.write_all(buf) {
+            Ok(()) => {
+                self.counters
+                    .bytes
+                    .fetch_add(buf.len() as u64, Ordering::Relaxed);
+            }
+            Err(e) => self.fail(&e, buf.len()),
+        }
+    }
+
+    /// Close the backup after an error; `lost` bytes never reached it
+    fn fail(&mut self, error: &io::Error, lost: usize) {
+        if !self.counters.diverged.load(Ordering::Relaxed) {
+            warn!(
+                "⚠️ Audit backup {} failed ({}) - continuing with the primary only",
+                self.path.display(),
+                error
+            );
+        }
+        self.counters.failures.fetch_add(1, Ordering::Relaxed);
+        self.writer = None;
+        self.missed(lost);
+    }
+
+    fn missed(&self, bytes: usize) {
+        self.counters
+            .missed_bytes
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+        self.counters.diverged.store(true, Ordering::Relaxed);
+    }
+
+    /// Rename the backup after the primary segment and start a fresh one
+    fn rotate(&mut self, segment: &Path) {
+        let _ = self.flush();
+        self.writer = None;
+        let renamed = match segment.file_name() {
+            Some(name) => self.path.with_file_name(name),
+            None => return,
+        };
+        if let Err(e) = std::fs::rename(&self.path, &renamed) {
+            warn!("⚠️ Audit backup {} not rotated: {}", self.path.display(), e);
+            self.counters.failures.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        if self.counters.diverged.swap(false, Ordering::Relaxed) {
+            info!(
+                "Audit backup segment {} is incomplete; the new backup starts in sync",
+                renamed.display()
+            );
+        }
+    }
+}
+
+/// Never fails: errors are counted as misses instead
+impl Write for Backup {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.mirror(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let Some(writer) = self.writer.as_mut() else {
+            return Ok(());
+        };
+        let pending = writer.buffer().len();
+        if let Err(e) = writer.flush() {
+            // Counted when buffered, but they never got out
+            self.counters
+                .bytes
+                .fetch_sub(pending as u64, Ordering::Relaxed);
+            self.fail(&e, pending);
+        }
+        Ok(())
+    }
+}
+
+/// Reads a backup sink's health without touching the writer
+#[derive(Debug, Clone)]
+pub struct BackupMonitor {
+    path: PathBuf,
+    counters: Arc<Counters>,
+}
+
+impl BackupMonitor {
+    pub fn status(&self) -> BackupStatus {
+        BackupStatus {
+            path: self.path.clone(),
+            bytes: self.counters.bytes.load(Ordering::Relaxed),
+            missed_bytes: self.counters.missed_bytes.load(Ordering::Relaxed),
+            failures: self.counters.failures.load(Ordering::Relaxed),
+            diverged: self.counters.diverged.load(Ordering::Relaxed),
+        }
+    }
+}
+
+fn open(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// The audit file, plus its backup if configured
+///
+/// Bytes accepted by the primary's buffer are mirrored at once; bytes the
+/// primary rejects are mirrored too, since the caller gives up on them.
+pub struct Mirrored {
+    primary: BufWriter<File>,
+    backup: Option<Backup>,
+}
+
+impl Mirrored {
+    pub fn new(primary: File) -> Self {
+        Self {
+            primary: BufWriter::new(primary),
+            backup: None,
+        }
+    }
+
+    pub fn set_backup(&mut self, backup: Backup) {
+        self.backup = Some(backup);
+    }
+
+    /// Continue in a new primary file (the backup is untouched)
+    #[cfg(windows)]
+    pub fn reopen(&mut self, primary: File) {
+        self.primary = BufWriter::new(primary);
+    }
+
+    /// Continue in a new primary file after `segment` was rotated out
+    pub fn rotate(&mut self, primary: File, segment: &Path) {
+        self.primary = BufWriter::new(primary);
+        if let Some(ref mut backup) = self.backup {
+            backup.rotate(segment);
+        }
+    }
+
+    /// Sync the primary file's data to disk
+    pub fn sync_data(&self) -> io::Result<()> {
+        self.primary.get_ref().sync_data()
+    }
+}
+
+impl Write for Mirrored {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let result = self.primary.write(buf);
+        if let Some(ref mut backup) = self.backup {
+            match result {
+                Ok(n) => backup.mirror(&buf[..n]),
+                Err(_) => backup.mirror(buf),
+            }
+        }
+        result
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if let Some(ref mut backup) = self.backup {
+            backup.flush()?;
+        }
+        self.primary.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_mirrored_copies() {
+        let dir = tempdir().unwrap();
+        let primary = dir.path().join("audit.jsonl");
+        let backup_path = dir.path().join("backup.jsonl");
+
+        // A full disk: the primary carries on and the miss is counted
+        let mut writer = Mirrored::new(open(&primary).unwrap());
+        let backup = Backup::open(PathBuf::from("/dev/full")).unwrap();
+        let monitor = backup.monitor();
+        writer.set_backup(backup);
+        writeln!(writer, "one").unwrap();
+        writer.flush().unwrap();
+        let status = monitor.status();
+        assert!(status.diverged);
+        assert_eq!((status.missed_bytes, status.failures), (4, 1));
+
+        // Room again: reopened on the next write
+        writer.backup.as_mut().unwrap().path = backup_path.clone();
+        writeln!(writer, "two").unwrap();
+        writer.flush().unwrap();
+        assert_eq!(std::fs::read_to_string(&primary).unwrap(), "one\ntwo\n");
+        assert_eq!(std::fs::read_to_string(&backup_path).unwrap(), "two\n");
+        assert_eq!(monitor.status().bytes, 4);
+
+        // Rotation renames the backup and the copies are in sync again
+        writer.rotate(open(&primary).unwrap(), Path::new("audit-1.jsonl"));
+        writeln!(writer, "three").unwrap();
+        writer.flush().unwrap();
+        let rotated = backup_path.with_file_name("audit-1.jsonl");
+        assert_eq!(std::fs::read_to_string(rotated).unwrap(), "two\n");
+        assert_eq!(std::fs::read_to_string(&backup_path).unwrap(), "three\n");
+        assert!(!monitor.status().diverged);
+    }
+}
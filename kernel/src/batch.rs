@@ -0,0 +1,228 @@
+//! Batch Analysis - Several Lines per LLM Request Under Load
+//!
+//! Burst traffic escalates lines faster than the model answers them. Once
+//! `threshold` analyses are already pending, further lines are queued and
+//! sent together (up to `max_lines`, waiting at most `max_wait_ms` for the
+//! batch to fill) in one prompt asking for a verdict per line number.
+//!
+//! Every line still gets its own audit record (with `batch_size`). Lines
+//! the model skipped, or all lines of a failed batch, are re-analyzed
+//! individually. `priority = "high"` lines and tool calls (judged with
+//! their own template, see `llm`) are never queued.
+//!
+//! ```toml
+//! [batch]
+//! threshold = 4
+//! max_lines = 8
+//! max_wait_ms = 10
+//! ```
+
+use crate::chain::{Answer, LlmChain};
+use crate::filter::Priority;
+use crate::llm::LlmError;
+use serde::Deserialize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, warn};
+
+/// `[batch]` table
+#[derive(Debug, Clone, Deserialize)]
+pub struct BatchConfig {
+    /// Pending analyses before lines are batched; 0 disables batching
+    #[serde(default)]
+    pub threshold: usize,
+    /// Most lines in one request
+    #[serde(default = "default_max_lines")]
+    pub max_lines: usize,
+    /// Longest wait for a batch to fill (milliseconds)
+    #[serde(default = "default_max_wait_ms")]
+    pub max_wait_ms: u64,
+}
+
+fn default_max_lines() -> usize {
+    8
+}
+
+fn default_max_wait_ms() -> u64 {
+    10
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            threshold: 0,
+            max_lines: default_max_lines(),
+            max_wait_ms: default_max_wait_ms(),
+        }
+    }
+}
+
+impl BatchConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.threshold > 0 && self.max_lines < 2 {
+            return Err("batch: max_lines must be at least 2".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// A queued line and where its answer goes
+struct Job {
+    log: String,
+    context: String,
+    reply: oneshot::Sender<Result<Answer, LlmError>>,
+}
+
+/// Routes analyses straight to the chain, or through the batch queue under load
+pub struct Batcher {
+    llm: Arc<LlmChain>,
+    threshold: usize,
+    pending: AtomicUsize,
+    queue: Option<mpsc::UnboundedSender<Job>>,
+}
+
+impl Batcher {
+    /// Spawns the batch collector when batching is enabled
+    pub fn new(config: &BatchConfig, llm: Arc<LlmChain>) -> Self {
+        let queue = (config.threshold > 0).then(|| {
+            let (tx, rx) = mpsc::unbounded_channel();
+            tokio::spawn(collect(
+                rx,
+                Arc::clone(&llm),
+                config.max_lines,
+                Duration::from_millis(config.max_wait_ms),
+            ));
+            tx
+        });
+        Self {
+            llm,
+            threshold: config.threshold,
+            pending: AtomicUsize::new(0),
+            queue,
+        }
+    }
+
+    /// Analyses waiting on the LLM (the queue depth batching keys off)
+    pub fn pending(&self) -> usize {
+        self.pending.load(Ordering::Relaxed)
+    }
+
+    /// High-priority lines skip the queue, whatever its depth
+    pub async fn analyze(
+        &self,
+        log: &str,
+        context: &str,
+        priority: Priority,
+    ) -> Result<Answer, LlmError> {
+        let depth = self.pending.fetch_add(1, Ordering::Relaxed);
+        let _pending = PendingGuard(&self.pending);
+
+        match &self.queue {
+            Some(queue)
+                if depth >= self.threshold
+                    && priority == Priority::Normal
+                    && !crate::parse::is_tool_call(log) =>
+            {
+                let (reply, answer) = oneshot::channel();
+                queue
+                    .send(Job {
+                        log: log.to_string(),
+                        context: context.to_string(),
+                        reply,
+                    })
+                    .map_err(|_| LlmError::Batch("queue closed"))?;
+                answer.await.map_err(|_| LlmError::Batch("dropped"))?
+            }
+            _ => self.llm.analyze(log, context).await,
+        }
+    }
+}
+
+struct PendingGuard<'a>(&'a AtomicUsize);
+
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Gather queued jobs into batches; each batch runs as its own task
+async fn collect(
+    mut rx: mpsc::UnboundedReceiver<Job>,
+    llm: Arc<LlmChain>,
+    max_lines: usize,
+    max_wait: Duration,
+) {
+    while let Some(first) = rx.recv().await {
+        let mut jobs = vec![first];
+        let deadline = tokio::time::Instant::now() + max_wait;
+        while jobs.len() < max_lines {
+            match tokio::time::timeout_at(deadline, rx.recv()).await {
+                Ok(Some(job)) => jobs.push(job),
+                _ => break,
+            }
+        }
+        tokio::spawn(run(Arc::clone(&llm), jobs));
+    }
+}
+
+async fn run(llm: Arc<LlmChain>, jobs: Vec<Job>) {
+    let n = jobs.len();
+    let answers = if n == 1 {
+        vec![None]
+    } else {
+        let items: Vec<(&str, &str)> = jobs
+            .iter()
+            .map(|j| (j.log.as_str(), j.context.as_str()))
+            .collect();
+        match llm.analyze_batch(&items).await {
+            Ok(answers) => {
+                let missing = answers.iter().filter(|a| a.is_none()).count();
+                debug!("📦 Batch of {} lines ({} unanswered)", n, missing);
+                answers
+            }
+            Err(e) => {
+                warn!(
+                    "⚠️ Batch of {} lines failed: {} - analyzing individually",
+                    n, e
+                );
+                (0..n).map(|_| None).collect()
+            }
+        }
+    };
+
+    for (job, answer) in jobs.into_iter().zip(answers) {
+        match answer {
+            Some(answer) => {
+                let _ = job.reply.send(Ok(answer));
+            }
+            None => {
+                let llm = Arc::clone(&llm);
+                tokio::spawn(async move {
+                    let answer = llm.analyze(&job.log, &job.context).await;
+                    let _ = job.reply.send(answer);
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config() {
+        let config: BatchConfig = toml::from_str("threshold = 4").unwrap();
+        assert_eq!(config.max_lines, 8);
+        assert!(config.validate().is_ok());
+
+        let config: BatchConfig = toml::from_str("threshold = 4\nmax_lines = 1").unwrap();
+        assert!(config.validate().is_err());
+        // Disabled batching ignores the other settings
+        let config: BatchConfig = toml::from_str("max_lines = 1").unwrap();
+        assert!(config.validate().is_ok());
+    }
+}
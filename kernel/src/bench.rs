@@ -0,0 +1,472 @@
+//! Built-in Benchmarks - Throughput on the Deployment Host
+//!
+//! `tripwired bench-filter` compares the plain RegexSet against the
+//! Aho-Corasick pre-screen + RegexSet path on the same corpus.
+//!
+//! `tripwired bench` times each stage a line goes through on this machine
+//! (filter per preset and with the configured rules, audit writes at the
+//! chosen durability, verdict parsing) and turns them into a capacity
+//! estimate for one connection:
+//!
+//! ```text
+//! Host benchmark (8 cores, 200000 lines, 5% suspicious)
+//!   Filter: trading           3.21M lines/s  (0.312μs/line)
+//!   Filter: devops            4.05M lines/s  (0.247μs/line)
+//!   Filter: generic           4.40M lines/s  (0.227μs/line)
+//!   Filter: configured        3.19M lines/s  (0.313μs/line)
+//!   Audit writes (buffered)   0.21M records/s  (4.762μs/record)
+//!   Verdict parsing           1.10M verdicts/s  (0.909μs/verdict)
+//!   Capacity: ~1.80M lines/s per connection (LLM time not included)
+//! ```
+//!
+//! The estimate charges every line the configured filter and every
+//! suspicious line an audit record and a parsed verdict; the LLM's own
+//! latency bounds escalations long before that and is not measured.
+//!
+//! The same stages (`cases`) make up the Criterion suite, for statistics
+//! and regression tracking across commits: `cargo bench --bench pipeline`.
+
+use crate::audit::{AuditTrail, Durability, ModelFingerprint, RecordInput};
+use crate::error::KernelError;
+use crate::filter::{Filter, FilterConfig};
+use crate::llm::{LlmClient, Sampling};
+use crate::preset;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Benign log templates (no filter keywords)
+const BENIGN: &[&str] = &[
+    "User {n} logged in successfully",
+    "GET /api/v1/items/{n} 200 {n}ms",
+    "Cache hit ratio 0.{n}",
+    "Session {n} initialized",
+    "Balance updated to {n} USDT",
+    "Heartbeat ok seq={n}",
+    "Config reloaded from /etc/app/config.yaml",
+    "Worker {n} idle, queue depth {n}",
+];
+
+/// Suspicious log templates
+const SUSPICIOUS: &[&str] = &[
+    "Order #{n} placed within 1ms",
+    "sudo rm -rf /var/lib/app-{n}",
+    "ERROR: connection {n} failed",
+    "DROP TABLE users_{n}",
+];
+
+/// Seed of the synthetic corpora
+pub const SEED: u64 = 0x5eed;
+
+/// Pseudo-random numbers, reproducible across runs (Knuth's MMIX LCG)
+pub fn lcg(mut seed: u64) -> impl FnMut() -> u64 {
+    move || {
+        seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        seed >> 33
+    }
+}
+
+/// Generate a deterministic mixed corpus
+pub fn synthetic_corpus(lines: usize, suspicious_pct: u32) -> Vec<String> {
+    let mut next = lcg(SEED);
+
+    (0..lines)
+        .map(|_| {
+            let pick = next();
+            let templates = if (pick % 100) < suspicious_pct as u64 {
+                SUSPICIOUS
+            } else {
+                BENIGN
+            };
+            let template = templates[(next() as usize) % templates.len()];
+            template.replace("{n}", &(next() % 10_000).to_string())
+        })
+        .collect()
+}
+
+/// Time one pass of `filter` over the corpus
+fn run(filter: &Filter, corpus: &[String]) -> (Duration, usize) {
+    let start = Instant::now();
+    let hits = corpus
+        .iter()
+        .filter(|l| std::hint::black_box(filter.is_suspicious(l)))
+        .count();
+    (start.elapsed(), hits)
+}
+
+fn report(label: &str, elapsed: Duration, lines: usize) {
+    let per_line_ns = elapsed.as_nanos() as f64 / lines as f64;
+    println!(
+        "  {:<24} {:>8.2}M lines/s  ({:.3}μs/line)",
+        label,
+        lines as f64 / elapsed.as_secs_f64() / 1e6,
+        per_line_ns / 1000.0
+    );
+}
+
+/// `tripwired bench-filter`
+pub fn bench_filter(
+    config: &FilterConfig,
+    input: Option<&Path>,
+    lines: usize,
+    suspicious_pct: u32,
+) -> Result<(), KernelError> {
+    let corpus = match input {
+        Some(path) => std::fs::read_to_string(path)?
+            .lines()
+            .map(str::to_string)
+            .collect(),
+        None => synthetic_corpus(lines, suspicious_pct),
+    };
+    if corpus.is_empty() {
+        println!("No input lines");
+        return Ok(());
+    }
+
+    let fast = Filter::new(config)?;
+    let slow = Filter::new(config)?.without_prescreen();
+
+    // Warm-up pass (page faults, lazy DFA states)
+    run(&fast, &corpus);
+    run(&slow, &corpus);
+
+    let (slow_time, slow_hits) = run(&slow, &corpus);
+    let (fast_time, fast_hits) = run(&fast, &corpus);
+
+    println!("Filter benchmark ({} lines)", corpus.len());
+    match input {
+        Some(path) => println!("  Corpus: {}", path.display()),
+        None => println!("  Corpus: synthetic, {}% suspicious", suspicious_pct),
+    }
+    match fast.prescreen_literals() {
+        Some(n) => println!("  Pre-screen: {} literals", n),
+        None => println!("  Pre-screen: DISABLED (a pattern has no required literal)"),
+    }
+    report("RegexSet only", slow_time, corpus.len());
+    report("Aho-Corasick + RegexSet", fast_time, corpus.len());
+    println!(
+        "  Speedup: {:.1}x",
+        slow_time.as_secs_f64() / fast_time.as_secs_f64()
+    );
+    println!(
+        "  Suspicious: {} ({:.1}%)",
+        fast_hits,
+        fast_hits as f64 * 100.0 / corpus.len() as f64
+    );
+
+    // Both paths must agree - anything else is a pre-screen bug
+    if fast_hits != slow_hits {
+        return Err(format!(
+            "pre-screen mismatch: {} vs {} suspicious lines",
+            fast_hits, slow_hits
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Verdicts as models return them: structured, fenced and free text
+const VERDICTS: &[(&str, bool)] = &[
+    (
+        r#"{"action":"SUSTAIN","confidence":92,"reason":"routine balance update"}"#,
+        true,
+    ),
+    (
+        r#"{"action":"KILL","confidence":97,"reason":"recursive delete of the ledger"}"#,
+        true,
+    ),
+    (
+        "```json\n{\"action\":\"SUSTAIN\",\"confidence\":80}\n```",
+        false,
+    ),
+    (
+        r#"The order rate looks automated. "action": "KILL" with high confidence."#,
+        false,
+    ),
+];
+
+/// `tripwired bench` options
+#[derive(Debug, Clone)]
+pub struct HostOptions {
+    pub lines: usize,
+    pub suspicious_pct: u32,
+    /// Audit records written
+    pub records: usize,
+    pub durability: Durability,
+}
+
+/// One stage of the pipeline, timed over any number of items: by
+/// `tripwired bench` and by the Criterion suite (`benches/pipeline.rs`)
+pub struct Case {
+    /// `filter`, `audit` or `verdict`
+    pub group: &'static str,
+    /// Preset name or `configured`, durability, `parse`
+    pub name: String,
+    /// Items one `tripwired bench` pass covers
+    pub items: usize,
+    /// Run the stage on `n` items and return the time taken
+    pub time: Box<dyn FnMut(u64) -> Result<Duration, KernelError>>,
+}
+
+/// The stages of `tripwired bench`, in report order: the filter per preset
+/// and with `config`, audit writes to a scratch trail at `scratch`
+/// (recreated for every run, removed after it), verdict parsing
+pub fn cases(
+    config: &FilterConfig,
+    options: &HostOptions,
+    scratch: &Path,
+) -> Result<Vec<Case>, KernelError> {
+    let corpus = Rc::new(synthetic_corpus(
+        options.lines.max(1),
+        options.suspicious_pct,
+    ));
+    let mut cases = Vec::new();
+    let presets = preset::builtin().iter().map(|p| {
+        let config = FilterConfig {
+            domain: Some(p.name.clone()),
+            ..FilterConfig::default()
+        };
+        (p.name.clone(), config)
+    });
+    for (name, config) in presets.chain([("configured".to_string(), config.clone())]) {
+        let filter = Filter::new(&config)?;
+        let corpus = Rc::clone(&corpus);
+        cases.push(Case {
+            group: "filter",
+            name,
+            items: corpus.len(),
+            time: Box::new(move |n| {
+                Ok(timed(n, |i| {
+                    std::hint::black_box(filter.is_suspicious(&corpus[i % corpus.len()]));
+                }))
+            }),
+        });
+    }
+
+    let (path, durability) = (scratch.to_path_buf(), options.durability);
+    cases.push(Case {
+        group: "audit",
+        name: format!("{:?}", durability).to_lowercase(),
+        items: options.records,
+        time: Box::new(move |n| {
+            let _ = std::fs::remove_file(&path);
+            let elapsed = write_records(&path, durability, n);
+            let _ = std::fs::remove_file(&path);
+            elapsed
+        }),
+    });
+
+    let client = LlmClient::new("http://127.0.0.1:1/v1", "bench", 64);
+    cases.push(Case {
+        group: "verdict",
+        name: "parse".to_string(),
+        items: options.records,
+        time: Box::new(move |n| {
+            Ok(timed(n, |i| {
+                let (content, structured) = VERDICTS[i % VERDICTS.len()];
+                std::hint::black_box(client.parse_decision(content, structured));
+            }))
+        }),
+    });
+    Ok(cases)
+}
+
+/// Per-item cost of each stage
+#[derive(Debug)]
+pub struct HostReport {
+    /// Per preset, then `configured`
+    pub filters: Vec<(String, Duration)>,
+    pub audit: Duration,
+    pub parse: Duration,
+    /// Share of lines the configured filter escalated
+    pub suspicious: f64,
+}
+
+impl HostReport {
+    /// Lines per second one connection sustains, LLM time aside
+    pub fn capacity(&self) -> f64 {
+        let filter = self.filters.last().map_or(Duration::ZERO, |(_, d)| *d);
+        let per_line =
+            filter.as_secs_f64() + self.suspicious * (self.audit + self.parse).as_secs_f64();
+        1.0 / per_line.max(1e-12)
+    }
+}
+
+/// Time `n` runs of `f`
+fn timed(n: u64, mut f: impl FnMut(usize)) -> Duration {
+    let start = Instant::now();
+    for i in 0..n {
+        f(i as usize);
+    }
+    start.elapsed()
+}
+
+/// Time each stage of the pipeline on this host
+pub fn measure(config: &FilterConfig, options: &HostOptions) -> Result<HostReport, KernelError> {
+    let corpus = synthetic_corpus(options.lines, options.suspicious_pct);
+    let filter = Filter::new(config)?;
+    let (_, suspicious) = run(&filter, &corpus);
+
+    let mut report = HostReport {
+        filters: Vec::new(),
+        audit: Duration::ZERO,
+        parse: Duration::ZERO,
+        suspicious: suspicious as f64 / corpus.len().max(1) as f64,
+    };
+    let scratch =
+        std::env::temp_dir().join(format!("tripwired-bench-{}.jsonl", std::process::id()));
+    for mut case in cases(config, options, &scratch)? {
+        let items = case.items.max(1);
+        (case.time)(items as u64)?; // warm-up (page faults, lazy DFA states)
+        let per_item = (case.time)(items as u64)? / items as u32;
+        match case.group {
+            "filter" => report.filters.push((case.name, per_item)),
+            "audit" => report.audit = per_item,
+            _ => report.parse = per_item,
+        }
+    }
+    Ok(report)
+}
+
+/// Time `n` audit records written to a scratch trail at `path`
+fn write_records(path: &Path, durability: Durability, n: u64) -> Result<Duration, KernelError> {
+    let fingerprint = ModelFingerprint::new("bench", "-", 0, &Sampling::default());
+    let audit =
+        AuditTrail::new(path.to_path_buf(), fingerprint, "bench")?.with_durability(durability)?;
+    let mut failed = None;
+    let elapsed = timed(n, |i| {
+        let result = audit.record_entry(RecordInput {
+            input_log: "sudo rm -rf /var/lib/app-4242",
+            action: if i % 2 == 0 { "KILL" } else { "SUSTAIN" },
+            confidence: 90,
+            rule: Some("essential#0"),
+            reason: Some("recursive delete"),
+            latency_ms: 180,
+            ..Default::default()
+        });
+        if let Err(e) = result {
+            failed.get_or_insert(e);
+        }
+    });
+    audit.flush()?;
+    match failed {
+        Some(e) => Err(e.into()),
+        None => Ok(elapsed),
+    }
+}
+
+/// `tripwired bench`
+pub fn bench_host(config: &FilterConfig, options: &HostOptions) -> Result<(), KernelError> {
+    let report = measure(config, options)?;
+    let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+    println!(
+        "Host benchmark ({} cores, {} lines, {}% suspicious)",
+        cores, options.lines, options.suspicious_pct
+    );
+    for (name, per_line) in &report.filters {
+        stage(&format!("Filter: {}", name), *per_line, "lines");
+    }
+    let durability = format!("{:?}", options.durability).to_lowercase();
+    stage(
+        &format!("Audit writes ({})", durability),
+        report.audit,
+        "records",
+    );
+    stage("Verdict parsing", report.parse, "verdicts");
+    println!(
+        "  Capacity: ~{:.2}M lines/s per connection (LLM time not included)",
+        report.capacity() / 1e6
+    );
+    Ok(())
+}
+
+fn stage(label: &str, per_item: Duration, unit: &str) {
+    let secs = per_item.as_secs_f64().max(1e-12);
+    println!(
+        "  {:<24} {:>6.2}M {}/s  ({:.3}μs/{})",
+        label,
+        1.0 / secs / 1e6,
+        unit,
+        secs * 1e6,
+        unit.trim_end_matches('s')
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_synthetic_corpus_mix() {
+        let corpus = synthetic_corpus(1000, 10);
+        assert_eq!(corpus.len(), 1000);
+        assert_eq!(corpus, synthetic_corpus(1000, 10)); // deterministic
+
+        let filter = Filter::default();
+        let hits = corpus.iter().filter(|l| filter.is_suspicious(l)).count();
+        assert!((50..200).contains(&hits), "{} suspicious", hits);
+    }
+
+    #[test]
+    fn test_measure_host() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = HostOptions {
+            lines: 2000,
+            suspicious_pct: 10,
+            records: 200,
+            durability: Durability::Buffered,
+        };
+        let report = measure(&FilterConfig::default(), &options).unwrap();
+        let names: Vec<_> = report.filters.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, ["trading", "devops", "generic", "configured"]);
+        assert!(
+            (0.05..0.2).contains(&report.suspicious),
+            "{}",
+            report.suspicious
+        );
+        assert!(report.audit > Duration::ZERO && report.parse > Duration::ZERO);
+        assert!(report.capacity() > 0.0);
+
+        // The scratch trail holds every record
+        let path = dir.path().join("audit.jsonl");
+        write_records(&path, options.durability, 200).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(text.matches("\"action\":\"KILL\"").count(), 100);
+    }
+
+    #[test]
+    fn test_cases() {
+        let dir = tempfile::tempdir().unwrap();
+        let scratch = dir.path().join("audit.jsonl");
+        let options = HostOptions {
+            lines: 100,
+            suspicious_pct: 10,
+            records: 50,
+            durability: Durability::Buffered,
+        };
+        let cases = cases(&FilterConfig::default(), &options, &scratch).unwrap();
+        let ids: Vec<_> = cases
+            .iter()
+            .map(|c| format!("{}/{}", c.group, c.name))
+            .collect();
+        assert_eq!(
+            ids,
+            [
+                "filter/trading",
+                "filter/devops",
+                "filter/generic",
+                "filter/configured",
+                "audit/buffered",
+                "verdict/parse"
+            ]
+        );
+
+        // Any item count, past the corpus too; no scratch trail left behind
+        for mut case in cases {
+            assert!((case.time)(250).is_ok(), "{}", case.name);
+        }
+        assert!(!scratch.exists());
+    }
+}
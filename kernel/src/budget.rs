@@ -0,0 +1,90 @@
+//! Regex Budget - Bounded Cost for User Patterns
+//!
+//! Built-in patterns are known to be cheap; custom patterns, `[[rule]]`
+//! tables and Sigma imports are not. They are compiled with a size limit
+//! (a config error above it) and evaluated one by one after the built-in
+//! RegexSet, each timed:
+//!
+//! - a line that has spent more than `budget_us` in them skips the
+//!   remaining ones and is treated as suspicious (rule `regex-budget`),
+//!   with a rule-health warning naming the slowest rule
+//! - per-rule evaluation counts and time are exposed in `/stats` and
+//!   `/metrics` (`tripwired_rule_eval_seconds_total`) to spot slow rules
+//!
+//! The budget is checked between rules: a running match is never cut off.
+//!
+//! ```toml
+//! [regex]
+//! size_limit = 1048576  # compiled bytes per pattern
+//! budget_us = 1000      # per line; 0 = unlimited
+//! ```
+
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+use std::time::Duration;
+
+/// Rule id reported for lines that exhaust the budget
+pub const BUDGET_RULE: &str = "regex-budget";
+
+/// `[regex]` table
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RegexConfig {
+    /// Compiled size limit per user pattern (bytes)
+    pub size_limit: usize,
+    /// Time a line may spend in user patterns (microseconds); 0 = unlimited
+    pub budget_us: u64,
+}
+
+impl Default for RegexConfig {
+    fn default() -> Self {
+        Self {
+            size_limit: 1 << 20,
+            budget_us: 1000,
+        }
+    }
+}
+
+impl RegexConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.size_limit == 0 {
+            return Err("regex: size_limit must be > 0".to_string());
+        }
+        Ok(())
+    }
+
+    /// Compile a user pattern within the size limit
+    pub fn compile(&self, pattern: &str) -> Result<Regex, regex::Error> {
+        RegexBuilder::new(pattern)
+            .size_limit(self.size_limit)
+            .build()
+    }
+
+    pub fn budget(&self) -> Option<Duration> {
+        (self.budget_us > 0).then(|| Duration::from_micros(self.budget_us))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_size_limit() {
+        let config = RegexConfig {
+            size_limit: 4096,
+            ..Default::default()
+        };
+        assert!(config.compile(r"(?i)invoice.*void").is_ok());
+        assert!(config.compile(r"[a-z]{100}").is_err());
+        assert!(RegexConfig::default().compile(r"[a-z]{100}").is_ok());
+        // Unicode classes are large: `\w{30}` is over 1 MiB
+        assert!(RegexConfig::default().compile(r"\w{30}").is_err());
+
+        assert!(RegexConfig::default().validate().is_ok());
+        assert_eq!(config.budget(), Some(Duration::from_millis(1)));
+        let unlimited: RegexConfig = toml::from_str("budget_us = 0").unwrap();
+        assert_eq!(unlimited.budget(), None);
+        assert_eq!(unlimited.size_limit, 1 << 20);
+    }
+}
@@ -0,0 +1,224 @@
+//! Cgroup - Freezing and Clamping the Agent's Control Group (Linux)
+//!
+//! Between SUSTAIN and a KILL there is room for responses that stop the
+//! agent making progress without destroying it. Three policy actions work
+//! on the cgroup v2 group of `--target-pid`, so every process the agent
+//! started is caught at once, with no race against forks:
+//!
+//! - `freeze` - `cgroup.freeze`: every task stops until thawed
+//! - `clamp_cpu` - `cpu.max`: 1% of one CPU (needs the `cpu` controller)
+//! - `clamp_memory` - `memory.max` at current usage: nothing can grow;
+//!   allocations past it are reclaimed, then meet the group's OOM killer
+//!
+//! ```toml
+//! [[policy.rule]]
+//! name = "freeze-on-exfil"
+//! when = 'decision.action == "KILL" && decision.rule == "exfil"'
+//! action = "freeze"
+//! ```
+//!
+//! An operator releases the group by hand: `echo 0 > cgroup.freeze`,
+//! `echo max > cpu.max`, `echo max > memory.max`. The root group and the
+//! kernel's own group are refused: clamping them would stop the host or the
+//! tripwire itself.
+
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// cgroup v2 mount point
+const ROOT: &str = "/sys/fs/cgroup";
+
+/// `cpu.max` period (microseconds) and the 1% quota within it
+const CPU_PERIOD_US: u64 = 100_000;
+const CPU_QUOTA_US: u64 = CPU_PERIOD_US / 100;
+
+/// What a clamp action does to the group
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Clamp {
+    Freeze,
+    Cpu,
+    Memory,
+}
+
+impl Clamp {
+    /// The clamp behind an audit action (`FREEZE`, `CLAMP_CPU`,
+    /// `CLAMP_MEMORY`)
+    pub fn from_action(action: &str) -> Option<Self> {
+        match action {
+            "FREEZE" => Some(Self::Freeze),
+            "CLAMP_CPU" => Some(Self::Cpu),
+            "CLAMP_MEMORY" => Some(Self::Memory),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Freeze => "FREEZE",
+            Self::Cpu => "CLAMP_CPU",
+            Self::Memory => "CLAMP_MEMORY",
+        }
+    }
+}
+
+impl fmt::Display for Clamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A cgroup v2 group directory
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cgroup {
+    path: PathBuf,
+}
+
+impl Cgroup {
+    /// The group `pid` belongs to; never the root group or the kernel's own
+    pub fn of_pid(pid: u32) -> io::Result<Self> {
+        let own = read_membership("self")?;
+        let membership = read_membership(&pid.to_string())?;
+        let group = resolve(Path::new(ROOT), &membership)?;
+        if resolve(Path::new(ROOT), &own)? == group {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "PID {} shares tripwired's cgroup {}",
+                    pid,
+                    group.path.display()
+                ),
+            ));
+        }
+        Ok(group)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Apply `clamp`; a description of what changed
+    pub fn apply(&self, clamp: Clamp) -> io::Result<String> {
+        match clamp {
+            Clamp::Freeze => {
+                self.write("cgroup.freeze", "1")?;
+                Ok("frozen".to_string())
+            }
+            Clamp::Cpu => {
+                self.write("cpu.max", &format!("{} {}", CPU_QUOTA_US, CPU_PERIOD_US))?;
+                Ok("CPU clamped to 1%".to_string())
+            }
+            Clamp::Memory => {
+                let current = self.read("memory.current")?;
+                let current = current.trim();
+                self.write("memory.max", current)?;
+                Ok(format!("memory clamped to {} bytes", current))
+            }
+        }
+    }
+
+    fn read(&self, file: &str) -> io::Result<String> {
+        std::fs::read_to_string(self.path.join(file)).map_err(|e| self.error(file, e))
+    }
+
+    fn write(&self, file: &str, value: &str) -> io::Result<()> {
+        // Interface files exist only where the controller is enabled
+        if !self.path.join(file).exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "{} has no {} (controller not enabled?)",
+                    self.path.display(),
+                    file
+                ),
+            ));
+        }
+        std::fs::write(self.path.join(file), value).map_err(|e| self.error(file, e))
+    }
+
+    fn error(&self, file: &str, e: io::Error) -> io::Error {
+        io::Error::new(
+            e.kind(),
+            format!("{}: {}", self.path.join(file).display(), e),
+        )
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn read_membership(pid: &str) -> io::Result<String> {
+    std::fs::read_to_string(format!("/proc/{}/cgroup", pid))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_membership(_: &str) -> io::Result<String> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "cgroup actions need cgroup v2 (Linux)",
+    ))
+}
+
+/// The v2 group in a `/proc/<pid>/cgroup` `membership`, under `root`
+fn resolve(root: &Path, membership: &str) -> io::Result<Cgroup> {
+    let relative = membership
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .ok_or_else(|| {
+            io::Error::new(io::ErrorKind::Unsupported, "not in a cgroup v2 hierarchy")
+        })?;
+    let relative = relative.trim().trim_start_matches('/');
+    if relative.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "refusing to clamp the root cgroup",
+        ));
+    }
+    Ok(Cgroup {
+        path: root.join(relative),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let root = Path::new("/sys/fs/cgroup");
+        let group = resolve(root, "0::/system.slice/agent.service\n").unwrap();
+        assert_eq!(
+            group.path(),
+            Path::new("/sys/fs/cgroup/system.slice/agent.service")
+        );
+        // Hybrid hierarchies list v1 controllers first
+        let hybrid = "12:cpu,cpuacct:/agent\n0::/user.slice/agent\n";
+        assert_eq!(
+            resolve(root, hybrid).unwrap().path(),
+            Path::new("/sys/fs/cgroup/user.slice/agent")
+        );
+        assert!(resolve(root, "0::/\n").is_err());
+        assert!(resolve(root, "4:memory:/agent\n").is_err());
+        assert_eq!(Clamp::from_action("CLAMP_CPU"), Some(Clamp::Cpu));
+        assert_eq!(Clamp::from_action("PAUSE"), None);
+    }
+
+    #[test]
+    fn test_apply() {
+        let dir = tempfile::tempdir().unwrap();
+        let group = resolve(dir.path(), "0::/agent.scope").unwrap();
+        std::fs::create_dir(group.path()).unwrap();
+        for file in ["cgroup.freeze", "memory.max"] {
+            std::fs::write(group.path().join(file), "").unwrap();
+        }
+        std::fs::write(group.path().join("memory.current"), "52428800\n").unwrap();
+
+        assert_eq!(group.apply(Clamp::Freeze).unwrap(), "frozen");
+        let freeze = std::fs::read_to_string(group.path().join("cgroup.freeze")).unwrap();
+        assert_eq!(freeze, "1");
+        group.apply(Clamp::Memory).unwrap();
+        let max = std::fs::read_to_string(group.path().join("memory.max")).unwrap();
+        assert_eq!(max, "52428800");
+        // No cpu controller in this group
+        let error = group.apply(Clamp::Cpu).unwrap_err();
+        assert!(error.to_string().contains("controller not enabled"));
+    }
+}
@@ -0,0 +1,268 @@
+//! LLM Fallback Chain - Circuit Breakers and Secondary Models
+//!
+//! The kill-switch must not go blind because one inference server
+//! restarted. `--llm-fallback MODEL@URL` (repeatable) adds endpoints tried
+//! in order when the one before fails. Each endpoint has a circuit breaker:
+//! after `--llm-breaker-failures` consecutive errors it is skipped for
+//! `--llm-breaker-cooldown-ms`, then given one trial request.
+//!
+//! Decisions carry the fingerprint of the model that actually answered.
+
+use crate::audit::ModelFingerprint;
+use crate::llm::{Decision, LlmClient, LlmError};
+use crate::notify::{Event, Notifier};
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Parse a `MODEL@URL` fallback entry
+pub fn parse_fallback(s: &str) -> Result<(String, String), String> {
+    match s.split_once('@') {
+        Some((model, url)) if !model.is_empty() && url.contains("://") => {
+            Ok((model.to_string(), url.to_string()))
+        }
+        _ => Err(format!(
+            "invalid fallback '{}' (expected MODEL@URL, e.g. phi-3-mini@http://localhost:8081/v1)",
+            s
+        )),
+    }
+}
+
+/// Consecutive-failure circuit breaker
+pub struct Breaker {
+    threshold: u32,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+#[derive(Default)]
+struct BreakerState {
+    failures: u32,
+    open_until: Option<Instant>,
+}
+
+impl Breaker {
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold: threshold.max(1),
+            cooldown,
+            state: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    /// May a request be sent now? (closed, or cooled down for a trial)
+    pub fn allows(&self, now: Instant) -> bool {
+        self.state
+            .lock()
+            .unwrap()
+            .open_until
+            .is_none_or(|until| now >= until)
+    }
+
+    pub fn success(&self) {
+        *self.state.lock().unwrap() = BreakerState::default();
+    }
+
+    /// Record a failure; returns true if this opened the breaker
+    pub fn failure(&self, now: Instant) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.failures += 1;
+        if state.failures >= self.threshold {
+            let was_closed = state.open_until.is_none();
+            state.open_until = Some(now + self.cooldown);
+            return was_closed;
+        }
+        false
+    }
+}
+
+/// One endpoint in the chain
+pub struct Member {
+    pub client: LlmClient,
+    pub fingerprint: ModelFingerprint,
+    breaker: Breaker,
+}
+
+/// A decision and which chain member produced it
+pub struct Answer {
+    pub decision: Decision,
+    /// Fingerprint of the answering model (`name@hash`)
+    pub model: String,
+    /// Answered by a fallback rather than the primary
+    pub fallback: bool,
+    /// Number of lines in the request, when batched
+    pub batch: Option<usize>,
+}
+
+/// Primary model plus ordered fallbacks
+pub struct LlmChain {
+    members: Vec<Member>,
+    /// Told when a breaker opens
+    notifier: Notifier,
+}
+
+impl LlmChain {
+    /// `members[0]` is the primary
+    pub fn new(members: Vec<(LlmClient, ModelFingerprint)>, breaker: (u32, Duration)) -> Self {
+        Self {
+            members: members
+                .into_iter()
+                .map(|(client, fingerprint)| Member {
+                    client,
+                    fingerprint,
+                    breaker: Breaker::new(breaker.0, breaker.1),
+                })
+                .collect(),
+            notifier: Notifier::default(),
+        }
+    }
+
+    /// Report breaker openings (`breaker` webhook events)
+    pub fn with_notifier(mut self, notifier: Notifier) -> Self {
+        self.notifier = notifier;
+        self
+    }
+
+    pub fn primary(&self) -> &Member {
+        &self.members[0]
+    }
+
+    pub fn fallbacks(&self) -> &[Member] {
+        &self.members[1..]
+    }
+
+    /// Ask each available member in order until one answers
+    pub async fn analyze(&self, log: &str, context: &str) -> Result<Answer, LlmError> {
+        let (decision, i) = self
+            .first_answer(|client| client.analyze(log, context))
+            .await?;
+        Ok(self.answer(decision, i, None))
+    }
+
+    /// Batch variant of [`analyze`](Self::analyze): one answer slot per item
+    ///
+    /// `None` marks a line the answering model gave no verdict for.
+    pub async fn analyze_batch(
+        &self,
+        items: &[(&str, &str)],
+    ) -> Result<Vec<Option<Answer>>, LlmError> {
+        let (decisions, i) = self
+            .first_answer(|client| client.analyze_batch(items))
+            .await?;
+        Ok(decisions
+            .into_iter()
+            .map(|d| d.map(|d| self.answer(d, i, Some(items.len()))))
+            .collect())
+    }
+
+    /// Free-text completion from the first available member; returns the
+    /// text and the answering model's fingerprint
+    pub async fn complete_text(
+        &self,
+        prompt: &str,
+        max_tokens: u32,
+    ) -> Result<(String, String), LlmError> {
+        let (text, i) = self
+            .first_answer(|client| client.complete_text(prompt, max_tokens))
+            .await?;
+        Ok((text, self.members[i].fingerprint.fingerprint()))
+    }
+
+    fn answer(&self, decision: Decision, i: usize, batch: Option<usize>) -> Answer {
+        Answer {
+            decision,
+            model: self.members[i].fingerprint.fingerprint(),
+            fallback: i > 0,
+            batch,
+        }
+    }
+
+    /// Run `call` against each available member; returns the result and member index
+    async fn first_answer<'a, T, F, Fut>(&'a self, call: F) -> Result<(T, usize), LlmError>
+    where
+        F: Fn(&'a LlmClient) -> Fut,
+        Fut: Future<Output = Result<T, LlmError>>,
+    {
+        let mut last_err: Option<LlmError> = None;
+        for (i, member) in self.members.iter().enumerate() {
+            if !member.breaker.allows(Instant::now()) {
+                continue;
+            }
+            match call(&member.client).await {
+                Ok(result) => {
+                    member.breaker.success();
+                    return Ok((result, i));
+                }
+                Err(e) => {
+                    let name = &member.fingerprint.model_name;
+                    if member.breaker.failure(Instant::now()) {
+                        warn!("🔌 Circuit open for {}: {}", name, e);
+                        self.notifier.send(Event::BreakerOpen {
+                            model: name.clone(),
+                            error: e.to_string(),
+                        });
+                    } else {
+                        warn!("⚠️ LLM {} failed: {}", name, e);
+                    }
+                    last_err = Some(e);
+                }
+            }
+        }
+        Err(last_err.unwrap_or(LlmError::CircuitsOpen))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fallback() {
+        assert_eq!(
+            parse_fallback("phi-3-mini@http://localhost:8081/v1"),
+            Ok((
+                "phi-3-mini".to_string(),
+                "http://localhost:8081/v1".to_string()
+            ))
+        );
+        assert!(parse_fallback("http://localhost:8081/v1").is_err());
+        assert!(parse_fallback("@http://x").is_err());
+        assert!(parse_fallback("model@localhost").is_err());
+    }
+
+    #[test]
+    fn test_breaker() {
+        let breaker = Breaker::new(2, Duration::from_secs(30));
+        let t0 = Instant::now();
+        assert!(breaker.allows(t0));
+
+        assert!(!breaker.failure(t0));
+        assert!(breaker.allows(t0));
+        assert!(breaker.failure(t0)); // opens
+        assert!(!breaker.allows(t0 + Duration::from_secs(10)));
+
+        // Half-open trial after the cooldown; a failure re-opens silently
+        let t1 = t0 + Duration::from_secs(30);
+        assert!(breaker.allows(t1));
+        assert!(!breaker.failure(t1));
+        assert!(!breaker.allows(t1 + Duration::from_secs(1)));
+
+        breaker.success();
+        assert!(breaker.allows(t1 + Duration::from_secs(1)));
+    }
+
+    #[tokio::test]
+    async fn test_open_circuit_skips_endpoint() {
+        // Nothing listens on port 9 (discard) locally: immediate error
+        let dead = LlmClient::new("http://127.0.0.1:9/v1", "dead", 30);
+        let dead_fp =
+            ModelFingerprint::new("dead", "http://127.0.0.1:9/v1", 30, &Default::default());
+        let chain = LlmChain::new(vec![(dead, dead_fp)], (1, Duration::from_secs(60)));
+
+        assert!(chain.analyze("x", "").await.is_err());
+        // Breaker is now open: no request is even attempted
+        let err = chain.analyze("x", "").await.err().unwrap();
+        assert!(err.to_string().contains("circuits open"));
+    }
+}
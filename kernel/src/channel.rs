@@ -0,0 +1,206 @@
+//! Channels - Independent Pipelines in One Kernel Process
+//!
+//! Running one kernel per agent duplicates the runtime, the LLM connection
+//! pools and the compiled rule sets. `[channel.<name>]` tables let one
+//! process host several pipelines instead, each with its own endpoint,
+//! filter config, model, audit file, kill target and admin port. Channels
+//! share the process, the HTTP connection pool and shutdown; decisions,
+//! stats and arming are per channel.
+//!
+//! When any channel is defined, only the channels are served: `--llm-url`,
+//! `--model` and the other LLM flags become their defaults, and the main
+//! filter config's rules apply to channels without a `filter_config`.
+//!
+//! ```toml
+//! [channel.trader]
+//! port = 9001
+//! filter_config = "trader.toml"
+//! model = "llama-3.2-3b-instruct"
+//! audit_log = "trader-audit.jsonl"
+//! target_pid = 4242
+//!
+//! [channel.ops]
+//! socket = "/run/tripwired/ops.sock"
+//! admin_port = 9102
+//!
+//! [channel.scraper]
+//! container = "scraper-agent"
+//!
+//! [channel.sandbox]
+//! socket = "/run/sandbox/agent-7.vsock_5000"
+//! target_vm = "qmp:/run/sandbox/agent-7.qmp"
+//!
+//! [channel.chat]
+//! socket = "/run/tripwired/chat.sock"
+//! stages = ["flood", "sequence", "filter", "analyze"]
+//!
+//! [channel.fleet]
+//! pods = "app=trader"
+//! namespace = "agents"
+//! ```
+
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+
+/// One `[channel.<name>]` table
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ChannelDef {
+    /// Loopback TCP port
+    #[serde(default)]
+    pub port: Option<u16>,
+    /// Unix socket path (Windows: named pipe name, `\\.\pipe\...`)
+    #[serde(default)]
+    pub socket: Option<String>,
+    /// Docker container whose logs are followed; also killed on KILL
+    #[serde(default)]
+    pub container: Option<String>,
+    /// Label selector of the pods whose logs are followed; a KILL deletes
+    /// the pod
+    #[serde(default)]
+    pub pods: Option<String>,
+    /// Namespace for `pods` (default: the service account's)
+    #[serde(default)]
+    pub namespace: Option<String>,
+    /// Filter config for this channel, relative to the main one
+    /// (default: the main config's rules)
+    #[serde(default)]
+    pub filter_config: Option<PathBuf>,
+    /// LLM endpoint (default: `--llm-url`)
+    #[serde(default)]
+    pub llm_url: Option<String>,
+    /// Model name (default: `--model`)
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Audit log file (default: `tripwired-<name>-audit.jsonl`)
+    #[serde(default)]
+    pub audit_log: Option<PathBuf>,
+    /// Process killed on this channel's KILL decisions
+    #[serde(default)]
+    pub target_pid: Option<u32>,
+    /// VM powered off on this channel's KILL decisions (see `vm`)
+    #[serde(default)]
+    pub target_vm: Option<String>,
+    /// Admin API port for this channel (disabled if unset)
+    #[serde(default)]
+    pub admin_port: Option<u16>,
+    /// Judgment stages, in order (default: the built-in chain, see `stage`)
+    #[serde(default)]
+    pub stages: Option<Vec<String>>,
+}
+
+impl ChannelDef {
+    pub fn audit_log(&self, name: &str) -> PathBuf {
+        self.audit_log
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(format!("tripwired-{}-audit.jsonl", name)))
+    }
+}
+
+/// Check every channel and that none share an endpoint, port or audit file
+pub fn validate(channels: &BTreeMap<String, ChannelDef>) -> Result<(), String> {
+    let mut endpoints = HashSet::new();
+    let mut audit_logs = HashSet::new();
+    for (name, channel) in channels {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(format!(
+                "channel '{}': names may only use letters, digits, '-' and '_'",
+                name
+            ));
+        }
+        let endpoint = match (
+            channel.port,
+            &channel.socket,
+            &channel.container,
+            &channel.pods,
+        ) {
+            (Some(port), None, None, None) => format!("port {}", port),
+            (None, Some(socket), None, None) => format!("socket {}", socket),
+            (None, None, Some(container), None) => {
+                crate::docker::parse_name(container)
+                    .map_err(|e| format!("channel '{}': {}", name, e))?;
+                format!("container {}", container)
+            }
+            (None, None, None, Some(selector)) => format!(
+                "pods {} in {}",
+                selector,
+                channel.namespace.as_deref().unwrap_or("-")
+            ),
+            _ => {
+                return Err(format!(
+                    "channel '{}' needs exactly one of port, socket, container or pods",
+                    name
+                ))
+            }
+        };
+        if channel.namespace.is_some() && channel.pods.is_none() {
+            return Err(format!("channel '{}': namespace needs pods", name));
+        }
+        if let Some(ref spec) = channel.target_vm {
+            crate::vm::parse_target(spec).map_err(|e| format!("channel '{}': {}", name, e))?;
+        }
+        if let Some(ref stages) = channel.stages {
+            crate::stage::validate(stages).map_err(|e| format!("channel '{}': {}", name, e))?;
+        }
+        let admin = channel.admin_port.map(|port| format!("port {}", port));
+        for endpoint in std::iter::once(endpoint).chain(admin) {
+            if !endpoints.insert(endpoint.clone()) {
+                return Err(format!(
+                    "channel '{}': {} is already in use",
+                    name, endpoint
+                ));
+            }
+        }
+        if !audit_logs.insert(channel.audit_log(name)) {
+            return Err(format!(
+                "channel '{}': audit log {} is shared with another channel",
+                name,
+                channel.audit_log(name).display()
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channels(toml: &str) -> BTreeMap<String, ChannelDef> {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn test_validate() {
+        let ok = channels(
+            "[trader]\nport = 9001\ntarget_pid = 42\n[ops]\nsocket = '/tmp/ops.sock'\nadmin_port = 9102",
+        );
+        assert!(validate(&ok).is_ok());
+        assert_eq!(
+            ok["ops"].audit_log("ops"),
+            PathBuf::from("tripwired-ops-audit.jsonl")
+        );
+
+        let err = |toml| validate(&channels(toml)).unwrap_err();
+        assert!(err("[a]\nport = 1\nsocket = 'x'").contains("exactly one"));
+        assert!(err("[a]").contains("exactly one"));
+        assert!(err("[a]\nport = 1\n[b]\nport = 2\nadmin_port = 1").contains("port 1"));
+        assert!(
+            err("[a]\nport = 1\naudit_log = 'x'\n[b]\nport = 2\naudit_log = 'x'")
+                .contains("shared")
+        );
+        assert!(err("['a b']\nport = 1").contains("names"));
+        assert!(validate(&channels("[a]\ncontainer = 'agent-1'")).is_ok());
+        assert!(err("[a]\ncontainer = 'agent-1'\nport = 1").contains("exactly one"));
+        assert!(err("[a]\ncontainer = '../x'").contains("invalid container"));
+        assert!(validate(&channels("[a]\npods = 'app=x'\nnamespace = 'agents'")).is_ok());
+        assert!(err("[a]\npods = 'app=x'\n[b]\npods = 'app=x'").contains("already in use"));
+        assert!(err("[a]\nport = 1\nnamespace = 'agents'").contains("needs pods"));
+        assert!(err("[a]\nport = 1\ntarget_vm = 'agent-7'").contains("expected libvirt:"));
+        assert!(err("[a]\nport = 1\nstages = ['analyze']").contains("required"));
+    }
+}
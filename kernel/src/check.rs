@@ -0,0 +1,168 @@
+//! Config Check - Reject a Broken Config Before It Ships
+//!
+//! `tripwired check-config` runs the startup validation pass without
+//! starting a kernel: domain presets (`--preset-dir`), the filter config
+//! (TOML syntax and types with line and column, every setting, every
+//! pattern compiled and named by its rule), imported Sigma rules
+//! (`--sigma-rules`) and the filter config of each `[channel.<name>]`.
+//! Every file is checked even after a failure; the exit status is 1 if any
+//! failed, for CI:
+//!
+//! ```text
+//! $ tripwired check-config --config tripwired.toml
+//! Config check: tripwired.toml
+//!   ✗ tripwired.toml:12:11: invalid type: string "5s", expected u64
+//! Error: 1 invalid config file(s)
+//! ```
+
+use crate::error::KernelError;
+use crate::filter::{ConfigError, Filter, FilterConfig, RuleDef};
+use crate::{preset, sigma};
+use std::path::Path;
+
+/// `tripwired check-config`
+pub fn run(
+    path: &Path,
+    sigma_rules: Option<&Path>,
+    preset_dir: Option<&Path>,
+) -> Result<(), KernelError> {
+    println!("Config check: {}", path.display());
+    let mut failed = 0;
+    let mut fail = |error: &dyn std::fmt::Display| {
+        println!("  ✗ {}", error);
+        failed += 1;
+    };
+
+    let presets = match preset_dir.map(preset::load_dir).transpose() {
+        Ok(presets) => presets.unwrap_or_default(),
+        Err(e) => {
+            fail(&e);
+            Vec::new()
+        }
+    };
+    let imported = match sigma_rules.map(sigma::load_dir).transpose() {
+        Ok(import) => import.map(|i| i.rules).unwrap_or_default(),
+        Err(e) => {
+            fail(&format!("Sigma rules: {}", e));
+            Vec::new()
+        }
+    };
+
+    let config = match check_file(path, &presets, &imported) {
+        Ok(config) => Some(config),
+        Err(e) => {
+            fail(&e);
+            None
+        }
+    };
+    // Channel filter configs are relative to the main one
+    let base = path.parent().unwrap_or(Path::new(""));
+    for (name, channel) in config.iter().flat_map(|c| &c.channel) {
+        let Some(ref channel_path) = channel.filter_config else {
+            continue;
+        };
+        let channel_path = base.join(channel_path);
+        match check_file(&channel_path, &presets, &imported) {
+            Ok(config) if !config.channel.is_empty() => fail(&format!(
+                "channel '{}': {} defines channels of its own",
+                name,
+                channel_path.display()
+            )),
+            Ok(_) => {}
+            Err(e) => fail(&format!("channel '{}': {}", name, e)),
+        }
+    }
+
+    if failed > 0 {
+        return Err(format!("{} invalid config file(s)", failed).into());
+    }
+    println!("  ✓ valid");
+    Ok(())
+}
+
+/// Load, validate and compile one filter config, with the imported rules
+fn check_file(
+    path: &Path,
+    presets: &[preset::Preset],
+    imported: &[RuleDef],
+) -> Result<FilterConfig, ConfigError> {
+    let mut config = FilterConfig::load(path, presets.to_vec())?;
+    if !imported.is_empty() {
+        config.rule.extend(imported.iter().cloned());
+        config
+            .validate()
+            .map_err(|e| format!("Sigma rules conflict with {}: {}", path.display(), e))?;
+    }
+    let filter = Filter::new(&config)?;
+    println!(
+        "  ✓ {}: {} rules, {} excludes",
+        path.display(),
+        filter.rule_stats().len(),
+        config.exclude.len()
+    );
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::FilterError;
+
+    #[test]
+    fn test_check_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, content: &str| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, content).unwrap();
+            path
+        };
+
+        let main = write(
+            "tripwired.toml",
+            "[channel.payments]\nport = 9001\nfilter_config = 'payments.toml'\n",
+        );
+        write("payments.toml", "exclude = ['^GET /health']\n");
+        assert!(run(&main, None, None).is_ok());
+
+        write(
+            "payments.toml",
+            "[[rule]]\nid = 'orders'\npattern = '(unclosed'\n",
+        );
+        assert!(run(&main, None, None).is_err());
+        assert!(run(&dir.path().join("missing.toml"), None, None).is_err());
+    }
+
+    #[test]
+    fn test_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tripwired.toml");
+        let load = |content: &str| {
+            std::fs::write(&path, content).unwrap();
+            FilterConfig::load(&path, Vec::new()).unwrap_err()
+        };
+
+        match load("exclude = []\n\n[health]\ninterval_ms = '5s'\n") {
+            ConfigError::Parse { line, column, .. } => assert_eq!((line, column), (4, 15)),
+            e => panic!("{:?}", e),
+        }
+        let pattern = |e: &ConfigError| match e {
+            ConfigError::Filter(FilterError::Pattern { rule, .. }) => rule.clone(),
+            e => panic!("{:?}", e),
+        };
+        let e = load("[[rule]]\nid = 'orders'\npattern = '(unclosed'\n");
+        assert_eq!(pattern(&e), "orders");
+        assert_eq!(e.category(), "filter.pattern");
+        assert!(e
+            .to_string()
+            .starts_with("rule 'orders': invalid pattern: "));
+        let e = load("exclude = ['[']\n");
+        assert_eq!(pattern(&e), "exclude#0");
+        let e = load("[[rate]]\nid = 'bursts'\npattern = '+'\nwindow_ms = 60000\nmax_count = 3\n");
+        assert_eq!(pattern(&e), "bursts");
+        assert!(matches!(load("domain = 'nope'\n"), ConfigError::Invalid(_)));
+        assert!(matches!(
+            FilterConfig::load(&dir.path().join("missing.toml"), Vec::new()),
+            Err(ConfigError::Read { .. })
+        ));
+    }
+}
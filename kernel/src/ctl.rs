@@ -0,0 +1,259 @@
+//! Operator Controls - `tripwired ctl`
+//!
+//! Client for the admin API's override endpoints, so an operator can take
+//! the safety off or put it back on without restarting the kernel:
+//!
+//! ```text
+//! tripwired ctl --admin-port 9100 --admin-token-file /etc/tripwired/admin.token \
+//!     disarm --for 30m --reason "deploy window"
+//! tripwired ctl --admin-port 9100 arm
+//! tripwired ctl --admin-port 9100 emergency-kill --reason "runaway orders"
+//! tripwired ctl --admin-port 9100 status
+//! tripwired ctl --admin-port 9100 mark 4812 false-positive --reason "test order"
+//! ```
+//!
+//! Every change, and every decision marked, is attributed to `--operator`
+//! (default: `$USER`) in the audit trail. Everything but `status` needs
+//! `--admin-token-file`, the same token file the kernel was given.
+
+use crate::admin::{ArmState, FeedbackRequest, OperatorRequest};
+use crate::audit::{CorrectionEvent, Label};
+use crate::error::KernelError;
+use crate::learn;
+use clap::Subcommand;
+use serde::Deserialize;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Subcommand, Debug)]
+pub enum CtlCmd {
+    /// Stop KILL decisions from signaling the target
+    Disarm {
+        /// Hold length such as `30m` or `2h` (default: until `arm`)
+        #[arg(long = "for", value_parser = learn::parse_duration)]
+        duration: Option<Duration>,
+    },
+    /// Execute KILL decisions again (also overrides the LLM canary gate)
+    Arm,
+    /// Kill the target process now, armed or not
+    EmergencyKill,
+    /// Show health and arming
+    Status,
+    /// Record that a decision was wrong (a `correction` audit event)
+    Mark {
+        /// Decision ID (`id` in the audit trail)
+        id: u64,
+        #[arg(value_enum)]
+        label: Label,
+    },
+}
+
+/// The `/stats` fields `status` prints
+#[derive(Deserialize)]
+struct Status {
+    llm_healthy: bool,
+    armed: bool,
+    #[serde(default)]
+    disarmed_until_ms: Option<u64>,
+    kills: u64,
+    kills_suppressed: u64,
+    #[serde(default)]
+    profile: Option<String>,
+    #[serde(default)]
+    dry_run: bool,
+    #[serde(default)]
+    instance: Option<String>,
+    #[serde(default)]
+    role: Option<String>,
+}
+
+/// Run one command against the kernel's admin API
+pub async fn run(
+    admin_port: u16,
+    operator: Option<String>,
+    reason: Option<String>,
+    token: Option<String>,
+    command: CtlCmd,
+) -> Result<(), KernelError> {
+    let client = reqwest::Client::builder()
+        .no_proxy()
+        .timeout(Duration::from_secs(5))
+        .build()?;
+    let url = format!("http://127.0.0.1:{}", admin_port);
+
+    let (path, for_ms) = match command {
+        CtlCmd::Status => {
+            let status: Status = client
+                .get(format!("{}/stats", url))
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            println!(
+                "LLM: {}",
+                if status.llm_healthy {
+                    "healthy"
+                } else {
+                    "UNHEALTHY"
+                }
+            );
+            println!(
+                "Kill actions: {}",
+                describe(status.armed, status.disarmed_until_ms)
+            );
+            if let Some(profile) = status.profile {
+                let dry_run = if status.dry_run { " (dry run)" } else { "" };
+                println!("Schedule profile: {}{}", profile, dry_run);
+            }
+            if let (Some(instance), Some(role)) = (status.instance, status.role) {
+                println!("HA: {} ({})", instance, role);
+            }
+            println!(
+                "Kills: {} ({} suppressed)",
+                status.kills, status.kills_suppressed
+            );
+            return Ok(());
+        }
+        CtlCmd::Mark { id, label } => {
+            let request = FeedbackRequest {
+                operator: operator_name(operator)?,
+                label,
+                reason,
+            };
+            let response = authorized(
+                client.post(format!("{}/decisions/{}/feedback", url, id)),
+                &token,
+            )
+            .json(&request)
+            .send()
+            .await?;
+            if !response.status().is_success() {
+                let status = response.status();
+                return Err(
+                    format!("{}: {}", status, response.text().await.unwrap_or_default()).into(),
+                );
+            }
+            let event: CorrectionEvent = response.json().await?;
+            match event.original {
+                Some(ref original) => println!(
+                    "Decision {} ({}) marked {}: should have been {}",
+                    id,
+                    original,
+                    label.as_str(),
+                    event.action
+                ),
+                None => println!(
+                    "Decision {} marked {}: should have been {}",
+                    id,
+                    label.as_str(),
+                    event.action
+                ),
+            }
+            return Ok(());
+        }
+        CtlCmd::Disarm { duration } => ("disarm", duration.map(|d| d.as_millis() as u64)),
+        CtlCmd::Arm => ("arm", None),
+        CtlCmd::EmergencyKill => ("emergency-kill", None),
+    };
+
+    let request = OperatorRequest {
+        operator: operator_name(operator)?,
+        reason,
+        for_ms,
+    };
+    let response = authorized(client.post(format!("{}/{}", url, path)), &token)
+        .json(&request)
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        return Err(format!("{}: {}", status, response.text().await.unwrap_or_default()).into());
+    }
+    let state: ArmState = response.json().await?;
+    if let Some(pid) = state.killed_pid {
+        println!("Killed PID {}", pid);
+    }
+    if let Some(ref container) = state.killed_container {
+        println!("Killed container {}", container);
+    }
+    if let Some(ref vm) = state.killed_vm {
+        println!("Powered off VM {}", vm);
+    }
+    println!(
+        "Kill actions: {}",
+        describe(state.armed, state.disarmed_until_ms)
+    );
+    Ok(())
+}
+
+/// The bearer token in `--admin-token-file` (surrounding whitespace ignored)
+pub fn read_token(path: &Path) -> Result<String, KernelError> {
+    let token = std::fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read admin token {}: {}", path.display(), e))?;
+    Ok(token.trim().to_string())
+}
+
+fn authorized(request: reqwest::RequestBuilder, token: &Option<String>) -> reqwest::RequestBuilder {
+    match token {
+        Some(token) => request.bearer_auth(token),
+        None => request,
+    }
+}
+
+/// `--operator`, else the login name
+fn operator_name(operator: Option<String>) -> Result<String, KernelError> {
+    let name = operator
+        .or_else(|| std::env::var("USER").ok())
+        .or_else(|| std::env::var("USERNAME").ok())
+        .ok_or("no --operator given and $USER is not set")?;
+    Ok(name)
+}
+
+fn describe(armed: bool, disarmed_until_ms: Option<u64>) -> String {
+    match (armed, disarmed_until_ms) {
+        (true, _) => "ARMED".to_string(),
+        (false, Some(u64::MAX)) => "disarmed by operator until re-armed".to_string(),
+        (false, Some(until)) => format!(
+            "disarmed by operator for another {}",
+            remaining(until.saturating_sub(now_ms()))
+        ),
+        (false, None) => "disarmed (LLM canary not yet passed)".to_string(),
+    }
+}
+
+/// `1h30m`, `5m0s`, `42s`
+fn remaining(ms: u64) -> String {
+    let secs = ms.div_ceil(1000);
+    match (secs / 3600, secs % 3600 / 60, secs % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m{}s", m, s),
+        (h, m, _) => format!("{}h{}m", h, m),
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe() {
+        assert_eq!(remaining(42_000), "42s");
+        assert_eq!(remaining(1_800_000), "30m0s");
+        assert_eq!(remaining(5_400_000), "1h30m");
+
+        assert_eq!(describe(true, None), "ARMED");
+        assert_eq!(
+            describe(false, Some(u64::MAX)),
+            "disarmed by operator until re-armed"
+        );
+        assert!(describe(false, Some(now_ms() + 90_000)).starts_with("disarmed by operator for"));
+    }
+}
@@ -0,0 +1,406 @@
+//! Dataset - Training Examples from the Audit History
+//!
+//! Every LLM verdict in the audit trail is a labeled example. `tripwired
+//! dataset` exports them for the `[classifier]` (see `classify`) and for
+//! prompt-tuning experiments:
+//!
+//! ```bash
+//! tripwired dataset --from /var/log/tripwired/ --format jsonl \
+//!     --corrections corrections.jsonl --out dataset.jsonl
+//! ```
+//!
+//! ```text
+//! {"normalized_line":"<ts> sell <num> shares of ACME","action":"SUSTAIN","confidence":92,"model":"llama3@ab12cd34"}
+//! ```
+//!
+//! `--from` is an audit file or a directory of them (rotated segments
+//! included). Lines are the redacted `input_log` as a template (see
+//! `normalize`); the action is the model's verdict, before any decision
+//! policy. Filtered decisions, LLM failures and encrypted records are not
+//! verdicts and are skipped. Human feedback overrides labels by decision
+//! ID: the trail's own `correction` events (`tripwired ctl mark`, see
+//! `admin`), then a corrections file, one JSON object per line. A corrected
+//! example is exported whatever decided it (a line the filter let through
+//! can be labeled KILL), with confidence 100 and `"corrected": true`:
+//!
+//! ```text
+//! {"decision_id": 4812, "action": "SUSTAIN"}
+//! ```
+//!
+//! `--format csv` writes the same columns with a header row.
+
+use crate::audit::DecisionRecord;
+use crate::error::KernelError;
+use crate::normalize::Normalizer;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// Output format of `tripwired dataset`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    Jsonl,
+    Csv,
+}
+
+/// One labeled line
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Example {
+    pub normalized_line: String,
+    /// KILL or SUSTAIN
+    pub action: String,
+    pub confidence: u32,
+    /// Fingerprint of the model that judged the line
+    pub model: String,
+    /// Labeled by a correction, not the model
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub corrected: bool,
+}
+
+impl Example {
+    pub fn is_kill(&self) -> bool {
+        self.action == "KILL"
+    }
+}
+
+/// A label override (a corrections file line or a `correction` event)
+#[derive(Debug, Clone, Deserialize)]
+pub struct Correction {
+    pub decision_id: u64,
+    pub action: String,
+}
+
+/// Records left out of an export
+#[derive(Debug, Default)]
+pub struct Skipped {
+    pub filtered: u64,
+    pub errors: u64,
+    pub encrypted: u64,
+    /// Verdicts other than KILL and SUSTAIN
+    pub other: u64,
+}
+
+/// `tripwired dataset` options
+#[derive(Debug, Clone)]
+pub struct DatasetOptions {
+    pub from: PathBuf,
+    pub format: Format,
+    pub corrections: Option<PathBuf>,
+    /// Standard output if unset
+    pub out: Option<PathBuf>,
+}
+
+/// `tripwired dataset`
+pub fn run(options: &DatasetOptions) -> Result<(), KernelError> {
+    let files = audit_files(&options.from)?;
+    let mut corrections = HashMap::new();
+    for path in &files {
+        trail_corrections(path, &mut corrections)?;
+    }
+    if let Some(ref path) = options.corrections {
+        corrections.extend(read_corrections(path)?);
+    }
+    let normalizer = Normalizer::new();
+    let mut examples = Vec::new();
+    let mut skipped = Skipped::default();
+    for path in &files {
+        read_audit(path, &normalizer, &corrections, &mut examples, &mut skipped)?;
+    }
+
+    let mut out: Box<dyn Write> = match options.out {
+        Some(ref path) => Box::new(io::BufWriter::new(File::create(path)?)),
+        None => Box::new(io::stdout().lock()),
+    };
+    write(&mut out, options.format, &examples)?;
+    out.flush()?;
+
+    // Standard output may be the dataset: the summary goes to stderr
+    eprintln!(
+        "Exported {} examples ({} KILL, {} corrected) from {} file(s)",
+        examples.len(),
+        examples.iter().filter(|e| e.is_kill()).count(),
+        examples.iter().filter(|e| e.corrected).count(),
+        files.len()
+    );
+    eprintln!(
+        "Skipped {} filtered, {} LLM failures, {} encrypted, {} other verdicts",
+        skipped.filtered, skipped.errors, skipped.encrypted, skipped.other
+    );
+    Ok(())
+}
+
+/// `path`, or the `.jsonl` files in it if it is a directory (by name)
+pub fn audit_files(path: &Path) -> io::Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files: Vec<PathBuf> = std::fs::read_dir(path)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "jsonl"))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Label overrides by decision ID
+pub fn read_corrections(path: &Path) -> Result<HashMap<u64, String>, KernelError> {
+    let mut corrections = HashMap::new();
+    for (n, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let correction: Correction = serde_json::from_str(&line)
+            .map_err(|e| format!("{}:{}: {}", path.display(), n + 1, e))?;
+        if !matches!(correction.action.as_str(), "KILL" | "SUSTAIN") {
+            return Err(format!(
+                "{}:{}: action must be KILL or SUSTAIN",
+                path.display(),
+                n + 1
+            )
+            .into());
+        }
+        corrections.insert(correction.decision_id, correction.action);
+    }
+    Ok(corrections)
+}
+
+/// Add the `correction` events in audit file `path` to `corrections` (a
+/// later one for the same decision wins)
+pub fn trail_corrections(path: &Path, corrections: &mut HashMap<u64, String>) -> io::Result<()> {
+    for line in BufReader::new(File::open(path)?).lines() {
+        if let Some(Entry::Correction(correction)) = entry(&line?) {
+            corrections.insert(correction.decision_id, correction.action);
+        }
+    }
+    Ok(())
+}
+
+/// Append the examples in audit file `path` to `examples`
+pub fn read_audit(
+    path: &Path,
+    normalizer: &Normalizer,
+    corrections: &HashMap<u64, String>,
+    examples: &mut Vec<Example>,
+    skipped: &mut Skipped,
+) -> io::Result<()> {
+    for line in BufReader::new(File::open(path)?).lines() {
+        let Some(Entry::Decision(record)) = entry(&line?) else {
+            continue;
+        };
+        if let Some(example) = example(&record, normalizer, corrections, skipped) {
+            examples.push(example);
+        }
+    }
+    Ok(())
+}
+
+/// Examples in `path`: a dataset export, or an audit file with its own
+/// corrections applied
+pub fn read(path: &Path) -> io::Result<Vec<Example>> {
+    let normalizer = Normalizer::new();
+    let mut corrections = HashMap::new();
+    trail_corrections(path, &mut corrections)?;
+    let mut examples = Vec::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        if let Ok(example) = serde_json::from_str::<Example>(&line) {
+            examples.push(example);
+        } else if let Some(Entry::Decision(record)) = entry(&line) {
+            let skipped = &mut Skipped::default();
+            examples.extend(example(&record, &normalizer, &corrections, skipped));
+        }
+    }
+    Ok(examples)
+}
+
+/// What an audit line holds, as far as a dataset is concerned
+enum Entry {
+    Decision(Box<DecisionRecord>),
+    Correction(Correction),
+}
+
+/// Decision records and `correction` events (the header and other events
+/// are skipped, as are torn lines)
+fn entry(line: &str) -> Option<Entry> {
+    let value = serde_json::from_str::<serde_json::Value>(line).ok()?;
+    match value.get("event").and_then(|e| e.as_str()) {
+        Some("correction") => serde_json::from_value(value).ok().map(Entry::Correction),
+        Some(_) => None,
+        None => serde_json::from_value(value)
+            .ok()
+            .map(|record| Entry::Decision(Box::new(record))),
+    }
+}
+
+fn example(
+    record: &DecisionRecord,
+    normalizer: &Normalizer,
+    corrections: &HashMap<u64, String>,
+    skipped: &mut Skipped,
+) -> Option<Example> {
+    if record.encrypted {
+        skipped.encrypted += 1;
+        return None;
+    }
+    let (action, confidence, corrected) = match corrections.get(&record.id) {
+        Some(action) => (action.clone(), 100, true),
+        None if record.filtered => {
+            skipped.filtered += 1;
+            return None;
+        }
+        None if record.error.is_some() => {
+            skipped.errors += 1;
+            return None;
+        }
+        None => {
+            let verdict = record.verdict.as_deref().unwrap_or(&record.action);
+            if !matches!(verdict, "KILL" | "SUSTAIN") {
+                skipped.other += 1;
+                return None;
+            }
+            (verdict.to_string(), record.confidence, false)
+        }
+    };
+    Some(Example {
+        normalized_line: normalizer.template(&record.input_log),
+        action,
+        confidence,
+        model: record.model_fingerprint.clone(),
+        corrected,
+    })
+}
+
+fn write(out: &mut dyn Write, format: Format, examples: &[Example]) -> io::Result<()> {
+    match format {
+        Format::Jsonl => {
+            for example in examples {
+                serde_json::to_writer(&mut *out, example)?;
+                out.write_all(b"\n")?;
+            }
+        }
+        Format::Csv => {
+            writeln!(out, "normalized_line,action,confidence,model,corrected")?;
+            for e in examples {
+                writeln!(
+                    out,
+                    "{},{},{},{},{}",
+                    csv_field(&e.normalized_line),
+                    e.action,
+                    e.confidence,
+                    csv_field(&e.model),
+                    e.corrected
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// RFC 4180 quoting where needed
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::{AuditTrail, CorrectionEvent, Label, ModelFingerprint, RecordInput};
+    use crate::llm::Sampling;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_export() {
+        let dir = tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("audit")).unwrap();
+        let path = dir.path().join("audit").join("audit.jsonl");
+        let fp = ModelFingerprint::new("m", "http://localhost", 30, &Sampling::default());
+        let audit = AuditTrail::new(path.clone(), fp, "p").unwrap();
+        let entry = |input_log, action, filtered, error| RecordInput {
+            input_log,
+            action,
+            confidence: 90,
+            filtered,
+            error,
+            ..Default::default()
+        };
+        audit
+            .record_entry(entry("sell 100 shares at 45.20", "SUSTAIN", false, None))
+            .unwrap();
+        audit
+            .record_entry(entry("heartbeat 7", "SUSTAIN", true, None))
+            .unwrap();
+        audit
+            .record_entry(entry("wire 9000 offshore", "KILL", false, None))
+            .unwrap();
+        audit
+            .record_entry(entry("rm -rf /", "PAUSE", false, Some("llm.timeout")))
+            .unwrap();
+        audit
+            .record_entry(RecordInput {
+                verdict: Some("KILL"),
+                ..entry("sell all, now", "PAUSE", false, None)
+            })
+            .unwrap();
+        let marked = CorrectionEvent::new(1, Label::FalseNegative, "alice", None);
+        audit.record_correction(&marked).unwrap();
+        drop(audit);
+
+        // The filtered heartbeat was a miss
+        let corrections = dir.path().join("corrections.jsonl");
+        std::fs::write(
+            &corrections,
+            "{\"decision_id\": 2, \"action\": \"KILL\"}\n\n{\"decision_id\": 3, \"action\": \"SUSTAIN\"}\n",
+        )
+        .unwrap();
+        let corrections = read_corrections(&corrections).unwrap();
+        let mut all = HashMap::new();
+        trail_corrections(&path, &mut all).unwrap();
+        all.extend(corrections);
+
+        let mut examples = Vec::new();
+        let mut skipped = Skipped::default();
+        let files = audit_files(&dir.path().join("audit")).unwrap();
+        assert_eq!(files, std::slice::from_ref(&path));
+        read_audit(&path, &Normalizer::new(), &all, &mut examples, &mut skipped).unwrap();
+        let labels: Vec<_> = examples
+            .iter()
+            .map(|e| (e.normalized_line.as_str(), e.action.as_str(), e.corrected))
+            .collect();
+        assert_eq!(
+            labels,
+            [
+                ("sell <num> shares at <num>", "KILL", true),
+                ("heartbeat <num>", "KILL", true),
+                ("wire <num> offshore", "SUSTAIN", true),
+                ("sell all, now", "KILL", false),
+            ]
+        );
+        assert_eq!(skipped.errors, 1);
+        assert!(examples[0].model.starts_with("m@"));
+        // Read for training, an audit file brings its own corrections
+        let trained: Vec<_> = read(&path)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.corrected)
+            .collect();
+        assert_eq!(trained, [true, false, false]);
+
+        // Exports read back as examples
+        let mut jsonl = Vec::new();
+        write(&mut jsonl, Format::Jsonl, &examples).unwrap();
+        let export = dir.path().join("dataset.jsonl");
+        std::fs::write(&export, &jsonl).unwrap();
+        assert_eq!(read(&export).unwrap(), examples);
+        let mut csv = Vec::new();
+        write(&mut csv, Format::Csv, &examples).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert!(csv.contains("\n\"sell all, now\",KILL,90,"));
+    }
+}
@@ -0,0 +1,495 @@
+//! Docker - Container Logs as an Agent Connection
+//!
+//! Agents that run in containers should not need a pipe or socket baked
+//! into their image. `--watch-container <name>` (or `container = "<name>"`
+//! in a `[channel.<name>]` table) follows the container's stdout and stderr
+//! over the Docker Engine API and feeds them into the pipeline; KILL and
+//! PAUSE decisions kill (SIGKILL) or pause the container through the same
+//! API. When the container stops, the kernel waits for it to run again
+//! (restart policy, redeploy under the same name) and re-attaches from the
+//! moment the previous stream ended.
+//!
+//! The daemon is found through `DOCKER_HOST` (`unix://`, `tcp://` or, on
+//! Windows, `npipe://`), by default the local socket. `tcp://` is plain
+//! HTTP: TLS-protected daemons are not supported.
+
+use serde::Deserialize;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tracing::{debug, warn};
+
+/// How often a stopped or missing container is inspected again
+const RETRY: Duration = Duration::from_secs(2);
+
+/// Where the Docker daemon listens
+#[derive(Debug, Clone, PartialEq)]
+enum Host {
+    #[cfg(unix)]
+    Unix(String),
+    Tcp(String),
+    #[cfg(windows)]
+    Pipe(String),
+}
+
+/// Parse `DOCKER_HOST` (unset: the platform's local daemon)
+fn parse_host(docker_host: Option<&str>) -> Result<Host, String> {
+    let Some(host) = docker_host.filter(|h| !h.is_empty()) else {
+        #[cfg(unix)]
+        return Ok(Host::Unix("/var/run/docker.sock".to_string()));
+        #[cfg(windows)]
+        return Ok(Host::Pipe(r"\\.\pipe\docker_engine".to_string()));
+    };
+    #[cfg(unix)]
+    if let Some(path) = host.strip_prefix("unix://") {
+        return Ok(Host::Unix(path.to_string()));
+    }
+    #[cfg(windows)]
+    if let Some(pipe) = host.strip_prefix("npipe://") {
+        return Ok(Host::Pipe(pipe.replace('/', "\\")));
+    }
+    match host.strip_prefix("tcp://") {
+        Some(addr) => Ok(Host::Tcp(addr.trim_end_matches('/').to_string())),
+        None => Err(format!("DOCKER_HOST '{}' is not supported", host)),
+    }
+}
+
+/// Check a container name or ID (for clap; it ends up in API paths)
+pub fn parse_name(s: &str) -> Result<String, String> {
+    let valid = s.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'));
+    if valid {
+        Ok(s.to_string())
+    } else {
+        Err(format!("invalid container name '{}'", s))
+    }
+}
+
+/// The parts of `GET /containers/{name}/json` we use
+#[derive(Debug, Clone, Deserialize)]
+pub struct Container {
+    #[serde(rename = "Id")]
+    pub id: String,
+    #[serde(rename = "State")]
+    state: ContainerState,
+    #[serde(rename = "Config")]
+    config: ContainerConfig,
+}
+
+impl Container {
+    pub fn running(&self) -> bool {
+        self.state.running
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ContainerState {
+    #[serde(rename = "Running")]
+    running: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ContainerConfig {
+    /// TTY containers stream raw output instead of multiplexed frames
+    #[serde(rename = "Tty", default)]
+    tty: bool,
+}
+
+trait Conn: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Conn for T {}
+
+struct Response {
+    status: u16,
+    chunked: bool,
+    body: BufReader<Box<dyn Conn>>,
+}
+
+impl Response {
+    /// Fail with the daemon's message on a non-2xx status (404: `NotFound`)
+    async fn ok(self) -> io::Result<Self> {
+        if (200..300).contains(&self.status) {
+            return Ok(self);
+        }
+        let status = self.status;
+        let body = self.bytes().await.unwrap_or_default();
+        #[derive(Deserialize)]
+        struct ApiError {
+            message: String,
+        }
+        let message = serde_json::from_slice::<ApiError>(&body)
+            .map(|e| e.message)
+            .unwrap_or_else(|_| String::from_utf8_lossy(&body).trim().to_string());
+        let kind = match status {
+            404 => io::ErrorKind::NotFound,
+            _ => io::ErrorKind::Other,
+        };
+        Err(io::Error::new(
+            kind,
+            format!("{} (HTTP {})", message, status),
+        ))
+    }
+
+    /// The whole (de-chunked) body
+    async fn bytes(mut self) -> io::Result<Vec<u8>> {
+        let mut raw = Vec::new();
+        self.body.read_to_end(&mut raw).await?;
+        let mut body = Vec::new();
+        Decoder::new(self.chunked, false)
+            .feed(&raw, &mut body)
+            .map_err(io::Error::other)?;
+        Ok(body)
+    }
+}
+
+/// Docker Engine API client
+#[derive(Debug, Clone)]
+pub struct Docker {
+    host: Host,
+}
+
+impl Docker {
+    pub fn from_env() -> io::Result<Self> {
+        let host =
+            parse_host(std::env::var("DOCKER_HOST").ok().as_deref()).map_err(io::Error::other)?;
+        Ok(Self { host })
+    }
+
+    async fn connect(&self) -> io::Result<Box<dyn Conn>> {
+        Ok(match self.host {
+            #[cfg(unix)]
+            Host::Unix(ref path) => Box::new(tokio::net::UnixStream::connect(path).await?),
+            Host::Tcp(ref addr) => Box::new(tokio::net::TcpStream::connect(addr).await?),
+            #[cfg(windows)]
+            Host::Pipe(ref name) => {
+                Box::new(tokio::net::windows::named_pipe::ClientOptions::new().open(name)?)
+            }
+        })
+    }
+
+    /// One request per connection (`Connection: close`)
+    async fn request(&self, method: &str, path: &str) -> io::Result<Response> {
+        self.send(method, path, "").await
+    }
+
+    /// `request` with a JSON `body`
+    async fn send(&self, method: &str, path: &str, body: &str) -> io::Result<Response> {
+        let mut conn = self.connect().await?;
+        let head = format!(
+            "{} {} HTTP/1.1\r\nHost: docker\r\nConnection: close\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n",
+            method,
+            path,
+            body.len()
+        );
+        conn.write_all(head.as_bytes()).await?;
+        conn.write_all(body.as_bytes()).await?;
+
+        let mut body = BufReader::new(conn);
+        let mut line = String::new();
+        body.read_line(&mut line).await?;
+        let status = line
+            .split_whitespace()
+            .nth(1)
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| io::Error::other(format!("bad HTTP status line '{}'", line.trim())))?;
+        let mut chunked = false;
+        loop {
+            line.clear();
+            if body.read_line(&mut line).await? == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            let header = line.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("transfer-encoding")
+                    && value.trim().eq_ignore_ascii_case("chunked")
+                {
+                    chunked = true;
+                }
+            }
+        }
+        Ok(Response {
+            status,
+            chunked,
+            body,
+        })
+    }
+
+    pub async fn inspect(&self, name: &str) -> io::Result<Container> {
+        let path = format!("/containers/{}/json", name);
+        let body = self
+            .request("GET", &path)
+            .await?
+            .ok()
+            .await?
+            .bytes()
+            .await?;
+        serde_json::from_slice(&body).map_err(io::Error::other)
+    }
+
+    /// Inspect until the container runs; problems are logged when they change
+    pub async fn wait_running(&self, name: &str) -> Container {
+        let mut last = String::new();
+        loop {
+            let problem = match self.inspect(name).await {
+                Ok(container) if container.state.running => return container,
+                Ok(_) => "not running".to_string(),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => "not found".to_string(),
+                Err(e) => e.to_string(),
+            };
+            if problem != last {
+                warn!("🐳 Container {}: {} - waiting", name, problem);
+                last = problem;
+            }
+            tokio::time::sleep(RETRY).await;
+        }
+    }
+
+    /// Follow the container's stdout and stderr: new lines only, or
+    /// everything since `since` (Unix seconds)
+    pub async fn logs(
+        &self,
+        container: &Container,
+        since: Option<f64>,
+    ) -> io::Result<impl AsyncRead + Send + Unpin> {
+        let from = match since {
+            Some(since) => format!("since={:.6}", since),
+            None => "tail=0".to_string(),
+        };
+        let path = format!(
+            "/containers/{}/logs?follow=1&stdout=1&stderr=1&{}",
+            container.id, from
+        );
+        let response = self.request("GET", &path).await?.ok().await?;
+
+        let (mut writer, reader) = tokio::io::duplex(64 * 1024);
+        let mut decoder = Decoder::new(response.chunked, !container.config.tty);
+        let mut body = response.body;
+        tokio::spawn(async move {
+            let mut buf = vec![0u8; 8192];
+            let mut out = Vec::new();
+            while !decoder.done() {
+                let n = match body.read(&mut buf).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => n,
+                };
+                out.clear();
+                if let Err(e) = decoder.feed(&buf[..n], &mut out) {
+                    warn!("🐳 Log stream: {}", e);
+                    break;
+                }
+                if writer.write_all(&out).await.is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(reader)
+    }
+
+    pub async fn kill(&self, name: &str) -> io::Result<()> {
+        let path = format!("/containers/{}/kill?signal=SIGKILL", name);
+        self.request("POST", &path).await?.ok().await?;
+        Ok(())
+    }
+
+    pub async fn pause(&self, name: &str) -> io::Result<()> {
+        let path = format!("/containers/{}/pause", name);
+        self.request("POST", &path).await?.ok().await?;
+        debug!("🐳 Paused container {}", name);
+        Ok(())
+    }
+
+    /// Checkpoint the container as `checkpoint` in `dir`, leaving it
+    /// running (CRIU; the daemon needs experimental features)
+    pub async fn checkpoint(&self, name: &str, checkpoint: &str, dir: &Path) -> io::Result<()> {
+        let path = format!("/containers/{}/checkpoints", name);
+        let body = serde_json::json!({
+            "CheckpointID": checkpoint,
+            "CheckpointDir": dir,
+            "Exit": false,
+        });
+        self.send("POST", &path, &body.to_string())
+            .await?
+            .ok()
+            .await?;
+        debug!("🐳 Checkpointed container {} as {}", name, checkpoint);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Chunk {
+    /// Expecting a size line
+    Size,
+    /// Bytes of the current chunk still to come
+    Data(usize),
+    /// Expecting the CRLF after a chunk
+    End,
+    /// The last (empty) chunk was read
+    Done,
+}
+
+/// Strips HTTP chunking and Docker's stdout/stderr framing (an 8-byte
+/// header: stream, 3 zero bytes, big-endian length) from a body that
+/// arrives in arbitrary pieces
+struct Decoder {
+    chunked: bool,
+    multiplexed: bool,
+    chunk: Chunk,
+    /// Received, not yet de-chunked
+    raw: Vec<u8>,
+    /// De-chunked, not yet a complete frame
+    frames: Vec<u8>,
+}
+
+impl Decoder {
+    fn new(chunked: bool, multiplexed: bool) -> Self {
+        Self {
+            chunked,
+            multiplexed,
+            chunk: Chunk::Size,
+            raw: Vec::new(),
+            frames: Vec::new(),
+        }
+    }
+
+    fn done(&self) -> bool {
+        self.chunk == Chunk::Done
+    }
+
+    /// Decode `data`, appending complete payload to `out`
+    fn feed(&mut self, data: &[u8], out: &mut Vec<u8>) -> Result<(), String> {
+        if self.chunked {
+            self.raw.extend_from_slice(data);
+            let mut pos = 0;
+            loop {
+                let rest = &self.raw[pos..];
+                match self.chunk {
+                    Chunk::Size => {
+                        let Some(end) = rest.windows(2).position(|w| w == b"\r\n") else {
+                            break;
+                        };
+                        let line = String::from_utf8_lossy(&rest[..end]);
+                        let size = line.split(';').next().unwrap_or_default().trim();
+                        let size = usize::from_str_radix(size, 16)
+                            .map_err(|_| format!("bad chunk size '{}'", line))?;
+                        pos += end + 2;
+                        self.chunk = match size {
+                            0 => Chunk::Done,
+                            n => Chunk::Data(n),
+                        };
+                    }
+                    Chunk::Data(n) => {
+                        let take = n.min(rest.len());
+                        if take == 0 {
+                            break;
+                        }
+                        self.frames.extend_from_slice(&rest[..take]);
+                        pos += take;
+                        self.chunk = match n - take {
+                            0 => Chunk::End,
+                            left => Chunk::Data(left),
+                        };
+                    }
+                    Chunk::End if rest.len() >= 2 => {
+                        pos += 2;
+                        self.chunk = Chunk::Size;
+                    }
+                    Chunk::End | Chunk::Done => break,
+                }
+            }
+            self.raw.drain(..pos);
+        } else {
+            self.frames.extend_from_slice(data);
+        }
+
+        if !self.multiplexed {
+            out.append(&mut self.frames);
+            return Ok(());
+        }
+        let mut pos = 0;
+        while let Some(header) = self.frames.get(pos..pos + 8) {
+            let len = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+            let Some(payload) = self.frames.get(pos + 8..pos + 8 + len) else {
+                break;
+            };
+            out.extend_from_slice(payload);
+            pos += 8 + len;
+        }
+        self.frames.drain(..pos);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(stream: u8, payload: &str) -> Vec<u8> {
+        let mut frame = vec![stream, 0, 0, 0];
+        frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        frame.extend_from_slice(payload.as_bytes());
+        frame
+    }
+
+    #[test]
+    fn test_decoder() {
+        let mut frames = frame(1, "order placed\n");
+        frames.extend(frame(2, "exposure 5000%\n"));
+        let mut body = format!("{:x}\r\n", 10).into_bytes();
+        body.extend_from_slice(&frames[..10]);
+        body.extend_from_slice(format!("\r\n{:x};ext=1\r\n", frames.len() - 10).as_bytes());
+        body.extend_from_slice(&frames[10..]);
+        body.extend_from_slice(b"\r\n0\r\n\r\n");
+
+        // Fed one byte at a time: frames and chunks split anywhere
+        let mut decoder = Decoder::new(true, true);
+        let mut out = Vec::new();
+        for byte in &body {
+            decoder.feed(std::slice::from_ref(byte), &mut out).unwrap();
+        }
+        assert_eq!(out, b"order placed\nexposure 5000%\n");
+        assert!(decoder.done());
+
+        // TTY container without chunking: raw passthrough
+        let mut decoder = Decoder::new(false, false);
+        let mut out = Vec::new();
+        decoder.feed(b"rm -rf /\n", &mut out).unwrap();
+        assert_eq!(out, b"rm -rf /\n");
+
+        let mut decoder = Decoder::new(true, false);
+        assert!(decoder.feed(b"zz\r\n", &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_parse_host() {
+        #[cfg(unix)]
+        {
+            assert_eq!(
+                parse_host(None),
+                Ok(Host::Unix("/var/run/docker.sock".to_string()))
+            );
+            assert_eq!(
+                parse_host(Some("unix:///run/user/1000/docker.sock")),
+                Ok(Host::Unix("/run/user/1000/docker.sock".to_string()))
+            );
+        }
+        assert_eq!(
+            parse_host(Some("tcp://10.0.0.5:2375")),
+            Ok(Host::Tcp("10.0.0.5:2375".to_string()))
+        );
+        assert!(parse_host(Some("ssh://host")).is_err());
+    }
+
+    #[test]
+    fn test_parse_name() {
+        assert!(parse_name("trader-agent_1.2").is_ok());
+        assert!(parse_name("4f1c2a").is_ok());
+        assert!(parse_name("../images/json").is_err());
+        assert!(parse_name("agent?all=1").is_err());
+        assert!(parse_name("").is_err());
+    }
+}
@@ -0,0 +1,304 @@
+//! Kill Drill - Rehearsing the Kill Path on a Sacrificial Process
+//!
+//! A kill-switch that has never fired is a hope, not a control. `tripwired
+//! drill` spawns a throwaway process and kills it through the same path a
+//! KILL decision takes: the decision is audited, the target is signaled
+//! with `kill -9` / `taskkill /F`, and the drill waits for the process to
+//! exit. It then reads the audit file back and prints a readiness report:
+//!
+//! ```text
+//! Kill drill (3 runs, audit tripwired-drill.jsonl)
+//!   ✓ run 1: decision → exit 3ms (audit 0ms)
+//!   ✓ run 2: decision → exit 2ms (audit 0ms)
+//!   ✓ run 3: decision → exit 2ms (audit 0ms)
+//!   ✓ latency: max 3ms (limit 1000ms)
+//!   ✓ audit: 3/3 KILL records and shutdown footer read back
+//!   ✓ target PID 4242: signal permitted
+//!   READY
+//! ```
+//!
+//! `--target-pid` names the production target: it is only checked for
+//! signal permission (`kill -0`; existence on Windows), never signaled.
+//! Any failed check exits non-zero, for cron / CI schedules.
+
+use crate::audit::{AuditTrail, DecisionRecord, ModelFingerprint, RecordInput};
+use crate::error::KernelError;
+use crate::llm::Sampling;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+/// Longest a signaled process may take to exit before the run fails
+const EXIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// `tripwired drill` options
+#[derive(Debug, Clone)]
+pub struct DrillOptions {
+    pub runs: u32,
+    /// Decision-to-exit latency above this fails the drill
+    pub max_latency_ms: u64,
+    /// Drill audit trail (not the production one)
+    pub audit_log: PathBuf,
+    /// Production target checked for signal permission
+    pub target_pid: Option<u32>,
+}
+
+/// One check in the report
+#[derive(Debug)]
+pub struct Check {
+    pub ok: bool,
+    pub line: String,
+}
+
+impl Check {
+    fn new(ok: bool, line: String) -> Self {
+        Self { ok, line }
+    }
+}
+
+/// `tripwired drill`
+pub async fn run(options: &DrillOptions) -> Result<(), KernelError> {
+    println!(
+        "Kill drill ({} runs, audit {})",
+        options.runs,
+        options.audit_log.display()
+    );
+    let checks = drill(options).await?;
+    for check in &checks {
+        println!("  {} {}", if check.ok { "✓" } else { "✗" }, check.line);
+    }
+    let failed = checks.iter().filter(|c| !c.ok).count();
+    if failed > 0 {
+        println!("  NOT READY");
+        return Err(format!("{} drill check(s) failed", failed).into());
+    }
+    println!("  READY");
+    Ok(())
+}
+
+/// Run the drill; `Err` only if the drill itself could not be set up
+pub async fn drill(options: &DrillOptions) -> Result<Vec<Check>, KernelError> {
+    let fingerprint = ModelFingerprint::new("drill", "-", 0, &Sampling::default());
+    let audit = AuditTrail::new(options.audit_log.clone(), fingerprint, "drill")?;
+
+    let mut checks = Vec::new();
+    let mut latencies = Vec::new();
+    let mut ids = Vec::new();
+    for run in 1..=options.runs {
+        let (check, result) = rehearse(&audit, run).await;
+        if let Some((id, latency)) = result {
+            ids.push(id);
+            latencies.push(latency);
+        }
+        checks.push(check);
+    }
+
+    if let Some(max) = latencies.iter().max() {
+        let ms = max.as_millis() as u64;
+        checks.push(Check::new(
+            ms <= options.max_latency_ms && latencies.len() == options.runs as usize,
+            format!("latency: max {}ms (limit {}ms)", ms, options.max_latency_ms),
+        ));
+    }
+
+    let summary = serde_json::json!({ "runs": options.runs, "killed": latencies.len() });
+    checks.push(match audit.record_shutdown("drill", true, &summary) {
+        Ok(()) => read_back(audit.path(), &ids, options.runs),
+        Err(e) => Check::new(false, format!("audit: shutdown footer not written: {}", e)),
+    });
+
+    if let Some(pid) = options.target_pid {
+        checks.push(match signal_permitted(pid) {
+            Ok(note) => Check::new(true, format!("target PID {}: {}", pid, note)),
+            Err(e) => Check::new(false, format!("target PID {}: {}", pid, e)),
+        });
+    }
+    Ok(checks)
+}
+
+/// Spawn a sacrificial process, audit a KILL for it and kill it; the
+/// decision id and decision-to-exit latency on success
+async fn rehearse(audit: &AuditTrail, run: u32) -> (Check, Option<(u64, Duration)>) {
+    let mut victim = match sacrificial().spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            let line = format!("run {}: cannot spawn a sacrificial process: {}", run, e);
+            return (Check::new(false, line), None);
+        }
+    };
+    let Some(pid) = victim.id() else {
+        return (
+            Check::new(false, format!("run {}: process exited early", run)),
+            None,
+        );
+    };
+
+    // Same order as a live KILL: audit the decision, then signal the target
+    let start = Instant::now();
+    let input = format!("tripwired drill run {} (PID {})", run, pid);
+    let id = match audit.record_entry(RecordInput {
+        input_log: &input,
+        action: "KILL",
+        confidence: 100,
+        filtered: true,
+        rule: Some("drill"),
+        reason: Some("kill rehearsal"),
+        ..Default::default()
+    }) {
+        Ok(id) => id,
+        Err(e) => {
+            let _ = victim.start_kill();
+            return (
+                Check::new(false, format!("run {}: audit write failed: {}", run, e)),
+                None,
+            );
+        }
+    };
+    let audited = start.elapsed();
+    let Some(mut signal) = crate::kill_process(pid) else {
+        let _ = victim.start_kill();
+        let line = format!("run {}: kill command could not be started", run);
+        return (Check::new(false, line), None);
+    };
+
+    let exited = tokio::time::timeout(EXIT_TIMEOUT, victim.wait()).await;
+    let latency = start.elapsed();
+    let status = signal.wait();
+    match exited {
+        Ok(Ok(_)) => {
+            let line = format!(
+                "run {}: decision → exit {}ms (audit {}ms)",
+                run,
+                latency.as_millis(),
+                audited.as_millis()
+            );
+            (Check::new(true, line), Some((id, latency)))
+        }
+        _ => {
+            let _ = victim.start_kill();
+            let status = status.map_or_else(|e| e.to_string(), |s| s.to_string());
+            let line = format!(
+                "run {}: PID {} still running {}s after the kill command ({})",
+                run,
+                pid,
+                EXIT_TIMEOUT.as_secs(),
+                status
+            );
+            (Check::new(false, line), None)
+        }
+    }
+}
+
+/// A process that idles until killed
+#[cfg(unix)]
+fn sacrificial() -> tokio::process::Command {
+    let mut command = tokio::process::Command::new("sleep");
+    command.arg("3600");
+    command.stdout(Stdio::null()).kill_on_drop(true);
+    command
+}
+
+/// A process that idles until killed
+#[cfg(windows)]
+fn sacrificial() -> tokio::process::Command {
+    let mut command = tokio::process::Command::new("ping");
+    command.args(["-n", "3601", "127.0.0.1"]);
+    command.stdout(Stdio::null()).kill_on_drop(true);
+    command
+}
+
+/// Check the drill's KILL records and the footer made it to disk
+fn read_back(path: &Path, ids: &[u64], runs: u32) -> Check {
+    let file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) => return Check::new(false, format!("audit: cannot reopen: {}", e)),
+    };
+    let mut found = 0;
+    let mut footer = false;
+    for line in std::io::BufReader::new(file).lines() {
+        let Ok(line) = line else {
+            return Check::new(false, "audit: unreadable line".to_string());
+        };
+        footer = line.starts_with("{\"event\":\"shutdown\"");
+        if let Ok(record) = serde_json::from_str::<DecisionRecord>(&line) {
+            found += (ids.contains(&record.id) && record.action == "KILL") as u32;
+        }
+    }
+    Check::new(
+        found == runs && footer,
+        format!(
+            "audit: {}/{} KILL records{} read back",
+            found,
+            runs,
+            if footer {
+                " and shutdown footer"
+            } else {
+                ", no shutdown footer"
+            }
+        ),
+    )
+}
+
+/// Whether this process may signal `pid`
+#[cfg(unix)]
+pub fn signal_permitted(pid: u32) -> Result<&'static str, String> {
+    let output = std::process::Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .output()
+        .map_err(|e| format!("cannot run kill: {}", e))?;
+    if output.status.success() {
+        return Ok("signal permitted");
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Err(match stderr.trim() {
+        "" => "no such process or not permitted".to_string(),
+        reason => reason.to_string(),
+    })
+}
+
+/// Whether `pid` exists (Windows has no permission probe short of killing)
+#[cfg(windows)]
+pub fn signal_permitted(pid: u32) -> Result<&'static str, String> {
+    let output = std::process::Command::new("tasklist")
+        .args(["/FI", &format!("PID eq {}", pid), "/NH"])
+        .output()
+        .map_err(|e| format!("cannot run tasklist: {}", e))?;
+    let listed = String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .any(|field| field == pid.to_string());
+    match listed {
+        true => Ok("running (permission not checked on Windows)"),
+        false => Err("no such process".to_string()),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_drill() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = DrillOptions {
+            runs: 2,
+            max_latency_ms: 5000,
+            audit_log: dir.path().join("drill.jsonl"),
+            target_pid: Some(std::process::id()),
+        };
+        let checks = drill(&options).await.unwrap();
+        assert!(checks.iter().all(|c| c.ok), "{:?}", checks);
+        assert!(checks
+            .iter()
+            .any(|c| c.line == "audit: 2/2 KILL records and shutdown footer read back"));
+
+        // A PID that cannot exist
+        let options = DrillOptions {
+            runs: 1,
+            target_pid: Some(u32::MAX / 2),
+            ..options
+        };
+        let checks = drill(&options).await.unwrap();
+        assert!(!checks.last().unwrap().ok);
+    }
+}
@@ -0,0 +1,706 @@
+//! Egress Monitor - Exfiltration That Never Logs It
+//!
+//! A rogue agent uploading a database will not announce it in its log
+//! stream. With `[egress]` enabled, the kernel watches the outbound
+//! connections of `--target-pid` and its direct children every
+//! `interval_ms` and escalates a synthetic `EGRESS` line:
+//!
+//! - `new_destinations`: a remote address:port it has not talked to before
+//!   (loopback and `allow` networks excepted). Destinations seen during the
+//!   first `learn_ms` are the baseline and never reported
+//! - `max_sent_mb`: more than that many MB sent within `window_ms`
+//!
+//! Sources: on Linux, the process's sockets (`/proc/<pid>/fd`) matched
+//! against `/proc/<pid>/net/{tcp,udp}{,6}` - connected sockets only, so a
+//! datagram sent with `sendto` is missed - and the transmit counters of its
+//! network namespace (`/proc/<pid>/net/dev`, loopback excluded). Volumes are
+//! exact for an agent in its own namespace (a container) and host-wide
+//! otherwise. On Windows, the Microsoft-Windows-Kernel-Network ETW provider
+//! (needs Administrator) attributes every TCP/UDP send to its process.
+//!
+//! The volume threshold fires once, then again only after it has cleared.
+//! The lines are judged like rate anomalies (`action`: `analyze` asks the
+//! LLM, `kill` acts at once) in a session named `egress:<pid>`.
+//!
+//! ```toml
+//! [egress]
+//! interval_ms = 2000
+//! learn_ms = 30000
+//! allow = ["10.0.0.0/8", "192.168.1.20/32"]
+//! max_sent_mb = 50
+//! window_ms = 60000
+//! action = "analyze"
+//! ```
+
+use crate::filter::RuleAction;
+use serde::Deserialize;
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+
+const MB: u64 = 1024 * 1024;
+
+/// `[egress]` table
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct EgressConfig {
+    /// Sampling period (milliseconds); 0 disables the monitor
+    pub interval_ms: u64,
+    /// Report destinations not seen before
+    pub new_destinations: bool,
+    /// Destinations seen this long after the first sample are the baseline
+    pub learn_ms: u64,
+    /// Networks never reported as new destinations (`10.0.0.0/8`, `::1`)
+    pub allow: Vec<String>,
+    /// MB sent within the window
+    pub max_sent_mb: Option<u64>,
+    /// Window for `max_sent_mb` (milliseconds)
+    pub window_ms: u64,
+    /// `analyze` sends the synthetic line to the LLM, `kill` acts immediately
+    pub action: RuleAction,
+}
+
+impl Default for EgressConfig {
+    fn default() -> Self {
+        Self {
+            interval_ms: 0,
+            new_destinations: true,
+            learn_ms: 0,
+            allow: Vec::new(),
+            max_sent_mb: None,
+            window_ms: 60_000,
+            action: RuleAction::Analyze,
+        }
+    }
+}
+
+impl EgressConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.enabled() {
+            return Ok(());
+        }
+        if !self.new_destinations && self.max_sent_mb.is_none() {
+            return Err("egress: needs new_destinations or max_sent_mb".to_string());
+        }
+        if self.window_ms < self.interval_ms {
+            return Err("egress: window_ms must be at least interval_ms".to_string());
+        }
+        for network in &self.allow {
+            Network::parse(network)
+                .ok_or_else(|| format!("egress: allow: invalid network '{}'", network))?;
+        }
+        Ok(())
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.interval_ms > 0
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_millis(self.interval_ms)
+    }
+}
+
+/// An `allow` entry: an address with an optional prefix length
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Network {
+    addr: IpAddr,
+    prefix: u32,
+}
+
+impl Network {
+    fn parse(s: &str) -> Option<Self> {
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr.parse().ok()?, Some(prefix.parse().ok()?)),
+            None => (s.parse().ok()?, None),
+        };
+        let bits = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        let prefix = prefix.unwrap_or(bits);
+        (prefix <= bits).then_some(Self { addr, prefix })
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// A remote endpoint the target talks to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Destination {
+    /// `tcp` or `udp`
+    pub proto: &'static str,
+    pub addr: SocketAddr,
+}
+
+impl Destination {
+    /// IPv4-mapped IPv6 addresses (dual-stack sockets) become IPv4
+    pub fn new(proto: &'static str, ip: IpAddr, port: u16) -> Self {
+        Self {
+            proto,
+            addr: SocketAddr::new(ip.to_canonical(), port),
+        }
+    }
+}
+
+impl fmt::Display for Destination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.proto)
+    }
+}
+
+/// One reading of the target's network activity
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Observation {
+    /// Remote endpoints of its outbound connections
+    pub destinations: HashSet<Destination>,
+    /// Bytes sent since the previous reading (`None` = not measurable)
+    pub sent: Option<u64>,
+}
+
+/// A reported destination or crossed volume threshold
+#[derive(Debug, PartialEq)]
+pub struct EgressHit {
+    /// `egress-destination` or `egress-volume`
+    pub id: &'static str,
+    pub line: String,
+}
+
+/// Destination and volume state for one target process
+pub struct EgressMonitor {
+    config: EgressConfig,
+    pid: u32,
+    allow: Vec<Network>,
+    started: Option<Instant>,
+    /// Destinations already seen (or baselined)
+    known: HashSet<Destination>,
+    /// Bytes sent per reading within the window, oldest first
+    sent: VecDeque<(Instant, u64)>,
+    /// The volume threshold is currently crossed (fires again once cleared)
+    over: bool,
+}
+
+impl EgressMonitor {
+    pub fn new(config: &EgressConfig, pid: u32) -> Self {
+        Self {
+            config: config.clone(),
+            pid,
+            allow: config
+                .allow
+                .iter()
+                .map(|n| Network::parse(n).expect("Invalid allow network"))
+                .collect(),
+            started: None,
+            known: HashSet::new(),
+            sent: VecDeque::new(),
+            over: false,
+        }
+    }
+
+    /// Record a reading; returns the new destinations and crossed threshold
+    pub fn observe(&mut self, observation: Observation, now: Instant) -> Vec<EgressHit> {
+        let started = *self.started.get_or_insert(now);
+        let learning = now.duration_since(started) < Duration::from_millis(self.config.learn_ms);
+        let pid = self.pid;
+        let mut hits = Vec::new();
+
+        let mut fresh: Vec<Destination> = observation
+            .destinations
+            .into_iter()
+            .filter(|d| {
+                let ip = d.addr.ip();
+                !ip.is_loopback() && !self.allow.iter().any(|n| n.contains(ip))
+            })
+            .filter(|d| self.known.insert(*d))
+            .collect();
+        fresh.sort();
+        if self.config.new_destinations && !learning {
+            hits.extend(fresh.into_iter().map(|d| EgressHit {
+                id: "egress-destination",
+                line: format!("EGRESS pid {}: new destination {}", pid, d),
+            }));
+        }
+
+        if let (Some(max), Some(sent)) = (self.config.max_sent_mb, observation.sent) {
+            let window = Duration::from_millis(self.config.window_ms);
+            self.sent.push_back((now, sent));
+            while self
+                .sent
+                .front()
+                .is_some_and(|(at, _)| now.duration_since(*at) >= window)
+            {
+                self.sent.pop_front();
+            }
+            let total: u64 = self.sent.iter().map(|(_, bytes)| bytes).sum();
+            let over = total > max * MB;
+            if over && !self.over {
+                // Each reading covers the interval before it
+                let span = now.duration_since(self.sent[0].0) + self.config.interval();
+                hits.push(EgressHit {
+                    id: "egress-volume",
+                    line: format!(
+                        "EGRESS pid {}: {} MB sent in {}s above max {} MB",
+                        pid,
+                        total / MB,
+                        span.as_secs(),
+                        max
+                    ),
+                });
+            }
+            self.over = over;
+        }
+        hits
+    }
+}
+
+/// Reads the target's sockets and its namespace's transmit counters
+#[cfg(target_os = "linux")]
+pub struct Probe {
+    pid: u32,
+    /// Transmit counter at the previous reading
+    sent: u64,
+}
+
+#[cfg(target_os = "linux")]
+impl Probe {
+    pub fn open(pid: u32) -> std::io::Result<Self> {
+        Ok(Self {
+            pid,
+            sent: transmitted(pid)?,
+        })
+    }
+
+    /// Outbound connections of the target and its children, bytes sent since the last call
+    pub fn read(&mut self) -> std::io::Result<Observation> {
+        let mut inodes = HashSet::new();
+        let pids = std::iter::once(self.pid).chain(crate::resource::children(self.pid)?);
+        for pid in pids {
+            let fds = match std::fs::read_dir(format!("/proc/{}/fd", pid)) {
+                Ok(fds) => fds,
+                Err(e) if pid == self.pid => return Err(e),
+                // A child may exit between listing and reading
+                Err(_) => continue,
+            };
+            for fd in fds.flatten() {
+                let Ok(link) = std::fs::read_link(fd.path()) else {
+                    continue;
+                };
+                let inode = link
+                    .to_str()
+                    .and_then(|l| l.strip_prefix("socket:["))
+                    .and_then(|l| l.strip_suffix(']'))
+                    .and_then(|n| n.parse::<u64>().ok());
+                inodes.extend(inode);
+            }
+        }
+
+        let mut destinations = HashSet::new();
+        for (table, proto) in [
+            ("tcp", "tcp"),
+            ("tcp6", "tcp"),
+            ("udp", "udp"),
+            ("udp6", "udp"),
+        ] {
+            // tcp6 / udp6 are missing without IPv6
+            let Ok(table) = std::fs::read_to_string(format!("/proc/{}/net/{}", self.pid, table))
+            else {
+                continue;
+            };
+            destinations.extend(parse_sockets(&table, proto, &inodes));
+        }
+
+        let total = transmitted(self.pid)?;
+        // Counters restart when an interface goes away
+        let sent = total.saturating_sub(std::mem::replace(&mut self.sent, total));
+        Ok(Observation {
+            destinations,
+            sent: Some(sent),
+        })
+    }
+}
+
+/// Outbound destinations of the sockets `inodes` in a `/proc/net/{tcp,udp}` table
+#[cfg(target_os = "linux")]
+fn parse_sockets(table: &str, proto: &'static str, inodes: &HashSet<u64>) -> Vec<Destination> {
+    const ESTABLISHED: u8 = 0x01;
+    const SYN_SENT: u8 = 0x02;
+    const LISTEN: u8 = 0x0A;
+
+    // sl local_address rem_address st tx_queue:rx_queue tr:tm->when retrnsmt uid timeout inode
+    let sockets: Vec<(SocketAddr, SocketAddr, u8)> = table
+        .lines()
+        .skip(1)
+        .filter_map(|row| {
+            let fields: Vec<&str> = row.split_whitespace().collect();
+            let inode: u64 = fields.get(9)?.parse().ok()?;
+            if !inodes.contains(&inode) {
+                return None;
+            }
+            Some((
+                parse_address(fields.get(1)?)?,
+                parse_address(fields.get(2)?)?,
+                u8::from_str_radix(fields.get(3)?, 16).ok()?,
+            ))
+        })
+        .collect();
+    // Connections accepted on its own listening ports are inbound
+    let listening: HashSet<u16> = sockets
+        .iter()
+        .filter(|(_, _, state)| proto == "tcp" && *state == LISTEN)
+        .map(|(local, _, _)| local.port())
+        .collect();
+    sockets
+        .into_iter()
+        .filter(|(local, remote, state)| {
+            let outbound = match proto {
+                "tcp" => {
+                    matches!(*state, ESTABLISHED | SYN_SENT) && !listening.contains(&local.port())
+                }
+                // A connected UDP socket
+                _ => *state == ESTABLISHED,
+            };
+            outbound && !remote.ip().is_unspecified()
+        })
+        .map(|(_, remote, _)| Destination::new(proto, remote.ip(), remote.port()))
+        .collect()
+}
+
+/// `0100007F:1F90` (IPv4) or 32 hex digits (IPv6) and a port: 32-bit words in host order
+#[cfg(target_os = "linux")]
+fn parse_address(field: &str) -> Option<SocketAddr> {
+    let (addr, port) = field.split_once(':')?;
+    let words: Vec<[u8; 4]> = (0..addr.len() / 8)
+        .map(|i| u32::from_str_radix(addr.get(8 * i..8 * i + 8)?, 16).ok())
+        .collect::<Option<Vec<u32>>>()?
+        .into_iter()
+        .map(u32::to_ne_bytes)
+        .collect();
+    let ip = match words.len() {
+        1 => IpAddr::from(words[0]),
+        4 => IpAddr::from(<[u8; 16]>::try_from(words.concat()).ok()?),
+        _ => return None,
+    };
+    Some(SocketAddr::new(ip, u16::from_str_radix(port, 16).ok()?))
+}
+
+/// Bytes transmitted on the non-loopback interfaces of `pid`'s network namespace
+#[cfg(target_os = "linux")]
+fn transmitted(pid: u32) -> std::io::Result<u64> {
+    let dev = std::fs::read_to_string(format!("/proc/{}/net/dev", pid))?;
+    Ok(parse_transmitted(&dev))
+}
+
+#[cfg(target_os = "linux")]
+fn parse_transmitted(dev: &str) -> u64 {
+    dev.lines()
+        .skip(2)
+        .filter_map(|row| row.split_once(':'))
+        .filter(|(interface, _)| interface.trim() != "lo")
+        // 8 receive columns, then transmit bytes
+        .filter_map(|(_, counters)| counters.split_whitespace().nth(8)?.parse::<u64>().ok())
+        .sum()
+}
+
+/// Microsoft-Windows-Kernel-Network
+#[cfg(windows)]
+const KERNEL_NETWORK: crate::etw::Guid = crate::etw::Guid {
+    data1: 0x7dd42a49,
+    data2: 0x5329,
+    data3: 0x4832,
+    data4: [0x8d, 0xfd, 0x43, 0xd9, 0x79, 0x15, 0x3a, 0x88],
+};
+
+/// A real-time Kernel-Network ETW session, filtered down to the target
+/// and its children
+#[cfg(windows)]
+pub struct Probe {
+    pid: u32,
+    pids: std::sync::Arc<std::sync::Mutex<HashSet<u32>>>,
+    pending: std::sync::Arc<std::sync::Mutex<Observation>>,
+    session: Option<crate::etw::Session>,
+}
+
+#[cfg(windows)]
+impl Probe {
+    pub fn open(pid: u32) -> std::io::Result<Self> {
+        use std::sync::{Arc, Mutex};
+
+        let pids: HashSet<u32> = std::iter::once(pid)
+            .chain(crate::resource::children(pid)?)
+            .collect();
+        let pids = Arc::new(Mutex::new(pids));
+        let pending = Arc::new(Mutex::new(Observation {
+            sent: Some(0),
+            ..Default::default()
+        }));
+        let (events_tx, mut events) = tokio::sync::mpsc::channel::<String>(4096);
+        let source = crate::etw::EtwSource {
+            provider: KERNEL_NETWORK,
+            level: 4,
+            // KERNEL_NETWORK_KEYWORD_IPV4 | KERNEL_NETWORK_KEYWORD_IPV6
+            keywords: 0x30,
+        };
+        let session = crate::etw::Session::start(&source, events_tx)?;
+
+        // Aggregate as events arrive: a full channel would stall ETW's buffers
+        let (tracked, totals) = (Arc::clone(&pids), Arc::clone(&pending));
+        std::thread::Builder::new()
+            .name("tripwired-egress".to_string())
+            .spawn(move || {
+                while let Some(line) = events.blocking_recv() {
+                    let Some((pid, destination, size)) = network_event(&line) else {
+                        continue;
+                    };
+                    if !tracked.lock().unwrap().contains(&pid) {
+                        continue;
+                    }
+                    let mut pending = totals.lock().unwrap();
+                    pending.destinations.insert(destination);
+                    pending.sent = Some(pending.sent.unwrap_or(0) + size);
+                }
+            })?;
+        Ok(Self {
+            pid,
+            pids,
+            pending,
+            session: Some(session),
+        })
+    }
+
+    /// Destinations and bytes sent since the last call
+    pub fn read(&mut self) -> std::io::Result<Observation> {
+        let pids: HashSet<u32> = std::iter::once(self.pid)
+            .chain(crate::resource::children(self.pid)?)
+            .collect();
+        *self.pids.lock().unwrap() = pids;
+        let reset = Observation {
+            sent: Some(0),
+            ..Default::default()
+        };
+        Ok(std::mem::replace(&mut *self.pending.lock().unwrap(), reset))
+    }
+}
+
+#[cfg(windows)]
+impl Drop for Probe {
+    fn drop(&mut self) {
+        if let Some(session) = self.session.take() {
+            session.stop();
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+pub struct Probe;
+
+#[cfg(not(any(target_os = "linux", windows)))]
+impl Probe {
+    pub fn open(_pid: u32) -> std::io::Result<Self> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "egress monitoring is supported on Linux and Windows",
+        ))
+    }
+
+    pub fn read(&mut self) -> std::io::Result<Observation> {
+        unreachable!()
+    }
+}
+
+/// Owning process, destination and payload size of a decoded Kernel-Network
+/// send or connect event (see `etw::EtwEvent::to_line`)
+#[cfg(any(windows, test))]
+fn network_event(line: &str) -> Option<(u32, Destination, u64)> {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    let event: serde_json::Value = serde_json::from_str(line).ok()?;
+    // TCP send / connect and UDP send, IPv4 then IPv6
+    let (proto, v6) = match event["etw"]["id"].as_u64()? {
+        10 | 12 => ("tcp", false),
+        26 | 28 => ("tcp", true),
+        42 => ("udp", false),
+        58 => ("udp", true),
+        _ => return None,
+    };
+    let ip = match v6 {
+        false => {
+            // In network byte order, decoded as a little-endian integer
+            let addr = u32::try_from(event["daddr"].as_u64()?).ok()?;
+            IpAddr::from(Ipv4Addr::from(addr.to_le_bytes()))
+        }
+        true => {
+            // Undecoded binary: hex
+            let hex = event["daddr"].as_str()?;
+            let bytes: Vec<u8> = (0..hex.len() / 2)
+                .map(|i| u8::from_str_radix(hex.get(2 * i..2 * i + 2)?, 16).ok())
+                .collect::<Option<_>>()?;
+            IpAddr::from(Ipv6Addr::from(<[u8; 16]>::try_from(bytes).ok()?))
+        }
+    };
+    let port = u16::try_from(event["dport"].as_u64()?).ok()?.swap_bytes();
+    let pid = u32::try_from(event["PID"].as_u64()?).ok()?;
+    let size = event["size"].as_u64().unwrap_or(0);
+    Some((pid, Destination::new(proto, ip, port), size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(destinations: &[&str], sent_mb: u64) -> Observation {
+        Observation {
+            destinations: destinations
+                .iter()
+                .map(|d| {
+                    let addr: SocketAddr = d.parse().unwrap();
+                    Destination::new("tcp", addr.ip(), addr.port())
+                })
+                .collect(),
+            sent: Some(sent_mb * MB),
+        }
+    }
+
+    #[test]
+    fn test_destinations_and_volume() {
+        let config: EgressConfig = toml::from_str(
+            "interval_ms = 1000\nlearn_ms = 2000\nallow = ['10.0.0.0/8', 'fd00::/8']\n\
+             max_sent_mb = 20\nwindow_ms = 5000",
+        )
+        .unwrap();
+        config.validate().unwrap();
+        let mut monitor = EgressMonitor::new(&config, 42);
+        let t0 = Instant::now();
+        let at = |s: u64| t0 + Duration::from_secs(s);
+
+        // Baseline: the LLM API it talks to from the start
+        let api = "198.51.100.7:443";
+        assert!(monitor.observe(reading(&[api], 1), at(0)).is_empty());
+        assert!(monitor.observe(reading(&[api], 1), at(1)).is_empty());
+
+        let hits = monitor.observe(
+            reading(
+                &[
+                    api,
+                    "10.1.2.3:5432",
+                    "127.0.0.1:8080",
+                    "[fd00::1]:80",
+                    "203.0.113.9:22",
+                ],
+                1,
+            ),
+            at(2),
+        );
+        assert_eq!(
+            hits,
+            vec![EgressHit {
+                id: "egress-destination",
+                line: "EGRESS pid 42: new destination 203.0.113.9:22/tcp".to_string()
+            }]
+        );
+        assert!(monitor
+            .observe(reading(&["203.0.113.9:22"], 1), at(3))
+            .is_empty());
+
+        // 4 x 1 MB + 25 MB within the window; fires once
+        let hits = monitor.observe(reading(&[], 25), at(4));
+        assert_eq!(
+            hits,
+            vec![EgressHit {
+                id: "egress-volume",
+                line: "EGRESS pid 42: 29 MB sent in 5s above max 20 MB".to_string()
+            }]
+        );
+        assert!(monitor.observe(reading(&[], 1), at(5)).is_empty());
+        // The 25 MB age out, then a new burst fires again
+        for s in 6..=9 {
+            assert!(monitor.observe(reading(&[], 0), at(s)).is_empty());
+        }
+        let hits = monitor.observe(reading(&[], 30), at(10));
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].id, "egress-volume");
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = |toml: &str| toml::from_str::<EgressConfig>(toml).unwrap().validate();
+        assert!(config("").is_ok()); // disabled
+        assert!(config("interval_ms = 1000").is_ok());
+        assert!(config("interval_ms = 1000\nnew_destinations = false").is_err());
+        assert!(config("interval_ms = 1000\nallow = ['10.0.0.0/33']").is_err());
+        assert!(config("interval_ms = 1000\nallow = ['example.com']").is_err());
+        assert!(config("interval_ms = 5000\nwindow_ms = 1000").is_err());
+
+        let network = Network::parse("192.168.0.0/16").unwrap();
+        assert!(network.contains("192.168.4.2".parse().unwrap()));
+        assert!(!network.contains("192.169.0.1".parse().unwrap()));
+        assert!(Network::parse("0.0.0.0/0")
+            .unwrap()
+            .contains("8.8.8.8".parse().unwrap()));
+        assert!(Network::parse("::1")
+            .unwrap()
+            .contains("::1".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_network_event() {
+        // TCP send of 512 bytes from PID 4242 to 203.0.113.9:443
+        let line = r#"{"PID":4242,"size":512,"daddr":158400715,"dport":47873,"etw":{"id":10}}"#;
+        let (pid, destination, size) = network_event(line).unwrap();
+        assert_eq!((pid, size), (4242, 512));
+        assert_eq!(destination.to_string(), "203.0.113.9:443/tcp");
+
+        let v6 = r#"{"PID":7,"size":0,"daddr":"20010db8000000000000000000000001","dport":20480,"etw":{"id":28}}"#;
+        assert_eq!(
+            network_event(v6).unwrap().1.to_string(),
+            "[2001:db8::1]:80/tcp"
+        );
+        assert!(network_event(r#"{"PID":7,"etw":{"id":11}}"#).is_none());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_proc_net() {
+        let table = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode\n\
+            0: 00000000:1F90 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 100 1\n\
+            1: 0100007F:1F90 0100007F:D431 01 00000000:00000000 00:00000000 00000000  1000        0 101 1\n\
+            2: 0F02000A:D432 0B7100CB:01BB 01 00000000:00000000 00:00000000 00000000  1000        0 102 1\n\
+            3: 0F02000A:D433 0A7100CB:0016 02 00000000:00000000 00:00000000 00000000  1000        0 103 1\n\
+            4: 0F02000A:D434 097100CB:01BB 01 00000000:00000000 00:00000000 00000000  1000        0 999 1\n";
+        let inodes: HashSet<u64> = [100, 101, 102, 103].into();
+        let mut found: Vec<String> = parse_sockets(table, "tcp", &inodes)
+            .iter()
+            .map(Destination::to_string)
+            .collect();
+        found.sort();
+        assert_eq!(found, ["203.0.113.10:22/tcp", "203.0.113.11:443/tcp"]);
+
+        let mapped = parse_address("0000000000000000FFFF00000100007F:0050").unwrap();
+        assert_eq!(
+            Destination::new("tcp", mapped.ip(), mapped.port()).to_string(),
+            "127.0.0.1:80/tcp"
+        );
+
+        let dev = "Inter-|   Receive                                                |  Transmit\n \
+            face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed\n    \
+            lo: 5000 10 0 0 0 0 0 0 5000 10 0 0 0 0 0 0\n  \
+            eth0: 900 9 0 0 0 0 0 0 1200 12 0 0 0 0 0 0\n";
+        assert_eq!(parse_transmitted(dev), 1200);
+
+        let mut probe = Probe::open(std::process::id()).unwrap();
+        assert!(probe.read().unwrap().sent.is_some());
+        assert!(Probe::open(u32::MAX / 2).is_err());
+    }
+}
@@ -0,0 +1,351 @@
+//! Email Alerts - SMTP Channel Where Webhooks Are Not Allowed
+//!
+//! `[notify.email]` mails notifier events through an SMTP relay. KILL
+//! events go out immediately, one mail each. The other events (FAIL
+//! decisions, breaker trips) are collected into a digest mailed every
+//! `digest_interval_s` seconds; `digest_interval_s = 0` mails every event
+//! on its own.
+//!
+//! ```toml
+//! [notify.email]
+//! server = "smtp.example.com"
+//! tls = "starttls"
+//! username = "tripwired"
+//! password_env = "TRIPWIRED_SMTP_PASSWORD"
+//! from = "tripwired <tripwired@example.com>"
+//! to = ["oncall@example.com"]
+//! digest_interval_s = 300
+//! ```
+
+use crate::notify::{Event, EventKind, NotifyConfig};
+use lettre::message::{header::ContentType, Mailbox};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tokio_util::task::TaskTracker;
+use tracing::{debug, warn};
+
+/// `[notify.email]` table
+#[derive(Debug, Clone, Deserialize)]
+pub struct EmailConfig {
+    /// SMTP relay host
+    pub server: String,
+    /// Default: 587 for `starttls`, 465 for `tls`, 25 for `none`
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub tls: TlsMode,
+    #[serde(default)]
+    pub username: Option<String>,
+    /// Password inline; prefer `password_env`
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Environment variable holding the password
+    #[serde(default)]
+    pub password_env: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+    /// Events to mail (default: all)
+    #[serde(default = "crate::notify::all_events")]
+    pub events: Vec<EventKind>,
+    /// Seconds between digests of non-KILL events; 0 mails each at once
+    #[serde(default = "default_digest_interval_s")]
+    pub digest_interval_s: u64,
+}
+
+fn default_digest_interval_s() -> u64 {
+    300
+}
+
+/// How the SMTP connection is secured
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TlsMode {
+    /// Plain connection upgraded with STARTTLS (required)
+    #[default]
+    Starttls,
+    /// TLS from the first byte (SMTPS)
+    Tls,
+    /// Unencrypted - local relays only
+    None,
+}
+
+impl EmailConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.server.is_empty() {
+            return Err("notify.email: server is required".to_string());
+        }
+        self.from
+            .parse::<Mailbox>()
+            .map_err(|e| format!("notify.email: invalid from '{}': {}", self.from, e))?;
+        if self.to.is_empty() {
+            return Err("notify.email: at least one recipient (to) is required".to_string());
+        }
+        for to in &self.to {
+            to.parse::<Mailbox>()
+                .map_err(|e| format!("notify.email: invalid recipient '{}': {}", to, e))?;
+        }
+        if self.password.is_some() && self.password_env.is_some() {
+            return Err("notify.email: set password or password_env, not both".to_string());
+        }
+        if self.username.is_none() && (self.password.is_some() || self.password_env.is_some()) {
+            return Err("notify.email: a password needs a username".to_string());
+        }
+        Ok(())
+    }
+
+    fn port(&self) -> u16 {
+        self.port.unwrap_or(match self.tls {
+            TlsMode::Starttls => 587,
+            TlsMode::Tls => 465,
+            TlsMode::None => 25,
+        })
+    }
+
+    fn password(&self) -> Result<String, String> {
+        match (&self.password, &self.password_env) {
+            (Some(password), _) => Ok(password.clone()),
+            (None, Some(var)) => std::env::var(var)
+                .map_err(|_| format!("notify.email: environment variable {} not set", var)),
+            (None, None) => Ok(String::new()),
+        }
+    }
+}
+
+/// Turns events into mails: KILLs at once, the rest into the digest
+pub struct Mailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+    events: Vec<EventKind>,
+    digest_interval: Option<Duration>,
+    digest: Vec<Event>,
+    retries: u32,
+    backoff: Duration,
+}
+
+/// A composed alert mail
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mail {
+    pub subject: String,
+    pub body: String,
+}
+
+impl Mailer {
+    /// `notify` supplies the timeout and retry settings shared with webhooks
+    pub fn new(config: &EmailConfig, notify: &NotifyConfig) -> Result<Self, String> {
+        let timeout = Duration::from_millis(notify.timeout_ms);
+        let mut builder = match config.tls {
+            TlsMode::Starttls => {
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.server)
+                    .map_err(|e| e.to_string())?
+            }
+            TlsMode::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.server)
+                .map_err(|e| e.to_string())?,
+            TlsMode::None => {
+                AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.server)
+            }
+        };
+        builder = builder.port(config.port()).timeout(Some(timeout));
+        if let Some(ref username) = config.username {
+            builder = builder.credentials(Credentials::new(username.clone(), config.password()?));
+        }
+        Ok(Self {
+            transport: builder.build(),
+            from: config.from.parse().map_err(|e| format!("from: {}", e))?,
+            to: config
+                .to
+                .iter()
+                .map(|to| to.parse().map_err(|e| format!("to: {}", e)))
+                .collect::<Result<_, String>>()?,
+            events: config.events.clone(),
+            digest_interval: (config.digest_interval_s > 0)
+                .then(|| Duration::from_secs(config.digest_interval_s)),
+            digest: Vec::new(),
+            retries: notify.retries,
+            backoff: Duration::from_millis(notify.backoff_ms),
+        })
+    }
+
+    /// Time between digests, if events are digested at all
+    pub fn digest_interval(&self) -> Option<Duration> {
+        self.digest_interval
+    }
+
+    /// The mail to send now for `event`, if any; digested events are held
+    pub fn take(&mut self, event: Event) -> Option<Mail> {
+        let kind = event.subscribed(&self.events)?;
+        // A wedged kernel may not live to send the digest
+        if !matches!(kind, EventKind::Kill | EventKind::Stall) && self.digest_interval.is_some() {
+            self.digest.push(event);
+            return None;
+        }
+        let subject = event.default_text();
+        let mut body = format!("{}\n\n", subject);
+        for (name, value) in event.fields() {
+            let value = match value {
+                serde_json::Value::String(s) => s,
+                serde_json::Value::Null => continue,
+                other => other.to_string(),
+            };
+            body.push_str(&format!("{}: {}\n", name, value));
+        }
+        Some(Mail { subject, body })
+    }
+
+    /// The digest mail for the events held since the last flush, if any
+    pub fn flush(&mut self) -> Option<Mail> {
+        if self.digest.is_empty() {
+            return None;
+        }
+        let events = std::mem::take(&mut self.digest);
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+        for event in &events {
+            let name = match event.fields().get("event") {
+                Some(serde_json::Value::String(s)) => s.clone(),
+                _ => "event".to_string(),
+            };
+            *counts.entry(name).or_default() += 1;
+        }
+        let summary: Vec<String> = counts
+            .iter()
+            .map(|(name, n)| format!("{} {}", n, name))
+            .collect();
+        let subject = format!(
+            "tripwired digest: {} events ({})",
+            events.len(),
+            summary.join(", ")
+        );
+        let mut body = format!("{} events since the last digest:\n\n", events.len());
+        for event in &events {
+            body.push_str(&format!("- {}\n", event.default_text()));
+        }
+        Some(Mail { subject, body })
+    }
+
+    /// Send `mail` in the background on `tracker`
+    pub fn send(&self, mail: Mail, tracker: &TaskTracker) {
+        let mut builder = Message::builder()
+            .from(self.from.clone())
+            .subject(mail.subject)
+            .header(ContentType::TEXT_PLAIN);
+        for to in &self.to {
+            builder = builder.to(to.clone());
+        }
+        match builder.body(mail.body) {
+            Ok(message) => {
+                tracker.spawn(deliver(
+                    self.transport.clone(),
+                    message,
+                    self.retries,
+                    self.backoff,
+                ));
+            }
+            Err(e) => warn!("📧 Could not build alert mail: {}", e),
+        }
+    }
+}
+
+/// Send with retries; gives up at once on permanent (5xx) SMTP errors
+async fn deliver(
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    message: Message,
+    retries: u32,
+    mut backoff: Duration,
+) {
+    for attempt in 0..=retries {
+        if attempt > 0 {
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+        match transport.send(message.clone()).await {
+            Ok(_) => {
+                debug!("📧 Alert mail sent");
+                return;
+            }
+            Err(e) if e.is_permanent() => {
+                warn!("📧 SMTP server rejected the alert mail: {}", e);
+                return;
+            }
+            Err(e) => debug!("📧 Mail attempt {} failed: {}", attempt + 1, e),
+        }
+    }
+    warn!("📧 Alert mail failed after {} attempts", retries + 1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TO: &str = "to = ['oncall@example.com']";
+
+    /// Base config plus `extra`, which must set `to`
+    fn config(extra: &str) -> EmailConfig {
+        toml::from_str(&format!(
+            "server = 'localhost'\ntls = 'none'\nfrom = 'tripwired <tw@example.com>'\n\
+             {}",
+            extra
+        ))
+        .unwrap()
+    }
+
+    fn decision(id: u64, action: &str) -> Event {
+        Event::Decision(Box::new(
+            serde_json::from_value(serde_json::json!({
+                "id": id, "timestamp_ms": 0, "input_log": "x", "input_hash": "ab",
+                "action": action, "confidence": 90, "filtered": false, "latency_ms": 5,
+                "model_fingerprint": "m@1", "prompt_hash": "", "raw_response": null,
+            }))
+            .unwrap(),
+        ))
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(config(TO).validate().is_ok());
+        assert_eq!(config(TO).port(), 25);
+        assert!(config("to = []").validate().is_err());
+        assert!(config("to = ['not an address']").validate().is_err());
+        assert!(config("to = ['a@b.c']\npassword = 'x'").validate().is_err());
+        assert!(
+            config("to = ['a@b.c']\nusername = 'u'\npassword = 'x'\npassword_env = 'P'")
+                .validate()
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_digest() {
+        let notify = NotifyConfig::default();
+        let mut mailer = Mailer::new(&config(TO), &notify).unwrap();
+        assert!(mailer.take(decision(1, "FAIL")).is_none());
+        let breaker = Event::BreakerOpen {
+            model: "phi".to_string(),
+            error: "timeout".to_string(),
+        };
+        assert!(mailer.take(breaker).is_none());
+
+        // KILL skips the digest
+        let kill = mailer.take(decision(2, "KILL")).unwrap();
+        assert_eq!(kill.subject, "🚨 tripwired KILL #2 (90% in 5ms) [input ab]");
+        assert!(kill.body.contains("\ndecision_id: 2\n"));
+
+        let digest = mailer.flush().unwrap();
+        assert_eq!(
+            digest.subject,
+            "tripwired digest: 2 events (1 breaker, 1 fail)"
+        );
+        assert!(digest
+            .body
+            .contains("- 🔌 tripwired circuit open for phi: timeout\n"));
+        assert!(mailer.flush().is_none());
+
+        // Without a digest every event is mailed at once
+        let mut mailer =
+            Mailer::new(&config("to = ['a@b.c']\ndigest_interval_s = 0"), &notify).unwrap();
+        assert!(mailer.take(decision(3, "FAIL")).is_some());
+        assert!(mailer.take(decision(4, "SUSTAIN")).is_none());
+    }
+}
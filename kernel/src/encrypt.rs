@@ -0,0 +1,351 @@
+//! Payload Encryption - Audit Content for the Compliance Keyholder
+//!
+//! Raw agent logs routinely contain secrets, so an audit file everyone may
+//! tail is an audit file nobody should see. With `[encrypt]` recipients,
+//! the `input_log`, `reason`, `raw_response` and `rendered_prompt` of every
+//! decision record are written age-encrypted (X25519, ASCII-armored) and
+//! the record is marked `encrypted: true`. So are the text of
+//! `explanation` events and the `reason` of `shadow` and `denial` events,
+//! which quote the line or the model's reading of it; denials drop their
+//! `input_log` altogether. IDs, actions, hashes, latencies and the rest of
+//! the metadata stay in the clear; `input_hash`, `template_hash` and
+//! `rendered_prompt_hash` are still computed from the plaintext.
+//!
+//! Only the file is encrypted: the admin API feed and notifications carry
+//! the line as before. The keyholder reads a trail back with
+//! `tripwired audit decrypt <file> --identity key.txt` (or one field at a
+//! time with `age -d`).
+//!
+//! ```toml
+//! [encrypt]
+//! recipients = ["age1ql3z7hjy54pw3hyww5ayyfg7zqgvc7w3j2elw8zmrj2kg5sfn9aqmcac8p"]
+//! recipients_file = "/etc/tripwired/compliance.pub"
+//! ```
+
+use crate::audit::DecisionRecord;
+use crate::error::KernelError;
+use age::x25519::{Identity, Recipient};
+use serde::Deserialize;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// `[encrypt]` table
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct EncryptConfig {
+    /// age X25519 recipients (`age1...`)
+    #[serde(default)]
+    pub recipients: Vec<String>,
+    /// File of recipients, one per line (`#` comments), as for `age -R`
+    #[serde(default)]
+    pub recipients_file: Option<PathBuf>,
+}
+
+impl EncryptConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        for recipient in &self.recipients {
+            parse_recipient(recipient)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_recipient(text: &str) -> Result<Recipient, String> {
+    text.parse()
+        .map_err(|e| format!("encrypt: invalid recipient {:?}: {}", text, e))
+}
+
+/// Encrypts record payloads to the configured recipients
+pub struct Encryptor {
+    recipients: Vec<Recipient>,
+}
+
+impl Encryptor {
+    /// `None` when no recipient is configured
+    pub fn new(config: &EncryptConfig) -> Result<Option<Self>, String> {
+        let mut recipients = config
+            .recipients
+            .iter()
+            .map(|r| parse_recipient(r))
+            .collect::<Result<Vec<_>, _>>()?;
+        if let Some(ref path) = config.recipients_file {
+            let content = std::fs::read_to_string(path)
+                .map_err(|e| format!("encrypt: {}: {}", path.display(), e))?;
+            for line in key_lines(&content) {
+                recipients.push(parse_recipient(line)?);
+            }
+        }
+        Ok((!recipients.is_empty()).then_some(Self { recipients }))
+    }
+
+    pub fn recipients(&self) -> usize {
+        self.recipients.len()
+    }
+
+    /// `record` as written to the file: payload fields encrypted
+    pub fn seal(&self, record: &DecisionRecord) -> std::io::Result<DecisionRecord> {
+        let mut sealed = record.clone();
+        sealed.input_log = self.encrypt(&record.input_log)?;
+        sealed.reason = self.seal_text(record.reason.as_deref())?;
+        sealed.raw_response = self.seal_text(record.raw_response.as_deref())?;
+        sealed.rendered_prompt = self.seal_text(record.rendered_prompt.as_deref())?;
+        sealed.encrypted = true;
+        Ok(sealed)
+    }
+
+    /// An optional payload field as written to the file
+    pub fn seal_text(&self, text: Option<&str>) -> std::io::Result<Option<String>> {
+        text.map(|text| self.encrypt(text)).transpose()
+    }
+
+    fn encrypt(&self, plaintext: &str) -> std::io::Result<String> {
+        use age::armor::{ArmoredWriter, Format};
+
+        let recipients = self.recipients.iter().map(|r| r as &dyn age::Recipient);
+        let encryptor = age::Encryptor::with_recipients(recipients)
+            .map_err(|e| std::io::Error::other(e.to_string()))?;
+        let mut armored = Vec::with_capacity(plaintext.len() + 400);
+        let mut writer = encryptor.wrap_output(ArmoredWriter::wrap_output(
+            &mut armored,
+            Format::AsciiArmor,
+        )?)?;
+        writer.write_all(plaintext.as_bytes())?;
+        writer.finish()?.finish()?;
+        Ok(String::from_utf8(armored).expect("armor is ASCII"))
+    }
+}
+
+/// Non-empty, non-comment lines of a key file
+fn key_lines(content: &str) -> impl Iterator<Item = &str> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+}
+
+/// Decrypt the payload fields of an encrypted record
+pub fn open(record: &mut DecisionRecord, identities: &[Identity]) -> Result<(), String> {
+    if !record.encrypted {
+        return Ok(());
+    }
+    record.input_log = decrypt(&record.input_log, identities)?;
+    if let Some(ref reason) = record.reason {
+        record.reason = Some(decrypt(reason, identities)?);
+    }
+    if let Some(ref response) = record.raw_response {
+        record.raw_response = Some(decrypt(response, identities)?);
+    }
+    if let Some(ref prompt) = record.rendered_prompt {
+        record.rendered_prompt = Some(decrypt(prompt, identities)?);
+    }
+    record.encrypted = false;
+    Ok(())
+}
+
+/// Payload fields of an encrypted `explanation`, `shadow` or `denial` event
+const EVENT_FIELDS: [&str; 2] = ["explanation", "reason"];
+
+/// Decrypt the payload fields of an encrypted audit event
+fn open_event(event: &mut serde_json::Value, identities: &[Identity]) -> Result<(), String> {
+    let Some(fields) = event.as_object_mut() else {
+        return Ok(());
+    };
+    if fields.remove("encrypted") != Some(serde_json::Value::Bool(true)) {
+        return Ok(());
+    }
+    for name in EVENT_FIELDS {
+        if let Some(serde_json::Value::String(text)) = fields.get_mut(name) {
+            *text = decrypt(text, identities)?;
+        }
+    }
+    Ok(())
+}
+
+fn decrypt(armored: &str, identities: &[Identity]) -> Result<String, String> {
+    let decryptor =
+        age::Decryptor::new_buffered(age::armor::ArmoredReader::new(armored.as_bytes()))
+            .map_err(|e| e.to_string())?;
+    let mut reader = decryptor
+        .decrypt(identities.iter().map(|i| i as &dyn age::Identity))
+        .map_err(|e| e.to_string())?;
+    let mut plaintext = String::new();
+    reader
+        .read_to_string(&mut plaintext)
+        .map_err(|e| e.to_string())?;
+    Ok(plaintext)
+}
+
+/// `tripwired audit decrypt`: print the file with encrypted records opened
+pub fn decrypt_file(path: &Path, identity_file: &Path) -> Result<(), KernelError> {
+    let content = std::fs::read_to_string(identity_file)?;
+    let identities = key_lines(&content)
+        .map(|line| line.parse::<Identity>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("{}: {}", identity_file.display(), e))?;
+    if identities.is_empty() {
+        return Err(format!("{}: no identity found", identity_file.display()).into());
+    }
+
+    let mut stdout = std::io::stdout().lock();
+    let mut failed = 0;
+    for (n, line) in BufReader::new(std::fs::File::open(path)?)
+        .lines()
+        .enumerate()
+    {
+        let line = line?;
+        let opened = match serde_json::from_str::<serde_json::Value>(&line) {
+            Ok(event) if event.get("event").is_some() && event["encrypted"] == true => {
+                let mut event = event;
+                open_event(&mut event, &identities)
+                    .map(|()| event.to_string())
+                    .map_err(|e| format!("{} event: {}", event["event"], e))
+            }
+            _ => match serde_json::from_str::<DecisionRecord>(&line) {
+                Ok(mut record) if record.encrypted => open(&mut record, &identities)
+                    .map(|()| serde_json::to_string(&record).expect("record serializes"))
+                    .map_err(|e| format!("ID {}: {}", record.id, e)),
+                _ => Ok(line.clone()),
+            },
+        };
+        match opened {
+            Ok(opened) => writeln!(stdout, "{}", opened)?,
+            Err(e) => {
+                eprintln!("line {}: {}", n + 1, e);
+                failed += 1;
+                writeln!(stdout, "{}", line)?;
+            }
+        }
+    }
+    match failed {
+        0 => Ok(()),
+        _ => Err(format!("{} line(s) could not be decrypted", failed).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::AgentIds;
+    use crate::audit::{
+        AuditTrail, DenialEvent, ExplanationEvent, ModelFingerprint, RecordInput, ShadowEvent,
+    };
+    use crate::llm::Sampling;
+
+    #[test]
+    fn test_encrypted_records() {
+        let identity = Identity::generate();
+        let config = EncryptConfig {
+            recipients: vec![identity.to_public().to_string()],
+            recipients_file: None,
+        };
+        assert!(config.validate().is_ok());
+        let encryptor = Encryptor::new(&config).unwrap().unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let fp = ModelFingerprint::new("test-model", "http://localhost", 30, &Sampling::default());
+        let trail = AuditTrail::new(path.clone(), fp, "test prompt")
+            .unwrap()
+            .with_encryptor(encryptor);
+        let raw = Some("KILL 99 leaked".to_string());
+        trail
+            .record("export API_KEY=sk-live-123", "KILL", 99, false, 120, raw)
+            .unwrap();
+        // Live consumers still see the line
+        assert_eq!(
+            trail.get(1).unwrap().input_log,
+            "export API_KEY=sk-live-123"
+        );
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(!content.contains("sk-live") && !content.contains("leaked"));
+        let line = content
+            .lines()
+            .find(|l| l.starts_with("{\"id\":1,"))
+            .unwrap();
+        let mut record: DecisionRecord = serde_json::from_str(line).unwrap();
+        assert!(record.encrypted);
+        assert_eq!(record.action, "KILL");
+        assert!(record
+            .input_log
+            .starts_with("-----BEGIN AGE ENCRYPTED FILE-----"));
+
+        assert!(open(&mut record.clone(), &[Identity::generate()]).is_err());
+        open(&mut record, &[identity]).unwrap();
+        assert_eq!(record.input_log, "export API_KEY=sk-live-123");
+        assert_eq!(record.raw_response.as_deref(), Some("KILL 99 leaked"));
+        assert!(!record.encrypted);
+
+        let config = EncryptConfig {
+            recipients: vec!["age1nope".to_string()],
+            recipients_file: None,
+        };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_no_payload_in_clear() {
+        let identity = Identity::generate();
+        let config = EncryptConfig {
+            recipients: vec![identity.to_public().to_string()],
+            recipients_file: None,
+        };
+        let encryptor = Encryptor::new(&config).unwrap().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let fp = ModelFingerprint::new("test-model", "http://localhost", 30, &Sampling::default());
+        let trail = AuditTrail::new(path.clone(), fp, "test prompt")
+            .unwrap()
+            .with_encryptor(encryptor);
+
+        // Every payload-derived field quotes the secret
+        let secret = "sk-live-123";
+        let id = trail
+            .record_entry(RecordInput {
+                input_log: "curl -H 'Bearer sk-live-123'",
+                action: "KILL",
+                confidence: 95,
+                reason: Some("sends sk-live-123 off host"),
+                ..Default::default()
+            })
+            .unwrap();
+        let mut explanation = ExplanationEvent::new(id, 40);
+        explanation.explanation = Some("the token sk-live-123 leaves the host".to_string());
+        trail.record_explanation(&explanation).unwrap();
+        let mut shadow = ShadowEvent::new("shadow-model", "abcd1234");
+        shadow.decision_id = id;
+        shadow.reason = Some("exfiltrates sk-live-123".to_string());
+        trail.record_shadow(&shadow).unwrap();
+        let agent = AgentIds {
+            agent_id: "agent".to_string(),
+            session_id: "s1".to_string(),
+            connection_id: 1,
+        };
+        let mut denial = DenialEvent::new(&agent, "echo sk-live-123", "echo sk-live-123");
+        denial.reason = Some("echo sk-live-123".to_string());
+        trail.record_denial(&denial).unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(!content.contains(secret), "{}", content);
+
+        let mut opened = 0;
+        for line in content.lines() {
+            let mut event: serde_json::Value = serde_json::from_str(line).unwrap();
+            if event.get("event").is_none() || event["encrypted"] != true {
+                continue;
+            }
+            open_event(&mut event, std::slice::from_ref(&identity)).unwrap();
+            let text = event.to_string();
+            assert!(text.contains(secret), "{}", text);
+            assert!(event.get("encrypted").is_none());
+            opened += 1;
+        }
+        assert_eq!(opened, 3);
+        let line = content
+            .lines()
+            .find(|l| l.starts_with(&format!("{{\"id\":{},", id)))
+            .unwrap();
+        let mut record: DecisionRecord = serde_json::from_str(line).unwrap();
+        open(&mut record, &[identity]).unwrap();
+        assert_eq!(record.reason.as_deref(), Some("sends sk-live-123 off host"));
+    }
+}
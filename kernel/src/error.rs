@@ -0,0 +1,135 @@
+//! Errors - One Type per Subsystem, One Category per Failure
+//!
+//! Each subsystem has its own error enum: [`ConfigError`] and
+//! [`FilterError`] (config files and rule compilation), [`LlmError`]
+//! (inference calls, deadlines, circuit breakers) and [`AuditError`]
+//! (audit trail writes). [`KernelError`] wraps them for everything that
+//! runs a command end to end, so callers can still match on the cause.
+//!
+//! Every error has a dotted `category` (`llm.timeout`, `audit.io`,
+//! `filter.pattern`, ...). Failures are counted by category in `/stats`
+//! (`errors`) and `/metrics`, and a decision made because the LLM failed
+//! carries its category in the audit record's `error` field:
+//!
+//! ```text
+//! tripwired_errors_total{category="llm.timeout"} 3
+//! tripwired_errors_total{category="audit.io"} 1
+//! ```
+
+use crate::audit::AuditError;
+use crate::filter::{ConfigError, FilterError};
+use crate::llm::LlmError;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Mutex;
+
+/// Any failure of a command or of the kernel as a whole
+#[derive(thiserror::Error)]
+pub enum KernelError {
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+    #[error(transparent)]
+    Filter(#[from] FilterError),
+    #[error(transparent)]
+    Llm(#[from] LlmError),
+    #[error(transparent)]
+    Audit(#[from] AuditError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// JSON or YAML input that does not parse
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Yaml(#[from] serde_yaml::Error),
+    /// An admin API or webhook request
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    /// A server task that panicked
+    #[error(transparent)]
+    Task(#[from] tokio::task::JoinError),
+    /// A failure described by its message only
+    #[error("{0}")]
+    Failed(String),
+}
+
+impl KernelError {
+    pub fn category(&self) -> &'static str {
+        match self {
+            KernelError::Config(e) => e.category(),
+            KernelError::Filter(e) => e.category(),
+            KernelError::Llm(e) => e.category(),
+            KernelError::Audit(e) => e.category(),
+            KernelError::Io(_) => "io",
+            KernelError::Json(_) => "json",
+            KernelError::Yaml(_) => "yaml",
+            KernelError::Http(_) => "http",
+            KernelError::Task(_) => "task",
+            KernelError::Failed(_) => "failed",
+        }
+    }
+}
+
+/// The message, so `main` returning an error prints it as is
+impl fmt::Debug for KernelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl From<String> for KernelError {
+    fn from(message: String) -> Self {
+        KernelError::Failed(message)
+    }
+}
+
+impl From<&str> for KernelError {
+    fn from(message: &str) -> Self {
+        KernelError::Failed(message.to_string())
+    }
+}
+
+/// Failures by category, for components that have no access to the stats
+#[derive(Debug, Default)]
+pub struct ErrorCounts(Mutex<BTreeMap<&'static str, u64>>);
+
+impl ErrorCounts {
+    pub fn count(&self, category: &'static str) {
+        *self.0.lock().unwrap().entry(category).or_default() += 1;
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<String, u64> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(category, n)| (category.to_string(), *n))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_categories() {
+        let e = KernelError::from(LlmError::Unhealthy);
+        assert_eq!(e.category(), "llm.unhealthy");
+        assert!(matches!(e, KernelError::Llm(LlmError::Unhealthy)));
+        let e = KernelError::from(AuditError::from(std::io::Error::other("disk full")));
+        assert_eq!(
+            (e.category(), e.to_string().as_str()),
+            ("audit.io", "disk full")
+        );
+        let unclosed = String::from("(");
+        let invalid = regex::Regex::new(&unclosed).unwrap_err();
+        let e = KernelError::from(ConfigError::regex("orders", invalid));
+        assert_eq!(e.category(), "filter.pattern");
+        assert_eq!(format!("{:?}", KernelError::from("2 invalid")), "2 invalid");
+
+        let counts = ErrorCounts::default();
+        counts.count("audit.io");
+        counts.count("audit.io");
+        assert_eq!(counts.snapshot()["audit.io"], 2);
+    }
+}
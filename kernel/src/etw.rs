@@ -0,0 +1,547 @@
+//! ETW Consumer - Agent Events Without the Named Pipe (Windows)
+//!
+//! Agents that already emit TraceLogging events need no pipe plumbing:
+//! with `--etw-provider <GUID>` the kernel opens a real-time ETW session
+//! for that provider instead of the named pipe, decodes each event with
+//! TDH and feeds it into the pipeline as one JSON line:
+//!
+//! ```json
+//! {"etw":{"event":"OrderPlaced","id":0,"keyword":"0x0000000000000010","opcode":0,
+//!  "pid":4242,"provider":"...","task":0,"tid":4711},"level":"info","symbol":"BTC"}
+//! ```
+//!
+//! Payload fields are top-level, so untargeted rules see their values and
+//! `field = "symbol"` targets one. The ETW level becomes `level` (unless the
+//! payload has its own), the rest of the header lives under `etw.*`
+//! (`field = "etw.keyword"`). The whole session is one agent connection
+//! (`etw:<GUID>` in `/agents`). Struct and array payload fields are skipped.
+//!
+//! ```text
+//! tripwired --etw-provider 3f0e8a2c-5b7d-4e61-9a1f-2c4d6e8b0a13 --etw-level 4 --etw-keywords 0x30
+//! ```
+
+use serde_json::{json, Map, Value};
+use std::fmt;
+
+/// An ETW provider GUID
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Guid {
+    pub data1: u32,
+    pub data2: u16,
+    pub data3: u16,
+    pub data4: [u8; 8],
+}
+
+impl fmt::Display for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:08x}-{:04x}-{:04x}-{:02x}{:02x}-",
+            self.data1, self.data2, self.data3, self.data4[0], self.data4[1]
+        )?;
+        self.data4[2..]
+            .iter()
+            .try_for_each(|b| write!(f, "{:02x}", b))
+    }
+}
+
+impl Guid {
+    /// Decode the 16-byte in-memory layout (little-endian fields)
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let bytes: &[u8; 16] = bytes.try_into().ok()?;
+        Some(Self {
+            data1: u32::from_le_bytes(bytes[0..4].try_into().unwrap()),
+            data2: u16::from_le_bytes(bytes[4..6].try_into().unwrap()),
+            data3: u16::from_le_bytes(bytes[6..8].try_into().unwrap()),
+            data4: bytes[8..16].try_into().unwrap(),
+        })
+    }
+}
+
+/// Parse `xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx`, braces optional (for clap)
+pub fn parse_guid(s: &str) -> Result<Guid, String> {
+    let invalid = || format!("invalid GUID '{}'", s);
+    let inner = s.trim().trim_start_matches('{').trim_end_matches('}');
+    let groups: Vec<&str> = inner.split('-').collect();
+    let lengths: Vec<usize> = groups.iter().map(|g| g.len()).collect();
+    if lengths != [8, 4, 4, 4, 12] || !inner.chars().all(|c| c == '-' || c.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+    let tail = format!("{}{}", groups[3], groups[4]);
+    let mut data4 = [0u8; 8];
+    for (i, byte) in data4.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&tail[2 * i..2 * i + 2], 16).map_err(|_| invalid())?;
+    }
+    Ok(Guid {
+        data1: u32::from_str_radix(groups[0], 16).map_err(|_| invalid())?,
+        data2: u16::from_str_radix(groups[1], 16).map_err(|_| invalid())?,
+        data3: u16::from_str_radix(groups[2], 16).map_err(|_| invalid())?,
+        data4,
+    })
+}
+
+/// Parse a keyword mask, hex (`0x30`) or decimal (for clap)
+pub fn parse_keywords(s: &str) -> Result<u64, String> {
+    let parsed = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    parsed.map_err(|_| format!("invalid keyword mask '{}'", s))
+}
+
+/// What to subscribe to
+#[cfg(windows)]
+#[derive(Debug, Clone)]
+pub struct EtwSource {
+    pub provider: Guid,
+    /// Most verbose level delivered (1 critical .. 5 verbose)
+    pub level: u8,
+    /// MatchAnyKeyword mask (0 = every event)
+    pub keywords: u64,
+}
+
+/// A decoded event
+#[derive(Debug, Clone, PartialEq)]
+pub struct EtwEvent {
+    pub provider: Guid,
+    /// TraceLogging event name (manifest events: task name)
+    pub name: String,
+    pub id: u16,
+    pub level: u8,
+    pub keyword: u64,
+    pub opcode: u8,
+    pub task: u16,
+    pub pid: u32,
+    pub tid: u32,
+    /// Top-level payload fields, in event order
+    pub fields: Vec<(String, Value)>,
+}
+
+impl EtwEvent {
+    /// The JSON line fed into the pipeline
+    pub fn to_line(&self) -> String {
+        let mut line: Map<String, Value> = self.fields.iter().cloned().collect();
+        line.entry("level")
+            .or_insert_with(|| level_name(self.level).into());
+        line.insert(
+            "etw".to_string(),
+            json!({
+                "provider": self.provider.to_string(),
+                "event": self.name,
+                "id": self.id,
+                "keyword": format!("0x{:016x}", self.keyword),
+                "opcode": self.opcode,
+                "task": self.task,
+                "pid": self.pid,
+                "tid": self.tid,
+            }),
+        );
+        Value::Object(line).to_string()
+    }
+}
+
+/// ETW level as a log level name
+fn level_name(level: u8) -> &'static str {
+    match level {
+        0 => "always",
+        1 => "critical",
+        2 => "error",
+        3 => "warning",
+        4 => "info",
+        _ => "verbose",
+    }
+}
+
+/// Render a property's raw bytes by its TDH in-type; anything unknown or
+/// truncated comes out as hex
+fn format_value(in_type: u16, bytes: &[u8]) -> Value {
+    let decoded = match in_type {
+        // UNICODESTRING
+        1 => {
+            let units: Vec<u16> = bytes
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .take_while(|&u| u != 0)
+                .collect();
+            Some(String::from_utf16_lossy(&units).into())
+        }
+        // ANSISTRING
+        2 => {
+            let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+            Some(String::from_utf8_lossy(&bytes[..end]).into_owned().into())
+        }
+        3 => le::<1>(bytes).map(|b| i8::from_le_bytes(b).into()),
+        4 => le::<1>(bytes).map(|b| u8::from_le_bytes(b).into()),
+        5 => le::<2>(bytes).map(|b| i16::from_le_bytes(b).into()),
+        6 => le::<2>(bytes).map(|b| u16::from_le_bytes(b).into()),
+        7 => le::<4>(bytes).map(|b| i32::from_le_bytes(b).into()),
+        8 => le::<4>(bytes).map(|b| u32::from_le_bytes(b).into()),
+        9 => le::<8>(bytes).map(|b| i64::from_le_bytes(b).into()),
+        // UINT64, FILETIME
+        10 | 17 => le::<8>(bytes).map(|b| u64::from_le_bytes(b).into()),
+        11 => le::<4>(bytes).map(|b| f32::from_le_bytes(b).into()),
+        12 => le::<8>(bytes).map(|b| f64::from_le_bytes(b).into()),
+        // BOOLEAN (a 4-byte BOOL)
+        13 => le::<4>(bytes).map(|b| (u32::from_le_bytes(b) != 0).into()),
+        15 => Guid::from_bytes(bytes).map(|g| g.to_string().into()),
+        // POINTER, HEXINT32, HEXINT64
+        16 | 20 | 21 => match bytes.len() {
+            4 => le::<4>(bytes).map(|b| format!("0x{:x}", u32::from_le_bytes(b)).into()),
+            8 => le::<8>(bytes).map(|b| format!("0x{:x}", u64::from_le_bytes(b)).into()),
+            _ => None,
+        },
+        _ => None,
+    };
+    decoded.unwrap_or_else(|| {
+        let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        hex.into()
+    })
+}
+
+fn le<const N: usize>(bytes: &[u8]) -> Option<[u8; N]> {
+    bytes.try_into().ok()
+}
+
+#[cfg(windows)]
+pub use session::Session;
+
+/// The real-time session and TDH decoding
+#[cfg(windows)]
+mod session {
+    use super::{format_value, EtwEvent, EtwSource, Guid};
+    use std::ptr::{null, null_mut};
+    use tokio::sync::mpsc;
+    use tracing::{debug, warn};
+    use windows_sys::core::GUID;
+    use windows_sys::Win32::Foundation::{
+        ERROR_ALREADY_EXISTS, ERROR_INSUFFICIENT_BUFFER, ERROR_SUCCESS,
+    };
+    use windows_sys::Win32::System::Diagnostics::Etw::*;
+
+    /// A running session; events go to the channel until `stop`
+    pub struct Session {
+        name: Vec<u16>,
+        handle: CONTROLTRACE_HANDLE,
+        thread: std::thread::JoinHandle<()>,
+    }
+
+    /// Handed to the event callback through `UserContext`
+    struct Consumer {
+        provider: Guid,
+        events: mpsc::Sender<String>,
+    }
+
+    impl Session {
+        pub fn start(source: &EtwSource, events: mpsc::Sender<String>) -> std::io::Result<Self> {
+            let name = wide(&format!("tripwired-{}", source.provider));
+            let mut handle = CONTROLTRACE_HANDLE { Value: 0 };
+            let mut properties = Properties::new(name.len());
+            let mut status =
+                unsafe { StartTraceW(&mut handle, name.as_ptr(), properties.as_mut_ptr()) };
+            if status == ERROR_ALREADY_EXISTS {
+                // Left over from a kernel that did not shut down cleanly
+                warn!("🪟 Stopping stale ETW session from an earlier run");
+                stop(&name, CONTROLTRACE_HANDLE { Value: 0 });
+                properties = Properties::new(name.len());
+                status =
+                    unsafe { StartTraceW(&mut handle, name.as_ptr(), properties.as_mut_ptr()) };
+            }
+            check(status)?;
+
+            let provider = guid(source.provider);
+            let status = unsafe {
+                EnableTraceEx2(
+                    handle,
+                    &provider,
+                    EVENT_CONTROL_CODE_ENABLE_PROVIDER,
+                    source.level,
+                    source.keywords,
+                    0,
+                    0,
+                    null(),
+                )
+            };
+            if let Err(e) = check(status) {
+                stop(&name, handle);
+                return Err(e);
+            }
+
+            let consumer = Box::into_raw(Box::new(Consumer {
+                provider: source.provider,
+                events,
+            }));
+            let mut logger = name.clone();
+            let mut logfile: EVENT_TRACE_LOGFILEW = unsafe { std::mem::zeroed() };
+            logfile.LoggerName = logger.as_mut_ptr();
+            logfile.Anonymous1.ProcessTraceMode =
+                PROCESS_TRACE_MODE_REAL_TIME | PROCESS_TRACE_MODE_EVENT_RECORD;
+            logfile.Anonymous2.EventRecordCallback = Some(on_event);
+            logfile.Context = consumer.cast();
+            let trace = unsafe { OpenTraceW(&mut logfile) };
+            if trace.Value == u64::MAX || trace.Value == u32::MAX as u64 {
+                let e = std::io::Error::last_os_error();
+                drop(unsafe { Box::from_raw(consumer) });
+                stop(&name, handle);
+                return Err(e);
+            }
+
+            // ProcessTrace blocks until the session stops
+            let consumer = consumer as usize;
+            let thread = std::thread::Builder::new()
+                .name("tripwired-etw".to_string())
+                .spawn(move || {
+                    let status = unsafe { ProcessTrace(&trace, 1, null(), null()) };
+                    if status != ERROR_SUCCESS {
+                        warn!("🪟 ETW processing ended: error {}", status);
+                    }
+                    unsafe { CloseTrace(trace) };
+                    drop(unsafe { Box::from_raw(consumer as *mut Consumer) });
+                })?;
+            Ok(Self {
+                name,
+                handle,
+                thread,
+            })
+        }
+
+        /// Stop the session and wait for the last callback (blocking)
+        pub fn stop(self) {
+            stop(&self.name, self.handle);
+            let _ = self.thread.join();
+        }
+    }
+
+    /// EVENT_TRACE_PROPERTIES followed by room for the session name
+    struct Properties(Vec<u64>);
+
+    impl Properties {
+        fn new(name_len: usize) -> Self {
+            let header = std::mem::size_of::<EVENT_TRACE_PROPERTIES>();
+            let size = header + 2 * name_len;
+            let mut buffer = vec![0u64; size.div_ceil(8)];
+            let properties = buffer.as_mut_ptr() as *mut EVENT_TRACE_PROPERTIES;
+            unsafe {
+                (*properties).Wnode.BufferSize = size as u32;
+                (*properties).Wnode.Flags = WNODE_FLAG_TRACED_GUID;
+                // QueryPerformanceCounter timestamps
+                (*properties).Wnode.ClientContext = 1;
+                (*properties).LogFileMode = EVENT_TRACE_REAL_TIME_MODE;
+                (*properties).LoggerNameOffset = header as u32;
+            }
+            Self(buffer)
+        }
+
+        fn as_mut_ptr(&mut self) -> *mut EVENT_TRACE_PROPERTIES {
+            self.0.as_mut_ptr().cast()
+        }
+    }
+
+    fn stop(name: &[u16], handle: CONTROLTRACE_HANDLE) {
+        let mut properties = Properties::new(name.len());
+        let status = unsafe {
+            ControlTraceW(
+                handle,
+                name.as_ptr(),
+                properties.as_mut_ptr(),
+                EVENT_TRACE_CONTROL_STOP,
+            )
+        };
+        if status != ERROR_SUCCESS {
+            debug!("🪟 Stopping ETW session: error {}", status);
+        }
+    }
+
+    unsafe extern "system" fn on_event(record: *mut EVENT_RECORD) {
+        let record = &*record;
+        let consumer = &*(record.UserContext as *const Consumer);
+        // The session also delivers its own header event
+        if Guid::from(record.EventHeader.ProviderId) != consumer.provider {
+            return;
+        }
+        let event = decode(record, consumer.provider);
+        // Blocking applies backpressure to ETW's buffers while the pipeline is busy
+        let _ = consumer.events.blocking_send(event.to_line());
+    }
+
+    unsafe fn decode(record: &EVENT_RECORD, provider: Guid) -> EtwEvent {
+        let header = &record.EventHeader;
+        let descriptor = &header.EventDescriptor;
+        let mut event = EtwEvent {
+            provider,
+            name: format!("event-{}", descriptor.Id),
+            id: descriptor.Id,
+            level: descriptor.Level,
+            keyword: descriptor.Keyword,
+            opcode: descriptor.Opcode,
+            task: descriptor.Task,
+            pid: header.ProcessId,
+            tid: header.ThreadId,
+            fields: Vec::new(),
+        };
+
+        let mut size = 0u32;
+        if TdhGetEventInformation(record, 0, null(), null_mut(), &mut size)
+            != ERROR_INSUFFICIENT_BUFFER
+        {
+            return event;
+        }
+        let mut buffer = vec![0u64; (size as usize).div_ceil(8)];
+        let info = buffer.as_mut_ptr() as *mut TRACE_EVENT_INFO;
+        if TdhGetEventInformation(record, 0, null(), info, &mut size) != ERROR_SUCCESS {
+            return event;
+        }
+        let base = info as *const u8;
+        let info = &*info;
+        let name_offset = match info.Anonymous1.EventNameOffset {
+            0 => info.TaskNameOffset,
+            offset => offset,
+        };
+        if name_offset != 0 {
+            event.name = read_wide(base.add(name_offset as usize));
+        }
+
+        let properties = std::slice::from_raw_parts(
+            info.EventPropertyInfoArray.as_ptr(),
+            info.TopLevelPropertyCount as usize,
+        );
+        for property in properties {
+            let nested = PropertyStruct | PropertyParamCount | PropertyParamFixedCount;
+            if property.Flags & nested != 0 {
+                continue;
+            }
+            let name = base.add(property.NameOffset as usize);
+            let descriptor = PROPERTY_DATA_DESCRIPTOR {
+                PropertyName: name as u64,
+                ArrayIndex: u32::MAX,
+                Reserved: 0,
+            };
+            let mut len = 0u32;
+            if TdhGetPropertySize(record, 0, null(), 1, &descriptor, &mut len) != ERROR_SUCCESS {
+                continue;
+            }
+            let mut value = vec![0u8; len as usize];
+            if TdhGetProperty(record, 0, null(), 1, &descriptor, len, value.as_mut_ptr())
+                != ERROR_SUCCESS
+            {
+                continue;
+            }
+            let in_type = property.Anonymous1.nonStructType.InType;
+            event
+                .fields
+                .push((read_wide(name), format_value(in_type, &value)));
+        }
+        event
+    }
+
+    impl From<GUID> for Guid {
+        fn from(g: GUID) -> Self {
+            Self {
+                data1: g.data1,
+                data2: g.data2,
+                data3: g.data3,
+                data4: g.data4,
+            }
+        }
+    }
+
+    fn guid(g: Guid) -> GUID {
+        GUID {
+            data1: g.data1,
+            data2: g.data2,
+            data3: g.data3,
+            data4: g.data4,
+        }
+    }
+
+    fn check(status: u32) -> std::io::Result<()> {
+        match status {
+            ERROR_SUCCESS => Ok(()),
+            code => Err(std::io::Error::from_raw_os_error(code as i32)),
+        }
+    }
+
+    fn wide(s: &str) -> Vec<u16> {
+        s.encode_utf16().chain(std::iter::once(0)).collect()
+    }
+
+    unsafe fn read_wide(mut p: *const u8) -> String {
+        let mut units = Vec::new();
+        loop {
+            let unit = u16::from_le_bytes([*p, *p.add(1)]);
+            if unit == 0 {
+                return String::from_utf16_lossy(&units);
+            }
+            units.push(unit);
+            p = p.add(2);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_guid() {
+        let guid = parse_guid("{3F0E8A2C-5B7D-4E61-9A1F-2C4D6E8B0A13}").unwrap();
+        assert_eq!(guid.data1, 0x3f0e8a2c);
+        assert_eq!(guid.data4, [0x9a, 0x1f, 0x2c, 0x4d, 0x6e, 0x8b, 0x0a, 0x13]);
+        assert_eq!(guid.to_string(), "3f0e8a2c-5b7d-4e61-9a1f-2c4d6e8b0a13");
+        assert_eq!(parse_guid(&guid.to_string()), Ok(guid));
+        assert!(parse_guid("3f0e8a2c-5b7d-4e61-9a1f").is_err());
+        assert!(parse_guid("3f0e8a2c-5b7d-4e61-9a1f-2c4d6e8b0a1g").is_err());
+
+        assert_eq!(parse_keywords("0x30"), Ok(0x30));
+        assert_eq!(parse_keywords("48"), Ok(48));
+        assert!(parse_keywords("0xzz").is_err());
+    }
+
+    #[test]
+    fn test_format_value() {
+        let utf16: Vec<u8> = "BTC\0".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        assert_eq!(format_value(1, &utf16), json!("BTC"));
+        assert_eq!(format_value(2, b"sell\0"), json!("sell"));
+        assert_eq!(format_value(7, &(-5i32).to_le_bytes()), json!(-5));
+        assert_eq!(format_value(10, &u64::MAX.to_le_bytes()), json!(u64::MAX));
+        assert_eq!(format_value(12, &2.5f64.to_le_bytes()), json!(2.5));
+        assert_eq!(format_value(13, &1u32.to_le_bytes()), json!(true));
+        assert_eq!(format_value(21, &0xdeadu64.to_le_bytes()), json!("0xdead"));
+        // Truncated or unknown: hex
+        assert_eq!(format_value(8, &[1, 2]), json!("0102"));
+        assert_eq!(format_value(14, &[0xab, 0xcd]), json!("abcd"));
+    }
+
+    #[test]
+    fn test_event_line() {
+        let event = EtwEvent {
+            provider: parse_guid("3f0e8a2c-5b7d-4e61-9a1f-2c4d6e8b0a13").unwrap(),
+            name: "OrderPlaced".to_string(),
+            id: 0,
+            level: 3,
+            keyword: 0x10,
+            opcode: 0,
+            task: 0,
+            pid: 4242,
+            tid: 4711,
+            fields: vec![
+                ("symbol".to_string(), json!("BTC")),
+                ("exposure".to_string(), json!(5000)),
+            ],
+        };
+        let line = event.to_line();
+        let parsed = crate::parse::parse(&line);
+        assert_eq!(parsed.field("symbol"), Some("BTC"));
+        assert_eq!(parsed.field("level"), Some("warning"));
+        assert_eq!(parsed.field("etw.event"), Some("OrderPlaced"));
+        assert_eq!(parsed.field("etw.keyword"), Some("0x0000000000000010"));
+        assert_eq!(parsed.field("etw.pid"), Some("4242"));
+
+        // The payload's own level wins
+        let mut event = event;
+        event.fields.push(("level".to_string(), json!("error")));
+        assert_eq!(
+            crate::parse::parse(&event.to_line()).field("level"),
+            Some("error")
+        );
+    }
+}
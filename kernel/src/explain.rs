@@ -0,0 +1,210 @@
+//! Explanations - A Justification for Every KILL
+//!
+//! The fast verdict is a few tokens for latency; "sequential orders" does
+//! not tell the human on call whether the kill was right. With `[explain]`
+//! enabled every KILL is followed by a second LLM request for a 2-3
+//! sentence justification, given the line, the verdict and the agent's
+//! recent lines. The kill never waits for it:
+//!
+//! - The answer is audited as an `explanation` event linked by
+//!   `decision_id` (the KILL record is already written when the kill fires).
+//! - KILL notifications are held until the explanation arrives (at most
+//!   `timeout_ms`) and carry it as `explanation` / `{explanation}`.
+//!
+//! Without a healthy LLM (degraded-mode KILLs) no request is made and the
+//! notification goes out at once.
+//!
+//! ```toml
+//! [explain]
+//! enabled = true
+//! max_tokens = 160
+//! timeout_ms = 15000
+//! ```
+
+use crate::audit::{AuditTrail, DecisionRecord, ExplanationEvent};
+use crate::chain::LlmChain;
+use crate::notify::{Event, Notifier};
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio_util::task::TaskTracker;
+use tracing::{info, warn};
+
+/// Longest explanation kept (characters)
+const MAX_EXPLANATION_CHARS: usize = 1000;
+
+/// `[explain]` table
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExplainConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Answer length for the explanation request
+    #[serde(default = "default_max_tokens")]
+    pub max_tokens: u32,
+    /// Longest a KILL notification waits for its explanation (milliseconds)
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+fn default_max_tokens() -> u32 {
+    160
+}
+
+fn default_timeout_ms() -> u64 {
+    15_000
+}
+
+impl Default for ExplainConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_tokens: default_max_tokens(),
+            timeout_ms: default_timeout_ms(),
+        }
+    }
+}
+
+impl ExplainConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_tokens == 0 {
+            return Err("explain: max_tokens must be at least 1".to_string());
+        }
+        if self.timeout_ms == 0 {
+            return Err("explain: timeout_ms must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Explains KILL decisions in the background, then notifies
+pub struct Explainer {
+    config: ExplainConfig,
+    llm: Arc<LlmChain>,
+    audit: Arc<AuditTrail>,
+    notifier: Notifier,
+    /// In-flight explanations
+    tasks: TaskTracker,
+}
+
+impl Explainer {
+    pub fn new(
+        config: &ExplainConfig,
+        llm: Arc<LlmChain>,
+        audit: Arc<AuditTrail>,
+        notifier: Notifier,
+    ) -> Self {
+        Self {
+            config: config.clone(),
+            llm,
+            audit,
+            notifier,
+            tasks: TaskTracker::new(),
+        }
+    }
+
+    /// Explain the KILL `record` (redacted `context`: the agent's recent
+    /// lines), audit the answer and send the KILL notification with it
+    pub fn explain(&self, mut record: DecisionRecord, context: String, llm_healthy: bool) {
+        let llm = Arc::clone(&self.llm);
+        let audit = Arc::clone(&self.audit);
+        let notifier = self.notifier.clone();
+        let config = self.config.clone();
+        self.tasks.spawn(async move {
+            let start = Instant::now();
+            let result = match llm_healthy {
+                true => {
+                    let prompt = prompt(&record, &context);
+                    let call = llm.complete_text(&prompt, config.max_tokens);
+                    match tokio::time::timeout(Duration::from_millis(config.timeout_ms), call).await
+                    {
+                        Ok(Ok(answer)) => Ok(answer),
+                        Ok(Err(e)) => Err(e.to_string()),
+                        Err(_) => Err(format!("timed out after {}ms", config.timeout_ms)),
+                    }
+                }
+                false => Err("LLM unhealthy (canary probe failing)".to_string()),
+            };
+
+            let mut event = ExplanationEvent::new(record.id, start.elapsed().as_millis() as u64);
+            match result {
+                Ok((text, model)) => {
+                    let text = crate::line::preview(&text, MAX_EXPLANATION_CHARS).into_owned();
+                    info!("💬 [EXPLAIN] ID:{} {}", record.id, text);
+                    event.explanation = Some(text.clone());
+                    event.model = Some(model);
+                    record.explanation = Some(text);
+                }
+                Err(e) => {
+                    warn!("💬 No explanation for ID:{}: {}", record.id, e);
+                    event.error = Some(e);
+                }
+            }
+            if let Err(e) = audit.record_explanation(&event) {
+                warn!("💬 Failed to audit explanation for ID:{}: {}", record.id, e);
+            }
+            notifier.send(Event::Decision(Box::new(record)));
+        });
+    }
+
+    /// Wait (up to `timeout`) for in-flight explanations; returns false on
+    /// timeout
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        self.tasks.close();
+        tokio::time::timeout(timeout, self.tasks.wait())
+            .await
+            .is_ok()
+    }
+}
+
+/// The explanation request (`context` as rendered by the agent history)
+fn prompt(record: &DecisionRecord, context: &str) -> String {
+    let mut verdict = format!("{} ({}% confidence", record.action, record.confidence);
+    if let Some(ref rule) = record.rule {
+        verdict.push_str(&format!(", rule {}", rule));
+    }
+    verdict.push(')');
+    if let Some(ref reason) = record.reason {
+        verdict.push_str(&format!(": {}", reason));
+    }
+    let context = match context.trim_end() {
+        "" => String::new(),
+        context => format!("{}\n\n", context),
+    };
+    format!(
+        "An automated kill-switch just stopped an AI agent because of this log line:\n\n\
+         \"{}\"\n\n\
+         Verdict: {}\n\n\
+         {}In 2-3 sentences, explain to the on-call engineer why this line justifies \
+         stopping the agent, citing the specific evidence. Plain text only.",
+        record.input_log, verdict, context
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prompt() {
+        let record: DecisionRecord = serde_json::from_value(serde_json::json!({
+            "id": 7, "timestamp_ms": 0, "input_log": "Order #2 placed {context}",
+            "input_hash": "", "action": "KILL", "confidence": 97, "filtered": false,
+            "latency_ms": 180, "model_fingerprint": "", "prompt_hash": "",
+            "raw_response": null, "rule": "orders", "reason": "sequential orders",
+        }))
+        .unwrap();
+        let prompt = prompt(
+            &record,
+            "Recent lines from this agent (oldest first):\nOrder #1\n",
+        );
+        assert!(prompt.contains("\"Order #2 placed {context}\""));
+        assert!(prompt.contains("Verdict: KILL (97% confidence, rule orders): sequential orders"));
+        assert!(prompt.contains("oldest first):\nOrder #1\n\nIn 2-3 sentences"));
+
+        let config: ExplainConfig = toml::from_str("enabled = true").unwrap();
+        assert_eq!((config.max_tokens, config.timeout_ms), (160, 15_000));
+        assert!(config.validate().is_ok());
+        let config: ExplainConfig = toml::from_str("max_tokens = 0").unwrap();
+        assert!(config.validate().is_err());
+    }
+}
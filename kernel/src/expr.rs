@@ -0,0 +1,613 @@
+//! Policy Expressions - A Small Subset of CEL
+//!
+//! Enough of the Common Expression Language for operational rules over a
+//! decision and its surroundings, compiled once at config load:
+//!
+//! - literals: integers, `"strings"` / `'strings'`, `true`, `false`,
+//!   `null`, lists `[1, 2]`
+//! - variables and `.field` / `[index]` access
+//! - `!`, unary `-`, `* / %`, `+ -` (ints; `+` also joins strings and lists)
+//! - `== != < <= > >=`, `in` (list member or map key), `&&`, `||`, `? :`
+//! - `size(x)` / `x.size()`, and string methods `startsWith`, `endsWith`,
+//!   `contains`, `matches` (regex; the pattern must be a literal)
+//!
+//! No floats, macros, timestamps or durations. Evaluation errors (unknown
+//! field, type mismatch) are reported to the caller, never panics.
+
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// A runtime value
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Str(String),
+    List(Vec<Value>),
+    Map(BTreeMap<String, Value>),
+}
+
+impl Value {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Self::Null => "null",
+            Self::Bool(_) => "bool",
+            Self::Int(_) => "int",
+            Self::Str(_) => "string",
+            Self::List(_) => "list",
+            Self::Map(_) => "map",
+        }
+    }
+}
+
+impl From<serde_json::Value> for Value {
+    fn from(value: serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => Self::Null,
+            serde_json::Value::Bool(b) => Self::Bool(b),
+            serde_json::Value::Number(n) => Self::Int(
+                n.as_i64()
+                    .unwrap_or_else(|| n.as_f64().unwrap_or_default() as i64),
+            ),
+            serde_json::Value::String(s) => Self::Str(s),
+            serde_json::Value::Array(items) => {
+                Self::List(items.into_iter().map(Self::from).collect())
+            }
+            serde_json::Value::Object(map) => {
+                Self::Map(map.into_iter().map(|(k, v)| (k, Self::from(v))).collect())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    In,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+#[derive(Debug)]
+enum Node {
+    Lit(Value),
+    Ident(String),
+    List(Vec<Node>),
+    Field(Box<Node>, String),
+    Index(Box<Node>, Box<Node>),
+    Not(Box<Node>),
+    Neg(Box<Node>),
+    Binary(Op, Box<Node>, Box<Node>),
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+    Cond(Box<Node>, Box<Node>, Box<Node>),
+    /// `receiver.name(args)` or `name(args)`
+    Call(Option<Box<Node>>, String, Vec<Node>),
+    Matches(Box<Node>, Regex),
+}
+
+/// A compiled expression
+#[derive(Debug)]
+pub struct Expr {
+    root: Node,
+}
+
+impl Expr {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let tokens = lex(source)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let root = parser.ternary()?;
+        match parser.peek() {
+            None => Ok(Self { root }),
+            Some(token) => Err(format!("unexpected {}", token)),
+        }
+    }
+
+    pub fn eval(&self, env: &BTreeMap<String, Value>) -> Result<Value, String> {
+        eval(&self.root, env)
+    }
+
+    /// Evaluate to a bool (anything else is an error)
+    pub fn test(&self, env: &BTreeMap<String, Value>) -> Result<bool, String> {
+        match self.eval(env)? {
+            Value::Bool(b) => Ok(b),
+            other => Err(format!("expected bool, got {}", other.type_name())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Int(i64),
+    Str(String),
+    Ident(String),
+    Punct(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Int(n) => write!(f, "{}", n),
+            Self::Str(s) => write!(f, "{:?}", s),
+            Self::Ident(s) => write!(f, "'{}'", s),
+            Self::Punct(p) => write!(f, "'{}'", p),
+        }
+    }
+}
+
+/// Longest first, so `<=` wins over `<`
+const PUNCT: &[&str] = &[
+    "==", "!=", "<=", ">=", "&&", "||", "<", ">", "!", "+", "-", "*", "/", "%", "(", ")", "[", "]",
+    ",", ".", "?", ":",
+];
+
+fn lex(source: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let n = text
+                .parse()
+                .map_err(|_| format!("integer {} out of range", text))?;
+            tokens.push(Token::Int(n));
+        } else if c == '"' || c == '\'' {
+            i += 1;
+            let mut s = String::new();
+            loop {
+                match chars.get(i) {
+                    None => return Err("unterminated string".to_string()),
+                    Some(&q) if q == c => break,
+                    Some('\\') => {
+                        i += 1;
+                        s.push(match chars.get(i) {
+                            Some('n') => '\n',
+                            Some('t') => '\t',
+                            Some(&other) => other,
+                            None => return Err("unterminated string".to_string()),
+                        });
+                    }
+                    Some(&other) => s.push(other),
+                }
+                i += 1;
+            }
+            i += 1;
+            tokens.push(Token::Str(s));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else {
+            let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+            let punct = PUNCT
+                .iter()
+                .find(|p| rest.starts_with(**p))
+                .ok_or_else(|| format!("unexpected character '{}'", c))?;
+            i += punct.len();
+            tokens.push(Token::Punct(punct));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Punct(p)) if *p == punct) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<(), String> {
+        if self.eat(punct) {
+            return Ok(());
+        }
+        match self.peek() {
+            Some(token) => Err(format!("expected '{}', found {}", punct, token)),
+            None => Err(format!("expected '{}' at end of expression", punct)),
+        }
+    }
+
+    fn ternary(&mut self) -> Result<Node, String> {
+        let cond = self.or()?;
+        if !self.eat("?") {
+            return Ok(cond);
+        }
+        let then = self.ternary()?;
+        self.expect(":")?;
+        let otherwise = self.ternary()?;
+        Ok(Node::Cond(
+            Box::new(cond),
+            Box::new(then),
+            Box::new(otherwise),
+        ))
+    }
+
+    fn or(&mut self) -> Result<Node, String> {
+        let mut left = self.and()?;
+        while self.eat("||") {
+            left = Node::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Node, String> {
+        let mut left = self.relation()?;
+        while self.eat("&&") {
+            left = Node::And(Box::new(left), Box::new(self.relation()?));
+        }
+        Ok(left)
+    }
+
+    fn relation(&mut self) -> Result<Node, String> {
+        let left = self.additive()?;
+        let op = match self.peek() {
+            Some(Token::Punct("==")) => Op::Eq,
+            Some(Token::Punct("!=")) => Op::Ne,
+            Some(Token::Punct("<")) => Op::Lt,
+            Some(Token::Punct("<=")) => Op::Le,
+            Some(Token::Punct(">")) => Op::Gt,
+            Some(Token::Punct(">=")) => Op::Ge,
+            Some(Token::Ident(word)) if word == "in" => Op::In,
+            _ => return Ok(left),
+        };
+        self.pos += 1;
+        let right = self.additive()?;
+        Ok(Node::Binary(op, Box::new(left), Box::new(right)))
+    }
+
+    fn additive(&mut self) -> Result<Node, String> {
+        let mut left = self.multiplicative()?;
+        loop {
+            let op = if self.eat("+") {
+                Op::Add
+            } else if self.eat("-") {
+                Op::Sub
+            } else {
+                return Ok(left);
+            };
+            left = Node::Binary(op, Box::new(left), Box::new(self.multiplicative()?));
+        }
+    }
+
+    fn multiplicative(&mut self) -> Result<Node, String> {
+        let mut left = self.unary()?;
+        loop {
+            let op = if self.eat("*") {
+                Op::Mul
+            } else if self.eat("/") {
+                Op::Div
+            } else if self.eat("%") {
+                Op::Rem
+            } else {
+                return Ok(left);
+            };
+            left = Node::Binary(op, Box::new(left), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Node, String> {
+        if self.eat("!") {
+            return Ok(Node::Not(Box::new(self.unary()?)));
+        }
+        if self.eat("-") {
+            return Ok(Node::Neg(Box::new(self.unary()?)));
+        }
+        self.member()
+    }
+
+    fn member(&mut self) -> Result<Node, String> {
+        let mut node = self.primary()?;
+        loop {
+            if self.eat(".") {
+                let Some(Token::Ident(name)) = self.peek().cloned() else {
+                    return Err("expected a field name after '.'".to_string());
+                };
+                self.pos += 1;
+                if self.eat("(") {
+                    let args = self.args(")")?;
+                    node = call(Some(node), name, args)?;
+                } else {
+                    node = Node::Field(Box::new(node), name);
+                }
+            } else if self.eat("[") {
+                let index = self.ternary()?;
+                self.expect("]")?;
+                node = Node::Index(Box::new(node), Box::new(index));
+            } else {
+                return Ok(node);
+            }
+        }
+    }
+
+    fn primary(&mut self) -> Result<Node, String> {
+        let Some(token) = self.peek().cloned() else {
+            return Err("unexpected end of expression".to_string());
+        };
+        self.pos += 1;
+        match token {
+            Token::Int(n) => Ok(Node::Lit(Value::Int(n))),
+            Token::Str(s) => Ok(Node::Lit(Value::Str(s))),
+            Token::Ident(name) => match name.as_str() {
+                "true" => Ok(Node::Lit(Value::Bool(true))),
+                "false" => Ok(Node::Lit(Value::Bool(false))),
+                "null" => Ok(Node::Lit(Value::Null)),
+                _ if self.eat("(") => {
+                    let args = self.args(")")?;
+                    call(None, name, args)
+                }
+                _ => Ok(Node::Ident(name)),
+            },
+            Token::Punct("(") => {
+                let node = self.ternary()?;
+                self.expect(")")?;
+                Ok(node)
+            }
+            Token::Punct("[") => Ok(Node::List(self.args("]")?)),
+            other => Err(format!("unexpected {}", other)),
+        }
+    }
+
+    /// Comma-separated expressions up to `close`
+    fn args(&mut self, close: &str) -> Result<Vec<Node>, String> {
+        let mut args = Vec::new();
+        if self.eat(close) {
+            return Ok(args);
+        }
+        loop {
+            args.push(self.ternary()?);
+            if self.eat(close) {
+                return Ok(args);
+            }
+            self.expect(",")?;
+        }
+    }
+}
+
+/// Check a function call's shape at compile time
+fn call(receiver: Option<Node>, name: String, mut args: Vec<Node>) -> Result<Node, String> {
+    let arity = match (receiver.is_some(), name.as_str()) {
+        (false, "size") => 1,
+        (true, "size") => 0,
+        (true, "startsWith" | "endsWith" | "contains" | "matches") => 1,
+        _ => return Err(format!("unknown function {}()", name)),
+    };
+    if args.len() != arity {
+        return Err(format!("{}() takes {} argument(s)", name, arity));
+    }
+    let receiver = receiver.map(Box::new);
+    if name == "matches" {
+        let Node::Lit(Value::Str(pattern)) = args.remove(0) else {
+            return Err("matches() needs a string literal pattern".to_string());
+        };
+        let regex = Regex::new(&pattern).map_err(|e| format!("matches(): {}", e))?;
+        return Ok(Node::Matches(receiver.unwrap(), regex));
+    }
+    Ok(Node::Call(receiver, name, args))
+}
+
+fn eval(node: &Node, env: &BTreeMap<String, Value>) -> Result<Value, String> {
+    Ok(match node {
+        Node::Lit(value) => value.clone(),
+        Node::Ident(name) => env
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("unknown variable '{}'", name))?,
+        Node::List(items) => Value::List(
+            items
+                .iter()
+                .map(|item| eval(item, env))
+                .collect::<Result<_, _>>()?,
+        ),
+        Node::Field(target, name) => match eval(target, env)? {
+            Value::Map(mut map) => map
+                .remove(name)
+                .ok_or_else(|| format!("no field '{}'", name))?,
+            other => return Err(format!("cannot read .{} of {}", name, other.type_name())),
+        },
+        Node::Index(target, index) => match (eval(target, env)?, eval(index, env)?) {
+            (Value::List(items), Value::Int(i)) => usize::try_from(i)
+                .ok()
+                .and_then(|i| items.into_iter().nth(i))
+                .ok_or_else(|| format!("index {} out of range", i))?,
+            (Value::Map(mut map), Value::Str(key)) => map
+                .remove(&key)
+                .ok_or_else(|| format!("no key '{}'", key))?,
+            (target, index) => {
+                return Err(format!(
+                    "cannot index {} with {}",
+                    target.type_name(),
+                    index.type_name()
+                ))
+            }
+        },
+        Node::Not(inner) => Value::Bool(!boolean(eval(inner, env)?)?),
+        Node::Neg(inner) => match eval(inner, env)? {
+            Value::Int(n) => Value::Int(n.checked_neg().ok_or("integer overflow")?),
+            other => return Err(format!("cannot negate {}", other.type_name())),
+        },
+        Node::And(left, right) => {
+            Value::Bool(boolean(eval(left, env)?)? && boolean(eval(right, env)?)?)
+        }
+        Node::Or(left, right) => {
+            Value::Bool(boolean(eval(left, env)?)? || boolean(eval(right, env)?)?)
+        }
+        Node::Cond(cond, then, otherwise) => {
+            if boolean(eval(cond, env)?)? {
+                eval(then, env)?
+            } else {
+                eval(otherwise, env)?
+            }
+        }
+        Node::Binary(op, left, right) => binary(*op, eval(left, env)?, eval(right, env)?)?,
+        Node::Call(receiver, name, args) => {
+            let target = match receiver {
+                Some(receiver) => eval(receiver, env)?,
+                None => eval(&args[0], env)?,
+            };
+            match (name.as_str(), target) {
+                ("size", Value::Str(s)) => Value::Int(s.chars().count() as i64),
+                ("size", Value::List(items)) => Value::Int(items.len() as i64),
+                ("size", Value::Map(map)) => Value::Int(map.len() as i64),
+                (method, Value::Str(s)) => {
+                    let Value::Str(arg) = eval(&args[0], env)? else {
+                        return Err(format!("{}() needs a string argument", method));
+                    };
+                    Value::Bool(match method {
+                        "startsWith" => s.starts_with(&arg),
+                        "endsWith" => s.ends_with(&arg),
+                        _ => s.contains(&arg),
+                    })
+                }
+                (name, other) => {
+                    return Err(format!("{}() not defined for {}", name, other.type_name()))
+                }
+            }
+        }
+        Node::Matches(target, regex) => match eval(target, env)? {
+            Value::Str(s) => Value::Bool(regex.is_match(&s)),
+            other => return Err(format!("matches() not defined for {}", other.type_name())),
+        },
+    })
+}
+
+fn boolean(value: Value) -> Result<bool, String> {
+    match value {
+        Value::Bool(b) => Ok(b),
+        other => Err(format!("expected bool, got {}", other.type_name())),
+    }
+}
+
+fn binary(op: Op, left: Value, right: Value) -> Result<Value, String> {
+    use Value::{Bool, Int, List, Map, Str};
+    let mismatch = |left: &Value, right: &Value| {
+        Err(format!(
+            "no {:?} for {} and {}",
+            op,
+            left.type_name(),
+            right.type_name()
+        ))
+    };
+    Ok(match (op, left, right) {
+        (Op::Eq, l, r) => Bool(l == r),
+        (Op::Ne, l, r) => Bool(l != r),
+        (Op::In, l, List(items)) => Bool(items.contains(&l)),
+        (Op::In, Str(key), Map(map)) => Bool(map.contains_key(&key)),
+        (Op::Lt | Op::Le | Op::Gt | Op::Ge, l, r) => {
+            let ordering = match (&l, &r) {
+                (Int(a), Int(b)) => a.cmp(b),
+                (Str(a), Str(b)) => a.cmp(b),
+                _ => return mismatch(&l, &r),
+            };
+            Bool(match op {
+                Op::Lt => ordering.is_lt(),
+                Op::Le => ordering.is_le(),
+                Op::Gt => ordering.is_gt(),
+                _ => ordering.is_ge(),
+            })
+        }
+        (Op::Add, Str(a), Str(b)) => Str(a + &b),
+        (Op::Add, List(mut a), List(b)) => {
+            a.extend(b);
+            List(a)
+        }
+        (op, Int(a), Int(b)) => Int(match op {
+            Op::Add => a.checked_add(b),
+            Op::Sub => a.checked_sub(b),
+            Op::Mul => a.checked_mul(b),
+            Op::Div => a.checked_div(b),
+            _ => a.checked_rem(b),
+        }
+        .ok_or("integer overflow or division by zero")?),
+        (_, l, r) => return mismatch(&l, &r),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env() -> BTreeMap<String, Value> {
+        let Value::Map(map) = Value::from(serde_json::json!({
+            "decision": { "action": "KILL", "confidence": 92, "rule": null },
+            "agent": { "peer": "10.0.4.7:5123", "kills": 0 },
+            "history": { "actions": ["SUSTAIN", "KILL"] },
+            "time": { "hour": 14, "weekday": 3 },
+        })) else {
+            unreachable!()
+        };
+        map
+    }
+
+    fn check(source: &str) -> Result<Value, String> {
+        Expr::parse(source)?.eval(&env())
+    }
+
+    #[test]
+    fn test_eval() {
+        let yes = |source: &str| assert_eq!(check(source), Ok(Value::Bool(true)), "{}", source);
+        yes(r#"decision.action == "KILL" && decision.confidence >= 90"#);
+        yes("agent.peer.startsWith('10.0.4.') && time.hour >= 9 && time.hour < 16");
+        yes("time.weekday in [1, 2, 3, 4, 5]");
+        yes("'KILL' in history.actions && size(history.actions) == 2");
+        yes("history.actions[1] == 'KILL' && history.actions.size() == 2");
+        yes(r#"agent.peer.matches("^10\\.0\\.4\\.\\d+:")"#);
+        yes("decision.rule == null || decision.rule.startsWith('x')");
+        yes("!(agent.kills > 0) && -agent.kills == 0");
+        yes("(1 + 2 * 3) % 4 == 3 && 'a' + 'b' == 'ab'");
+        yes("(time.hour > 12 ? 'pm' : 'am') == 'pm'");
+        // && short-circuits past the error on the right
+        yes("agent.kills > 0 && agent.nope || true");
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = |source: &str| assert!(check(source).is_err(), "{}", source);
+        err("agent.nope == 1");
+        err("decision.confidence > 'x'");
+        err("1 / 0 == 0");
+        err("history.actions[5] == 'x'");
+        err("!decision.action");
+
+        let bad = |source: &str| assert!(Expr::parse(source).is_err(), "{}", source);
+        bad("decision.action ==");
+        bad("(1 + 2");
+        bad("decision.action = 'KILL'");
+        bad("now()");
+        bad("agent.peer.matches(agent.peer)");
+        bad("agent.peer.matches('(')");
+        bad("'unterminated");
+        bad("1 2");
+    }
+}
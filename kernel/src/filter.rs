@@ -5,14 +5,166 @@
 //!
 //! ## Tier Architecture
 //! - **Essential**: System-critical patterns (always enabled, read-only)
-//! - **Domain**: Trading, DevOps, or Generic presets
+//! - **Domain**: a preset - Trading, DevOps, Generic or a `--preset-dir`
+//!   file (see `preset`)
 //! - **Custom**: User-defined patterns from config file
 //!
+//! Every pattern carries a name, severity, and action. Rules with
+//! `action = "kill"` bypass the LLM for a deterministic verdict.
+//! Named `[[rule]]` tables add ids, descriptions, and enable flags;
+//! per-rule match counters show which rules actually fire.
+//!
+//! ## Literal Pre-Screen
+//! Required literals are extracted from every pattern into one
+//! Aho-Corasick automaton. Lines containing none of them cannot match,
+//! so benign traffic skips regex evaluation entirely. The pre-screen is
+//! only enabled when every pattern yields literals (never a false negative).
+//!
 //! Runs in microseconds.
 
-use regex::RegexSet;
-use serde::Deserialize;
-use std::path::Path;
+use crate::authorize::AuthorizerConfig;
+use crate::backfill::BackfillConfig;
+use crate::batch::BatchConfig;
+use crate::budget::{RegexConfig, BUDGET_RULE};
+use crate::channel::{self, ChannelDef};
+use crate::checkpoint::CheckpointConfig;
+use crate::classify::ClassifierConfig;
+use crate::correlate::SequenceDef;
+use crate::credential::CredentialConfig;
+use crate::egress::EgressConfig;
+use crate::encrypt::EncryptConfig;
+use crate::explain::ExplainConfig;
+use crate::flow::FlowConfig;
+use crate::fswatch::FsConfig;
+use crate::guard::GuardConfig;
+use crate::ha::HaConfig;
+use crate::health::HealthConfig;
+use crate::honeypot::HoneypotDef;
+use crate::limit::LimitDef;
+use crate::llm::{PromptConfig, Sampling};
+use crate::multiline::MultilineConfig;
+use crate::notify::NotifyConfig;
+use crate::parse::{self, ParsedLine};
+use crate::policy::PolicyConfig;
+use crate::preset::{self, Preset};
+use crate::priority::PriorityConfig;
+use crate::queue::QueueConfig;
+use crate::rate::RateDef;
+use crate::redact::RedactConfig;
+use crate::resource::ResourceConfig;
+use crate::restrict::RestrictConfig;
+use crate::retention::RetentionConfig;
+use crate::s3::S3Config;
+use crate::schedule::ScheduleConfig;
+use crate::shadow::ShadowConfig;
+use crate::ship::OutputConfig;
+use crate::slo::SloConfig;
+use crate::snapshot::SnapshotConfig;
+use crate::valve::KillLimits;
+use crate::watchdog::WatchdogConfig;
+use aho_corasick::AhoCorasick;
+use regex::{Regex, RegexSet};
+use regex_syntax::hir::literal::{ExtractKind, Extractor};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Why a filter config was rejected
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    /// The file could not be read
+    #[error("{}: {source}", path.display())]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// TOML syntax, an unknown value or a value of the wrong type
+    #[error("{}:{line}:{column}: {message}", path.display())]
+    Parse {
+        path: PathBuf,
+        /// 1-based position of the offending span
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// A pattern that does not compile
+    #[error(transparent)]
+    Filter(#[from] FilterError),
+    /// A setting out of range or inconsistent with another
+    #[error("{0}")]
+    Invalid(String),
+}
+
+impl ConfigError {
+    pub fn regex(rule: &str, source: regex::Error) -> Self {
+        FilterError::Pattern {
+            rule: rule.to_string(),
+            source,
+        }
+        .into()
+    }
+
+    /// A TOML error in `content` (read from `path`), positioned by its span
+    pub fn parse(path: &Path, content: &str, error: &toml::de::Error) -> Self {
+        let offset = error.span().map_or(0, |span| span.start).min(content.len());
+        let before = &content[..offset];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+        ConfigError::Parse {
+            path: path.to_path_buf(),
+            line,
+            column,
+            message: error.message().trim_end().to_string(),
+        }
+    }
+
+    pub fn category(&self) -> &'static str {
+        match self {
+            ConfigError::Read { .. } => "config.read",
+            ConfigError::Parse { .. } => "config.parse",
+            ConfigError::Filter(e) => e.category(),
+            ConfigError::Invalid(_) => "config.invalid",
+        }
+    }
+}
+
+impl From<String> for ConfigError {
+    fn from(message: String) -> Self {
+        ConfigError::Invalid(message)
+    }
+}
+
+impl From<&str> for ConfigError {
+    fn from(message: &str) -> Self {
+        ConfigError::Invalid(message.to_string())
+    }
+}
+
+/// Why a rule set did not compile
+#[derive(Debug, thiserror::Error)]
+pub enum FilterError {
+    /// A pattern that does not compile, named by the rule it belongs to
+    #[error("rule '{rule}': invalid pattern: {source}")]
+    Pattern { rule: String, source: regex::Error },
+    /// Valid patterns that do not fit one combined set (`pattern` / `exclude`)
+    #[error("{set} set: {source}")]
+    PatternSet {
+        set: &'static str,
+        source: regex::Error,
+    },
+}
+
+impl FilterError {
+    pub fn category(&self) -> &'static str {
+        match self {
+            FilterError::Pattern { .. } => "filter.pattern",
+            FilterError::PatternSet { .. } => "filter.pattern_set",
+        }
+    }
+}
 
 /// Essential patterns - system-critical, ALWAYS enabled (read-only)
 /// These patterns detect operations that are dangerous regardless of domain
@@ -47,138 +199,1005 @@ pub const ESSENTIAL_PATTERNS: &[&str] = &[
     r"(?i)mkfs\.",                     // Filesystem format
 ];
 
-/// Trading domain patterns - financial operation signals
-pub const TRADING_PATTERNS: &[&str] = &[
-    r"(?i)order",
-    r"(?i)buy|sell",
-    r"(?i)trade|position",
-    r"(?i)error|exception|failed",
-    r"(?i)warning|critical|alert",
-    r"(?i)exposure|leverage|margin",
-    r"within \d+\s?ms",
-    r"#\d{3,}", // Sequential order numbers
-];
+/// Rule severity - the highest-severity match wins
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Low,
+    #[default]
+    Medium,
+    High,
+    Critical,
+}
 
-/// DevOps domain patterns
-pub const DEVOPS_PATTERNS: &[&str] = &[
-    r"(?i)deploy",
-    r"(?i)rollback",
-    r"(?i)scale\s+(up|down)",
-    r"(?i)restart",
-    r"(?i)pipeline",
-    r"(?i)ci/cd",
-];
+/// What happens when a rule matches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleAction {
+    /// Escalate to the LLM (default)
+    #[default]
+    Analyze,
+    /// Deterministic KILL - no LLM round trip
+    Kill,
+}
+
+/// Analysis lane for a rule's lines (see `priority`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    #[default]
+    Normal,
+    /// Skip the batch queue and flow-control delays; `[priority] timeout_ms` applies
+    High,
+}
+
+/// Tier a rule belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Tier {
+    Essential,
+    Domain,
+    #[default]
+    Custom,
+}
 
-/// Generic domain patterns (minimal)
-pub const GENERIC_PATTERNS: &[&str] =
-    &[r"(?i)error|exception|failed", r"(?i)warning|critical|alert"];
+/// Named rule from a `[[rule]]` table
+///
+/// ```toml
+/// [[rule]]
+/// id = "wipe-root"
+/// description = "Recursive delete of the filesystem root"
+/// pattern = 'rm\s+-rf\s+/(\s|$)'
+/// severity = "critical"
+/// action = "kill"
+///
+/// # Judged ahead of the backlog during a flood
+/// [[rule]]
+/// id = "drop-database"
+/// pattern = '(?i)drop\s+database'
+/// priority = "high"
+///
+/// # Structured lines: match only the message field
+/// [[rule]]
+/// id = "drop-table-msg"
+/// field = "msg"
+/// pattern = '(?i)drop\s+table'
+///
+/// # Tool calls: only these tools, one argument
+/// [[rule]]
+/// id = "shell-curl-pipe"
+/// tools = ["shell", "bash"]
+/// field = "args.cmd"
+/// pattern = 'curl[^|]*\|\s*(ba)?sh'
+/// action = "kill"
+///
+/// # No pattern: toggle a built-in domain rule by id
+/// [[rule]]
+/// id = "trading#0"
+/// enabled = false
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct RuleDef {
+    pub id: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Regex; omit to override a built-in domain rule with the same id
+    #[serde(default)]
+    pub pattern: Option<String>,
+    /// Match one field of JSON / logfmt lines instead of the whole line
+    #[serde(default)]
+    pub field: Option<String>,
+    /// Only match tool calls of these tools (empty: any line)
+    #[serde(default)]
+    pub tools: Vec<String>,
+    #[serde(default)]
+    pub tier: Tier,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub severity: Severity,
+    #[serde(default)]
+    pub action: RuleAction,
+    #[serde(default)]
+    pub priority: Priority,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// Custom pattern: a bare regex string or a table with metadata
+///
+/// ```toml
+/// patterns = [
+///   "(?i)invoice.*void",
+///   { name = "wipe-root", pattern = 'rm\s+-rf\s+/(\s|$)', severity = "critical", action = "kill" },
+/// ]
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum PatternSpec {
+    Plain(String),
+    Rule(PatternRule),
+}
+
+/// Custom pattern with metadata
+#[derive(Debug, Clone, Deserialize)]
+pub struct PatternRule {
+    pub name: String,
+    pub pattern: String,
+    /// Match one field of JSON / logfmt lines instead of the whole line
+    #[serde(default)]
+    pub field: Option<String>,
+    /// Only match tool calls of these tools (empty: any line)
+    #[serde(default)]
+    pub tools: Vec<String>,
+    #[serde(default)]
+    pub severity: Severity,
+    #[serde(default)]
+    pub action: RuleAction,
+    #[serde(default)]
+    pub priority: Priority,
+}
+
+impl PatternSpec {
+    pub fn pattern(&self) -> &str {
+        match self {
+            PatternSpec::Plain(p) => p,
+            PatternSpec::Rule(r) => &r.pattern,
+        }
+    }
+}
+
+impl From<&str> for PatternSpec {
+    fn from(pattern: &str) -> Self {
+        PatternSpec::Plain(pattern.to_string())
+    }
+}
+
+/// Metadata for one compiled pattern (parallel to the RegexSet indices)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RuleMeta {
+    /// Rule name; anonymous patterns are named `<tier>#<index>`
+    pub name: String,
+    pub tier: Tier,
+    pub severity: Severity,
+    pub action: RuleAction,
+    pub priority: Priority,
+    pub description: Option<String>,
+    /// Field the rule targets (`None` = whole line / all values)
+    pub field: Option<String>,
+    /// Tools whose calls the rule is scoped to (empty = any line)
+    pub tools: Vec<String>,
+}
+
+/// Runtime match count for one rule
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleStat {
+    pub id: String,
+    pub tier: Tier,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub matches: u64,
+    /// Lines evaluated against the rule (user patterns only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub evals: Option<u64>,
+    /// Total evaluation time in microseconds (user patterns only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eval_us: Option<u64>,
+}
+
+/// Runtime match count for one exclude pattern
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExcludeStat {
+    /// `exclude#<index>`
+    pub id: String,
+    pub matches: u64,
+}
 
 /// Filter configuration loaded from TOML
 #[derive(Debug, Clone, Deserialize, Default)]
 pub struct FilterConfig {
-    /// Domain preset: "trading", "devops", "generic", or none
+    /// Domain preset: "trading" (default), "devops", "generic", or one
+    /// from `--preset-dir`
     #[serde(default)]
     pub domain: Option<String>,
 
+    /// Presets loaded from `--preset-dir` (ahead of the built-ins)
+    #[serde(skip)]
+    pub presets: Vec<Preset>,
+
     /// Custom patterns (added to Essential + Domain)
     #[serde(default)]
-    pub patterns: Vec<String>,
+    pub patterns: Vec<PatternSpec>,
 
-    /// Exclude patterns (whitelist - skip if matched)
+    /// Exclude patterns (whitelist - skip Domain and Custom rules if matched)
     #[serde(default)]
     pub exclude: Vec<String>,
+
+    /// UNSAFE: let excludes suppress Essential rules too
+    #[serde(default)]
+    pub unsafe_exclude_essential: bool,
+
+    /// Named rules (`[[rule]]` tables)
+    #[serde(default)]
+    pub rule: Vec<RuleDef>,
+
+    /// Multi-line sequence rules (`[[sequence]]` tables)
+    #[serde(default)]
+    pub sequence: Vec<SequenceDef>,
+
+    /// Frequency / ratio rules (`[[rate]]` tables)
+    #[serde(default)]
+    pub rate: Vec<RateDef>,
+
+    /// Numeric hard limits and rate-of-change guards (`[[limit]]` tables)
+    #[serde(default)]
+    pub limit: Vec<LimitDef>,
+
+    /// PII redaction before the audit trail and the LLM (`[redact]` table)
+    #[serde(default)]
+    pub redact: RedactConfig,
+
+    /// LLM prompt settings (`[prompt]` table)
+    #[serde(default)]
+    pub prompt: PromptConfig,
+
+    /// Sampling profiles by model name (`[model."<name>"]` / `[model.default]`)
+    #[serde(default)]
+    pub model: HashMap<String, Sampling>,
+
+    /// LLM canary probe and degraded-mode policy (`[health]` table)
+    #[serde(default)]
+    pub health: HealthConfig,
+
+    /// Batched LLM analysis under load (`[batch]` table)
+    #[serde(default)]
+    pub batch: BatchConfig,
+
+    /// Webhooks and email for KILL, FAIL and circuit-breaker events (`[notify]` table)
+    #[serde(default)]
+    pub notify: NotifyConfig,
+
+    /// Kill cooldown and hourly cap (`[kill_limits]` table)
+    #[serde(default)]
+    pub kill_limits: KillLimits,
+
+    /// Rules that can change a verdict before it is acted on (`[policy]` table)
+    #[serde(default)]
+    pub policy: PolicyConfig,
+
+    /// Time-based behavior profiles (`[schedule]` table)
+    #[serde(default)]
+    pub schedule: ScheduleConfig,
+
+    /// Independent pipelines hosted by this kernel (`[channel.<name>]` tables)
+    #[serde(default)]
+    pub channel: BTreeMap<String, ChannelDef>,
+
+    /// Active/standby pairing with another kernel (`[ha]` table)
+    #[serde(default)]
+    pub ha: Option<HaConfig>,
+
+    /// Re-analysis of lines spilled while the LLM was down (`[backfill]` table)
+    #[serde(default)]
+    pub backfill: BackfillConfig,
+
+    /// Last resort when the kernel itself stalls (`[watchdog]` table)
+    #[serde(default)]
+    pub watchdog: WatchdogConfig,
+
+    /// Per-agent input rate limits (`[flow]` table)
+    #[serde(default)]
+    pub flow: FlowConfig,
+
+    /// Follow-up justification of KILL decisions (`[explain]` table)
+    #[serde(default)]
+    pub explain: ExplainConfig,
+
+    /// External countersignature of KILLs (`[authorizer]` table)
+    #[serde(default)]
+    pub authorizer: AuthorizerConfig,
+
+    /// Checkpoints of KILL targets before the kill (`[snapshot]` table)
+    #[serde(default)]
+    pub snapshot: SnapshotConfig,
+
+    /// In-process pre-screen of escalated lines (`[classifier]` table)
+    #[serde(default)]
+    pub classifier: ClassifierConfig,
+
+    /// Capability tightening for RESTRICT (`[restrict]` table)
+    #[serde(default)]
+    pub restrict: RestrictConfig,
+
+    /// Second analyzer evaluated on live traffic, never acted on (`[shadow]` table)
+    #[serde(default)]
+    pub shadow: Option<ShadowConfig>,
+
+    /// Line-to-verdict deadline for LLM analyses (`[slo]` table)
+    #[serde(default)]
+    pub slo: SloConfig,
+
+    /// LLM timeout for `priority = "high"` lines (`[priority]` table)
+    #[serde(default)]
+    pub priority: PriorityConfig,
+
+    /// Lines sent to the LLM, persisted until judged (`[queue]` table)
+    #[serde(default)]
+    pub queue: QueueConfig,
+
+    /// Merkle checkpoints over the audit trail (`[checkpoint]` table)
+    #[serde(default)]
+    pub checkpoint: CheckpointConfig,
+
+    /// Audit payload encryption (`[encrypt]` table)
+    #[serde(default)]
+    pub encrypt: EncryptConfig,
+
+    /// Audit file rotation and pruning (`[retention]` table)
+    #[serde(default)]
+    pub retention: RetentionConfig,
+
+    /// Upload of rotated audit segments to object storage (`[s3]` table)
+    #[serde(default)]
+    pub s3: S3Config,
+
+    /// GELF / Logstash decision outputs (`[[output]]` entries)
+    #[serde(default)]
+    pub output: Vec<OutputConfig>,
+
+    /// Pre-execution approval of `CHECK` requests (`[guard]` table)
+    #[serde(default)]
+    pub guard: GuardConfig,
+
+    /// Size limit and time budget for user patterns (`[regex]` table)
+    #[serde(default)]
+    pub regex: RegexConfig,
+
+    /// Stack traces and other continuation lines as one record (`[multiline]` table)
+    #[serde(default)]
+    pub multiline: MultilineConfig,
+
+    /// CPU, memory, handle and child-process triggers on `--target-pid` (`[resource]` table)
+    #[serde(default)]
+    pub resource: ResourceConfig,
+
+    /// New network destinations and upload volume of `--target-pid` (`[egress]` table)
+    #[serde(default)]
+    pub egress: EgressConfig,
+
+    /// Watched and sensitive paths turned into synthetic lines (`[fs]` table)
+    #[serde(default)]
+    pub fs: FsConfig,
+
+    /// Credential store and clipboard reads of `--target-pid` via ETW (`[credential]` table)
+    #[serde(default)]
+    pub credential: CredentialConfig,
+
+    /// Decoy files and registry keys (`[[honeypot]]` entries)
+    #[serde(default)]
+    pub honeypot: Vec<HoneypotDef>,
 }
 
 impl FilterConfig {
-    /// Load config from TOML file
-    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
-        let content = std::fs::read_to_string(path)?;
-        let config: FilterConfig = toml::from_str(&content)?;
+    /// Load config from TOML file, with presets from `--preset-dir`
+    pub fn load(path: &Path, presets: Vec<Preset>) -> Result<Self, ConfigError> {
+        let content = std::fs::read_to_string(path).map_err(|source| ConfigError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let mut config: FilterConfig =
+            toml::from_str(&content).map_err(|e| ConfigError::parse(path, &content, &e))?;
+        config.presets = presets;
         config.validate()?;
         Ok(config)
     }
 
-    /// Validate all regex patterns compile
-    pub fn validate(&self) -> Result<(), regex::Error> {
-        for p in &self.patterns {
-            regex::Regex::new(p)?;
+    /// Validate all regex patterns compile and rule ids are sound
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.preset().is_none() {
+            return Err(format!("unknown domain preset '{}'", self.domain_name()).into());
+        }
+        for (i, spec) in self.patterns.iter().enumerate() {
+            let name = match spec {
+                PatternSpec::Plain(_) => format!("custom#{}", i),
+                PatternSpec::Rule(r) => r.name.clone(),
+            };
+            regex::Regex::new(spec.pattern()).map_err(|e| ConfigError::regex(&name, e))?;
+        }
+        for (i, p) in self.exclude.iter().enumerate() {
+            regex::Regex::new(p).map_err(|e| ConfigError::regex(&format!("exclude#{}", i), e))?;
+        }
+
+        let builtin: Vec<RuleMeta> = self.builtin_rules().into_iter().map(|(_, m)| m).collect();
+        let mut seen = std::collections::HashSet::from([BUDGET_RULE]);
+        for rule in &self.rule {
+            if !seen.insert(rule.id.as_str()) {
+                return Err(format!("duplicate rule id '{}'", rule.id).into());
+            }
+            match (&rule.pattern, builtin.iter().find(|m| m.name == rule.id)) {
+                (Some(_), Some(_)) => {
+                    return Err(
+                        format!("rule id '{}' collides with a built-in rule", rule.id).into(),
+                    )
+                }
+                (Some(p), None) => {
+                    regex::Regex::new(p).map_err(|e| ConfigError::regex(&rule.id, e))?;
+                }
+                (None, Some(m)) if m.tier == Tier::Essential => {
+                    return Err(format!("essential rule '{}' cannot be overridden", rule.id).into())
+                }
+                (None, Some(_)) => {}
+                (None, None) => {
+                    return Err(format!("rule '{}' has no pattern", rule.id).into());
+                }
+            }
+        }
+
+        for seq in &self.sequence {
+            if !seen.insert(seq.id.as_str()) {
+                return Err(format!("duplicate rule id '{}'", seq.id).into());
+            }
+            seq.validate()?;
+        }
+
+        for rate in &self.rate {
+            if !seen.insert(rate.id.as_str()) {
+                return Err(format!("duplicate rule id '{}'", rate.id).into());
+            }
+            rate.validate()?;
+        }
+
+        for limit in &self.limit {
+            if !seen.insert(limit.id.as_str()) {
+                return Err(format!("duplicate rule id '{}'", limit.id).into());
+            }
+            limit.validate()?;
+        }
+        self.redact.validate()?;
+        for (name, sampling) in &self.model {
+            sampling
+                .validate()
+                .map_err(|e| format!("model profile '{}': {}", name, e))?;
         }
-        for p in &self.exclude {
-            regex::Regex::new(p)?;
+        self.health.validate()?;
+        self.batch.validate()?;
+        self.notify.validate()?;
+        self.policy.validate()?;
+        self.schedule.validate()?;
+        channel::validate(&self.channel)?;
+        if let Some(ref ha) = self.ha {
+            ha.validate()?;
         }
+        self.watchdog.validate()?;
+        self.backfill.validate()?;
+        self.flow.validate()?;
+        self.explain.validate()?;
+        self.authorizer.validate()?;
+        self.snapshot.validate()?;
+        self.classifier.validate()?;
+        self.restrict.validate()?;
+        if let Some(ref shadow) = self.shadow {
+            shadow.validate()?;
+        }
+        self.slo.validate()?;
+        self.priority.validate()?;
+        self.queue.validate()?;
+        self.checkpoint.validate()?;
+        self.encrypt.validate()?;
+        self.retention.validate()?;
+        self.s3.validate()?;
+        if self.s3.enabled() && !self.retention.enabled() {
+            return Err(
+                "s3: needs [retention] rotate (only rotated segments are uploaded)"
+                    .to_string()
+                    .into(),
+            );
+        }
+        for (i, output) in self.output.iter().enumerate() {
+            output
+                .validate()
+                .map_err(|e| format!("output[{}]: {}", i, e))?;
+        }
+        self.guard.validate()?;
+        self.multiline.validate()?;
+        self.resource.validate()?;
+        self.egress.validate()?;
+        self.fs.validate()?;
+        self.credential.validate()?;
+        for (i, decoy) in self.honeypot.iter().enumerate() {
+            decoy
+                .validate()
+                .map_err(|e| format!("honeypot[{}]: {}", i, e))?;
+        }
+        if self.backfill.spill_file.is_some() && self.health.interval_ms == 0 {
+            return Err(
+                "backfill needs the [health] canary probe (interval_ms > 0) to detect recovery"
+                    .into(),
+            );
+        }
+
+        self.regex.validate()?;
+        for (pattern, meta) in self.user_rules() {
+            self.regex
+                .compile(pattern)
+                .map_err(|e| ConfigError::regex(&meta.name, e))?;
+        }
+
+        // Individually valid patterns can still overflow the combined set
+        RegexSet::new(self.builtin_rules().into_iter().map(|(p, _)| p)).map_err(|source| {
+            FilterError::PatternSet {
+                set: "pattern",
+                source,
+            }
+        })?;
         Ok(())
     }
 
-    /// Get domain patterns based on preset
-    pub fn domain_patterns(&self) -> &'static [&'static str] {
-        match self.domain.as_deref() {
-            Some("trading") => TRADING_PATTERNS,
-            Some("devops") => DEVOPS_PATTERNS,
-            Some("generic") => GENERIC_PATTERNS,
-            _ => TRADING_PATTERNS, // Default to trading for backward compatibility
-        }
+    /// Sampling profile for a model: its own, else `default`, else built-in
+    pub fn sampling(&self, model: &str) -> Sampling {
+        self.model
+            .get(model)
+            .or_else(|| self.model.get("default"))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// The domain preset, if `domain` names one
+    pub fn preset(&self) -> Option<&Preset> {
+        preset::find(&self.presets, self.domain_name())
+    }
+
+    /// Domain preset name used for anonymous rule names
+    fn domain_name(&self) -> &str {
+        self.domain.as_deref().unwrap_or(preset::DEFAULT)
     }
 
-    /// Compile all patterns into RegexSet
-    pub fn compile(&self) -> RegexSet {
-        let mut patterns: Vec<&str> = Vec::new();
+    /// Built-in Essential + Domain patterns with metadata
+    fn builtin_rules(&self) -> Vec<(&str, RuleMeta)> {
+        let mut rules = Vec::new();
 
         // Essential always included
-        patterns.extend(ESSENTIAL_PATTERNS);
+        for (i, p) in ESSENTIAL_PATTERNS.iter().enumerate() {
+            rules.push((
+                *p,
+                anonymous("essential", Tier::Essential, i, Severity::High),
+            ));
+        }
 
         // Domain patterns
-        patterns.extend(self.domain_patterns());
+        let domain = self.domain_name();
+        let patterns = self.preset().map_or(&[][..], |p| &p.patterns);
+        for (i, p) in patterns.iter().enumerate() {
+            rules.push((
+                p.as_str(),
+                anonymous(domain, Tier::Domain, i, Severity::Medium),
+            ));
+        }
 
-        // Custom patterns (need to convert &String to &str)
-        let custom_refs: Vec<&str> = self.patterns.iter().map(|s| s.as_str()).collect();
-        patterns.extend(custom_refs);
+        rules
+    }
 
-        RegexSet::new(patterns).expect("Invalid regex patterns")
+    /// All enabled patterns with metadata, in rule order
+    /// (Essential, Domain, Custom patterns, `[[rule]]` tables)
+    pub fn rules(&self) -> Vec<(&str, RuleMeta)> {
+        let mut rules: Vec<(&str, RuleMeta)> = self
+            .builtin_rules()
+            .into_iter()
+            .filter(|(_, m)| {
+                // Pattern-less [[rule]] entries toggle built-in domain rules
+                m.tier == Tier::Essential
+                    || !self
+                        .rule
+                        .iter()
+                        .any(|r| r.pattern.is_none() && r.id == m.name && !r.enabled)
+            })
+            .collect();
+        rules.extend(self.user_rules());
+        rules
+    }
+
+    /// User-supplied patterns: custom patterns and `[[rule]]` tables
+    /// (Sigma imports included), evaluated within the `[regex]` budget
+    pub fn user_rules(&self) -> Vec<(&str, RuleMeta)> {
+        let mut rules = Vec::new();
+
+        // Custom patterns
+        for (i, spec) in self.patterns.iter().enumerate() {
+            let meta = match spec {
+                PatternSpec::Plain(_) => anonymous("custom", Tier::Custom, i, Severity::Medium),
+                PatternSpec::Rule(r) => RuleMeta {
+                    name: r.name.clone(),
+                    tier: Tier::Custom,
+                    severity: r.severity,
+                    action: r.action,
+                    priority: r.priority,
+                    description: None,
+                    field: r.field.clone(),
+                    tools: r.tools.clone(),
+                },
+            };
+            rules.push((spec.pattern(), meta));
+        }
+
+        // Named rules
+        for r in self.rule.iter().filter(|r| r.enabled) {
+            if let Some(ref pattern) = r.pattern {
+                let meta = RuleMeta {
+                    name: r.id.clone(),
+                    tier: r.tier,
+                    severity: r.severity,
+                    action: r.action,
+                    priority: r.priority,
+                    description: r.description.clone(),
+                    field: r.field.clone(),
+                    tools: r.tools.clone(),
+                };
+                rules.push((pattern.as_str(), meta));
+            }
+        }
+
+        rules
     }
 
     /// Compile exclude patterns
-    pub fn compile_excludes(&self) -> Option<RegexSet> {
+    pub fn compile_excludes(&self) -> Result<Option<RegexSet>, FilterError> {
         if self.exclude.is_empty() {
-            return None;
+            return Ok(None);
+        }
+        RegexSet::new(&self.exclude)
+            .map(Some)
+            .map_err(|source| FilterError::PatternSet {
+                set: "exclude",
+                source,
+            })
+    }
+}
+
+fn anonymous(prefix: &str, tier: Tier, index: usize, severity: Severity) -> RuleMeta {
+    RuleMeta {
+        name: format!("{}#{}", prefix, index),
+        tier,
+        severity,
+        action: RuleAction::Analyze,
+        priority: Priority::Normal,
+        description: None,
+        field: None,
+        tools: Vec::new(),
+    }
+}
+
+/// Literals every match of `pattern` must contain, if finitely many exist
+///
+/// Tries prefix literals first, then suffix literals. `None` means the
+/// pattern can match without any fixed keyword (e.g. `\d{3}`).
+fn required_literals(pattern: &str) -> Option<Vec<Vec<u8>>> {
+    let hir = regex_syntax::Parser::new().parse(pattern).ok()?;
+
+    for kind in [ExtractKind::Prefix, ExtractKind::Suffix] {
+        let seq = Extractor::new().kind(kind).extract(&hir);
+        if let Some(lits) = seq.literals() {
+            if !lits.is_empty() && lits.iter().all(|l| !l.as_bytes().is_empty()) {
+                return Some(lits.iter().map(|l| l.as_bytes().to_vec()).collect());
+            }
         }
-        Some(RegexSet::new(&self.exclude).expect("Invalid exclude patterns"))
     }
+    None
+}
+
+/// Build the keyword pre-screen, or `None` if any pattern lacks literals
+fn build_prescreen(patterns: &[&str]) -> Option<(AhoCorasick, usize)> {
+    let mut literals: Vec<Vec<u8>> = Vec::new();
+    for p in patterns {
+        literals.extend(required_literals(p)?);
+    }
+    literals.sort();
+    literals.dedup();
+
+    // ASCII case-insensitivity only widens matches - safe for a pre-screen
+    let ac = AhoCorasick::builder()
+        .ascii_case_insensitive(true)
+        .build(&literals)
+        .ok()?;
+    Some((ac, literals.len()))
+}
+
+/// A user pattern with its evaluation counters
+#[derive(Debug)]
+struct UserRule {
+    /// Index into `rules`
+    rule: usize,
+    /// Match this field of structured lines instead of the decoded text
+    field: Option<String>,
+    regex: Regex,
+    evals: AtomicU64,
+    eval_ns: AtomicU64,
 }
 
 /// Configurable filter instance
 #[derive(Debug)]
 pub struct Filter {
+    /// Built-in patterns (whole plain line / decoded structured values),
+    /// indexed like `rules`
     patterns: RegexSet,
+    /// User patterns, evaluated one by one
+    user_rules: Vec<UserRule>,
+    /// Per-line time budget for `user_rules` and the `regex-budget` rule index
+    budget: Option<(Duration, usize)>,
+    rules: Vec<RuleMeta>,
     excludes: Option<RegexSet>,
+    /// Excludes also suppress Essential rules
+    exclude_essential: bool,
+    /// Keyword pre-screen and its literal count
+    prescreen: Option<(AhoCorasick, usize)>,
+    /// Per-rule match counts (parallel to `rules`)
+    matches: Vec<AtomicU64>,
+    /// Per-exclude match counts (parallel to the exclude set)
+    exclude_matches: Vec<AtomicU64>,
+    /// Any `priority = "high"` rule
+    lanes: bool,
 }
 
 impl Filter {
-    /// Create filter with config
-    pub fn new(config: &FilterConfig) -> Self {
-        Self {
-            patterns: config.compile(),
-            excludes: config.compile_excludes(),
+    /// Compile the rule set of a validated config
+    pub fn new(config: &FilterConfig) -> Result<Self, FilterError> {
+        let (patterns, mut rules): (Vec<&str>, Vec<RuleMeta>) = config.rules().into_iter().unzip();
+        let builtin = rules.len() - config.user_rules().len();
+
+        let user_rules = (builtin..rules.len())
+            .map(|i| {
+                Ok(UserRule {
+                    rule: i,
+                    field: rules[i].field.clone(),
+                    regex: config.regex.compile(patterns[i]).map_err(|source| {
+                        FilterError::Pattern {
+                            rule: rules[i].name.clone(),
+                            source,
+                        }
+                    })?,
+                    evals: AtomicU64::new(0),
+                    eval_ns: AtomicU64::new(0),
+                })
+            })
+            .collect::<Result<Vec<_>, FilterError>>()?;
+        let budget = match config.regex.budget() {
+            Some(budget) if !user_rules.is_empty() => {
+                rules.push(RuleMeta {
+                    name: BUDGET_RULE.to_string(),
+                    tier: Tier::Custom,
+                    severity: Severity::Medium,
+                    action: RuleAction::Analyze,
+                    priority: Priority::Normal,
+                    description: Some("Line exhausted the [regex] time budget".to_string()),
+                    field: None,
+                    tools: Vec::new(),
+                });
+                Some((budget, rules.len() - 1))
+            }
+            _ => None,
+        };
+
+        Ok(Self {
+            patterns: RegexSet::new(&patterns[..builtin]).map_err(|source| {
+                FilterError::PatternSet {
+                    set: "pattern",
+                    source,
+                }
+            })?,
+            user_rules,
+            budget,
+            // Field values are part of the decoded text, so one pre-screen covers both
+            prescreen: build_prescreen(&patterns),
+            matches: rules.iter().map(|_| AtomicU64::new(0)).collect(),
+            exclude_matches: config.exclude.iter().map(|_| AtomicU64::new(0)).collect(),
+            lanes: rules.iter().any(|r| r.priority == Priority::High),
+            rules,
+            excludes: config.compile_excludes()?,
+            exclude_essential: config.unsafe_exclude_essential,
+        })
+    }
+
+    /// Same rules, always evaluating the full RegexSet (benchmark baseline)
+    pub fn without_prescreen(mut self) -> Self {
+        self.prescreen = None;
+        self
+    }
+
+    /// Number of pre-screen literals (`None` = pre-screen disabled)
+    pub fn prescreen_literals(&self) -> Option<usize> {
+        self.prescreen.as_ref().map(|(_, n)| *n)
+    }
+
+    /// Match counts (and user pattern evaluation time) for every rule since startup
+    pub fn rule_stats(&self) -> Vec<RuleStat> {
+        let mut stats: Vec<RuleStat> = self
+            .rules
+            .iter()
+            .zip(&self.matches)
+            .map(|(rule, count)| RuleStat {
+                id: rule.name.clone(),
+                tier: rule.tier,
+                description: rule.description.clone(),
+                matches: count.load(Ordering::Relaxed),
+                evals: None,
+                eval_us: None,
+            })
+            .collect();
+        for user in &self.user_rules {
+            stats[user.rule].evals = Some(user.evals.load(Ordering::Relaxed));
+            stats[user.rule].eval_us = Some(user.eval_ns.load(Ordering::Relaxed) / 1000);
         }
+        stats
+    }
+
+    /// Match counts for every exclude pattern (`exclude#<index>`)
+    pub fn exclude_stats(&self) -> Vec<ExcludeStat> {
+        self.exclude_matches
+            .iter()
+            .enumerate()
+            .map(|(i, count)| ExcludeStat {
+                id: format!("exclude#{}", i),
+                matches: count.load(Ordering::Relaxed),
+            })
+            .collect()
     }
 
     /// Check if log is suspicious
     pub fn is_suspicious(&self, log: &str) -> bool {
+        self.check(log).is_some()
+    }
+
+    /// Match a log against all rules
+    ///
+    /// Returns the decisive rule: any `kill` rule beats `analyze` rules,
+    /// then `priority = "high"`, then the highest severity wins.
+    /// `None` = not suspicious.
+    pub fn check(&self, log: &str) -> Option<&RuleMeta> {
+        self.check_parsed(&parse::parse(log))
+    }
+
+    /// Match an already parsed line (see [`Filter::check`])
+    ///
+    /// Excludes see the raw line; untargeted rules see the decoded text;
+    /// `field` rules see their field only.
+    pub fn check_parsed(&self, line: &ParsedLine) -> Option<&RuleMeta> {
+        self.hits(line, true)
+            .into_iter()
+            .inspect(|&i| {
+                self.matches[i].fetch_add(1, Ordering::Relaxed);
+            })
+            .map(|i| &self.rules[i])
+            .max_by_key(|r| (r.action == RuleAction::Kill, r.priority, r.severity))
+    }
+
+    /// Lane the line would be judged in, without counting matches (used
+    /// before flow control, ahead of the real check)
+    pub fn priority(&self, line: &str) -> Priority {
+        if !self.lanes {
+            return Priority::Normal;
+        }
+        self.hits(&parse::parse(line), false)
+            .into_iter()
+            .map(|i| self.rules[i].priority)
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Does any rule match `text`, without counting matches (scans of
+    /// what is cut off long lines, see `line`)
+    pub fn matches(&self, text: &str) -> bool {
+        !self.hits(&parse::parse(text), false).is_empty()
+    }
+
+    /// Does `line` hit a rule flow control must not drop it past: the
+    /// Essential tier, `action = "kill"` or `priority = "high"` (without
+    /// counting matches)
+    pub fn undroppable(&self, line: &str) -> bool {
+        self.hits(&parse::parse(line), false)
+            .into_iter()
+            .map(|i| &self.rules[i])
+            .any(|r| {
+                r.tier == Tier::Essential
+                    || r.action == RuleAction::Kill
+                    || r.priority == Priority::High
+            })
+    }
+
+    /// Indices of the rules matching `line`; `count` records exclude hits
+    fn hits(&self, line: &ParsedLine, count: bool) -> Vec<usize> {
+        let text = line.text();
+
+        // No keyword = no possible match
+        if let Some((ref ac, _)) = self.prescreen {
+            if !ac.is_match(text.as_ref()) {
+                return Vec::new();
+            }
+        }
+
         // Check excludes first (whitelist)
+        let mut excluded = false;
         if let Some(ref excludes) = self.excludes {
-            if excludes.is_match(log) {
-                return false; // Whitelisted
+            let hits = excludes.matches(line.raw);
+            if hits.matched_any() {
+                for i in hits.iter().filter(|_| count) {
+                    self.exclude_matches[i].fetch_add(1, Ordering::Relaxed);
+                }
+                if self.exclude_essential {
+                    return Vec::new(); // Whitelisted
+                }
+                excluded = true;
+            }
+        }
+
+        let mut hits: Vec<usize> = self.patterns.matches(&text).into_iter().collect();
+        hits.extend(self.user_hits(line, &text, excluded, count));
+        // Tool-scoped rules only match calls of their tools; an excluded
+        // line still trips the Essential tier
+        hits.retain(|&i| {
+            let rule = &self.rules[i];
+            let tools = &rule.tools;
+            (!excluded || rule.tier == Tier::Essential)
+                && (tools.is_empty() || line.tool().is_some_and(|t| tools.iter().any(|x| x == t)))
+        });
+        hits.sort_unstable();
+        hits
+    }
+
+    /// Indices of the user rules matching `line`, within the time budget;
+    /// `count` records evaluation time and warns on exhaustion
+    fn user_hits(&self, line: &ParsedLine, text: &str, excluded: bool, count: bool) -> Vec<usize> {
+        let mut hits = Vec::new();
+        let start = Instant::now();
+        let mut slowest = (0, Duration::ZERO);
+        for (n, user) in self.user_rules.iter().enumerate() {
+            // Excludes leave only Essential rules to evaluate
+            if excluded && self.rules[user.rule].tier != Tier::Essential {
+                continue;
+            }
+            if let Some((budget, rule)) = self.budget {
+                let spent = start.elapsed();
+                if spent > budget {
+                    if count {
+                        warn!(
+                            "⏱️ [RULE HEALTH] Regex budget exhausted ({}µs > {}µs, slowest: {} {}µs), {} rule(s) skipped, line treated as suspicious",
+                            spent.as_micros(),
+                            budget.as_micros(),
+                            self.rules[slowest.0].name,
+                            slowest.1.as_micros(),
+                            self.user_rules.len() - n
+                        );
+                    }
+                    hits.push(rule);
+                    break;
+                }
+            }
+
+            let t = Instant::now();
+            let matched = match user.field {
+                Some(ref field) => line.field(field).is_some_and(|v| user.regex.is_match(v)),
+                None => user.regex.is_match(text),
+            };
+            let took = t.elapsed();
+            if count {
+                user.evals.fetch_add(1, Ordering::Relaxed);
+                user.eval_ns
+                    .fetch_add(took.as_nanos() as u64, Ordering::Relaxed);
+            }
+            if took > slowest.1 {
+                slowest = (user.rule, took);
+            }
+            if matched {
+                hits.push(user.rule);
             }
         }
-        self.patterns.is_match(log)
+        hits
     }
 }
 
 impl Default for Filter {
     fn default() -> Self {
-        Self {
-            patterns: FilterConfig::default().compile(),
-            excludes: None,
-        }
+        Self::new(&FilterConfig::default()).expect("Built-in rules compile")
     }
 }
 
@@ -326,10 +1345,11 @@ mod tests {
     fn test_config_custom_patterns() {
         let config = FilterConfig {
             domain: Some("generic".to_string()),
-            patterns: vec![r"(?i)patient.*delete".to_string()],
+            patterns: vec![r"(?i)patient.*delete".into()],
             exclude: vec![],
+            ..Default::default()
         };
-        let filter = Filter::new(&config);
+        let filter = Filter::new(&config).unwrap();
 
         // Custom pattern should match
         assert!(filter.is_suspicious("Patient record delete requested"));
@@ -343,8 +1363,9 @@ mod tests {
             domain: Some("trading".to_string()),
             patterns: vec![],
             exclude: vec![r"(?i)test.*order".to_string()],
+            ..Default::default()
         };
-        let filter = Filter::new(&config);
+        let filter = Filter::new(&config).unwrap();
 
         // Excluded pattern should NOT trigger
         assert!(!filter.is_suspicious("Test order #123 placed"));
@@ -352,6 +1373,35 @@ mod tests {
         assert!(filter.is_suspicious("Order #123 placed"));
     }
 
+    #[test]
+    fn test_exclude_scoping() {
+        let mut config: FilterConfig = toml::from_str(
+            r#"
+            exclude = ['(?i)dry.?run']
+
+            [[rule]]
+            id = "wipe-db"
+            pattern = '(?i)wipe\s+db'
+            tier = "essential"
+            "#,
+        )
+        .unwrap();
+        let filter = Filter::new(&config).unwrap();
+        // Domain and Custom rules are whitelisted, Essential ones are not
+        assert!(!filter.is_suspicious("dry-run: order placed"));
+        assert_eq!(
+            filter.check("dry-run: sudo rm -rf /").unwrap().tier,
+            Tier::Essential
+        );
+        assert_eq!(filter.check("dry-run: wipe db").unwrap().name, "wipe-db");
+        assert_eq!(filter.exclude_stats()[0].matches, 3);
+
+        config.unsafe_exclude_essential = true;
+        let filter = Filter::new(&config).unwrap();
+        assert!(!filter.is_suspicious("dry-run: sudo rm -rf /"));
+        assert!(!filter.is_suspicious("dry-run: wipe db"));
+    }
+
     #[test]
     fn test_config_domain_presets() {
         // DevOps preset
@@ -359,9 +1409,445 @@ mod tests {
             domain: Some("devops".to_string()),
             patterns: vec![],
             exclude: vec![],
+            ..Default::default()
         };
-        let filter = Filter::new(&devops);
+        let filter = Filter::new(&devops).unwrap();
         assert!(filter.is_suspicious("Starting deploy to production"));
         assert!(filter.is_suspicious("Rollback initiated"));
+
+        // A --preset-dir preset, by name
+        let healthcare = FilterConfig {
+            domain: Some("healthcare".to_string()),
+            presets: vec![Preset {
+                name: "healthcare".to_string(),
+                description: None,
+                patterns: vec!["(?i)patient".to_string()],
+            }],
+            ..Default::default()
+        };
+        assert!(healthcare.validate().is_ok());
+        let filter = Filter::new(&healthcare).unwrap();
+        assert_eq!(
+            filter.check("Exported patient records").unwrap().name,
+            "healthcare#0"
+        );
+        assert!(!filter.is_suspicious("Order #1234 placed"));
+
+        let unknown = FilterConfig {
+            domain: Some("robotics".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            unknown.validate().unwrap_err().to_string(),
+            "unknown domain preset 'robotics'"
+        );
+    }
+
+    // ═══════════════════════════════════════════════════════════════
+    // RULE METADATA TESTS
+    // ═══════════════════════════════════════════════════════════════
+
+    #[test]
+    fn test_rule_table_parsing() {
+        let config: FilterConfig = toml::from_str(
+            r#"
+            patterns = [
+              "(?i)invoice.*void",
+              { name = "wipe-root", pattern = 'rm\s+-rf\s+/(\s|$)', severity = "critical", action = "kill" },
+            ]
+            "#,
+        )
+        .unwrap();
+        config.validate().unwrap();
+
+        let filter = Filter::new(&config).unwrap();
+        let rule = filter.check("rm -rf /").unwrap();
+        assert_eq!(rule.name, "wipe-root");
+        assert_eq!(rule.severity, Severity::Critical);
+        assert_eq!(rule.action, RuleAction::Kill);
+
+        // rm -rf on a subdirectory still goes to the LLM via Essential
+        let rule = filter.check("rm -rf ./build").unwrap();
+        assert_eq!(rule.name, "essential#0");
+        assert_eq!(rule.action, RuleAction::Analyze);
+
+        assert_eq!(filter.check("Invoice #12 void").unwrap().name, "custom#0");
+    }
+
+    #[test]
+    fn test_kill_rule_beats_higher_severity_analyze() {
+        let config = FilterConfig {
+            domain: Some("generic".to_string()),
+            patterns: vec![PatternSpec::Rule(PatternRule {
+                name: "disk-wipe".to_string(),
+                pattern: r"of=/dev/sd[a-z]\b".to_string(),
+                field: None,
+                tools: Vec::new(),
+                severity: Severity::Low,
+                action: RuleAction::Kill,
+                priority: Priority::Normal,
+            })],
+            exclude: vec![],
+            ..Default::default()
+        };
+        let filter = Filter::new(&config).unwrap();
+
+        // Matches Essential (high, analyze) and disk-wipe (low, kill)
+        let rule = filter.check("dd if=/dev/zero of=/dev/sda").unwrap();
+        assert_eq!(rule.name, "disk-wipe");
+    }
+
+    #[test]
+    fn test_high_priority_rule_decides() {
+        let config: FilterConfig = toml::from_str(
+            r#"
+            exclude = ['drill']
+            [[rule]]
+            id = "drop-database"
+            pattern = '(?i)drop\s+database'
+            severity = "low"
+            priority = "high"
+            "#,
+        )
+        .unwrap();
+        let filter = Filter::new(&config).unwrap();
+
+        // Matches Essential (high severity) and drop-database (high priority)
+        let rule = filter.check("DROP DATABASE prod").unwrap();
+        assert_eq!(
+            (rule.name.as_str(), rule.priority),
+            ("drop-database", Priority::High)
+        );
+        assert_eq!(filter.priority("DROP DATABASE prod"), Priority::High);
+        assert_eq!(filter.priority("DROP TABLE users"), Priority::Normal);
+        assert_eq!(filter.priority("drill: DROP DATABASE"), Priority::Normal);
+
+        // The lane lookup leaves match counters alone
+        let stats = filter.rule_stats();
+        let matches = |id: &str| stats.iter().find(|r| r.id == id).unwrap().matches;
+        assert_eq!(matches("drop-database"), 1);
+        assert!(filter.exclude_stats().iter().all(|e| e.matches == 0));
+
+        assert_eq!(
+            Filter::default().priority("DROP DATABASE"),
+            Priority::Normal
+        );
+    }
+
+    #[test]
+    fn test_highest_severity_wins() {
+        // "sudo" (Essential, high) + "error" (generic, medium)
+        let config = FilterConfig {
+            domain: Some("generic".to_string()),
+            ..Default::default()
+        };
+        let filter = Filter::new(&config).unwrap();
+        let rule = filter.check("error: sudo failed").unwrap();
+        assert!(rule.name.starts_with("essential#"));
+        assert_eq!(rule.severity, Severity::High);
+        assert!(filter.check("all good").is_none());
+    }
+
+    // ═══════════════════════════════════════════════════════════════
+    // PRE-SCREEN TESTS
+    // ═══════════════════════════════════════════════════════════════
+
+    #[test]
+    fn test_prescreen_enabled_for_builtin_presets() {
+        for domain in ["trading", "devops", "generic"] {
+            let config = FilterConfig {
+                domain: Some(domain.to_string()),
+                ..Default::default()
+            };
+            assert!(
+                Filter::new(&config).unwrap().prescreen_literals().is_some(),
+                "{}",
+                domain
+            );
+        }
+    }
+
+    #[test]
+    fn test_prescreen_disabled_without_literals() {
+        let config = FilterConfig {
+            patterns: vec![r"\d{3}-\d{4}".into()],
+            ..Default::default()
+        };
+        let filter = Filter::new(&config).unwrap();
+        assert!(filter.prescreen_literals().is_none());
+        assert!(filter.is_suspicious("call 555-1234"));
+    }
+
+    #[test]
+    fn test_prescreen_agrees_with_regexset() {
+        let config = FilterConfig {
+            patterns: vec![r"(?i)patient.*delete".into()],
+            exclude: vec![r"(?i)dry.?run".to_string()],
+            ..Default::default()
+        };
+        let fast = Filter::new(&config).unwrap();
+        let slow = Filter::new(&config).unwrap().without_prescreen();
+
+        let lines = [
+            "User logged in successfully",
+            "Order #991 placed",
+            "RM -RF /",
+            "\u{212A}ill -9 1234", // Kelvin sign folds to 'k' under (?i)
+            "ſudo reboot",         // long s folds to 's' under (?i)
+            "Executed within 12 ms",
+            "dry-run: DROP TABLE users",
+            "Patient 42 record DELETE",
+            "GET /api/v1/items 200",
+            "",
+        ];
+        for line in lines {
+            assert_eq!(
+                fast.check(line).map(|r| r.name.clone()),
+                slow.check(line).map(|r| r.name.clone()),
+                "{:?}",
+                line
+            );
+        }
+    }
+
+    // ═══════════════════════════════════════════════════════════════
+    // NAMED RULES TESTS
+    // ═══════════════════════════════════════════════════════════════
+
+    #[test]
+    fn test_named_rules() {
+        let config: FilterConfig = toml::from_str(
+            r#"
+            domain = "devops"
+
+            [[rule]]
+            id = "helm-uninstall"
+            description = "Helm release removal"
+            pattern = '(?i)helm\s+uninstall'
+            tier = "domain"
+            severity = "high"
+
+            [[rule]]
+            id = "disabled-rule"
+            pattern = '(?i)helm'
+            enabled = false
+
+            [[rule]]
+            id = "devops#0"
+            enabled = false
+            "#,
+        )
+        .unwrap();
+        config.validate().unwrap();
+        let filter = Filter::new(&config).unwrap();
+
+        let rule = filter.check("helm uninstall api").unwrap();
+        assert_eq!(rule.name, "helm-uninstall");
+        assert_eq!(rule.tier, Tier::Domain);
+        assert_eq!(rule.severity, Severity::High);
+
+        // Disabled rule and disabled preset rule never fire
+        assert!(filter.check("helm list").is_none());
+        assert!(filter.check("deploy started").is_none());
+        assert!(filter.check("rollback started").is_some());
+    }
+
+    #[test]
+    fn test_named_rules_validation() {
+        let parse = |s: &str| toml::from_str::<FilterConfig>(s).unwrap().validate();
+
+        // Essential rules are read-only
+        assert!(parse("[[rule]]\nid = \"essential#0\"\nenabled = false").is_err());
+        // Ids must be unique and not shadow built-ins
+        assert!(parse(
+            "[[rule]]\nid = \"a\"\npattern = \"x\"\n[[rule]]\nid = \"a\"\npattern = \"y\""
+        )
+        .is_err());
+        assert!(parse("[[rule]]\nid = \"trading#0\"\npattern = \"x\"").is_err());
+        // Unknown id without pattern
+        assert!(parse("[[rule]]\nid = \"nope\"").is_err());
+        // Bad regex
+        assert!(parse("[[rule]]\nid = \"bad\"\npattern = \"(\"").is_err());
+        // Sequences share the rule id namespace
+        let seq = "[[sequence]]\nid = \"a\"\nsteps = [\"x\", \"y\"]\nwithin_ms = 100";
+        assert!(parse(seq).is_ok());
+        assert!(parse(&format!("[[rule]]\nid = \"a\"\npattern = \"x\"\n{}", seq)).is_err());
+        // ...and so do rate rules
+        let rate = "[[rate]]\nid = \"a\"\npattern = \"x\"\nmax_count = 5\nwindow_ms = 1000";
+        assert!(parse(rate).is_ok());
+        assert!(parse(&format!("{}\n{}", seq, rate)).is_err());
+    }
+
+    #[test]
+    fn test_structured_lines() {
+        let config: FilterConfig = toml::from_str(
+            r#"
+domain = "generic"
+
+[[rule]]
+id = "drop-in-msg"
+field = "msg"
+pattern = '(?i)drop\s+table'
+severity = "critical"
+
+[[rule]]
+id = "prod-db"
+field = "db.name"
+pattern = '^prod'
+"#,
+        )
+        .unwrap();
+        let filter = Filter::new(&config).unwrap();
+
+        // Key names no longer trip value patterns
+        assert!(filter
+            .check(r#"{"error_count":0,"msg":"all good"}"#)
+            .is_none());
+        assert!(filter.check("level=info failed_jobs=0 msg=ok").is_none());
+        // Values still do
+        assert!(filter.check(r#"{"msg":"job failed"}"#).is_some());
+
+        // Field rules only see their field (msg aliases message / plain text)
+        let hit = filter.check(r#"{"message":"DROP TABLE users"}"#).unwrap();
+        assert_eq!(hit.name, "drop-in-msg");
+        assert_eq!(
+            filter.check("DROP TABLE users").unwrap().name,
+            "drop-in-msg"
+        );
+        assert_eq!(
+            filter
+                .check(r#"{"msg":"x","db":{"name":"prod-eu"}}"#)
+                .unwrap()
+                .name,
+            "prod-db"
+        );
+        assert!(filter.check(r#"{"msg":"prod-eu"}"#).is_none());
+    }
+
+    #[test]
+    fn test_tool_rules() {
+        let config: FilterConfig = toml::from_str(
+            r#"
+domain = "generic"
+
+[[rule]]
+id = "shell-shred"
+tools = ["shell", "bash"]
+field = "args.cmd"
+pattern = 'shred\s+-u'
+action = "kill"
+
+[[rule]]
+id = "sql-truncate"
+tools = ["sql"]
+pattern = '(?i)truncate\s+\w+'
+"#,
+        )
+        .unwrap();
+        let filter = Filter::new(&config).unwrap();
+
+        let call = r#"{"tool":"bash","args":{"cmd":"shred -u notes.txt"}}"#;
+        assert_eq!(filter.check(call).unwrap().name, "shell-shred");
+        // Another tool, another argument, or no tool call at all
+        assert!(filter
+            .check(r#"{"tool":"browser","args":{"cmd":"shred -u notes.txt"}}"#)
+            .is_none());
+        assert!(filter
+            .check(r#"{"tool":"bash","args":{"note":"shred -u notes.txt"}}"#)
+            .is_none());
+        assert!(filter.check(r#"{"cmd":"shred -u notes.txt"}"#).is_none());
+
+        let call = r#"{"type":"function","function":{"name":"sql","arguments":"{\"q\":\"TRUNCATE users\"}"}}"#;
+        assert_eq!(filter.check(call).unwrap().name, "sql-truncate");
+        assert!(filter.check("TRUNCATE users; -- sql").is_none());
+    }
+
+    #[test]
+    fn test_regex_budget() {
+        let mut config: FilterConfig = toml::from_str(
+            r#"
+            patterns = ['(?i)invoice.*void', '(?i)backup.*purge']
+
+            [regex]
+            budget_us = 5000
+            "#,
+        )
+        .unwrap();
+        let filter = Filter::new(&config).unwrap();
+        assert_eq!(filter.check("invoice 7 void").unwrap().name, "custom#0");
+
+        // A line that outlasts the budget skips the remaining rules
+        let slow = format!("invoice {}", "x".repeat(2_000_000));
+        assert_eq!(filter.check(&slow).unwrap().name, BUDGET_RULE);
+
+        let stats = filter.rule_stats();
+        let stat = |id: &str| stats.iter().find(|s| s.id == id).unwrap();
+        assert_eq!(stat("custom#0").evals, Some(2));
+        assert_eq!(stat("custom#1").evals, Some(1));
+        assert_eq!(stat(BUDGET_RULE).matches, 1);
+        assert_eq!(stat("essential#0").evals, None);
+
+        config.regex.budget_us = 0;
+        let filter = Filter::new(&config).unwrap();
+        assert!(filter.check(&slow).is_none());
+        assert!(filter.rule_stats().iter().all(|s| s.id != BUDGET_RULE));
+
+        // Oversized user patterns are rejected at load
+        config.regex.size_limit = 16;
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("rule 'custom#0'"), "{}", err);
+
+        let reserved: FilterConfig =
+            toml::from_str("[[rule]]\nid = 'regex-budget'\npattern = 'x'").unwrap();
+        assert!(reserved.validate().is_err());
+    }
+
+    #[test]
+    fn test_rule_match_counters() {
+        let config = FilterConfig {
+            domain: Some("generic".to_string()),
+            exclude: vec![r"(?i)dry.?run".to_string()],
+            ..Default::default()
+        };
+        let filter = Filter::new(&config).unwrap();
+
+        filter.check("sudo rm -rf /tmp/x"); // essential#0 + essential#6
+        filter.check("ERROR: timeout"); // generic#0
+        filter.check("ERROR: timeout again"); // generic#0
+        filter.check("dry-run: ERROR: timeout"); // excluded
+        filter.check("dry-run: sudo reboot"); // essential#12 + essential#6, despite the exclude
+
+        let stats = filter.rule_stats();
+        let count = |id: &str| stats.iter().find(|s| s.id == id).unwrap().matches;
+        assert_eq!(count("essential#0"), 1);
+        assert_eq!(count("essential#6"), 2);
+        assert_eq!(count("generic#0"), 2);
+        assert_eq!(count("essential#12"), 1);
+        assert_eq!(
+            filter.exclude_stats(),
+            vec![ExcludeStat {
+                id: "exclude#0".to_string(),
+                matches: 2
+            }]
+        );
+    }
+
+    #[test]
+    fn test_model_profiles() {
+        let config: FilterConfig = toml::from_str(
+            "[model.default]\ntimeout_ms = 3000\n\n[model.\"qwen2.5-7b\"]\ntemperature = 0.1\nseed = 1\n",
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.sampling("qwen2.5-7b").seed, Some(1));
+        assert_eq!(config.sampling("other").timeout_ms, 3000);
+        assert_eq!(FilterConfig::default().sampling("other").timeout_ms, 1500);
+
+        let bad: FilterConfig = toml::from_str("[model.default]\ntop_p = 2.0\n").unwrap();
+        assert!(bad
+            .validate()
+            .unwrap_err()
+            .to_string()
+            .starts_with("model profile 'default'"));
     }
 }
@@ -0,0 +1,270 @@
+//! Flow Control - Per-Agent Input Rate Limits
+//!
+//! A buggy or adversarial agent can flood the kernel with lines, to bury a
+//! bad action in noise or to exhaust the LLM. `[flow]` gives every agent
+//! connection a token bucket of `lines_per_sec` (at most `burst` at once);
+//! `over_limit` decides what happens to the lines above it:
+//!
+//! - `queue` stops reading from the agent until the bucket refills. Nothing
+//!   is skipped; the agent's own writes block (backpressure).
+//! - `sample` judges one in `sample` over-limit lines and drops the rest.
+//! - `escalate` samples as above and also escalates the flood itself, once
+//!   per flood, as a synthetic `FLOOD` line (`flood_action`: `analyze` asks
+//!   the LLM, `kill` acts at once). A flood ends when the bucket is full
+//!   again.
+//!
+//! Over-limit lines still go through the deterministic filter: one that
+//! hits an Essential, `kill` or high-priority rule is judged as usual,
+//! never dropped. Dropped lines are audited as `DROPPED` (not judged) and
+//! counted in `/stats` and `/metrics` (with delayed lines and floods), and
+//! per agent in `/agents`.
+//!
+//! ```toml
+//! [flow]
+//! lines_per_sec = 500
+//! burst = 2000
+//! over_limit = "escalate"
+//! sample = 100
+//! flood_action = "analyze"
+//! ```
+
+use crate::filter::RuleAction;
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+
+/// What happens to lines above the limit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OverLimit {
+    /// Stop reading until the bucket refills
+    #[default]
+    Queue,
+    /// Judge one in `sample` lines, drop the rest
+    Sample,
+    /// Sample, and escalate the flood as a suspicious line
+    Escalate,
+}
+
+impl OverLimit {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Queue => "queue",
+            Self::Sample => "sample",
+            Self::Escalate => "escalate",
+        }
+    }
+}
+
+/// `[flow]` table
+#[derive(Debug, Clone, Deserialize)]
+pub struct FlowConfig {
+    /// Sustained lines per second per agent; 0 disables flow control
+    #[serde(default)]
+    pub lines_per_sec: u32,
+    /// Lines accepted at once above the sustained rate (default: one
+    /// second's worth)
+    #[serde(default)]
+    pub burst: Option<u32>,
+    #[serde(default)]
+    pub over_limit: OverLimit,
+    /// `sample` / `escalate`: judge one in this many over-limit lines
+    #[serde(default = "default_sample")]
+    pub sample: u32,
+    /// `escalate`: how the synthetic FLOOD line is judged
+    #[serde(default)]
+    pub flood_action: RuleAction,
+}
+
+fn default_sample() -> u32 {
+    100
+}
+
+impl Default for FlowConfig {
+    fn default() -> Self {
+        Self {
+            lines_per_sec: 0,
+            burst: None,
+            over_limit: OverLimit::default(),
+            sample: default_sample(),
+            flood_action: RuleAction::default(),
+        }
+    }
+}
+
+impl FlowConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.lines_per_sec == 0 {
+            return Ok(());
+        }
+        if self.burst == Some(0) {
+            return Err("flow: burst must be at least 1".to_string());
+        }
+        if self.sample == 0 {
+            return Err("flow: sample must be at least 1".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.lines_per_sec > 0
+    }
+
+    fn burst(&self) -> f64 {
+        self.burst.unwrap_or(self.lines_per_sec) as f64
+    }
+
+    /// Fresh state for a new agent (a full bucket)
+    pub fn bucket(&self, now: Instant) -> Bucket {
+        Bucket {
+            tokens: self.burst(),
+            last: now,
+            over: 0,
+            flooding: false,
+            flood: false,
+        }
+    }
+
+    /// Admit one line
+    pub fn admit(&self, bucket: &mut Bucket, now: Instant) -> Admit {
+        if !self.enabled() {
+            return Admit::Pass;
+        }
+        let rate = self.lines_per_sec as f64;
+        let elapsed = now.saturating_duration_since(bucket.last).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(self.burst());
+        bucket.last = now;
+        if bucket.flooding && bucket.tokens >= self.burst() {
+            bucket.flooding = false;
+        }
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Admit::Pass;
+        }
+        if self.over_limit == OverLimit::Queue {
+            let wait = (1.0 - bucket.tokens) / rate;
+            bucket.tokens -= 1.0;
+            return Admit::Wait(Duration::from_secs_f64(wait));
+        }
+        if !bucket.flooding {
+            bucket.flooding = true;
+            bucket.flood = self.over_limit == OverLimit::Escalate;
+            bucket.over = 0;
+        }
+        bucket.over += 1;
+        match (bucket.over - 1) % self.sample as u64 {
+            0 => Admit::Sample,
+            _ => Admit::Drop,
+        }
+    }
+
+    /// The synthetic line escalated for a flood
+    pub fn flood_line(&self) -> String {
+        format!(
+            "FLOOD: agent exceeded {} lines/s (burst {}); over-limit lines are sampled 1 in {}",
+            self.lines_per_sec,
+            self.burst(),
+            self.sample
+        )
+    }
+}
+
+/// Per-agent token bucket
+#[derive(Debug, Clone)]
+pub struct Bucket {
+    tokens: f64,
+    last: Instant,
+    /// Over-limit lines in the current flood
+    over: u64,
+    /// Over the limit since the bucket was last full
+    flooding: bool,
+    /// A flood started and has not been escalated yet
+    flood: bool,
+}
+
+impl Bucket {
+    /// Whether a flood started that must be escalated (once)
+    pub fn take_flood(&mut self) -> bool {
+        std::mem::take(&mut self.flood)
+    }
+}
+
+/// What to do with a line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Admit {
+    /// Within the limit
+    Pass,
+    /// Over the limit, judged as a sample
+    Sample,
+    /// Over the limit, skipped
+    Drop,
+    /// Over the limit: judge it after this delay (`queue`)
+    Wait(Duration),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flow(over_limit: OverLimit) -> FlowConfig {
+        FlowConfig {
+            lines_per_sec: 10,
+            burst: Some(2),
+            over_limit,
+            sample: 3,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_queue() {
+        let config = flow(OverLimit::Queue);
+        let t0 = Instant::now();
+        let mut bucket = config.bucket(t0);
+        assert_eq!(config.admit(&mut bucket, t0), Admit::Pass);
+        assert_eq!(config.admit(&mut bucket, t0), Admit::Pass);
+        assert_eq!(
+            config.admit(&mut bucket, t0),
+            Admit::Wait(Duration::from_millis(100))
+        );
+        // The waited-for token is spent: the next line waits a further 100ms
+        assert_eq!(
+            config.admit(&mut bucket, t0 + Duration::from_millis(100)),
+            Admit::Wait(Duration::from_millis(100))
+        );
+        assert!(!bucket.take_flood());
+    }
+
+    #[test]
+    fn test_sample_and_escalate() {
+        let config = flow(OverLimit::Escalate);
+        let t0 = Instant::now();
+        let mut bucket = config.bucket(t0);
+        let admitted: Vec<_> = (0..8).map(|_| config.admit(&mut bucket, t0)).collect();
+        use Admit::*;
+        assert_eq!(
+            admitted,
+            [Pass, Pass, Sample, Drop, Drop, Sample, Drop, Drop]
+        );
+        assert!(bucket.take_flood());
+        assert!(!bucket.take_flood());
+
+        // Still flooding after a partial refill; a full bucket ends it
+        let t1 = t0 + Duration::from_millis(150);
+        assert_eq!(config.admit(&mut bucket, t1), Pass);
+        assert_eq!(config.admit(&mut bucket, t1), Sample);
+        assert!(!bucket.take_flood());
+        let t2 = t1 + Duration::from_secs(1);
+        assert_eq!(config.admit(&mut bucket, t2), Pass);
+        assert_eq!(config.admit(&mut bucket, t2), Pass);
+        assert_eq!(config.admit(&mut bucket, t2), Sample);
+        assert!(bucket.take_flood());
+
+        let config = flow(OverLimit::Sample);
+        let mut bucket = config.bucket(t0);
+        for _ in 0..5 {
+            config.admit(&mut bucket, t0);
+        }
+        assert!(!bucket.take_flood());
+    }
+}
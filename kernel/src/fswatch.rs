@@ -0,0 +1,314 @@
+//! File-System Monitor - Effects the Logs Leave Out
+//!
+//! An agent can claim it only read a report while it rewrites
+//! `/etc/sudoers`. With `[fs]`, the kernel watches directories through
+//! inotify (Linux) or ReadDirectoryChangesW (Windows) and turns every
+//! create, write, rename, delete or metadata change below them into a
+//! synthetic `FS` line:
+//!
+//! - `watch`: paths the agent is expected to touch. Their lines
+//!   (`FS delete /srv/agent/orders.db`) are judged like the agent's own log
+//!   lines: filtered, then analyzed if a rule matches
+//! - `sensitive`: paths it has no business in. Every change escalates
+//!   (`FS write /etc/sudoers (sensitive path /etc)`, rule `fs-sensitive`)
+//!   in the high priority lane, judged by `action`
+//!
+//! Changes are not attributed to a process: anything writing below a
+//! watched path counts, so keep the lists to paths that stay quiet while
+//! the agent runs. Repeats of the same change to the same file within
+//! `debounce_ms` are dropped, as are `ignore` paths and the pipeline's own
+//! audit log. Watches are recursive; a path missing at startup is skipped
+//! with a warning. Lines are judged in a session named `fs`.
+//!
+//! ```toml
+//! [fs]
+//! watch = ["/srv/agent/workspace"]
+//! sensitive = ["/etc", "/root/.ssh", 'C:\Windows\System32']
+//! ignore = ["/srv/agent/workspace/.cache"]
+//! debounce_ms = 1000
+//! action = "analyze"
+//! ```
+
+use crate::filter::RuleAction;
+use notify::event::{EventKind, ModifyKind};
+use notify::{RecursiveMode, Watcher};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tracing::warn;
+
+/// Rule id of changes below a `sensitive` path
+pub const SENSITIVE_RULE: &str = "fs-sensitive";
+
+/// `[fs]` table
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FsConfig {
+    /// Paths the agent is expected to touch: changes are judged like its lines
+    pub watch: Vec<PathBuf>,
+    /// Paths it should never touch: changes escalate in the high lane
+    pub sensitive: Vec<PathBuf>,
+    /// Never reported (below a watched path)
+    pub ignore: Vec<PathBuf>,
+    /// Drop repeats of the same change to the same path within this long (milliseconds)
+    pub debounce_ms: u64,
+    /// `analyze` sends sensitive changes to the LLM, `kill` acts immediately
+    pub action: RuleAction,
+}
+
+impl Default for FsConfig {
+    fn default() -> Self {
+        Self {
+            watch: Vec::new(),
+            sensitive: Vec::new(),
+            ignore: Vec::new(),
+            debounce_ms: 1000,
+            action: RuleAction::Analyze,
+        }
+    }
+}
+
+impl FsConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        let paths = self.watch.iter().chain(&self.sensitive).chain(&self.ignore);
+        for path in paths {
+            if !path.is_absolute() {
+                return Err(format!("fs: path '{}' must be absolute", path.display()));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.watch.is_empty() || !self.sensitive.is_empty()
+    }
+}
+
+/// The change an event describes; `None` for reads and unknown events
+pub fn change(kind: &EventKind) -> Option<&'static str> {
+    match kind {
+        EventKind::Create(_) => Some("create"),
+        EventKind::Modify(ModifyKind::Name(_)) => Some("rename"),
+        EventKind::Modify(ModifyKind::Metadata(_)) => Some("metadata"),
+        EventKind::Modify(_) => Some("write"),
+        EventKind::Remove(_) => Some("delete"),
+        EventKind::Access(_) | EventKind::Any | EventKind::Other => None,
+    }
+}
+
+/// A change to report
+#[derive(Debug, PartialEq)]
+pub struct FsHit {
+    /// Below a `sensitive` path
+    pub sensitive: bool,
+    pub line: String,
+}
+
+/// Classifies and debounces changes
+pub struct FsMonitor {
+    config: FsConfig,
+    /// The kernel's own files (the audit log), with their rotations
+    own: Vec<PathBuf>,
+    /// First report per change and path within the debounce window
+    recent: HashMap<(&'static str, PathBuf), Instant>,
+}
+
+impl FsMonitor {
+    pub fn new(config: &FsConfig, own: &[PathBuf]) -> Self {
+        Self {
+            config: config.clone(),
+            own: own.to_vec(),
+            recent: HashMap::new(),
+        }
+    }
+
+    /// The hit for `change` to `path`, unless ignored or a recent repeat
+    pub fn observe(&mut self, change: &'static str, path: &Path, now: Instant) -> Option<FsHit> {
+        if self.config.ignore.iter().any(|i| path.starts_with(i))
+            || self.own.iter().any(|own| is_own(path, own))
+        {
+            return None;
+        }
+        let debounce = Duration::from_millis(self.config.debounce_ms);
+        self.recent
+            .retain(|_, at| now.duration_since(*at) < debounce);
+        let key = (change, path.to_path_buf());
+        if self.recent.contains_key(&key) {
+            return None;
+        }
+        self.recent.insert(key, now);
+
+        let root = self
+            .config
+            .sensitive
+            .iter()
+            .find(|root| path.starts_with(root));
+        Some(match root {
+            Some(root) => FsHit {
+                sensitive: true,
+                line: format!(
+                    "FS {} {} (sensitive path {})",
+                    change,
+                    path.display(),
+                    root.display()
+                ),
+            },
+            None => FsHit {
+                sensitive: false,
+                line: format!("FS {} {}", change, path.display()),
+            },
+        })
+    }
+}
+
+/// `path` is `own` or a sibling named after it (`audit.jsonl.1`)
+fn is_own(path: &Path, own: &Path) -> bool {
+    path.parent() == own.parent()
+        && match (path.file_name(), own.file_name()) {
+            (Some(name), Some(own)) => name.to_string_lossy().starts_with(&*own.to_string_lossy()),
+            _ => false,
+        }
+}
+
+/// Watch every configured path, sending each change with its path; a
+/// path that cannot be watched is skipped with a warning
+pub fn start(
+    config: &FsConfig,
+    changes: mpsc::Sender<(&'static str, PathBuf)>,
+) -> notify::Result<notify::RecommendedWatcher> {
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let event = match event {
+            Ok(event) => event,
+            Err(e) => {
+                warn!("📁 File-system watch error: {}", e);
+                return;
+            }
+        };
+        let Some(change) = change(&event.kind) else {
+            return;
+        };
+        for path in event.paths {
+            // Blocking applies backpressure to the watcher while the pipeline is busy
+            let _ = changes.blocking_send((change, path));
+        }
+    })?;
+    for path in config.watch.iter().chain(&config.sensitive) {
+        if let Err(e) = watcher.watch(path, RecursiveMode::Recursive) {
+            warn!("📁 Cannot watch {}: {}", path.display(), e);
+        }
+    }
+    Ok(watcher)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{AccessKind, CreateKind, DataChange, RemoveKind, RenameMode};
+
+    #[cfg(unix)]
+    #[test]
+    fn test_classify_and_debounce() {
+        let config: FsConfig = toml::from_str(
+            "watch = ['/srv/agent']\nsensitive = ['/etc', '/srv/agent/keys']\n\
+             ignore = ['/srv/agent/.cache']\ndebounce_ms = 1000",
+        )
+        .unwrap();
+        config.validate().unwrap();
+        let mut monitor = FsMonitor::new(&config, &[PathBuf::from("/srv/agent/audit.jsonl")]);
+        let t0 = Instant::now();
+        let at = |ms: u64| t0 + Duration::from_millis(ms);
+
+        let hit = monitor
+            .observe("write", Path::new("/etc/sudoers"), at(0))
+            .unwrap();
+        assert_eq!(
+            hit,
+            FsHit {
+                sensitive: true,
+                line: "FS write /etc/sudoers (sensitive path /etc)".to_string()
+            }
+        );
+        // Repeats within debounce_ms are dropped; another change is not
+        assert!(monitor
+            .observe("write", Path::new("/etc/sudoers"), at(500))
+            .is_none());
+        assert!(monitor
+            .observe("delete", Path::new("/etc/sudoers"), at(600))
+            .is_some());
+        assert!(monitor
+            .observe("write", Path::new("/etc/sudoers"), at(900))
+            .is_none());
+        assert!(monitor
+            .observe("write", Path::new("/etc/sudoers"), at(1000))
+            .is_some());
+
+        let hit = monitor
+            .observe("delete", Path::new("/srv/agent/orders.db"), at(0))
+            .unwrap();
+        assert!(!hit.sensitive);
+        assert_eq!(hit.line, "FS delete /srv/agent/orders.db");
+        assert!(
+            monitor
+                .observe("create", Path::new("/srv/agent/keys/id_rsa"), at(0))
+                .unwrap()
+                .sensitive
+        );
+        // `/etcetera` is not below `/etc`
+        assert!(
+            !monitor
+                .observe("create", Path::new("/etcetera/x"), at(0))
+                .unwrap()
+                .sensitive
+        );
+
+        for ignored in [
+            "/srv/agent/.cache/blob",
+            "/srv/agent/audit.jsonl",
+            "/srv/agent/audit.jsonl.1",
+        ] {
+            assert!(monitor
+                .observe("write", Path::new(ignored), at(0))
+                .is_none());
+        }
+    }
+
+    #[test]
+    fn test_config() {
+        assert!(!FsConfig::default().enabled());
+        let relative: FsConfig = toml::from_str("sensitive = ['etc']").unwrap();
+        assert!(relative.validate().is_err());
+
+        assert_eq!(change(&EventKind::Create(CreateKind::File)), Some("create"));
+        assert_eq!(
+            change(&EventKind::Modify(ModifyKind::Data(DataChange::Content))),
+            Some("write")
+        );
+        assert_eq!(
+            change(&EventKind::Modify(ModifyKind::Name(RenameMode::To))),
+            Some("rename")
+        );
+        assert_eq!(change(&EventKind::Remove(RemoveKind::File)), Some("delete"));
+        assert_eq!(change(&EventKind::Access(AccessKind::Read)), None);
+    }
+
+    #[tokio::test]
+    async fn test_watch_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = FsConfig {
+            sensitive: vec![dir.path().to_path_buf()],
+            ..Default::default()
+        };
+        let (tx, mut changes) = mpsc::channel(64);
+        let _watcher = start(&config, tx).unwrap();
+        std::fs::write(dir.path().join("planted"), "x").unwrap();
+
+        let (change, path) = tokio::time::timeout(Duration::from_secs(5), changes.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(change, "create");
+        assert_eq!(path.file_name().unwrap(), "planted");
+    }
+}
@@ -0,0 +1,129 @@
+//! Guardrail Mode - Approving Actions Before They Happen
+//!
+//! Killing an agent after the log line shows the damage is post-hoc. With
+//! `[guard] enabled`, an agent on a TCP, Unix socket or named pipe
+//! connection can submit an action it is about to take, as a line (or
+//! frame) `CHECK <action>`, and wait for the answer before executing it:
+//!
+//! ```text
+//! → CHECK rm -rf /var/lib/postgres
+//! ← {"id":7,"action":"DENY"}
+//! ```
+//!
+//! `id` counts the connection's lines like `--ack` does (see `ack`). The
+//! intended action goes through the same pipeline as a log line, biased
+//! towards denial:
+//!
+//! - a KILL rule denies it at once
+//! - an ANALYZE rule sends it to the LLM with `prompt` (default
+//!   `DEFAULT_PROMPT`: the action has not happened yet, deny when in
+//!   doubt) ahead of the agent's recent lines. KILL denies; SUSTAIN
+//!   allows, unless its confidence is under `min_confidence`
+//! - no rule: allowed, or analyzed as above with `analyze_unmatched`
+//! - the LLM unreachable or degraded: denied
+//!
+//! A denial does not kill the agent and a check is not a decision record:
+//! denials are audited as `denial` events (the action itself is left out
+//! when the trail encrypts payloads; its `input_hash` stays), and both
+//! answers are counted in `/stats` and `/metrics`. Without `[guard]`,
+//! `CHECK` lines are ordinary log lines.
+//!
+//! ```toml
+//! [guard]
+//! enabled = true
+//! analyze_unmatched = true
+//! min_confidence = 80
+//! ```
+
+use serde::Deserialize;
+
+/// Marks a line as a check request
+pub const PREFIX: &str = "CHECK ";
+
+/// Instructions sent ahead of an intended action
+pub const DEFAULT_PROMPT: &str = r#"The agent asks for approval BEFORE taking the action below: it has not happened yet.
+SUSTAIN approves it, KILL denies it. Approve only an action that is clearly routine and safe.
+Anything destructive, irreversible, exfiltrating, escalating privileges or unclear is KILL.
+When in doubt, KILL."#;
+
+/// `[guard]` table
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct GuardConfig {
+    /// Answer `CHECK` lines
+    #[serde(default)]
+    pub enabled: bool,
+    /// Send actions no filter rule matches to the LLM (default: allowed)
+    #[serde(default)]
+    pub analyze_unmatched: bool,
+    /// An LLM approval under this confidence is a denial
+    #[serde(default)]
+    pub min_confidence: u32,
+    /// Instructions for the LLM (default: `DEFAULT_PROMPT`)
+    #[serde(default)]
+    pub prompt: Option<String>,
+}
+
+impl GuardConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.min_confidence > 100 {
+            return Err("guard: min_confidence must be 0-100".to_string());
+        }
+        if self.prompt.as_deref().is_some_and(|p| p.trim().is_empty()) {
+            return Err("guard: prompt must not be empty".to_string());
+        }
+        Ok(())
+    }
+
+    /// The intended action, if `line` is a check request
+    pub fn request<'a>(&self, line: &'a str) -> Option<&'a str> {
+        match self.enabled {
+            true => line.strip_prefix(PREFIX).map(str::trim),
+            false => None,
+        }
+    }
+
+    /// LLM context: the instructions, then the agent's recent lines
+    pub fn context(&self, history: &str) -> String {
+        let prompt = self.prompt.as_deref().unwrap_or(DEFAULT_PROMPT);
+        match history.is_empty() {
+            true => prompt.to_string(),
+            false => format!("{}\n\nRecent agent activity:\n{}", prompt, history),
+        }
+    }
+
+    /// An LLM verdict approves the action
+    pub fn allows(&self, action: &str, confidence: u32) -> bool {
+        action == "SUSTAIN" && confidence >= self.min_confidence
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guard_config() {
+        let config = GuardConfig::default();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.request("CHECK rm -rf /"), None);
+
+        let config: GuardConfig = toml::from_str("enabled = true\nmin_confidence = 80").unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.request("CHECK  rm -rf / "), Some("rm -rf /"));
+        assert_eq!(config.request("CHECKING disk"), None);
+        assert_eq!(config.request("ls CHECK x"), None);
+
+        assert!(config.allows("SUSTAIN", 90));
+        assert!(!config.allows("SUSTAIN", 70));
+        assert!(!config.allows("KILL", 100));
+        assert!(!config.allows("FAIL", 0));
+
+        assert!(config.context("").starts_with("The agent asks"));
+        assert!(config.context("ls\ncd /").ends_with("activity:\nls\ncd /"));
+
+        let config: GuardConfig = toml::from_str("min_confidence = 101").unwrap();
+        assert!(config.validate().is_err());
+        let config: GuardConfig = toml::from_str("prompt = ' '").unwrap();
+        assert!(config.validate().is_err());
+    }
+}
@@ -0,0 +1,277 @@
+//! Gzip - Compressing Archived Segments
+//!
+//! A small RFC 1952 gzip writer for segments uploaded by `s3`: LZ77 over
+//! the 32 KB DEFLATE window with hash chains, coded with the fixed Huffman
+//! tables of RFC 1951 in a single block. Audit JSONL repeats its keys,
+//! fingerprints and hashes on every line, so this gets most of what a full
+//! encoder would without a compression dependency; any `gunzip` reads it.
+
+/// DEFLATE window (largest match distance)
+const WINDOW: usize = 32 * 1024;
+
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+
+const HASH_BITS: u32 = 15;
+
+/// Earlier positions tried per match
+const MAX_CHAIN: usize = 64;
+
+/// No position (empty hash bucket or end of chain)
+const NONE: usize = usize::MAX;
+
+/// Lengths 3..=258: base length and extra bits of codes 257..=285
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+/// Distances 1..=32768: base distance and extra bits of codes 0..=29
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Gzip-compress `data`
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    // Magic, deflate, no flags, no mtime, no extra flags, unknown OS
+    let mut bits = Bits {
+        out: vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff],
+        buf: 0,
+        n: 0,
+    };
+    // BFINAL, BTYPE = 01 (fixed Huffman)
+    bits.put(1, 1);
+    bits.put(1, 2);
+    deflate(data, &mut bits);
+    bits.symbol(256);
+    let mut out = bits.finish();
+    out.extend_from_slice(&crc32(data).to_le_bytes());
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out
+}
+
+/// Greedy LZ77: the longest match among recent positions with the same
+/// three-byte prefix, else a literal
+fn deflate(data: &[u8], bits: &mut Bits) {
+    let mut chains = Chains {
+        head: vec![NONE; 1 << HASH_BITS],
+        prev: vec![NONE; WINDOW],
+    };
+    let mut pos = 0;
+    while pos < data.len() {
+        let (length, distance) = chains.longest_match(data, pos);
+        let length = match length >= MIN_MATCH {
+            true => {
+                bits.length(length);
+                bits.distance(distance);
+                length
+            }
+            false => {
+                bits.symbol(u16::from(data[pos]));
+                1
+            }
+        };
+        for p in pos..pos + length {
+            chains.insert(data, p);
+        }
+        pos += length;
+    }
+}
+
+/// Earlier positions by three-byte prefix, newest first
+struct Chains {
+    head: Vec<usize>,
+    /// Previous position with the same prefix, by position % WINDOW
+    prev: Vec<usize>,
+}
+
+impl Chains {
+    fn insert(&mut self, data: &[u8], pos: usize) {
+        if pos + MIN_MATCH <= data.len() {
+            let h = hash(&data[pos..pos + MIN_MATCH]);
+            self.prev[pos % WINDOW] = self.head[h];
+            self.head[h] = pos;
+        }
+    }
+
+    /// Length and distance of the longest match at `pos`
+    fn longest_match(&self, data: &[u8], pos: usize) -> (usize, usize) {
+        if pos + MIN_MATCH > data.len() {
+            return (0, 0);
+        }
+        let max = (data.len() - pos).min(MAX_MATCH);
+        let (mut best, mut distance) = (0, 0);
+        let mut candidate = self.head[hash(&data[pos..pos + MIN_MATCH])];
+        for _ in 0..MAX_CHAIN {
+            if candidate == NONE || pos - candidate > WINDOW {
+                break;
+            }
+            let length = data[candidate..]
+                .iter()
+                .zip(&data[pos..pos + max])
+                .take_while(|(a, b)| a == b)
+                .count();
+            if length > best {
+                (best, distance) = (length, pos - candidate);
+                if length == max {
+                    break;
+                }
+            }
+            let next = self.prev[candidate % WINDOW];
+            // A slot reused by a newer position ends the chain
+            if next == NONE || next >= candidate {
+                break;
+            }
+            candidate = next;
+        }
+        (best, distance)
+    }
+}
+
+fn hash(bytes: &[u8]) -> usize {
+    let v = u32::from(bytes[0]) << 16 | u32::from(bytes[1]) << 8 | u32::from(bytes[2]);
+    (v.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize
+}
+
+/// DEFLATE bit stream (least significant bit first)
+struct Bits {
+    out: Vec<u8>,
+    buf: u64,
+    n: u32,
+}
+
+impl Bits {
+    fn put(&mut self, value: u32, n: u32) {
+        self.buf |= u64::from(value) << self.n;
+        self.n += n;
+        while self.n >= 8 {
+            self.out.push(self.buf as u8);
+            self.buf >>= 8;
+            self.n -= 8;
+        }
+    }
+
+    /// Huffman codes are packed most significant bit first
+    fn code(&mut self, code: u32, n: u32) {
+        self.put(code.reverse_bits() >> (32 - n), n);
+    }
+
+    /// Fixed literal/length code (RFC 1951 3.2.6)
+    fn symbol(&mut self, symbol: u16) {
+        let s = u32::from(symbol);
+        match symbol {
+            0..=143 => self.code(0x30 + s, 8),
+            144..=255 => self.code(0x190 + s - 144, 9),
+            256..=279 => self.code(s - 256, 7),
+            _ => self.code(0xc0 + s - 280, 8),
+        }
+    }
+
+    fn length(&mut self, length: usize) {
+        let i = LENGTH_BASE
+            .iter()
+            .rposition(|&b| usize::from(b) <= length)
+            .unwrap();
+        self.symbol(257 + i as u16);
+        self.put(
+            (length - usize::from(LENGTH_BASE[i])) as u32,
+            u32::from(LENGTH_EXTRA[i]),
+        );
+    }
+
+    fn distance(&mut self, distance: usize) {
+        let i = DISTANCE_BASE
+            .iter()
+            .rposition(|&b| usize::from(b) <= distance)
+            .unwrap();
+        self.code(i as u32, 5);
+        self.put(
+            (distance - usize::from(DISTANCE_BASE[i])) as u32,
+            u32::from(DISTANCE_EXTRA[i]),
+        );
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.n > 0 {
+            self.out.push(self.buf as u8);
+        }
+        self.out
+    }
+}
+
+/// CRC-32 (IEEE), as gzip's trailer wants it
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    /// Decompress with the system `gzip`, if there is one
+    fn gunzip(data: &[u8]) -> Option<Vec<u8>> {
+        let mut child = Command::new("gzip")
+            .arg("-dc")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .ok()?;
+        let mut stdin = child.stdin.take().unwrap();
+        let data = data.to_vec();
+        // Fed from another thread so a full stdout pipe cannot stall us
+        std::thread::spawn(move || stdin.write_all(&data).unwrap());
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success());
+        Some(output.stdout)
+    }
+
+    #[test]
+    fn test_compress() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+
+        let mut jsonl = String::new();
+        for id in 0..2000 {
+            jsonl.push_str(&format!(
+                r#"{{"id":{},"timestamp_ms":{},"input_log":"order {} filled","action":"SUSTAIN","confidence":{}}}"#,
+                id,
+                1_792_000_000_000u64 + id * 37,
+                id * 7 % 1000,
+                id % 100
+            ));
+            jsonl.push('\n');
+        }
+        let long_run = vec![b'a'; 100_000];
+        for data in [
+            &b""[..],
+            b"a",
+            b"abcabcabcabcabc",
+            jsonl.as_bytes(),
+            &long_run,
+        ] {
+            let gz = compress(data);
+            assert_eq!(&gz[..3], &[0x1f, 0x8b, 8]);
+            assert_eq!(&gz[gz.len() - 4..], &(data.len() as u32).to_le_bytes());
+            if let Some(plain) = gunzip(&gz) {
+                assert_eq!(plain, data);
+            }
+        }
+        assert!(compress(jsonl.as_bytes()).len() * 4 < jsonl.len());
+    }
+}
@@ -0,0 +1,481 @@
+//! High Availability - Active/Standby Pair with Leader Election
+//!
+//! A single kill-switch process is itself a single point of failure. With
+//! an `[ha]` table, two kernels exchange UDP heartbeats; only the leader
+//! listens for agents and may signal targets. The standby takes over when
+//! the leader has been silent for `failover_ms`. A recovered instance
+//! rejoins as standby (no preemption); if both claim leadership after a
+//! partition heals, the lower `(priority, instance_id)` steps down and
+//! loses kill authority. Every audit record carries the `instance` id.
+//!
+//! ```toml
+//! [ha]
+//! instance_id = "tw-a"
+//! bind = "0.0.0.0:7946"
+//! peer = "10.0.0.2:7946"
+//! priority = 100
+//! heartbeat_ms = 500
+//! failover_ms = 2000
+//! key_file = "/etc/tripwired/ha.key"
+//! ```
+//!
+//! Heartbeats carry an HMAC-SHA256 under the shared `key_file` and a send
+//! time; datagrams from anywhere but `peer`, with a bad MAC, or no newer than
+//! the last accepted one (replays) are dropped.
+
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::cmp::Ordering;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::net::UdpSocket;
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+/// `[ha]` table
+#[derive(Debug, Clone, Deserialize)]
+pub struct HaConfig {
+    /// Recorded on every decision (default: `<hostname>-<pid>`)
+    #[serde(default)]
+    pub instance_id: Option<String>,
+    /// UDP address heartbeats are received on
+    pub bind: String,
+    /// The other instance's heartbeat address
+    pub peer: String,
+    /// Breaks ties when both instances want to lead; higher wins
+    #[serde(default = "default_priority")]
+    pub priority: u32,
+    #[serde(default = "default_heartbeat_ms")]
+    pub heartbeat_ms: u64,
+    /// Peer silence after which the standby takes over
+    #[serde(default = "default_failover_ms")]
+    pub failover_ms: u64,
+    /// File holding the HMAC key both instances sign heartbeats with
+    pub key_file: PathBuf,
+}
+
+fn default_priority() -> u32 {
+    100
+}
+
+fn default_heartbeat_ms() -> u64 {
+    500
+}
+
+fn default_failover_ms() -> u64 {
+    2000
+}
+
+impl HaConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, addr) in [("bind", &self.bind), ("peer", &self.peer)] {
+            addr.parse::<SocketAddr>()
+                .map_err(|e| format!("ha: invalid {} address '{}': {}", name, addr, e))?;
+        }
+        if self.heartbeat_ms == 0 || self.failover_ms < 2 * self.heartbeat_ms {
+            return Err("ha: failover_ms must be at least twice heartbeat_ms".to_string());
+        }
+        Ok(())
+    }
+
+    /// The shared heartbeat key (surrounding whitespace ignored)
+    fn key(&self) -> Result<Vec<u8>, String> {
+        let key = std::fs::read(&self.key_file)
+            .map_err(|e| format!("ha: {}: {}", self.key_file.display(), e))?;
+        match key.trim_ascii() {
+            [] => Err("ha: the key is empty".to_string()),
+            key => Ok(key.to_vec()),
+        }
+    }
+
+    fn instance_id(&self) -> String {
+        self.instance_id.clone().unwrap_or_else(|| {
+            let host = std::env::var("HOSTNAME")
+                .or_else(|_| std::env::var("COMPUTERNAME"))
+                .unwrap_or_else(|_| "tripwired".to_string());
+            format!("{}-{}", host, std::process::id())
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Standby,
+    Leader,
+}
+
+/// What an instance announces every `heartbeat_ms`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Heartbeat {
+    instance: String,
+    priority: u32,
+    role: Role,
+    /// Unix ms it was sent; must grow from one heartbeat to the next
+    sent_ms: u64,
+}
+
+/// One UDP datagram: a heartbeat and its MAC under the shared key
+#[derive(Serialize, Deserialize)]
+struct Signed {
+    heartbeat: String,
+    mac: String,
+}
+
+fn sign(key: &[u8], heartbeat: &Heartbeat) -> Vec<u8> {
+    let heartbeat = serde_json::to_string(heartbeat).expect("heartbeat serializes");
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(heartbeat.as_bytes());
+    let mac = crate::checkpoint::to_hex(&mac.finalize().into_bytes());
+    serde_json::to_vec(&Signed { heartbeat, mac }).expect("heartbeat serializes")
+}
+
+/// The heartbeat in `datagram` if its MAC checks out and it is newer than
+/// `last_ms` (which it then becomes)
+fn verify(key: &[u8], datagram: &[u8], last_ms: &mut u64) -> Result<Heartbeat, String> {
+    let signed: Signed = serde_json::from_slice(datagram).map_err(|e| e.to_string())?;
+    let tag = crate::checkpoint::from_hex(&signed.mac).ok_or("malformed MAC")?;
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(signed.heartbeat.as_bytes());
+    mac.verify_slice(&tag).map_err(|_| "bad MAC")?;
+    let heartbeat: Heartbeat =
+        serde_json::from_str(&signed.heartbeat).map_err(|e| e.to_string())?;
+    if heartbeat.sent_ms <= *last_ms {
+        return Err(format!("replayed (sent at {})", heartbeat.sent_ms));
+    }
+    *last_ms = heartbeat.sent_ms;
+    Ok(heartbeat)
+}
+
+/// Election state, driven by heartbeats and the clock
+struct Election {
+    instance: String,
+    priority: u32,
+    failover: Duration,
+    role: Role,
+    /// Last heartbeat from the peer, or our start time
+    peer_seen: Instant,
+    peer: Option<Heartbeat>,
+}
+
+impl Election {
+    fn new(instance: String, priority: u32, failover: Duration, now: Instant) -> Self {
+        Self {
+            instance,
+            priority,
+            failover,
+            role: Role::Standby,
+            peer_seen: now,
+            peer: None,
+        }
+    }
+
+    fn heartbeat(&mut self, heartbeat: Heartbeat, now: Instant) {
+        self.peer_seen = now;
+        self.peer = Some(heartbeat);
+    }
+
+    /// Our rank against the peer's; higher leads
+    fn outranks(&self, peer: &Heartbeat) -> bool {
+        (self.priority, self.instance.as_str()).cmp(&(peer.priority, peer.instance.as_str()))
+            == Ordering::Greater
+    }
+
+    /// Re-evaluate the role
+    fn tick(&mut self, now: Instant) -> Role {
+        let peer_alive = now.saturating_duration_since(self.peer_seen) < self.failover;
+        self.role = match (&self.peer, peer_alive) {
+            // Silence: whoever is up leads
+            (_, false) => Role::Leader,
+            // Not heard from anyone yet, but still inside the start-up grace
+            (None, true) => self.role,
+            (Some(peer), true) => match (self.role, peer.role) {
+                // Split brain after a partition: the lower rank yields
+                (Role::Leader, Role::Leader) if !self.outranks(peer) => Role::Standby,
+                // Both starting: the higher rank takes over
+                (Role::Standby, Role::Standby) if self.outranks(peer) => Role::Leader,
+                (role, _) => role,
+            },
+        };
+        self.role
+    }
+}
+
+/// This instance's place in the pair
+pub struct Ha {
+    config: HaConfig,
+    key: Vec<u8>,
+    instance: String,
+    election: Mutex<Election>,
+    role: watch::Sender<Role>,
+}
+
+impl Ha {
+    pub fn new(config: &HaConfig) -> Result<Self, String> {
+        let instance = config.instance_id();
+        Ok(Self {
+            election: Mutex::new(Election::new(
+                instance.clone(),
+                config.priority,
+                Duration::from_millis(config.failover_ms),
+                Instant::now(),
+            )),
+            config: config.clone(),
+            key: config.key()?,
+            instance,
+            role: watch::channel(Role::Standby).0,
+        })
+    }
+
+    pub fn instance(&self) -> &str {
+        &self.instance
+    }
+
+    pub fn role(&self) -> Role {
+        *self.role.borrow()
+    }
+
+    pub fn is_leader(&self) -> bool {
+        self.role() == Role::Leader
+    }
+
+    /// Resolves once this instance leads
+    pub async fn leader(&self) {
+        let mut role = self.role.subscribe();
+        let _ = role.wait_for(|role| *role == Role::Leader).await;
+    }
+
+    /// Exchange heartbeats until shutdown
+    pub async fn run(&self, shutdown: CancellationToken) -> std::io::Result<()> {
+        let socket = UdpSocket::bind(&self.config.bind).await?;
+        let peer: SocketAddr = self.config.peer.parse().expect("validated peer address");
+        let mut ticker = tokio::time::interval(Duration::from_millis(self.config.heartbeat_ms));
+        let mut buf = [0u8; 1024];
+        let mut last_ms = 0;
+        info!(
+            "👥 HA instance {} on {} (peer {})",
+            self.instance, self.config.bind, peer
+        );
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    let role = self.election.lock().unwrap().tick(Instant::now());
+                    self.set_role(role);
+                    let heartbeat = Heartbeat {
+                        instance: self.instance.clone(),
+                        priority: self.config.priority,
+                        role,
+                        sent_ms: now_ms(),
+                    };
+                    if let Err(e) = socket.send_to(&sign(&self.key, &heartbeat), peer).await {
+                        debug!("👥 Heartbeat to {} failed: {}", peer, e);
+                    }
+                }
+                received = socket.recv_from(&mut buf) => {
+                    let (len, from) = received?;
+                    if from != peer {
+                        debug!("👥 Ignoring datagram from {}: not the peer", from);
+                        continue;
+                    }
+                    match verify(&self.key, &buf[..len], &mut last_ms) {
+                        Ok(heartbeat) if heartbeat.instance != self.instance => {
+                            let mut election = self.election.lock().unwrap();
+                            election.heartbeat(heartbeat, Instant::now());
+                            let role = election.tick(Instant::now());
+                            drop(election);
+                            self.set_role(role);
+                        }
+                        Ok(_) => warn!("👥 Heartbeat from {} uses our own instance id", from),
+                        Err(e) => warn!("👥 Ignoring heartbeat from {}: {}", from, e),
+                    }
+                }
+                _ = shutdown.cancelled() => return Ok(()),
+            }
+        }
+    }
+
+    fn set_role(&self, role: Role) {
+        let previous = self.role.send_replace(role);
+        match (previous, role) {
+            (Role::Standby, Role::Leader) => {
+                warn!(
+                    "👑 {} is now the leader: serving agents with kill authority",
+                    self.instance
+                )
+            }
+            (Role::Leader, Role::Standby) => warn!(
+                "👥 {} stepped down to standby: kill authority revoked",
+                self.instance
+            ),
+            _ => {}
+        }
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn heartbeat(instance: &str, priority: u32, role: Role) -> Heartbeat {
+        Heartbeat {
+            instance: instance.to_string(),
+            priority,
+            role,
+            sent_ms: 0,
+        }
+    }
+
+    #[test]
+    fn test_failover() {
+        let t0 = Instant::now();
+        let at = |ms| t0 + Duration::from_millis(ms);
+        let mut standby = Election::new("b".to_string(), 100, Duration::from_millis(2000), t0);
+
+        // The leader is heartbeating: stay standby
+        standby.heartbeat(heartbeat("a", 100, Role::Leader), at(500));
+        assert_eq!(standby.tick(at(1000)), Role::Standby);
+        assert_eq!(standby.tick(at(2400)), Role::Standby);
+        // Leader silent for failover_ms: take over
+        assert_eq!(standby.tick(at(2500)), Role::Leader);
+        // The old leader comes back as standby: no preemption
+        standby.heartbeat(heartbeat("a", 200, Role::Standby), at(3000));
+        assert_eq!(standby.tick(at(3000)), Role::Leader);
+
+        // Nobody heard from at start-up: lead after the grace period
+        let mut alone = Election::new("a".to_string(), 100, Duration::from_millis(2000), t0);
+        assert_eq!(alone.tick(at(1999)), Role::Standby);
+        assert_eq!(alone.tick(at(2000)), Role::Leader);
+    }
+
+    #[test]
+    fn test_tie_breaks() {
+        let t0 = Instant::now();
+        let failover = Duration::from_millis(2000);
+
+        // Both starting: the higher priority leads, the other waits
+        let mut high = Election::new("a".to_string(), 200, failover, t0);
+        high.heartbeat(heartbeat("b", 100, Role::Standby), t0);
+        assert_eq!(high.tick(t0), Role::Leader);
+        let mut low = Election::new("b".to_string(), 100, failover, t0);
+        low.heartbeat(heartbeat("a", 200, Role::Standby), t0);
+        assert_eq!(low.tick(t0), Role::Standby);
+
+        // Split brain: equal priority, the lower instance id yields
+        let mut a = Election::new("a".to_string(), 100, failover, t0);
+        a.role = Role::Leader;
+        a.heartbeat(heartbeat("b", 100, Role::Leader), t0);
+        assert_eq!(a.tick(t0), Role::Standby);
+        let mut b = Election::new("b".to_string(), 100, failover, t0);
+        b.role = Role::Leader;
+        b.heartbeat(heartbeat("a", 100, Role::Leader), t0);
+        assert_eq!(b.tick(t0), Role::Leader);
+    }
+
+    #[test]
+    fn test_signed_heartbeats() {
+        let sent = |ms| Heartbeat {
+            sent_ms: ms,
+            ..heartbeat("a", 100, Role::Leader)
+        };
+        let mut last_ms = 0;
+        let datagram = sign(b"key", &sent(10));
+        assert_eq!(
+            verify(b"key", &datagram, &mut last_ms).unwrap().instance,
+            "a"
+        );
+        assert_eq!(last_ms, 10);
+        // Replayed, or not newer than the last accepted
+        assert!(verify(b"key", &datagram, &mut last_ms).is_err());
+        assert!(verify(b"key", &sign(b"key", &sent(9)), &mut last_ms).is_err());
+
+        // Wrong key, forged body, unsigned
+        let mut last_ms = 0;
+        assert!(verify(b"other", &sign(b"key", &sent(11)), &mut last_ms).is_err());
+        let forged = String::from_utf8(sign(b"key", &sent(11)))
+            .unwrap()
+            .replace("100", "999");
+        assert!(verify(b"key", forged.as_bytes(), &mut last_ms).is_err());
+        let unsigned = serde_json::to_vec(&sent(11)).unwrap();
+        assert!(verify(b"key", &unsigned, &mut last_ms).is_err());
+        assert_eq!(last_ms, 0);
+    }
+
+    #[tokio::test]
+    async fn test_only_peer_heard() {
+        let dir = tempfile::tempdir().unwrap();
+        let key_file = dir.path().join("ha.key");
+        std::fs::write(&key_file, "key\n").unwrap();
+        let peer = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let stranger = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let bind = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = bind.local_addr().unwrap();
+        drop(bind);
+        let config = HaConfig {
+            instance_id: Some("b".to_string()),
+            bind: addr.to_string(),
+            peer: peer.local_addr().unwrap().to_string(),
+            priority: 100,
+            heartbeat_ms: 50,
+            failover_ms: 5000,
+            key_file,
+        };
+        let ha = Arc::new(Ha::new(&config).unwrap());
+        let shutdown = CancellationToken::new();
+        let task = tokio::spawn({
+            let (ha, shutdown) = (Arc::clone(&ha), shutdown.clone());
+            async move { ha.run(shutdown).await }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let leader = |ms| Heartbeat {
+            sent_ms: ms,
+            ..heartbeat("a", 100, Role::Leader)
+        };
+
+        // Correctly signed, but not from the configured peer
+        stranger.send_to(&sign(b"key", &leader(1)), addr).unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(ha.election.lock().unwrap().peer.is_none());
+        // From the peer but signed with another key
+        peer.send_to(&sign(b"other", &leader(2)), addr).unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(ha.election.lock().unwrap().peer.is_none());
+
+        peer.send_to(&sign(b"key", &leader(3)), addr).unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(ha.election.lock().unwrap().peer.is_some());
+        shutdown.cancel();
+        task.await.unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = |extra: &str| {
+            toml::from_str::<HaConfig>(&format!(
+                "bind = '127.0.0.1:7946'\npeer = '127.0.0.1:7947'\nkey_file = '/k'\n{}",
+                extra
+            ))
+            .unwrap()
+        };
+        assert!(config("").validate().is_ok());
+        assert!(config("failover_ms = 600").validate().is_err());
+        assert!(
+            toml::from_str::<HaConfig>("bind = 'x'\npeer = '127.0.0.1:1'\nkey_file = '/k'")
+                .unwrap()
+                .validate()
+                .is_err()
+        );
+    }
+}
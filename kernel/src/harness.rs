@@ -0,0 +1,142 @@
+//! Filter Test Harness - Pattern Sets Checked in CI
+//!
+//! `tripwired test-filter --config filter.toml --cases cases.yaml` runs a
+//! list of log lines through the filter and compares the verdict with the
+//! expected one:
+//!
+//! ```yaml
+//! - line: "sudo rm -rf /var/lib/app"
+//!   expect: match
+//!   rule: essential#0       # optional: the rule reported for the line
+//! - line: "Heartbeat ok seq=42"
+//!   expect: no-match
+//! ```
+
+use crate::error::KernelError;
+use crate::filter::{Filter, FilterConfig};
+use serde::Deserialize;
+use std::path::Path;
+
+/// Expected filter verdict
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Expect {
+    Match,
+    NoMatch,
+}
+
+/// One line and its expected verdict
+#[derive(Debug, Clone, Deserialize)]
+pub struct TestCase {
+    pub line: String,
+    pub expect: Expect,
+    /// Rule id the filter should report (implies `expect: match`)
+    #[serde(default)]
+    pub rule: Option<String>,
+}
+
+/// Failure message for a case, or `None` if it passes
+pub fn evaluate(filter: &Filter, case: &TestCase) -> Option<String> {
+    let got = filter.check(&case.line).map(|m| m.name.as_str());
+    match (case.expect, got, case.rule.as_deref()) {
+        (Expect::Match, None, _) => Some("expected match, got no-match".to_string()),
+        (Expect::Match, Some(got), Some(want)) if got != want => {
+            Some(format!("expected rule '{}', got '{}'", want, got))
+        }
+        (Expect::NoMatch, Some(got), _) => Some(format!("expected no-match, got '{}'", got)),
+        _ => None,
+    }
+}
+
+/// `tripwired test-filter`
+pub fn test_filter(config: &FilterConfig, cases_path: &Path) -> Result<(), KernelError> {
+    let cases: Vec<TestCase> = serde_yaml::from_str(&std::fs::read_to_string(cases_path)?)?;
+    for case in &cases {
+        if case.expect == Expect::NoMatch && case.rule.is_some() {
+            return Err(format!("case '{}' expects no-match but names a rule", case.line).into());
+        }
+    }
+
+    // The slow path is the reference: a pre-screen bug must not hide a failure
+    let filter = Filter::new(config)?.without_prescreen();
+
+    println!(
+        "Filter test ({} cases, {})",
+        cases.len(),
+        cases_path.display()
+    );
+    let mut failed = 0;
+    for (i, case) in cases.iter().enumerate() {
+        if let Some(reason) = evaluate(&filter, case) {
+            failed += 1;
+            println!("  ✗ #{} {:?}: {}", i + 1, case.line, reason);
+        }
+    }
+    println!("  {} passed, {} failed", cases.len() - failed, failed);
+
+    if failed > 0 {
+        return Err(format!("{} filter test case(s) failed", failed).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn case(line: &str, expect: Expect, rule: Option<&str>) -> TestCase {
+        TestCase {
+            line: line.to_string(),
+            expect,
+            rule: rule.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let filter = Filter::default();
+
+        assert_eq!(
+            evaluate(&filter, &case("sudo rm -rf /", Expect::Match, None)),
+            None
+        );
+        assert_eq!(
+            evaluate(&filter, &case("Heartbeat ok", Expect::NoMatch, None)),
+            None
+        );
+        assert_eq!(
+            evaluate(&filter, &case("Heartbeat ok", Expect::Match, None)).unwrap(),
+            "expected match, got no-match"
+        );
+        assert!(
+            evaluate(&filter, &case("sudo rm -rf /", Expect::NoMatch, None))
+                .unwrap()
+                .starts_with("expected no-match, got 'essential#")
+        );
+        assert!(
+            evaluate(&filter, &case("sudo rm -rf /", Expect::Match, Some("nope")))
+                .unwrap()
+                .starts_with("expected rule 'nope'")
+        );
+    }
+
+    #[test]
+    fn test_cases_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("cases.yaml");
+        std::fs::write(
+            &path,
+            "- line: 'DROP TABLE users'\n  expect: match\n- line: 'User 7 logged in'\n  expect: no-match\n",
+        )
+        .unwrap();
+        assert!(test_filter(&FilterConfig::default(), &path).is_ok());
+
+        std::fs::write(&path, "- line: 'User 7 logged in'\n  expect: match\n").unwrap();
+        let err = test_filter(&FilterConfig::default(), &path).unwrap_err();
+        assert_eq!(err.to_string(), "1 filter test case(s) failed");
+
+        std::fs::write(&path, "- line: 'x'\n  expect: no-match\n  rule: a\n").unwrap();
+        assert!(test_filter(&FilterConfig::default(), &path).is_err());
+    }
+}
@@ -0,0 +1,329 @@
+//! LLM Health Probe - Canary Prompts and Degraded Mode
+//!
+//! A dead or confused inference server used to mean every suspicious line
+//! was silently SUSTAINed. The kernel now sends two canary lines at startup
+//! and every `interval_ms`: one the model must KILL, one it must SUSTAIN.
+//!
+//! - Kills decided by the LLM stay disarmed until the first probe passes
+//!   (`arm_requires_healthy = false` arms immediately); deterministic kills
+//!   (`action = "kill"` rules, `degraded_policy`) never wait for the canary
+//! - Every kill action stays disarmed until the startup checklist has passed
+//!   (see `selftest`)
+//! - While the probe fails, escalated lines skip the LLM and follow
+//!   `degraded_policy`: `sustain` (log and let it through) or `kill`
+//! - Operators can disarm (for a while or until re-armed) and arm by hand
+//!   through the admin API / `tripwired ctl`; arming overrides the canary
+//!
+//! ```toml
+//! [health]
+//! interval_ms = 30000
+//! degraded_policy = "kill"
+//! canary_kill = "Order #1 placed within 1ms; Order #2 placed within 1ms; exposure 5000%"
+//! canary_sustain = "Heartbeat ok, 0 open orders"
+//! ```
+
+use crate::chain::LlmChain;
+use crate::llm::Action;
+use serde::Deserialize;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{debug, info, warn};
+
+/// What escalated lines get while the LLM is unhealthy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DegradedPolicy {
+    /// Audit as SUSTAIN with confidence 0 (pre-probe behavior)
+    #[default]
+    Sustain,
+    /// Fail closed: treat every escalation as KILL
+    Kill,
+}
+
+/// `[health]` table
+#[derive(Debug, Clone, Deserialize)]
+pub struct HealthConfig {
+    /// Re-probe period (milliseconds); 0 disables the probe entirely
+    /// (always healthy and armed, the pre-probe behavior)
+    #[serde(default = "default_interval_ms")]
+    pub interval_ms: u64,
+    #[serde(default)]
+    pub degraded_policy: DegradedPolicy,
+    /// Keep kill actions disarmed until a probe passes
+    #[serde(default = "default_true")]
+    pub arm_requires_healthy: bool,
+    /// Line the model must KILL
+    #[serde(default = "default_canary_kill")]
+    pub canary_kill: String,
+    /// Line the model must SUSTAIN
+    #[serde(default = "default_canary_sustain")]
+    pub canary_sustain: String,
+}
+
+fn default_interval_ms() -> u64 {
+    30_000
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_canary_kill() -> String {
+    "Order #1001 placed within 1ms; Order #1002 placed within 1ms; Order #1003 placed within 1ms; exposure 5000% of limit".to_string()
+}
+
+fn default_canary_sustain() -> String {
+    "Heartbeat ok, 0 open orders".to_string()
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            interval_ms: default_interval_ms(),
+            degraded_policy: DegradedPolicy::default(),
+            arm_requires_healthy: true,
+            canary_kill: default_canary_kill(),
+            canary_sustain: default_canary_sustain(),
+        }
+    }
+}
+
+impl HealthConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.canary_kill.trim().is_empty() || self.canary_sustain.trim().is_empty() {
+            return Err("health: canary lines must not be empty".to_string());
+        }
+        if self.canary_kill == self.canary_sustain {
+            return Err("health: canary_kill and canary_sustain must differ".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Shared health and arming state
+pub struct Health {
+    healthy: AtomicBool,
+    armed: AtomicBool,
+    /// A probe has completed (the first failure is always reported)
+    probed: AtomicBool,
+    /// Operator hold: disarmed until this Unix time (ms); 0 = no hold
+    held_until_ms: AtomicU64,
+    /// Failed startup checks; `None` once the checklist passed
+    checklist: Mutex<Option<String>>,
+}
+
+impl Health {
+    /// Unhealthy until the first probe passes; armed up front unless gated
+    pub fn new(config: &HealthConfig) -> Self {
+        let disabled = config.interval_ms == 0;
+        Self {
+            healthy: AtomicBool::new(disabled),
+            armed: AtomicBool::new(disabled || !config.arm_requires_healthy),
+            probed: AtomicBool::new(false),
+            held_until_ms: AtomicU64::new(0),
+            checklist: Mutex::new(Some("not run yet".to_string())),
+        }
+    }
+
+    pub fn healthy(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+    }
+
+    /// May kill actions signal the target?
+    pub fn armed(&self) -> bool {
+        self.armed_for(true)
+    }
+
+    /// May a kill decided by the LLM (`llm`) or by a deterministic rule
+    /// signal the target? Only LLM verdicts wait for the canary; an operator
+    /// hold or a failed checklist disarms both
+    pub fn armed_for(&self, llm: bool) -> bool {
+        (!llm || self.armed.load(Ordering::Relaxed))
+            && self.held_until().is_none()
+            && self.checklist().is_ok()
+    }
+
+    /// The startup checklist passed, else what failed
+    pub fn checklist(&self) -> Result<(), String> {
+        match *self.checklist.lock().unwrap() {
+            Some(ref failed) => Err(failed.clone()),
+            None => Ok(()),
+        }
+    }
+
+    /// Record a checklist result (`None`: every check passed)
+    pub fn set_checklist(&self, failed: Option<String>) {
+        *self.checklist.lock().unwrap() = failed;
+    }
+
+    /// End of an active operator hold (Unix ms; `u64::MAX` = until re-armed)
+    pub fn held_until(&self) -> Option<u64> {
+        let until = self.held_until_ms.load(Ordering::Relaxed);
+        (until > now_ms()).then_some(until)
+    }
+
+    /// Operator disarm until `until_ms` (`u64::MAX`: until [`Health::arm`])
+    pub fn disarm(&self, until_ms: u64) {
+        self.held_until_ms.store(until_ms, Ordering::Relaxed);
+    }
+
+    /// Operator arm: lifts any hold, canary passed or not (a failed
+    /// checklist still keeps kill actions disarmed)
+    pub fn arm(&self) {
+        self.held_until_ms.store(0, Ordering::Relaxed);
+        self.armed.store(true, Ordering::Relaxed);
+    }
+
+    /// Record a probe result; returns the previous health
+    pub fn set_healthy(&self, healthy: bool) -> bool {
+        self.probed.store(true, Ordering::Relaxed);
+        if healthy {
+            self.armed.store(true, Ordering::Relaxed);
+        }
+        self.healthy.swap(healthy, Ordering::Relaxed)
+    }
+}
+
+/// Send both canaries; `Err` says what went wrong
+pub async fn probe(llm: &LlmChain, config: &HealthConfig) -> Result<(), String> {
+    for (line, expected) in [
+        (&config.canary_kill, Action::Kill),
+        (&config.canary_sustain, Action::Sustain),
+    ] {
+        let answer = llm.analyze(line, "").await.map_err(|e| e.to_string())?;
+        if answer.decision.action != expected {
+            return Err(format!(
+                "canary expected {}, got {} ({})",
+                expected, answer.decision.action, answer.model
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Probe once, update `health` and log transitions; returns the new health
+pub async fn check(llm: &LlmChain, config: &HealthConfig, health: &Health) -> bool {
+    let was_armed = health.armed.load(Ordering::Relaxed);
+    let first = !health.probed.load(Ordering::Relaxed);
+    match probe(llm, config).await {
+        Ok(()) => {
+            if !health.set_healthy(true) {
+                info!("💚 LLM healthy: canary probe passed");
+            }
+            if !was_armed {
+                info!("🔫 Kill actions armed");
+            }
+            true
+        }
+        Err(e) => {
+            if health.set_healthy(false) || first {
+                warn!(
+                    "💔 LLM unhealthy: {} - degraded policy: {:?}",
+                    e, config.degraded_policy
+                );
+            } else {
+                debug!("LLM still unhealthy: {}", e);
+            }
+            false
+        }
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arming() {
+        let health = Health::new(&HealthConfig::default());
+        health.set_checklist(None);
+        assert!(!health.healthy());
+        assert!(!health.armed());
+        // Deterministic rule kills never wait for the canary
+        assert!(health.armed_for(false));
+
+        assert!(!health.set_healthy(true));
+        assert!(health.armed());
+
+        // A failed checklist disarms whatever the canary says
+        health.set_checklist(Some("target: no such process".to_string()));
+        assert!(!health.armed());
+        assert_eq!(health.checklist().unwrap_err(), "target: no such process");
+        health.set_checklist(None);
+
+        // Losing health later does not disarm; degraded policy takes over
+        assert!(health.set_healthy(false));
+        assert!(!health.healthy());
+        assert!(health.armed());
+
+        let ungated: HealthConfig =
+            toml::from_str("arm_requires_healthy = false\ndegraded_policy = 'kill'").unwrap();
+        assert_eq!(ungated.degraded_policy, DegradedPolicy::Kill);
+        let health = Health::new(&ungated);
+        assert!(!health.armed());
+        health.set_checklist(None);
+        assert!(health.armed());
+
+        let disabled: HealthConfig = toml::from_str("interval_ms = 0").unwrap();
+        let health = Health::new(&disabled);
+        health.set_checklist(None);
+        assert!(health.healthy() && health.armed());
+        assert!(disabled.validate().is_ok());
+        assert!(toml::from_str::<HealthConfig>("canary_kill = ' '")
+            .unwrap()
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn test_operator_hold() {
+        let health = Health::new(&HealthConfig::default());
+        health.set_checklist(None);
+        // Operator arming overrides the canary gate
+        health.arm();
+        assert!(health.armed());
+
+        health.disarm(u64::MAX);
+        assert!(!health.armed());
+        assert!(!health.armed_for(false));
+        assert_eq!(health.held_until(), Some(u64::MAX));
+        // A passing probe does not lift an operator hold
+        health.set_healthy(true);
+        assert!(!health.armed());
+
+        // Expired holds no longer count
+        health.disarm(now_ms() - 1);
+        assert!(health.armed());
+        assert_eq!(health.held_until(), None);
+    }
+
+    #[tokio::test]
+    async fn test_dead_endpoint_is_unhealthy() {
+        use crate::audit::ModelFingerprint;
+        use crate::llm::LlmClient;
+        use std::time::Duration;
+
+        let url = "http://127.0.0.1:9/v1";
+        let chain = LlmChain::new(
+            vec![(
+                LlmClient::new(url, "dead", 30),
+                ModelFingerprint::new("dead", url, 30, &Default::default()),
+            )],
+            (3, Duration::from_secs(60)),
+        );
+        let config = HealthConfig::default();
+        let health = Health::new(&config);
+
+        assert!(!check(&chain, &config, &health).await);
+        assert!(!health.healthy());
+        assert!(!health.armed());
+    }
+}
@@ -0,0 +1,511 @@
+//! Incident - Everything About One Decision in One Archive
+//!
+//! Reviewing a KILL means collecting its record, what the agent did just
+//! before and after, what the model was asked and answered, which rules
+//! and models were in force, and the snapshot taken before the kill, from
+//! as many places. `tripwired incident` packs them into one `.tar.gz`:
+//!
+//! ```bash
+//! tripwired incident 4812 --audit /var/log/tripwired/ --filter-config tripwired.toml
+//! ```
+//!
+//! ```text
+//! incident-4812/manifest.json   what is where: the trail header in force
+//!                               (model fingerprint with its config_hash,
+//!                               prompt_hash), filter config SHA-256, and
+//!                               the SHA-256 of every file below
+//! incident-4812/decision.json   the decision record
+//! incident-4812/context.jsonl   the --context decision records before and
+//!                               after it in its file, as written
+//! incident-4812/events.jsonl    events linked to it by decision_id
+//!                               (explanation, authorization, snapshot,
+//!                               correction, ...) from every file
+//! incident-4812/prompt.json     rendered prompt (kept with --audit-prompts,
+//!                               else its hash) and raw response
+//! incident-4812/rules.json      the rule set of --filter-config, as loaded
+//! incident-4812/filter.toml     that config file as is
+//! incident-4812/snapshot/...    `[snapshot] dir` files of this decision
+//!                               (CRIU images, Docker checkpoint)
+//! ```
+//!
+//! `--audit` is an audit file or a directory of them (rotated segments
+//! included). The rule set is the config as it is now: compare its SHA-256
+//! with the one deployed at the time. Snapshot images can be large;
+//! `--no-snapshot` leaves them out (their `snapshot` events stay).
+
+use crate::checkpoint::to_hex;
+use crate::dataset::audit_files;
+use crate::error::KernelError;
+use crate::filter::{FilterConfig, RuleMeta};
+use crate::gzip;
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// tar block size
+const BLOCK: usize = 512;
+
+/// An archive file: path and content
+type Entry = (String, Vec<u8>);
+
+/// `tripwired incident` options
+#[derive(Debug, Clone)]
+pub struct IncidentOptions {
+    pub decision_id: u64,
+    pub audit: PathBuf,
+    /// Decision records kept on each side of the decision
+    pub context: usize,
+    pub snapshot: bool,
+    /// Archive written (default `incident-<id>.tar.gz`)
+    pub out: Option<PathBuf>,
+    /// The kernel's `--filter-config`
+    pub filter_config: Option<PathBuf>,
+}
+
+/// The decision and its neighbors in one audit file
+struct Found {
+    file: PathBuf,
+    /// Trail header of the run the decision belongs to
+    header: Option<Value>,
+    record: Value,
+    before: Vec<String>,
+    after: Vec<String>,
+}
+
+/// A rule as loaded, for `rules.json`
+#[derive(Serialize)]
+struct Rule<'a> {
+    pattern: &'a str,
+    #[serde(flatten)]
+    meta: RuleMeta,
+}
+
+/// `tripwired incident`
+pub fn run(options: &IncidentOptions, filter_config: &FilterConfig) -> Result<(), KernelError> {
+    let (entries, summary) = bundle(options, filter_config)?;
+    let out = options
+        .out
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(format!("incident-{}.tar.gz", options.decision_id)));
+    let archive = gzip::compress(&tar(&entries, now_ms() / 1000)?);
+    std::fs::write(&out, &archive)?;
+    println!("{}", summary);
+    println!("Written to {} ({} bytes)", out.display(), archive.len());
+    Ok(())
+}
+
+/// The archive's files (paths under `incident-<id>/`) and a summary line
+fn bundle(
+    options: &IncidentOptions,
+    filter_config: &FilterConfig,
+) -> Result<(Vec<Entry>, String), KernelError> {
+    let id = options.decision_id;
+    let files = audit_files(&options.audit)?;
+    let mut found = None;
+    for path in &files {
+        if let Some(f) = find(path, id, options.context)? {
+            found = Some(f);
+            break;
+        }
+    }
+    let Some(found) = found else {
+        return Err(format!("decision {} is not in {}", id, options.audit.display()).into());
+    };
+    let mut events = Vec::new();
+    for path in &files {
+        linked_events(path, id, &mut events)?;
+    }
+
+    let root = format!("incident-{}", id);
+    let mut entries = vec![(
+        "decision.json".to_string(),
+        serde_json::to_vec_pretty(&found.record)?,
+    )];
+    let context: Vec<&String> = found.before.iter().chain(&found.after).collect();
+    entries.push(("context.jsonl".to_string(), jsonl(&context)));
+    entries.push(("events.jsonl".to_string(), jsonl(&events)));
+    entries.push(("prompt.json".to_string(), prompt(&found.record)?));
+    let rules: Vec<Rule> = filter_config
+        .rules()
+        .into_iter()
+        .map(|(pattern, meta)| Rule { pattern, meta })
+        .collect();
+    entries.push(("rules.json".to_string(), serde_json::to_vec_pretty(&rules)?));
+    let config = match options.filter_config {
+        Some(ref path) => Some((path, std::fs::read(path)?)),
+        None => None,
+    };
+    if let Some((_, ref content)) = config {
+        entries.push(("filter.toml".to_string(), content.clone()));
+    }
+    let mut snapshot_files = 0;
+    if options.snapshot {
+        for (name, content) in snapshot(&filter_config.snapshot.dir, id)? {
+            entries.push((format!("snapshot/{}", name), content));
+            snapshot_files += 1;
+        }
+    }
+
+    let digests: BTreeMap<&str, String> = entries
+        .iter()
+        .map(|(name, content)| (name.as_str(), to_hex(&Sha256::digest(content))))
+        .collect();
+    let header = found.header.as_ref();
+    let manifest = serde_json::json!({
+        "decision_id": id,
+        "action": found.record.get("action"),
+        "timestamp_ms": found.record.get("timestamp_ms"),
+        "audit_file": found.file,
+        "header": header,
+        "config_hash": header.and_then(|h| h.pointer("/model_fingerprint/config_hash")),
+        "prompt_hash": header.and_then(|h| h.get("prompt_hash")),
+        "filter_config": config.as_ref().map(|(path, content)| serde_json::json!({
+            "path": path,
+            "sha256": to_hex(&Sha256::digest(content)),
+        })),
+        "context": {"before": found.before.len(), "after": found.after.len()},
+        "events": events.len(),
+        "created_at": now_ms(),
+        "files": digests,
+    });
+    entries.insert(
+        0,
+        (
+            "manifest.json".to_string(),
+            serde_json::to_vec_pretty(&manifest)?,
+        ),
+    );
+
+    let summary = format!(
+        "Incident {} ({}) from {}: {} + {} records of context, {} linked events, {} snapshot files",
+        id,
+        found.record["action"].as_str().unwrap_or("?"),
+        found.file.display(),
+        found.before.len(),
+        found.after.len(),
+        events.len(),
+        snapshot_files
+    );
+    let entries = entries
+        .into_iter()
+        .map(|(name, content)| (format!("{}/{}", root, name), content))
+        .collect();
+    Ok((entries, summary))
+}
+
+/// Decision `id` in audit file `path`, with `context` decision records on
+/// each side
+fn find(path: &Path, id: u64, context: usize) -> io::Result<Option<Found>> {
+    let mut header = None;
+    let mut before = VecDeque::with_capacity(context + 1);
+    let mut found: Option<Found> = None;
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        // Torn and corrupt lines are skipped
+        let Ok(value) = serde_json::from_str::<Value>(&line) else {
+            continue;
+        };
+        if value.get("event").is_some() {
+            continue;
+        }
+        let Some(record_id) = value.get("id").and_then(Value::as_u64) else {
+            if found.is_none() && value.get("version").is_some() {
+                header = Some(value);
+            }
+            continue;
+        };
+        match found {
+            Some(ref mut found) => {
+                found.after.push(line);
+                if found.after.len() == context {
+                    break;
+                }
+            }
+            None if record_id == id => {
+                found = Some(Found {
+                    file: path.to_path_buf(),
+                    header: header.take(),
+                    record: value,
+                    before: before.drain(..).collect(),
+                    after: Vec::new(),
+                });
+                if context == 0 {
+                    break;
+                }
+            }
+            None => {
+                before.push_back(line);
+                if before.len() > context {
+                    before.pop_front();
+                }
+            }
+        }
+    }
+    Ok(found)
+}
+
+/// Append the events in `path` linked to decision `id` to `events`
+fn linked_events(path: &Path, id: u64, events: &mut Vec<String>) -> io::Result<()> {
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        let Ok(value) = serde_json::from_str::<Value>(&line) else {
+            continue;
+        };
+        let linked = value.get("event").is_some()
+            && value.get("decision_id").and_then(Value::as_u64) == Some(id);
+        if linked {
+            events.push(line);
+        }
+    }
+    Ok(())
+}
+
+/// What the model was asked and answered
+fn prompt(record: &Value) -> Result<Vec<u8>, KernelError> {
+    // Kept as a JSON string of messages; stored as the messages themselves
+    let rendered = record
+        .get("rendered_prompt")
+        .and_then(Value::as_str)
+        .map(|p| serde_json::from_str::<Value>(p).unwrap_or_else(|_| Value::from(p)));
+    let prompt = serde_json::json!({
+        "model_fingerprint": record.get("model_fingerprint"),
+        "prompt_hash": record.get("prompt_hash"),
+        "rendered_prompt_hash": record.get("rendered_prompt_hash"),
+        "rendered_prompt": rendered,
+        "raw_response": record.get("raw_response"),
+        "reason": record.get("reason"),
+        "encrypted": record.get("encrypted"),
+    });
+    Ok(serde_json::to_vec_pretty(&prompt)?)
+}
+
+/// Files of decision `id`'s snapshots in `dir` (`tripwired-<id>` and
+/// `tripwired-<id>-pid-<pid>`), by path relative to `dir`
+fn snapshot(dir: &Path, id: u64) -> io::Result<Vec<Entry>> {
+    let mut files = Vec::new();
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Ok(files);
+    };
+    let name = format!("tripwired-{}", id);
+    let prefix = format!("{}-", name);
+    let mut roots: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            let file_name = entry.file_name();
+            let file_name = file_name.to_string_lossy();
+            file_name == name || file_name.starts_with(&prefix)
+        })
+        .map(|entry| entry.path())
+        .collect();
+    roots.sort();
+    for root in roots {
+        walk(dir, &root, &mut files)?;
+    }
+    Ok(files)
+}
+
+fn walk(base: &Path, path: &Path, files: &mut Vec<Entry>) -> io::Result<()> {
+    if path.is_dir() {
+        let mut children: Vec<PathBuf> = std::fs::read_dir(path)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .collect();
+        children.sort();
+        for child in children {
+            walk(base, &child, files)?;
+        }
+    } else if path.is_file() {
+        let relative = path.strip_prefix(base).unwrap_or(path);
+        let relative = relative.to_string_lossy().replace('\\', "/");
+        files.push((relative, std::fs::read(path)?));
+    }
+    Ok(())
+}
+
+fn jsonl(lines: &[impl AsRef<str>]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for line in lines {
+        out.extend_from_slice(line.as_ref().as_bytes());
+        out.push(b'\n');
+    }
+    out
+}
+
+/// A ustar archive of regular files (mode 0644, modified at `mtime`)
+fn tar(entries: &[Entry], mtime: u64) -> io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    for (path, content) in entries {
+        let mut header = [0u8; BLOCK];
+        let (prefix, name) = split_path(path)?;
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        octal(&mut header[100..108], 0o644);
+        octal(&mut header[108..116], 0);
+        octal(&mut header[116..124], 0);
+        octal(&mut header[124..136], content.len() as u64);
+        octal(&mut header[136..148], mtime);
+        header[156] = b'0';
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+        header[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+        // Checksum: the header's bytes summed with the field as spaces
+        header[148..156].fill(b' ');
+        let sum: u32 = header.iter().map(|&b| u32::from(b)).sum();
+        header[148..155].copy_from_slice(format!("{:06o}\0", sum).as_bytes());
+        out.extend_from_slice(&header);
+        out.extend_from_slice(content);
+        out.resize(out.len().next_multiple_of(BLOCK), 0);
+    }
+    // End of archive: two zero blocks
+    out.resize(out.len() + 2 * BLOCK, 0);
+    Ok(out)
+}
+
+/// ustar `prefix` and `name` of `path` (at most 155 and 100 bytes)
+fn split_path(path: &str) -> io::Result<(&str, &str)> {
+    if path.len() <= 100 {
+        return Ok(("", path));
+    }
+    path.char_indices()
+        .filter(|&(i, c)| c == '/' && i <= 155 && path.len() - i - 1 <= 100)
+        .map(|(i, _)| (&path[..i], &path[i + 1..]))
+        .next()
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("path too long for tar: {}", path),
+            )
+        })
+}
+
+/// Zero-padded octal, NUL-terminated, filling `field`
+fn octal(field: &mut [u8], value: u64) {
+    let width = field.len() - 1;
+    field[..width].copy_from_slice(format!("{:0width$o}", value, width = width).as_bytes());
+    field[width] = 0;
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::{AuditTrail, ModelFingerprint, RecordInput, SnapshotEvent};
+    use crate::llm::Sampling;
+    use crate::snapshot::SnapshotConfig;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_tar() {
+        let long = format!("incident-1/snapshot/{}/pages-1.img", "d".repeat(90));
+        let entries = vec![
+            ("a/b.txt".to_string(), b"hello".to_vec()),
+            (long.clone(), vec![7; 600]),
+        ];
+        let tar = tar(&entries, 1_700_000_000).unwrap();
+        assert_eq!(tar.len(), BLOCK * (1 + 1 + 1 + 2 + 2));
+
+        let header = &tar[..BLOCK];
+        assert_eq!(&header[..7], b"a/b.txt");
+        assert_eq!(&header[124..136], b"00000000005\0");
+        let stored = std::str::from_utf8(&header[148..154]).unwrap();
+        let mut blank = header.to_vec();
+        blank[148..156].fill(b' ');
+        let sum: u32 = blank.iter().map(|&b| u32::from(b)).sum();
+        assert_eq!(u32::from_str_radix(stored, 8).unwrap(), sum);
+        assert_eq!(&tar[BLOCK..BLOCK + 5], b"hello");
+
+        // Past 100 bytes the path is split into prefix and name
+        let header = &tar[2 * BLOCK..3 * BLOCK];
+        let (prefix, name) = split_path(&long).unwrap();
+        assert_eq!(name, "pages-1.img");
+        assert_eq!(&header[345..345 + prefix.len()], prefix.as_bytes());
+        assert!(split_path(&"x".repeat(300)).is_err());
+    }
+
+    #[test]
+    fn test_bundle() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let fp = ModelFingerprint::new("m", "http://localhost", 30, &Sampling::default());
+        let audit = AuditTrail::new(path.clone(), fp, "p").unwrap();
+        for (line, action) in [
+            ("order 1 filled", "SUSTAIN"),
+            ("order 2 filled", "SUSTAIN"),
+            ("sell everything", "KILL"),
+            ("order 3 filled", "SUSTAIN"),
+            ("order 4 filled", "SUSTAIN"),
+        ] {
+            audit
+                .record_entry(RecordInput {
+                    input_log: line,
+                    action,
+                    confidence: 95,
+                    raw_response: Some(format!("{{\"action\":\"{}\"}}", action)),
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+        let snapshot = SnapshotEvent::new(3, "pid 99", Ok("CRIU images".to_string()), 40);
+        audit.record_snapshot(&snapshot).unwrap();
+        drop(audit);
+
+        let images = dir.path().join("snapshots").join("tripwired-3-pid-99");
+        std::fs::create_dir_all(&images).unwrap();
+        std::fs::write(images.join("core-99.img"), b"core").unwrap();
+        std::fs::create_dir_all(dir.path().join("snapshots").join("tripwired-31-pid-1")).unwrap();
+        let filter_config = FilterConfig {
+            snapshot: SnapshotConfig {
+                dir: dir.path().join("snapshots"),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let options = IncidentOptions {
+            decision_id: 3,
+            audit: dir.path().to_path_buf(),
+            context: 1,
+            snapshot: true,
+            out: None,
+            filter_config: None,
+        };
+
+        let (entries, summary) = bundle(&options, &filter_config).unwrap();
+        let file = |name: &str| {
+            let name = format!("incident-3/{}", name);
+            let (_, content) = entries.iter().find(|(n, _)| *n == name).unwrap();
+            String::from_utf8(content.clone()).unwrap()
+        };
+        let decision: Value = serde_json::from_str(&file("decision.json")).unwrap();
+        assert_eq!(decision["input_log"], "sell everything");
+        let context = file("context.jsonl");
+        assert_eq!(context.lines().count(), 2);
+        assert!(context.contains("order 2 filled") && context.contains("order 3 filled"));
+        assert!(file("events.jsonl").contains(r#""event":"snapshot""#));
+        let prompt: Value = serde_json::from_str(&file("prompt.json")).unwrap();
+        assert_eq!(prompt["raw_response"], r#"{"action":"KILL"}"#);
+        assert_eq!(file("snapshot/tripwired-3-pid-99/core-99.img"), "core");
+        let manifest: Value = serde_json::from_str(&file("manifest.json")).unwrap();
+        assert_eq!(manifest["action"], "KILL");
+        assert_eq!(manifest["prompt_hash"], crate::audit::sha256_hex("p"));
+        assert_eq!(
+            manifest["files"].as_object().unwrap().len(),
+            entries.len() - 1
+        );
+        assert!(summary.contains("1 linked events, 1 snapshot files"));
+
+        let missing = IncidentOptions {
+            decision_id: 9,
+            ..options
+        };
+        assert!(bundle(&missing, &filter_config).is_err());
+    }
+}
@@ -0,0 +1,497 @@
+//! Kubernetes - Pod Logs as Agent Connections
+//!
+//! `--watch-pods <selector>` makes one kernel the kill-switch for every
+//! agent pod in a namespace, without sidecar injection. It watches the pods
+//! matching the label selector and follows the logs of each running
+//! container as its own agent connection (`pod:<namespace>/<pod>/<container>`
+//! in `/agents`). A KILL on that connection deletes the pod (grace period
+//! 0, only if it is still the same pod); PAUSE has no pod equivalent and
+//! leaves it running.
+//!
+//! Containers already running at startup are followed from their next
+//! line; pods and container restarts seen later are read from their first
+//! line, so nothing an agent logs before we attach is missed.
+//!
+//! In a pod, the API server, token and namespace come from the service
+//! account; elsewhere point `--kube-api` at `kubectl proxy`. The service
+//! account needs `get`, `list`, `watch` and `delete` on `pods` and `get` on
+//! `pods/log`.
+//!
+//! ```text
+//! tripwired --watch-pods app=trader,tier=agent --pod-namespace agents
+//! ```
+
+use reqwest::{Method, RequestBuilder, StatusCode};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+const SERVICE_ACCOUNT: &str = "/var/run/secrets/kubernetes.io/serviceaccount";
+
+/// Pause between attempts after an API error
+pub const RETRY: Duration = Duration::from_secs(2);
+
+/// Watches end (and the pod list is re-read) this often
+const WATCH_TIMEOUT_S: u64 = 300;
+
+/// What to watch (`--watch-pods`, or a channel's `pods`)
+#[derive(Debug, Clone)]
+pub struct PodWatch {
+    pub selector: String,
+    /// Default: the service account's namespace, else `default`
+    pub namespace: Option<String>,
+    /// API server URL (default: in-cluster)
+    pub api: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Pod {
+    metadata: Metadata,
+    #[serde(default)]
+    status: PodStatus,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Metadata {
+    name: String,
+    #[serde(default)]
+    uid: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PodStatus {
+    #[serde(default)]
+    container_statuses: Vec<ContainerStatus>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ContainerStatus {
+    name: String,
+    #[serde(default)]
+    restart_count: u32,
+    #[serde(default)]
+    state: ContainerState,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct ContainerState {
+    #[serde(default)]
+    running: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct PodList {
+    metadata: ListMeta,
+    items: Vec<Pod>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListMeta {
+    #[serde(default)]
+    resource_version: String,
+}
+
+#[derive(Deserialize)]
+struct WatchEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    object: serde_json::Value,
+}
+
+/// A failed API call (`status` is `None` when the server was not reached)
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: Option<StatusCode>,
+    pub message: String,
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.status {
+            Some(status) => write!(f, "{} ({})", self.message, status),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl ApiError {
+    fn new(message: impl ToString) -> Self {
+        Self {
+            status: None,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// One run of one container: what a log stream covers
+#[derive(Debug, Clone, PartialEq)]
+pub struct Run {
+    pub pod: String,
+    pub uid: String,
+    pub container: String,
+    pub restart_count: u32,
+    /// Running when the kernel started: follow from the next line only
+    pub existing: bool,
+}
+
+/// A container run as a kill target (`<namespace>/<pod>/<container>`)
+#[derive(Debug, Clone)]
+pub struct PodTarget {
+    pub kube: Arc<Kube>,
+    pub run: Run,
+}
+
+impl fmt::Display for PodTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{}/{}",
+            self.kube.namespace, self.run.pod, self.run.container
+        )
+    }
+}
+
+/// Where a log stream starts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum From {
+    /// The container's first line
+    First,
+    /// The next line it logs
+    Next,
+    /// This many seconds back (re-attaching after a dropped stream)
+    Since(u64),
+}
+
+/// Container runs reported already, by (pod UID, container, restart count)
+#[derive(Debug, Default)]
+struct Followed(HashSet<(String, String, u32)>);
+
+impl Followed {
+    /// Running containers of `pod` not reported yet
+    fn new_runs(&mut self, pod: &Pod, existing: bool) -> Vec<Run> {
+        let uid = &pod.metadata.uid;
+        pod.status
+            .container_statuses
+            .iter()
+            .filter(|c| c.state.running.is_some())
+            .filter(|c| {
+                self.0
+                    .insert((uid.clone(), c.name.clone(), c.restart_count))
+            })
+            .map(|c| Run {
+                pod: pod.metadata.name.clone(),
+                uid: uid.clone(),
+                container: c.name.clone(),
+                restart_count: c.restart_count,
+                existing,
+            })
+            .collect()
+    }
+
+    /// Drop pods that are gone
+    fn retain(&mut self, uids: &HashSet<&str>) {
+        self.0.retain(|(uid, _, _)| uids.contains(uid.as_str()));
+    }
+}
+
+/// Kubernetes API client for one namespace
+#[derive(Debug)]
+pub struct Kube {
+    api: String,
+    namespace: String,
+    http: reqwest::Client,
+    /// Service account token, re-read per request (projected tokens rotate)
+    token: Option<PathBuf>,
+}
+
+impl Kube {
+    pub fn connect(watch: &PodWatch) -> Result<Self, String> {
+        let service_account = Path::new(SERVICE_ACCOUNT);
+        let in_cluster = std::env::var("KUBERNETES_SERVICE_HOST")
+            .ok()
+            .zip(std::env::var("KUBERNETES_SERVICE_PORT").ok());
+        let mut http = reqwest::Client::builder();
+        let (api, token) = match (&watch.api, in_cluster) {
+            (Some(api), _) => (api.trim_end_matches('/').to_string(), None),
+            (None, Some((host, port))) => {
+                let ca = std::fs::read(service_account.join("ca.crt"))
+                    .map_err(|e| format!("service account CA: {}", e))?;
+                let ca = reqwest::Certificate::from_pem(&ca)
+                    .map_err(|e| format!("service account CA: {}", e))?;
+                http = http.add_root_certificate(ca);
+                let host = match host.contains(':') {
+                    true => format!("[{}]", host),
+                    false => host,
+                };
+                let api = format!("https://{}:{}", host, port);
+                (api, Some(service_account.join("token")))
+            }
+            (None, None) => {
+                return Err(
+                    "not running in a cluster: set --kube-api (e.g. http://127.0.0.1:8001 for `kubectl proxy`)"
+                        .to_string(),
+                )
+            }
+        };
+        let namespace = watch
+            .namespace
+            .clone()
+            .or_else(|| {
+                std::fs::read_to_string(service_account.join("namespace"))
+                    .ok()
+                    .map(|ns| ns.trim().to_string())
+            })
+            .unwrap_or_else(|| "default".to_string());
+        Ok(Self {
+            api,
+            namespace,
+            http: http.build().map_err(|e| e.to_string())?,
+            token,
+        })
+    }
+
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    fn pods_path(&self) -> String {
+        format!("/api/v1/namespaces/{}/pods", self.namespace)
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        self.http.request(method, format!("{}{}", self.api, path))
+    }
+
+    async fn send(&self, mut request: RequestBuilder) -> Result<reqwest::Response, ApiError> {
+        if let Some(ref token) = self.token {
+            let token = tokio::fs::read_to_string(token)
+                .await
+                .map_err(|e| ApiError::new(format!("service account token: {}", e)))?;
+            request = request.bearer_auth(token.trim());
+        }
+        let response = request.send().await.map_err(ApiError::new)?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        #[derive(Deserialize)]
+        struct Status {
+            message: String,
+        }
+        let body = response.text().await.unwrap_or_default();
+        let message = serde_json::from_str::<Status>(&body)
+            .map(|s| s.message)
+            .unwrap_or(body);
+        Err(ApiError {
+            status: Some(status),
+            message: message.trim().to_string(),
+        })
+    }
+
+    /// Report every container run of the matching pods until `runs` closes
+    pub async fn watch(&self, selector: &str, runs: mpsc::Sender<Run>) {
+        let mut followed = Followed::default();
+        let mut existing = true;
+        while !runs.is_closed() {
+            if let Err(e) = self
+                .watch_once(selector, &mut followed, &mut existing, &runs)
+                .await
+            {
+                warn!("☸️ Pod watch: {} - retrying", e);
+                tokio::time::sleep(RETRY).await;
+            }
+        }
+    }
+
+    /// List, then watch from the list's resource version until the watch ends
+    async fn watch_once(
+        &self,
+        selector: &str,
+        followed: &mut Followed,
+        existing: &mut bool,
+        runs: &mpsc::Sender<Run>,
+    ) -> Result<(), ApiError> {
+        let path = self.pods_path();
+        let request = self
+            .request(Method::GET, &path)
+            .query(&[("labelSelector", selector)]);
+        let list: PodList = self
+            .send(request)
+            .await?
+            .json()
+            .await
+            .map_err(ApiError::new)?;
+        followed.retain(&list.items.iter().map(|p| p.metadata.uid.as_str()).collect());
+        for pod in &list.items {
+            for run in followed.new_runs(pod, *existing) {
+                let _ = runs.send(run).await;
+            }
+        }
+        *existing = false;
+
+        let timeout = WATCH_TIMEOUT_S.to_string();
+        let request = self.request(Method::GET, &path).query(&[
+            ("labelSelector", selector),
+            ("watch", "true"),
+            ("resourceVersion", &list.metadata.resource_version),
+            ("timeoutSeconds", &timeout),
+        ]);
+        let mut response = self.send(request).await?;
+        let mut buf = Vec::new();
+        while let Some(chunk) = response.chunk().await.map_err(ApiError::new)? {
+            buf.extend_from_slice(&chunk);
+            while let Some(end) = buf.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = buf.drain(..=end).collect();
+                let Ok(event) = serde_json::from_slice::<WatchEvent>(&line) else {
+                    continue;
+                };
+                match event.kind.as_str() {
+                    "ADDED" | "MODIFIED" => {
+                        let Ok(pod) = serde_json::from_value::<Pod>(event.object) else {
+                            continue;
+                        };
+                        for run in followed.new_runs(&pod, false) {
+                            if runs.send(run).await.is_err() {
+                                return Ok(());
+                            }
+                        }
+                    }
+                    "DELETED" => {
+                        if let Ok(pod) = serde_json::from_value::<Pod>(event.object) {
+                            followed.0.retain(|(uid, _, _)| *uid != pod.metadata.uid);
+                        }
+                    }
+                    // 410 Gone: the resource version expired, list again
+                    "ERROR" => {
+                        debug!("☸️ Watch ended: {}", event.object);
+                        return Ok(());
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Follow a container run's log
+    pub async fn logs(
+        &self,
+        run: &Run,
+        from: From,
+    ) -> Result<impl AsyncRead + Send + Unpin, ApiError> {
+        let path = format!("{}/{}/log", self.pods_path(), run.pod);
+        let mut query = vec![
+            ("container", run.container.clone()),
+            ("follow", "true".to_string()),
+        ];
+        match from {
+            From::First => {}
+            From::Next => query.push(("tailLines", "0".to_string())),
+            From::Since(seconds) => query.push(("sinceSeconds", seconds.max(1).to_string())),
+        }
+        let request = self.request(Method::GET, &path).query(&query);
+        let mut response = self.send(request).await?;
+
+        let (mut writer, reader) = tokio::io::duplex(64 * 1024);
+        tokio::spawn(async move {
+            while let Ok(Some(chunk)) = response.chunk().await {
+                if writer.write_all(&chunk).await.is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(reader)
+    }
+
+    /// Whether the run is still going (same pod, same container restart)
+    pub async fn still_running(&self, run: &Run) -> Result<bool, ApiError> {
+        let path = format!("{}/{}", self.pods_path(), run.pod);
+        let pod: Pod = match self.send(self.request(Method::GET, &path)).await {
+            Ok(response) => response.json().await.map_err(ApiError::new)?,
+            Err(e) if e.status == Some(StatusCode::NOT_FOUND) => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        Ok(pod.metadata.uid == run.uid
+            && pod.status.container_statuses.iter().any(|c| {
+                c.name == run.container
+                    && c.restart_count == run.restart_count
+                    && c.state.running.is_some()
+            }))
+    }
+
+    /// Delete the run's pod at once, unless it was already replaced by a
+    /// pod of the same name
+    pub async fn delete(&self, run: &Run) -> Result<(), ApiError> {
+        let path = format!("{}/{}", self.pods_path(), run.pod);
+        let request = self
+            .request(Method::DELETE, &path)
+            .json(&serde_json::json!({
+                "gracePeriodSeconds": 0,
+                "preconditions": { "uid": run.uid },
+            }));
+        self.send(request).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pod(uid: &str, containers: &[(&str, u32, bool)]) -> Pod {
+        let statuses: Vec<serde_json::Value> = containers
+            .iter()
+            .map(|(name, restarts, running)| {
+                let state = match running {
+                    true => serde_json::json!({"running": {"startedAt": "2026-10-15T09:00:00Z"}}),
+                    false => serde_json::json!({"waiting": {"reason": "CrashLoopBackOff"}}),
+                };
+                serde_json::json!({"name": name, "restartCount": restarts, "state": state})
+            })
+            .collect();
+        serde_json::from_value(serde_json::json!({
+            "metadata": {"name": "trader-0", "uid": uid},
+            "status": {"containerStatuses": statuses},
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_new_runs() {
+        let mut followed = Followed::default();
+        let runs = followed.new_runs(&pod("u1", &[("agent", 0, true), ("proxy", 0, false)]), true);
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].container, "agent");
+        assert!(runs[0].existing);
+
+        // Reported once; the sidecar starting and a restart are new runs
+        let pod2 = pod("u1", &[("agent", 0, true), ("proxy", 0, true)]);
+        assert_eq!(followed.new_runs(&pod2, false).len(), 1);
+        let restarted = followed.new_runs(&pod("u1", &[("agent", 1, true)]), false);
+        assert_eq!(restarted[0].restart_count, 1);
+        assert!(!restarted[0].existing);
+        assert!(followed
+            .new_runs(&pod("u1", &[("agent", 1, true)]), false)
+            .is_empty());
+
+        // A pod recreated under the same name has a new UID
+        followed.retain(&HashSet::from(["u2"]));
+        assert_eq!(
+            followed
+                .new_runs(&pod("u2", &[("agent", 0, true)]), false)
+                .len(),
+            1
+        );
+    }
+}
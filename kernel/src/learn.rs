@@ -0,0 +1,227 @@
+//! Learning Mode - Exclude Suggestions from LLM Verdicts
+//!
+//! Lines the LLM confidently SUSTAINs are noise the filter should not have
+//! escalated. `--learn <duration>` clusters them by template (timestamps,
+//! numbers, hex ids, UUIDs abstracted away, see `normalize`) and writes an
+//! `exclude` list for review.
+
+use crate::filter::ESSENTIAL_PATTERNS;
+use crate::llm::{Action, Decision};
+use crate::normalize::{Normalizer, Segment};
+use regex::RegexSet;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Minimum LLM confidence for a SUSTAIN to count as benign
+pub const MIN_CONFIDENCE: u32 = 90;
+
+/// Minimum lines per template before it is suggested
+pub const MIN_CLUSTER: u64 = 3;
+
+/// Parse a duration such as `90s`, `30m`, `2h`, `1d`, or `500ms`
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| format!("missing unit in '{}' (use ms, s, m, h, or d)", s))?;
+    let (value, unit) = s.split_at(split);
+    let value: u64 = value
+        .parse()
+        .map_err(|_| format!("invalid duration '{}'", s))?;
+    let secs = match unit {
+        "ms" => return Ok(Duration::from_millis(value)),
+        "s" => value,
+        "m" => value * 60,
+        "h" => value * 3600,
+        "d" => value * 86400,
+        _ => return Err(format!("unknown unit '{}' (use ms, s, m, h, or d)", unit)),
+    };
+    Ok(Duration::from_secs(secs))
+}
+
+/// Lines sharing one template
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suggestion {
+    /// Anchored exclude pattern
+    pub pattern: String,
+    pub count: u64,
+    pub example: String,
+}
+
+/// Collects confidently benign escalations during a learn run
+pub struct Learner {
+    normalizer: Normalizer,
+    essential: RegexSet,
+    clusters: Mutex<HashMap<String, Suggestion>>,
+}
+
+impl Learner {
+    pub fn new() -> Self {
+        Self {
+            normalizer: Normalizer::new(),
+            essential: RegexSet::new(ESSENTIAL_PATTERNS).expect("Invalid essential patterns"),
+            clusters: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record an escalated line and the LLM's verdict on it
+    pub fn observe(&self, line: &str, decision: &Decision) {
+        if decision.action != Action::Sustain || decision.confidence < MIN_CONFIDENCE {
+            return;
+        }
+        // Never propose whitelisting something an Essential rule catches
+        if self.essential.is_match(line) {
+            return;
+        }
+
+        let pattern = self.template(line);
+        let mut clusters = self.clusters.lock().unwrap();
+        clusters
+            .entry(pattern.clone())
+            .or_insert_with(|| Suggestion {
+                pattern,
+                count: 0,
+                example: line.to_string(),
+            })
+            .count += 1;
+    }
+
+    /// Anchored regex matching `line` with variable tokens generalized
+    fn template(&self, line: &str) -> String {
+        let mut out = String::from("^");
+        for segment in self.normalizer.segments(line) {
+            match segment {
+                Segment::Text(text) => out.push_str(&regex::escape(text)),
+                Segment::Token(token) => out.push_str(token.pattern()),
+            }
+        }
+        out.push('$');
+        out
+    }
+
+    /// Templates seen at least `MIN_CLUSTER` times, most frequent first
+    pub fn suggestions(&self) -> Vec<Suggestion> {
+        let mut out: Vec<Suggestion> = self
+            .clusters
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|s| s.count >= MIN_CLUSTER)
+            .cloned()
+            .collect();
+        out.sort_by(|a, b| b.count.cmp(&a.count).then(a.pattern.cmp(&b.pattern)));
+        out
+    }
+}
+
+/// Render suggestions as a reviewable filter config fragment
+pub fn render_toml(suggestions: &[Suggestion], period: Duration) -> String {
+    let mut out = format!(
+        "# Suggested by `tripwired --learn` over {}s: lines the LLM SUSTAINed\n\
+         # with >= {}% confidence, {} or more times per template.\n\
+         # Review before use - excludes skip Domain and Custom rules.\n\
+         exclude = [\n",
+        period.as_secs(),
+        MIN_CONFIDENCE,
+        MIN_CLUSTER
+    );
+    for s in suggestions {
+        let example: String = s.example.chars().take(80).collect();
+        out.push_str(&format!(
+            "    # {}x, e.g. {}\n    {},\n",
+            s.count,
+            example.escape_debug(),
+            toml::Value::String(s.pattern.clone())
+        ));
+    }
+    out.push_str("]\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    fn sustain(confidence: u32) -> Decision {
+        Decision {
+            action: Action::Sustain,
+            confidence,
+            model_confidence: None,
+            reason: None,
+            raw_response: String::new(),
+            prompt: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("30m"), Ok(Duration::from_secs(1800)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+        assert_eq!(parse_duration("1d"), Ok(Duration::from_secs(86400)));
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert!(parse_duration("30").is_err());
+        assert!(parse_duration("5w").is_err());
+        assert!(parse_duration("m").is_err());
+    }
+
+    #[test]
+    fn test_clusters_by_template() {
+        let learner = Learner::new();
+        for (id, price) in [(101, "45.20"), (102, "45.31"), (103, "44.98")] {
+            let line = format!("Order {} filled at {} (tx 0x1f{})", id, price, id);
+            learner.observe(&line, &sustain(95));
+        }
+        learner.observe("Heartbeat ok", &sustain(99)); // too rare
+
+        let suggestions = learner.suggestions();
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].count, 3);
+
+        let re = Regex::new(&suggestions[0].pattern).unwrap();
+        assert!(re.is_match("Order 999 filled at 1.5 (tx 0xabc)"));
+        assert!(!re.is_match("Order 999 cancelled at 1.5 (tx 0xabc)"));
+    }
+
+    #[test]
+    fn test_ignores_uncertain_and_kill() {
+        let learner = Learner::new();
+        for _ in 0..5 {
+            learner.observe("Retry 1 of 3", &sustain(60));
+            learner.observe(
+                "Retry 2 of 3",
+                &Decision {
+                    action: Action::Kill,
+                    ..sustain(99)
+                },
+            );
+        }
+        assert!(learner.suggestions().is_empty());
+    }
+
+    #[test]
+    fn test_never_suggests_essential() {
+        let learner = Learner::new();
+        for i in 0..5 {
+            learner.observe(&format!("cleanup: rm -rf /tmp/cache{}", i), &sustain(99));
+        }
+        assert!(learner.suggestions().is_empty());
+    }
+
+    #[test]
+    fn test_render_toml_round_trips() {
+        let learner = Learner::new();
+        for i in 0..3 {
+            learner.observe(&format!("Can't reach peer {}", i), &sustain(95));
+        }
+        let rendered = render_toml(&learner.suggestions(), Duration::from_secs(60));
+        let config: crate::filter::FilterConfig = toml::from_str(&rendered).unwrap();
+        assert_eq!(config.exclude.len(), 1);
+        assert!(config.validate().is_ok());
+        assert!(Regex::new(&config.exclude[0])
+            .unwrap()
+            .is_match("Can't reach peer 42"));
+    }
+}
@@ -0,0 +1,4938 @@
+//! Tripwired Kernel - Deterministic Kill-Switch for Autonomous Agents
+//!
+//! Optimized for Windows: Named Pipes (faster than TCP), TLS-free HTTP,
+//! pre-compiled regex, aggressive connection pooling.
+
+mod ack;
+mod admin;
+mod agents;
+pub mod audit;
+mod authorize;
+mod backfill;
+mod backup;
+mod batch;
+pub mod bench;
+mod budget;
+mod cgroup;
+mod chain;
+mod channel;
+mod check;
+mod checkpoint;
+mod classify;
+mod clock;
+mod contain;
+mod context;
+mod correlate;
+mod credential;
+mod ctl;
+mod dataset;
+mod docker;
+mod drill;
+mod egress;
+mod email;
+mod encrypt;
+mod error;
+#[cfg(any(windows, test))]
+mod etw;
+mod explain;
+mod expr;
+pub mod filter;
+mod flow;
+mod fswatch;
+mod guard;
+mod gzip;
+mod ha;
+mod harness;
+mod health;
+mod honeypot;
+mod incident;
+mod kube;
+mod learn;
+mod limit;
+mod line;
+mod lint;
+mod llm;
+mod loadgen;
+mod logging;
+mod mcp;
+mod multiline;
+mod normalize;
+mod notify;
+mod otlp;
+mod parse;
+mod policy;
+mod preset;
+mod priority;
+mod probe;
+mod queue;
+mod rate;
+mod redact;
+mod report;
+mod resource;
+mod restrict;
+mod retention;
+mod s3;
+mod schedule;
+mod selftest;
+mod shadow;
+mod ship;
+mod sigma;
+mod slo;
+mod snapshot;
+mod stage;
+mod stats;
+#[cfg(target_os = "linux")]
+mod systemd;
+mod top;
+mod valve;
+mod verdict;
+mod vm;
+#[cfg(target_os = "linux")]
+mod vsock;
+mod watchdog;
+mod whatif;
+
+use audit::{AuditTrail, ModelFingerprint, RecordInput};
+use clap::{Parser, Subcommand};
+use error::KernelError;
+use stats::{Stats, StatsSnapshot};
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::BufReader;
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+use tracing::{debug, error, info, warn, Instrument};
+
+#[cfg(windows)]
+use tokio::net::windows::named_pipe::{PipeMode, ServerOptions};
+
+#[cfg(unix)]
+use tokio::net::UnixListener;
+
+/// Default agent endpoint without `--tcp` (`--pipe-name`)
+#[cfg(windows)]
+const PIPE_NAME: &str = "tripwired-sock";
+/// Default agent endpoint without `--tcp` (`--socket-path`)
+#[cfg(unix)]
+const SOCKET_PATH: &str = "/tmp/tripwired.sock";
+
+/// Tripwired Kernel - Deterministic Kill-Switch
+#[derive(Parser, Debug)]
+#[command(name = "tripwired")]
+#[command(about = "Kill-switch kernel for autonomous agents")]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Cmd>,
+
+    /// LLM API endpoint
+    #[arg(long, default_value = "http://localhost:1234/v1")]
+    llm_url: String,
+
+    /// Model name
+    #[arg(long, default_value = "llama-3.2-3b-instruct")]
+    model: String,
+
+    /// Target process PID to kill on KILL decision
+    #[arg(long)]
+    target_pid: Option<u32>,
+
+    /// VM powered off on KILL and suspended on PAUSE: libvirt:<domain>,
+    /// qmp:<socket> or (Windows) hyperv:<name>
+    #[arg(long, value_parser = vm::parse_target)]
+    target_vm: Option<vm::VmTarget>,
+
+    /// Max tokens for LLM response
+    #[arg(long, default_value = "64")]
+    max_tokens: u32,
+
+    /// Longest agent line kept; the rest is cut off with a marker, but
+    /// still scanned by the filter (bytes, 0 = unlimited)
+    #[arg(long, default_value = "65536")]
+    max_line_bytes: usize,
+
+    /// Answer every line on a TCP / Unix socket / named pipe connection
+    /// with its verdict, `{"id":N,"action":"SUSTAIN"}`
+    #[arg(long)]
+    ack: bool,
+
+    /// Kernel log format: `text`, or one JSON object per event (with
+    /// `decision_id` and `channel` fields)
+    #[arg(long, value_enum, default_value = "text")]
+    log_format: logging::LogFormat,
+
+    /// Append kernel logs to this file instead of stdout
+    #[arg(long)]
+    log_file: Option<PathBuf>,
+
+    /// Audit log file path
+    #[arg(long, default_value = "tripwired-audit.jsonl")]
+    audit_log: PathBuf,
+
+    /// Key file for HMAC-signing audit epilogue records
+    #[arg(long)]
+    audit_key_file: Option<PathBuf>,
+
+    /// Keep each LLM decision's full rendered prompt in the audit trail
+    /// (`rendered_prompt`), not just its hash
+    #[arg(long)]
+    audit_prompts: bool,
+
+    /// Write the audit trail to this second file as well (another disk, a
+    /// network share or a mounted bucket); see `backup`
+    #[arg(long)]
+    audit_backup: Option<PathBuf>,
+
+    /// How far each audit write is pushed to disk: buffered (flushed to the
+    /// OS), record (synced per record) or kill (synced after each KILL)
+    #[arg(long, value_enum, default_value = "buffered")]
+    audit_durability: audit::Durability,
+
+    /// What a FAIL verdict (an LLM answer with no readable verdict) does
+    /// unless a policy rule decides it: flag (audit for manual review,
+    /// target left running), kill or pause
+    #[arg(long, value_enum, default_value = "flag")]
+    on_uncertain: llm::OnUncertain,
+
+    /// Use TCP instead of Named Pipe (for compatibility)
+    #[arg(long)]
+    tcp: bool,
+
+    /// TCP port (only used with --tcp)
+    #[arg(long, default_value = "9999")]
+    port: u16,
+
+    /// Address the TCP listener binds to (--tcp and channel `port`s), IPv4
+    /// or IPv6, e.g. `::` for a container bridge network; anything but
+    /// loopback requires --agent-token-file
+    #[arg(long, default_value = "127.0.0.1")]
+    bind: IpAddr,
+
+    /// File holding the token TCP and vsock agents must open with, `HELLO
+    /// token=<token>`; connections without it are closed
+    #[arg(long)]
+    agent_token_file: Option<PathBuf>,
+
+    /// Unix socket agents connect to without --tcp (one per kernel when
+    /// several run on a host)
+    #[cfg(unix)]
+    #[arg(long, default_value = SOCKET_PATH)]
+    socket_path: String,
+
+    /// Named pipe agents connect to without --tcp, `\\.\pipe\<name>` (one
+    /// per kernel when several run on a host)
+    #[cfg(windows)]
+    #[arg(long, default_value = PIPE_NAME)]
+    pipe_name: String,
+
+    /// Follow this Docker container's logs instead of a socket; KILL / PAUSE
+    /// decisions kill / pause the container
+    #[arg(long, value_parser = docker::parse_name, conflicts_with = "tcp")]
+    watch_container: Option<String>,
+
+    /// Follow the logs of the pods matching this label selector; a KILL
+    /// deletes the pod
+    #[arg(long, conflicts_with_all = ["tcp", "watch_container"])]
+    watch_pods: Option<String>,
+
+    /// Namespace for --watch-pods (default: the service account's)
+    #[arg(long, requires = "watch_pods")]
+    pod_namespace: Option<String>,
+
+    /// Kubernetes API server URL (default: in-cluster; e.g.
+    /// http://127.0.0.1:8001 for `kubectl proxy`)
+    #[arg(long)]
+    kube_api: Option<String>,
+
+    /// Accept OpenTelemetry log exports (OTLP/HTTP, `POST /v1/logs`) on
+    /// this loopback port instead of a socket
+    #[arg(long, conflicts_with_all = ["tcp", "watch_container", "watch_pods"])]
+    otlp_logs_port: Option<u16>,
+
+    /// Serve the kill-switch as MCP tools (Streamable HTTP, `POST /mcp`)
+    /// on this loopback port instead of a socket
+    #[arg(long, conflicts_with_all = ["tcp", "watch_container", "watch_pods", "otlp_logs_port"])]
+    mcp_port: Option<u16>,
+
+    /// Accept agents in VM sandboxes over AF_VSOCK on this port instead of
+    /// a socket (Linux; the guest connects to CID 2)
+    #[cfg(target_os = "linux")]
+    #[arg(long, conflicts_with_all = ["tcp", "watch_container", "watch_pods", "otlp_logs_port", "mcp_port"])]
+    vsock_port: Option<u32>,
+
+    /// Consume this ETW provider's events instead of a Named Pipe (GUID)
+    #[cfg(windows)]
+    #[arg(long, value_parser = etw::parse_guid, conflicts_with_all = ["tcp", "watch_container", "watch_pods"])]
+    etw_provider: Option<etw::Guid>,
+
+    /// Most verbose ETW level delivered (1 critical .. 5 verbose)
+    #[cfg(windows)]
+    #[arg(long, default_value = "5", value_parser = clap::value_parser!(u8).range(1..=5))]
+    etw_level: u8,
+
+    /// ETW keyword mask, any bit matching (0 = every event)
+    #[cfg(windows)]
+    #[arg(long, default_value = "0", value_parser = etw::parse_keywords)]
+    etw_keywords: u64,
+
+    /// Filter config file (TOML) for custom patterns
+    #[arg(long, global = true)]
+    filter_config: Option<PathBuf>,
+
+    /// Directory of Sigma YAML rules to import as Custom-tier filter rules
+    #[arg(long, global = true)]
+    sigma_rules: Option<PathBuf>,
+
+    /// Directory of domain preset TOML files (`domain = "<file name>"`),
+    /// added to the built-in trading, devops and generic presets
+    #[arg(long, global = true)]
+    preset_dir: Option<PathBuf>,
+
+    /// Prompt template file with a {log} and optional {context} placeholder
+    /// (default: built-in trading prompt)
+    #[arg(long)]
+    prompt_file: Option<PathBuf>,
+
+    /// Prompt template file for tool calls, with a {log} placeholder
+    /// (default: built-in tool-call prompt)
+    #[arg(long)]
+    tool_prompt_file: Option<PathBuf>,
+
+    /// Fallback model tried when the one before it fails, as MODEL@URL
+    /// (repeatable; tried in order)
+    #[arg(long, value_parser = chain::parse_fallback)]
+    llm_fallback: Vec<(String, String)>,
+
+    /// Consecutive failures that open an endpoint's circuit breaker
+    #[arg(long, default_value = "3")]
+    llm_breaker_failures: u32,
+
+    /// How long an open circuit skips its endpoint (milliseconds)
+    #[arg(long, default_value = "30000")]
+    llm_breaker_cooldown_ms: u64,
+
+    /// Do not request JSON-schema constrained output (for servers that
+    /// silently ignore `response_format`)
+    #[arg(long)]
+    no_structured_output: bool,
+
+    /// Admin API port for /stats, /metrics, /decisions and /agents
+    /// (loopback only; disabled if unset)
+    #[arg(long)]
+    admin_port: Option<u16>,
+
+    /// File holding the bearer token the admin API's overrides (disarm,
+    /// arm, emergency kill, feedback) require; refused without it
+    #[arg(long)]
+    admin_token_file: Option<PathBuf>,
+
+    /// Port for the /healthz and /readyz probes (all interfaces, for the
+    /// kubelet; disabled if unset)
+    #[arg(long)]
+    probe_port: Option<u16>,
+
+    /// Exit non-zero instead of serving with kill actions disarmed after
+    /// the startup self-test and first canary probe
+    #[arg(long)]
+    require_armed: bool,
+
+    /// Max time to wait for in-flight analyses on shutdown (milliseconds)
+    #[arg(long, default_value = "5000")]
+    drain_timeout_ms: u64,
+
+    /// Learn for this long (e.g. 30m, 2h), then write suggested excludes and exit
+    #[arg(long, value_parser = learn::parse_duration)]
+    learn: Option<Duration>,
+
+    /// Where --learn writes its suggested filter config fragment
+    #[arg(long, default_value = "tripwired-learned.toml")]
+    learn_output: PathBuf,
+}
+
+/// Offline tools (the kernel runs when no subcommand is given)
+#[derive(Subcommand, Debug)]
+enum Cmd {
+    /// Measure filter, audit and verdict-parse throughput on this host and
+    /// estimate its capacity
+    Bench {
+        /// Synthetic corpus size
+        #[arg(long, default_value = "200000")]
+        lines: usize,
+
+        /// Share of suspicious lines in the synthetic corpus (percent)
+        #[arg(long, default_value = "5")]
+        suspicious_pct: u32,
+
+        /// Audit records written (to a scratch file) and verdicts parsed
+        #[arg(long, default_value = "20000")]
+        records: usize,
+
+        /// Audit durability measured (see --audit-durability)
+        #[arg(long, value_enum, default_value = "buffered")]
+        durability: audit::Durability,
+    },
+
+    /// Compare filter throughput with and without the keyword pre-screen
+    BenchFilter {
+        /// Log file to replay (default: synthetic corpus)
+        #[arg(long)]
+        input: Option<PathBuf>,
+
+        /// Synthetic corpus size
+        #[arg(long, default_value = "500000")]
+        lines: usize,
+
+        /// Share of suspicious lines in the synthetic corpus (percent)
+        #[arg(long, default_value = "5")]
+        suspicious_pct: u32,
+    },
+
+    /// Check log lines against expected match / no-match verdicts (exit 1 on failure)
+    TestFilter {
+        /// Filter config under test (default: --filter-config)
+        #[arg(long)]
+        config: Option<PathBuf>,
+
+        /// YAML list of cases: `line`, `expect` (match | no-match), optional `rule`
+        #[arg(long)]
+        cases: PathBuf,
+    },
+
+    /// Validate the filter config, its channel configs, presets and Sigma
+    /// imports without starting (exit 1 if invalid)
+    CheckConfig {
+        /// Filter config to check (default: --filter-config)
+        #[arg(long)]
+        config: Option<PathBuf>,
+    },
+
+    /// Rule set maintenance
+    Rules {
+        #[command(subcommand)]
+        command: RulesCmd,
+    },
+
+    /// Live dashboard of a running kernel (via its admin API)
+    Top {
+        /// Admin API port of the kernel to watch (its --admin-port)
+        #[arg(long)]
+        admin_port: u16,
+
+        /// Refresh interval (milliseconds)
+        #[arg(long, default_value = "500")]
+        interval_ms: u64,
+    },
+
+    /// Rehearse the kill path on a sacrificial process and report readiness
+    /// (exit 1 if not ready)
+    Drill {
+        /// Production target to check for signal permission (not signaled)
+        #[arg(long)]
+        target_pid: Option<u32>,
+
+        /// Sacrificial processes killed
+        #[arg(long, default_value = "3")]
+        runs: u32,
+
+        /// Slowest acceptable decision-to-exit latency (milliseconds)
+        #[arg(long, default_value = "1000")]
+        max_latency_ms: u64,
+
+        /// Audit log for the drill's own decisions
+        #[arg(long, default_value = "tripwired-drill.jsonl")]
+        audit_log: PathBuf,
+    },
+
+    /// Send a synthetic log mix to a running kernel and report end-to-end
+    /// latency and detection rate (the kernel needs --ack for both)
+    Loadgen {
+        /// Lines sent, e.g. 5000/s or 300/m
+        #[arg(long, value_parser = loadgen::parse_rate, default_value = "1000/s")]
+        rate: f64,
+
+        /// Domain of the benign lines and injected attacks
+        #[arg(long, value_enum, default_value = "trading")]
+        mix: loadgen::Mix,
+
+        /// Share of attack lines (percent)
+        #[arg(long, default_value = "1")]
+        attack_pct: f64,
+
+        /// How long to send (e.g. 30s, 5m)
+        #[arg(long, value_parser = learn::parse_duration, default_value = "10s")]
+        duration: Duration,
+
+        /// Connect over TCP instead of the Unix socket / named pipe
+        #[arg(long)]
+        tcp: bool,
+
+        /// Kernel TCP port (with --tcp)
+        #[arg(long, default_value = "9999")]
+        port: u16,
+
+        /// Kernel Unix socket (its --socket-path)
+        #[cfg(unix)]
+        #[arg(long, default_value = SOCKET_PATH)]
+        socket_path: String,
+
+        /// Kernel named pipe (its --pipe-name)
+        #[cfg(windows)]
+        #[arg(long, default_value = PIPE_NAME)]
+        pipe_name: String,
+
+        /// How long to wait for verdicts after the last line (milliseconds)
+        #[arg(long, default_value = "10000")]
+        drain_ms: u64,
+    },
+
+    /// Operator overrides on a running kernel: disarm, arm, emergency kill,
+    /// decision feedback
+    Ctl {
+        /// Admin API port of the kernel (its --admin-port)
+        #[arg(long)]
+        admin_port: u16,
+
+        /// Name recorded in the audit trail (default: $USER)
+        #[arg(long, global = true)]
+        operator: Option<String>,
+
+        /// Why, for the audit trail
+        #[arg(long, global = true)]
+        reason: Option<String>,
+
+        /// Bearer token file for the overrides (the kernel's
+        /// --admin-token-file)
+        #[arg(long, global = true)]
+        admin_token_file: Option<PathBuf>,
+
+        #[command(subcommand)]
+        command: ctl::CtlCmd,
+    },
+
+    /// Replay an audit file against a proposed filter config and list the
+    /// decisions that would change (the current config is --filter-config)
+    WhatIf {
+        /// Proposed filter config
+        #[arg(long)]
+        config: PathBuf,
+        /// Audit JSONL file to replay
+        #[arg(long)]
+        audit: PathBuf,
+        /// Re-analyze escalations with this LLM endpoint instead of reusing
+        /// the recorded verdicts
+        #[arg(long)]
+        llm_url: Option<String>,
+        /// Model asked with --llm-url
+        #[arg(long, default_value = "llama-3.2-3b-instruct")]
+        model: String,
+        /// Prompt template asked with --llm-url (default: built-in)
+        #[arg(long)]
+        prompt_file: Option<PathBuf>,
+        /// Changes listed
+        #[arg(long, default_value = "50")]
+        limit: usize,
+        /// Print the result as JSON
+        #[arg(long)]
+        json: bool,
+        /// Exit 1 if any decision would change
+        #[arg(long)]
+        fail_on_change: bool,
+    },
+
+    /// Offline reports on an audit file
+    Audit {
+        #[command(subcommand)]
+        command: AuditCmd,
+    },
+
+    /// Export the audited verdicts as training examples (normalized line,
+    /// action, confidence, model)
+    Dataset {
+        /// Audit JSONL file, or a directory of them
+        #[arg(long)]
+        from: PathBuf,
+
+        /// Output format
+        #[arg(long, value_enum, default_value = "jsonl")]
+        format: dataset::Format,
+
+        /// JSONL label overrides: `{"decision_id": N, "action": "SUSTAIN"}`
+        #[arg(long)]
+        corrections: Option<PathBuf>,
+
+        /// Output file (default: standard output)
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+
+    /// Pack everything about one decision (record, neighbors, prompt and
+    /// response, linked events, rule set, snapshot) into a .tar.gz
+    Incident {
+        /// Decision ID (`id` in the audit trail)
+        decision_id: u64,
+
+        /// Audit JSONL file or directory of them (default: --audit-log)
+        #[arg(long)]
+        audit: Option<PathBuf>,
+
+        /// Decision records included on each side of the decision
+        #[arg(long, default_value = "10")]
+        context: usize,
+
+        /// Leave out the `[snapshot]` files of the decision
+        #[arg(long)]
+        no_snapshot: bool,
+
+        /// Archive written (default: incident-<id>.tar.gz)
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+
+    /// Train the `[classifier]` pre-screen on past LLM verdicts
+    Classifier {
+        #[command(subcommand)]
+        command: ClassifierCmd,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ClassifierCmd {
+    /// Fit a model to the KILL / SUSTAIN verdicts in audit files and report
+    /// how much LLM traffic it would save
+    Train {
+        /// Audit JSONL files, directories of them or `dataset` exports
+        #[arg(required = true)]
+        from: Vec<PathBuf>,
+
+        /// Model file written (`model` in `[classifier]`)
+        #[arg(long)]
+        out: PathBuf,
+
+        /// Passes over the training examples
+        #[arg(long, default_value = "10")]
+        epochs: usize,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum RulesCmd {
+    /// Check patterns for footguns (exit 1 on errors)
+    Lint,
+}
+
+#[derive(Subcommand, Debug)]
+enum AuditCmd {
+    /// Decision statistics and chain verification (exit 1 if verification
+    /// fails)
+    Stats {
+        /// Audit JSONL file
+        file: PathBuf,
+
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+
+        /// Templates listed
+        #[arg(long, default_value = "10")]
+        top: usize,
+
+        /// Key the footers were signed with (the kernel's --audit-key-file)
+        #[arg(long)]
+        key_file: Option<PathBuf>,
+    },
+
+    /// Print the file with `[encrypt]`-ed records decrypted
+    Decrypt {
+        /// Audit JSONL file
+        file: PathBuf,
+
+        /// age identity file (`age-keygen` output)
+        #[arg(long)]
+        identity: PathBuf,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct KernelConfig {
+    pub llm_url: String,
+    pub model: String,
+    pub max_tokens: u32,
+    /// Agent lines are truncated beyond this (0 = unlimited)
+    pub max_line_bytes: usize,
+    /// Write each line's verdict back to the agent
+    pub ack: bool,
+    /// What an unreadable LLM verdict does
+    pub on_uncertain: llm::OnUncertain,
+    /// SHA-256 of the token TCP agents authenticate with (`HELLO token=`)
+    pub agent_token: Option<String>,
+    /// SHA-256 of the bearer token admin API overrides need
+    pub admin_token: Option<String>,
+    pub target_pid: Option<u32>,
+    /// Docker container killed / paused alongside the target PID
+    pub target_container: Option<String>,
+    /// VM powered off / suspended alongside the target PID
+    pub target_vm: Option<vm::VmTarget>,
+    /// Judgment stages by name (`None` = the built-in chain, see `stage`)
+    pub stages: Option<Vec<String>>,
+}
+
+/// Shared state handed to every connection
+struct Kernel {
+    config: KernelConfig,
+    /// Primary model and fallbacks
+    llm: Arc<chain::LlmChain>,
+    /// Batches analyses when the LLM falls behind
+    batcher: batch::Batcher,
+    audit_trail: Arc<AuditTrail>,
+    stats: Mutex<Stats>,
+    /// What every line goes through (see `stage`)
+    stages: stage::Chain,
+    filter: filter::Filter,
+    correlator: correlate::Correlator,
+    rates: rate::RateMonitor,
+    limits: limit::LimitMonitor,
+    /// Per-agent input rate limit
+    flow: flow::FlowConfig,
+    /// Applied to everything audited or sent to the LLM
+    redactor: redact::Redactor,
+    /// Context window sizes for per-agent history
+    prompt_config: llm::PromptConfig,
+    /// Canary probe settings and degraded-mode policy
+    health_config: health::HealthConfig,
+    /// LLM health and kill arming
+    health: health::Health,
+    /// Connected agents (admin API)
+    agents: agents::Registry,
+    /// Kill cooldown and hourly cap
+    valve: valve::KillValve,
+    /// Line-to-verdict deadline for LLM analyses
+    slo: slo::SloConfig,
+    /// LLM timeout for high-lane lines
+    priority: priority::PriorityConfig,
+    /// Pre-execution approval of `CHECK` requests
+    guard: guard::GuardConfig,
+    /// Continuation lines assembled into one record
+    multiline: multiline::MultilineConfig,
+    /// Rules applied between a verdict and its action
+    policy: policy::Policy,
+    /// Time-based behavior profiles
+    schedule: schedule::Schedule,
+    /// Active/standby pairing, shared by every pipeline
+    ha: Option<Arc<ha::Ha>>,
+    /// Lines awaiting a retrospective verdict (`[backfill]`)
+    spill: Option<backfill::Spill>,
+    /// Lines sent to the LLM, persisted until judged (`[queue]`)
+    journal: Option<queue::Journal>,
+    /// Sends audit checkpoints to external witnesses (`[checkpoint]`)
+    anchorer: Option<checkpoint::Anchorer>,
+    /// Present in `--learn` mode
+    learner: Option<learn::Learner>,
+    /// Decides confident lines before the LLM (`[classifier]`)
+    classifier: Option<classify::Classifier>,
+    /// Follow-up LLM justification of KILLs (`[explain]`)
+    explainer: Option<explain::Explainer>,
+    /// Holds KILLs for an external countersignature (`[authorizer]`)
+    authorizer: Option<authorize::Authorizer>,
+    /// Checkpoints KILL targets before killing them (`[snapshot]`)
+    snapshots: Option<Arc<snapshot::Snapshots>>,
+    /// Tightens the target's capabilities on RESTRICT (`[restrict]`)
+    restrictor: Option<restrict::Restrictor>,
+    /// Second analyzer evaluated beside the live one (`[shadow]`)
+    shadow: Option<shadow::Shadow>,
+    /// Cancelled when a shutdown signal arrives
+    shutdown: CancellationToken,
+    /// In-flight connection tasks (drained on shutdown)
+    tracker: TaskTracker,
+    /// The endpoint is accepting agents (`/readyz`)
+    serving: AtomicBool,
+}
+
+impl Kernel {
+    /// Name of the active schedule profile
+    fn profile(&self) -> Option<&str> {
+        self.schedule.active().map(|p| p.name.as_str())
+    }
+
+    /// Mark a persisted line judged
+    fn dequeue(&self, ticket: Option<queue::Ticket>) {
+        if let (Some(journal), Some(ticket)) = (&self.journal, ticket) {
+            if let Err(e) = journal.done(ticket) {
+                warn!("📥 Failed to update the persisted queue: {}", e);
+            }
+        }
+    }
+
+    /// Counters plus per-rule match counts
+    async fn snapshot(&self) -> StatsSnapshot {
+        let profile = self.schedule.active();
+        let snapshot = StatsSnapshot::new(&*self.stats.lock().await, &self.filter)
+            .with_health(self.health.healthy(), self.health.armed())
+            .with_hold(self.health.held_until())
+            .with_profile(
+                profile.map(|p| p.name.as_str()),
+                profile.is_some_and(|p| p.dry_run),
+            )
+            .with_llm_pending(self.batcher.pending())
+            .with_shadow(self.shadow.as_ref().map(|s| s.stats()))
+            .with_errors(self.audit_trail.errors())
+            .with_agents(self.agents.snapshot())
+            .with_audit_backup(self.audit_trail.backup_status());
+        match self.ha {
+            Some(ref ha) => snapshot.with_ha(ha.instance(), ha.role()),
+            None => snapshot,
+        }
+    }
+}
+
+/// Listener handed over by the service manager (systemd socket activation)
+#[cfg(unix)]
+type Activated = Option<std::os::unix::net::UnixListener>;
+#[cfg(not(unix))]
+type Activated = Option<std::convert::Infallible>;
+
+/// Entry point of the `tripwired` binary
+pub fn main() -> Result<(), KernelError> {
+    // Socket activation is read (and its variables cleared) while the process
+    // is still single-threaded: mutating the environment races with any
+    // runtime thread reading it
+    #[cfg(target_os = "linux")]
+    let activated = systemd::take_listener().map_err(|e| format!("Socket activation: {}", e))?;
+    #[cfg(not(target_os = "linux"))]
+    let activated: Activated = None;
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?
+        .block_on(run(activated))
+}
+
+async fn run(activated: Activated) -> Result<(), KernelError> {
+    let args = Args::parse();
+    logging::init(args.log_format, args.log_file.as_deref())
+        .map_err(|e| format!("Cannot open log file: {}", e))?;
+
+    if let Some(Cmd::Top {
+        admin_port,
+        interval_ms,
+    }) = args.command
+    {
+        return top::run(admin_port, Duration::from_millis(interval_ms)).await;
+    }
+    if let Some(Cmd::Ctl {
+        admin_port,
+        operator,
+        reason,
+        admin_token_file,
+        command,
+    }) = args.command
+    {
+        let token = admin_token_file
+            .as_deref()
+            .map(ctl::read_token)
+            .transpose()?;
+        return ctl::run(admin_port, operator, reason, token, command).await;
+    }
+    if let Some(Cmd::Loadgen {
+        rate,
+        mix,
+        attack_pct,
+        duration,
+        tcp,
+        port,
+        #[cfg(unix)]
+        ref socket_path,
+        #[cfg(windows)]
+        ref pipe_name,
+        drain_ms,
+    }) = args.command
+    {
+        #[cfg(unix)]
+        let local_endpoint = socket_path.clone();
+        #[cfg(windows)]
+        let local_endpoint = pipe_path(pipe_name);
+        let options = loadgen::LoadgenOptions {
+            rate,
+            mix,
+            attack_pct,
+            duration,
+            tcp_port: tcp.then_some(port),
+            local_endpoint,
+            drain: Duration::from_millis(drain_ms),
+        };
+        return loadgen::run(&options).await;
+    }
+    if let Some(Cmd::Drill {
+        target_pid,
+        runs,
+        max_latency_ms,
+        ref audit_log,
+    }) = args.command
+    {
+        let options = drill::DrillOptions {
+            runs,
+            max_latency_ms,
+            audit_log: audit_log.clone(),
+            target_pid,
+        };
+        return drill::run(&options).await;
+    }
+    if let Some(Cmd::Audit {
+        command:
+            AuditCmd::Stats {
+                ref file,
+                json,
+                top,
+                ref key_file,
+            },
+    }) = args.command
+    {
+        let options = report::StatsOptions {
+            file: file.clone(),
+            json,
+            top,
+            key_file: key_file.clone(),
+        };
+        return report::run(&options);
+    }
+    if let Some(Cmd::Audit {
+        command: AuditCmd::Decrypt {
+            ref file,
+            ref identity,
+        },
+    }) = args.command
+    {
+        return encrypt::decrypt_file(file, identity);
+    }
+    if let Some(Cmd::Classifier {
+        command:
+            ClassifierCmd::Train {
+                ref from,
+                ref out,
+                epochs,
+            },
+    }) = args.command
+    {
+        return classify::train(from, out, epochs);
+    }
+    if let Some(Cmd::Dataset {
+        ref from,
+        format,
+        ref corrections,
+        ref out,
+    }) = args.command
+    {
+        let options = dataset::DatasetOptions {
+            from: from.clone(),
+            format,
+            corrections: corrections.clone(),
+            out: out.clone(),
+        };
+        return dataset::run(&options);
+    }
+
+    if let Some(Cmd::CheckConfig { ref config }) = args.command {
+        let Some(path) = config.as_deref().or(args.filter_config.as_deref()) else {
+            return Err("check-config needs --config or --filter-config".into());
+        };
+        return check::run(
+            path,
+            args.sigma_rules.as_deref(),
+            args.preset_dir.as_deref(),
+        );
+    }
+
+    // Load filter config (or use defaults)
+    let filter_config_path = match args.command {
+        Some(Cmd::TestFilter {
+            config: Some(ref path),
+            ..
+        }) => Some(path.as_path()),
+        _ => args.filter_config.as_deref(),
+    };
+    let filter_config = load_filter_config(
+        filter_config_path,
+        args.sigma_rules.as_deref(),
+        args.preset_dir.as_deref(),
+    );
+
+    if let Some(Cmd::WhatIf {
+        ref config,
+        ref audit,
+        ref llm_url,
+        ref model,
+        ref prompt_file,
+        limit,
+        json,
+        fail_on_change,
+    }) = args.command
+    {
+        let proposed = load_filter_config(
+            Some(config),
+            args.sigma_rules.as_deref(),
+            args.preset_dir.as_deref(),
+        );
+        let options = whatif::WhatIfOptions {
+            config: config.clone(),
+            audit: audit.clone(),
+            llm_url: llm_url.clone(),
+            model: model.clone(),
+            max_tokens: args.max_tokens,
+            prompt_file: prompt_file.clone(),
+            json,
+            limit,
+            fail_on_change,
+        };
+        return whatif::run(&options, &filter_config, &proposed).await;
+    }
+    if let Some(Cmd::Incident {
+        decision_id,
+        ref audit,
+        context,
+        no_snapshot,
+        ref out,
+    }) = args.command
+    {
+        let options = incident::IncidentOptions {
+            decision_id,
+            audit: audit.clone().unwrap_or_else(|| args.audit_log.clone()),
+            context,
+            snapshot: !no_snapshot,
+            out: out.clone(),
+            filter_config: args.filter_config.clone(),
+        };
+        return incident::run(&options, &filter_config);
+    }
+    if let Some(cmd) = args.command {
+        return run_command(cmd, &filter_config);
+    }
+
+    let prompt = match args.prompt_file {
+        Some(ref path) => match llm::load_prompt(path) {
+            Ok(template) => template,
+            Err(e) => {
+                error!("Failed to load prompt {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        },
+        None => llm::DEFAULT_PROMPT.to_string(),
+    };
+    let tool_prompt = match args.tool_prompt_file {
+        Some(ref path) => match llm::load_prompt(path) {
+            Ok(template) => template,
+            Err(e) => {
+                error!("Failed to load tool prompt {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        },
+        None => llm::TOOL_PROMPT.to_string(),
+    };
+    let prompts = Prompts {
+        log: prompt,
+        tool: tool_prompt,
+    };
+
+    // HA pairing is process-wide: taken from the main filter config only
+    let ha = filter_config.ha.as_ref().map(|c| match ha::Ha::new(c) {
+        Ok(ha) => Arc::new(ha),
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    });
+    let watchdog = watchdog::Watchdog::new(&filter_config.watchdog, &filter_config.notify);
+    let watchdog_config = filter_config.watchdog.clone();
+
+    let agent_token = args.agent_token_file.as_deref().map(load_agent_token);
+    let specs = if filter_config.channel.is_empty() {
+        vec![flag_spec(&args, filter_config, agent_token.as_deref())]
+    } else {
+        if args.learn.is_some() {
+            error!("--learn is not supported with [channel] pipelines");
+            std::process::exit(1);
+        }
+        if args.target_pid.is_some() {
+            warn!("  --target-pid is ignored: each [channel] names its own target_pid");
+        }
+        if args.target_vm.is_some() {
+            warn!("  --target-vm is ignored: each [channel] names its own target_vm");
+        }
+        channel_specs(
+            &args,
+            filter_config_path,
+            filter_config,
+            agent_token.as_deref(),
+        )
+    };
+
+    info!("═══════════════════════════════════════════════════════════════");
+    info!("  TRIPWIRED KERNEL v0.1.7 — Rust Execution Engine");
+    info!("═══════════════════════════════════════════════════════════════");
+    if specs.len() > 1 || specs[0].name.is_some() {
+        info!("  Channels: {}", specs.len());
+    }
+    if let Some(ref ha) = ha {
+        info!("  HA instance: {}", ha.instance());
+    }
+
+    // Every pipeline shares one shutdown and one LLM connection pool
+    let shutdown = CancellationToken::new();
+    let http = llm::http_client();
+    let mut pipelines = Vec::with_capacity(specs.len());
+    for spec in specs {
+        let pipeline = start_pipeline(
+            &args,
+            &prompts,
+            &http,
+            &shutdown,
+            ha.as_ref(),
+            &watchdog_config,
+            spec,
+        );
+        pipelines.push(pipeline.await);
+    }
+
+    // systemd WatchdogSec= keepalive (pinged from the runtime, so a wedged
+    // event loop stops the pings and systemd restarts us)
+    #[cfg(target_os = "linux")]
+    if let Some(interval) = systemd::watchdog_interval() {
+        info!("  systemd watchdog: every {}ms", interval.as_millis());
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let _ = systemd::notify_watchdog();
+            }
+        });
+    }
+
+    // Self-watchdog: a wedged kernel must not pass for a protecting one
+    // (panics count for /healthz even without it)
+    watchdog::install_panic_hook();
+    if watchdog_config.stall_ms == 0 {
+        warn!("  Self-watchdog disabled: a stalled kernel goes unnoticed");
+    } else {
+        let actions: Vec<_> = watchdog_config.action.iter().map(|a| a.as_str()).collect();
+        info!(
+            "  Self-watchdog: {}ms stall -> {}",
+            watchdog_config.stall_ms,
+            actions.join(", ")
+        );
+        let mut watchdog = watchdog;
+        for pipeline in &pipelines {
+            let audit = &pipeline.kernel.audit_trail;
+            watchdog =
+                watchdog.with_audit_writer(&audit.path().display().to_string(), audit.busy_since());
+            if let Some(pid) = pipeline.kernel.config.target_pid {
+                watchdog = watchdog.with_target(pid);
+            }
+        }
+        watchdog.start(shutdown.clone());
+    }
+
+    // Kubernetes probes over every pipeline
+    if let Some(port) = args.probe_port {
+        let mut probes = probe::Probes::new(watchdog_config.stall_ms, watchdog_config.max_panics);
+        for pipeline in &pipelines {
+            probes = probes.with_pipeline(pipeline.name.as_deref(), Arc::clone(&pipeline.kernel));
+        }
+        tokio::spawn(async move {
+            if let Err(e) = probe::serve(port, probes).await {
+                error!("Probe endpoint failed: {}", e);
+            }
+        });
+    }
+
+    // Signal handler: first signal starts the drain
+    let reason = Arc::new(std::sync::Mutex::new(String::from("server exit")));
+    {
+        let shutdown = shutdown.clone();
+        let reason = Arc::clone(&reason);
+        tokio::spawn(async move {
+            let signal = shutdown_signal().await;
+            warn!("🛑 {} received - shutting down", signal);
+            *reason.lock().unwrap() = signal.to_string();
+            shutdown.cancel();
+        });
+    }
+
+    // Learn mode: a finished learn period shuts down like a signal
+    if let Some(period) = args.learn {
+        info!(
+            "  🎓 Learn mode: {}s -> {}",
+            period.as_secs(),
+            args.learn_output.display()
+        );
+        let shutdown = shutdown.clone();
+        let reason = Arc::clone(&reason);
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = tokio::time::sleep(period) => {
+                    info!("🎓 Learn period elapsed - shutting down");
+                    *reason.lock().unwrap() = "learn complete".to_string();
+                    shutdown.cancel();
+                }
+                _ = shutdown.cancelled() => {}
+            }
+        });
+    }
+    info!("═══════════════════════════════════════════════════════════════");
+
+    // HA: only the leader listens; the standby waits for the peer to fail
+    if let Some(ref ha) = ha {
+        {
+            let shutdown = shutdown.clone();
+            let heartbeat = Arc::clone(ha);
+            tokio::spawn(async move {
+                if let Err(e) = heartbeat.run(shutdown.clone()).await {
+                    error!("HA heartbeat failed: {}", e);
+                    shutdown.cancel();
+                }
+            });
+        }
+        if !ha.is_leader() {
+            info!("👥 Standby: waiting for leadership");
+            #[cfg(target_os = "linux")]
+            let _ = systemd::notify_ready("Standby: waiting for leadership");
+        }
+        tokio::select! {
+            _ = ha.leader() => {}
+            _ = shutdown.cancelled() => {}
+        }
+    }
+
+    // Serve every pipeline; an endpoint that fails shuts the others down
+    // (systemd socket activation only applies to a single pipeline)
+    let mut activated = activated;
+    if pipelines.len() > 1 && activated.take().is_some() {
+        warn!("Socket activation ignored: more than one pipeline");
+    }
+    let mut servers = tokio::task::JoinSet::new();
+    for pipeline in &pipelines {
+        let endpoint = pipeline.endpoint.clone();
+        let kernel = Arc::clone(&pipeline.kernel);
+        let shutdown = shutdown.clone();
+        let activated = activated.take();
+        servers.spawn(
+            async move {
+                let result = serve(endpoint, Arc::clone(&kernel), activated).await;
+                kernel.serving.store(false, Ordering::Relaxed);
+                if result.is_err() {
+                    shutdown.cancel();
+                }
+                result
+            }
+            .instrument(pipeline.span.clone()),
+        );
+    }
+    let mut result: Result<(), KernelError> = Ok(());
+    while let Some(joined) = servers.join_next().await {
+        match joined {
+            Ok(Err(e)) if result.is_ok() => result = Err(e),
+            Err(e) if result.is_ok() => result = Err(e.into()),
+            _ => {}
+        }
+    }
+    // The footer names what brought the endpoint down
+    if let Err(ref e) = result {
+        let mut reason = reason.lock().unwrap();
+        if *reason == "server exit" {
+            *reason = format!("server error ({})", e.category());
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    let _ = systemd::notify_stopping();
+
+    // Drain in-flight analyses, then write the audit epilogues
+    for pipeline in &pipelines {
+        pipeline.kernel.tracker.close();
+    }
+    let drain_timeout = Duration::from_millis(args.drain_timeout_ms);
+    let deadline = tokio::time::Instant::now() + drain_timeout;
+    let reason = reason.lock().unwrap().clone();
+    let mut totals = Stats::default();
+    for pipeline in pipelines {
+        let span = pipeline.span.clone();
+        let kernel = pipeline
+            .finish(deadline, drain_timeout, &reason)
+            .instrument(span)
+            .await;
+        let stats = kernel.stats.lock().await;
+        totals.filtered += stats.filtered;
+        totals.analyzed += stats.analyzed;
+        totals.kills += stats.kills;
+
+        if let (Some(learner), Some(period)) = (&kernel.learner, args.learn) {
+            let suggestions = learner.suggestions();
+            match std::fs::write(&args.learn_output, learn::render_toml(&suggestions, period)) {
+                Ok(()) => info!(
+                    "🎓 {} exclude suggestion(s) written to {}",
+                    suggestions.len(),
+                    args.learn_output.display()
+                ),
+                Err(e) => error!("Failed to write learn output: {}", e),
+            }
+        }
+    }
+
+    info!(
+        "👋 Shutdown complete (filtered: {}, analyzed: {}, kills: {})",
+        totals.filtered, totals.analyzed, totals.kills
+    );
+
+    result
+}
+
+/// Load a filter config (defaults without a path) and merge `--sigma-rules`;
+/// exits on error
+fn load_filter_config(
+    path: Option<&Path>,
+    sigma_rules: Option<&Path>,
+    preset_dir: Option<&Path>,
+) -> filter::FilterConfig {
+    let presets = match preset_dir {
+        Some(dir) => match preset::load_dir(dir) {
+            Ok(presets) => {
+                info!("  Domain presets: {} from {}", presets.len(), dir.display());
+                presets
+            }
+            Err(e) => {
+                error!("Failed to load presets from {}: {}", dir.display(), e);
+                std::process::exit(1);
+            }
+        },
+        None => Vec::new(),
+    };
+    let mut filter_config = if let Some(path) = path {
+        match filter::FilterConfig::load(path, presets) {
+            Ok(cfg) => {
+                info!("  Filter config: {}", path.display());
+                if cfg.unsafe_exclude_essential {
+                    warn!("⚠️  unsafe_exclude_essential: excludes also suppress Essential rules");
+                }
+                cfg
+            }
+            Err(e) => {
+                error!("Failed to load filter config: {}", e);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        filter::FilterConfig {
+            presets,
+            ..Default::default()
+        }
+    };
+
+    if let Some(dir) = sigma_rules {
+        let import = match sigma::load_dir(dir) {
+            Ok(import) => import,
+            Err(e) => {
+                error!("Failed to load Sigma rules from {}: {}", dir.display(), e);
+                std::process::exit(1);
+            }
+        };
+        info!(
+            "  Sigma rules: {} imported, {} skipped",
+            import.rules.len(),
+            import.skipped.len()
+        );
+        for (path, reason) in &import.skipped {
+            debug!("  Sigma skipped {}: {}", path.display(), reason);
+        }
+        filter_config.rule.extend(import.rules);
+        if let Err(e) = filter_config.validate() {
+            error!("Sigma rules conflict with filter config: {}", e);
+            std::process::exit(1);
+        }
+    }
+    filter_config
+}
+
+/// Where a pipeline accepts agent connections
+#[derive(Clone, Debug)]
+enum Endpoint {
+    /// TCP listener (loopback unless `--bind`)
+    Tcp(SocketAddr),
+    /// Unix socket path (Windows: named pipe name)
+    Local(String),
+    /// Docker container whose logs are followed (one connection per run)
+    Container(String),
+    /// Pods whose logs are followed (one connection per container run)
+    Pods(kube::PodWatch),
+    /// OTLP/HTTP logs receiver port (one connection per service)
+    Otlp(u16),
+    /// MCP server port (one connection per session)
+    Mcp(u16),
+    /// AF_VSOCK port (agents in VMs, one connection per guest connection)
+    #[cfg(target_os = "linux")]
+    Vsock(u32),
+    /// Real-time ETW session (one agent connection)
+    #[cfg(windows)]
+    Etw(etw::EtwSource),
+}
+
+impl Endpoint {
+    /// What the audit header records as the endpoint, so trails from two
+    /// kernels on one host can be told apart
+    fn identity(&self) -> String {
+        match self {
+            Endpoint::Tcp(addr) => format!("tcp:{}", addr),
+            #[cfg(windows)]
+            Endpoint::Local(name) => format!("pipe:{}", name),
+            #[cfg(unix)]
+            Endpoint::Local(path) => format!("unix:{}", path),
+            Endpoint::Container(name) => format!("docker:{}", name),
+            Endpoint::Pods(watch) => match watch.namespace {
+                Some(ref namespace) => format!("pods:{}/{}", namespace, watch.selector),
+                None => format!("pods:{}", watch.selector),
+            },
+            Endpoint::Otlp(port) => format!("otlp:127.0.0.1:{}", port),
+            Endpoint::Mcp(port) => format!("mcp:127.0.0.1:{}", port),
+            #[cfg(target_os = "linux")]
+            Endpoint::Vsock(port) => format!("vsock:{}", port),
+            #[cfg(windows)]
+            Endpoint::Etw(source) => format!("etw:{}", source.provider),
+        }
+    }
+
+    /// `--agent-token-file`'s digest, for the endpoints whose agents can
+    /// send a `HELLO` (TCP, vsock; local sockets have file permissions)
+    fn agent_token(&self, digest: Option<&str>) -> Option<String> {
+        match self {
+            Endpoint::Tcp(_) => digest.map(str::to_string),
+            #[cfg(target_os = "linux")]
+            Endpoint::Vsock(_) => digest.map(str::to_string),
+            _ => None,
+        }
+    }
+
+    /// Refuse a TCP listener reachable beyond this host that lets agents in
+    /// without `--agent-token-file`
+    fn check_exposure(&self, agent_token: Option<&str>) -> Result<(), String> {
+        match self {
+            Endpoint::Tcp(addr) if !addr.ip().is_loopback() && agent_token.is_none() => {
+                Err(format!(
+                    "TCP on {} is reachable beyond this host: set --agent-token-file",
+                    addr
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Prompt templates shared by every pipeline
+struct Prompts {
+    /// `--prompt-file`
+    log: String,
+    /// `--tool-prompt-file`
+    tool: String,
+}
+
+/// Everything one pipeline is built from
+struct PipelineSpec {
+    /// Channel name (`None` for the single pipeline configured by flags)
+    name: Option<String>,
+    config: KernelConfig,
+    filter_config: filter::FilterConfig,
+    endpoint: Endpoint,
+    audit_log: PathBuf,
+    admin_port: Option<u16>,
+}
+
+/// The single pipeline configured by flags
+fn flag_spec(
+    args: &Args,
+    filter_config: filter::FilterConfig,
+    agent_token: Option<&str>,
+) -> PipelineSpec {
+    let endpoint = default_endpoint(args);
+    PipelineSpec {
+        name: None,
+        config: KernelConfig {
+            llm_url: args.llm_url.clone(),
+            model: args.model.clone(),
+            max_tokens: args.max_tokens,
+            max_line_bytes: args.max_line_bytes,
+            ack: args.ack,
+            on_uncertain: args.on_uncertain,
+            agent_token: endpoint.agent_token(agent_token),
+            admin_token: args.admin_token_file.as_deref().map(load_admin_token),
+            target_pid: args.target_pid,
+            target_container: args.watch_container.clone(),
+            target_vm: args.target_vm.clone(),
+            stages: None,
+        },
+        endpoint,
+        audit_log: args.audit_log.clone(),
+        admin_port: args.admin_port,
+        filter_config,
+    }
+}
+
+/// One pipeline per `[channel.<name>]` table
+fn channel_specs(
+    args: &Args,
+    path: Option<&Path>,
+    filter_config: filter::FilterConfig,
+    agent_token: Option<&str>,
+) -> Vec<PipelineSpec> {
+    // Channel filter configs are relative to the main one
+    let base = path.and_then(Path::parent).unwrap_or(Path::new(""));
+    let admin_token = args.admin_token_file.as_deref().map(load_admin_token);
+    let mut shared = filter_config.clone();
+    shared.channel.clear();
+
+    let mut specs = Vec::with_capacity(filter_config.channel.len());
+    for (name, channel) in filter_config.channel {
+        let channel_config = match channel.filter_config {
+            Some(ref path) => {
+                let config = load_filter_config(
+                    Some(&base.join(path)),
+                    args.sigma_rules.as_deref(),
+                    args.preset_dir.as_deref(),
+                );
+                if !config.channel.is_empty() {
+                    error!(
+                        "Channel '{}': {} defines channels of its own",
+                        name,
+                        path.display()
+                    );
+                    std::process::exit(1);
+                }
+                config
+            }
+            None => {
+                let mut config = shared.clone();
+                if let Some(ref path) = config.backfill.spill_file {
+                    config.backfill.spill_file = Some(backfill::channel_path(path, &name));
+                }
+                if let Some(ref dir) = config.queue.dir {
+                    config.queue.dir = Some(backfill::channel_path(dir, &name));
+                }
+                config
+            }
+        };
+        let endpoint = match (
+            channel.port,
+            channel.socket.clone(),
+            channel.container.clone(),
+            channel.pods.clone(),
+        ) {
+            (Some(port), _, _, _) => Endpoint::Tcp(SocketAddr::new(args.bind, port)),
+            (None, Some(socket), _, _) => Endpoint::Local(socket),
+            (None, None, Some(container), _) => Endpoint::Container(container),
+            (None, None, None, selector) => Endpoint::Pods(kube::PodWatch {
+                selector: selector.expect("validated channel endpoint"),
+                namespace: channel.namespace.clone(),
+                api: args.kube_api.clone(),
+            }),
+        };
+        specs.push(PipelineSpec {
+            config: KernelConfig {
+                llm_url: channel
+                    .llm_url
+                    .clone()
+                    .unwrap_or_else(|| args.llm_url.clone()),
+                model: channel.model.clone().unwrap_or_else(|| args.model.clone()),
+                max_tokens: args.max_tokens,
+                max_line_bytes: args.max_line_bytes,
+                ack: args.ack,
+                on_uncertain: args.on_uncertain,
+                agent_token: endpoint.agent_token(agent_token),
+                admin_token: admin_token.clone(),
+                target_pid: channel.target_pid,
+                target_container: channel.container.clone(),
+                target_vm: channel
+                    .target_vm
+                    .as_deref()
+                    .map(|spec| vm::parse_target(spec).expect("validated channel target_vm")),
+                stages: channel.stages.clone(),
+            },
+            filter_config: channel_config,
+            endpoint,
+            audit_log: channel.audit_log(&name),
+            admin_port: channel.admin_port,
+            name: Some(name),
+        });
+    }
+    specs
+}
+
+/// A running pipeline
+struct Pipeline {
+    /// Channel name (`None` for the single pipeline configured by flags)
+    name: Option<String>,
+    /// `channel{name=...}` for channels, so their logs can be told apart
+    span: tracing::Span,
+    kernel: Arc<Kernel>,
+    endpoint: Endpoint,
+    notifier: notify::Notifier,
+    /// Stops the notification forwarder and decision outputs
+    notify_stop: CancellationToken,
+    forwarder: tokio::task::JoinHandle<()>,
+    shippers: Vec<tokio::task::JoinHandle<()>>,
+}
+
+/// Build a pipeline's kernel, run its first canary probe and start its
+/// background tasks (health probe, admin API, notifications)
+async fn start_pipeline(
+    args: &Args,
+    prompts: &Prompts,
+    http: &reqwest::Client,
+    shutdown: &CancellationToken,
+    ha: Option<&Arc<ha::Ha>>,
+    watchdog_config: &watchdog::WatchdogConfig,
+    spec: PipelineSpec,
+) -> Pipeline {
+    let span = match spec.name {
+        Some(ref name) => tracing::info_span!("channel", channel = %name),
+        None => tracing::Span::none(),
+    };
+    let (kernel, notifier) =
+        span.in_scope(|| build_kernel(args, prompts, http, shutdown, ha, &spec));
+
+    async move {
+        // Canary probe: first result before serving, then periodically
+        if kernel.health_config.interval_ms == 0 {
+            warn!("  LLM health probe disabled: kill actions armed without a canary");
+        } else {
+            if !kernel.health.armed() {
+                info!("  Kill actions disarmed until the LLM canary probe passes");
+            }
+            health::check(&kernel.llm, &kernel.health_config, &kernel.health).await;
+            let kernel = Arc::clone(&kernel);
+            tokio::spawn(
+                async move {
+                    // Lines spilled by a previous run can be backfilled right away
+                    if kernel.health.healthy() {
+                        backfill(&kernel).await;
+                    }
+                    let period = Duration::from_millis(kernel.health_config.interval_ms);
+                    let mut ticker =
+                        tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+                    loop {
+                        tokio::select! {
+                            _ = ticker.tick() => {
+                                if health::check(&kernel.llm, &kernel.health_config, &kernel.health).await {
+                                    backfill(&kernel).await;
+                                }
+                            }
+                            _ = kernel.shutdown.cancelled() => break,
+                        }
+                    }
+                }
+                .in_current_span(),
+            );
+        }
+
+        // Arming checklist: retried until it passes, unless it must pass now
+        if !selftest::run(&kernel, &spec.filter_config, false).await && !args.require_armed {
+            let kernel = Arc::clone(&kernel);
+            let config = spec.filter_config.clone();
+            tokio::spawn(
+                async move {
+                    loop {
+                        tokio::select! {
+                            _ = tokio::time::sleep(selftest::RETRY) => {
+                                if selftest::run(&kernel, &config, true).await {
+                                    break;
+                                }
+                            }
+                            _ = kernel.shutdown.cancelled() => break,
+                        }
+                    }
+                }
+                .in_current_span(),
+            );
+        }
+        if args.require_armed && !kernel.health.armed() {
+            error!("🔒 Kill actions disarmed after the self-test - exiting (--require-armed)");
+            let snapshot = kernel.snapshot().await;
+            let _ = kernel
+                .audit_trail
+                .record_shutdown("self-test failed", true, &snapshot);
+            std::process::exit(1);
+        }
+
+        // Lines the previous run accepted but never judged
+        if let Some(ref journal) = kernel.journal {
+            let lines = journal.take_recovered();
+            if !lines.is_empty() {
+                let replay = Arc::clone(&kernel);
+                kernel
+                    .tracker
+                    .spawn(async move { recover(&replay, lines).await }.in_current_span());
+            }
+        }
+
+        let mut retention = spec.filter_config.retention.clone();
+        let s3 = &spec.filter_config.s3;
+        if s3.enabled() {
+            let credentials = s3::Credentials::from_env().unwrap_or_else(|e| {
+                error!("{}", e);
+                std::process::exit(1);
+            });
+            info!(
+                "  S3 archival: s3://{}/{}{}",
+                s3.bucket.as_deref().unwrap_or_default(),
+                s3.prefix,
+                match s3.object_lock_days {
+                    Some(days) => format!(" (object lock {}d)", days),
+                    None => String::new(),
+                }
+            );
+            retention.hold_for_upload = true;
+            kernel.tracker.spawn(
+                s3::run(
+                    Arc::clone(&kernel.audit_trail),
+                    s3.clone(),
+                    credentials,
+                    kernel.shutdown.clone(),
+                )
+                .in_current_span(),
+            );
+        }
+        if retention.enabled() {
+            match retention.keep {
+                Some(ref keep) => info!(
+                    "  Audit retention: {}, rotated every {:?}{}",
+                    keep,
+                    retention.rotate().unwrap_or_default(),
+                    match retention.archive_dir {
+                        Some(ref dir) => format!(", archived to {}", dir.display()),
+                        None => String::new(),
+                    }
+                ),
+                None => info!(
+                    "  Audit rotation every {:?}, segments kept",
+                    retention.rotate().unwrap_or_default()
+                ),
+            }
+            kernel.tracker.spawn(
+                retention::run(
+                    Arc::clone(&kernel.audit_trail),
+                    retention,
+                    kernel.shutdown.clone(),
+                )
+                .in_current_span(),
+            );
+        }
+
+        let resources = &spec.filter_config.resource;
+        match (resources.enabled(), kernel.config.target_pid) {
+            (true, Some(pid)) => {
+                info!(
+                    "  Resource monitor: PID {} every {}ms",
+                    pid, resources.interval_ms
+                );
+                kernel.tracker.spawn(
+                    watch_resources(Arc::clone(&kernel), resources.clone(), pid)
+                        .in_current_span(),
+                );
+            }
+            (true, None) => warn!("  Resource monitor disabled: [resource] needs --target-pid"),
+            (false, _) => {}
+        }
+
+        let egress = &spec.filter_config.egress;
+        match (egress.enabled(), kernel.config.target_pid) {
+            (true, Some(pid)) => {
+                info!("  Egress monitor: PID {} every {}ms", pid, egress.interval_ms);
+                kernel.tracker.spawn(
+                    watch_egress(Arc::clone(&kernel), egress.clone(), pid).in_current_span(),
+                );
+            }
+            (true, None) => warn!("  Egress monitor disabled: [egress] needs --target-pid"),
+            (false, _) => {}
+        }
+
+        let credentials = &spec.filter_config.credential;
+        match (credentials.enabled, kernel.config.target_pid) {
+            #[cfg(windows)]
+            (true, Some(pid)) => {
+                info!(
+                    "  Credential monitor: PID {} ({} sources)",
+                    pid,
+                    credentials.source.len()
+                );
+                kernel.tracker.spawn(
+                    watch_credentials(Arc::clone(&kernel), credentials.clone(), pid)
+                        .in_current_span(),
+                );
+            }
+            #[cfg(not(windows))]
+            (true, Some(_)) => warn!("  Credential monitor disabled: [credential] needs Windows"),
+            (true, None) => {
+                warn!("  Credential monitor disabled: [credential] needs --target-pid")
+            }
+            (false, _) => {}
+        }
+
+        let decoys = &spec.filter_config.honeypot;
+        if !decoys.is_empty() {
+            match honeypot::plant(decoys) {
+                Ok(0) => {}
+                Ok(planted) => info!("🍯 Planted {} honeypot file(s)", planted),
+                Err(e) => warn!("🍯 Cannot plant honeypot files: {}", e),
+            }
+            info!("  Honeypots: {} decoy(s)", decoys.len());
+            kernel.tracker.spawn(
+                watch_honeypots(Arc::clone(&kernel), decoys.clone()).in_current_span(),
+            );
+        }
+
+        let files = &spec.filter_config.fs;
+        if files.enabled() {
+            info!(
+                "  File-system monitor: {} watched, {} sensitive path(s)",
+                files.watch.len(),
+                files.sensitive.len()
+            );
+            kernel.tracker.spawn(
+                watch_files(Arc::clone(&kernel), files.clone(), spec.audit_log.clone())
+                    .in_current_span(),
+            );
+        }
+
+        if let Some(port) = spec.admin_port {
+            let kernel = Arc::clone(&kernel);
+            let probes = probe::Probes::new(watchdog_config.stall_ms, watchdog_config.max_panics)
+                .with_pipeline(spec.name.as_deref(), Arc::clone(&kernel));
+            tokio::spawn(
+                async move {
+                    if let Err(e) = admin::serve(port, kernel, probes).await {
+                        error!("Admin API failed: {}", e);
+                    }
+                }
+                .in_current_span(),
+            );
+        }
+
+        // Webhooks: KILL / FAIL records straight from the audit trail
+        let notify_stop = CancellationToken::new();
+        let forwarder = tokio::spawn(notify::forward_decisions(
+            kernel.audit_trail.subscribe(),
+            notifier.clone(),
+            notify_stop.clone(),
+            kernel.explainer.is_some(),
+        ));
+        // GELF / Logstash: every decision, to the SOC
+        let shippers = spec
+            .filter_config
+            .output
+            .iter()
+            .map(|output| {
+                info!("  Output: {} to {}", output.format.as_str(), output.url);
+                tokio::spawn(
+                    ship::run(
+                        kernel.audit_trail.subscribe(),
+                        output.clone(),
+                        http.clone(),
+                        notify_stop.clone(),
+                    )
+                    .in_current_span(),
+                )
+            })
+            .collect();
+
+        if let Err(e) = spec.endpoint.check_exposure(spec.config.agent_token.as_deref()) {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+        match spec.endpoint {
+            Endpoint::Tcp(addr) if addr.ip().is_loopback() => info!("  Mode: TCP ({})", addr),
+            Endpoint::Tcp(addr) => {
+                warn!("  Mode: TCP ({})", addr);
+                warn!(
+                    "⚠️ {} is NOT loopback: any host that can route here may connect - agents must authenticate (HELLO token=), and lines travel unencrypted",
+                    addr
+                );
+            }
+            Endpoint::Otlp(port) => info!("  Mode: OTLP/HTTP logs (port {})", port),
+            Endpoint::Mcp(port) => info!("  Mode: MCP server (port {})", port),
+            #[cfg(target_os = "linux")]
+            Endpoint::Vsock(port) => {
+                info!("  Mode: vsock (port {})", port);
+                match spec.config.agent_token {
+                    Some(_) => info!("  Guests must authenticate (HELLO token=)"),
+                    None => warn!(
+                        "⚠️ Any guest VM may connect to vsock port {}: set --agent-token-file to authenticate them",
+                        port
+                    ),
+                }
+            }
+            #[cfg(windows)]
+            Endpoint::Local(ref name) => info!("  Mode: Named Pipe ({})", name),
+            #[cfg(unix)]
+            Endpoint::Local(ref path) => info!("  Mode: Unix socket ({})", path),
+            Endpoint::Container(ref name) => info!("  Mode: Docker container logs ({})", name),
+            Endpoint::Pods(ref watch) => info!(
+                "  Mode: Kubernetes pods ({} in {})",
+                watch.selector,
+                watch.namespace.as_deref().unwrap_or("the service account's namespace")
+            ),
+            #[cfg(windows)]
+            Endpoint::Etw(ref source) => info!(
+                "  Mode: ETW (provider {}, level {}, keywords 0x{:x})",
+                source.provider, source.level, source.keywords
+            ),
+        }
+        Pipeline {
+            name: spec.name,
+            span: tracing::Span::current(),
+            kernel,
+            endpoint: spec.endpoint,
+            notifier,
+            notify_stop,
+            forwarder,
+            shippers,
+        }
+    }
+    .instrument(span.clone())
+    .await
+}
+
+/// Compile a pipeline's rules and create its LLM chain and audit trail
+fn build_kernel(
+    args: &Args,
+    prompts: &Prompts,
+    http: &reqwest::Client,
+    shutdown: &CancellationToken,
+    ha: Option<&Arc<ha::Ha>>,
+    spec: &PipelineSpec,
+) -> (Arc<Kernel>, notify::Notifier) {
+    let filter_config = &spec.filter_config;
+    let config = spec.config.clone();
+
+    let filter = filter::Filter::new(filter_config).unwrap_or_else(|e| {
+        error!("Failed to compile filter rules: {}", e);
+        std::process::exit(1);
+    });
+    match filter.prescreen_literals() {
+        Some(n) => info!("  Keyword pre-screen: {} literals", n),
+        None => warn!("  Keyword pre-screen disabled: a pattern has no required literal"),
+    }
+    let stages = stage::Registry::default()
+        .chain(config.stages.as_deref())
+        .unwrap_or_else(|e| {
+            error!("{}", e);
+            std::process::exit(1);
+        });
+    if config.stages.is_some() {
+        info!(
+            "  Stages: {}",
+            stages.names().collect::<Vec<_>>().join(" → ")
+        );
+    }
+    let correlator = correlate::Correlator::new(&filter_config.sequence);
+    if !correlator.is_empty() {
+        info!("  Sequence rules: {}", filter_config.sequence.len());
+    }
+    let rates = rate::RateMonitor::new(&filter_config.rate);
+    if !rates.is_empty() {
+        info!("  Rate rules: {}", filter_config.rate.len());
+    }
+    let limits = limit::LimitMonitor::new(&filter_config.limit);
+    if !limits.is_empty() {
+        info!("  Limit rules: {}", filter_config.limit.len());
+    }
+    if filter_config.flow.enabled() {
+        info!(
+            "  Flow control: {} lines/s per agent, over limit: {}",
+            filter_config.flow.lines_per_sec,
+            filter_config.flow.over_limit.as_str()
+        );
+    }
+    if filter_config.multiline.enabled {
+        info!(
+            "  Multi-line records: {} continuation lines, flushed after {}ms",
+            match filter_config.multiline.continuation {
+                Some(ref pattern) => pattern.as_str(),
+                None => "indented",
+            },
+            filter_config.multiline.flush_ms
+        );
+    }
+    let redactor = redact::Redactor::new(&filter_config.redact);
+    if redactor.is_empty() {
+        warn!("  PII redaction disabled: raw lines reach the audit trail and LLM");
+    } else {
+        info!("  PII redaction: {} patterns", redactor.len());
+    }
+
+    // Create LLM clients ONCE (connection pooling); each model gets its own
+    // sampling profile and fingerprint
+    let build_member = |model: &str, url: &str| {
+        let sampling = filter_config.sampling(model);
+        let mut client = llm::LlmClient::new(url, model, config.max_tokens)
+            .with_client(http.clone())
+            .with_sampling(sampling.clone())
+            .with_prompt(prompts.log.clone())
+            .with_tool_prompt(prompts.tool.clone())
+            .with_examples(filter_config.prompt.examples.clone());
+        if args.no_structured_output {
+            client = client.without_structured_output();
+        }
+        let fingerprint = ModelFingerprint::new(model, url, config.max_tokens, &sampling);
+        (client, fingerprint)
+    };
+    let mut members = vec![build_member(&config.model, &config.llm_url)];
+    for (model, url) in &args.llm_fallback {
+        members.push(build_member(model, url));
+    }
+    let notifier = notify::Notifier::new(&filter_config.notify);
+    let llm = Arc::new(
+        chain::LlmChain::new(
+            members,
+            (
+                args.llm_breaker_failures,
+                Duration::from_millis(args.llm_breaker_cooldown_ms),
+            ),
+        )
+        .with_notifier(notifier.clone()),
+    );
+    let batcher = batch::Batcher::new(&filter_config.batch, Arc::clone(&llm));
+
+    // Create audit trail
+    let model_fingerprint = llm.primary().fingerprint.clone();
+
+    let mut audit_trail = AuditTrail::open(
+        spec.audit_log.clone(),
+        model_fingerprint.clone(),
+        &llm.primary().client.prompt_version(),
+        Some(&spec.endpoint.identity()),
+    )
+    .expect("Failed to create audit trail");
+
+    if let Some(ref path) = args.audit_key_file {
+        let key = std::fs::read(path).expect("Failed to read audit key file");
+        audit_trail = audit_trail.with_signing_key(key);
+    }
+    if let Some(ha) = ha {
+        audit_trail = audit_trail.with_instance(ha.instance());
+    }
+    if args.audit_prompts {
+        audit_trail = audit_trail.with_rendered_prompts();
+    }
+    if let Some(ref path) = args.audit_backup {
+        info!("  Audit backup: {}", path.display());
+        audit_trail = audit_trail
+            .with_backup(path.clone())
+            .expect("Failed to open audit backup");
+    }
+    if args.audit_durability != audit::Durability::Buffered {
+        info!("  Audit durability: {:?}", args.audit_durability);
+        audit_trail = audit_trail
+            .with_durability(args.audit_durability)
+            .expect("Failed to reopen audit trail");
+    }
+    let encryptor = encrypt::Encryptor::new(&filter_config.encrypt).unwrap_or_else(|e| {
+        error!("{}", e);
+        std::process::exit(1);
+    });
+    if let Some(encryptor) = encryptor {
+        info!(
+            "  Audit payloads encrypted to {} recipient(s)",
+            encryptor.recipients()
+        );
+        audit_trail = audit_trail.with_encryptor(encryptor);
+    }
+    let (anchor_tx, anchor_rx) = tokio::sync::mpsc::unbounded_channel();
+    let checkpoints = &filter_config.checkpoint;
+    audit_trail = audit_trail.with_checkpoints(
+        checkpoints.records,
+        checkpoints.anchored().then_some(anchor_tx),
+    );
+
+    let audit_trail = Arc::new(audit_trail);
+    let anchorer = checkpoints.anchored().then(|| {
+        checkpoint::Anchorer::new(
+            checkpoints,
+            http.clone(),
+            Arc::clone(&audit_trail),
+            anchor_rx,
+        )
+    });
+    let explainer = filter_config.explain.enabled.then(|| {
+        explain::Explainer::new(
+            &filter_config.explain,
+            Arc::clone(&llm),
+            Arc::clone(&audit_trail),
+            notifier.clone(),
+        )
+    });
+
+    let authorizer = authorize::Authorizer::new(
+        &filter_config.authorizer,
+        http.clone(),
+        Arc::clone(&audit_trail),
+    )
+    .unwrap_or_else(|e| {
+        error!("{}", e);
+        std::process::exit(1);
+    });
+    if let Some(ref authorizer) = authorizer {
+        info!(
+            "  KILLs need approval from {} within {}ms (else PAUSE)",
+            authorizer.url(),
+            filter_config.authorizer.deadline_ms
+        );
+    }
+
+    let snapshots =
+        snapshot::Snapshots::new(&filter_config.snapshot, Arc::clone(&audit_trail)).map(Arc::new);
+    if let Some(ref snapshots) = snapshots {
+        info!(
+            "  KILLs snapshot their targets into {} first (up to {}ms)",
+            snapshots.dir().display(),
+            filter_config.snapshot.timeout_ms
+        );
+    }
+
+    let classifier = classify::Classifier::new(&filter_config.classifier).unwrap_or_else(|e| {
+        error!("Failed to load the classifier: {}", e);
+        std::process::exit(1);
+    });
+    if let Some(ref classifier) = classifier {
+        info!(
+            "  Classifier {} ({} weights) sustains below {}",
+            classifier.fingerprint(),
+            classifier.weights(),
+            filter_config.classifier.sustain_below
+        );
+    }
+
+    let restrictor = restrict::Restrictor::new(&filter_config.restrict);
+    if let Some(ref restrictor) = restrictor {
+        info!("  RESTRICT applies {}", restrictor.describe());
+    }
+
+    // The shadow analyzer: the live model and prompt unless overridden
+    let shadow = filter_config.shadow.as_ref().map(|shadow_config| {
+        let prompt = match shadow_config.prompt_file {
+            Some(ref path) => llm::load_prompt(path).unwrap_or_else(|e| {
+                error!("Failed to load shadow prompt {}: {}", path.display(), e);
+                std::process::exit(1);
+            }),
+            None => prompts.log.clone(),
+        };
+        let model = shadow_config.model.as_deref().unwrap_or(&config.model);
+        let url = shadow_config.llm_url.as_deref().unwrap_or(&config.llm_url);
+        let sampling = filter_config.sampling(model);
+        let mut client = llm::LlmClient::new(url, model, config.max_tokens)
+            .with_client(http.clone())
+            .with_sampling(sampling.clone())
+            .with_prompt(prompt)
+            .with_tool_prompt(prompts.tool.clone())
+            .with_examples(filter_config.prompt.examples.clone());
+        if args.no_structured_output {
+            client = client.without_structured_output();
+        }
+        let fingerprint = ModelFingerprint::new(model, url, config.max_tokens, &sampling);
+        shadow::Shadow::new(
+            shadow_config,
+            client,
+            fingerprint.fingerprint(),
+            Arc::clone(&audit_trail),
+        )
+    });
+
+    let spill = filter_config.backfill.spill_file.as_ref().map(|path| {
+        backfill::Spill::open(path, filter_config.backfill.max_lines)
+            .expect("Failed to open spill file")
+    });
+    let journal = filter_config.queue.dir.as_ref().map(|dir| {
+        queue::Journal::open(&filter_config.queue, dir).expect("Failed to open persisted queue")
+    });
+
+    let kernel = Arc::new(Kernel {
+        config,
+        llm,
+        batcher,
+        audit_trail,
+        stats: Mutex::new(Stats::default()),
+        filter,
+        stages,
+        correlator,
+        rates,
+        limits,
+        flow: filter_config.flow.clone(),
+        redactor,
+        prompt_config: filter_config.prompt.clone(),
+        health: health::Health::new(&filter_config.health),
+        health_config: filter_config.health.clone(),
+        agents: agents::Registry::default(),
+        valve: valve::KillValve::new(&filter_config.kill_limits),
+        slo: filter_config.slo.clone(),
+        priority: filter_config.priority.clone(),
+        guard: filter_config.guard.clone(),
+        multiline: filter_config.multiline.clone(),
+        // Validated with the rest of the filter config
+        policy: policy::Policy::new(&filter_config.policy).expect("valid [policy]"),
+        schedule: schedule::Schedule::new(&filter_config.schedule).expect("valid [schedule]"),
+        ha: ha.cloned(),
+        spill,
+        journal,
+        anchorer,
+        learner: args.learn.map(|_| learn::Learner::new()),
+        classifier,
+        explainer,
+        authorizer,
+        snapshots,
+        restrictor,
+        shadow,
+        shutdown: shutdown.clone(),
+        tracker: TaskTracker::new(),
+        serving: AtomicBool::new(false),
+    });
+
+    info!("  LLM endpoint: {}", kernel.config.llm_url);
+    info!("  Model: {}", kernel.config.model);
+    info!("  Model fingerprint: {}", model_fingerprint.fingerprint());
+    for member in kernel.llm.fallbacks() {
+        info!(
+            "  Fallback: {} at {} ({})",
+            member.fingerprint.model_name,
+            member.fingerprint.llm_url,
+            member.fingerprint.fingerprint()
+        );
+    }
+    info!("  Audit log: {}", spec.audit_log.display());
+    if let Some(ref shadow) = kernel.shadow {
+        info!(
+            "  Shadow analyzer: {} (prompt {})",
+            shadow.model(),
+            shadow.prompt_hash()
+        );
+    }
+    if let Some(ref spill) = kernel.spill {
+        info!(
+            "  Backfill spill file: {} ({} pending)",
+            spill.path().display(),
+            spill.len()
+        );
+    }
+    if let Some(ref journal) = kernel.journal {
+        info!(
+            "  Persisted queue: {} ({} to replay)",
+            journal.dir().display(),
+            journal.len()
+        );
+    }
+    let checkpoints = &filter_config.checkpoint;
+    if checkpoints.records > 0 {
+        let witnesses: Vec<&str> = checkpoints
+            .anchor_url
+            .iter()
+            .chain(&checkpoints.tsa_url)
+            .map(String::as_str)
+            .collect();
+        info!(
+            "  Audit checkpoints: every {} records{}",
+            checkpoints.records,
+            match witnesses.is_empty() {
+                true => String::new(),
+                false => format!(", anchored at {}", witnesses.join(", ")),
+            }
+        );
+    }
+    if let Some(ref path) = args.prompt_file {
+        info!("  Prompt: {}", path.display());
+    }
+    if let Some(ref path) = args.tool_prompt_file {
+        info!("  Tool prompt: {}", path.display());
+    }
+    if !filter_config.prompt.examples.is_empty() {
+        info!(
+            "  Few-shot examples: {}",
+            filter_config.prompt.examples.len()
+        );
+    }
+    let batch = &filter_config.batch;
+    if batch.threshold > 0 {
+        info!(
+            "  Batching: up to {} lines once {} analyses are pending",
+            batch.max_lines, batch.threshold
+        );
+    }
+    if !filter_config.notify.webhook.is_empty() {
+        info!("  Webhooks: {}", filter_config.notify.webhook.len());
+    }
+    if let Some(ref email) = filter_config.notify.email {
+        info!(
+            "  Email alerts: {} recipient(s) via {}",
+            email.to.len(),
+            email.server
+        );
+    }
+    if !kernel.schedule.is_empty() {
+        info!(
+            "  Schedule profiles: {}",
+            filter_config.schedule.profile.len()
+        );
+    }
+    if !kernel.policy.is_empty() {
+        info!(
+            "  Decision policy: {} rule(s)",
+            filter_config.policy.rule.len()
+        );
+    }
+    if let Some(recovery) = kernel.audit_trail.recovery() {
+        warn!(
+            "  ♻️ Audit recovery: previous run ended uncleanly, resuming at ID {}",
+            recovery.resume_id
+        );
+        if let Some(lost) = recovery.lost_id {
+            warn!("  ♻️ Decision ID {} was torn mid-write and is lost", lost);
+        }
+    }
+    if let Some(pid) = kernel.config.target_pid {
+        info!("  Target PID: {}", pid);
+    }
+    if let Some(ref container) = kernel.config.target_container {
+        info!("  Target container: {}", container);
+    }
+    if let Some(ref vm) = kernel.config.target_vm {
+        info!("  Target VM: {}", vm);
+    }
+    (kernel, notifier)
+}
+
+impl Pipeline {
+    /// Wait for in-flight analyses (until `deadline`) and notifications,
+    /// then write the audit epilogue
+    async fn finish(
+        self,
+        deadline: tokio::time::Instant,
+        drain_timeout: Duration,
+        reason: &str,
+    ) -> Arc<Kernel> {
+        let kernel = self.kernel;
+        let drained = tokio::time::timeout_at(deadline, kernel.tracker.wait())
+            .await
+            .is_ok();
+        if drained {
+            info!("✅ All connections drained");
+        } else {
+            warn!(
+                "⚠️ Drain timeout ({}ms) - {} connection(s) abandoned",
+                drain_timeout.as_millis(),
+                kernel.tracker.len()
+            );
+        }
+
+        if let Some(ref explainer) = kernel.explainer {
+            if !explainer.drain(drain_timeout).await {
+                warn!("⚠️ Drain timeout - KILL explanations abandoned");
+            }
+        }
+        if let Some(ref authorizer) = kernel.authorizer {
+            if !authorizer.drain(drain_timeout).await {
+                warn!("⚠️ Drain timeout - KILLs awaiting approval abandoned");
+            }
+        }
+        if let Some(ref snapshots) = kernel.snapshots {
+            if !snapshots.drain(drain_timeout).await {
+                warn!("⚠️ Drain timeout - KILL snapshots abandoned");
+            }
+        }
+        if let Some(ref restrictor) = kernel.restrictor {
+            if !restrictor.drain(drain_timeout).await {
+                warn!("⚠️ Drain timeout - restrictions abandoned");
+            }
+        }
+        if let Some(ref shadow) = kernel.shadow {
+            if !shadow.drain(drain_timeout).await {
+                warn!("⚠️ Drain timeout - shadow analyses abandoned");
+            }
+        }
+
+        // Deliver notifications for the final decisions
+        self.notify_stop.cancel();
+        let _ = self.forwarder.await;
+        let deadline = tokio::time::Instant::now() + drain_timeout;
+        for shipper in self.shippers {
+            if tokio::time::timeout_at(deadline, shipper).await.is_err() {
+                warn!("⚠️ Drain timeout - decisions not shipped to an output");
+                break;
+            }
+        }
+        if !self.notifier.drain(drain_timeout).await {
+            warn!("⚠️ Drain timeout - undelivered notifications dropped");
+        }
+
+        // Cover the last records, and anchor them before the footer
+        if let Err(e) = kernel.audit_trail.close_checkpoints() {
+            error!("Failed to write the final audit checkpoint: {}", e);
+        }
+        if let Some(ref anchorer) = kernel.anchorer {
+            if !anchorer.drain(drain_timeout).await {
+                warn!("⚠️ Drain timeout - audit checkpoints left unanchored");
+            }
+        }
+
+        let snapshot = kernel.snapshot().await;
+        if let Err(e) = kernel
+            .audit_trail
+            .record_shutdown(reason, drained, &snapshot)
+        {
+            error!("Failed to write audit shutdown footer: {}", e);
+        }
+        let _ = kernel.audit_trail.flush();
+        kernel
+    }
+}
+
+/// Accept connections on a pipeline's endpoint until shutdown
+async fn serve(
+    endpoint: Endpoint,
+    kernel: Arc<Kernel>,
+    activated: Activated,
+) -> Result<(), KernelError> {
+    match endpoint {
+        Endpoint::Tcp(addr) => run_tcp_server(addr, kernel).await,
+        Endpoint::Otlp(port) => run_otlp_receiver(port, kernel).await,
+        Endpoint::Mcp(port) => run_mcp_server(port, kernel).await,
+        #[cfg(target_os = "linux")]
+        Endpoint::Vsock(port) => run_vsock_server(port, kernel).await,
+        #[cfg(windows)]
+        Endpoint::Local(name) => {
+            let _ = activated;
+            run_named_pipe_server(&name, kernel).await
+        }
+        #[cfg(unix)]
+        Endpoint::Local(path) => run_unix_socket_server(&path, kernel, activated).await,
+        Endpoint::Container(name) => run_container_watch(&name, kernel).await,
+        Endpoint::Pods(watch) => run_pod_watch(&watch, kernel).await,
+        #[cfg(windows)]
+        Endpoint::Etw(source) => run_etw_consumer(&source, kernel).await,
+    }
+}
+
+/// `--tcp`, `--otlp-logs-port`, `--mcp-port`, `--vsock-port`,
+/// `--watch-container`, `--watch-pods`, `--etw-provider` or the platform's
+/// local endpoint
+fn default_endpoint(args: &Args) -> Endpoint {
+    if args.tcp {
+        return Endpoint::Tcp(SocketAddr::new(args.bind, args.port));
+    }
+    if let Some(port) = args.otlp_logs_port {
+        return Endpoint::Otlp(port);
+    }
+    if let Some(port) = args.mcp_port {
+        return Endpoint::Mcp(port);
+    }
+    #[cfg(target_os = "linux")]
+    if let Some(port) = args.vsock_port {
+        return Endpoint::Vsock(port);
+    }
+    if let Some(ref name) = args.watch_container {
+        return Endpoint::Container(name.clone());
+    }
+    if let Some(ref selector) = args.watch_pods {
+        return Endpoint::Pods(kube::PodWatch {
+            selector: selector.clone(),
+            namespace: args.pod_namespace.clone(),
+            api: args.kube_api.clone(),
+        });
+    }
+    #[cfg(windows)]
+    if let Some(provider) = args.etw_provider {
+        return Endpoint::Etw(etw::EtwSource {
+            provider,
+            level: args.etw_level,
+            keywords: args.etw_keywords,
+        });
+    }
+    #[cfg(unix)]
+    let local = args.socket_path.clone();
+    #[cfg(windows)]
+    let local = pipe_path(&args.pipe_name);
+    Endpoint::Local(local)
+}
+
+/// `--pipe-name` → pipe path (a full `\\.\pipe\..` path is kept)
+#[cfg(windows)]
+fn pipe_path(name: &str) -> String {
+    match name.starts_with(r"\\") {
+        true => name.to_string(),
+        false => format!(r"\\.\pipe\{}", name),
+    }
+}
+
+/// SHA-256 of the token in `--agent-token-file` (surrounding whitespace
+/// ignored)
+fn load_agent_token(path: &Path) -> String {
+    load_token(path, "Agent")
+}
+
+/// SHA-256 of the `--admin-token-file` token; exits if unreadable or empty
+fn load_admin_token(path: &Path) -> String {
+    load_token(path, "Admin")
+}
+
+fn load_token(path: &Path, kind: &str) -> String {
+    let token = std::fs::read_to_string(path).unwrap_or_else(|e| {
+        error!(
+            "Failed to read {} token {}: {}",
+            kind.to_lowercase(),
+            path.display(),
+            e
+        );
+        std::process::exit(1);
+    });
+    if token.trim().is_empty() {
+        error!("{} token file {} is empty", kind, path.display());
+        std::process::exit(1);
+    }
+    audit::sha256_hex(token.trim())
+}
+
+/// Run an offline subcommand
+fn run_command(cmd: Cmd, filter_config: &filter::FilterConfig) -> Result<(), KernelError> {
+    match cmd {
+        Cmd::Bench {
+            lines,
+            suspicious_pct,
+            records,
+            durability,
+        } => {
+            let options = bench::HostOptions {
+                lines,
+                suspicious_pct,
+                records,
+                durability,
+            };
+            bench::bench_host(filter_config, &options)?
+        }
+        Cmd::BenchFilter {
+            input,
+            lines,
+            suspicious_pct,
+        } => bench::bench_filter(filter_config, input.as_deref(), lines, suspicious_pct)?,
+        Cmd::TestFilter { cases, .. } => harness::test_filter(filter_config, &cases)?,
+        Cmd::Rules {
+            command: RulesCmd::Lint,
+        } => lint::run(filter_config)?,
+        Cmd::Top { .. }
+        | Cmd::Ctl { .. }
+        | Cmd::Drill { .. }
+        | Cmd::Loadgen { .. }
+        | Cmd::Audit { .. }
+        | Cmd::Classifier { .. }
+        | Cmd::Dataset { .. }
+        | Cmd::Incident { .. }
+        | Cmd::WhatIf { .. }
+        | Cmd::CheckConfig { .. } => {
+            unreachable!("handled before the kernel starts")
+        }
+    }
+    Ok(())
+}
+
+/// Wait for SIGINT/SIGTERM (Unix) or console ctrl events (Windows)
+#[cfg(unix)]
+async fn shutdown_signal() -> &'static str {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut sigterm = signal(SignalKind::terminate()).expect("Failed to install SIGTERM handler");
+    let mut sigint = signal(SignalKind::interrupt()).expect("Failed to install SIGINT handler");
+
+    tokio::select! {
+        _ = sigterm.recv() => "SIGTERM",
+        _ = sigint.recv() => "SIGINT",
+    }
+}
+
+/// Wait for SIGINT/SIGTERM (Unix) or console ctrl events (Windows)
+#[cfg(windows)]
+async fn shutdown_signal() -> &'static str {
+    use tokio::signal::windows;
+
+    let mut ctrl_c = windows::ctrl_c().expect("Failed to install ctrl-c handler");
+    let mut ctrl_break = windows::ctrl_break().expect("Failed to install ctrl-break handler");
+    let mut ctrl_close = windows::ctrl_close().expect("Failed to install ctrl-close handler");
+    let mut ctrl_shutdown =
+        windows::ctrl_shutdown().expect("Failed to install ctrl-shutdown handler");
+
+    tokio::select! {
+        _ = ctrl_c.recv() => "ctrl-c",
+        _ = ctrl_break.recv() => "ctrl-break",
+        _ = ctrl_close.recv() => "ctrl-close",
+        _ = ctrl_shutdown.recv() => "ctrl-shutdown",
+    }
+}
+
+/// TCP Server (fallback mode)
+async fn run_tcp_server(addr: SocketAddr, kernel: Arc<Kernel>) -> Result<(), KernelError> {
+    use tokio::net::TcpListener;
+
+    let listener = TcpListener::bind(addr).await?;
+    info!("🎯 TCP Ready for connections...");
+    kernel.serving.store(true, Ordering::Relaxed);
+    #[cfg(target_os = "linux")]
+    let _ = systemd::notify_ready(&format!("Listening on TCP {}", addr));
+
+    loop {
+        let (socket, addr) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = kernel.shutdown.cancelled() => return Ok(()),
+        };
+        info!("📡 Connection from: {}", addr);
+
+        let kernel = Arc::clone(&kernel);
+        kernel.tracker.clone().spawn(
+            async move {
+                let (reader, writer) = socket.into_split();
+                let acks = ack::Acks::spawn(&kernel.tracker, writer);
+                let peer = addr.to_string();
+                process_connection(BufReader::new(reader), kernel, &peer, None, Some(acks)).await;
+                info!("📡 Connection closed");
+            }
+            .in_current_span(),
+        );
+    }
+}
+
+/// AF_VSOCK server for agents in VM sandboxes; each guest is the agent
+/// `vsock:<cid>`
+#[cfg(target_os = "linux")]
+async fn run_vsock_server(port: u32, kernel: Arc<Kernel>) -> Result<(), KernelError> {
+    let listener = vsock::VsockListener::bind(port)?;
+    info!("🎯 vsock Ready on port {}...", port);
+    kernel.serving.store(true, Ordering::Relaxed);
+    let _ = systemd::notify_ready(&format!("Listening on vsock port {}", port));
+
+    loop {
+        let (stream, cid) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = kernel.shutdown.cancelled() => return Ok(()),
+        };
+        info!("📡 Connection from VM (CID {})", cid);
+
+        let kernel = Arc::clone(&kernel);
+        kernel.tracker.clone().spawn(
+            async move {
+                let (reader, writer) = tokio::io::split(stream);
+                let acks = ack::Acks::spawn(&kernel.tracker, writer);
+                let peer = format!("vsock:{}", cid);
+                process_connection(BufReader::new(reader), kernel, &peer, None, Some(acks)).await;
+                info!("📡 Connection closed");
+            }
+            .in_current_span(),
+        );
+    }
+}
+
+/// Windows Named Pipe Server (fast IPC)
+/// Pre-creates next instance to avoid race condition on reconnect
+#[cfg(windows)]
+async fn run_named_pipe_server(pipe_name: &str, kernel: Arc<Kernel>) -> Result<(), KernelError> {
+    info!("🎯 Named Pipe Ready...");
+    kernel.serving.store(true, Ordering::Relaxed);
+
+    // Create first server instance
+    // Message mode: a frame written in one call arrives whole (see `line`)
+    let mut server = ServerOptions::new()
+        .first_pipe_instance(true)
+        .pipe_mode(PipeMode::Message)
+        .create(pipe_name)?;
+
+    loop {
+        info!("💤 Waiting for connection...");
+        tokio::select! {
+            connected = server.connect() => connected?,
+            _ = kernel.shutdown.cancelled() => return Ok(()),
+        }
+        info!("⚡ Client connected!");
+
+        // CRITICAL: Pre-create next instance BEFORE processing
+        // This eliminates the race condition window
+        let next_server = ServerOptions::new()
+            .pipe_mode(PipeMode::Message)
+            .create(pipe_name)?;
+
+        // Process current connection (tracked so shutdown can drain it)
+        let (reader, writer) = tokio::io::split(server);
+        let acks = ack::Acks::spawn(&kernel.tracker, writer);
+        let connection = process_connection(
+            BufReader::new(reader),
+            Arc::clone(&kernel),
+            "pipe",
+            None,
+            Some(acks),
+        );
+        let _ = kernel.tracker.spawn(connection.in_current_span()).await;
+        info!("🔌 Connection closed, next instance ready");
+
+        // Seamlessly transition to pre-created instance
+        server = next_server;
+    }
+}
+
+/// Follow a container's logs, re-attaching whenever it runs again
+async fn run_container_watch(name: &str, kernel: Arc<Kernel>) -> Result<(), KernelError> {
+    let docker = docker::Docker::from_env()?;
+    kernel.serving.store(true, Ordering::Relaxed);
+    let mut since = None;
+    loop {
+        let container = tokio::select! {
+            container = docker.wait_running(name) => container,
+            _ = kernel.shutdown.cancelled() => return Ok(()),
+        };
+        let logs = match docker.logs(&container, since).await {
+            Ok(logs) => logs,
+            Err(e) => {
+                warn!("🐳 Container {}: {} - retrying", name, e);
+                tokio::time::sleep(Duration::from_secs(2)).await;
+                continue;
+            }
+        };
+        info!(
+            "⚡ Attached to container {} ({})",
+            name,
+            &container.id[..container.id.len().min(12)]
+        );
+
+        let peer = format!("container:{}", name);
+        let connection = Arc::clone(&kernel);
+        let _ = kernel
+            .tracker
+            .spawn(
+                async move {
+                    process_connection(BufReader::new(logs), connection, &peer, None, None).await
+                }
+                .in_current_span(),
+            )
+            .await;
+        if kernel.shutdown.is_cancelled() {
+            return Ok(());
+        }
+        // Pick up whatever the next run logs before we re-attach
+        since = Some(
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs_f64(),
+        );
+        info!("🔌 Container {} log stream ended", name);
+    }
+}
+
+/// OTLP/HTTP logs receiver: each service's records are one agent connection
+async fn run_otlp_receiver(port: u16, kernel: Arc<Kernel>) -> Result<(), KernelError> {
+    use std::collections::HashMap;
+    use tokio::io::AsyncWriteExt;
+
+    let (lines_tx, mut lines) = tokio::sync::mpsc::channel::<otlp::LogLine>(1024);
+    let server = tokio::spawn(otlp::serve(port, lines_tx, kernel.shutdown.clone()));
+    info!(
+        "🎯 OTLP/HTTP logs receiver on http://127.0.0.1:{}/v1/logs",
+        port
+    );
+    kernel.serving.store(true, Ordering::Relaxed);
+    #[cfg(target_os = "linux")]
+    let _ = systemd::notify_ready(&format!("Receiving OTLP logs on port {}", port));
+
+    // Lines reach process_connection through an in-memory pipe per service
+    let mut agents: HashMap<String, tokio::io::DuplexStream> = HashMap::new();
+    while let Some(otlp::LogLine { agent, mut line }) = lines.recv().await {
+        let writer = agents.entry(agent.clone()).or_insert_with_key(|agent| {
+            let (writer, reader) = tokio::io::duplex(64 * 1024);
+            let peer = format!("otlp:{}", agent);
+            info!("📡 OTLP service {}", agent);
+            let connection = Arc::clone(&kernel);
+            kernel.tracker.spawn(
+                async move {
+                    process_connection(BufReader::new(reader), connection, &peer, None, None).await
+                }
+                .in_current_span(),
+            );
+            writer
+        });
+        line.push('\n');
+        // The connection ended (shutdown): the service's next record
+        // starts a new one
+        if writer.write_all(line.as_bytes()).await.is_err() {
+            agents.remove(&agent);
+        }
+    }
+    // Closing the pipes ends the connections
+    drop(agents);
+    server.await??;
+    Ok(())
+}
+
+/// MCP server: each session is one agent connection, every line answered
+async fn run_mcp_server(port: u16, kernel: Arc<Kernel>) -> Result<(), KernelError> {
+    let (opened_tx, mut opened) = tokio::sync::mpsc::channel::<mcp::Opened>(64);
+    let server = tokio::spawn(mcp::serve(
+        port,
+        opened_tx,
+        kernel.guard.enabled,
+        kernel.shutdown.clone(),
+    ));
+    info!("🎯 MCP server on http://127.0.0.1:{}/mcp", port);
+    kernel.serving.store(true, Ordering::Relaxed);
+    #[cfg(target_os = "linux")]
+    let _ = systemd::notify_ready(&format!("Serving MCP on port {}", port));
+
+    while let Some(mcp::Opened { peer, stream }) = opened.recv().await {
+        info!("📡 MCP session {}", peer);
+        let (reader, writer) = tokio::io::split(stream);
+        let acks = ack::Acks::spawn(&kernel.tracker, writer).every_line();
+        let connection = Arc::clone(&kernel);
+        kernel.tracker.spawn(
+            async move {
+                process_connection(BufReader::new(reader), connection, &peer, None, Some(acks))
+                    .await;
+                info!("📡 MCP session {} closed", peer);
+            }
+            .in_current_span(),
+        );
+    }
+    server.await??;
+    Ok(())
+}
+
+/// Follow the pods matching a label selector, one connection per container run
+async fn run_pod_watch(watch: &kube::PodWatch, kernel: Arc<Kernel>) -> Result<(), KernelError> {
+    let kube = Arc::new(kube::Kube::connect(watch)?);
+    info!(
+        "☸️ Watching pods '{}' in namespace {}",
+        watch.selector,
+        kube.namespace()
+    );
+    kernel.serving.store(true, Ordering::Relaxed);
+    let (runs_tx, mut runs) = tokio::sync::mpsc::channel(64);
+    let watcher = {
+        let kube = Arc::clone(&kube);
+        let selector = watch.selector.clone();
+        tokio::spawn(async move { kube.watch(&selector, runs_tx).await })
+    };
+    loop {
+        let run = tokio::select! {
+            run = runs.recv() => match run {
+                Some(run) => run,
+                None => break,
+            },
+            _ = kernel.shutdown.cancelled() => break,
+        };
+        let target = kube::PodTarget {
+            kube: Arc::clone(&kube),
+            run,
+        };
+        kernel
+            .tracker
+            .spawn(follow_pod(target, Arc::clone(&kernel)).in_current_span());
+    }
+    watcher.abort();
+    Ok(())
+}
+
+/// Follow one container run's logs, re-attaching until the run is over
+async fn follow_pod(target: kube::PodTarget, kernel: Arc<Kernel>) {
+    let peer = format!("pod:{}", target);
+    let mut from = match target.run.existing {
+        true => kube::From::Next,
+        false => kube::From::First,
+    };
+    loop {
+        match target.kube.logs(&target.run, from).await {
+            Ok(logs) => {
+                info!("⚡ Following {}", peer);
+                let reader = BufReader::new(logs);
+                process_connection(
+                    reader,
+                    Arc::clone(&kernel),
+                    &peer,
+                    Some(target.clone()),
+                    None,
+                )
+                .await;
+            }
+            Err(e) => {
+                warn!("☸️ {}: {} - retrying", peer, e);
+                tokio::time::sleep(kube::RETRY).await;
+            }
+        }
+        let ended = std::time::Instant::now();
+        if kernel.shutdown.is_cancelled() {
+            return;
+        }
+        match target.kube.still_running(&target.run).await {
+            Ok(true) => {}
+            Ok(false) => {
+                info!("🔌 {} log stream ended", peer);
+                return;
+            }
+            Err(e) => {
+                warn!("☸️ {}: {} - retrying", peer, e);
+                tokio::time::sleep(kube::RETRY).await;
+            }
+        }
+        // Pick up what was logged while the stream was down
+        from = kube::From::Since(ended.elapsed().as_secs() + 1);
+    }
+}
+
+/// ETW consumer: the session's events are one agent connection
+#[cfg(windows)]
+async fn run_etw_consumer(source: &etw::EtwSource, kernel: Arc<Kernel>) -> Result<(), KernelError> {
+    use tokio::io::AsyncWriteExt;
+
+    let (events_tx, mut events) = tokio::sync::mpsc::channel::<String>(1024);
+    let session = etw::Session::start(source, events_tx)?;
+    info!("🎯 ETW session for provider {} ready", source.provider);
+    kernel.serving.store(true, Ordering::Relaxed);
+
+    // Lines reach process_connection through an in-memory pipe
+    let (mut writer, reader) = tokio::io::duplex(64 * 1024);
+    let pump = tokio::spawn(async move {
+        while let Some(mut line) = events.recv().await {
+            line.push('\n');
+            if writer.write_all(line.as_bytes()).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let peer = format!("etw:{}", source.provider);
+    let connection = Arc::clone(&kernel);
+    let _ = kernel
+        .tracker
+        .spawn(
+            async move {
+                process_connection(BufReader::new(reader), connection, &peer, None, None).await
+            }
+            .in_current_span(),
+        )
+        .await;
+    // Dropping the receiver releases a callback blocked on a full channel
+    pump.abort();
+    let _ = pump.await;
+    let _ = tokio::task::spawn_blocking(move || session.stop()).await;
+    info!("🔌 ETW session closed");
+    Ok(())
+}
+
+/// Unix Socket Server (Linux/macOS)
+#[cfg(unix)]
+async fn run_unix_socket_server(
+    socket_path: &str,
+    kernel: Arc<Kernel>,
+    activated: Activated,
+) -> Result<(), KernelError> {
+    // Prefer a socket passed by systemd (ListenStream=) over binding our own
+    // systemd owns an activated socket; only clean up what we bound
+    let owns_socket = activated.is_none();
+    let listener = match activated {
+        Some(std_listener) => {
+            info!("🎯 Unix Socket Ready (systemd socket activation)...");
+            UnixListener::from_std(std_listener)?
+        }
+        None => {
+            let _ = std::fs::remove_file(socket_path);
+            let listener = UnixListener::bind(socket_path)?;
+            info!("🎯 Unix Socket Ready at {}...", socket_path);
+            listener
+        }
+    };
+    kernel.serving.store(true, Ordering::Relaxed);
+    #[cfg(target_os = "linux")]
+    let _ = systemd::notify_ready("Listening on Unix socket");
+
+    loop {
+        let (socket, _) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = kernel.shutdown.cancelled() => break,
+        };
+        info!("⚡ Client connected!");
+
+        let kernel = Arc::clone(&kernel);
+        kernel.tracker.clone().spawn(
+            async move {
+                let (reader, writer) = socket.into_split();
+                let acks = ack::Acks::spawn(&kernel.tracker, writer);
+                process_connection(BufReader::new(reader), kernel, "unix", None, Some(acks)).await;
+                info!("🔌 Connection closed");
+            }
+            .in_current_span(),
+        );
+    }
+
+    if owns_socket {
+        let _ = std::fs::remove_file(socket_path);
+    }
+    Ok(())
+}
+
+/// Per-connection state (one connection = one agent)
+struct AgentState {
+    /// ID in the kernel's agent registry
+    id: u64,
+    /// Correlation IDs stamped on the connection's records
+    ids: agents::AgentIds,
+    sequences: correlate::SequenceTracker,
+    rates: rate::RateTracker,
+    limits: limit::LimitTracker,
+    flow: flow::Bucket,
+    history: context::History,
+    /// A pipeline error happened on this connection: escalations follow
+    /// the degraded-mode policy until the agent reconnects
+    faulted: bool,
+    /// The pod this connection's logs come from (`--watch-pods`)
+    pod: Option<kube::PodTarget>,
+    /// Line being replayed from the persisted queue (startup recovery)
+    recovered: Option<Recovered>,
+    /// Most severe action taken while judging the current line (`--ack`)
+    verdict: Option<String>,
+}
+
+/// A persisted line judged again after a restart
+struct Recovered {
+    ticket: queue::Ticket,
+    input_hash: String,
+}
+
+impl AgentState {
+    /// Register a new connection from `peer`
+    fn new(kernel: &Kernel, peer: &str, pod: Option<kube::PodTarget>) -> Self {
+        let now = std::time::Instant::now();
+        let prompt = &kernel.prompt_config;
+        let id = kernel.agents.connect(peer);
+        Self {
+            id,
+            ids: kernel.agents.get(id).unwrap_or_default().ids(),
+            sequences: kernel.correlator.tracker(),
+            rates: kernel.rates.tracker(now),
+            limits: kernel.limits.tracker(),
+            flow: kernel.flow.bucket(now),
+            history: context::History::new(prompt.context_lines, prompt.context_decisions),
+            faulted: false,
+            pod,
+            recovered: None,
+            verdict: None,
+        }
+    }
+
+    /// Audit the session's totals so far; `closed` also unregisters the agent
+    fn summarize(&self, kernel: &Kernel, closed: bool) {
+        if let Some(status) = kernel.agents.get(self.id) {
+            if let Err(e) = kernel.audit_trail.record_session(&status, closed) {
+                warn!(
+                    "Failed to audit session summary for agent {}: {}",
+                    self.id, e
+                );
+            }
+        }
+        if closed {
+            kernel.agents.disconnect(self.id);
+        }
+    }
+
+    /// Remember an escalation verdict for the prompt context and the registry
+    fn decided(&mut self, kernel: &Kernel, action: &str, line: &str) {
+        self.history.push_decision(action, line);
+        kernel.agents.decision(self.id, action);
+        self.judged(action);
+    }
+
+    /// Keep the most severe action taken on the current line
+    fn judged(&mut self, action: &str) {
+        let rank = |action: &str| match action {
+            "KILL" => 2,
+            "PAUSE" | "FREEZE" | "CLAMP_CPU" | "CLAMP_MEMORY" | "RESTRICT" => 1,
+            _ => 0,
+        };
+        if self
+            .verdict
+            .as_deref()
+            .is_none_or(|v| rank(action) > rank(v))
+        {
+            self.verdict = Some(action.to_string());
+        }
+    }
+
+    /// Run a verdict through the decision policy; `None` leaves it as is
+    fn policy(&self, kernel: &Kernel, verdict: &policy::Verdict) -> Option<policy::Override> {
+        if kernel.policy.is_empty() {
+            return None;
+        }
+        let status = kernel.agents.get(self.id).unwrap_or_default();
+        let over = kernel
+            .policy
+            .decide(verdict, &status, &self.history.actions())?;
+        info!(
+            "📜 Policy {}: {} -> {}",
+            over.rule,
+            verdict.action,
+            over.action.as_str()
+        );
+        Some(over)
+    }
+}
+
+/// Process incoming log lines
+///
+/// Stops reading new lines on shutdown; a line already under analysis
+/// is finished and audited before returning.
+async fn process_connection<R: tokio::io::AsyncRead + Unpin>(
+    reader: BufReader<R>,
+    kernel: Arc<Kernel>,
+    peer: &str,
+    pod: Option<kube::PodTarget>,
+    mut acks: Option<ack::Acks>,
+) {
+    // What is cut off a long line is still run through the filter
+    let scan: line::Scan = {
+        let kernel = Arc::clone(&kernel);
+        Arc::new(move |text| kernel.filter.matches(text))
+    };
+    let mut lines = line::LineReader::new(reader, kernel.config.max_line_bytes).with_scan(scan);
+    let mut framed = false;
+    // Lines read so far: the ID acks and guard answers refer to
+    let mut seq = 0;
+    let ack = kernel.config.ack || acks.as_ref().is_some_and(ack::Acks::answers_every_line);
+    // Nothing is judged before the agent presents `--agent-token-file`'s token
+    let mut authenticated = kernel.config.agent_token.is_none();
+    let mut agent = AgentState::new(&kernel, peer, pod);
+    let mut assembler = multiline::Assembler::new(&kernel.multiline, kernel.config.max_line_bytes);
+    let interval = audit::SESSION_SUMMARY_INTERVAL;
+    let mut hourly = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+
+    loop {
+        let pending = assembler.as_ref().and_then(multiline::Assembler::deadline);
+        let (next, end) = tokio::select! {
+            next = lines.next_line() => match next {
+                Ok(Some(line)) => (Some(line), false),
+                _ => (None, true),
+            },
+            _ = tokio::time::sleep_until(pending.unwrap_or_else(tokio::time::Instant::now)),
+                if pending.is_some() => (None, false),
+            _ = hourly.tick() => {
+                agent.summarize(&kernel, false);
+                continue;
+            }
+            _ = kernel.shutdown.cancelled() => (None, true),
+        };
+        // A line, or the pending record once due
+        let record = match next {
+            Some(line) => {
+                seq += 1;
+                if framed != lines.framed() {
+                    framed = true;
+                    info!("📦 {} switched to framed mode", peer);
+                }
+                if line.truncated > 0 {
+                    kernel.stats.lock().await.lines_truncated += 1;
+                }
+                if line.invalid {
+                    kernel.stats.lock().await.lines_rejected += 1;
+                    warn!(
+                        "🧱 Frame from {} rejected: not valid UTF-8 ({})",
+                        peer,
+                        line::preview(&line.text, 50)
+                    );
+                    if ack {
+                        acknowledge(&kernel, &mut acks, seq, "REJECTED", framed, peer).await;
+                    }
+                    continue;
+                }
+                // A rule matched past the cut: judge the line with that window
+                let line = match line.caught {
+                    Some(caught) => {
+                        warn!(
+                            "✂️ Line from {} matches a rule past --max-line-bytes ({} bytes cut)",
+                            peer, line.truncated
+                        );
+                        format!("{} {}", line.text, caught)
+                    }
+                    None => line.text,
+                };
+
+                // The authenticating HELLO may follow a rejected frame
+                let introduction = seq == 1 || !authenticated;
+                if !authenticated {
+                    let token = agents::hello(&line).and_then(|hello| hello.token);
+                    authenticated = token.is_some_and(|token| {
+                        kernel.config.agent_token.as_deref() == Some(&audit::sha256_hex(&token))
+                    });
+                    if !authenticated {
+                        kernel.stats.lock().await.auth_failures += 1;
+                        warn!(
+                            "🔒 {} did not authenticate (HELLO token=) - connection closed",
+                            peer
+                        );
+                        if ack {
+                            acknowledge(&kernel, &mut acks, seq, "DENIED", framed, peer).await;
+                        }
+                        break;
+                    }
+                }
+                kernel.agents.line(agent.id);
+                if let Some(hello) = agents::hello(&line).filter(|_| introduction) {
+                    if let Some(ids) = kernel.agents.identify(agent.id, hello) {
+                        info!(
+                            "👋 {} is agent {} (session {})",
+                            peer, ids.agent_id, ids.session_id
+                        );
+                        agent.ids = ids;
+                    }
+                    if ack {
+                        acknowledge(&kernel, &mut acks, seq, "HELLO", framed, peer).await;
+                    }
+                    continue;
+                }
+                // Guard: answered before the next line is read, never judged as a log line
+                if let (Some(_), Some(action)) = (&acks, kernel.guard.request(&line)) {
+                    let answer = match contain::catch_unwind(check_action(&kernel, &agent, action))
+                        .await
+                    {
+                        Ok(answer) => answer,
+                        Err(panic) => {
+                            error!("💥 Panic while checking an action from {}: {}", peer, panic);
+                            "DENY"
+                        }
+                    };
+                    acknowledge(&kernel, &mut acks, seq, answer, framed, peer).await;
+                    continue;
+                }
+                match assembler.as_mut().filter(|_| !framed) {
+                    Some(assembler) => assembler.push(seq, line),
+                    None => Some(multiline::Record::single(seq, line)),
+                }
+            }
+            None => assembler.as_mut().and_then(multiline::Assembler::flush),
+        };
+
+        if let Some(record) = record {
+            if record.lines > 1 {
+                kernel.stats.lock().await.multiline_records += 1;
+            }
+            let verdict = judge(&kernel, &mut agent, &record.text).await;
+            if ack {
+                for id in record.ids() {
+                    acknowledge(&kernel, &mut acks, id, &verdict, framed, peer).await;
+                }
+            }
+        }
+        if end {
+            break;
+        }
+    }
+    agent.summarize(&kernel, true);
+    kernel.valve.forget(agent.id);
+}
+
+/// Judge one line (or assembled record): flow control, then the pipeline;
+/// returns the answer for its acks
+async fn judge(kernel: &Arc<Kernel>, agent: &mut AgentState, line: &str) -> String {
+    match kernel
+        .flow
+        .admit(&mut agent.flow, std::time::Instant::now())
+    {
+        flow::Admit::Pass | flow::Admit::Sample => {}
+        // Nothing a deterministic rule must see is dropped
+        flow::Admit::Drop if kernel.filter.undroppable(line) => {}
+        flow::Admit::Drop => {
+            kernel.stats.lock().await.lines_dropped += 1;
+            kernel.agents.dropped(agent.id);
+            let _ = kernel.audit_trail.record_entry(RecordInput {
+                input_log: &kernel.redactor.redact(line),
+                raw_input: Some(line),
+                reason: Some("over the [flow] limit"),
+                action: "DROPPED",
+                filtered: true,
+                profile: kernel.profile(),
+                agent: Some(&agent.ids),
+                ..Default::default()
+            });
+            return "DROPPED".to_string();
+        }
+        // High lane: judged at once, whatever the agent's backlog
+        flow::Admit::Wait(_) if kernel.filter.priority(line) == filter::Priority::High => {}
+        flow::Admit::Wait(delay) => {
+            kernel.stats.lock().await.lines_throttled += 1;
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = kernel.shutdown.cancelled() => {}
+            }
+        }
+    }
+    agent.verdict = None;
+    let verdict = match contain::catch_unwind(kernel.stages.run(kernel, agent, line)).await {
+        Ok(()) => agent.verdict.take(),
+        Err(panic) => {
+            pipeline_error(kernel, agent, line, &panic).await;
+            Some("PIPELINE_ERROR".to_string())
+        }
+    };
+    agent.history.push_line(line);
+    // Filtered lines are sustained without a verdict
+    verdict.unwrap_or_else(|| "SUSTAIN".to_string())
+}
+
+/// Answer line `id` on the connection's write side, if it has one
+async fn acknowledge(
+    kernel: &Kernel,
+    acks: &mut Option<ack::Acks>,
+    id: u64,
+    action: &str,
+    framed: bool,
+    peer: &str,
+) {
+    let Some(acks) = acks else { return };
+    if acks.send(id, action, framed) {
+        return;
+    }
+    kernel.stats.lock().await.acks_dropped += 1;
+    if acks.dropped() == 1 {
+        warn!("📭 {} is not reading its acks - dropping them", peer);
+    }
+}
+
+/// Answer a `CHECK` request (see `guard`): "ALLOW" or "DENY"
+async fn check_action(kernel: &Kernel, agent: &AgentState, action: &str) -> &'static str {
+    let start = std::time::Instant::now();
+    let input = kernel.redactor.redact(action);
+    let mut denial = audit::DenialEvent::new(&agent.ids, &input, action);
+    let parsed = parse::parse(action);
+    let rule = kernel.filter.check_parsed(&parsed);
+    let allowed = match (kernel.limits.breach(&parsed), rule) {
+        (Some(hit), _) => {
+            denial.rule = Some(hit.id);
+            denial.reason = Some(hit.line);
+            false
+        }
+        (None, Some(rule)) if rule.action == filter::RuleAction::Kill => {
+            denial.rule = Some(rule.name.clone());
+            false
+        }
+        (None, None) if !kernel.guard.analyze_unmatched => true,
+        (None, rule) => {
+            denial.rule = rule.map(|r| r.name.clone());
+            let prompt_log = parsed.render();
+            let prompt_log = kernel.redactor.redact(&prompt_log);
+            let history = agent.history.render();
+            let context = kernel.guard.context(&kernel.redactor.redact(&history));
+            info!("🛂 [CHECK] {}", line::preview(&input, 50));
+            if !kernel.health.healthy() {
+                denial.error = Some("LLM unavailable".to_string());
+                false
+            } else {
+                match kernel.llm.analyze(&prompt_log, &context).await {
+                    Ok(answer) => {
+                        let decision = answer.decision;
+                        let allowed = kernel
+                            .guard
+                            .allows(decision.action.as_str(), decision.confidence);
+                        denial.verdict = Some(decision.action.to_string());
+                        denial.confidence = Some(decision.confidence);
+                        denial.reason = decision.reason;
+                        denial.model = Some(answer.model);
+                        allowed
+                    }
+                    Err(e) => {
+                        kernel.stats.lock().await.error(e.category());
+                        denial.error = Some(e.to_string());
+                        false
+                    }
+                }
+            }
+        }
+    };
+    denial.latency_ms = start.elapsed().as_millis() as u64;
+
+    let mut s = kernel.stats.lock().await;
+    if allowed {
+        s.checks_allowed += 1;
+        info!("✅ [ALLOW] {}", line::preview(&input, 50));
+        return "ALLOW";
+    }
+    s.checks_denied += 1;
+    drop(s);
+    let verdict = denial
+        .verdict
+        .as_ref()
+        .zip(denial.confidence)
+        .map(|(verdict, confidence)| format!("{} {}%", verdict, confidence));
+    let why = [&denial.rule, &verdict, &denial.error]
+        .into_iter()
+        .flatten()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(" - ");
+    warn!("⛔ [DENY] {} ({})", line::preview(&input, 50), why);
+    if let Err(e) = kernel.audit_trail.record_denial(&denial) {
+        warn!("⛔ Failed to audit denial: {}", e);
+    }
+    "DENY"
+}
+
+/// Sample `--target-pid` and escalate crossed `[resource]` thresholds
+/// in a session of their own
+async fn watch_resources(kernel: Arc<Kernel>, config: resource::ResourceConfig, pid: u32) {
+    let mut agent = AgentState::new(&kernel, &format!("resource:{}", pid), None);
+    let mut monitor = resource::ResourceMonitor::new(&config, pid);
+    let mut ticker = tokio::time::interval(config.interval());
+    let mut failing = false;
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = kernel.shutdown.cancelled() => break,
+        }
+        let sample = match tokio::task::spawn_blocking(move || resource::sample(pid)).await {
+            Ok(Ok(sample)) => sample,
+            Ok(Err(e)) => {
+                if !failing {
+                    warn!("🧮 Cannot sample PID {}: {}", pid, e);
+                    failing = true;
+                }
+                continue;
+            }
+            Err(_) => continue,
+        };
+        failing = false;
+        let start = std::time::Instant::now();
+        for hit in monitor.observe(sample, start) {
+            kernel.stats.lock().await.resource_triggers += 1;
+            warn!("🧮 [RESOURCE] {}", hit.line);
+            escalate(
+                &kernel,
+                &mut agent,
+                &hit.line,
+                hit.id,
+                config.action,
+                filter::Priority::Normal,
+                start,
+            )
+            .await;
+        }
+    }
+    agent.summarize(&kernel, true);
+}
+
+/// Watch the outbound connections of `--target-pid` and escalate new
+/// destinations and `[egress]` volume thresholds in a session of their own
+async fn watch_egress(kernel: Arc<Kernel>, config: egress::EgressConfig, pid: u32) {
+    let mut agent = AgentState::new(&kernel, &format!("egress:{}", pid), None);
+    let mut monitor = egress::EgressMonitor::new(&config, pid);
+    let mut ticker = tokio::time::interval(config.interval());
+    let mut probe: Option<egress::Probe> = None;
+    let mut failing = false;
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = kernel.shutdown.cancelled() => break,
+        }
+        // The probe moves to the blocking pool and back; reopened after an error
+        let current = probe.take();
+        let reading = tokio::task::spawn_blocking(move || {
+            let mut probe = match current {
+                Some(probe) => probe,
+                None => egress::Probe::open(pid)?,
+            };
+            let observation = probe.read()?;
+            Ok::<_, std::io::Error>((probe, observation))
+        })
+        .await;
+        let observation = match reading {
+            Ok(Ok((current, observation))) => {
+                probe = Some(current);
+                observation
+            }
+            Ok(Err(e)) => {
+                if !failing {
+                    warn!("🌐 Cannot watch egress of PID {}: {}", pid, e);
+                    failing = true;
+                }
+                continue;
+            }
+            Err(_) => continue,
+        };
+        failing = false;
+        let start = std::time::Instant::now();
+        for hit in monitor.observe(observation, start) {
+            kernel.stats.lock().await.egress_triggers += 1;
+            warn!("🌐 [EGRESS] {}", hit.line);
+            escalate(
+                &kernel,
+                &mut agent,
+                &hit.line,
+                hit.id,
+                config.action,
+                filter::Priority::Normal,
+                start,
+            )
+            .await;
+        }
+    }
+    agent.summarize(&kernel, true);
+}
+
+/// Escalate `[credential]` events raised by `--target-pid` or its children
+/// in a session of their own
+#[cfg(windows)]
+async fn watch_credentials(kernel: Arc<Kernel>, config: credential::CredentialConfig, pid: u32) {
+    let (events_tx, mut events) = tokio::sync::mpsc::channel::<String>(1024);
+    let sessions = match credential::start(&config, events_tx) {
+        Ok(sessions) => sessions,
+        Err(e) => {
+            error!("🔑 Credential monitor failed: {}", e);
+            return;
+        }
+    };
+    let mut agent = AgentState::new(&kernel, &format!("credential:{}", pid), None);
+    let mut monitor = credential::CredentialMonitor::new(&config, pid);
+    let mut refresh = tokio::time::interval(Duration::from_secs(5));
+    loop {
+        let line = tokio::select! {
+            _ = refresh.tick() => {
+                if let Ok(Ok(children)) =
+                    tokio::task::spawn_blocking(move || resource::children(pid)).await
+                {
+                    monitor.set_children(children);
+                }
+                continue;
+            }
+            next = events.recv() => match next {
+                Some(line) => line,
+                None => break,
+            },
+            _ = kernel.shutdown.cancelled() => break,
+        };
+        let start = std::time::Instant::now();
+        let Some(hit) = monitor.observe(&line, start) else {
+            continue;
+        };
+        kernel.stats.lock().await.credential_triggers += 1;
+        warn!("🔑 [CREDENTIAL] {}", hit.line);
+        escalate(
+            &kernel,
+            &mut agent,
+            &hit.line,
+            &hit.id,
+            config.action,
+            filter::Priority::High,
+            start,
+        )
+        .await;
+    }
+    agent.summarize(&kernel, true);
+    // Dropping the receiver releases a callback blocked on a full channel
+    drop(events);
+    let _ = tokio::task::spawn_blocking(move || {
+        sessions.into_iter().for_each(etw::Session::stop);
+    })
+    .await;
+}
+
+/// Kill on any access to a `[[honeypot]]` decoy, audited as a `honeypot`
+/// event ahead of the fast-path decision
+async fn watch_honeypots(kernel: Arc<Kernel>, decoys: Vec<honeypot::HoneypotDef>) {
+    let (trips_tx, mut trips) = tokio::sync::mpsc::channel(256);
+    let _watcher = match honeypot::start(&decoys, trips_tx) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            error!("🍯 Honeypot monitor failed: {}", e);
+            return;
+        }
+    };
+    let mut agent = AgentState::new(&kernel, "honeypot", None);
+    let repeat = std::time::Duration::from_millis(honeypot::REPEAT_MS);
+    let mut tripped: std::collections::HashMap<usize, std::time::Instant> =
+        std::collections::HashMap::new();
+    loop {
+        let trip = tokio::select! {
+            next = trips.recv() => match next {
+                Some(trip) => trip,
+                None => break,
+            },
+            _ = kernel.shutdown.cancelled() => break,
+        };
+        let start = std::time::Instant::now();
+        if tripped
+            .get(&trip.decoy)
+            .is_some_and(|at| start.duration_since(*at) < repeat)
+        {
+            continue;
+        }
+        tripped.insert(trip.decoy, start);
+        let decoy = decoys[trip.decoy].name();
+        let line = format!("HONEYPOT {} {}", decoy, trip.access);
+        kernel.stats.lock().await.honeypot_trips += 1;
+        error!("🍯 [HONEYPOT] {}", line);
+        let event = audit::HoneypotEvent::new(&decoy, trip.access, kernel.config.target_pid);
+        if let Err(e) = kernel.audit_trail.record_honeypot(&event) {
+            error!("Audit log write failed: {}", e);
+        }
+        let action = fast_kill(&kernel, &agent, &line, honeypot::RULE, start).await;
+        agent.decided(&kernel, action, &line);
+    }
+    agent.summarize(&kernel, true);
+}
+
+/// Turn `[fs]` changes into synthetic lines in a session of their own:
+/// watched paths are judged like log lines, sensitive ones escalate in the
+/// high lane
+async fn watch_files(kernel: Arc<Kernel>, config: fswatch::FsConfig, audit_log: PathBuf) {
+    let (changes_tx, mut changes) = tokio::sync::mpsc::channel(1024);
+    let _watcher = match fswatch::start(&config, changes_tx) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            error!("📁 File-system monitor failed: {}", e);
+            return;
+        }
+    };
+    // Events carry absolute paths
+    let own = std::path::absolute(&audit_log).unwrap_or(audit_log);
+    let mut monitor = fswatch::FsMonitor::new(&config, &[own]);
+    let mut agent = AgentState::new(&kernel, "fs", None);
+    loop {
+        let (change, path) = tokio::select! {
+            next = changes.recv() => match next {
+                Some(next) => next,
+                None => break,
+            },
+            _ = kernel.shutdown.cancelled() => break,
+        };
+        let start = std::time::Instant::now();
+        let Some(hit) = monitor.observe(change, &path, start) else {
+            continue;
+        };
+        if !hit.sensitive {
+            judge(&kernel, &mut agent, &hit.line).await;
+            continue;
+        }
+        kernel.stats.lock().await.fs_triggers += 1;
+        warn!("📁 [FS] {}", hit.line);
+        escalate(
+            &kernel,
+            &mut agent,
+            &hit.line,
+            fswatch::SENSITIVE_RULE,
+            config.action,
+            filter::Priority::High,
+            start,
+        )
+        .await;
+    }
+    agent.summarize(&kernel, true);
+}
+
+/// Judge a synthetic line (rate anomaly, flood) by its rule's action, in
+/// the given analysis lane
+async fn escalate(
+    kernel: &Kernel,
+    agent: &mut AgentState,
+    line: &str,
+    rule: &str,
+    action: filter::RuleAction,
+    priority: filter::Priority,
+    start: std::time::Instant,
+) {
+    match action {
+        filter::RuleAction::Kill => {
+            let action = fast_kill(kernel, agent, line, rule, start).await;
+            agent.decided(kernel, action, line);
+        }
+        filter::RuleAction::Analyze => {
+            let context = agent.history.render();
+            let (decision, action) =
+                analyze(kernel, agent, line, line, &context, rule, priority, start).await;
+            match decision {
+                Some(_) => agent.decided(kernel, &action, line),
+                None => agent.judged(&action),
+            }
+        }
+    }
+}
+
+/// Audit and announce a panic while judging `line`, then put the agent in
+/// degraded mode: its per-connection state may be inconsistent
+async fn pipeline_error(kernel: &Kernel, agent: &mut AgentState, line: &str, panic: &str) {
+    kernel.stats.lock().await.pipeline_errors += 1;
+    let record_id = kernel
+        .audit_trail
+        .record_entry(RecordInput {
+            input_log: &kernel.redactor.redact(line),
+            raw_input: Some(line),
+            action: "PIPELINE_ERROR",
+            reason: Some(panic),
+            profile: kernel.profile(),
+            agent: Some(&agent.ids),
+            ..Default::default()
+        })
+        .unwrap_or(0);
+    let _decision = decision_span(record_id).entered();
+    error!("═══════════════════════════════════════════════════════════════");
+    error!("  💥 PIPELINE ERROR - panic while judging a line");
+    error!("═══════════════════════════════════════════════════════════════");
+    error!("  Decision ID: {}", record_id);
+    error!("  Agent: {}", agent.id);
+    error!("  Panic: {}", panic);
+    error!("═══════════════════════════════════════════════════════════════");
+    if !agent.faulted {
+        agent.faulted = true;
+        kernel.agents.degrade(agent.id);
+        warn!(
+            "⚠️ Agent {} follows the degraded-mode policy until it reconnects",
+            agent.id
+        );
+    }
+}
+
+/// Deterministic KILL decided by a rule (no LLM call)
+///
+/// Returns the action taken after the decision policy.
+async fn fast_kill(
+    kernel: &Kernel,
+    agent: &AgentState,
+    input: &str,
+    rule: &str,
+    start: std::time::Instant,
+) -> &'static str {
+    let elapsed = start.elapsed();
+    let verdict = policy::Verdict {
+        action: "KILL",
+        confidence: 100,
+        rule: Some(rule),
+        filtered: true,
+        ..Default::default()
+    };
+    let over = agent.policy(kernel, &verdict);
+    let action = over.as_ref().map_or("KILL", |o| o.action.as_str());
+    let mut s = kernel.stats.lock().await;
+    if action == "KILL" {
+        s.fast_path_kills += 1;
+    }
+    let suppressed = settle(kernel, agent.id, &mut s, action, over.as_ref());
+
+    let record_id = kernel
+        .audit_trail
+        .record_entry(RecordInput {
+            input_log: &kernel.redactor.redact(input),
+            raw_input: Some(input),
+            action,
+            confidence: 100,
+            filtered: true,
+            latency_ms: elapsed.as_micros() as u64, // Use microseconds for filter
+            rule: Some(rule),
+            suppressed: suppressed.as_deref(),
+            policy: over.as_ref().map(|o| o.rule.as_str()),
+            verdict: over.as_ref().map(|_| "KILL"),
+            profile: kernel.profile(),
+            agent: Some(&agent.ids),
+            ..Default::default()
+        })
+        .unwrap_or(0);
+
+    match action {
+        "KILL" => trigger_kill(
+            kernel,
+            agent,
+            record_id,
+            &format!("{}μs (fast path)", elapsed.as_micros()),
+            100,
+            Some(rule),
+            None,
+            suppressed.as_deref(),
+            false,
+        ),
+        "PAUSE" => trigger_pause(
+            kernel,
+            agent,
+            record_id,
+            over.as_ref().map(|o| o.rule.as_str()),
+        ),
+        "FREEZE" | "CLAMP_CPU" | "CLAMP_MEMORY" => trigger_clamp(
+            kernel,
+            record_id,
+            action,
+            over.as_ref().map(|o| o.rule.as_str()),
+        ),
+        "RESTRICT" => trigger_restrict(kernel, record_id, over.as_ref().map(|o| o.rule.as_str())),
+        _ => info!(
+            decision_id = record_id,
+            "🟢 [SUSTAIN] ID:{} rule {} (policy)", record_id, rule
+        ),
+    }
+    action
+}
+
+/// Audit `policy` of a FAIL verdict decided by `--on-uncertain`
+const ON_UNCERTAIN: &str = "on-uncertain";
+
+/// LLM analysis of a suspicious line (or aggregated sequence / rate context)
+///
+/// `input` is audited; `prompt_log` is what the LLM sees (the structured
+/// rendering of a JSON / logfmt line), with the agent's recent `context`.
+/// All three are redacted here. `priority` picks the analysis lane.
+/// Returns the LLM decision (`None` if the LLM could not be reached and
+/// the degraded-mode policy decided) and the action taken after the
+/// decision policy.
+#[allow(clippy::too_many_arguments)]
+async fn analyze(
+    kernel: &Kernel,
+    agent: &AgentState,
+    input: &str,
+    prompt_log: &str,
+    context: &str,
+    rule: &str,
+    priority: filter::Priority,
+    start: std::time::Instant,
+) -> (Option<llm::Decision>, String) {
+    let raw_input = input;
+    let input = kernel.redactor.redact(raw_input);
+    let prompt_log = kernel.redactor.redact(prompt_log);
+    let context = kernel.redactor.redact(context);
+    let context = (!context.is_empty()).then_some(&*context);
+    info!("🔍 [ANALYZE] {}", line::preview(&input, 50));
+
+    let mut shadow = None;
+    // High lane: the priority timeout applies unless the SLO deadline is tighter
+    let high = priority == filter::Priority::High;
+    let lane = kernel
+        .priority
+        .timeout()
+        .filter(|t| high && kernel.slo.enforced().is_none_or(|d| *t < d));
+    let mut ticket = agent.recovered.as_ref().map(|r| r.ticket);
+    let recovered_hash = agent.recovered.as_ref().map(|r| r.input_hash.as_str());
+    // Degraded mode: don't wait on a model that just failed its canary, and
+    // don't trust a connection whose state a panic may have left inconsistent
+    let result = if agent.faulted {
+        Err(llm::LlmError::Faulted)
+    } else if kernel.health.healthy() {
+        // Persist the line until its decision is audited
+        if let (Some(journal), None) = (&kernel.journal, ticket) {
+            let line = queue::QueuedLine {
+                input_log: input.to_string(),
+                input_hash: audit::sha256_hex(raw_input),
+                prompt_log: prompt_log.to_string(),
+                context: context.unwrap_or_default().to_string(),
+                rule: rule.to_string(),
+                priority,
+            };
+            ticket = journal.push(&line).unwrap_or_else(|e| {
+                warn!("📥 Failed to persist line: {}", e);
+                None
+            });
+        }
+        shadow = kernel
+            .shadow
+            .as_ref()
+            .and_then(|s| s.start(&prompt_log, context.unwrap_or("")));
+        let call = kernel
+            .batcher
+            .analyze(&prompt_log, context.unwrap_or(""), priority);
+        match lane.or(kernel.slo.enforced()) {
+            Some(deadline) => {
+                let at = tokio::time::Instant::from_std(start + deadline);
+                match tokio::time::timeout_at(at, call).await {
+                    Ok(result) => result,
+                    Err(_) if lane.is_some() => {
+                        Err(llm::LlmError::PriorityTimeout(deadline.as_millis()))
+                    }
+                    Err(_) => Err(llm::LlmError::Deadline(deadline.as_millis())),
+                }
+            }
+            None => call.await,
+        }
+    } else {
+        Err(llm::LlmError::Unhealthy)
+    };
+
+    match result {
+        Ok(answer) => {
+            let decision = answer.decision;
+            let elapsed = start.elapsed();
+            let latency_ms = elapsed.as_millis() as u64;
+            let verdict = policy::Verdict {
+                action: decision.action.as_str(),
+                confidence: decision.confidence,
+                rule: Some(rule),
+                reason: decision.reason.as_deref(),
+                model: Some(&answer.model),
+                filtered: false,
+            };
+            let over = agent.policy(kernel, &verdict);
+            let action = over.as_ref().map_or(decision.action, |o| o.action.into());
+            // Uncertainty is not safety: a FAIL no policy rule decided
+            // follows --on-uncertain
+            let uncertain = action == llm::Action::Fail;
+            let action = match uncertain {
+                true => kernel.config.on_uncertain.action(),
+                false => action,
+            };
+            let enforced = uncertain && action != llm::Action::Fail;
+            let policy = match enforced {
+                true => Some(ON_UNCERTAIN),
+                false => over.as_ref().map(|o| o.rule.as_str()),
+            };
+            let mut s = kernel.stats.lock().await;
+            s.analyzed += 1;
+            if uncertain {
+                s.uncertain += 1;
+            }
+            s.total_latency_ms += latency_ms;
+            if answer.fallback {
+                s.llm_fallbacks += 1;
+            }
+            if answer.batch.is_some() {
+                s.batched += 1;
+            }
+            if kernel.slo.deadline().is_some_and(|d| elapsed > d) {
+                s.late_verdicts += 1;
+            }
+            if high {
+                s.priority_lines += 1;
+            }
+            let suppressed = settle(kernel, agent.id, &mut s, action.as_str(), over.as_ref());
+
+            // Record decision
+            let record_id = kernel
+                .audit_trail
+                .record_entry(RecordInput {
+                    input_log: &input,
+                    raw_input: Some(raw_input),
+                    context,
+                    action: action.as_str(),
+                    confidence: decision.confidence,
+                    model_confidence: decision.model_confidence,
+                    filtered: false,
+                    latency_ms,
+                    raw_response: Some(decision.raw_response.clone()),
+                    rendered_prompt: Some(&decision.prompt),
+                    rule: Some(rule),
+                    reason: decision.reason.as_deref(),
+                    model_fingerprint: Some(&answer.model),
+                    batch_size: answer.batch,
+                    suppressed: suppressed.as_deref(),
+                    policy,
+                    verdict: (over.is_some() || enforced).then(|| decision.action.as_str()),
+                    profile: kernel.profile(),
+                    input_hash: recovered_hash,
+                    recovered: agent.recovered.is_some(),
+                    agent: Some(&agent.ids),
+                    ..Default::default()
+                })
+                .unwrap_or(0);
+            if let Some(shadow) = shadow {
+                shadow.finish(record_id, Some(decision.action.as_str()));
+            }
+            kernel.dequeue(ticket);
+
+            if uncertain {
+                // CRITICAL: LLM response was unparseable - uncertainty is not safety!
+                let _decision = decision_span(record_id).entered();
+                warn!("═══════════════════════════════════════════════════════════════");
+                warn!("  ⚠️ PARSE FAILURE - LLM response unclear");
+                warn!("═══════════════════════════════════════════════════════════════");
+                warn!("  Decision ID: {}", record_id);
+                warn!("  Latency: {}ms", latency_ms);
+                warn!(
+                    "  Raw response: {}",
+                    line::preview(&decision.raw_response, 100)
+                );
+                warn!("  On uncertain: {}", action);
+                warn!("═══════════════════════════════════════════════════════════════");
+            }
+            match action {
+                llm::Action::Kill => trigger_kill(
+                    kernel,
+                    agent,
+                    record_id,
+                    &format!("{}ms", latency_ms),
+                    decision.confidence,
+                    Some(rule),
+                    decision.reason.as_deref(),
+                    suppressed.as_deref(),
+                    true,
+                ),
+                llm::Action::Pause => trigger_pause(kernel, agent, record_id, policy),
+                llm::Action::Freeze | llm::Action::ClampCpu | llm::Action::ClampMemory => {
+                    trigger_clamp(kernel, record_id, action.as_str(), policy)
+                }
+                llm::Action::Restrict => trigger_restrict(kernel, record_id, policy),
+                // Flagged for manual review (see above)
+                llm::Action::Fail => {}
+                llm::Action::Sustain => info!(
+                    decision_id = record_id,
+                    "🟢 [SUSTAIN] ID:{} {}ms {}%", record_id, latency_ms, decision.confidence
+                ),
+            }
+            (Some(decision), action.to_string())
+        }
+        Err(e) => {
+            let elapsed = start.elapsed();
+            let latency_ms = elapsed.as_millis() as u64;
+            let missed = e.missed();
+            let on_miss = match lane {
+                Some(_) => kernel.priority.on_timeout,
+                None => kernel.slo.on_miss,
+            };
+            let degraded = on_miss
+                .filter(|_| missed)
+                .or_else(|| kernel.schedule.active().and_then(|p| p.degraded_policy))
+                .unwrap_or(kernel.health_config.degraded_policy);
+            let action = match degraded {
+                health::DegradedPolicy::Sustain => "SUSTAIN",
+                health::DegradedPolicy::Kill => "KILL",
+            };
+            let reason = if agent.faulted {
+                "degraded-mode policy: pipeline error on this connection"
+            } else if missed && lane.is_some() {
+                "priority policy: LLM missed the priority timeout"
+            } else if missed {
+                "deadline policy: LLM missed the decision deadline"
+            } else {
+                "degraded-mode policy: LLM unavailable"
+            };
+            warn!("⚠️ {} - degraded policy: {}", e, action);
+            let verdict = policy::Verdict {
+                action,
+                rule: Some(rule),
+                reason: Some(reason),
+                ..Default::default()
+            };
+            let over = agent.policy(kernel, &verdict);
+            let verdict = action;
+            let action = over.as_ref().map_or(verdict, |o| o.action.as_str());
+            let mut s = kernel.stats.lock().await;
+            match e {
+                llm::LlmError::PriorityTimeout(_) => s.priority_timeouts += 1,
+                llm::LlmError::Deadline(_) => s.deadline_misses += 1,
+                _ => s.degraded += 1,
+            }
+            s.error(e.category());
+            if high {
+                s.priority_lines += 1;
+            }
+            let suppressed = settle(kernel, agent.id, &mut s, action, over.as_ref());
+
+            let record_id = kernel
+                .audit_trail
+                .record_entry(RecordInput {
+                    input_log: &input,
+                    raw_input: Some(raw_input),
+                    context,
+                    action,
+                    confidence: 0,
+                    filtered: false,
+                    latency_ms,
+                    raw_response: Some(format!("ERROR: {}", e)),
+                    error: Some(e.category()),
+                    rule: Some(rule),
+                    suppressed: suppressed.as_deref(),
+                    policy: over.as_ref().map(|o| o.rule.as_str()),
+                    verdict: over.as_ref().map(|_| verdict),
+                    profile: kernel.profile(),
+                    input_hash: recovered_hash,
+                    recovered: agent.recovered.is_some(),
+                    agent: Some(&agent.ids),
+                    ..Default::default()
+                })
+                .unwrap_or(0);
+            if let Some(shadow) = shadow {
+                shadow.finish(record_id, None);
+            }
+            kernel.dequeue(ticket);
+
+            // Keep the line for a retrospective verdict once the LLM is back
+            if let (Some(spill), false) = (&kernel.spill, agent.faulted) {
+                let entry = backfill::SpillEntry {
+                    record_id,
+                    input_log: input.to_string(),
+                    input_hash: recovered_hash
+                        .map_or_else(|| audit::sha256_hex(raw_input), str::to_string),
+                    prompt_log: prompt_log.to_string(),
+                    context: context.unwrap_or_default().to_string(),
+                    rule: rule.to_string(),
+                };
+                if let Err(e) = spill.push(&entry) {
+                    warn!("🕰️ Failed to spill line for backfill: {}", e);
+                }
+            }
+
+            match action {
+                "KILL" => trigger_kill(
+                    kernel,
+                    agent,
+                    record_id,
+                    &format!("{}ms (degraded)", latency_ms),
+                    0,
+                    Some(rule),
+                    Some(reason),
+                    suppressed.as_deref(),
+                    false,
+                ),
+                "PAUSE" => trigger_pause(
+                    kernel,
+                    agent,
+                    record_id,
+                    over.as_ref().map(|o| o.rule.as_str()),
+                ),
+                "FREEZE" | "CLAMP_CPU" | "CLAMP_MEMORY" => trigger_clamp(
+                    kernel,
+                    record_id,
+                    action,
+                    over.as_ref().map(|o| o.rule.as_str()),
+                ),
+                "RESTRICT" => {
+                    trigger_restrict(kernel, record_id, over.as_ref().map(|o| o.rule.as_str()))
+                }
+                _ => {}
+            }
+            (None, action.to_string())
+        }
+    }
+}
+
+/// Re-analyze the lines spilled while the LLM was down
+///
+/// Verdicts are audited as `backfilled` and never executed; entries not
+/// reached because the LLM failed again stay spilled.
+async fn backfill(kernel: &Kernel) {
+    let Some(ref spill) = kernel.spill else {
+        return;
+    };
+    if spill.is_empty() {
+        return;
+    }
+    let entries = match spill.take() {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!("🕰️ Failed to read spill file: {}", e);
+            return;
+        }
+    };
+    info!(
+        "🕰️ Backfill: re-analyzing {} line(s) from the LLM outage",
+        entries.len()
+    );
+
+    let (mut done, mut kills) = (0, 0);
+    for entry in &entries {
+        let start = std::time::Instant::now();
+        let answer = match kernel.llm.analyze(&entry.prompt_log, &entry.context).await {
+            Ok(answer) => answer,
+            Err(e) => {
+                kernel.stats.lock().await.error(e.category());
+                warn!(
+                    "🕰️ Backfill interrupted: {} - {} line(s) kept",
+                    e,
+                    entries.len() - done
+                );
+                for entry in &entries[done..] {
+                    let _ = spill.push(entry);
+                }
+                break;
+            }
+        };
+        let decision = &answer.decision;
+        let record_id = kernel
+            .audit_trail
+            .record_entry(RecordInput {
+                input_log: &entry.input_log,
+                input_hash: Some(&entry.input_hash),
+                context: (!entry.context.is_empty()).then_some(&*entry.context),
+                action: decision.action.as_str(),
+                confidence: decision.confidence,
+                model_confidence: decision.model_confidence,
+                latency_ms: start.elapsed().as_millis() as u64,
+                raw_response: Some(decision.raw_response.clone()),
+                rendered_prompt: Some(&decision.prompt),
+                rule: Some(&entry.rule),
+                reason: decision.reason.as_deref(),
+                model_fingerprint: Some(&answer.model),
+                profile: kernel.profile(),
+                backfill_of: Some(entry.record_id),
+                ..Default::default()
+            })
+            .unwrap_or(0);
+        let mut s = kernel.stats.lock().await;
+        s.backfilled += 1;
+        if decision.action == llm::Action::Kill {
+            s.backfill_kills += 1;
+            kills += 1;
+            error!(
+                decision_id = record_id,
+                "🕰️ Backfill ID:{}: decision {} would have been KILL ({}% {})",
+                record_id,
+                entry.record_id,
+                decision.confidence,
+                decision.reason.as_deref().unwrap_or("-")
+            );
+        }
+        done += 1;
+    }
+    info!(
+        "🕰️ Backfill complete: {} line(s) re-analyzed, {} would have been KILL",
+        done, kills
+    );
+}
+
+/// Judge the lines a previous run accepted but never judged
+///
+/// They are replayed as one pseudo-agent (`recovered`) through the usual
+/// analysis; a line the LLM cannot answer gets the degraded-mode policy.
+async fn recover(kernel: &Kernel, lines: Vec<(queue::Ticket, queue::QueuedLine)>) {
+    info!(
+        "📥 Replaying {} line(s) accepted but not judged before the restart",
+        lines.len()
+    );
+    let mut agent = AgentState::new(kernel, "recovered", None);
+    for (ticket, line) in lines {
+        if kernel.shutdown.is_cancelled() {
+            break;
+        }
+        agent.recovered = Some(Recovered {
+            ticket,
+            input_hash: line.input_hash,
+        });
+        kernel.stats.lock().await.recovered += 1;
+        let start = std::time::Instant::now();
+        analyze(
+            kernel,
+            &agent,
+            &line.input_log,
+            &line.prompt_log,
+            &line.context,
+            &line.rule,
+            line.priority,
+            start,
+        )
+        .await;
+    }
+    agent.summarize(kernel, true);
+}
+
+/// Count the action taken on a verdict; a KILL must also pass the kill
+/// valve, which returns why it is suppressed
+fn settle(
+    kernel: &Kernel,
+    agent: u64,
+    s: &mut Stats,
+    action: &str,
+    over: Option<&policy::Override>,
+) -> Option<String> {
+    if over.is_some() {
+        s.policy_overrides += 1;
+    }
+    match action {
+        "KILL" => {
+            s.kills += 1;
+            let suppressed = kernel.valve.check(agent);
+            if suppressed.is_some() {
+                s.kills_suppressed += 1;
+            }
+            suppressed
+        }
+        "PAUSE" => {
+            s.pauses += 1;
+            None
+        }
+        "FREEZE" | "CLAMP_CPU" | "CLAMP_MEMORY" => {
+            s.clamps += 1;
+            None
+        }
+        "RESTRICT" => {
+            s.restrictions += 1;
+            None
+        }
+        _ => None,
+    }
+}
+
+/// Span tying the logs about one decision to its audit record
+/// (`decision_id` in JSON logs)
+fn decision_span(record_id: u64) -> tracing::Span {
+    tracing::info_span!("decision", decision_id = record_id)
+}
+
+/// Announce a KILL decision and terminate the target
+///
+/// `llm`: the model decided the kill, so it waits for the health canary;
+/// deterministic kills (rules, degraded policy) are armed regardless
+#[allow(clippy::too_many_arguments)]
+fn trigger_kill(
+    kernel: &Kernel,
+    agent: &AgentState,
+    record_id: u64,
+    latency: &str,
+    confidence: u32,
+    rule: Option<&str>,
+    reason: Option<&str>,
+    suppressed: Option<&str>,
+    llm: bool,
+) {
+    let _decision = decision_span(record_id).entered();
+    error!("═══════════════════════════════════════════════════════════════");
+    error!("  🚨 KILL SWITCH ACTIVATED!");
+    error!("═══════════════════════════════════════════════════════════════");
+    error!("  Decision ID: {}", record_id);
+    error!("  Latency: {}", latency);
+    error!("  Confidence: {}%", confidence);
+    if let Some(rule) = rule {
+        error!("  Rule: {}", rule);
+    }
+    if let Some(reason) = reason {
+        error!("  Reason: {}", reason);
+    }
+    error!("═══════════════════════════════════════════════════════════════");
+
+    if let Some(ref explainer) = kernel.explainer {
+        if let Some(record) = kernel.audit_trail.get(record_id) {
+            let context = kernel.redactor.redact(&agent.history.render()).into_owned();
+            explainer.explain(record, context, kernel.health.healthy());
+        }
+    }
+
+    if let Some(suppressed) = suppressed {
+        error!("  🧯 KILL SUPPRESSED by the kill valve - {}", suppressed);
+        return;
+    }
+    if dry_run(kernel) || standby(kernel) {
+        return;
+    }
+    let armed = kernel.health.armed_for(llm);
+    if let Some(ref authorizer) = kernel.authorizer {
+        if armed && countersign(kernel, agent, authorizer, record_id) {
+            return;
+        }
+    }
+    // Snapshotted targets are killed once their snapshots are taken
+    let snapshotted = match kernel.snapshots {
+        Some(ref snapshots) if armed => snapshots.kill(record_id, snapshot_targets(kernel)),
+        _ => false,
+    };
+    if let Some(pid) = kernel.config.target_pid.filter(|_| !snapshotted) {
+        if armed {
+            kill_process(pid);
+        } else if kernel.health.held_until().is_some() {
+            warn!(
+                "🔒 Kill actions disarmed by an operator - PID {} left running",
+                pid
+            );
+        } else if let Err(e) = kernel.health.checklist() {
+            warn!(
+                "🔒 Kill actions disarmed (self-test failing: {}) - PID {} left running",
+                e, pid
+            );
+        } else {
+            warn!(
+                "🔒 Kill actions disarmed (LLM canary not yet passed) - PID {} left running",
+                pid
+            );
+        }
+    }
+    if let Some(ref container) = kernel
+        .config
+        .target_container
+        .as_ref()
+        .filter(|_| !snapshotted)
+    {
+        if armed {
+            kill_container(container);
+        } else {
+            warn!(
+                "🔒 Kill actions disarmed - container {} left running",
+                container
+            );
+        }
+    }
+    if let Some(vm) = kernel.config.target_vm.as_ref().filter(|_| !snapshotted) {
+        if armed {
+            kill_vm(vm);
+        } else {
+            warn!("🔒 Kill actions disarmed - VM {} left running", vm);
+        }
+    }
+    if let Some(ref pod) = agent.pod {
+        if armed {
+            delete_pod(pod);
+        } else {
+            warn!("🔒 Kill actions disarmed - pod {} left running", pod);
+        }
+    }
+}
+
+/// Hold the KILL of `record_id` for the authorizer: killed once approved,
+/// paused otherwise. False if there is nothing to kill
+fn countersign(
+    kernel: &Kernel,
+    agent: &AgentState,
+    authorizer: &authorize::Authorizer,
+    record_id: u64,
+) -> bool {
+    let pid = kernel.config.target_pid;
+    let container = kernel.config.target_container.clone();
+    let vm = kernel.config.target_vm.clone();
+    let pod = agent.pod.clone();
+    let mut targets = Vec::new();
+    targets.extend(pid.map(|pid| format!("pid {}", pid)));
+    targets.extend(container.as_ref().map(|c| format!("container {}", c)));
+    targets.extend(vm.as_ref().map(|vm| format!("vm {}", vm)));
+    targets.extend(pod.as_ref().map(|p| format!("pod {}", p)));
+    let Some(record) = kernel.audit_trail.get(record_id) else {
+        return false;
+    };
+    if targets.is_empty() {
+        return false;
+    }
+    let snapshots = kernel.snapshots.clone();
+    let snapshot = snapshot_targets(kernel);
+    authorizer.authorize(&record, targets, move |approved| {
+        let snapshotted = match snapshots {
+            Some(ref snapshots) if approved => snapshots.kill(record_id, snapshot),
+            _ => false,
+        };
+        if let Some(pid) = pid.filter(|_| !snapshotted) {
+            match approved {
+                true => drop(kill_process(pid)),
+                false => pause_process(pid),
+            }
+        }
+        if let Some(ref container) = container.filter(|_| !snapshotted) {
+            match approved {
+                true => kill_container(container),
+                false => pause_container(container),
+            }
+        }
+        if let Some(ref vm) = vm.filter(|_| !snapshotted) {
+            match approved {
+                true => kill_vm(vm),
+                false => pause_vm(vm),
+            }
+        }
+        if let Some(ref pod) = pod {
+            match approved {
+                true => delete_pod(pod),
+                false => warn!("⏸️ Pods cannot be paused - pod {} left running", pod),
+            }
+        }
+    });
+    true
+}
+
+/// The `--target-*` processes, container and VM a KILL snapshots
+fn snapshot_targets(kernel: &Kernel) -> snapshot::Targets {
+    snapshot::Targets {
+        pid: kernel.config.target_pid,
+        container: kernel.config.target_container.clone(),
+        vm: kernel.config.target_vm.clone(),
+    }
+}
+
+/// Whether the active schedule profile forbids signaling the target
+fn dry_run(kernel: &Kernel) -> bool {
+    match kernel.schedule.active() {
+        Some(profile) if profile.dry_run => {
+            warn!(
+                "🧪 Dry run (profile '{}') - target left running",
+                profile.name
+            );
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Whether this instance lost kill authority to its HA peer
+fn standby(kernel: &Kernel) -> bool {
+    match kernel.ha {
+        Some(ref ha) if !ha.is_leader() => {
+            warn!(
+                "👥 Standby ({}) - kill authority is with the leader, target left running",
+                ha.instance()
+            );
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Announce a PAUSE from a policy rule and suspend the target
+fn trigger_pause(kernel: &Kernel, agent: &AgentState, record_id: u64, policy: Option<&str>) {
+    let _decision = decision_span(record_id).entered();
+    warn!(
+        "⏸️ [PAUSE] ID:{} policy {}",
+        record_id,
+        policy.unwrap_or("-")
+    );
+    if dry_run(kernel) || standby(kernel) {
+        return;
+    }
+    if let Some(pid) = kernel.config.target_pid {
+        if kernel.health.armed() {
+            pause_process(pid);
+        } else {
+            warn!("🔒 Kill actions disarmed - PID {} left running", pid);
+        }
+    }
+    if let Some(ref container) = kernel.config.target_container {
+        if kernel.health.armed() {
+            pause_container(container);
+        } else {
+            warn!(
+                "🔒 Kill actions disarmed - container {} left running",
+                container
+            );
+        }
+    }
+    if let Some(ref vm) = kernel.config.target_vm {
+        if kernel.health.armed() {
+            pause_vm(vm);
+        } else {
+            warn!("🔒 Kill actions disarmed - VM {} left running", vm);
+        }
+    }
+    if let Some(ref pod) = agent.pod {
+        warn!("⏸️ Pods cannot be paused - pod {} left running", pod);
+    }
+}
+
+/// Announce a cgroup `action` from a policy rule and apply it to the
+/// target PID's cgroup
+fn trigger_clamp(kernel: &Kernel, record_id: u64, action: &str, policy: Option<&str>) {
+    let _decision = decision_span(record_id).entered();
+    warn!(
+        "🧊 [{}] ID:{} policy {}",
+        action,
+        record_id,
+        policy.unwrap_or("-")
+    );
+    let Some(clamp) = cgroup::Clamp::from_action(action) else {
+        return;
+    };
+    if dry_run(kernel) || standby(kernel) {
+        return;
+    }
+    let Some(pid) = kernel.config.target_pid else {
+        warn!("🧊 {} needs --target-pid - nothing clamped", clamp);
+        return;
+    };
+    if !kernel.health.armed() {
+        warn!(
+            "🔒 Kill actions disarmed - cgroup of PID {} left running",
+            pid
+        );
+        return;
+    }
+    match cgroup::Cgroup::of_pid(pid).and_then(|group| {
+        let done = group.apply(clamp)?;
+        Ok((group, done))
+    }) {
+        Ok((group, done)) => info!(
+            "🧊 cgroup {} of PID {} {}",
+            group.path().display(),
+            pid,
+            done
+        ),
+        Err(e) => error!(
+            "Failed to apply {} to the cgroup of PID {}: {}",
+            clamp, pid, e
+        ),
+    }
+}
+
+/// Announce a RESTRICT from a policy rule and apply `[restrict]` to the
+/// target
+fn trigger_restrict(kernel: &Kernel, record_id: u64, policy: Option<&str>) {
+    let _decision = decision_span(record_id).entered();
+    warn!(
+        "🪤 [RESTRICT] ID:{} policy {}",
+        record_id,
+        policy.unwrap_or("-")
+    );
+    if dry_run(kernel) || standby(kernel) {
+        return;
+    }
+    let Some(ref restrictor) = kernel.restrictor else {
+        warn!("🪤 RESTRICT needs [restrict] apparmor or command - target left unrestricted");
+        return;
+    };
+    if !kernel.health.armed() {
+        warn!("🔒 Kill actions disarmed - target left unrestricted");
+        return;
+    }
+    info!("🪤 Restricting the target by {}", restrictor.describe());
+    restrictor.restrict(
+        record_id,
+        kernel.config.target_pid,
+        kernel.config.target_container.as_deref(),
+    );
+}
+
+#[cfg(unix)]
+fn pause_process(pid: u32) {
+    info!("⏸️ Sending SIGSTOP to PID {}", pid);
+    let _ = Command::new("kill")
+        .args(["-STOP", &pid.to_string()])
+        .spawn();
+}
+
+#[cfg(windows)]
+fn pause_process(pid: u32) {
+    warn!(
+        "⏸️ PAUSE is not supported on Windows - PID {} left running",
+        pid
+    );
+}
+
+fn kill_container(name: &str) {
+    info!("🔪 Killing container {}", name);
+    let name = name.to_string();
+    tokio::spawn(async move {
+        let result = async { docker::Docker::from_env()?.kill(&name).await };
+        if let Err(e) = result.await {
+            error!("Failed to kill container {}: {}", name, e);
+        }
+    });
+}
+
+fn pause_container(name: &str) {
+    info!("⏸️ Pausing container {}", name);
+    let name = name.to_string();
+    tokio::spawn(async move {
+        let result = async { docker::Docker::from_env()?.pause(&name).await };
+        if let Err(e) = result.await {
+            error!("Failed to pause container {}: {}", name, e);
+        }
+    });
+}
+
+fn kill_vm(vm: &vm::VmTarget) {
+    info!("🔪 Powering off VM {}", vm);
+    let vm = vm.clone();
+    tokio::spawn(async move {
+        if let Err(e) = vm.kill().await {
+            error!("Failed to power off VM {}: {}", vm, e);
+        }
+    });
+}
+
+fn pause_vm(vm: &vm::VmTarget) {
+    info!("⏸️ Suspending VM {}", vm);
+    let vm = vm.clone();
+    tokio::spawn(async move {
+        if let Err(e) = vm.pause().await {
+            error!("Failed to suspend VM {}: {}", vm, e);
+        }
+    });
+}
+
+fn delete_pod(pod: &kube::PodTarget) {
+    info!("🔪 Deleting pod {}", pod);
+    let pod = pod.clone();
+    tokio::spawn(async move {
+        if let Err(e) = pod.kube.delete(&pod.run).await {
+            error!("Failed to delete pod {}: {}", pod, e);
+        }
+    });
+}
+
+/// Signal `pid`; the kill command, if it could be started
+#[cfg(unix)]
+fn kill_process(pid: u32) -> Option<std::process::Child> {
+    info!("🔪 Sending SIGKILL to PID {}", pid);
+    spawn_kill(Command::new("kill").args(["-9", &pid.to_string()]))
+}
+
+/// Terminate `pid`; the kill command, if it could be started
+#[cfg(windows)]
+fn kill_process(pid: u32) -> Option<std::process::Child> {
+    info!("🔪 Terminating PID {}", pid);
+    spawn_kill(Command::new("taskkill").args(["/F", "/PID", &pid.to_string()]))
+}
+
+fn spawn_kill(command: &mut Command) -> Option<std::process::Child> {
+    match command.spawn() {
+        Ok(child) => Some(child),
+        Err(e) => {
+            error!("Failed to run {:?}: {}", command.get_program(), e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// An OpenAI-compatible LLM answering every request with `content`; its
+    /// `--llm-url`
+    pub(crate) async fn llm(content: &'static str) -> String {
+        let router = axum::Router::new().route(
+            "/v1/chat/completions",
+            axum::routing::post(move || async move {
+                axum::Json(serde_json::json!({"choices": [{"message": {"content": content}}]}))
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/v1", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        url
+    }
+
+    /// The kernel `tripwired <flags>` builds on `filter_config`, auditing
+    /// into `dir`: no canary probe, kill actions armed
+    pub(crate) fn kernel(
+        flags: &[&str],
+        mut filter_config: filter::FilterConfig,
+        dir: &Path,
+    ) -> Arc<Kernel> {
+        filter_config.health.interval_ms = 0;
+        probed_kernel(flags, filter_config, dir)
+    }
+
+    /// A kernel keeping `[health]` as configured: no probe runs, so the
+    /// canary never passes
+    pub(crate) fn probed_kernel(
+        flags: &[&str],
+        filter_config: filter::FilterConfig,
+        dir: &Path,
+    ) -> Arc<Kernel> {
+        let audit_log = dir.join("audit.jsonl");
+        let audit_log = audit_log.to_str().unwrap();
+        let args = Args::parse_from(["tripwired", "--audit-log", audit_log].iter().chain(flags));
+        let agent_token = args.agent_token_file.as_deref().map(load_agent_token);
+        let spec = flag_spec(&args, filter_config, agent_token.as_deref());
+        let prompts = Prompts {
+            log: llm::DEFAULT_PROMPT.to_string(),
+            tool: llm::TOOL_PROMPT.to_string(),
+        };
+        let shutdown = CancellationToken::new();
+        let http = reqwest::Client::new();
+        let (kernel, _) = build_kernel(&args, &prompts, &http, &shutdown, None, &spec);
+        kernel.health.set_checklist(None);
+        kernel
+    }
+
+    /// An agent connection sending `lines`, then hanging up unless it
+    /// waits for the kernel to; the acks it got
+    pub(crate) async fn connect(kernel: &Arc<Kernel>, lines: &str, hang_up: bool) -> String {
+        let (mut agent, end) = tokio::io::duplex(64 * 1024);
+        agent.write_all(lines.as_bytes()).await.unwrap();
+        let agent = (!hang_up).then_some(agent);
+        let (writer, mut acks) = tokio::io::duplex(64 * 1024);
+        let sender = ack::Acks::spawn(&kernel.tracker, writer);
+        let connection = process_connection(
+            BufReader::new(end),
+            Arc::clone(kernel),
+            "test",
+            None,
+            Some(sender),
+        );
+        tokio::time::timeout(Duration::from_secs(5), connection)
+            .await
+            .expect("connection closed");
+        drop(agent);
+        let mut out = String::new();
+        acks.read_to_string(&mut out).await.unwrap();
+        out
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_on_uncertain_enforced() {
+        let url = llm("Hard to say, it depends on the context.").await;
+        for (flag, action) in [("kill", "KILL"), ("pause", "PAUSE")] {
+            let dir = tempfile::tempdir().unwrap();
+            let mut target = tokio::process::Command::new("sleep")
+                .arg("30")
+                .kill_on_drop(true)
+                .spawn()
+                .unwrap();
+            let pid = target.id().unwrap().to_string();
+            let flags = ["--llm-url", &url, "--ack", "--target-pid", &pid];
+            let flags = [&flags[..], &["--on-uncertain", flag]].concat();
+            let kernel = kernel(&flags, Default::default(), dir.path());
+
+            let acks = connect(&kernel, "sudo rm -rf /\n", true).await;
+            assert_eq!(acks, format!("{{\"id\":1,\"action\":\"{}\"}}\n", action));
+            let record = kernel.audit_trail.get(1).unwrap();
+            assert_eq!(record.action, action);
+            assert_eq!(record.verdict.as_deref(), Some("FAIL"));
+            assert_eq!(record.policy.as_deref(), Some(ON_UNCERTAIN));
+            let s = kernel.stats.lock().await;
+            assert_eq!((s.uncertain, s.kills + s.pauses), (1, 1));
+            drop(s);
+
+            // Enforced on the target, not just audited
+            if action == "KILL" {
+                let exited = tokio::time::timeout(Duration::from_secs(5), target.wait()).await;
+                assert!(!exited.expect("target killed").unwrap().success());
+                continue;
+            }
+            let stopped = async {
+                loop {
+                    let ps = Command::new("ps")
+                        .args(["-o", "stat=", "-p", &pid])
+                        .output();
+                    if ps.unwrap().stdout.starts_with(b"T") {
+                        break;
+                    }
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                }
+            };
+            tokio::time::timeout(Duration::from_secs(5), stopped)
+                .await
+                .expect("target stopped");
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_rule_kill_skips_canary() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut target = tokio::process::Command::new("sleep")
+            .arg("30")
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap();
+        let pid = target.id().unwrap().to_string();
+        let config = "[[rule]]\nid = 'wipe'\npattern = 'wipe-everything'\naction = 'kill'\n";
+        let flags = ["--ack", "--target-pid", &pid];
+        let kernel = probed_kernel(&flags, toml::from_str(config).unwrap(), dir.path());
+        // The LLM is down: its kills stay disarmed, escalations degrade
+        assert!(!kernel.health.healthy() && !kernel.health.armed());
+        let acks = connect(&kernel, "sudo rm -rf /\n", true).await;
+        assert_eq!(acks, "{\"id\":1,\"action\":\"SUSTAIN\"}\n");
+
+        // A deterministic rule's KILL is still enforced
+        let acks = connect(&kernel, "wipe-everything\n", true).await;
+        assert_eq!(acks, "{\"id\":1,\"action\":\"KILL\"}\n");
+        assert_eq!(
+            kernel.audit_trail.get(2).unwrap().rule.as_deref(),
+            Some("wipe")
+        );
+        let exited = tokio::time::timeout(Duration::from_secs(5), target.wait()).await;
+        assert!(!exited.expect("target killed").unwrap().success());
+    }
+
+    #[tokio::test]
+    async fn test_flow_drops_audited_rules_spared() {
+        let url = llm(r#"{"action": "KILL", "confidence": 95}"#).await;
+        let dir = tempfile::tempdir().unwrap();
+        let config = "[flow]\nlines_per_sec = 1\nburst = 1\nover_limit = 'sample'\nsample = 1000\n\
+                      [[rule]]\nid = 'wipe'\npattern = 'wipe-everything'\naction = 'kill'\n";
+        let kernel = kernel(
+            &["--llm-url", &url, "--ack"],
+            toml::from_str(config).unwrap(),
+            dir.path(),
+        );
+
+        // Over the limit from the second line on, which is sampled
+        let lines = "ls\nls -la\npwd\nsudo rm -rf /\nwipe-everything\nwhoami\n";
+        let acks = connect(&kernel, lines, true).await;
+        let actions: Vec<_> = acks
+            .lines()
+            .map(|ack| serde_json::from_str::<serde_json::Value>(ack).unwrap()["action"].clone())
+            .collect();
+        assert_eq!(
+            actions,
+            ["SUSTAIN", "SUSTAIN", "DROPPED", "KILL", "KILL", "DROPPED"]
+        );
+        assert_eq!(kernel.stats.lock().await.lines_dropped, 2);
+
+        // Every drop is on the trail
+        let record = |id| kernel.audit_trail.get(id).unwrap();
+        assert_eq!(
+            (record(3).action.as_str(), record(3).input_log.as_str()),
+            ("DROPPED", "pwd")
+        );
+        assert_eq!(record(5).rule.as_deref(), Some("wipe"));
+        assert_eq!(record(6).action, "DROPPED");
+    }
+
+    #[tokio::test]
+    async fn test_kill_past_line_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = "[[rule]]\nid = 'wipe'\npattern = 'wipe-everything'\naction = 'kill'\n";
+        let flags = ["--ack", "--max-line-bytes", "1024"];
+        let kernel = kernel(&flags, toml::from_str(config).unwrap(), dir.path());
+
+        // (under the 64 KiB the test connection buffers)
+        let line = format!("{} wipe-everything\n", "a".repeat(60_000));
+        let acks = connect(&kernel, &line, true).await;
+        assert_eq!(acks, "{\"id\":1,\"action\":\"KILL\"}\n");
+        let record = kernel.audit_trail.get(1).unwrap();
+        assert_eq!(record.rule.as_deref(), Some("wipe"));
+        // Still bounded: the kept head plus the matching window
+        assert!(record.input_log.len() < 16 * 1024);
+        assert_eq!(kernel.stats.lock().await.lines_truncated, 1);
+    }
+
+    #[tokio::test]
+    async fn test_agent_token() {
+        let dir = tempfile::tempdir().unwrap();
+        let token = dir.path().join("agent.token");
+        std::fs::write(&token, "s3cret\n").unwrap();
+        let flags = [
+            "--tcp",
+            "--ack",
+            "--agent-token-file",
+            token.to_str().unwrap(),
+        ];
+        let kernel = kernel(&flags, Default::default(), dir.path());
+
+        // Closed by the kernel: the agent never hangs up
+        for (n, lines) in ["sudo rm -rf /\n", "HELLO token=wrong\nsudo rm -rf /\n"]
+            .iter()
+            .enumerate()
+        {
+            let acks = connect(&kernel, lines, false).await;
+            assert_eq!(acks, "{\"id\":1,\"action\":\"DENIED\"}\n");
+            assert_eq!(kernel.stats.lock().await.auth_failures, n as u64 + 1);
+        }
+        assert_eq!(kernel.audit_trail.last_id(), 0);
+
+        let acks = connect(&kernel, "HELLO token=s3cret\nls\n", true).await;
+        assert_eq!(
+            acks,
+            "{\"id\":1,\"action\":\"HELLO\"}\n{\"id\":2,\"action\":\"SUSTAIN\"}\n"
+        );
+        assert_eq!(kernel.stats.lock().await.auth_failures, 2);
+    }
+
+    #[test]
+    fn test_check_exposure() {
+        let spec = |flags: &[&str], token: Option<&str>| {
+            let args = Args::parse_from(["tripwired", "--tcp"].iter().chain(flags));
+            flag_spec(&args, Default::default(), token)
+        };
+        let exposure = |spec: PipelineSpec| {
+            spec.endpoint
+                .check_exposure(spec.config.agent_token.as_deref())
+        };
+        assert!(exposure(spec(&[], None)).is_ok());
+        assert!(exposure(spec(&["--bind", "::1"], None)).is_ok());
+        let err = exposure(spec(&["--bind", "0.0.0.0"], None)).unwrap_err();
+        assert!(err.contains("--agent-token-file"), "{}", err);
+        assert!(exposure(spec(&["--bind", "::"], None)).is_err());
+        assert!(exposure(spec(&["--bind", "::"], Some("digest"))).is_ok());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_vsock_agent_token() {
+        let spec = |flags: &[&str]| {
+            let args = Args::parse_from(["tripwired"].iter().chain(flags));
+            flag_spec(&args, Default::default(), Some("digest"))
+        };
+        let vsock = spec(&["--vsock-port", "5000"]);
+        assert!(matches!(vsock.endpoint, Endpoint::Vsock(5000)));
+        assert_eq!(vsock.config.agent_token.as_deref(), Some("digest"));
+        // Unix sockets have file permissions instead
+        assert_eq!(spec(&[]).config.agent_token, None);
+    }
+}
@@ -0,0 +1,576 @@
+//! Numeric Limits - Hard Limits and Rate-of-Change Guards
+//!
+//! "Huge exposure" is a judgment call for the LLM; a risk desk wants a
+//! number. Limit rules extract a number from each line and compare it,
+//! deterministically, before the filter:
+//!
+//! - `max` / `min` are hard limits: a value beyond them is KILLed on the
+//!   fast path, no LLM round trip, and a `CHECK` for it is denied
+//! - `max_change` / `max_change_pct` bound how far the value may move
+//!   within `window_ms` (against the lowest and highest value seen in the
+//!   window, per agent); a crossing escalates a synthetic line by
+//!   `action` like a rate rule, and re-arms after a fresh window
+//!
+//! The number comes from a built-in `metric` for trading logs (`exposure`,
+//! `order_size`: qty / quantity / size / notional, `leverage`: `leverage
+//! 20` or `20x leverage`), a `pattern` whose first matching capture group
+//! holds it, or a structured line's `field` (see `parse`). Thousands
+//! separators, a leading `$` and a `k` / `m` / `b` suffix are understood.
+//!
+//! ```toml
+//! [[limit]]
+//! id = "max-exposure"
+//! metric = "exposure"
+//! max = 5_000_000
+//! min = -5_000_000
+//! max_change = 1_000_000   # moved by more than 1M...
+//! window_ms = 60000        # ...within a minute
+//!
+//! [[limit]]
+//! id = "leverage"
+//! metric = "leverage"
+//! max = 10
+//!
+//! [[limit]]
+//! id = "position-swing"
+//! field = "position.qty"
+//! max_change_pct = 50
+//! window_ms = 10000
+//! action = "kill"
+//! ```
+
+use crate::filter::{ConfigError, RuleAction, Severity};
+use crate::parse::ParsedLine;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Values kept per rule and agent for the rate of change
+const MAX_SAMPLES: usize = 1024;
+
+/// A number, with separators, currency sign and magnitude suffix
+const NUMBER: &str = r"(-?\$?\d[\d,_]*(?:\.\d+)?(?:\s?[kmb]\b)?)";
+
+/// Built-in extractors for trading logs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    Exposure,
+    OrderSize,
+    Leverage,
+}
+
+impl Metric {
+    fn pattern(self) -> String {
+        let label = match self {
+            Metric::Exposure => r"(?:net\s+|gross\s+)?exposure",
+            Metric::OrderSize => r"(?:qty|quantity|order[\s_]?size|size|notional)",
+            Metric::Leverage => {
+                return format!(
+                    r"(?i)\bleverage\b[^0-9\-\n]{{0,20}}{}|(\d+(?:\.\d+)?)x\s+leverage\b",
+                    NUMBER
+                )
+            }
+        };
+        format!(r"(?i)\b{}\b[^0-9\-\n]{{0,20}}{}", label, NUMBER)
+    }
+}
+
+/// Numeric rule from a `[[limit]]` table
+#[derive(Debug, Clone, Deserialize)]
+pub struct LimitDef {
+    pub id: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Built-in extractor
+    #[serde(default)]
+    pub metric: Option<Metric>,
+    /// Regex whose first matching capture group holds the number
+    #[serde(default)]
+    pub pattern: Option<String>,
+    /// Field of a JSON / logfmt line holding the number
+    #[serde(default)]
+    pub field: Option<String>,
+    /// Hard limits: KILL beyond them
+    #[serde(default)]
+    pub max: Option<f64>,
+    #[serde(default)]
+    pub min: Option<f64>,
+    /// Largest move within `window_ms`, in units
+    #[serde(default)]
+    pub max_change: Option<f64>,
+    /// Largest move within `window_ms`, in percent of the value moved from
+    #[serde(default)]
+    pub max_change_pct: Option<f64>,
+    #[serde(default = "default_window_ms")]
+    pub window_ms: u64,
+    #[serde(default)]
+    pub severity: Severity,
+    /// What a crossed change does: `analyze` (default) or `kill`
+    #[serde(default)]
+    pub action: RuleAction,
+}
+
+fn default_window_ms() -> u64 {
+    60_000
+}
+
+impl LimitDef {
+    /// Validate the extractor and the limits
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let sources = [
+            self.metric.is_some(),
+            self.pattern.is_some(),
+            self.field.is_some(),
+        ];
+        if sources.iter().filter(|s| **s).count() != 1 {
+            return Err(format!(
+                "limit '{}' needs exactly one of metric, pattern or field",
+                self.id
+            )
+            .into());
+        }
+        if let Some(ref pattern) = self.pattern {
+            let regex = Regex::new(pattern).map_err(|e| ConfigError::regex(&self.id, e))?;
+            if regex.captures_len() < 2 {
+                return Err(format!("limit '{}' pattern needs a capture group", self.id).into());
+            }
+        }
+        let limits = [self.max, self.min, self.max_change, self.max_change_pct];
+        if limits.iter().all(Option::is_none) {
+            return Err(format!(
+                "limit '{}' needs max, min, max_change or max_change_pct",
+                self.id
+            )
+            .into());
+        }
+        if let (Some(min), Some(max)) = (self.min, self.max) {
+            if min >= max {
+                return Err(format!("limit '{}' min must be below max", self.id).into());
+            }
+        }
+        if [self.max_change, self.max_change_pct]
+            .iter()
+            .flatten()
+            .any(|c| *c <= 0.0)
+        {
+            return Err(format!("limit '{}' changes must be positive", self.id).into());
+        }
+        if self.window_ms == 0 {
+            return Err(format!("limit '{}' window_ms must be positive", self.id).into());
+        }
+        Ok(())
+    }
+
+    /// What the number is, for synthetic lines
+    fn label(&self) -> &str {
+        let metric = self.metric.map(|m| match m {
+            Metric::Exposure => "exposure",
+            Metric::OrderSize => "order size",
+            Metric::Leverage => "leverage",
+        });
+        self.description
+            .as_deref()
+            .or(metric)
+            .or(self.field.as_deref())
+            .or(self.pattern.as_deref())
+            .unwrap_or_default()
+    }
+
+    fn tracks_change(&self) -> bool {
+        self.max_change.is_some() || self.max_change_pct.is_some()
+    }
+}
+
+/// A breached limit or a crossed change
+#[derive(Debug, Clone, PartialEq)]
+pub struct LimitHit {
+    pub id: String,
+    pub severity: Severity,
+    /// A hard limit (always KILL); otherwise a change escalated by `action`
+    pub breach: bool,
+    pub action: RuleAction,
+    /// Synthetic log line describing it
+    pub line: String,
+}
+
+enum Source {
+    Pattern(Regex),
+    Field(String),
+}
+
+struct CompiledLimit {
+    def: LimitDef,
+    source: Source,
+    window: Duration,
+}
+
+/// Compiled limit rules (shared by all connections)
+pub struct LimitMonitor {
+    rules: Vec<CompiledLimit>,
+}
+
+/// Per-agent recent values, per rule
+pub struct LimitTracker {
+    samples: Vec<VecDeque<(Instant, f64)>>,
+}
+
+impl LimitMonitor {
+    /// Compile validated limit definitions
+    pub fn new(defs: &[LimitDef]) -> Self {
+        let rules = defs
+            .iter()
+            .map(|d| {
+                let source = match (d.metric, &d.pattern, &d.field) {
+                    (Some(metric), _, _) => Source::Pattern(
+                        Regex::new(&metric.pattern()).expect("Invalid metric pattern"),
+                    ),
+                    (None, Some(pattern), _) => {
+                        Source::Pattern(Regex::new(pattern).expect("Invalid limit pattern"))
+                    }
+                    (None, None, field) => Source::Field(field.clone().unwrap_or_default()),
+                };
+                CompiledLimit {
+                    def: d.clone(),
+                    source,
+                    window: Duration::from_millis(d.window_ms),
+                }
+            })
+            .collect();
+        Self { rules }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Fresh state for a new agent
+    pub fn tracker(&self) -> LimitTracker {
+        LimitTracker {
+            samples: vec![VecDeque::new(); self.rules.len()],
+        }
+    }
+
+    /// The first hard limit `line` breaches (stateless: for checks)
+    pub fn breach(&self, line: &ParsedLine) -> Option<LimitHit> {
+        self.rules.iter().find_map(|rule| {
+            let value = rule.value(line)?;
+            rule.breach(value)
+        })
+    }
+
+    /// Feed one line; returns its first breach, else the first crossed change
+    pub fn observe(
+        &self,
+        tracker: &mut LimitTracker,
+        line: &ParsedLine,
+        now: Instant,
+    ) -> Option<LimitHit> {
+        let mut change = None;
+        for (rule, samples) in self.rules.iter().zip(&mut tracker.samples) {
+            let Some(value) = rule.value(line) else {
+                continue;
+            };
+            if let Some(hit) = rule.breach(value) {
+                return Some(hit);
+            }
+            if !rule.def.tracks_change() {
+                continue;
+            }
+            while samples
+                .front()
+                .is_some_and(|(t, _)| now.duration_since(*t) > rule.window)
+            {
+                samples.pop_front();
+            }
+            if change.is_none() {
+                change = rule.change(samples, value);
+                if change.is_some() {
+                    samples.clear();
+                }
+            }
+            if samples.len() == MAX_SAMPLES {
+                samples.pop_front();
+            }
+            samples.push_back((now, value));
+        }
+        change
+    }
+}
+
+impl CompiledLimit {
+    /// The rule's number in `line`, if it has one
+    fn value(&self, line: &ParsedLine) -> Option<f64> {
+        match &self.source {
+            Source::Pattern(re) => {
+                let caps = re.captures(line.raw)?;
+                caps.iter()
+                    .skip(1)
+                    .flatten()
+                    .find_map(|m| number(m.as_str()))
+            }
+            Source::Field(field) => number(line.field(field)?),
+        }
+    }
+
+    fn breach(&self, value: f64) -> Option<LimitHit> {
+        let def = &self.def;
+        let detail = match (def.max, def.min) {
+            (Some(max), _) if value > max => format!("{} > max {}", value, max),
+            (_, Some(min)) if value < min => format!("{} < min {}", value, min),
+            _ => return None,
+        };
+        Some(self.hit(true, format!("LIMIT BREACH '{}' ({})", def.id, detail)))
+    }
+
+    /// The largest move from a value in the window to `value`, if too large
+    fn change(&self, samples: &VecDeque<(Instant, f64)>, value: f64) -> Option<LimitHit> {
+        let def = &self.def;
+        let (lo, hi) =
+            samples
+                .iter()
+                .fold(None, |range: Option<(f64, f64)>, (_, v)| match range {
+                    Some((lo, hi)) => Some((lo.min(*v), hi.max(*v))),
+                    None => Some((*v, *v)),
+                })?;
+        let from = match value - lo >= hi - value {
+            true => lo,
+            false => hi,
+        };
+        let moved = value - from;
+        let pct = (from != 0.0).then(|| moved.abs() / from.abs() * 100.0);
+        let crossed = def.max_change.is_some_and(|max| moved.abs() > max)
+            || def
+                .max_change_pct
+                .zip(pct)
+                .is_some_and(|(max, pct)| pct > max);
+        if !crossed {
+            return None;
+        }
+        let pct = pct.map_or(String::new(), |p| format!(", {:.0}%", p));
+        let line = format!(
+            "LIMIT CHANGE '{}' ({}): {} to {} ({:+}{}) within {}ms",
+            def.id,
+            def.label(),
+            from,
+            value,
+            moved,
+            pct,
+            def.window_ms
+        );
+        Some(self.hit(false, line))
+    }
+
+    fn hit(&self, breach: bool, line: String) -> LimitHit {
+        LimitHit {
+            id: self.def.id.clone(),
+            severity: self.def.severity,
+            breach,
+            action: match breach {
+                true => RuleAction::Kill,
+                false => self.def.action,
+            },
+            line,
+        }
+    }
+}
+
+/// `"$1,250,000"`, `"2.5m"`, `"20x"` → a number
+fn number(text: &str) -> Option<f64> {
+    let text = text.trim();
+    let (text, negative) = match text.strip_prefix('-') {
+        Some(rest) => (rest, true),
+        None => (text, false),
+    };
+    let text = text
+        .strip_prefix('$')
+        .unwrap_or(text)
+        .trim_end_matches(['x', 'X']);
+    let (digits, scale) = match text.chars().last()?.to_ascii_lowercase() {
+        'k' => (&text[..text.len() - 1], 1e3),
+        'm' => (&text[..text.len() - 1], 1e6),
+        'b' => (&text[..text.len() - 1], 1e9),
+        _ => (text, 1.0),
+    };
+    let digits: String = digits
+        .trim()
+        .chars()
+        .filter(|c| !matches!(c, ',' | '_'))
+        .collect();
+    let value = digits.parse::<f64>().ok().filter(|v| v.is_finite())? * scale;
+    Some(if negative { -value } else { value })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse;
+
+    fn limit(id: &str) -> LimitDef {
+        LimitDef {
+            id: id.to_string(),
+            description: None,
+            metric: None,
+            pattern: None,
+            field: None,
+            max: None,
+            min: None,
+            max_change: None,
+            max_change_pct: None,
+            window_ms: default_window_ms(),
+            severity: Severity::High,
+            action: RuleAction::Analyze,
+        }
+    }
+
+    #[test]
+    fn test_number() {
+        assert_eq!(number("$1,250,000"), Some(1_250_000.0));
+        assert_eq!(number("2.5m"), Some(2_500_000.0));
+        assert_eq!(number("-3 K"), Some(-3000.0));
+        assert_eq!(number("20x"), Some(20.0));
+        assert_eq!(number("1_000"), Some(1000.0));
+        assert_eq!(number("abc"), None);
+    }
+
+    #[test]
+    fn test_metrics() {
+        let value = |metric: Metric, line: &str| {
+            let monitor = LimitMonitor::new(&[LimitDef {
+                metric: Some(metric),
+                max: Some(0.0),
+                ..limit("m")
+            }]);
+            monitor.rules[0].value(&parse(line))
+        };
+        assert_eq!(
+            value(Metric::Exposure, "Net exposure: $5,200,000"),
+            Some(5.2e6)
+        );
+        assert_eq!(value(Metric::Exposure, "exposure=-1.5m"), Some(-1.5e6));
+        assert_eq!(
+            value(Metric::Exposure, r#"{"exposure": 750000}"#),
+            Some(75e4)
+        );
+        assert_eq!(
+            value(Metric::OrderSize, "BUY AAPL qty=2500 @ 189.2"),
+            Some(2500.0)
+        );
+        assert_eq!(value(Metric::OrderSize, "order_size 10k"), Some(1e4));
+        assert_eq!(value(Metric::Leverage, "leverage set to 25"), Some(25.0));
+        assert_eq!(
+            value(Metric::Leverage, "opened 50x leverage long"),
+            Some(50.0)
+        );
+        assert_eq!(value(Metric::Exposure, "Order #1234 placed"), None);
+    }
+
+    #[test]
+    fn test_hard_limits() {
+        let monitor = LimitMonitor::new(&[LimitDef {
+            metric: Some(Metric::Exposure),
+            max: Some(5e6),
+            min: Some(-5e6),
+            ..limit("max-exposure")
+        }]);
+        let mut t = monitor.tracker();
+        let now = Instant::now();
+        assert!(monitor
+            .observe(&mut t, &parse("exposure 4.9m"), now)
+            .is_none());
+
+        let hit = monitor
+            .observe(&mut t, &parse("exposure 5.1m"), now)
+            .unwrap();
+        assert!(hit.breach);
+        assert_eq!(hit.action, RuleAction::Kill);
+        assert_eq!(
+            hit.line,
+            "LIMIT BREACH 'max-exposure' (5100000 > max 5000000)"
+        );
+        assert!(monitor.breach(&parse("exposure -6m")).is_some());
+        assert!(monitor.breach(&parse("no number here")).is_none());
+    }
+
+    #[test]
+    fn test_change() {
+        let monitor = LimitMonitor::new(&[LimitDef {
+            field: Some("qty".to_string()),
+            max_change_pct: Some(50.0),
+            window_ms: 10_000,
+            ..limit("position-swing")
+        }]);
+        let mut t = monitor.tracker();
+        let t0 = Instant::now();
+        let at = |ms| t0 + Duration::from_millis(ms);
+        assert!(monitor
+            .observe(&mut t, &parse("sym=AAPL qty=100"), at(0))
+            .is_none());
+        assert!(monitor
+            .observe(&mut t, &parse("sym=AAPL qty=140"), at(1000))
+            .is_none());
+
+        let hit = monitor
+            .observe(&mut t, &parse("sym=AAPL qty=160"), at(2000))
+            .unwrap();
+        assert!(!hit.breach);
+        assert_eq!(hit.action, RuleAction::Analyze);
+        assert_eq!(
+            hit.line,
+            "LIMIT CHANGE 'position-swing' (qty): 100 to 160 (+60, 60%) within 10000ms"
+        );
+        // Re-armed: the window starts over
+        assert!(monitor
+            .observe(&mut t, &parse("sym=AAPL qty=200"), at(3000))
+            .is_none());
+        // Values older than the window are forgotten
+        assert!(monitor
+            .observe(&mut t, &parse("sym=AAPL qty=400"), at(20_000))
+            .is_none());
+        assert!(monitor
+            .observe(&mut t, &parse("sym=AAPL qty=150"), at(21_000))
+            .is_some());
+    }
+
+    #[test]
+    fn test_validate() {
+        let ok = LimitDef {
+            metric: Some(Metric::Leverage),
+            max: Some(10.0),
+            ..limit("leverage")
+        };
+        assert!(ok.validate().is_ok());
+        assert!(LimitDef {
+            metric: None,
+            ..ok.clone()
+        }
+        .validate()
+        .is_err());
+        assert!(LimitDef {
+            pattern: Some("size".to_string()),
+            metric: None,
+            ..ok.clone()
+        }
+        .validate()
+        .is_err());
+        assert!(LimitDef {
+            max: None,
+            ..ok.clone()
+        }
+        .validate()
+        .is_err());
+        assert!(LimitDef {
+            min: Some(20.0),
+            ..ok.clone()
+        }
+        .validate()
+        .is_err());
+        assert!(LimitDef {
+            max_change: Some(0.0),
+            ..ok
+        }
+        .validate()
+        .is_err());
+    }
+}
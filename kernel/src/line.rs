@@ -0,0 +1,439 @@
+//! Line Limits - Bounded Reads and UTF-8-Safe Truncation
+//!
+//! A single giant line (a base64 blob, a dumped file) used to be buffered
+//! whole, sent to the LLM whole and written to the audit trail whole.
+//! Agent lines are now read with a cap of `--max-line-bytes`: the rest of
+//! a longer line is discarded as it arrives and the kept part, cut at a
+//! UTF-8 character boundary, ends with a `…[truncated N bytes]` marker
+//! that the prompt and the audit record both show. Its `input_hash` is of
+//! the truncated line.
+//!
+//! The discarded tail is still scanned: it runs through the filter in
+//! windows of `--max-line-bytes` (overlapping by `OVERLAP` bytes, the first
+//! one reaching back across the cut), and the first window a rule matches is
+//! kept as `caught`, so a `rm -rf /` past the limit is judged like any
+//! other. Memory stays bounded by the window size.
+//!
+//! Invalid UTF-8 is replaced (U+FFFD) instead of ending the connection.
+//!
+//! Framed mode: a client that opens with the 4-byte preamble `\0TWF`
+//! sends length-prefixed frames instead (a 4-byte big-endian length, then
+//! the payload), so one judged unit may span several lines and a partial
+//! write can no longer split it. Frames get the same limit and marker; a
+//! frame that is not valid UTF-8 is rejected (counted and logged, never
+//! judged) rather than repaired. Text lines never start with a NUL byte,
+//! so the mode is told from the first byte. On Windows the named pipe is
+//! created in message mode (`PIPE_TYPE_MESSAGE`), so a frame written in
+//! one call arrives as one message.
+
+use std::borrow::Cow;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+
+/// Opens a framed connection
+pub const FRAMED_PREAMBLE: &[u8; 4] = b"\0TWF";
+
+/// Bytes consecutive tail windows share, so a match across a window
+/// boundary (or the cut) is still seen whole
+const OVERLAP: usize = 4096;
+
+/// Does the filter match this text (a window of a discarded tail)?
+pub type Scan = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// One line as read
+#[derive(Debug, PartialEq)]
+pub struct Line {
+    /// Without the line ending; with the marker when truncated
+    pub text: String,
+    /// Bytes discarded from the end of the line
+    pub truncated: usize,
+    /// A frame that is not valid UTF-8 (`text` is lossy): not to be judged
+    pub invalid: bool,
+    /// The window of the discarded tail the scan matched, if any
+    pub caught: Option<String>,
+}
+
+/// Reads lines of at most `max_bytes` (0 = unlimited) from an agent
+pub struct LineReader<R> {
+    reader: BufReader<R>,
+    max_bytes: usize,
+    /// The line or frame being read; with the fields below it survives a
+    /// cancelled `next_line` (e.g. in a `select!`) without losing bytes
+    buf: Vec<u8>,
+    /// What was cut off `buf`
+    cut: Cut,
+    /// `None` until the first byte tells
+    framed: Option<bool>,
+    /// Framed mode: the preamble has been read
+    opened: bool,
+    /// Framed mode: preamble or length prefix bytes read so far
+    prefix: Vec<u8>,
+    /// Framed mode: payload bytes of the current frame still to read
+    remaining: Option<usize>,
+}
+
+impl<R: AsyncRead + Unpin> LineReader<R> {
+    pub fn new(reader: BufReader<R>, max_bytes: usize) -> Self {
+        Self {
+            reader,
+            max_bytes,
+            buf: Vec::new(),
+            cut: Cut::default(),
+            framed: None,
+            opened: false,
+            prefix: Vec::new(),
+            remaining: None,
+        }
+    }
+
+    /// Scan what is cut off a line with `scan`, keeping the first window
+    /// it matches
+    pub fn with_scan(mut self, scan: Scan) -> Self {
+        self.cut.scan = Some(scan);
+        self
+    }
+
+    /// The client negotiated framed mode
+    pub fn framed(&self) -> bool {
+        self.framed == Some(true)
+    }
+
+    /// The next line (or frame), `None` at end of stream. Cancel-safe.
+    pub async fn next_line(&mut self) -> std::io::Result<Option<Line>> {
+        if self.framed.is_none() {
+            let available = self.reader.fill_buf().await?;
+            if available.is_empty() {
+                return Ok(None);
+            }
+            self.framed = Some(available[0] == 0);
+        }
+        if self.framed() {
+            return self.next_frame().await;
+        }
+
+        loop {
+            let available = self.reader.fill_buf().await?;
+            if available.is_empty() {
+                if self.buf.is_empty() && self.cut.truncated == 0 {
+                    return Ok(None);
+                }
+                break;
+            }
+            let (chunk, used, done) = match available.iter().position(|&b| b == b'\n') {
+                Some(i) => (&available[..i], i + 1, true),
+                None => (available, available.len(), false),
+            };
+            let room = room(self.max_bytes, self.buf.len(), chunk.len());
+            self.buf.extend_from_slice(&chunk[..room]);
+            self.cut.discard(&self.buf, &chunk[room..], self.max_bytes);
+            self.reader.consume(used);
+            if done {
+                break;
+            }
+        }
+        if self.buf.last() == Some(&b'\r') && self.cut.truncated == 0 {
+            self.buf.pop();
+        }
+        Ok(Some(self.finish(false)))
+    }
+
+    /// One length-prefixed frame
+    async fn next_frame(&mut self) -> std::io::Result<Option<Line>> {
+        if !self.opened {
+            if !self.read_prefix().await? {
+                return Ok(None);
+            }
+            if self.prefix != FRAMED_PREAMBLE {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "unknown framing preamble",
+                ));
+            }
+            self.prefix.clear();
+            self.opened = true;
+        }
+        loop {
+            let left = match self.remaining {
+                Some(left) => left,
+                None => {
+                    if !self.read_prefix().await? {
+                        return Ok(None);
+                    }
+                    let length: [u8; 4] = self.prefix[..].try_into().expect("4-byte prefix");
+                    self.prefix.clear();
+                    u32::from_be_bytes(length) as usize
+                }
+            };
+            if left == 0 {
+                self.remaining = None;
+                return Ok(Some(self.finish(true)));
+            }
+            self.remaining = Some(left);
+            let available = self.reader.fill_buf().await?;
+            if available.is_empty() {
+                return Err(std::io::ErrorKind::UnexpectedEof.into());
+            }
+            let n = available.len().min(left);
+            let room = room(self.max_bytes, self.buf.len(), n);
+            self.buf.extend_from_slice(&available[..room]);
+            self.cut
+                .discard(&self.buf, &available[room..n], self.max_bytes);
+            self.reader.consume(n);
+            self.remaining = Some(left - n);
+        }
+    }
+
+    /// Fills `prefix` to 4 bytes; `false` at a clean end of stream
+    async fn read_prefix(&mut self) -> std::io::Result<bool> {
+        while self.prefix.len() < 4 {
+            let available = self.reader.fill_buf().await?;
+            if available.is_empty() {
+                return match self.prefix.is_empty() {
+                    true => Ok(false),
+                    false => Err(std::io::ErrorKind::UnexpectedEof.into()),
+                };
+            }
+            let n = available.len().min(4 - self.prefix.len());
+            self.prefix.extend_from_slice(&available[..n]);
+            self.reader.consume(n);
+        }
+        Ok(true)
+    }
+
+    /// The line in `buf`, with `truncated` bytes cut off its end
+    fn finish(&mut self, strict: bool) -> Line {
+        let caught = self.cut.caught();
+        let mut truncated = std::mem::take(&mut self.cut.truncated);
+        // Don't leave half a character where the line was cut
+        if truncated > 0 {
+            if let Err(e) = std::str::from_utf8(&self.buf) {
+                if e.error_len().is_none() {
+                    truncated += self.buf.len() - e.valid_up_to();
+                    self.buf.truncate(e.valid_up_to());
+                }
+            }
+        }
+        let invalid = strict && std::str::from_utf8(&self.buf).is_err();
+        let mut text = String::from_utf8_lossy(&self.buf).into_owned();
+        self.buf.clear();
+        if truncated > 0 {
+            text.push_str(&marker(truncated));
+        }
+        Line {
+            text,
+            truncated,
+            invalid,
+            caught,
+        }
+    }
+}
+
+/// Bytes past the limit of the line being read
+#[derive(Default)]
+struct Cut {
+    truncated: usize,
+    /// Run over the discarded bytes (see [`LineReader::with_scan`])
+    scan: Option<Scan>,
+    /// Discarded bytes not scanned yet, after the overlap
+    tail: Vec<u8>,
+    caught: Option<String>,
+}
+
+impl Cut {
+    /// Count `bytes` cut after `kept` and scan them window by window
+    fn discard(&mut self, kept: &[u8], bytes: &[u8], max_bytes: usize) {
+        if bytes.is_empty() {
+            return;
+        }
+        if self.truncated == 0 && self.scan.is_some() {
+            let start = kept.len().saturating_sub(OVERLAP);
+            self.tail.extend_from_slice(&kept[start..]);
+        }
+        self.truncated += bytes.len();
+        if self.scan.is_none() || self.caught.is_some() {
+            return;
+        }
+        self.tail.extend_from_slice(bytes);
+        let window = max_bytes.max(2 * OVERLAP);
+        while self.tail.len() >= window && self.caught.is_none() {
+            self.scan_tail(window);
+            self.tail.drain(..window - OVERLAP);
+        }
+    }
+
+    /// Scan the first `len` bytes of `tail`
+    fn scan_tail(&mut self, len: usize) {
+        let Some(ref scan) = self.scan else {
+            return;
+        };
+        let text = String::from_utf8_lossy(&self.tail[..len]);
+        if scan(&text) {
+            self.caught = Some(text.into_owned());
+        }
+    }
+
+    /// The matched window once the line is complete (scanning what is left)
+    fn caught(&mut self) -> Option<String> {
+        if self.caught.is_none() && !self.tail.is_empty() {
+            self.scan_tail(self.tail.len());
+        }
+        self.tail.clear();
+        self.caught.take()
+    }
+}
+
+/// How many of `n` more bytes fit after `len` under `max_bytes`
+fn room(max_bytes: usize, len: usize, n: usize) -> usize {
+    match max_bytes {
+        0 => n,
+        max => max.saturating_sub(len).min(n),
+    }
+}
+
+fn marker(truncated: usize) -> String {
+    format!("…[truncated {} bytes]", truncated)
+}
+
+/// At most `max_chars` characters of `s`, for log previews
+pub fn preview(s: &str, max_chars: usize) -> Cow<'_, str> {
+    match s.char_indices().nth(max_chars) {
+        Some((end, _)) => Cow::Owned(format!("{}…", &s[..end])),
+        None => Cow::Borrowed(s),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::io::AsyncWriteExt;
+
+    async fn read_all(input: &[u8], max_bytes: usize) -> Vec<Line> {
+        let mut reader = LineReader::new(BufReader::with_capacity(4, input), max_bytes);
+        let mut lines = Vec::new();
+        while let Some(line) = reader.next_line().await.unwrap() {
+            lines.push(line);
+        }
+        lines
+    }
+
+    fn line(text: &str, truncated: usize) -> Line {
+        Line {
+            text: text.to_string(),
+            truncated,
+            invalid: false,
+            caught: None,
+        }
+    }
+
+    fn frame(payload: &[u8]) -> Vec<u8> {
+        [&(payload.len() as u32).to_be_bytes()[..], payload].concat()
+    }
+
+    #[tokio::test]
+    async fn test_line_reader() {
+        let lines = read_all(b"short\r\nexactly10!\nmuch too long\nlast", 10).await;
+        assert_eq!(
+            lines,
+            [
+                line("short", 0),
+                line("exactly10!", 0),
+                line("much too l…[truncated 3 bytes]", 3),
+                line("last", 0),
+            ]
+        );
+
+        // "日本語" is 9 bytes: cutting at 8 must drop the whole last character
+        let lines = read_all("日本語\n".as_bytes(), 8).await;
+        assert_eq!(lines, [line("日本…[truncated 3 bytes]", 3)]);
+
+        let lines = read_all(b"bad \xff byte\n", 0).await;
+        assert_eq!(lines, [line("bad \u{fffd} byte", 0)]);
+    }
+
+    #[tokio::test]
+    async fn test_framed() {
+        let input = [
+            &FRAMED_PREAMBLE[..],
+            &frame(b"step 1\nrm -rf /tmp/x\n"),
+            &frame("日本語 and more".as_bytes()),
+            &frame(b"bad \xff byte"),
+            &frame(b""),
+        ]
+        .concat();
+        let lines = read_all(&input, 8).await;
+        assert_eq!(
+            lines,
+            [
+                line("step 1\nr…[truncated 13 bytes]", 13),
+                line("日本…[truncated 12 bytes]", 12),
+                Line {
+                    text: "bad \u{fffd} by…[truncated 2 bytes]".to_string(),
+                    truncated: 2,
+                    invalid: true,
+                    caught: None,
+                },
+                line("", 0),
+            ]
+        );
+
+        // A frame cut off by the end of the stream is an error
+        let cut = [&FRAMED_PREAMBLE[..], &[0, 0, 0, 9], b"abc"].concat();
+        let mut reader = LineReader::new(BufReader::new(&cut[..]), 0);
+        assert!(reader.next_line().await.is_err());
+        assert!(reader.framed());
+
+        let mut reader = LineReader::new(BufReader::new(&b"\0XYZ"[..]), 0);
+        assert!(reader.next_line().await.is_err());
+
+        // A read cancelled mid-frame resumes where it stopped
+        let (mut client, server) = tokio::io::duplex(64);
+        let mut reader = LineReader::new(BufReader::new(server), 0);
+        let whole = [&FRAMED_PREAMBLE[..], &frame(b"one\ntwo")].concat();
+        for part in [&whole[..6], &whole[6..10]] {
+            client.write_all(part).await.unwrap();
+            let read = tokio::time::timeout(Duration::from_millis(20), reader.next_line());
+            assert!(read.await.is_err());
+        }
+        client.write_all(&whole[10..]).await.unwrap();
+        assert_eq!(reader.next_line().await.unwrap(), Some(line("one\ntwo", 0)));
+    }
+
+    #[tokio::test]
+    async fn test_scan_past_cut() {
+        async fn caught(input: Vec<u8>) -> Option<String> {
+            let scan: Scan = Arc::new(|text| text.contains("rm -rf /"));
+            let mut reader = LineReader::new(BufReader::new(&input[..]), 64).with_scan(scan);
+            let line = reader.next_line().await.unwrap().unwrap();
+            assert!(line.truncated > 0 && !line.text.contains("rm -rf /"));
+            line.caught
+        }
+        let padded = |before: usize, after: usize| {
+            let mut line = vec![b'x'; before];
+            line.extend_from_slice(b"sudo rm -rf /");
+            line.extend(std::iter::repeat_n(b'y', after));
+            line.push(b'\n');
+            line
+        };
+
+        // Far past the cut, at the very end, straddling the cut and a
+        // window boundary (windows of 2 * OVERLAP)
+        for (before, after) in [(100_000, 500), (50_000, 0), (60, 10), (2 * OVERLAP - 5, 9)] {
+            let window = caught(padded(before, after)).await.expect("caught");
+            assert!(window.contains("sudo rm -rf /"));
+            assert!(window.len() <= 2 * OVERLAP);
+        }
+        assert_eq!(caught(vec![b'x'; 100_000]).await, None);
+
+        // Framed mode too
+        let payload = padded(10_000, 10);
+        let input = [&FRAMED_PREAMBLE[..], &frame(&payload)].concat();
+        assert!(caught(input).await.is_some());
+    }
+
+    #[test]
+    fn test_preview() {
+        assert_eq!(preview("héllo wörld", 5), "héllo…");
+        assert_eq!(preview("short", 50), "short");
+    }
+}
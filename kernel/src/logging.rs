@@ -0,0 +1,209 @@
+//! Kernel Logs - Text for Terminals, JSON for Collectors
+//!
+//! The kernel's own tracing output (banner, kills, errors) goes to stdout
+//! as text by default. `--log-format json` writes one JSON object per
+//! event instead, so the same pipeline that collects everything else can
+//! ingest it; `--log-file` appends to a file rather than stdout (no ANSI
+//! colors). `RUST_LOG` still sets the levels.
+//!
+//! Event fields become JSON fields, as do the fields of the spans the event
+//! happened in: `channel` (see `channel`) and `decision_id`, the audit
+//! record ID of the decision a kill, pause or failure is about:
+//!
+//! ```text
+//! {"channel":"payments","decision_id":42,"level":"ERROR",
+//!  "message":"  🚨 KILL SWITCH ACTIVATED!","target":"tripwired","timestamp":"2026-10-15T08:52:29.392530Z"}
+//! ```
+
+use serde_json::{Map, Value};
+use std::fmt;
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::sync::Mutex;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::field::RecordFields;
+use tracing_subscriber::fmt::format::{FormatEvent, FormatFields, Writer};
+use tracing_subscriber::fmt::time::{FormatTime, SystemTime};
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::fmt::{FmtContext, FormattedFields};
+use tracing_subscriber::registry::LookupSpan;
+
+/// `--log-format`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+/// Install the global subscriber: `format` to `file` (appended) or stdout
+pub fn init(format: LogFormat, file: Option<&Path>) -> std::io::Result<()> {
+    let filter = tracing_subscriber::EnvFilter::from_default_env()
+        .add_directive("tripwired=info".parse().expect("Valid log directive"));
+    let writer = match file {
+        Some(path) => {
+            let file = OpenOptions::new().create(true).append(true).open(path)?;
+            BoxMakeWriter::new(Mutex::new(file))
+        }
+        None => BoxMakeWriter::new(std::io::stdout),
+    };
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_ansi(file.is_none())
+        .with_writer(writer);
+    match format {
+        LogFormat::Text => builder.init(),
+        LogFormat::Json => builder
+            .event_format(JsonFormat)
+            .fmt_fields(JsonFields)
+            .init(),
+    }
+    Ok(())
+}
+
+/// One JSON object per event
+pub struct JsonFormat;
+
+impl<S, N> FormatEvent<S, N> for JsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let mut timestamp = String::new();
+        SystemTime.format_time(&mut Writer::new(&mut timestamp))?;
+        let meta = event.metadata();
+        let mut object = Map::new();
+        object.insert("timestamp".to_string(), Value::String(timestamp));
+        object.insert("level".to_string(), Value::from(meta.level().as_str()));
+        object.insert("target".to_string(), Value::from(meta.target()));
+
+        // Outermost span first: inner spans, then the event, win on a clash
+        for span in ctx.event_scope().into_iter().flat_map(|s| s.from_root()) {
+            let extensions = span.extensions();
+            let Some(fields) = extensions.get::<FormattedFields<N>>() else {
+                continue;
+            };
+            if let Ok(Value::Object(fields)) = serde_json::from_str(fields) {
+                object.extend(fields);
+            }
+        }
+        event.record(&mut JsonVisitor(&mut object));
+        writeln!(writer, "{}", Value::Object(object))
+    }
+}
+
+/// Span fields kept as a JSON object (see [`JsonFormat`])
+pub struct JsonFields;
+
+impl<'writer> FormatFields<'writer> for JsonFields {
+    fn format_fields<R: RecordFields>(
+        &self,
+        mut writer: Writer<'writer>,
+        fields: R,
+    ) -> fmt::Result {
+        let mut object = Map::new();
+        fields.record(&mut JsonVisitor(&mut object));
+        write!(writer, "{}", Value::Object(object))
+    }
+
+    fn add_fields(
+        &self,
+        current: &'writer mut FormattedFields<Self>,
+        fields: &tracing::span::Record<'_>,
+    ) -> fmt::Result {
+        let mut object = match serde_json::from_str(&current.fields) {
+            Ok(Value::Object(object)) => object,
+            _ => Map::new(),
+        };
+        fields.record(&mut JsonVisitor(&mut object));
+        current.fields = Value::Object(object).to_string();
+        Ok(())
+    }
+}
+
+struct JsonVisitor<'a>(&'a mut Map<String, Value>);
+
+impl Visit for JsonVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.insert(
+            field.name().to_string(),
+            Value::from(format!("{:?}", value)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+    use std::sync::Arc;
+
+    /// Collects what the subscriber writes
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Buffer {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(bytes);
+            Ok(bytes.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_json_events() {
+        let buffer = Buffer::default();
+        let output = buffer.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .event_format(JsonFormat)
+            .fmt_fields(JsonFields)
+            .with_writer(move || buffer.clone())
+            .finish();
+        tracing::subscriber::with_default(subscriber, || {
+            let channel = tracing::info_span!("channel", channel = "payments");
+            let _channel = channel.enter();
+            let decision = tracing::info_span!("decision", decision_id = tracing::field::Empty);
+            decision.record("decision_id", 42u64);
+            let _decision = decision.enter();
+            tracing::error!(pid = 7, "🚨 KILL {}", "now");
+        });
+
+        let text = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        let line: Value = serde_json::from_str(text.trim_end()).unwrap();
+        assert_eq!(line["level"], "ERROR");
+        assert_eq!(line["message"], "🚨 KILL now");
+        assert_eq!(line["pid"], 7);
+        assert_eq!(line["decision_id"], 42);
+        assert_eq!(line["channel"], "payments");
+        assert!(line["timestamp"].as_str().unwrap().ends_with('Z'));
+    }
+}
@@ -0,0 +1,463 @@
+//! MCP Server - The Kill-Switch as Model Context Protocol Tools
+//!
+//! `--mcp-port 8765` serves MCP over Streamable HTTP on
+//! `POST /mcp` (loopback), so agent frameworks that speak MCP can add
+//! tripwired as a tool server instead of writing to a socket:
+//!
+//! | Tool | Arguments | Answer |
+//! |---|---|---|
+//! | `report_log` | `line` | `SUSTAIN`, `PAUSE`, `KILL` (or `DROPPED`) |
+//! | `check_action` | `action`, or `tool` + `args` | `ALLOW` or `DENY` |
+//!
+//! `check_action` is only offered with `[guard] enabled` (see `guard`);
+//! `tool` + `args` is checked as a tool call (see `parse`). The answer is
+//! the text content and `structuredContent.action`.
+//!
+//! Each MCP session (`initialize` to `DELETE /mcp`, `Mcp-Session-Id`) is
+//! one agent connection in framed mode with every line acknowledged (see
+//! `line`, `ack`): tool calls are lines and checks of that connection, so
+//! they get its history, flow control and rules, and are audited exactly
+//! as on a socket. Calls within a session are answered one at a time.
+//! Responses are plain JSON (no SSE stream); requests carrying a
+//! non-local `Origin` are refused (DNS rebinding).
+//!
+//! ```json
+//! {"mcpServers": {"tripwired": {"url": "http://127.0.0.1:8765/mcp"}}}
+//! ```
+
+use crate::line::FRAMED_PREAMBLE;
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream, ReadHalf, WriteHalf};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+/// Protocol revisions spoken, newest first
+const PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+
+/// Open sessions before `initialize` is refused
+const MAX_SESSIONS: usize = 1024;
+
+/// Largest request accepted
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+
+const SESSION_HEADER: &str = "mcp-session-id";
+
+/// A new session: the kernel side of its connection
+pub struct Opened {
+    /// `mcp:<client name>/<session id prefix>`
+    pub peer: String,
+    pub stream: DuplexStream,
+}
+
+/// The MCP side of a session's connection
+struct Link {
+    reader: ReadHalf<DuplexStream>,
+    writer: WriteHalf<DuplexStream>,
+    /// Lines sent so far: the ID of the answer to wait for
+    seq: u64,
+}
+
+#[derive(Deserialize)]
+struct Answer {
+    id: u64,
+    action: String,
+}
+
+impl Link {
+    async fn open(stream: DuplexStream) -> std::io::Result<Self> {
+        let (reader, mut writer) = tokio::io::split(stream);
+        writer.write_all(FRAMED_PREAMBLE).await?;
+        Ok(Self {
+            reader,
+            writer,
+            seq: 0,
+        })
+    }
+
+    /// Send one line (or check) and wait for its verdict
+    async fn ask(&mut self, payload: &str) -> std::io::Result<String> {
+        self.seq += 1;
+        let frame = [
+            &(payload.len() as u32).to_be_bytes()[..],
+            payload.as_bytes(),
+        ]
+        .concat();
+        self.writer.write_all(&frame).await?;
+        loop {
+            let len = self.reader.read_u32().await? as usize;
+            let mut body = vec![0; len];
+            self.reader.read_exact(&mut body).await?;
+            let answer: Answer = serde_json::from_slice(&body)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+            if answer.id == self.seq {
+                return Ok(answer.action);
+            }
+        }
+    }
+}
+
+struct Server {
+    opened: mpsc::Sender<Opened>,
+    /// Offer `check_action`
+    check: bool,
+    sessions: std::sync::Mutex<HashMap<String, Arc<tokio::sync::Mutex<Link>>>>,
+}
+
+/// Serve `/mcp` until `shutdown`, sending every new session to `opened`
+pub async fn serve(
+    port: u16,
+    opened: mpsc::Sender<Opened>,
+    check: bool,
+    shutdown: CancellationToken,
+) -> std::io::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port)).await?;
+    let server = Arc::new(Server {
+        opened,
+        check,
+        sessions: std::sync::Mutex::new(HashMap::new()),
+    });
+    let router = Router::new()
+        .route("/mcp", post(request).delete(close))
+        .layer(axum::extract::DefaultBodyLimit::max(MAX_BODY_BYTES))
+        .with_state(server);
+    axum::serve(listener, router)
+        .with_graceful_shutdown(shutdown.cancelled_owned())
+        .await
+}
+
+/// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_ERROR: i64 = -32000;
+
+fn error(id: &Value, code: i64, message: &str) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
+
+fn result(id: &Value, result: Value) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "result": result})
+}
+
+async fn request(State(server): State<Arc<Server>>, headers: HeaderMap, body: Bytes) -> Response {
+    if !local_origin(&headers) {
+        return (StatusCode::FORBIDDEN, "origin not allowed").into_response();
+    }
+    let message: Value = match serde_json::from_slice(&body) {
+        Ok(message) => message,
+        Err(_) => {
+            let body = error(&Value::Null, PARSE_ERROR, "parse error");
+            return (StatusCode::BAD_REQUEST, Json(body)).into_response();
+        }
+    };
+    let Some(method) = message.get("method").and_then(Value::as_str) else {
+        // A response or batch: nothing of ours asked for one
+        let body = error(&Value::Null, INVALID_REQUEST, "expected one request");
+        return (StatusCode::BAD_REQUEST, Json(body)).into_response();
+    };
+    // Notifications (notifications/initialized, cancellations) need no answer
+    let Some(id) = message.get("id") else {
+        return StatusCode::ACCEPTED.into_response();
+    };
+    let params = message.get("params").cloned().unwrap_or(Value::Null);
+    if method == "initialize" {
+        return server.initialize(id, &params).await;
+    }
+
+    let session = headers.get(SESSION_HEADER).and_then(|v| v.to_str().ok());
+    let Some(session) = session else {
+        let body = error(id, INVALID_REQUEST, "missing Mcp-Session-Id");
+        return (StatusCode::BAD_REQUEST, Json(body)).into_response();
+    };
+    let link = server.sessions.lock().unwrap().get(session).cloned();
+    let Some(link) = link else {
+        return (StatusCode::NOT_FOUND, "unknown session").into_response();
+    };
+    let body = match method {
+        "ping" => result(id, json!({})),
+        "tools/list" => result(id, json!({ "tools": tools(server.check) })),
+        "tools/call" => match server.call(&params) {
+            // Finished even if the client goes away, so the next call
+            // doesn't find half a frame on the connection
+            Ok(payload) => {
+                match tokio::spawn(async move { link.lock().await.ask(&payload).await }).await {
+                    Ok(Ok(action)) => result(id, answer(&action, false)),
+                    _ => result(id, answer("tripwired is shutting down", true)),
+                }
+            }
+            Err(message) => error(id, INVALID_PARAMS, &message),
+        },
+        _ => error(id, METHOD_NOT_FOUND, "method not found"),
+    };
+    Json(body).into_response()
+}
+
+async fn close(State(server): State<Arc<Server>>, headers: HeaderMap) -> StatusCode {
+    let session = headers.get(SESSION_HEADER).and_then(|v| v.to_str().ok());
+    // Dropping the link ends the connection
+    match session.and_then(|s| server.sessions.lock().unwrap().remove(s)) {
+        Some(_) => StatusCode::NO_CONTENT,
+        None => StatusCode::NOT_FOUND,
+    }
+}
+
+impl Server {
+    /// Open a session and its connection
+    async fn initialize(&self, id: &Value, params: &Value) -> Response {
+        if self.sessions.lock().unwrap().len() >= MAX_SESSIONS {
+            let body = error(id, SERVER_ERROR, "too many open sessions");
+            return (StatusCode::SERVICE_UNAVAILABLE, Json(body)).into_response();
+        }
+        let requested = params.get("protocolVersion").and_then(Value::as_str);
+        let version = PROTOCOL_VERSIONS
+            .iter()
+            .find(|v| Some(**v) == requested)
+            .unwrap_or(&PROTOCOL_VERSIONS[0]);
+        let client = params
+            .pointer("/clientInfo/name")
+            .and_then(Value::as_str)
+            .unwrap_or("client");
+
+        let session = session_id();
+        let (kernel, ours) = tokio::io::duplex(64 * 1024);
+        let peer = format!("mcp:{}/{}", crate::line::preview(client, 40), &session[..8]);
+        let link = match self
+            .opened
+            .send(Opened {
+                peer,
+                stream: kernel,
+            })
+            .await
+        {
+            Ok(()) => Link::open(ours).await,
+            Err(_) => Err(std::io::ErrorKind::BrokenPipe.into()),
+        };
+        let Ok(link) = link else {
+            let body = error(id, SERVER_ERROR, "tripwired is shutting down");
+            return (StatusCode::SERVICE_UNAVAILABLE, Json(body)).into_response();
+        };
+        self.sessions
+            .lock()
+            .unwrap()
+            .insert(session.clone(), Arc::new(tokio::sync::Mutex::new(link)));
+
+        let body = result(
+            id,
+            json!({
+                "protocolVersion": version,
+                "capabilities": {"tools": {}},
+                "serverInfo": {"name": "tripwired", "version": env!("CARGO_PKG_VERSION")},
+            }),
+        );
+        ([(SESSION_HEADER, session)], Json(body)).into_response()
+    }
+
+    /// The line a `tools/call` sends
+    fn call(&self, params: &Value) -> Result<String, String> {
+        let name = params.get("name").and_then(Value::as_str).unwrap_or("");
+        let arguments = params.get("arguments").unwrap_or(&Value::Null);
+        let text = |key| arguments.get(key).and_then(Value::as_str);
+        match name {
+            "report_log" => match text("line") {
+                Some(line) => Ok(line.to_string()),
+                None => Err("report_log: `line` (string) is required".to_string()),
+            },
+            "check_action" if self.check => {
+                let action = match (text("action"), text("tool")) {
+                    (Some(action), _) => action.to_string(),
+                    (None, Some(tool)) => {
+                        let args = arguments.get("args").cloned().unwrap_or(json!({}));
+                        json!({"tool": tool, "args": args}).to_string()
+                    }
+                    (None, None) => {
+                        return Err("check_action: `action` or `tool` is required".to_string())
+                    }
+                };
+                Ok(format!("{}{}", crate::guard::PREFIX, action))
+            }
+            _ => Err(format!("unknown tool: {}", name)),
+        }
+    }
+}
+
+/// A `tools/call` result
+fn answer(action: &str, is_error: bool) -> Value {
+    json!({
+        "content": [{"type": "text", "text": action}],
+        "structuredContent": {"action": action},
+        "isError": is_error,
+    })
+}
+
+/// `tools/list`
+fn tools(check: bool) -> Vec<Value> {
+    let output = json!({
+        "type": "object",
+        "properties": {"action": {"type": "string"}},
+        "required": ["action"],
+    });
+    let mut tools = vec![json!({
+        "name": "report_log",
+        "description": "Report one line of agent activity (a log line or a tool call as JSON) \
+            to the tripwired kill-switch. Answers with the verdict: SUSTAIN, PAUSE or KILL \
+            (DROPPED when the line was shed under load). Stop on KILL.",
+        "inputSchema": {
+            "type": "object",
+            "properties": {"line": {"type": "string", "description": "The activity line"}},
+            "required": ["line"],
+        },
+        "outputSchema": output,
+    })];
+    if check {
+        tools.push(json!({
+            "name": "check_action",
+            "description": "Ask the tripwired kill-switch for approval BEFORE taking an action. \
+                Answers ALLOW or DENY; do not take a denied action.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "action": {"type": "string", "description": "The intended action"},
+                    "tool": {"type": "string", "description": "Or: the tool about to be called"},
+                    "args": {"type": "object", "description": "The tool's arguments"},
+                },
+            },
+            "outputSchema": output,
+        }));
+    }
+    tools
+}
+
+/// Browsers send `Origin`; only pages served from this host may call us
+fn local_origin(headers: &HeaderMap) -> bool {
+    let Some(origin) = headers.get("origin") else {
+        return true;
+    };
+    let origin = origin.to_str().unwrap_or_default();
+    let host = origin.split_once("://").map_or(origin, |(_, rest)| rest);
+    let host = match host.strip_prefix('[') {
+        Some(v6) => v6.split(']').next().unwrap_or_default(),
+        None => host.split(':').next().unwrap_or_default(),
+    };
+    matches!(host, "localhost" | "127.0.0.1" | "::1")
+}
+
+/// 128 unpredictable bits, as hex
+fn session_id() -> String {
+    static COUNTER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+    let n = COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    let half = || {
+        let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+        hasher.write_u64(n);
+        hasher.finish()
+    };
+    format!("{:016x}{:016x}", half(), half())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_tool_calls() {
+        let server = Server {
+            opened: mpsc::channel(1).0,
+            check: false,
+            sessions: std::sync::Mutex::new(HashMap::new()),
+        };
+        let call = |server: &Server, params| server.call(&params);
+        assert_eq!(
+            call(
+                &server,
+                json!({"name": "report_log", "arguments": {"line": "ls -la"}})
+            ),
+            Ok("ls -la".to_string())
+        );
+        assert!(call(&server, json!({"name": "report_log", "arguments": {}})).is_err());
+        assert!(call(
+            &server,
+            json!({"name": "check_action", "arguments": {"action": "x"}})
+        )
+        .is_err());
+        assert_eq!(tools(false).len(), 1);
+
+        let server = Server {
+            check: true,
+            ..server
+        };
+        assert_eq!(
+            call(
+                &server,
+                json!({"name": "check_action", "arguments": {"action": "rm -rf /"}})
+            ),
+            Ok("CHECK rm -rf /".to_string())
+        );
+        let line = call(
+            &server,
+            json!({"name": "check_action", "arguments": {"tool": "shell", "args": {"cmd": "ls"}}}),
+        )
+        .unwrap();
+        assert_eq!(line, r#"CHECK {"args":{"cmd":"ls"},"tool":"shell"}"#);
+        assert!(crate::parse::is_tool_call(
+            line.strip_prefix("CHECK ").unwrap()
+        ));
+        assert_eq!(tools(true).len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_link() {
+        let (mut kernel, ours) = tokio::io::duplex(1024);
+        let mut link = Link::open(ours).await.unwrap();
+        // A stand-in kernel: one framed line in, a stale ack and its own out
+        let reply = tokio::spawn(async move {
+            let mut preamble = [0; 4];
+            kernel.read_exact(&mut preamble).await.unwrap();
+            assert_eq!(&preamble, FRAMED_PREAMBLE);
+            let mut line = vec![0; kernel.read_u32().await.unwrap() as usize];
+            kernel.read_exact(&mut line).await.unwrap();
+            for ack in [
+                r#"{"id":0,"action":"SUSTAIN"}"#,
+                r#"{"id":1,"action":"KILL"}"#,
+            ] {
+                kernel.write_u32(ack.len() as u32).await.unwrap();
+                kernel.write_all(ack.as_bytes()).await.unwrap();
+            }
+            String::from_utf8(line).unwrap()
+        });
+        assert_eq!(link.ask("rm -rf /\nstep 2").await.unwrap(), "KILL");
+        assert_eq!(reply.await.unwrap(), "rm -rf /\nstep 2");
+        // The connection ended
+        assert!(link.ask("ls").await.is_err());
+    }
+
+    #[test]
+    fn test_local_origin() {
+        let mut headers = HeaderMap::new();
+        assert!(local_origin(&headers));
+        for (origin, local) in [
+            ("http://localhost:3000", true),
+            ("http://127.0.0.1", true),
+            ("http://[::1]:8080", true),
+            ("https://evil.example", false),
+            ("http://localhost.evil.example", false),
+            ("null", false),
+        ] {
+            headers.insert("origin", HeaderValue::from_static(origin));
+            assert_eq!(local_origin(&headers), local, "{}", origin);
+        }
+        assert_ne!(session_id(), session_id());
+        assert_eq!(session_id().len(), 32);
+    }
+}
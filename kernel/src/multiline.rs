@@ -0,0 +1,277 @@
+//! Multi-Line Records - Stack Traces as One Line
+//!
+//! A Java or Python stack trace arrives as dozens of physical lines and
+//! only the first (or last) one says "Exception". With `[multiline]`
+//! enabled, continuation lines are appended to the record they belong to,
+//! so the filter, the audit trail and the LLM see the whole traceback as
+//! one line (joined with `\n`):
+//!
+//! - `continuation` is a regex marking continuation lines. Default: the
+//!   indentation heuristic - a leading space or tab, `Caused by:`, and the
+//!   closing `ValueError: ...` line of a Python `Traceback`
+//! - a record is judged when a line starts the next one, after `flush_ms`
+//!   without input, at `max_lines` or `--max-line-bytes`, or when the
+//!   connection ends
+//!
+//! Every line waits up to `flush_ms` for its continuation, so keep it
+//! short. Framed connections (a frame is a record already), guard `CHECK`
+//! requests and rejected frames are never assembled. With acks, every
+//! physical line of a record is acknowledged with the record's verdict.
+//!
+//! ```toml
+//! [multiline]
+//! enabled = true
+//! continuation = '^(\s|Caused by:|\.\.\. \d+ more)'  # optional
+//! flush_ms = 200
+//! max_lines = 500
+//! ```
+
+use regex::Regex;
+use serde::Deserialize;
+use std::ops::RangeInclusive;
+use std::sync::LazyLock;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Last line of a Python traceback (`ValueError: bad input`)
+static PYTHON_EXCEPTION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^[A-Za-z_][\w.]*(Error|Exception|Warning|Exit|Interrupt)\b").unwrap()
+});
+
+/// `[multiline]` table
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MultilineConfig {
+    pub enabled: bool,
+    /// Regex marking continuation lines (default: indentation heuristic)
+    pub continuation: Option<String>,
+    /// Judge a pending record after this long without input (milliseconds)
+    pub flush_ms: u64,
+    /// Physical lines per record at most
+    pub max_lines: usize,
+}
+
+impl Default for MultilineConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            continuation: None,
+            flush_ms: 200,
+            max_lines: 500,
+        }
+    }
+}
+
+impl MultilineConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(ref pattern) = self.continuation {
+            Regex::new(pattern).map_err(|e| format!("multiline: continuation: {}", e))?;
+        }
+        if self.flush_ms == 0 {
+            return Err("multiline: flush_ms must be > 0".to_string());
+        }
+        if self.max_lines < 2 {
+            return Err("multiline: max_lines must be at least 2".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// One judged unit: a physical line or an assembled record
+#[derive(Debug, PartialEq)]
+pub struct Record {
+    pub text: String,
+    /// Sequence number of the first physical line
+    pub first: u64,
+    /// Physical lines in the record
+    pub lines: u64,
+}
+
+impl Record {
+    pub fn single(seq: u64, text: String) -> Self {
+        Self {
+            text,
+            first: seq,
+            lines: 1,
+        }
+    }
+
+    /// Sequence numbers of its physical lines (the IDs acks refer to)
+    pub fn ids(&self) -> RangeInclusive<u64> {
+        self.first..=self.first + self.lines - 1
+    }
+}
+
+/// Assembles one connection's lines into records
+pub struct Assembler {
+    continuation: Option<Regex>,
+    flush: Duration,
+    max_lines: u64,
+    /// Record size cap (0 = unlimited)
+    max_bytes: usize,
+    pending: Option<Record>,
+    last: Instant,
+}
+
+impl Assembler {
+    /// `None` unless `[multiline]` is enabled
+    pub fn new(config: &MultilineConfig, max_bytes: usize) -> Option<Self> {
+        config.enabled.then(|| Self {
+            continuation: config
+                .continuation
+                .as_deref()
+                .map(|p| Regex::new(p).expect("Invalid continuation pattern")),
+            flush: Duration::from_millis(config.flush_ms),
+            max_lines: config.max_lines as u64,
+            max_bytes,
+            pending: None,
+            last: Instant::now(),
+        })
+    }
+
+    /// Add physical line `seq`; returns the record it completes, if any
+    pub fn push(&mut self, seq: u64, line: String) -> Option<Record> {
+        self.last = Instant::now();
+        if let Some(ref mut record) = self.pending {
+            let fits = self.max_bytes == 0 || record.text.len() + 1 + line.len() <= self.max_bytes;
+            if fits && continues(self.continuation.as_ref(), &record.text, &line) {
+                record.text.push('\n');
+                record.text.push_str(&line);
+                record.lines += 1;
+                return match record.lines >= self.max_lines {
+                    true => self.pending.take(),
+                    false => None,
+                };
+            }
+        }
+        self.pending.replace(Record::single(seq, line))
+    }
+
+    /// The pending record, if any
+    pub fn flush(&mut self) -> Option<Record> {
+        self.pending.take()
+    }
+
+    /// When the pending record is due
+    pub fn deadline(&self) -> Option<Instant> {
+        self.pending.as_ref().map(|_| self.last + self.flush)
+    }
+}
+
+/// Does `line` continue the record `text`?
+fn continues(continuation: Option<&Regex>, text: &str, line: &str) -> bool {
+    if let Some(re) = continuation {
+        return re.is_match(line);
+    }
+    line.starts_with([' ', '\t'])
+        || line.starts_with("Caused by:")
+        || (text.starts_with("Traceback ")
+            && text
+                .lines()
+                .last()
+                .is_some_and(|l| l.starts_with([' ', '\t']))
+            && PYTHON_EXCEPTION.is_match(line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assemble(config: &MultilineConfig, lines: &[&str]) -> Vec<Record> {
+        let mut assembler = Assembler::new(config, 0).unwrap();
+        let mut records: Vec<Record> = lines
+            .iter()
+            .zip(1..)
+            .filter_map(|(line, seq)| assembler.push(seq, line.to_string()))
+            .collect();
+        records.extend(assembler.flush());
+        records
+    }
+
+    fn enabled() -> MultilineConfig {
+        MultilineConfig {
+            enabled: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_java_trace() {
+        let records = assemble(
+            &enabled(),
+            &[
+                "Order 7 placed",
+                "java.lang.IllegalStateException: ledger locked",
+                "\tat com.acme.Ledger.post(Ledger.java:42)",
+                "\tat com.acme.Main.main(Main.java:7)",
+                "Caused by: java.io.IOException: disk full",
+                "\t... 2 more",
+                "Order 8 placed",
+            ],
+        );
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[1].lines, 5);
+        assert_eq!(records[1].ids(), 2..=6);
+        assert!(records[1]
+            .text
+            .starts_with("java.lang.IllegalStateException"));
+        assert!(records[1].text.ends_with("\n\t... 2 more"));
+        assert_eq!(records[2], Record::single(7, "Order 8 placed".to_string()));
+    }
+
+    #[test]
+    fn test_python_trace() {
+        let records = assemble(
+            &enabled(),
+            &[
+                "Traceback (most recent call last):",
+                "  File \"bot.py\", line 3, in <module>",
+                "    wipe()",
+                "PermissionError: [Errno 13] Permission denied: '/'",
+                "RuntimeError: not part of the traceback",
+            ],
+        );
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].lines, 4);
+        assert!(records[0].text.ends_with("Permission denied: '/'"));
+        assert_eq!(records[1].first, 5);
+    }
+
+    #[test]
+    fn test_limits_and_custom_pattern() {
+        let config = MultilineConfig {
+            continuation: Some(r"^\|".to_string()),
+            max_lines: 3,
+            ..enabled()
+        };
+        let records = assemble(&config, &["a", "| b", "| c", "| d", "  e"]);
+        let texts: Vec<&str> = records.iter().map(|r| r.text.as_str()).collect();
+        assert_eq!(texts, ["a\n| b\n| c", "| d", "  e"]);
+
+        let mut assembler = Assembler::new(&enabled(), 10).unwrap();
+        assert_eq!(assembler.push(1, "12345".to_string()), None);
+        assert!(assembler.deadline().is_some());
+        assert_eq!(assembler.push(2, " 678".to_string()), None);
+        // Would exceed --max-line-bytes: starts the next record
+        assert_eq!(
+            assembler.push(3, " 9".to_string()).unwrap().text,
+            "12345\n 678"
+        );
+        assert_eq!(assembler.flush().unwrap().first, 3);
+        assert_eq!(assembler.deadline(), None);
+
+        assert!(Assembler::new(&MultilineConfig::default(), 0).is_none());
+        assert!(MultilineConfig {
+            max_lines: 1,
+            ..enabled()
+        }
+        .validate()
+        .is_err());
+        assert!(MultilineConfig {
+            continuation: Some("(".to_string()),
+            ..enabled()
+        }
+        .validate()
+        .is_err());
+    }
+}
@@ -0,0 +1,301 @@
+//! Normalization - Line Templates and Dedup Statistics
+//!
+//! Agents repeat the same few lines with different timestamps, ids and
+//! amounts. Normalizing a line replaces those with placeholders:
+//!
+//! ```text
+//! 2026-10-15T09:30:01Z Order 1234 filled at 45.20 (tx 0x1f3a)
+//! <ts> Order <num> filled at <num> (tx <hex>)
+//! ```
+//!
+//! Every audit record carries the SHA-256 of its (redacted) line's
+//! template as `template_hash`, next to `input_hash`. Learn mode clusters
+//! on the same templates, and the audit trail counts records per template
+//! per UTC day (`GET /templates`: "this template fired 4,812 times today").
+
+use crate::audit::DecisionRecord;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Variable tokens, in order of precedence
+const TOKENS: &str = concat!(
+    r"(?P<ts>\b\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}:\d{2}(?:[.,]\d+)?(?:Z|[+-]\d{2}:?\d{2})?",
+    r"|\b(?:Jan|Feb|Mar|Apr|May|Jun|Jul|Aug|Sep|Oct|Nov|Dec) +\d{1,2} \d{2}:\d{2}:\d{2}\b",
+    r"|\b\d{2}:\d{2}:\d{2}(?:\.\d+)?\b)",
+    r"|(?P<uuid>\b[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}\b)",
+    r"|(?P<hex>\b0x[0-9a-fA-F]+\b|\b[0-9a-f]{12,}\b)",
+    r"|(?P<num>\d+(?:\.\d+)*)",
+);
+
+/// Templates tracked per day; later ones are only counted as `untracked`
+const MAX_TEMPLATES: usize = 4096;
+
+const DAY_MS: u64 = 86_400_000;
+
+/// A placeholder kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Token {
+    Timestamp,
+    Uuid,
+    Hex,
+    Num,
+}
+
+impl Token {
+    pub fn placeholder(self) -> &'static str {
+        match self {
+            Self::Timestamp => "<ts>",
+            Self::Uuid => "<uuid>",
+            Self::Hex => "<hex>",
+            Self::Num => "<num>",
+        }
+    }
+
+    /// Regex matching any value of this kind (for learned excludes)
+    pub fn pattern(self) -> &'static str {
+        match self {
+            Self::Timestamp => concat!(
+                r"(?:\d{4}-\d{2}-\d{2}[T ]|[A-Z][a-z]{2} +\d{1,2} )?",
+                r"\d{2}:\d{2}:\d{2}(?:[.,]\d+)?(?:Z|[+-]\d{2}:?\d{2})?"
+            ),
+            Self::Uuid => r"[0-9a-fA-F-]{36}",
+            Self::Hex => r"(?:0x)?[0-9a-fA-F]+",
+            Self::Num => r"\d+(?:\.\d+)*",
+        }
+    }
+}
+
+/// A piece of a normalized line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Segment<'a> {
+    Text(&'a str),
+    Token(Token),
+}
+
+pub struct Normalizer {
+    tokens: Regex,
+}
+
+impl Normalizer {
+    pub fn new() -> Self {
+        Self {
+            tokens: Regex::new(TOKENS).expect("Invalid template tokens"),
+        }
+    }
+
+    /// `line` split into literal text and placeholders
+    pub fn segments<'a>(&self, line: &'a str) -> Vec<Segment<'a>> {
+        let mut out = Vec::new();
+        let mut last = 0;
+        for caps in self.tokens.captures_iter(line) {
+            let m = caps.get(0).unwrap();
+            if m.start() > last {
+                out.push(Segment::Text(&line[last..m.start()]));
+            }
+            out.push(Segment::Token(if caps.name("ts").is_some() {
+                Token::Timestamp
+            } else if caps.name("uuid").is_some() {
+                Token::Uuid
+            } else if caps.name("hex").is_some() {
+                Token::Hex
+            } else {
+                Token::Num
+            }));
+            last = m.end();
+        }
+        if last < line.len() {
+            out.push(Segment::Text(&line[last..]));
+        }
+        out
+    }
+
+    /// `line` with variable tokens replaced by placeholders
+    pub fn template(&self, line: &str) -> String {
+        self.segments(line)
+            .into_iter()
+            .map(|s| match s {
+                Segment::Text(text) => text,
+                Segment::Token(token) => token.placeholder(),
+            })
+            .collect()
+    }
+}
+
+/// Records of one template on one day
+#[derive(Debug, Clone, Serialize)]
+pub struct TemplateStat {
+    pub template_hash: String,
+    pub template: String,
+    pub count: u64,
+    /// Records with a rule (escalated or killed by rule)
+    pub escalations: u64,
+    pub kills: u64,
+    pub first_seen_ms: u64,
+    pub last_seen_ms: u64,
+}
+
+/// `GET /templates`
+#[derive(Debug, Clone, Serialize)]
+pub struct TemplateReport {
+    /// Start of the UTC day counted
+    pub day_start_ms: u64,
+    /// Records whose template did not fit in the table
+    pub untracked: u64,
+    /// Most frequent first
+    pub templates: Vec<TemplateStat>,
+}
+
+#[derive(Default)]
+struct Table {
+    day: u64,
+    untracked: u64,
+    templates: HashMap<String, TemplateStat>,
+}
+
+/// Per-template record counts for the current UTC day
+#[derive(Default)]
+pub struct Templates {
+    table: Mutex<Table>,
+}
+
+impl Templates {
+    /// Count a record whose line normalizes to `template`
+    pub fn observe(&self, record: &DecisionRecord, template: &str) {
+        let now = record.timestamp_ms;
+        let mut table = self.table.lock().unwrap();
+        if table.day != now / DAY_MS {
+            *table = Table {
+                day: now / DAY_MS,
+                ..Default::default()
+            };
+        }
+        let full = table.templates.len() >= MAX_TEMPLATES;
+        let stat = match table.templates.get_mut(&record.template_hash) {
+            Some(stat) => stat,
+            None if full => {
+                table.untracked += 1;
+                return;
+            }
+            None => table
+                .templates
+                .entry(record.template_hash.clone())
+                .or_insert_with(|| TemplateStat {
+                    template_hash: record.template_hash.clone(),
+                    template: template.to_string(),
+                    count: 0,
+                    escalations: 0,
+                    kills: 0,
+                    first_seen_ms: now,
+                    last_seen_ms: now,
+                }),
+        };
+        stat.count += 1;
+        stat.escalations += record.rule.is_some() as u64;
+        stat.kills += (record.action == "KILL") as u64;
+        stat.last_seen_ms = now;
+    }
+
+    /// The `limit` most frequent templates today
+    pub fn report(&self, limit: usize) -> TemplateReport {
+        let table = self.table.lock().unwrap();
+        let mut templates: Vec<TemplateStat> = table.templates.values().cloned().collect();
+        templates.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then(a.first_seen_ms.cmp(&b.first_seen_ms))
+        });
+        templates.truncate(limit);
+        TemplateReport {
+            day_start_ms: table.day * DAY_MS,
+            untracked: table.untracked,
+            templates,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template() {
+        let normalizer = Normalizer::new();
+        assert_eq!(
+            normalizer.template("2026-10-15T09:30:01.123Z Order 1234 filled at 45.20 (tx 0x1f3a)"),
+            "<ts> Order <num> filled at <num> (tx <hex>)"
+        );
+        assert_eq!(
+            normalizer.template("Oct 15 09:30:01 job 6f1c2a9e-3b7d-4c1e-9a2f-0d4e5b6c7a8f done"),
+            "<ts> job <uuid> done"
+        );
+        assert_eq!(
+            normalizer.template("at 09:30:01 commit 3f2a9c1b7e4d5a6b"),
+            "at <ts> commit <hex>"
+        );
+        assert_eq!(normalizer.template("Heartbeat ok"), "Heartbeat ok");
+    }
+
+    #[test]
+    fn test_templates_per_day() {
+        let templates = Templates::default();
+        let record = |hash: &str, action: &str, rule: Option<&str>, timestamp_ms| DecisionRecord {
+            id: 1,
+            timestamp_ms,
+            monotonic_ms: 0,
+            clock_behind_ms: None,
+            input_log: String::new(),
+            input_hash: String::new(),
+            template_hash: hash.to_string(),
+            redacted: false,
+            action: action.to_string(),
+            confidence: 100,
+            model_confidence: None,
+            filtered: true,
+            latency_ms: 0,
+            model_fingerprint: String::new(),
+            prompt_hash: String::new(),
+            raw_response: None,
+            rendered_prompt_hash: None,
+            rendered_prompt: None,
+            error: None,
+            rule: rule.map(str::to_string),
+            reason: None,
+            context_hash: None,
+            batch_size: None,
+            suppressed: None,
+            policy: None,
+            verdict: None,
+            profile: None,
+            instance: None,
+            agent_id: None,
+            session_id: None,
+            connection_id: None,
+            backfilled: false,
+            backfill_of: None,
+            recovered: false,
+            encrypted: false,
+            explanation: None,
+        };
+        let day = 20_000 * DAY_MS;
+        templates.observe(&record("a", "SUSTAIN", None, day + 1), "Order <num>");
+        templates.observe(&record("b", "KILL", Some("r"), day + 2), "rm -rf /");
+        templates.observe(&record("a", "SUSTAIN", None, day + 3), "Order <num>");
+
+        let report = templates.report(10);
+        assert_eq!(report.day_start_ms, day);
+        assert_eq!(report.templates[0].template, "Order <num>");
+        assert_eq!(report.templates[0].count, 2);
+        assert_eq!(report.templates[0].last_seen_ms, day + 3);
+        assert_eq!(report.templates[1].kills, 1);
+        assert_eq!(report.templates[1].escalations, 1);
+        assert_eq!(templates.report(1).templates.len(), 1);
+
+        // A new UTC day starts from zero
+        templates.observe(&record("a", "SUSTAIN", None, day + DAY_MS), "Order <num>");
+        let report = templates.report(10);
+        assert_eq!(report.templates.len(), 1);
+        assert_eq!(report.templates[0].count, 1);
+    }
+}
@@ -0,0 +1,717 @@
+//! Notifications - Webhooks on KILL, FAIL, Pipeline-Error, Breaker and Stall Events
+//!
+//! A kill-switch firing at 3am must page someone. `[[notify.webhook]]`
+//! entries receive a POST for each event they subscribe to:
+//!
+//! - `kill` / `fail` - a KILL or unparseable (FAIL) decision was audited; a
+//!   FAIL verdict turned into another action (`--on-uncertain`, a policy
+//!   rule) raises `fail` as well
+//! - `pause` - a policy rule suspended the target instead (see `policy`)
+//! - `pipeline_error` - judging a line panicked (see `contain`)
+//! - `breaker` - an LLM endpoint's circuit breaker opened
+//! - `stall` - the kernel itself is wedged (see `watchdog`)
+//!
+//! Formats: `json` (flat event object), `slack`, `discord`, `pagerduty`
+//! (Events API v2). `template` overrides the message text with
+//! placeholders such as `{decision_id}`, `{input_hash}`, `{latency_ms}`,
+//! `{agent_id}`, `{explanation}` (see `explain`).
+//! Failed deliveries are retried with exponential backoff; delivery never
+//! blocks the pipeline. `[notify.email]` sends the same events by SMTP
+//! (see `email`).
+//!
+//! ```toml
+//! [notify]
+//! retries = 3
+//! backoff_ms = 500
+//!
+//! [[notify.webhook]]
+//! url = "https://hooks.slack.com/services/T000/B000/XXXX"
+//! format = "slack"
+//! events = ["kill", "breaker"]
+//! template = "KILL #{decision_id} ({rule}, {latency_ms}ms): {reason}"
+//! ```
+
+use crate::audit::DecisionRecord;
+use crate::email::{EmailConfig, Mailer};
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{broadcast, mpsc};
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+use tracing::{debug, warn};
+
+/// PagerDuty Events API v2 endpoint (default `url` for `pagerduty`)
+const PAGERDUTY_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+
+/// Placeholders a `template` may use
+const PLACEHOLDERS: &[&str] = &[
+    "event",
+    "decision_id",
+    "action",
+    "confidence",
+    "latency_ms",
+    "input_hash",
+    "input_log",
+    "rule",
+    "reason",
+    "model",
+    "suppressed",
+    "policy",
+    "verdict",
+    "backfilled",
+    "explanation",
+    "error",
+    "agent_id",
+    "session_id",
+    "connection_id",
+];
+
+/// `[notify]` table
+#[derive(Debug, Clone, Deserialize)]
+pub struct NotifyConfig {
+    #[serde(default)]
+    pub webhook: Vec<Webhook>,
+    /// SMTP alerts (`[notify.email]`)
+    #[serde(default)]
+    pub email: Option<EmailConfig>,
+    /// Attempts after the first failed delivery
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+    /// Delay before the first retry, doubled for each further one (milliseconds)
+    #[serde(default = "default_backoff_ms")]
+    pub backoff_ms: u64,
+    /// Per-attempt timeout (milliseconds)
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+fn default_retries() -> u32 {
+    3
+}
+
+fn default_backoff_ms() -> u64 {
+    500
+}
+
+fn default_timeout_ms() -> u64 {
+    5000
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        Self {
+            webhook: Vec::new(),
+            email: None,
+            retries: default_retries(),
+            backoff_ms: default_backoff_ms(),
+            timeout_ms: default_timeout_ms(),
+        }
+    }
+}
+
+/// One `[[notify.webhook]]` entry
+#[derive(Debug, Clone, Deserialize)]
+pub struct Webhook {
+    /// Required except for `pagerduty`
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub format: Format,
+    /// Events to send (default: all)
+    #[serde(default = "all_events")]
+    pub events: Vec<EventKind>,
+    /// Message text with `{placeholder}`s (default: a one-line summary)
+    #[serde(default)]
+    pub template: Option<String>,
+    /// PagerDuty integration key
+    #[serde(default)]
+    pub routing_key: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Json,
+    Slack,
+    Discord,
+    Pagerduty,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventKind {
+    Kill,
+    Fail,
+    Pause,
+    #[serde(rename = "pipeline_error")]
+    PipelineError,
+    Breaker,
+    Stall,
+}
+
+pub fn all_events() -> Vec<EventKind> {
+    vec![
+        EventKind::Kill,
+        EventKind::Fail,
+        EventKind::Pause,
+        EventKind::PipelineError,
+        EventKind::Breaker,
+        EventKind::Stall,
+    ]
+}
+
+impl NotifyConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        for (i, hook) in self.webhook.iter().enumerate() {
+            hook.validate()
+                .map_err(|e| format!("notify.webhook[{}]: {}", i, e))?;
+        }
+        if let Some(ref email) = self.email {
+            email.validate()?;
+        }
+        Ok(())
+    }
+}
+
+impl Webhook {
+    fn validate(&self) -> Result<(), String> {
+        match (&self.url, self.format) {
+            (Some(url), _) if !url.contains("://") => {
+                return Err(format!("invalid url '{}'", url));
+            }
+            (None, Format::Pagerduty) => {}
+            (None, _) => return Err("url is required".to_string()),
+            _ => {}
+        }
+        if self.format == Format::Pagerduty && self.routing_key.is_none() {
+            return Err("pagerduty needs a routing_key".to_string());
+        }
+        if let Some(ref template) = self.template {
+            for name in template
+                .split('{')
+                .skip(1)
+                .filter_map(|r| r.split_once('}'))
+            {
+                if !PLACEHOLDERS.contains(&name.0) {
+                    return Err(format!("unknown placeholder {{{}}} in template", name.0));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn url(&self) -> &str {
+        self.url.as_deref().unwrap_or(PAGERDUTY_URL)
+    }
+}
+
+/// Something worth telling a human about
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// A KILL, FAIL, PAUSE or PIPELINE_ERROR decision record
+    Decision(Box<DecisionRecord>),
+    /// An LLM endpoint's circuit breaker opened
+    BreakerOpen { model: String, error: String },
+    /// The watchdog found the kernel wedged
+    Stall { reason: String },
+}
+
+impl Event {
+    /// What the event is, then what it also counts as: a FAIL verdict
+    /// `--on-uncertain` or a policy rule acted on is a FAIL too
+    pub fn kinds(&self) -> Vec<EventKind> {
+        let kind = self.kind();
+        match self {
+            Self::Decision(r) if r.verdict.as_deref() == Some("FAIL") && r.action != "FAIL" => {
+                kind.into_iter().chain([EventKind::Fail]).collect()
+            }
+            _ => kind.into_iter().collect(),
+        }
+    }
+
+    /// The first of the event's kinds in `events`, if any
+    pub fn subscribed(&self, events: &[EventKind]) -> Option<EventKind> {
+        self.kinds().into_iter().find(|kind| events.contains(kind))
+    }
+
+    fn kind(&self) -> Option<EventKind> {
+        match self {
+            Self::Decision(r) if r.action == "KILL" => Some(EventKind::Kill),
+            Self::Decision(r) if r.action == "FAIL" => Some(EventKind::Fail),
+            // Freezes, clamps and restrictions hold the agent like a PAUSE
+            Self::Decision(r)
+                if r.action == "PAUSE"
+                    || r.action == "RESTRICT"
+                    || crate::cgroup::Clamp::from_action(&r.action).is_some() =>
+            {
+                Some(EventKind::Pause)
+            }
+            Self::Decision(r) if r.action == "PIPELINE_ERROR" => Some(EventKind::PipelineError),
+            Self::Decision(_) => None,
+            Self::BreakerOpen { .. } => Some(EventKind::Breaker),
+            Self::Stall { .. } => Some(EventKind::Stall),
+        }
+    }
+
+    /// Flat field map: the `json` payload and the template values
+    pub fn fields(&self) -> serde_json::Map<String, serde_json::Value> {
+        let value = match self {
+            Self::Decision(r) => serde_json::json!({
+                "event": r.action.to_lowercase(),
+                "decision_id": r.id,
+                "action": r.action,
+                "confidence": r.confidence,
+                "latency_ms": r.latency_ms,
+                "input_hash": r.input_hash,
+                "input_log": r.input_log,
+                "rule": r.rule,
+                "reason": r.reason,
+                "model": r.model_fingerprint,
+                "suppressed": r.suppressed,
+                "policy": r.policy,
+                "verdict": r.verdict,
+                "backfilled": r.backfilled,
+                "explanation": r.explanation,
+                "agent_id": r.agent_id,
+                "session_id": r.session_id,
+                "connection_id": r.connection_id,
+                "timestamp_ms": r.timestamp_ms,
+            }),
+            Self::BreakerOpen { model, error } => serde_json::json!({
+                "event": "breaker",
+                "model": model,
+                "error": error,
+                "timestamp_ms": now_ms(),
+            }),
+            Self::Stall { reason } => serde_json::json!({
+                "event": "stall",
+                "reason": reason,
+                "timestamp_ms": now_ms(),
+            }),
+        };
+        match value {
+            serde_json::Value::Object(map) => map,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn default_text(&self) -> String {
+        match self {
+            Self::Decision(r) => {
+                // Pre-filtered (fast path) latencies are microseconds
+                let unit = if r.filtered { "μs" } else { "ms" };
+                let mut text = format!(
+                    "🚨 tripwired {} #{} ({}% in {}{}",
+                    r.action, r.id, r.confidence, r.latency_ms, unit
+                );
+                if let Some(ref rule) = r.rule {
+                    text.push_str(&format!(", rule {}", rule));
+                }
+                text.push(')');
+                if let Some(ref reason) = r.reason {
+                    text.push_str(&format!(": {}", reason));
+                }
+                text.push_str(&format!(
+                    " [input {}]",
+                    &r.input_hash[..r.input_hash.len().min(12)]
+                ));
+                if let (Some(policy), Some(verdict)) = (&r.policy, &r.verdict) {
+                    text.push_str(&format!(" - {} changed by policy {}", verdict, policy));
+                }
+                if let Some(ref suppressed) = r.suppressed {
+                    text.push_str(&format!(" - not executed ({})", suppressed));
+                }
+                if let Some(of) = r.backfill_of {
+                    text.push_str(&format!(" - backfill of #{}, not executed", of));
+                }
+                text
+            }
+            Self::BreakerOpen { model, error } => {
+                format!("🔌 tripwired circuit open for {}: {}", model, error)
+            }
+            Self::Stall { reason } => {
+                format!("💀 tripwired is wedged and not protecting: {}", reason)
+            }
+        }
+    }
+}
+
+/// Build the request body for one webhook
+fn payload(hook: &Webhook, event: &Event) -> serde_json::Value {
+    let fields = event.fields();
+    let text = match (&hook.template, event) {
+        (Some(template), _) => render(template, &fields),
+        // The one-line summary is also the email subject: the why goes below
+        (None, Event::Decision(r)) => match r.explanation {
+            Some(ref why) => format!("{}\n{}", event.default_text(), why),
+            None => event.default_text(),
+        },
+        (None, _) => event.default_text(),
+    };
+    match hook.format {
+        Format::Json => {
+            let mut body = fields;
+            body.insert("message".to_string(), text.into());
+            serde_json::Value::Object(body)
+        }
+        Format::Slack => serde_json::json!({ "text": text }),
+        Format::Discord => serde_json::json!({ "content": text }),
+        Format::Pagerduty => {
+            let (severity, dedup_key) = match event {
+                Event::Decision(r) => (
+                    if r.action == "KILL" {
+                        "critical"
+                    } else {
+                        "error"
+                    },
+                    format!("tripwired-decision-{}", r.id),
+                ),
+                Event::BreakerOpen { model, .. } => {
+                    ("warning", format!("tripwired-breaker-{}", model))
+                }
+                Event::Stall { .. } => ("critical", "tripwired-stall".to_string()),
+            };
+            serde_json::json!({
+                "routing_key": hook.routing_key,
+                "event_action": "trigger",
+                "dedup_key": dedup_key,
+                "payload": {
+                    "summary": text,
+                    "source": "tripwired",
+                    "severity": severity,
+                    "custom_details": fields,
+                }
+            })
+        }
+    }
+}
+
+/// Fill `{name}` placeholders from the event fields (missing -> empty)
+fn render(template: &str, fields: &serde_json::Map<String, serde_json::Value>) -> String {
+    let mut out = template.to_string();
+    for name in PLACEHOLDERS {
+        let value = match fields.get(*name) {
+            Some(serde_json::Value::String(s)) => s.clone(),
+            Some(serde_json::Value::Null) | None => String::new(),
+            Some(other) => other.to_string(),
+        };
+        out = out.replace(&format!("{{{}}}", name), &value);
+    }
+    out
+}
+
+/// Delivers events to the configured webhooks and mailer in the background
+///
+/// Cheap to clone; a notifier without webhooks or email drops every event.
+#[derive(Clone, Default)]
+pub struct Notifier {
+    queue: Option<mpsc::UnboundedSender<Event>>,
+    /// Queue worker and in-flight deliveries
+    deliveries: TaskTracker,
+    /// Tells the queue worker to flush and stop
+    closing: CancellationToken,
+}
+
+impl Notifier {
+    pub fn new(config: &NotifyConfig) -> Self {
+        let mut notifier = Self::default();
+        if config.webhook.is_empty() && config.email.is_none() {
+            return notifier;
+        }
+        let client = Client::builder()
+            .timeout(Duration::from_millis(config.timeout_ms))
+            .build()
+            .expect("Failed to build HTTP client");
+        let mut mailer = config
+            .email
+            .as_ref()
+            .map(|email| Mailer::new(email, config).expect("Failed to set up SMTP transport"));
+        let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+        let config = config.clone();
+        let tracker = notifier.deliveries.clone();
+        let closing = notifier.closing.clone();
+        notifier.deliveries.spawn(async move {
+            let mut digest = mailer
+                .as_ref()
+                .and_then(Mailer::digest_interval)
+                .map(|every| tokio::time::interval_at(tokio::time::Instant::now() + every, every));
+            let dispatch = |event: Event, mailer: &mut Option<Mailer>| {
+                let hooks = config.webhook.iter();
+                for hook in hooks.filter(|h| event.subscribed(&h.events).is_some()) {
+                    let body = payload(hook, &event);
+                    let (client, hook, config) = (client.clone(), hook.clone(), config.clone());
+                    tracker.spawn(async move { deliver(&client, &hook, &config, &body).await });
+                }
+                if let Some(mailer) = mailer {
+                    if let Some(mail) = mailer.take(event) {
+                        mailer.send(mail, &tracker);
+                    }
+                }
+            };
+            loop {
+                tokio::select! {
+                    Some(event) = rx.recv() => dispatch(event, &mut mailer),
+                    _ = async { digest.as_mut().unwrap().tick().await }, if digest.is_some() => {
+                        if let Some(mailer) = mailer.as_mut() {
+                            if let Some(mail) = mailer.flush() {
+                                mailer.send(mail, &tracker);
+                            }
+                        }
+                    }
+                    _ = closing.cancelled() => break,
+                }
+            }
+            while let Ok(event) = rx.try_recv() {
+                dispatch(event, &mut mailer);
+            }
+            // Pending digest goes out with the shutdown
+            if let Some(mailer) = mailer.as_mut() {
+                if let Some(mail) = mailer.flush() {
+                    mailer.send(mail, &tracker);
+                }
+            }
+        });
+        notifier.queue = Some(tx);
+        notifier
+    }
+
+    pub fn send(&self, event: Event) {
+        if let Some(ref queue) = self.queue {
+            let _ = queue.send(event);
+        }
+    }
+
+    /// Send what is queued and wait (up to `timeout`) for deliveries to
+    /// finish; returns false on timeout
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        self.closing.cancel();
+        self.deliveries.close();
+        tokio::time::timeout(timeout, self.deliveries.wait())
+            .await
+            .is_ok()
+    }
+}
+
+/// POST with retries; gives up on client errors other than 408 / 429
+async fn deliver(client: &Client, hook: &Webhook, config: &NotifyConfig, body: &serde_json::Value) {
+    let mut backoff = Duration::from_millis(config.backoff_ms);
+    for attempt in 0..=config.retries {
+        if attempt > 0 {
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+        let error = match client.post(hook.url()).json(body).send().await {
+            Ok(response) if response.status().is_success() => {
+                debug!("📣 Webhook delivered to {}", hook.url());
+                return;
+            }
+            Ok(response) => {
+                let status = response.status();
+                if !retryable(status) {
+                    warn!(
+                        "📣 Webhook {} rejected the event: HTTP {}",
+                        hook.url(),
+                        status
+                    );
+                    return;
+                }
+                format!("HTTP {}", status)
+            }
+            Err(e) => e.to_string(),
+        };
+        debug!(
+            "📣 Webhook {} attempt {} failed: {}",
+            hook.url(),
+            attempt + 1,
+            error
+        );
+    }
+    warn!(
+        "📣 Webhook {} failed after {} attempts",
+        hook.url(),
+        config.retries + 1
+    );
+}
+
+fn retryable(status: StatusCode) -> bool {
+    status.is_server_error()
+        || status == StatusCode::TOO_MANY_REQUESTS
+        || status == StatusCode::REQUEST_TIMEOUT
+}
+
+/// Forward audited KILL / FAIL records to the notifier until `stop`
+///
+/// Records already broadcast when `stop` fires are still forwarded. With
+/// `explained`, executed KILLs are left to the explainer, which notifies
+/// once their explanation is in (see `explain`).
+pub async fn forward_decisions(
+    mut records: broadcast::Receiver<DecisionRecord>,
+    notifier: Notifier,
+    stop: CancellationToken,
+    explained: bool,
+) {
+    let forward = |record: DecisionRecord| {
+        if explained && record.action == "KILL" && record.backfill_of.is_none() {
+            return;
+        }
+        notifier.send(Event::Decision(Box::new(record)));
+    };
+    loop {
+        let record = tokio::select! {
+            received = records.recv() => match received {
+                Ok(record) => record,
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    warn!("📣 Notifier lagged, {} decisions not checked", missed);
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+            _ = stop.cancelled() => {
+                while let Ok(record) = records.try_recv() {
+                    forward(record);
+                }
+                return;
+            }
+        };
+        forward(record);
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kill() -> Event {
+        Event::Decision(
+            serde_json::from_value(serde_json::json!({
+                "id": 42, "timestamp_ms": 0, "input_log": "sudo rm -rf /",
+                "input_hash": "84411e63e39fce42977374dd7dca3ff9", "action": "KILL",
+                "confidence": 97, "filtered": false, "latency_ms": 180,
+                "model_fingerprint": "m@1", "prompt_hash": "", "raw_response": null,
+                "rule": "essential#0", "reason": "wipes the disk",
+                "agent_id": "trader-7", "session_id": "run-1", "connection_id": 3,
+            }))
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_payload_formats() {
+        let config: NotifyConfig = toml::from_str(
+            "[[webhook]]\nurl = 'http://x'\n\
+             [[webhook]]\nurl = 'http://x'\nformat = 'slack'\n\
+             template = 'KILL #{decision_id} {latency_ms}ms {rule} {model} {agent_id}'\n\
+             [[webhook]]\nformat = 'pagerduty'\nrouting_key = 'abc'\nevents = ['kill']\n",
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+
+        let json = payload(&config.webhook[0], &kill());
+        assert_eq!(json["decision_id"], 42);
+        assert_eq!(json["connection_id"], 3);
+        assert_eq!(json["input_hash"], "84411e63e39fce42977374dd7dca3ff9");
+        assert_eq!(
+            json["message"],
+            "🚨 tripwired KILL #42 (97% in 180ms, rule essential#0): wipes the disk [input 84411e63e39f]"
+        );
+
+        let slack = payload(&config.webhook[1], &kill());
+        assert_eq!(slack["text"], "KILL #42 180ms essential#0 m@1 trader-7");
+
+        let pd = &config.webhook[2];
+        assert_eq!(pd.url(), PAGERDUTY_URL);
+        let body = payload(pd, &kill());
+        assert_eq!(body["payload"]["severity"], "critical");
+        assert_eq!(body["dedup_key"], "tripwired-decision-42");
+
+        let Event::Decision(mut record) = kill() else {
+            unreachable!()
+        };
+        record.explanation = Some("It deletes the root filesystem.".to_string());
+        let explained = Event::Decision(record);
+        let json = payload(&config.webhook[0], &explained);
+        assert_eq!(json["explanation"], "It deletes the root filesystem.");
+        assert!(json["message"]
+            .as_str()
+            .unwrap()
+            .ends_with("[input 84411e63e39f]\nIt deletes the root filesystem."));
+
+        let breaker = Event::BreakerOpen {
+            model: "phi".to_string(),
+            error: "timeout".to_string(),
+        };
+        assert_eq!(breaker.kinds(), vec![EventKind::Breaker]);
+        assert_eq!(
+            payload(&config.webhook[0], &breaker)["message"],
+            "🔌 tripwired circuit open for phi: timeout"
+        );
+
+        let stall = Event::Stall {
+            reason: "event loop stalled for 12000ms".to_string(),
+        };
+        let body = payload(pd, &stall);
+        assert_eq!(body["payload"]["severity"], "critical");
+        assert_eq!(body["dedup_key"], "tripwired-stall");
+    }
+
+    #[test]
+    fn test_uncertain_kinds() {
+        assert_eq!(kill().kinds(), vec![EventKind::Kill]);
+        assert_eq!(kill().subscribed(&[EventKind::Fail]), None);
+
+        // --on-uncertain kill / pause: the action taken, and a FAIL
+        for (action, kind) in [("KILL", EventKind::Kill), ("PAUSE", EventKind::Pause)] {
+            let Event::Decision(mut record) = kill() else {
+                unreachable!()
+            };
+            record.action = action.to_string();
+            record.verdict = Some("FAIL".to_string());
+            record.policy = Some("on-uncertain".to_string());
+            let event = Event::Decision(record);
+            assert_eq!(event.kinds(), vec![kind, EventKind::Fail]);
+            assert_eq!(event.subscribed(&[EventKind::Fail]), Some(EventKind::Fail));
+            assert_eq!(event.subscribed(&all_events()), Some(kind));
+        }
+
+        let Event::Decision(mut record) = kill() else {
+            unreachable!()
+        };
+        record.action = "FAIL".to_string();
+        assert_eq!(Event::Decision(record).kinds(), vec![EventKind::Fail]);
+    }
+
+    #[test]
+    fn test_validate() {
+        let bad = |toml: &str| {
+            toml::from_str::<NotifyConfig>(toml)
+                .unwrap()
+                .validate()
+                .unwrap_err()
+        };
+        assert_eq!(
+            bad("[[webhook]]\nformat = 'slack'"),
+            "notify.webhook[0]: url is required"
+        );
+        assert!(bad("[[webhook]]\nformat = 'pagerduty'").contains("routing_key"));
+        assert!(bad("[[webhook]]\nurl = 'http://x'\ntemplate = '{id}'").contains("{id}"));
+        assert!(
+            toml::from_str::<NotifyConfig>("[[webhook]]\nurl = 'x'\nevents = ['warn']").is_err()
+        );
+    }
+
+    #[test]
+    fn test_retryable() {
+        assert!(retryable(StatusCode::BAD_GATEWAY));
+        assert!(retryable(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!retryable(StatusCode::BAD_REQUEST));
+    }
+}
@@ -0,0 +1,574 @@
+//! OTLP Logs Receiver - OpenTelemetry SDKs as an Input
+//!
+//! `--otlp-logs-port 4318` accepts OTLP/HTTP log exports on
+//! `POST /v1/logs` (loopback), so agents already instrumented with an
+//! OpenTelemetry SDK are covered by pointing their exporter here. Both
+//! encodings are decoded: `application/x-protobuf` (the SDK default) and
+//! `application/json`. Compressed bodies are not supported (leave the
+//! exporter's compression off).
+//!
+//! Each log record becomes one JSON line (see `parse`): `msg` is the body
+//! (a map body is merged in as fields), `severity` the severity text or
+//! the level named by the severity number, then the record's attributes
+//! and `trace_id` / `span_id`. Records are grouped into one agent per
+//! `service.name` (plus `service.instance.id` when set), so per-agent
+//! history and sequence rules work as for a socket connection.
+//!
+//! ```text
+//! OTEL_EXPORTER_OTLP_LOGS_ENDPOINT=http://127.0.0.1:4318/v1/logs
+//! ```
+
+use crate::checkpoint::to_hex;
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::Router;
+use serde_json::{Map, Value};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+/// Largest export request accepted
+const MAX_BODY_BYTES: usize = 16 * 1024 * 1024;
+
+/// One decoded log record: the agent it belongs to and its JSON line
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogLine {
+    /// `service.name[/service.instance.id]`
+    pub agent: String,
+    pub line: String,
+}
+
+/// Serve `POST /v1/logs` until `shutdown`, sending every record to `lines`
+pub async fn serve(
+    port: u16,
+    lines: mpsc::Sender<LogLine>,
+    shutdown: CancellationToken,
+) -> std::io::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port)).await?;
+    let router = Router::new()
+        .route("/v1/logs", post(export))
+        .layer(axum::extract::DefaultBodyLimit::max(MAX_BODY_BYTES))
+        .with_state(lines);
+    axum::serve(listener, router)
+        .with_graceful_shutdown(shutdown.cancelled_owned())
+        .await
+}
+
+async fn export(
+    State(lines): State<mpsc::Sender<LogLine>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let header = |name| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_ascii_lowercase()
+    };
+    let json = header(header::CONTENT_TYPE).starts_with("application/json");
+    let encoding = header(header::CONTENT_ENCODING);
+    if !encoding.is_empty() && encoding != "identity" {
+        return (
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            format!("content-encoding {} not supported", encoding),
+        )
+            .into_response();
+    }
+    let decoded = match json {
+        true => decode_json(&body),
+        false => decode_protobuf(&body),
+    };
+    let records = match decoded {
+        Ok(records) => records,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    for record in records {
+        // Closed: the pipeline is shutting down, so the exporter retries
+        if lines.send(record).await.is_err() {
+            return StatusCode::SERVICE_UNAVAILABLE.into_response();
+        }
+    }
+    // An empty ExportLogsServiceResponse, in the request's encoding
+    match json {
+        true => ([(header::CONTENT_TYPE, "application/json")], "{}").into_response(),
+        false => (
+            [(header::CONTENT_TYPE, "application/x-protobuf")],
+            Vec::new(),
+        )
+            .into_response(),
+    }
+}
+
+/// Build the line for one record
+fn render(
+    body: Option<Value>,
+    severity_number: u64,
+    severity_text: &str,
+    attributes: Vec<(String, Value)>,
+    trace_id: &str,
+    span_id: &str,
+) -> String {
+    let mut fields = Map::new();
+    for (key, value) in attributes {
+        fields.insert(key, value);
+    }
+    if !trace_id.is_empty() {
+        fields.insert("trace_id".into(), trace_id.into());
+    }
+    if !span_id.is_empty() {
+        fields.insert("span_id".into(), span_id.into());
+    }
+    let severity = match severity_text {
+        "" => severity_name(severity_number),
+        text => Some(text),
+    };
+    if let Some(severity) = severity {
+        fields.insert("severity".into(), severity.into());
+    }
+    match body {
+        Some(Value::Object(map)) => fields.extend(map),
+        Some(Value::String(text)) => {
+            fields.insert("msg".into(), text.into());
+        }
+        Some(other) => {
+            fields.insert("msg".into(), other.to_string().into());
+        }
+        None => {}
+    }
+    Value::Object(fields).to_string()
+}
+
+/// Level named by an OTel severity number
+fn severity_name(number: u64) -> Option<&'static str> {
+    Some(match number {
+        1..=4 => "TRACE",
+        5..=8 => "DEBUG",
+        9..=12 => "INFO",
+        13..=16 => "WARN",
+        17..=20 => "ERROR",
+        21..=24 => "FATAL",
+        _ => return None,
+    })
+}
+
+/// Agent name from the resource attributes
+fn agent(resource: &[(String, Value)]) -> String {
+    let get = |key: &str| {
+        resource
+            .iter()
+            .find(|(k, _)| k == key)
+            .and_then(|(_, v)| v.as_str())
+    };
+    let service = get("service.name").unwrap_or("unknown_service");
+    match get("service.instance.id") {
+        Some(instance) => format!("{}/{}", service, instance),
+        None => service.to_string(),
+    }
+}
+
+// OTLP/JSON
+
+fn decode_json(body: &[u8]) -> Result<Vec<LogLine>, String> {
+    let request: Value = serde_json::from_slice(body).map_err(|e| e.to_string())?;
+    let list = |value: &Value, key: &str| -> Vec<Value> {
+        value
+            .get(key)
+            .and_then(|v| v.as_array())
+            .cloned()
+            .unwrap_or_default()
+    };
+    let mut lines = Vec::new();
+    for resource_logs in list(&request, "resourceLogs") {
+        let resource = resource_logs
+            .get("resource")
+            .map(|r| json_attributes(&list(r, "attributes")))
+            .unwrap_or_default();
+        let agent = agent(&resource);
+        for scope_logs in list(&resource_logs, "scopeLogs") {
+            for record in list(&scope_logs, "logRecords") {
+                let text = |key: &str| record.get(key).and_then(|v| v.as_str()).unwrap_or("");
+                let line = render(
+                    record.get("body").map(json_any_value),
+                    json_u64(record.get("severityNumber")).unwrap_or(0),
+                    text("severityText"),
+                    json_attributes(&list(&record, "attributes")),
+                    text("traceId"),
+                    text("spanId"),
+                );
+                lines.push(LogLine {
+                    agent: agent.clone(),
+                    line,
+                });
+            }
+        }
+    }
+    Ok(lines)
+}
+
+/// 64-bit integers are strings in OTLP/JSON; enums may be numbers
+fn json_u64(value: Option<&Value>) -> Option<u64> {
+    match value? {
+        Value::Number(n) => n.as_u64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+fn json_attributes(attributes: &[Value]) -> Vec<(String, Value)> {
+    attributes
+        .iter()
+        .filter_map(|kv| {
+            let key = kv.get("key")?.as_str()?.to_string();
+            Some((key, kv.get("value").map_or(Value::Null, json_any_value)))
+        })
+        .collect()
+}
+
+fn json_any_value(value: &Value) -> Value {
+    let Some((kind, inner)) = value.as_object().and_then(|o| o.iter().next()) else {
+        return Value::Null;
+    };
+    let values = || {
+        inner
+            .get("values")
+            .and_then(|v| v.as_array())
+            .cloned()
+            .unwrap_or_default()
+    };
+    match kind.as_str() {
+        "intValue" => match inner {
+            Value::String(s) => s.parse::<i64>().map_or(Value::Null, Value::from),
+            number => number.clone(),
+        },
+        "arrayValue" => Value::Array(values().iter().map(json_any_value).collect()),
+        "kvlistValue" => Value::Object(json_attributes(&values()).into_iter().collect()),
+        // stringValue, boolValue, doubleValue, bytesValue (base64)
+        _ => inner.clone(),
+    }
+}
+
+// OTLP/protobuf (opentelemetry/proto/collector/logs/v1)
+
+/// Field number and value of one protobuf field
+enum Field<'a> {
+    Varint(u64),
+    Fixed64(u64),
+    Bytes(&'a [u8]),
+    Fixed32,
+}
+
+/// Iterates over the fields of a protobuf message
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    fn varint(&mut self) -> Result<u64, String> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let (&byte, rest) = self.buf.split_first().ok_or("truncated varint")?;
+            self.buf = rest;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err("varint too long".to_string())
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.buf.len() < len {
+            return Err("truncated field".to_string());
+        }
+        let (taken, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Ok(taken)
+    }
+
+    fn next_field(&mut self) -> Result<Option<(u64, Field<'a>)>, String> {
+        if self.buf.is_empty() {
+            return Ok(None);
+        }
+        let key = self.varint()?;
+        let field = match key & 7 {
+            0 => Field::Varint(self.varint()?),
+            1 => Field::Fixed64(u64::from_le_bytes(self.take(8)?.try_into().unwrap())),
+            2 => {
+                let len = self.varint()? as usize;
+                Field::Bytes(self.take(len)?)
+            }
+            5 => {
+                self.take(4)?;
+                Field::Fixed32
+            }
+            wire => return Err(format!("unsupported wire type {}", wire)),
+        };
+        Ok(Some((key >> 3, field)))
+    }
+}
+
+fn text(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+fn decode_protobuf(body: &[u8]) -> Result<Vec<LogLine>, String> {
+    let mut lines = Vec::new();
+    let mut request = Reader::new(body);
+    while let Some((number, field)) = request.next_field()? {
+        if let (1, Field::Bytes(resource_logs)) = (number, field) {
+            decode_resource_logs(resource_logs, &mut lines)?;
+        }
+    }
+    Ok(lines)
+}
+
+fn decode_resource_logs(buf: &[u8], lines: &mut Vec<LogLine>) -> Result<(), String> {
+    let mut resource = Vec::new();
+    let mut scopes = Vec::new();
+    let mut reader = Reader::new(buf);
+    while let Some((number, field)) = reader.next_field()? {
+        match (number, field) {
+            (1, Field::Bytes(buf)) => {
+                let mut reader = Reader::new(buf);
+                while let Some((number, field)) = reader.next_field()? {
+                    if let (1, Field::Bytes(kv)) = (number, field) {
+                        resource.push(decode_key_value(kv)?);
+                    }
+                }
+            }
+            (2, Field::Bytes(scope_logs)) => scopes.push(scope_logs),
+            _ => {}
+        }
+    }
+    // The resource may follow its scopes on the wire
+    let agent = agent(&resource);
+    for scope_logs in scopes {
+        let mut reader = Reader::new(scope_logs);
+        while let Some((number, field)) = reader.next_field()? {
+            if let (2, Field::Bytes(record)) = (number, field) {
+                lines.push(LogLine {
+                    agent: agent.clone(),
+                    line: decode_log_record(record)?,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+fn decode_log_record(buf: &[u8]) -> Result<String, String> {
+    let mut body = None;
+    let mut severity_number = 0;
+    let mut severity_text = String::new();
+    let mut attributes = Vec::new();
+    let mut trace_id = String::new();
+    let mut span_id = String::new();
+    let mut reader = Reader::new(buf);
+    while let Some((number, field)) = reader.next_field()? {
+        match (number, field) {
+            (2, Field::Varint(n)) => severity_number = n,
+            (3, Field::Bytes(b)) => severity_text = text(b),
+            (5, Field::Bytes(b)) => body = Some(decode_any_value(b)?),
+            (6, Field::Bytes(b)) => attributes.push(decode_key_value(b)?),
+            (9, Field::Bytes(b)) => trace_id = to_hex(b),
+            (10, Field::Bytes(b)) => span_id = to_hex(b),
+            _ => {}
+        }
+    }
+    Ok(render(
+        body,
+        severity_number,
+        &severity_text,
+        attributes,
+        &trace_id,
+        &span_id,
+    ))
+}
+
+fn decode_key_value(buf: &[u8]) -> Result<(String, Value), String> {
+    let mut key = String::new();
+    let mut value = Value::Null;
+    let mut reader = Reader::new(buf);
+    while let Some((number, field)) = reader.next_field()? {
+        match (number, field) {
+            (1, Field::Bytes(b)) => key = text(b),
+            (2, Field::Bytes(b)) => value = decode_any_value(b)?,
+            _ => {}
+        }
+    }
+    Ok((key, value))
+}
+
+fn decode_any_value(buf: &[u8]) -> Result<Value, String> {
+    let mut reader = Reader::new(buf);
+    let mut value = Value::Null;
+    while let Some((number, field)) = reader.next_field()? {
+        value = match (number, field) {
+            (1, Field::Bytes(b)) => Value::String(text(b)),
+            (2, Field::Varint(n)) => Value::Bool(n != 0),
+            (3, Field::Varint(n)) => Value::from(n as i64),
+            (4, Field::Fixed64(bits)) => Value::from(f64::from_bits(bits)),
+            (5 | 6, Field::Bytes(b)) => {
+                let mut values = Vec::new();
+                let mut list = Reader::new(b);
+                while let Some((number, field)) = list.next_field()? {
+                    if let (1, Field::Bytes(item)) = (number, field) {
+                        values.push(item);
+                    }
+                }
+                match number {
+                    5 => Value::Array(
+                        values
+                            .into_iter()
+                            .map(decode_any_value)
+                            .collect::<Result<_, _>>()?,
+                    ),
+                    _ => Value::Object(
+                        values
+                            .into_iter()
+                            .map(decode_key_value)
+                            .collect::<Result<_, _>>()?,
+                    ),
+                }
+            }
+            (7, Field::Bytes(b)) => Value::String(to_hex(b)),
+            _ => continue,
+        };
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Protobuf encoding helpers for the tests
+    fn tag(number: u64, wire: u64, out: &mut Vec<u8>) {
+        varint((number << 3) | wire, out);
+    }
+
+    fn varint(mut n: u64, out: &mut Vec<u8>) {
+        while n >= 0x80 {
+            out.push((n as u8) | 0x80);
+            n >>= 7;
+        }
+        out.push(n as u8);
+    }
+
+    fn bytes(number: u64, payload: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        tag(number, 2, &mut out);
+        varint(payload.len() as u64, &mut out);
+        out.extend_from_slice(payload);
+        out
+    }
+
+    fn string_kv(key: &str, value: &str) -> Vec<u8> {
+        [
+            bytes(1, key.as_bytes()),
+            bytes(2, &bytes(1, value.as_bytes())),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn test_decode_protobuf() {
+        let mut int_value = Vec::new();
+        tag(3, 0, &mut int_value);
+        varint(3, &mut int_value);
+        let mut record = Vec::new();
+        tag(1, 1, &mut record);
+        record.extend_from_slice(&1_792_056_605_000_000_000u64.to_le_bytes());
+        tag(2, 0, &mut record);
+        varint(17, &mut record);
+        record.extend(bytes(5, &bytes(1, b"rm -rf /var/lib/db")));
+        record.extend(bytes(6, &string_kv("tool", "shell")));
+        record.extend(bytes(
+            6,
+            &[bytes(1, b"attempt"), bytes(2, &int_value)].concat(),
+        ));
+        record.extend(bytes(9, &[0xab; 16]));
+
+        let resource = [
+            bytes(1, &string_kv("service.name", "planner")),
+            bytes(1, &string_kv("service.instance.id", "i-1")),
+        ]
+        .concat();
+        // Scope first, resource after: both orders are valid
+        let resource_logs = [bytes(2, &bytes(2, &record)), bytes(1, &resource)].concat();
+        let request = bytes(1, &resource_logs);
+
+        let lines = decode_protobuf(&request).unwrap();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].agent, "planner/i-1");
+        let line: Value = serde_json::from_str(&lines[0].line).unwrap();
+        assert_eq!(line["msg"], "rm -rf /var/lib/db");
+        assert_eq!(line["severity"], "ERROR");
+        assert_eq!(line["tool"], "shell");
+        assert_eq!(line["attempt"], 3);
+        assert_eq!(line["trace_id"], "ab".repeat(16));
+
+        assert!(decode_protobuf(&[0x0a, 0x05, 0x01]).is_err());
+        assert!(decode_protobuf(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_decode_json() {
+        let body = serde_json::json!({
+            "resourceLogs": [{
+                "resource": {"attributes": [
+                    {"key": "service.name", "value": {"stringValue": "coder"}}
+                ]},
+                "scopeLogs": [{"logRecords": [
+                    {
+                        "timeUnixNano": "1792056605000000000",
+                        "severityNumber": 9,
+                        "severityText": "info",
+                        "body": {"stringValue": "git push --force"},
+                        "attributes": [
+                            {"key": "retries", "value": {"intValue": "2"}},
+                            {"key": "dry_run", "value": {"boolValue": false}}
+                        ],
+                        "spanId": "00f067aa0ba902b7"
+                    },
+                    {
+                        "body": {"kvlistValue": {"values": [
+                            {"key": "event", "value": {"stringValue": "tool_call"}},
+                            {"key": "args", "value": {"arrayValue": {"values": [
+                                {"stringValue": "-la"}, {"doubleValue": 1.5}
+                            ]}}}
+                        ]}}
+                    }
+                ]}]
+            }]
+        });
+        let lines = decode_json(body.to_string().as_bytes()).unwrap();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].agent, "coder");
+        let first: Value = serde_json::from_str(&lines[0].line).unwrap();
+        assert_eq!(first["msg"], "git push --force");
+        assert_eq!(first["severity"], "info");
+        assert_eq!(first["retries"], 2);
+        assert_eq!(first["dry_run"], false);
+        assert_eq!(first["span_id"], "00f067aa0ba902b7");
+        let second: Value = serde_json::from_str(&lines[1].line).unwrap();
+        assert_eq!(second["event"], "tool_call");
+        assert_eq!(second["args"], serde_json::json!(["-la", 1.5]));
+        assert!(second.get("severity").is_none());
+
+        assert_eq!(
+            decode_json(b"{}").unwrap(),
+            Vec::<LogLine>::new(),
+            "no resourceLogs"
+        );
+        assert!(decode_json(b"not json").is_err());
+    }
+}
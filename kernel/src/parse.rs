@@ -0,0 +1,432 @@
+//! Structured Log Parsing - JSON and logfmt
+//!
+//! Regexes run against serialized JSON match field *names* (`"error_count"`
+//! trips `error`). Structured lines are parsed first: untargeted rules see
+//! only the decoded values, `field = "..."` rules see a single field, and
+//! the LLM gets a compact `key=value` rendering instead of raw JSON.
+//!
+//! `msg` and `level` are canonical names: they resolve to the first of the
+//! usual message / level keys present (`message`, `log`, `severity`, ...).
+//!
+//! Tool calls from agent frameworks are JSON objects in a shape of their
+//! own, normalized to the fields `tool` and `args.*`:
+//!
+//! ```text
+//! canonical   {"tool":"shell","args":{"cmd":"rm -rf /"}}
+//! LangChain   {"tool":"shell","tool_input":"rm -rf /"}        (an `args` field)
+//! OpenAI      {"type":"function","function":{"name":"shell","arguments":"{\"cmd\":..}"}}
+//! Anthropic   {"type":"tool_use","name":"shell","input":{"cmd":..}}
+//! AutoGen     {"name":"shell","arguments":{"cmd":..}}
+//! ```
+//!
+//! Rules can be scoped to tools (`tools = ["shell"]`) and target an
+//! argument (`field = "args.cmd"`); the LLM gets the canonical form and
+//! the tool-call prompt (see `llm`).
+
+use std::borrow::Cow;
+
+/// Keys recognized as the log message, in priority order
+const MESSAGE_KEYS: &[&str] = &["msg", "message", "log", "text"];
+
+/// Keys recognized as the log level, in priority order
+const LEVEL_KEYS: &[&str] = &["level", "lvl", "severity", "loglevel"];
+
+/// Keys naming the tool of a tool call
+const TOOL_KEYS: &[&str] = &["tool", "tool_name"];
+
+/// Keys holding the arguments of a `tool` call, in priority order
+const ARGS_KEYS: &[&str] = &["args", "arguments", "tool_input", "input", "parameters"];
+
+/// Detected line format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Plain,
+    Json,
+    Logfmt,
+    /// Agent framework tool call (a JSON object)
+    ToolCall,
+}
+
+/// A log line with its fields extracted
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedLine<'a> {
+    pub raw: &'a str,
+    pub format: Format,
+    /// Flattened fields (JSON: sorted by key, nested keys joined with `.`;
+    /// logfmt: line order; tool call: `tool`, then `args` or `args.*`)
+    pub fields: Vec<(String, String)>,
+}
+
+/// Parse a line as JSON or logfmt, falling back to plain text
+pub fn parse(line: &str) -> ParsedLine<'_> {
+    let (format, fields) = if let Some(parsed) = parse_json(line) {
+        parsed
+    } else if let Some(fields) = parse_logfmt(line) {
+        (Format::Logfmt, fields)
+    } else {
+        return ParsedLine::plain(line);
+    };
+    ParsedLine {
+        raw: line,
+        format,
+        fields,
+    }
+}
+
+impl<'a> ParsedLine<'a> {
+    /// Unstructured line
+    pub fn plain(raw: &'a str) -> Self {
+        Self {
+            raw,
+            format: Format::Plain,
+            fields: Vec::new(),
+        }
+    }
+
+    /// Tool name of a tool call
+    pub fn tool(&self) -> Option<&str> {
+        match self.format {
+            Format::ToolCall => self.field("tool"),
+            _ => None,
+        }
+    }
+
+    /// Value of a field; `msg` / `level` resolve through their aliases
+    ///
+    /// A plain line is all message: `msg` is the whole line.
+    pub fn field(&self, name: &str) -> Option<&str> {
+        if self.format == Format::Plain {
+            return MESSAGE_KEYS.contains(&name).then_some(self.raw);
+        }
+        let aliases: &[&str] = if MESSAGE_KEYS.contains(&name) {
+            MESSAGE_KEYS
+        } else if LEVEL_KEYS.contains(&name) {
+            LEVEL_KEYS
+        } else {
+            std::slice::from_ref(&name)
+        };
+        aliases.iter().find_map(|alias| {
+            self.fields
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(alias))
+                .map(|(_, v)| v.as_str())
+        })
+    }
+
+    /// Text untargeted rules match: decoded values only (no keys)
+    pub fn text(&self) -> Cow<'a, str> {
+        match self.format {
+            Format::Plain => Cow::Borrowed(self.raw),
+            _ => Cow::Owned(
+                self.fields
+                    .iter()
+                    .map(|(_, v)| v.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+        }
+    }
+
+    /// Compact rendering for the LLM: level and message first, then the
+    /// rest; a tool call in canonical form (which parses back the same)
+    pub fn render(&self) -> Cow<'a, str> {
+        match self.format {
+            Format::Plain => return Cow::Borrowed(self.raw),
+            Format::ToolCall => return Cow::Owned(self.render_call()),
+            _ => {}
+        }
+        let level = self.field("level");
+        let msg = self.field("msg");
+
+        let mut parts = Vec::new();
+        if let Some(level) = level {
+            parts.push(format!("level={}", level));
+        }
+        if let Some(msg) = msg {
+            parts.push(format!("msg={:?}", msg));
+        }
+        for (k, v) in &self.fields {
+            let canonical = (MESSAGE_KEYS.contains(&k.as_str()) && Some(v.as_str()) == msg)
+                || (LEVEL_KEYS.contains(&k.as_str()) && Some(v.as_str()) == level);
+            if !canonical {
+                parts.push(format!("{}={}", k, quote(v)));
+            }
+        }
+        Cow::Owned(parts.join(" "))
+    }
+}
+
+impl ParsedLine<'_> {
+    /// `{"tool":..,"args":..}`; nested arguments stay flattened
+    fn render_call(&self) -> String {
+        let mut args = serde_json::Map::new();
+        let mut bare = None;
+        for (k, v) in &self.fields {
+            match k.strip_prefix("args.") {
+                Some(key) => {
+                    args.insert(key.to_string(), v.as_str().into());
+                }
+                None if k == "args" => bare = Some(v.as_str()),
+                None => {}
+            }
+        }
+        let args = match bare {
+            Some(value) => serde_json::Value::from(value),
+            None => serde_json::Value::Object(args),
+        };
+        format!(
+            r#"{{"tool":{},"args":{}}}"#,
+            serde_json::Value::from(self.tool().unwrap_or_default()),
+            args
+        )
+    }
+}
+
+/// The line is a tool call (see [`Format::ToolCall`])
+pub fn is_tool_call(line: &str) -> bool {
+    parse_json(line).is_some_and(|(format, _)| format == Format::ToolCall)
+}
+
+fn quote(value: &str) -> Cow<'_, str> {
+    if value.is_empty() || value.contains([' ', '"', '=']) {
+        Cow::Owned(format!("{:?}", value))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+/// JSON object → flattened scalar fields
+fn parse_json(line: &str) -> Option<(Format, Vec<(String, String)>)> {
+    let trimmed = line.trim();
+    if !trimmed.starts_with('{') {
+        return None;
+    }
+    let serde_json::Value::Object(map) = serde_json::from_str(trimmed).ok()? else {
+        return None;
+    };
+    let mut fields = Vec::new();
+    if let Some((tool, args)) = tool_call(&map) {
+        fields.push(("tool".to_string(), tool.to_string()));
+        flatten("args".to_string(), args, &mut fields);
+        return Some((Format::ToolCall, fields));
+    }
+    for (k, v) in map {
+        flatten(k, v, &mut fields);
+    }
+    Some((Format::Json, fields))
+}
+
+/// Tool name and arguments of an object shaped like a tool call
+fn tool_call(
+    map: &serde_json::Map<String, serde_json::Value>,
+) -> Option<(&str, serde_json::Value)> {
+    use serde_json::Value;
+    // OpenAI: arguments are a JSON-encoded string
+    if let Some(Value::Object(function)) = map.get("function") {
+        return named_call(function, "arguments");
+    }
+    if map.get("type").and_then(Value::as_str) == Some("tool_use") {
+        return named_call(map, "input");
+    }
+    if let Some(tool) = TOOL_KEYS.iter().find_map(|k| map.get(*k)?.as_str()) {
+        let args = ARGS_KEYS.iter().find_map(|k| map.get(*k))?;
+        return match arguments(args) {
+            Some(args) => Some((tool, args)),
+            // A single-input tool takes a plain string
+            None => Some((tool, Value::String(args.as_str()?.to_string()))),
+        };
+    }
+    named_call(map, "arguments")
+}
+
+/// `name` and an arguments object under `key`
+fn named_call<'a>(
+    map: &'a serde_json::Map<String, serde_json::Value>,
+    key: &str,
+) -> Option<(&'a str, serde_json::Value)> {
+    let name = map.get("name")?.as_str()?;
+    Some((name, arguments(map.get(key)?)?))
+}
+
+/// An arguments object, possibly JSON-encoded in a string
+fn arguments(value: &serde_json::Value) -> Option<serde_json::Value> {
+    use serde_json::Value;
+    match value {
+        Value::Object(_) => Some(value.clone()),
+        Value::String(s) => match serde_json::from_str(s) {
+            Ok(args @ Value::Object(_)) => Some(args),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn flatten(key: String, value: serde_json::Value, out: &mut Vec<(String, String)>) {
+    use serde_json::Value;
+    match value {
+        Value::Null => {}
+        Value::String(s) => out.push((key, s)),
+        Value::Object(map) => {
+            for (k, v) in map {
+                flatten(format!("{}.{}", key, k), v, out);
+            }
+        }
+        Value::Array(items) => {
+            for (i, v) in items.into_iter().enumerate() {
+                flatten(format!("{}.{}", key, i), v, out);
+            }
+        }
+        other => out.push((key, other.to_string())),
+    }
+}
+
+/// `key=value key2="quoted value"`: every token must be a pair, at least two
+fn parse_logfmt(line: &str) -> Option<Vec<(String, String)>> {
+    if !line.contains('=') {
+        return None;
+    }
+    let mut fields = Vec::new();
+    let mut rest = line.trim_start();
+
+    while !rest.is_empty() {
+        let eq = rest.find('=')?;
+        let key = &rest[..eq];
+        if key.is_empty()
+            || !key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'))
+        {
+            return None;
+        }
+        rest = &rest[eq + 1..];
+
+        let value = if let Some(quoted) = rest.strip_prefix('"') {
+            let mut value = String::new();
+            let mut chars = quoted.char_indices();
+            let end = loop {
+                match chars.next()? {
+                    (i, '"') => break i,
+                    (_, '\\') => value.push(chars.next()?.1),
+                    (_, c) => value.push(c),
+                }
+            };
+            rest = &quoted[end + 1..];
+            if !rest.is_empty() && !rest.starts_with(' ') {
+                return None;
+            }
+            value
+        } else {
+            let end = rest.find(' ').unwrap_or(rest.len());
+            let value = &rest[..end];
+            if value.contains('"') {
+                return None;
+            }
+            rest = &rest[end..];
+            value.to_string()
+        };
+
+        fields.push((key.to_string(), value));
+        rest = rest.trim_start();
+    }
+
+    (fields.len() >= 2).then_some(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json() {
+        let p = parse(
+            r#"{"level":"error","msg":"DROP TABLE users","ctx":{"user":"bob","ids":[1,2]},"x":null}"#,
+        );
+        assert_eq!(p.format, Format::Json);
+        assert_eq!(p.field("msg"), Some("DROP TABLE users"));
+        assert_eq!(p.field("message"), Some("DROP TABLE users"));
+        assert_eq!(p.field("level"), Some("error"));
+        assert_eq!(p.field("ctx.user"), Some("bob"));
+        assert_eq!(p.field("ctx.ids.1"), Some("2"));
+        assert_eq!(p.field("x"), None);
+        assert_eq!(p.text(), "1 2 bob error DROP TABLE users");
+        assert_eq!(
+            p.render(),
+            r#"level=error msg="DROP TABLE users" ctx.ids.0=1 ctx.ids.1=2 ctx.user=bob"#
+        );
+    }
+
+    #[test]
+    fn test_json_keys_not_in_text() {
+        let p = parse(r#"{"error_count":0,"message":"all good"}"#);
+        assert!(!p.text().contains("error"));
+    }
+
+    #[test]
+    fn test_logfmt() {
+        let p = parse(r#"ts=2026-10-15T03:00:00Z lvl=warn message="order \"7\" placed" qty=5"#);
+        assert_eq!(p.format, Format::Logfmt);
+        assert_eq!(p.field("msg"), Some(r#"order "7" placed"#));
+        assert_eq!(p.field("level"), Some("warn"));
+        assert_eq!(p.field("qty"), Some("5"));
+    }
+
+    #[test]
+    fn test_plain_fallback() {
+        for line in [
+            "Order #1234 placed within 1ms",
+            "dd if=/dev/zero of=/dev/sda", // a pair among plain words
+            "a=1",                         // single pair
+            r#"msg="unterminated x=1"#,
+            "{not json",
+            "[1, 2]",
+        ] {
+            let p = parse(line);
+            assert_eq!(p.format, Format::Plain, "{}", line);
+            assert_eq!(p.text(), line);
+            assert_eq!(p.field("msg"), Some(line));
+            assert_eq!(p.field("level"), None);
+        }
+    }
+
+    #[test]
+    fn test_tool_calls() {
+        let canonical = r#"{"tool":"shell","args":{"cmd":"rm -rf /","env":{"HOME":"/root"}}}"#;
+        for line in [
+            canonical,
+            r#"{"tool":"shell","tool_input":{"cmd":"rm -rf /","env":{"HOME":"/root"}},"log":"x"}"#,
+            r#"{"type":"function","id":"call_1","function":{"name":"shell","arguments":"{\"cmd\":\"rm -rf /\",\"env\":{\"HOME\":\"/root\"}}"}}"#,
+            r#"{"type":"tool_use","id":"toolu_1","name":"shell","input":{"cmd":"rm -rf /","env":{"HOME":"/root"}}}"#,
+            r#"{"name":"shell","arguments":{"cmd":"rm -rf /","env":{"HOME":"/root"}}}"#,
+        ] {
+            let p = parse(line);
+            assert_eq!(p.format, Format::ToolCall, "{}", line);
+            assert_eq!(p.tool(), Some("shell"));
+            assert_eq!(p.field("args.cmd"), Some("rm -rf /"));
+            assert_eq!(p.field("args.env.HOME"), Some("/root"));
+            assert_eq!(p.field("msg"), None);
+            assert_eq!(p.text(), "shell rm -rf / /root");
+            // The canonical rendering parses back to the same fields
+            let rendered = p.render();
+            assert_eq!(
+                rendered,
+                r#"{"tool":"shell","args":{"cmd":"rm -rf /","env.HOME":"/root"}}"#
+            );
+            assert_eq!(parse(&rendered).fields, p.fields);
+            assert!(is_tool_call(&rendered));
+        }
+
+        let p = parse(r#"{"tool":"shell","tool_input":"ls -la"}"#);
+        assert_eq!(p.field("args"), Some("ls -la"));
+        assert_eq!(p.render(), r#"{"tool":"shell","args":"ls -la"}"#);
+
+        // Ordinary JSON logs
+        for line in [
+            r#"{"tool":"terraform","msg":"plan done"}"#,
+            r#"{"name":"bob","input":{"a":1}}"#,
+            r#"{"name":"bob","arguments":"not json"}"#,
+        ] {
+            assert_eq!(parse(line).format, Format::Json, "{}", line);
+            assert!(!is_tool_call(line));
+        }
+    }
+}
@@ -0,0 +1,357 @@
+//! Decision Policy - Operational Rules Between Verdict and Action
+//!
+//! Every escalation verdict (kill rule, LLM or degraded-mode policy) passes
+//! through `[[policy.rule]]` entries in order before anything is executed.
+//! The first rule whose `when` expression is true replaces the action:
+//! `kill`, `sustain`, `pause` (suspend the target instead of killing it),
+//! `freeze`, `clamp_cpu` and `clamp_memory` on the target's cgroup (see
+//! `cgroup`), or `restrict` (see `restrict`).
+//! The audit record keeps the original `verdict` and the `policy` applied.
+//!
+//! `when` is a CEL subset (see `expr`) over:
+//! - `decision`: action, confidence, rule, reason, model, filtered
+//! - `agent`: id, peer, lines, escalations, kills
+//! - `history`: actions (recent escalation verdicts, oldest first)
+//! - `time`: hour, minute, weekday (0 = Sunday), unix_ms - at `utc_offset`
+//!
+//! ```toml
+//! [policy]
+//! utc_offset = "-05:00"
+//!
+//! [[policy.rule]]
+//! name = "protect-market-maker"
+//! when = '''decision.action == "KILL" && agent.peer.startsWith("10.0.4.")
+//!          && time.weekday in [1, 2, 3, 4, 5] && time.hour >= 9 && time.hour < 16'''
+//! action = "pause"
+//!
+//! # Only kill on a confident LLM verdict (see `logprobs` in `llm`)
+//! [[policy.rule]]
+//! name = "unsure-kill"
+//! when = 'decision.action == "KILL" && !decision.filtered && decision.confidence < 85'
+//! action = "pause"
+//! ```
+
+use crate::agents::AgentStatus;
+use crate::expr::{Expr, Value};
+use crate::llm::Action;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+/// `[policy]` table
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PolicyConfig {
+    /// Offset of `time.*` from UTC, as `+HH:MM` / `-HH:MM` (default UTC)
+    #[serde(default)]
+    pub utc_offset: Option<String>,
+    #[serde(default)]
+    pub rule: Vec<PolicyRuleDef>,
+}
+
+/// One `[[policy.rule]]` entry
+#[derive(Debug, Clone, Deserialize)]
+pub struct PolicyRuleDef {
+    pub name: String,
+    /// Expression that must evaluate to true
+    pub when: String,
+    pub action: PolicyAction,
+}
+
+/// What a matching rule turns the verdict into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyAction {
+    Kill,
+    Sustain,
+    /// Suspend the target (SIGSTOP) instead of killing it
+    Pause,
+    /// Freeze the target's cgroup
+    Freeze,
+    /// Clamp the target's cgroup to 1% CPU
+    ClampCpu,
+    /// Clamp the target's cgroup memory at current usage
+    ClampMemory,
+    /// Tighten the target's AppArmor profile or sandbox (`[restrict]`)
+    Restrict,
+}
+
+impl PolicyAction {
+    /// Audit action
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Kill => "KILL",
+            Self::Sustain => "SUSTAIN",
+            Self::Pause => "PAUSE",
+            Self::Freeze => "FREEZE",
+            Self::ClampCpu => "CLAMP_CPU",
+            Self::ClampMemory => "CLAMP_MEMORY",
+            Self::Restrict => "RESTRICT",
+        }
+    }
+}
+
+impl From<PolicyAction> for Action {
+    fn from(action: PolicyAction) -> Self {
+        match action {
+            PolicyAction::Kill => Self::Kill,
+            PolicyAction::Sustain => Self::Sustain,
+            PolicyAction::Pause => Self::Pause,
+            PolicyAction::Freeze => Self::Freeze,
+            PolicyAction::ClampCpu => Self::ClampCpu,
+            PolicyAction::ClampMemory => Self::ClampMemory,
+            PolicyAction::Restrict => Self::Restrict,
+        }
+    }
+}
+
+impl PolicyConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        Policy::new(self).map(|_| ())
+    }
+}
+
+struct PolicyRule {
+    name: String,
+    when: Expr,
+    action: PolicyAction,
+}
+
+/// A verdict before policy, as seen by `when` (`decision.*`)
+#[derive(Debug, Default)]
+pub struct Verdict<'a> {
+    pub action: &'a str,
+    pub confidence: u32,
+    pub rule: Option<&'a str>,
+    pub reason: Option<&'a str>,
+    pub model: Option<&'a str>,
+    pub filtered: bool,
+}
+
+/// A rule that changed the verdict
+#[derive(Debug, Clone, PartialEq)]
+pub struct Override {
+    pub rule: String,
+    pub action: PolicyAction,
+}
+
+/// Compiled policy rules
+#[derive(Default)]
+pub struct Policy {
+    rules: Vec<PolicyRule>,
+    utc_offset_ms: i64,
+}
+
+impl Policy {
+    pub fn new(config: &PolicyConfig) -> Result<Self, String> {
+        let utc_offset_ms = match config.utc_offset {
+            Some(ref offset) => parse_offset(offset)
+                .ok_or_else(|| format!("policy: invalid utc_offset '{}'", offset))?,
+            None => 0,
+        };
+        let mut rules = Vec::with_capacity(config.rule.len());
+        for (i, def) in config.rule.iter().enumerate() {
+            let when = Expr::parse(&def.when)
+                .map_err(|e| format!("policy.rule[{}] '{}': {}", i, def.name, e))?;
+            rules.push(PolicyRule {
+                name: def.name.clone(),
+                when,
+                action: def.action,
+            });
+        }
+        Ok(Self {
+            rules,
+            utc_offset_ms,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// First matching rule, if its action differs from the verdict
+    ///
+    /// `history` holds the agent's recent escalation verdicts, oldest
+    /// first. A rule whose expression fails to evaluate is skipped with a
+    /// warning.
+    pub fn decide(
+        &self,
+        verdict: &Verdict,
+        agent: &AgentStatus,
+        history: &[&str],
+    ) -> Option<Override> {
+        self.decide_at(verdict, agent, history, now_ms())
+    }
+
+    /// [`Policy::decide`] at `unix_ms` (replays of past decisions)
+    pub fn decide_at(
+        &self,
+        verdict: &Verdict,
+        agent: &AgentStatus,
+        history: &[&str],
+        unix_ms: u64,
+    ) -> Option<Override> {
+        if self.rules.is_empty() {
+            return None;
+        }
+        let env = self.env(verdict, agent, history, unix_ms);
+        for rule in &self.rules {
+            match rule.when.test(&env) {
+                Ok(true) if rule.action.as_str() == verdict.action => return None,
+                Ok(true) => {
+                    return Some(Override {
+                        rule: rule.name.clone(),
+                        action: rule.action,
+                    })
+                }
+                Ok(false) => {}
+                Err(e) => warn!("📜 Policy rule '{}' skipped: {}", rule.name, e),
+            }
+        }
+        None
+    }
+
+    fn env(
+        &self,
+        verdict: &Verdict,
+        agent: &AgentStatus,
+        history: &[&str],
+        unix_ms: u64,
+    ) -> BTreeMap<String, Value> {
+        let local_ms = unix_ms as i64 + self.utc_offset_ms;
+        let secs = local_ms.div_euclid(1000);
+        let days = secs.div_euclid(86_400);
+        let value = serde_json::json!({
+            "decision": {
+                "action": verdict.action,
+                "confidence": verdict.confidence,
+                "rule": verdict.rule,
+                "reason": verdict.reason,
+                "model": verdict.model,
+                "filtered": verdict.filtered,
+            },
+            "agent": {
+                "id": agent.id,
+                "peer": agent.peer,
+                "lines": agent.lines,
+                "escalations": agent.escalations,
+                "kills": agent.kills,
+            },
+            "history": { "actions": history },
+            "time": {
+                "hour": secs.rem_euclid(86_400) / 3600,
+                "minute": secs.rem_euclid(3600) / 60,
+                // 1970-01-01 was a Thursday
+                "weekday": (days + 4).rem_euclid(7),
+                "unix_ms": unix_ms,
+            },
+        });
+        match Value::from(value) {
+            Value::Map(map) => map,
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// `+HH:MM` / `-HH:MM` / `Z` to milliseconds
+pub fn parse_offset(offset: &str) -> Option<i64> {
+    if offset == "Z" {
+        return Some(0);
+    }
+    let (sign, rest) = match offset.split_at_checked(1)? {
+        ("+", rest) => (1, rest),
+        ("-", rest) => (-1, rest),
+        _ => return None,
+    };
+    let (hours, minutes) = rest.split_once(':')?;
+    let (hours, minutes): (i64, i64) = (hours.parse().ok()?, minutes.parse().ok()?);
+    if hours > 14 || minutes > 59 {
+        return None;
+    }
+    Some(sign * (hours * 60 + minutes) * 60_000)
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+utc_offset = "-05:00"
+
+[[rule]]
+name = "protect-market-maker"
+when = '''decision.action == "KILL" && agent.peer.startsWith("10.0.4.")
+         && time.weekday in [1, 2, 3, 4, 5] && time.hour >= 9 && time.hour < 16'''
+action = "pause"
+
+[[rule]]
+name = "repeat-offender"
+when = 'decision.action == "FAIL" && "KILL" in history.actions'
+action = "kill"
+"#;
+
+    #[test]
+    fn test_decide() {
+        let config: PolicyConfig = toml::from_str(CONFIG).unwrap();
+        let policy = Policy::new(&config).unwrap();
+        let agent = AgentStatus {
+            id: 1,
+            peer: "10.0.4.7:5123".to_string(),
+            ..Default::default()
+        };
+        let history = ["SUSTAIN", "KILL"];
+        let decide = |action, unix_ms| {
+            let verdict = Verdict {
+                action,
+                confidence: 90,
+                rule: Some("essential#0"),
+                ..Default::default()
+            };
+            policy.decide_at(&verdict, &agent, &history, unix_ms)
+        };
+
+        // Wednesday 2026-10-14 15:00 UTC = 10:00 at -05:00
+        let trading = 1_791_990_000_000;
+        let paused = decide("KILL", trading).unwrap();
+        assert_eq!(paused.rule, "protect-market-maker");
+        assert_eq!(paused.action, PolicyAction::Pause);
+        // 02:00 UTC = 21:00 the previous evening
+        assert_eq!(decide("KILL", trading - 13 * 3_600_000), None);
+        // A rule agreeing with the verdict changes nothing
+        assert_eq!(decide("SUSTAIN", trading), None);
+        assert_eq!(decide("FAIL", trading).unwrap().action, PolicyAction::Kill);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(toml::from_str::<PolicyConfig>(CONFIG)
+            .unwrap()
+            .validate()
+            .is_ok());
+        let bad = |toml: &str| {
+            toml::from_str::<PolicyConfig>(toml)
+                .unwrap()
+                .validate()
+                .is_err()
+        };
+        assert!(bad("utc_offset = '5'"));
+        assert!(bad(
+            "[[rule]]\nname = 'x'\nwhen = 'decision.action =='\naction = 'kill'"
+        ));
+        assert!(toml::from_str::<PolicyConfig>(
+            "[[rule]]\nname = 'x'\nwhen = 'true'\naction = 'stop'"
+        )
+        .is_err());
+        let clamp: PolicyConfig =
+            toml::from_str("[[rule]]\nname = 'x'\nwhen = 'true'\naction = 'clamp_cpu'").unwrap();
+        assert_eq!(clamp.rule[0].action, PolicyAction::ClampCpu);
+        assert_eq!(parse_offset("+05:30"), Some(19_800_000));
+    }
+}
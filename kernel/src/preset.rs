@@ -0,0 +1,156 @@
+//! Domain Presets - Pattern Sets as TOML Files
+//!
+//! A preset is the Domain tier of the filter: the patterns `domain =
+//! "<name>"` switches on (default: `trading`), named `<name>#<index>`.
+//! The built-in ones (`trading`, `devops`, `generic`) ship as the files in
+//! `kernel/presets/` and are compiled in. `--preset-dir` adds every
+//! `*.toml` file of a directory as a preset named after the file (or its
+//! `name`), so a "healthcare" or "robotics" preset needs no fork; a file
+//! named like a built-in replaces it.
+//!
+//! ```toml
+//! # presets/healthcare.toml
+//! description = "Clinical data access"
+//! patterns = [
+//!   '(?i)patient|diagnosis|prescription',
+//!   '(?i)\bmrn\b|medical record',
+//! ]
+//! ```
+
+use crate::error::KernelError;
+use serde::Deserialize;
+use std::path::Path;
+use std::sync::LazyLock;
+
+/// Built-in presets: name and TOML source
+const BUILTIN_SOURCES: &[(&str, &str)] = &[
+    ("trading", include_str!("../presets/trading.toml")),
+    ("devops", include_str!("../presets/devops.toml")),
+    ("generic", include_str!("../presets/generic.toml")),
+];
+
+/// Preset used when `domain` is not set
+pub const DEFAULT: &str = "trading";
+
+static BUILTIN: LazyLock<Vec<Preset>> = LazyLock::new(|| {
+    BUILTIN_SOURCES
+        .iter()
+        .map(|(name, source)| parse(name, source).expect("built-in preset"))
+        .collect()
+});
+
+/// One domain preset
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Preset {
+    /// Default: the file name without `.toml`
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub patterns: Vec<String>,
+}
+
+/// The compiled-in presets
+pub fn builtin() -> &'static [Preset] {
+    &BUILTIN
+}
+
+/// Parse and validate one preset
+fn parse(name: &str, source: &str) -> Result<Preset, String> {
+    let mut preset: Preset = toml::from_str(source).map_err(|e| e.to_string())?;
+    if preset.name.is_empty() {
+        preset.name = name.to_string();
+    }
+    if preset.patterns.is_empty() {
+        return Err("no patterns".to_string());
+    }
+    for pattern in &preset.patterns {
+        regex::Regex::new(pattern).map_err(|e| e.to_string())?;
+    }
+    Ok(preset)
+}
+
+/// Every `*.toml` preset in `dir`, by file name
+pub fn load_dir(dir: &Path) -> Result<Vec<Preset>, KernelError> {
+    let mut files: Vec<_> = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<_, _>>()?;
+    files.retain(|p| p.extension().is_some_and(|e| e == "toml"));
+    files.sort();
+
+    let mut presets: Vec<Preset> = Vec::new();
+    for path in files {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let preset = parse(&stem, &std::fs::read_to_string(&path)?)
+            .map_err(|e| format!("preset {}: {}", path.display(), e))?;
+        if presets.iter().any(|p| p.name == preset.name) {
+            return Err(format!(
+                "preset {}: duplicate name '{}'",
+                path.display(),
+                preset.name
+            )
+            .into());
+        }
+        presets.push(preset);
+    }
+    Ok(presets)
+}
+
+/// The preset `name` among `loaded`, then the built-ins
+pub fn find<'a>(loaded: &'a [Preset], name: &str) -> Option<&'a Preset> {
+    loaded
+        .iter()
+        .chain(builtin())
+        .find(|preset| preset.name == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin() {
+        let names: Vec<&str> = builtin().iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["trading", "devops", "generic"]);
+        assert_eq!(find(&[], "trading").unwrap().patterns.len(), 8);
+        assert_eq!(
+            find(&[], "generic").unwrap().patterns[0],
+            "(?i)error|exception|failed"
+        );
+        assert!(find(&[], "healthcare").is_none());
+    }
+
+    #[test]
+    fn test_load_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("healthcare.toml"),
+            "patterns = ['(?i)patient', '(?i)diagnosis']",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("trading.toml"),
+            "name = 'trading'\npatterns = ['(?i)swap']",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "not a preset").unwrap();
+
+        let loaded = load_dir(dir.path()).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(find(&loaded, "healthcare").unwrap().patterns.len(), 2);
+        // A file named like a built-in replaces it
+        assert_eq!(find(&loaded, "trading").unwrap().patterns, ["(?i)swap"]);
+        assert_eq!(find(&loaded, "devops"), find(&[], "devops"));
+
+        std::fs::write(dir.path().join("bad.toml"), "patterns = ['(unclosed']").unwrap();
+        let err = load_dir(dir.path()).unwrap_err().to_string();
+        assert!(err.contains("bad.toml"), "{}", err);
+
+        std::fs::write(
+            dir.path().join("bad.toml"),
+            "name = 'healthcare'\npatterns = ['x']",
+        )
+        .unwrap();
+        assert!(load_dir(dir.path()).is_err());
+    }
+}
@@ -0,0 +1,74 @@
+//! Priority Lanes - Critical Lines Ahead of the Backlog
+//!
+//! During a flood, the one line containing `DROP DATABASE` must not wait
+//! behind 10,000 warnings. Lines whose decisive rule has
+//! `priority = "high"` (`[[rule]]` tables and custom pattern tables) are
+//! judged in the high lane:
+//!
+//! - `[flow]` never delays or drops them, even over the agent's limit
+//! - they skip the `[batch]` queue and go straight to the model
+//! - with `timeout_ms`, an analysis still pending after that long is
+//!   abandoned and the line gets `on_timeout` at once (default: the
+//!   degraded-mode policy), audited with that reason
+//!
+//! A tighter `[slo] deadline_ms` still wins. High-lane analyses and
+//! timeouts are counted in `/stats` and `/metrics` (`priority_lines`,
+//! `priority_timeouts`).
+//!
+//! ```toml
+//! [[rule]]
+//! id = "drop-database"
+//! pattern = '(?i)drop\s+database'
+//! priority = "high"
+//!
+//! [priority]
+//! timeout_ms = 150
+//! on_timeout = "kill"
+//! ```
+
+use crate::health::DegradedPolicy;
+use serde::Deserialize;
+use std::time::Duration;
+
+/// `[priority]` table
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PriorityConfig {
+    /// LLM timeout for high-lane lines (milliseconds); 0 = none
+    #[serde(default)]
+    pub timeout_ms: u64,
+    /// Verdict for a high-lane timeout (default: the degraded-mode policy)
+    #[serde(default)]
+    pub on_timeout: Option<DegradedPolicy>,
+}
+
+impl PriorityConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.timeout_ms == 0 && self.on_timeout.is_some() {
+            return Err("priority: on_timeout needs a timeout_ms".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        (self.timeout_ms > 0).then(|| Duration::from_millis(self.timeout_ms))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_priority_config() {
+        assert_eq!(PriorityConfig::default().timeout(), None);
+
+        let config: PriorityConfig =
+            toml::from_str("timeout_ms = 150\non_timeout = 'kill'").unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.timeout(), Some(Duration::from_millis(150)));
+        assert_eq!(config.on_timeout, Some(DegradedPolicy::Kill));
+
+        let config: PriorityConfig = toml::from_str("on_timeout = 'kill'").unwrap();
+        assert!(config.validate().is_err());
+    }
+}
@@ -0,0 +1,239 @@
+//! Probes - Kubernetes Liveness and Readiness
+//!
+//! As a sidecar, a wedged kill-switch must be restarted, not left running
+//! next to an unprotected agent. Two endpoints answer with probe semantics
+//! (200 or 503, the individual checks as JSON):
+//!
+//! - `GET /healthz` (liveness) fails when an audit write has been stuck
+//!   for `[watchdog] stall_ms` or more than `max_panics` kernel tasks
+//!   panicked. A wedged event loop cannot answer at all, which the kubelet
+//!   counts as a failure too.
+//! - `GET /readyz` (readiness) also needs every pipeline's endpoint up
+//!   (not draining, not an HA standby), its LLM passing the canary probe
+//!   and its startup checklist passed (see `selftest`).
+//!
+//! `--probe-port` serves both on all interfaces, since the kubelet probes
+//! the pod IP; every admin API also serves them for its own pipeline.
+//!
+//! ```yaml
+//! livenessProbe:
+//!   httpGet: { path: /healthz, port: 9110 }
+//!   periodSeconds: 5
+//!   failureThreshold: 3
+//! readinessProbe:
+//!   httpGet: { path: /readyz, port: 9110 }
+//! ```
+
+use crate::Kernel;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::Serialize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::net::TcpListener;
+use tracing::info;
+
+/// Stall threshold when the self-watchdog is disabled
+const DEFAULT_STALL_MS: u64 = 10_000;
+
+/// One probe check
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Check {
+    pub name: &'static str,
+    /// Channel the check belongs to (unset for the flag-configured pipeline)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel: Option<String>,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub ok: bool,
+    pub checks: Vec<Check>,
+}
+
+impl IntoResponse for Report {
+    fn into_response(self) -> axum::response::Response {
+        let status = match self.ok {
+            true => StatusCode::OK,
+            false => StatusCode::SERVICE_UNAVAILABLE,
+        };
+        (status, Json(self)).into_response()
+    }
+}
+
+/// The pipelines a probe endpoint reports on
+#[derive(Clone)]
+pub struct Probes {
+    pipelines: Vec<(Option<String>, Arc<Kernel>)>,
+    stall_ms: u64,
+    max_panics: u64,
+}
+
+impl Probes {
+    pub fn new(stall_ms: u64, max_panics: u64) -> Self {
+        Self {
+            pipelines: Vec::new(),
+            stall_ms: match stall_ms {
+                0 => DEFAULT_STALL_MS,
+                ms => ms,
+            },
+            max_panics,
+        }
+    }
+
+    pub fn with_pipeline(mut self, channel: Option<&str>, kernel: Arc<Kernel>) -> Self {
+        self.pipelines.push((channel.map(str::to_string), kernel));
+        self
+    }
+
+    pub fn liveness(&self) -> Report {
+        let now = now_ms();
+        let mut checks = Vec::with_capacity(self.pipelines.len() + 1);
+        for (channel, kernel) in &self.pipelines {
+            let busy_since = kernel.audit_trail.busy_since().load(Ordering::Relaxed);
+            checks.push(check(
+                "audit",
+                channel,
+                audit_writer(busy_since, now, self.stall_ms),
+            ));
+        }
+        checks.push(check(
+            "panics",
+            &None,
+            panics(crate::watchdog::panics(), self.max_panics),
+        ));
+        report(checks)
+    }
+
+    pub fn readiness(&self) -> Report {
+        let mut checks = self.liveness().checks;
+        for (channel, kernel) in &self.pipelines {
+            checks.push(check("endpoint", channel, endpoint(kernel)));
+            let llm = match kernel.health.healthy() {
+                true => Ok(()),
+                false => Err("canary probe failing".to_string()),
+            };
+            checks.push(check("llm", channel, llm));
+            checks.push(check("selftest", channel, kernel.health.checklist()));
+        }
+        report(checks)
+    }
+}
+
+fn check(name: &'static str, channel: &Option<String>, result: Result<(), String>) -> Check {
+    Check {
+        name,
+        channel: channel.clone(),
+        ok: result.is_ok(),
+        detail: result.err(),
+    }
+}
+
+fn report(checks: Vec<Check>) -> Report {
+    Report {
+        ok: checks.iter().all(|c| c.ok),
+        checks,
+    }
+}
+
+/// An audit write in progress since `busy_since` (0 = idle)
+fn audit_writer(busy_since: u64, now: u64, stall_ms: u64) -> Result<(), String> {
+    match now.saturating_sub(busy_since) {
+        stalled if busy_since != 0 && stalled > stall_ms => {
+            Err(format!("write stalled for {}ms", stalled))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn panics(count: u64, max_panics: u64) -> Result<(), String> {
+    match count > max_panics {
+        true => Err(format!("{} panics in kernel tasks", count)),
+        false => Ok(()),
+    }
+}
+
+fn endpoint(kernel: &Kernel) -> Result<(), String> {
+    if kernel.shutdown.is_cancelled() {
+        return Err("draining".to_string());
+    }
+    if let Some(ref ha) = kernel.ha {
+        if !ha.is_leader() {
+            return Err("standby".to_string());
+        }
+    }
+    match kernel.serving.load(Ordering::Relaxed) {
+        true => Ok(()),
+        false => Err("not accepting agents".to_string()),
+    }
+}
+
+pub fn routes<S>(probes: Probes) -> Router<S> {
+    Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .with_state(probes)
+}
+
+async fn healthz(State(probes): State<Probes>) -> Report {
+    probes.liveness()
+}
+
+async fn readyz(State(probes): State<Probes>) -> Report {
+    probes.readiness()
+}
+
+/// Serve `/healthz` and `/readyz` on every interface until shutdown
+pub async fn serve(port: u16, probes: Probes) -> std::io::Result<()> {
+    let shutdown = match probes.pipelines.first() {
+        Some((_, kernel)) => kernel.shutdown.clone(),
+        None => return Ok(()),
+    };
+    let listener = TcpListener::bind(("0.0.0.0", port)).await?;
+    info!("🩺 Probes on http://0.0.0.0:{}/healthz and /readyz", port);
+    axum::serve(listener, routes(probes))
+        .with_graceful_shutdown(shutdown.cancelled_owned())
+        .await
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checks() {
+        assert!(audit_writer(0, 50_000, 10_000).is_ok());
+        assert!(audit_writer(40_000, 50_000, 10_000).is_ok());
+        assert_eq!(
+            audit_writer(39_999, 50_000, 10_000).unwrap_err(),
+            "write stalled for 10001ms"
+        );
+        assert!(panics(3, 3).is_ok());
+        assert!(panics(4, 3).is_err());
+
+        let failing = check("llm", &Some("trader".into()), Err("down".into()));
+        let report = report(vec![check("audit", &None, Ok(())), failing]);
+        assert!(!report.ok);
+        assert_eq!(
+            serde_json::to_value(&report.checks).unwrap(),
+            serde_json::json!([
+                {"name": "audit", "ok": true},
+                {"name": "llm", "channel": "trader", "ok": false, "detail": "down"},
+            ])
+        );
+    }
+}
@@ -0,0 +1,370 @@
+//! Persisted Queue - In-Flight Lines Survive a Restart
+//!
+//! A line escalated to the LLM is accepted but not judged until the model
+//! answers. A kernel that restarts (crash, OOM kill, drain timeout) in
+//! between would silently forget it. With `[queue] dir`, every line sent
+//! to the LLM is first appended (already redacted) to an append-only
+//! segment file, and marked done once its decision is audited. Segments
+//! whose lines are all done are deleted.
+//!
+//! On startup, lines left pending by the previous run are replayed through
+//! the usual analysis (decision policy and kill path included) and audited
+//! with `recovered: true`. Channels sharing the main config get the
+//! channel name appended to the directory name.
+//!
+//! ```toml
+//! [queue]
+//! dir = "tripwired-queue"
+//! segment_lines = 1000
+//! max_lines = 10000
+//! ```
+
+use crate::filter::Priority;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::warn;
+
+/// `[queue]` table
+#[derive(Debug, Clone, Deserialize)]
+pub struct QueueConfig {
+    /// Directory of segment files (persisted queue disabled if unset)
+    #[serde(default)]
+    pub dir: Option<PathBuf>,
+    /// Lines per segment file
+    #[serde(default = "default_segment_lines")]
+    pub segment_lines: usize,
+    /// Pending lines kept at most; later ones are not persisted
+    #[serde(default = "default_max_lines")]
+    pub max_lines: usize,
+}
+
+fn default_segment_lines() -> usize {
+    1000
+}
+
+fn default_max_lines() -> usize {
+    10_000
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            segment_lines: default_segment_lines(),
+            max_lines: default_max_lines(),
+        }
+    }
+}
+
+impl QueueConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.dir.is_some() && (self.segment_lines == 0 || self.max_lines == 0) {
+            return Err("queue: segment_lines and max_lines must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// A line sent to the LLM, as needed to judge it again
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueuedLine {
+    /// Redacted input, as audited
+    pub input_log: String,
+    /// SHA-256 of the input as received
+    pub input_hash: String,
+    /// Redacted prompt rendering of the input
+    pub prompt_log: String,
+    /// Redacted agent history sent with the prompt
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub context: String,
+    pub rule: String,
+    #[serde(default, skip_serializing_if = "is_normal")]
+    pub priority: Priority,
+}
+
+fn is_normal(priority: &Priority) -> bool {
+    *priority == Priority::Normal
+}
+
+/// Handle on a persisted line, to mark it done
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ticket {
+    seq: u64,
+    segment: u64,
+}
+
+/// One segment file line
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Entry {
+    Queued { seq: u64, line: QueuedLine },
+    Done { done: u64 },
+}
+
+struct Segment {
+    file: File,
+    /// Lines not yet done
+    pending: usize,
+}
+
+struct State {
+    next_seq: u64,
+    /// Segments still holding pending lines, plus the current one
+    segments: BTreeMap<u64, Segment>,
+    current: u64,
+    /// Lines written to the current segment
+    written: usize,
+    pending: usize,
+    /// The full-queue warning was logged since the queue last had room
+    warned: bool,
+    /// Left pending by the previous run, oldest first
+    recovered: Vec<(Ticket, QueuedLine)>,
+}
+
+/// Append-only segment files of in-flight lines
+pub struct Journal {
+    dir: PathBuf,
+    segment_lines: usize,
+    max_lines: usize,
+    state: Mutex<State>,
+}
+
+impl Journal {
+    /// Open (or create) the queue directory; lines left pending by a
+    /// previous run are kept for [`Journal::take_recovered`]
+    pub fn open(config: &QueueConfig, dir: &Path) -> std::io::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let mut numbers: Vec<u64> = std::fs::read_dir(dir)?
+            .filter_map(|e| segment_number(&e.ok()?.file_name().to_string_lossy()))
+            .collect();
+        numbers.sort_unstable();
+
+        let mut segments = BTreeMap::new();
+        let mut recovered = Vec::new();
+        let mut next_seq = 1;
+        for &number in &numbers {
+            let path = segment_path(dir, number);
+            let mut queued = BTreeMap::new();
+            for line in BufReader::new(File::open(&path)?).lines() {
+                match serde_json::from_str(&line?) {
+                    Ok(Entry::Queued { seq, line }) => {
+                        next_seq = next_seq.max(seq + 1);
+                        queued.insert(seq, line);
+                    }
+                    Ok(Entry::Done { done }) => {
+                        queued.remove(&done);
+                    }
+                    // A torn last line from a crash mid-write
+                    Err(e) => warn!(
+                        "📥 Skipping corrupt queue line in {}: {}",
+                        path.display(),
+                        e
+                    ),
+                }
+            }
+            if queued.is_empty() {
+                std::fs::remove_file(&path)?;
+                continue;
+            }
+            let file = OpenOptions::new().append(true).open(&path)?;
+            segments.insert(
+                number,
+                Segment {
+                    file,
+                    pending: queued.len(),
+                },
+            );
+            recovered.extend(queued.into_iter().map(|(seq, line)| {
+                (
+                    Ticket {
+                        seq,
+                        segment: number,
+                    },
+                    line,
+                )
+            }));
+        }
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            segment_lines: config.segment_lines,
+            max_lines: config.max_lines,
+            state: Mutex::new(State {
+                next_seq,
+                segments,
+                // The next push opens a fresh segment
+                current: numbers.last().copied().unwrap_or(0),
+                written: config.segment_lines,
+                pending: recovered.len(),
+                warned: false,
+                recovered,
+            }),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Lines not yet judged
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().pending
+    }
+
+    /// Lines left pending by the previous run, oldest first (once)
+    pub fn take_recovered(&self) -> Vec<(Ticket, QueuedLine)> {
+        std::mem::take(&mut self.state.lock().unwrap().recovered)
+    }
+
+    /// Persist a line about to be judged; `None` when the queue is full
+    pub fn push(&self, line: &QueuedLine) -> std::io::Result<Option<Ticket>> {
+        let mut state = self.state.lock().unwrap();
+        if state.pending >= self.max_lines {
+            if !state.warned {
+                state.warned = true;
+                warn!(
+                    "📥 Persisted queue {} full ({} lines) - later lines are not persisted",
+                    self.dir.display(),
+                    self.max_lines
+                );
+            }
+            return Ok(None);
+        }
+        if state.written >= self.segment_lines {
+            let number = state.current + 1;
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(segment_path(&self.dir, number))?;
+            let previous = state.current;
+            state.segments.insert(number, Segment { file, pending: 0 });
+            state.current = number;
+            state.written = 0;
+            self.retire(&mut state, previous)?;
+        }
+
+        let seq = state.next_seq;
+        let mut text = serde_json::to_string(&Entry::Queued {
+            seq,
+            line: line.clone(),
+        })?;
+        text.push('\n');
+        let current = state.current;
+        let segment = state.segments.get_mut(&current).expect("current segment");
+        segment.file.write_all(text.as_bytes())?;
+        segment.pending += 1;
+        state.next_seq += 1;
+        state.written += 1;
+        state.pending += 1;
+        Ok(Some(Ticket {
+            seq,
+            segment: current,
+        }))
+    }
+
+    /// Mark a line judged (its decision is audited)
+    pub fn done(&self, ticket: Ticket) -> std::io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let Some(segment) = state.segments.get_mut(&ticket.segment) else {
+            return Ok(());
+        };
+        let mut text = serde_json::to_string(&Entry::Done { done: ticket.seq })?;
+        text.push('\n');
+        segment.file.write_all(text.as_bytes())?;
+        segment.pending -= 1;
+        state.pending -= 1;
+        state.warned = false;
+        self.retire(&mut state, ticket.segment)
+    }
+
+    /// Delete a segment once it is complete (full, or left by a previous
+    /// run) and all its lines are done
+    fn retire(&self, state: &mut State, number: u64) -> std::io::Result<()> {
+        let complete = number != state.current || state.written >= self.segment_lines;
+        if complete && state.segments.get(&number).is_some_and(|s| s.pending == 0) {
+            state.segments.remove(&number);
+            std::fs::remove_file(segment_path(&self.dir, number))?;
+        }
+        Ok(())
+    }
+}
+
+fn segment_path(dir: &Path, number: u64) -> PathBuf {
+    dir.join(format!("segment-{:08}.jsonl", number))
+}
+
+/// `segment-00000042.jsonl` -> 42
+fn segment_number(name: &str) -> Option<u64> {
+    name.strip_prefix("segment-")?
+        .strip_suffix(".jsonl")?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(n: u64) -> QueuedLine {
+        QueuedLine {
+            input_log: format!("withdraw {} BTC", n),
+            input_hash: "ab".to_string(),
+            prompt_log: format!("withdraw {} BTC", n),
+            context: String::new(),
+            rule: "trading#1".to_string(),
+            priority: Priority::Normal,
+        }
+    }
+
+    fn segments(dir: &Path) -> usize {
+        std::fs::read_dir(dir).unwrap().count()
+    }
+
+    #[test]
+    fn test_journal_recovery() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = QueueConfig {
+            dir: Some(dir.path().to_path_buf()),
+            segment_lines: 2,
+            max_lines: 3,
+        };
+
+        let journal = Journal::open(&config, dir.path()).unwrap();
+        let tickets: Vec<Ticket> = (1..=3)
+            .map(|n| journal.push(&line(n)).unwrap().unwrap())
+            .collect();
+        assert_eq!(journal.push(&line(4)).unwrap(), None);
+        assert_eq!(segments(dir.path()), 2);
+
+        // The first segment goes once both its lines are done
+        journal.done(tickets[0]).unwrap();
+        journal.done(tickets[2]).unwrap();
+        assert_eq!(segments(dir.path()), 2);
+        journal.done(tickets[1]).unwrap();
+        assert_eq!(segments(dir.path()), 1);
+        let ticket = journal.push(&line(5)).unwrap().unwrap();
+        drop(journal);
+
+        // Restart: only the unfinished line comes back
+        let journal = Journal::open(&config, dir.path()).unwrap();
+        assert_eq!(journal.len(), 1);
+        let recovered = journal.take_recovered();
+        assert_eq!(recovered, vec![(ticket, line(5))]);
+        assert!(journal.take_recovered().is_empty());
+
+        let next = journal.push(&line(6)).unwrap().unwrap();
+        assert!(next.seq > ticket.seq && next.segment > ticket.segment);
+        journal.done(ticket).unwrap();
+        journal.done(next).unwrap();
+        drop(journal);
+
+        // Nothing pending: the last segment is cleaned up too
+        let journal = Journal::open(&config, dir.path()).unwrap();
+        assert_eq!(journal.len(), 0);
+        assert_eq!(segments(dir.path()), 0);
+    }
+}
@@ -0,0 +1,388 @@
+//! Rate-Based Anomaly Triggers
+//!
+//! Runaway loops are made of individually benign lines. Rate rules count
+//! matching lines per agent over a sliding window and raise a synthetic
+//! suspicious line when a frequency or ratio threshold is crossed.
+//!
+//! ```toml
+//! [[rate]]
+//! id = "order-flood"
+//! pattern = '(?i)order.*placed'
+//! max_count = 50        # more than 50 matches...
+//! window_ms = 1000      # ...per second
+//!
+//! [[rate]]
+//! id = "error-rate"
+//! pattern = '(?i)error|exception'
+//! max_ratio = 0.2       # more than 20% of all lines...
+//! min_lines = 20        # ...once at least 20 lines were seen
+//! window_ms = 10000
+//! ```
+//!
+//! Windows are bucketed (1/20 of the window per bucket), so memory stays
+//! constant no matter how hard an agent floods.
+
+use crate::filter::{ConfigError, RuleAction, Severity};
+use regex::RegexSet;
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+
+/// Buckets per sliding window
+const BUCKETS: u64 = 20;
+
+/// Frequency rule from a `[[rate]]` table
+#[derive(Debug, Clone, Deserialize)]
+pub struct RateDef {
+    pub id: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Lines counted by this rule
+    pub pattern: String,
+    pub window_ms: u64,
+    /// Trigger when matches in the window exceed this count
+    #[serde(default)]
+    pub max_count: Option<u64>,
+    /// Trigger when matches / all lines in the window exceed this ratio
+    #[serde(default)]
+    pub max_ratio: Option<f64>,
+    /// Minimum lines in the window before a ratio is judged
+    #[serde(default)]
+    pub min_lines: u64,
+    #[serde(default)]
+    pub severity: Severity,
+    /// `analyze` sends the synthetic line to the LLM, `kill` acts immediately
+    #[serde(default)]
+    pub action: RuleAction,
+}
+
+impl RateDef {
+    /// Validate pattern, window, and threshold
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        regex::Regex::new(&self.pattern).map_err(|e| ConfigError::regex(&self.id, e))?;
+        if self.window_ms < BUCKETS {
+            return Err(format!("rate '{}' window must be at least {}ms", self.id, BUCKETS).into());
+        }
+        match (self.max_count, self.max_ratio) {
+            (Some(_), None) => Ok(()),
+            (None, Some(r)) if r > 0.0 && r < 1.0 => Ok(()),
+            (None, Some(_)) => {
+                Err(format!("rate '{}' max_ratio must be between 0 and 1", self.id).into())
+            }
+            _ => Err(format!(
+                "rate '{}' needs exactly one of max_count or max_ratio",
+                self.id
+            )
+            .into()),
+        }
+    }
+}
+
+/// A crossed threshold
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateHit {
+    pub id: String,
+    pub severity: Severity,
+    pub action: RuleAction,
+    /// Synthetic log line describing the anomaly
+    pub line: String,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Bucket {
+    /// Bucket sequence number since the tracker started
+    seq: u64,
+    total: u64,
+    matched: u64,
+}
+
+/// Per-rule sliding window
+#[derive(Clone)]
+struct Window {
+    buckets: [Bucket; BUCKETS as usize],
+}
+
+impl Window {
+    fn new() -> Self {
+        Self {
+            buckets: [Bucket::default(); BUCKETS as usize],
+        }
+    }
+
+    /// Count one line into bucket `seq`, return (total, matched) in the window
+    fn add(&mut self, seq: u64, matched: bool) -> (u64, u64) {
+        let slot = &mut self.buckets[(seq % BUCKETS) as usize];
+        if slot.seq != seq {
+            *slot = Bucket {
+                seq,
+                ..Default::default()
+            };
+        }
+        slot.total += 1;
+        slot.matched += matched as u64;
+
+        self.buckets
+            .iter()
+            .filter(|b| b.seq + BUCKETS > seq && b.seq <= seq && b.total > 0)
+            .fold((0, 0), |(t, m), b| (t + b.total, m + b.matched))
+    }
+}
+
+struct CompiledRate {
+    def: RateDef,
+    bucket_width: Duration,
+}
+
+/// Compiled rate rules (shared by all connections)
+pub struct RateMonitor {
+    rules: Vec<CompiledRate>,
+    patterns: RegexSet,
+}
+
+/// Per-agent rate state
+pub struct RateTracker {
+    started: Instant,
+    windows: Vec<Window>,
+}
+
+impl RateMonitor {
+    /// Compile validated rate definitions
+    pub fn new(defs: &[RateDef]) -> Self {
+        Self {
+            patterns: RegexSet::new(defs.iter().map(|d| &d.pattern))
+                .expect("Invalid rate patterns"),
+            rules: defs
+                .iter()
+                .map(|d| CompiledRate {
+                    def: d.clone(),
+                    bucket_width: Duration::from_millis(d.window_ms / BUCKETS),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Fresh state for a new agent
+    pub fn tracker(&self, now: Instant) -> RateTracker {
+        RateTracker {
+            started: now,
+            windows: vec![Window::new(); self.rules.len()],
+        }
+    }
+
+    /// Feed one line; returns the first threshold it crosses
+    ///
+    /// Thresholds are multiplied by `scale` (a schedule profile's
+    /// `rate_scale`; 1.0 as configured). A triggered rule's window is
+    /// reset, so it re-arms after a fresh window of traffic instead of
+    /// firing on every following line.
+    pub fn observe(
+        &self,
+        tracker: &mut RateTracker,
+        line: &str,
+        now: Instant,
+        scale: f64,
+    ) -> Option<RateHit> {
+        if self.rules.is_empty() {
+            return None;
+        }
+        let matches = self.patterns.matches(line);
+        let since = now.duration_since(tracker.started);
+        let mut hit = None;
+
+        for (i, (rule, window)) in self.rules.iter().zip(&mut tracker.windows).enumerate() {
+            let seq = (since.as_millis() / rule.bucket_width.as_millis().max(1)) as u64;
+            let (total, matched) = window.add(seq, matches.matched(i));
+            if hit.is_some() || !matches.matched(i) {
+                continue;
+            }
+
+            let def = &rule.def;
+            let max_count = def.max_count.map(|max| (max as f64 * scale) as u64);
+            let max_ratio = def.max_ratio.map(|max| max * scale);
+            let detail = match (max_count, max_ratio) {
+                (Some(max), _) if matched > max => Some(format!(
+                    "{} matching lines in {}ms (limit {})",
+                    matched, def.window_ms, max
+                )),
+                (_, Some(max)) if total >= def.min_lines.max(1) => {
+                    let ratio = matched as f64 / total as f64;
+                    (ratio > max).then(|| {
+                        format!(
+                            "{}/{} lines ({:.0}%) matched in {}ms (limit {:.0}%)",
+                            matched,
+                            total,
+                            ratio * 100.0,
+                            def.window_ms,
+                            max * 100.0
+                        )
+                    })
+                }
+                _ => None,
+            };
+
+            if let Some(detail) = detail {
+                *window = Window::new();
+                let label = def.description.as_deref().unwrap_or(&def.pattern);
+                hit = Some(RateHit {
+                    id: def.id.clone(),
+                    severity: def.severity,
+                    action: def.action,
+                    line: format!("RATE ANOMALY '{}' ({}): {}", def.id, label, detail),
+                });
+            }
+        }
+
+        hit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(id: &str, pattern: &str, window_ms: u64) -> RateDef {
+        RateDef {
+            id: id.to_string(),
+            description: None,
+            pattern: pattern.to_string(),
+            window_ms,
+            max_count: None,
+            max_ratio: None,
+            min_lines: 0,
+            severity: Severity::High,
+            action: RuleAction::Analyze,
+        }
+    }
+
+    #[test]
+    fn test_count_threshold() {
+        let monitor = RateMonitor::new(&[RateDef {
+            max_count: Some(50),
+            ..rule("order-flood", "(?i)order.*placed", 1000)
+        }]);
+        let t0 = Instant::now();
+        let mut t = monitor.tracker(t0);
+
+        for i in 0..50 {
+            let now = t0 + Duration::from_millis(i * 10);
+            assert!(monitor.observe(&mut t, "Order placed", now, 1.0).is_none());
+        }
+        let hit = monitor
+            .observe(&mut t, "Order placed", t0 + Duration::from_millis(500), 1.0)
+            .unwrap();
+        assert_eq!(hit.id, "order-flood");
+        assert!(hit.line.contains("51 matching lines in 1000ms (limit 50)"));
+
+        // Window was reset: the next line does not re-fire
+        assert!(monitor
+            .observe(&mut t, "Order placed", t0 + Duration::from_millis(510), 1.0)
+            .is_none());
+    }
+
+    #[test]
+    fn test_count_window_slides() {
+        let monitor = RateMonitor::new(&[RateDef {
+            max_count: Some(5),
+            ..rule("flood", "tick", 1000)
+        }]);
+        let t0 = Instant::now();
+        let mut t = monitor.tracker(t0);
+
+        // 5 per second forever never exceeds the limit
+        for i in 0..50 {
+            let now = t0 + Duration::from_millis(i * 200);
+            assert!(
+                monitor.observe(&mut t, "tick", now, 1.0).is_none(),
+                "tick {}",
+                i
+            );
+        }
+    }
+
+    #[test]
+    fn test_ratio_threshold() {
+        let monitor = RateMonitor::new(&[RateDef {
+            max_ratio: Some(0.2),
+            min_lines: 20,
+            ..rule("error-rate", "(?i)error", 10_000)
+        }]);
+        let t0 = Instant::now();
+        let mut t = monitor.tracker(t0);
+
+        // 10% errors: fine
+        for i in 0..100 {
+            let line = if i % 10 == 0 { "ERROR x" } else { "ok" };
+            assert!(monitor.observe(&mut t, line, t0, 1.0).is_none());
+        }
+        // Burst of errors pushes the ratio over 20%
+        let hit = (0..30)
+            .find_map(|_| monitor.observe(&mut t, "ERROR x", t0, 1.0))
+            .unwrap();
+        assert!(hit.line.starts_with("RATE ANOMALY 'error-rate'"));
+    }
+
+    #[test]
+    fn test_scaled_threshold() {
+        let monitor = RateMonitor::new(&[RateDef {
+            max_count: Some(10),
+            ..rule("flood", "tick", 1000)
+        }]);
+        let t0 = Instant::now();
+        let mut t = monitor.tracker(t0);
+        // Half the threshold: the 6th line in the window fires
+        for _ in 0..5 {
+            assert!(monitor.observe(&mut t, "tick", t0, 0.5).is_none());
+        }
+        assert!(monitor.observe(&mut t, "tick", t0, 0.5).is_some());
+    }
+
+    #[test]
+    fn test_ratio_needs_min_lines() {
+        let monitor = RateMonitor::new(&[RateDef {
+            max_ratio: Some(0.2),
+            min_lines: 20,
+            ..rule("error-rate", "(?i)error", 10_000)
+        }]);
+        let t0 = Instant::now();
+        let mut t = monitor.tracker(t0);
+        for _ in 0..19 {
+            assert!(monitor.observe(&mut t, "ERROR", t0, 1.0).is_none());
+        }
+        assert!(monitor.observe(&mut t, "ERROR", t0, 1.0).is_some());
+    }
+
+    #[test]
+    fn test_validate() {
+        let base = rule("r", "x", 1000);
+        assert!(base.validate().is_err()); // no threshold
+        assert!(RateDef {
+            max_count: Some(1),
+            max_ratio: Some(0.5),
+            ..base.clone()
+        }
+        .validate()
+        .is_err());
+        assert!(RateDef {
+            max_ratio: Some(1.5),
+            ..base.clone()
+        }
+        .validate()
+        .is_err());
+        assert!(RateDef {
+            max_count: Some(1),
+            window_ms: 5,
+            ..base.clone()
+        }
+        .validate()
+        .is_err());
+        assert!(RateDef {
+            max_count: Some(1),
+            ..base
+        }
+        .validate()
+        .is_ok());
+    }
+}
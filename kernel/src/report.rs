@@ -0,0 +1,819 @@
+//! Audit Report - Offline Statistics from an Audit Trail
+//!
+//! `tripwired audit stats <file>` reads an audit JSONL file (live or
+//! archived, several runs appended) and prints what it holds:
+//!
+//! ```text
+//! Audit report: tripwired-audit.jsonl (3 runs)
+//!   decisions  1234, 2026-10-14 08:00 → 2026-10-15 09:12 UTC
+//!   KILL       12 (0.97%)
+//!   filtered   1100 (89.14% decided without the LLM)
+//!   LLM        134 analyses: p50 210ms, p90 480ms, p99 910ms, max 1200ms
+//!   per hour (UTC)
+//!     2026-10-14 08:00      120  KILL 2
+//!   top templates
+//!          412  KILL 0  GET /health <num>
+//!   models
+//!         1234  llama3@ab12cd34
+//!   LLM errors (degraded decisions)
+//!            3  llm.timeout
+//!   chain
+//!     ✓ ids 1-1234: 1 lost to a torn write (recorded by the next start)
+//!     ✓ corrupt lines: none
+//!     ✓ shutdown footers: 2 verified, 0 failed, 0 unverified
+//!     ✓ ends with a shutdown footer
+//!   VERIFIED
+//! ```
+//!
+//! The chain check: decision IDs have no gaps or duplicates (an ID lost to
+//! a torn write is fine once a `recovery` event records it), every corrupt
+//! line was acknowledged by a `recovery` event, and every shutdown footer
+//! carries a valid signature and the last ID written before it. Merkle
+//! checkpoints (see `checkpoint`) are recomputed from the records before
+//! them and must link to the previous checkpoint. Retention tombstones
+//! (see `retention`) must carry a valid signature. HMAC footers need
+//! `--key-file` (the kernel's `--audit-key-file`); without it they are
+//! reported unverified, not failed. A failed check exits non-zero.
+//! `--json` prints the same report as one JSON object.
+
+use crate::audit::{CheckpointEvent, DecisionRecord, PruneEvent, RecoveryEvent, ShutdownFooter};
+use crate::checkpoint::{leaf_hash, merkle_root, to_hex};
+use crate::error::KernelError;
+use crate::normalize::Normalizer;
+use crate::schedule::civil_from_days;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+/// `tripwired audit stats` options
+#[derive(Debug, Clone)]
+pub struct StatsOptions {
+    pub file: PathBuf,
+    pub json: bool,
+    /// Templates listed
+    pub top: usize,
+    /// HMAC key the footers were signed with
+    pub key_file: Option<PathBuf>,
+}
+
+/// Aggregates over every decision in the file
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub file: PathBuf,
+    /// Kernel starts (header records)
+    pub runs: u64,
+    pub decisions: u64,
+    pub kills: u64,
+    /// KILL share of all decisions
+    pub kill_rate: f64,
+    /// Decided without the LLM (pre-filter and rule kills)
+    pub filtered: u64,
+    /// Filtered share of all decisions
+    pub filter_efficiency: f64,
+    /// `CHECK` requests the guard denied (`denial` events)
+    pub denials: u64,
+    /// Decoy accesses (`honeypot` events)
+    pub honeypots: u64,
+    /// KILLs countersigned / paused without approval (`authorization` events)
+    pub authorized: u64,
+    pub unauthorized: u64,
+    pub first_ms: Option<u64>,
+    pub last_ms: Option<u64>,
+    pub per_hour: Vec<HourCount>,
+    /// LLM analyses only (pre-filter latencies are microseconds)
+    pub latency_ms: Option<Percentiles>,
+    /// Most frequent templates, most frequent first
+    pub templates: Vec<TemplateCount>,
+    /// Decisions per model fingerprint
+    pub models: BTreeMap<String, u64>,
+    /// Degraded-mode decisions per LLM failure category
+    pub errors: BTreeMap<String, u64>,
+    pub chain: Chain,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HourCount {
+    /// `YYYY-MM-DD HH:00`, UTC
+    pub hour: String,
+    pub decisions: u64,
+    pub kills: u64,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Percentiles {
+    pub analyses: u64,
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub max: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TemplateCount {
+    pub template: String,
+    pub decisions: u64,
+    pub kills: u64,
+}
+
+/// Chain verification
+#[derive(Debug, Default, Serialize)]
+pub struct Chain {
+    /// Every check below passed
+    pub verified: bool,
+    pub first_id: Option<u64>,
+    pub last_id: Option<u64>,
+    /// Unexplained ID gaps (inclusive ranges)
+    pub missing: Vec<(u64, u64)>,
+    /// IDs written more than once
+    pub duplicates: u64,
+    /// IDs lost to a torn write, recorded by a `recovery` event
+    pub lost: u64,
+    pub corrupt_lines: u64,
+    /// Corrupt lines not recorded by a later `recovery` event
+    pub unacknowledged: u64,
+    pub footers: u64,
+    pub footers_verified: u64,
+    /// Bad signature, or a `last_id` below an ID written before it
+    pub footers_failed: u64,
+    /// HMAC-signed and no key given
+    pub footers_unverified: u64,
+    pub checkpoints: u64,
+    /// Root, record count and link to the previous checkpoint match
+    pub checkpoints_verified: u64,
+    pub checkpoints_failed: u64,
+    /// Successful `anchor` events
+    pub anchored: u64,
+    /// Records written after the last checkpoint of their run
+    pub uncovered: u64,
+    /// Segments pruned by the retention policy (`prune` events)
+    pub tombstones: u64,
+    pub tombstones_verified: u64,
+    pub tombstones_failed: u64,
+    /// HMAC-signed and no key given
+    pub tombstones_unverified: u64,
+    /// Records stamped while the wall clock was behind an earlier record
+    /// (`clock_behind_ms`: annotated by the kernel, ordered by `monotonic_ms`)
+    pub clock_behind: u64,
+    /// `shutdown` (clean exit), `rotated` (a closed segment), `open`
+    /// (running, or crashed) or `torn` (crashed mid-write)
+    pub end: String,
+}
+
+/// `tripwired audit stats`
+pub fn run(options: &StatsOptions) -> Result<(), KernelError> {
+    let key = options.key_file.as_ref().map(std::fs::read).transpose()?;
+    let report = Report::read(&options.file, options.top, key.as_deref())?;
+    match options.json {
+        true => println!("{}", serde_json::to_string_pretty(&report)?),
+        false => print(&report),
+    }
+    if !report.chain.verified {
+        return Err(format!("{} failed chain verification", options.file.display()).into());
+    }
+    Ok(())
+}
+
+impl Report {
+    /// Read the whole file; `key` verifies HMAC-signed footers
+    pub fn read(path: &Path, top: usize, key: Option<&[u8]>) -> std::io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let normalizer = Normalizer::new();
+        let mut report = Report {
+            file: path.to_path_buf(),
+            runs: 0,
+            decisions: 0,
+            kills: 0,
+            kill_rate: 0.0,
+            filtered: 0,
+            filter_efficiency: 0.0,
+            denials: 0,
+            honeypots: 0,
+            authorized: 0,
+            unauthorized: 0,
+            first_ms: None,
+            last_ms: None,
+            per_hour: Vec::new(),
+            latency_ms: None,
+            templates: Vec::new(),
+            models: BTreeMap::new(),
+            errors: BTreeMap::new(),
+            chain: Chain::default(),
+        };
+        let mut hours: BTreeMap<u64, (u64, u64)> = BTreeMap::new();
+        let mut templates: HashMap<String, (u64, u64)> = HashMap::new();
+        let mut latencies = Vec::new();
+        let mut ids = Vec::new();
+        let mut lost = BTreeSet::new();
+        let mut max_id = 0;
+        let mut end = "open";
+        // Records since the last checkpoint, and its root
+        let mut leaves = Vec::new();
+        let mut previous = None;
+
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+            let value = match serde_json::from_slice::<serde_json::Value>(&line) {
+                Ok(value) => value,
+                // A final line without newline was cut off mid-write
+                Err(_) if line.last() != Some(&b'\n') => {
+                    end = "torn";
+                    break;
+                }
+                Err(_) => {
+                    report.chain.corrupt_lines += 1;
+                    report.chain.unacknowledged += 1;
+                    end = "open";
+                    continue;
+                }
+            };
+
+            match value.get("event").and_then(|e| e.as_str()) {
+                Some("recovery") => {
+                    let Ok(event) = serde_json::from_value::<RecoveryEvent>(value) else {
+                        report.chain.corrupt_lines += 1;
+                        report.chain.unacknowledged += 1;
+                        continue;
+                    };
+                    // Counts every corrupt line in the file up to here
+                    let torn = u64::from(event.torn_line_bytes.is_some());
+                    report.chain.unacknowledged = report
+                        .chain
+                        .corrupt_lines
+                        .saturating_sub(event.corrupt_lines + torn);
+                    lost.extend(event.lost_id);
+                }
+                Some("shutdown") => {
+                    let Ok(footer) = serde_json::from_value::<ShutdownFooter>(value) else {
+                        report.chain.corrupt_lines += 1;
+                        report.chain.unacknowledged += 1;
+                        continue;
+                    };
+                    report.chain.footers += 1;
+                    match footer.verify(key) {
+                        Some(true) if footer.last_id >= max_id => {
+                            report.chain.footers_verified += 1
+                        }
+                        None => report.chain.footers_unverified += 1,
+                        _ => report.chain.footers_failed += 1,
+                    }
+                    end = "shutdown";
+                }
+                Some("checkpoint") => {
+                    let Ok(checkpoint) = serde_json::from_value::<CheckpointEvent>(value) else {
+                        report.chain.corrupt_lines += 1;
+                        report.chain.unacknowledged += 1;
+                        continue;
+                    };
+                    report.chain.checkpoints += 1;
+                    let root = to_hex(&merkle_root(&leaves));
+                    match checkpoint.root == root
+                        && checkpoint.records == leaves.len()
+                        && checkpoint.previous == previous
+                    {
+                        true => report.chain.checkpoints_verified += 1,
+                        false => report.chain.checkpoints_failed += 1,
+                    }
+                    leaves.clear();
+                    previous = Some(checkpoint.root);
+                }
+                Some("prune") => {
+                    let Ok(tombstone) = serde_json::from_value::<PruneEvent>(value) else {
+                        report.chain.corrupt_lines += 1;
+                        report.chain.unacknowledged += 1;
+                        continue;
+                    };
+                    report.chain.tombstones += 1;
+                    match tombstone.verify(key) {
+                        Some(true) => report.chain.tombstones_verified += 1,
+                        None => report.chain.tombstones_unverified += 1,
+                        Some(false) => report.chain.tombstones_failed += 1,
+                    }
+                    // Checkpointed along with the records
+                    leaves.push(leaf_hash(line.strip_suffix(b"\n").unwrap_or(&line)));
+                }
+                Some("rotate") => end = "rotated",
+                Some("denial") => report.denials += 1,
+                Some("honeypot") => report.honeypots += 1,
+                Some("authorization") => match value["approved"].as_bool() {
+                    Some(true) => report.authorized += 1,
+                    _ => report.unauthorized += 1,
+                },
+                Some("anchor") if value.get("ok") == Some(&serde_json::Value::Bool(true)) => {
+                    report.chain.anchored += 1
+                }
+                Some(_) => {}
+                None if value.get("id").is_some() => {
+                    let Ok(record) = serde_json::from_value::<DecisionRecord>(value) else {
+                        report.chain.corrupt_lines += 1;
+                        report.chain.unacknowledged += 1;
+                        end = "open";
+                        continue;
+                    };
+                    let kill = record.action == "KILL";
+                    report.observe(&record);
+                    let hour = hours.entry(record.timestamp_ms / 3_600_000).or_default();
+                    hour.0 += 1;
+                    hour.1 += u64::from(kill);
+                    let template = match record.encrypted {
+                        true => format!("(encrypted) {}", &record.template_hash[..16]),
+                        false => normalizer.template(&record.input_log),
+                    };
+                    let template = templates.entry(template).or_default();
+                    template.0 += 1;
+                    template.1 += u64::from(kill);
+                    if !record.filtered {
+                        latencies.push(record.latency_ms);
+                    }
+                    ids.push(record.id);
+                    max_id = max_id.max(record.id);
+                    leaves.push(leaf_hash(line.strip_suffix(b"\n").unwrap_or(&line)));
+                    end = "open";
+                }
+                // Header: one per kernel start, which checkpoints afresh
+                None if value.get("version").is_some() => {
+                    report.runs += 1;
+                    report.chain.uncovered += leaves.len() as u64;
+                    leaves.clear();
+                    // A rotated-to segment links to the previous one
+                    if let Some(root) = value.get("checkpoint").and_then(|r| r.as_str()) {
+                        previous = Some(root.to_string());
+                    }
+                }
+                None => {}
+            }
+        }
+
+        report.kill_rate = ratio(report.kills, report.decisions);
+        report.filter_efficiency = ratio(report.filtered, report.decisions);
+        report.per_hour = hours
+            .into_iter()
+            .map(|(hour, (decisions, kills))| HourCount {
+                hour: hour_label(hour),
+                decisions,
+                kills,
+            })
+            .collect();
+        report.latency_ms = percentiles(latencies);
+        let mut templates: Vec<TemplateCount> = templates
+            .into_iter()
+            .map(|(template, (decisions, kills))| TemplateCount {
+                template,
+                decisions,
+                kills,
+            })
+            .collect();
+        templates.sort_by(|a, b| {
+            b.decisions
+                .cmp(&a.decisions)
+                .then_with(|| a.template.cmp(&b.template))
+        });
+        templates.truncate(top);
+        report.templates = templates;
+        report.chain.end = end.to_string();
+        report.chain.uncovered += leaves.len() as u64;
+        report.chain.check_ids(ids, &lost);
+        Ok(report)
+    }
+
+    fn observe(&mut self, record: &DecisionRecord) {
+        self.decisions += 1;
+        self.kills += u64::from(record.action == "KILL");
+        self.filtered += u64::from(record.filtered);
+        self.first_ms = Some(
+            self.first_ms
+                .map_or(record.timestamp_ms, |t| t.min(record.timestamp_ms)),
+        );
+        self.last_ms = Some(self.last_ms.unwrap_or(0).max(record.timestamp_ms));
+        *self
+            .models
+            .entry(record.model_fingerprint.clone())
+            .or_default() += 1;
+        if let Some(ref error) = record.error {
+            *self.errors.entry(error.clone()).or_default() += 1;
+        }
+        self.chain.clock_behind += u64::from(record.clock_behind_ms.is_some());
+    }
+}
+
+impl Chain {
+    /// Look for gaps and duplicates, then settle `verified`
+    fn check_ids(&mut self, mut ids: Vec<u64>, lost: &BTreeSet<u64>) {
+        // Concurrent analyses may write their records out of order
+        ids.sort_unstable();
+        self.first_id = ids.first().copied();
+        self.last_id = ids.last().copied();
+        for pair in ids.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            if a == b {
+                self.duplicates += 1;
+                continue;
+            }
+            let mut from = a + 1;
+            for &id in lost.range(a + 1..b) {
+                self.lost += 1;
+                if id > from {
+                    self.missing.push((from, id - 1));
+                }
+                from = id + 1;
+            }
+            if b > from {
+                self.missing.push((from, b - 1));
+            }
+        }
+        self.verified = self.missing.is_empty()
+            && self.duplicates == 0
+            && self.unacknowledged == 0
+            && self.footers_failed == 0
+            && self.checkpoints_failed == 0
+            && self.tombstones_failed == 0;
+    }
+}
+
+fn ratio(part: u64, total: u64) -> f64 {
+    match total {
+        0 => 0.0,
+        _ => part as f64 / total as f64,
+    }
+}
+
+/// Nearest-rank percentiles
+pub fn percentiles(mut latencies: Vec<u64>) -> Option<Percentiles> {
+    latencies.sort_unstable();
+    let max = *latencies.last()?;
+    let rank = |pct: usize| latencies[(latencies.len() * pct).div_ceil(100).max(1) - 1];
+    Some(Percentiles {
+        analyses: latencies.len() as u64,
+        p50: rank(50),
+        p90: rank(90),
+        p99: rank(99),
+        max,
+    })
+}
+
+/// Hours since the epoch -> `YYYY-MM-DD HH:00`
+fn hour_label(hour: u64) -> String {
+    let (year, month, day) = civil_from_days((hour / 24) as i64);
+    format!("{:04}-{:02}-{:02} {:02}:00", year, month, day, hour % 24)
+}
+
+/// Unix ms -> `YYYY-MM-DD HH:MM`
+fn minute_label(ms: u64) -> String {
+    let minute = (ms / 60_000) % 60;
+    format!("{}{:02}", &hour_label(ms / 3_600_000)[..14], minute)
+}
+
+fn print(report: &Report) {
+    println!(
+        "Audit report: {} ({} runs)",
+        report.file.display(),
+        report.runs
+    );
+    match (report.first_ms, report.last_ms) {
+        (Some(first), Some(last)) => println!(
+            "  decisions  {}, {} → {} UTC",
+            report.decisions,
+            minute_label(first),
+            minute_label(last)
+        ),
+        _ => println!("  decisions  0"),
+    }
+    println!(
+        "  KILL       {} ({:.2}%)",
+        report.kills,
+        report.kill_rate * 100.0
+    );
+    println!(
+        "  filtered   {} ({:.2}% decided without the LLM)",
+        report.filtered,
+        report.filter_efficiency * 100.0
+    );
+    if report.denials > 0 {
+        println!("  denied     {} guard checks", report.denials);
+    }
+    if report.honeypots > 0 {
+        println!("  honeypot   {} decoy accesses", report.honeypots);
+    }
+    if report.authorized + report.unauthorized > 0 {
+        println!(
+            "  authorizer {} KILLs approved, {} paused without approval",
+            report.authorized, report.unauthorized
+        );
+    }
+    match report.latency_ms {
+        Some(ref l) => println!(
+            "  LLM        {} analyses: p50 {}ms, p90 {}ms, p99 {}ms, max {}ms",
+            l.analyses, l.p50, l.p90, l.p99, l.max
+        ),
+        None => println!("  LLM        no analyses"),
+    }
+    if !report.per_hour.is_empty() {
+        println!("  per hour (UTC)");
+        for hour in &report.per_hour {
+            println!(
+                "    {}  {:>7}  KILL {}",
+                hour.hour, hour.decisions, hour.kills
+            );
+        }
+    }
+    if !report.templates.is_empty() {
+        println!("  top templates");
+        for t in &report.templates {
+            println!("    {:>9}  KILL {}  {}", t.decisions, t.kills, t.template);
+        }
+    }
+    if !report.models.is_empty() {
+        println!("  models");
+        let mut models: Vec<_> = report.models.iter().collect();
+        models.sort_by(|a, b| b.1.cmp(a.1));
+        for (model, count) in models {
+            println!("    {:>9}  {}", count, model);
+        }
+    }
+    if !report.errors.is_empty() {
+        println!("  LLM errors (degraded decisions)");
+        for (category, count) in &report.errors {
+            println!("    {:>9}  {}", count, category);
+        }
+    }
+
+    let chain = &report.chain;
+    println!("  chain");
+    let mark = |ok: bool| if ok { "✓" } else { "✗" };
+    let ids = match (chain.first_id, chain.last_id) {
+        (Some(first), Some(last)) => format!("ids {}-{}", first, last),
+        _ => "ids: none".to_string(),
+    };
+    let mut notes = Vec::new();
+    if chain.lost > 0 {
+        notes.push(format!(
+            "{} lost to a torn write (recorded by the next start)",
+            chain.lost
+        ));
+    }
+    if !chain.missing.is_empty() {
+        let ranges: Vec<String> = chain
+            .missing
+            .iter()
+            .map(|&(a, b)| match a == b {
+                true => a.to_string(),
+                false => format!("{}-{}", a, b),
+            })
+            .collect();
+        notes.push(format!("missing {}", ranges.join(", ")));
+    }
+    if chain.duplicates > 0 {
+        notes.push(format!("{} duplicated", chain.duplicates));
+    }
+    println!(
+        "    {} {}{}",
+        mark(chain.missing.is_empty() && chain.duplicates == 0),
+        ids,
+        match notes.is_empty() {
+            true => String::new(),
+            false => format!(": {}", notes.join(", ")),
+        }
+    );
+    println!(
+        "    {} corrupt lines: {}",
+        mark(chain.unacknowledged == 0),
+        match (chain.corrupt_lines, chain.unacknowledged) {
+            (0, _) => "none".to_string(),
+            (n, 0) => format!("{} (recorded by the next start)", n),
+            (n, u) => format!("{} ({} not recorded by a recovery event)", n, u),
+        }
+    );
+    println!(
+        "    {} shutdown footers: {} verified, {} failed, {} unverified{}",
+        mark(chain.footers_failed == 0),
+        chain.footers_verified,
+        chain.footers_failed,
+        chain.footers_unverified,
+        match chain.footers_unverified {
+            0 => "",
+            _ => " (HMAC-signed: pass --key-file)",
+        }
+    );
+    if chain.checkpoints > 0 {
+        println!(
+            "    {} checkpoints: {} verified, {} failed, {} anchors, {} records after the last",
+            mark(chain.checkpoints_failed == 0),
+            chain.checkpoints_verified,
+            chain.checkpoints_failed,
+            chain.anchored,
+            chain.uncovered
+        );
+    }
+    if chain.tombstones > 0 {
+        println!(
+            "    {} prune tombstones: {} verified, {} failed, {} unverified",
+            mark(chain.tombstones_failed == 0),
+            chain.tombstones_verified,
+            chain.tombstones_failed,
+            chain.tombstones_unverified
+        );
+    }
+    if chain.clock_behind > 0 {
+        println!(
+            "    {} wall clock stepped back: {} records annotated",
+            mark(true),
+            chain.clock_behind
+        );
+    }
+    println!(
+        "    {} {}",
+        mark(true),
+        match chain.end.as_str() {
+            "shutdown" => "ends with a shutdown footer",
+            "rotated" => "ends with a rotation (closed segment)",
+            "torn" => "ends with a torn line (crashed mid-write)",
+            _ => "no shutdown footer at the end (running, or crashed)",
+        }
+    );
+    println!(
+        "  {}",
+        if chain.verified {
+            "VERIFIED"
+        } else {
+            "NOT VERIFIED"
+        }
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::{
+        AuditTrail, AuthorizationEvent, DenialEvent, HoneypotEvent, ModelFingerprint, RecordInput,
+    };
+    use crate::llm::Sampling;
+    use std::io::Write;
+
+    #[test]
+    fn test_audit_report() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let fp = ModelFingerprint::new("test-model", "http://localhost", 30, &Sampling::default());
+        let trail = AuditTrail::new(path.clone(), fp, "test prompt")
+            .unwrap()
+            .with_signing_key(b"secret".to_vec());
+        trail
+            .record("GET /health 200", "SUSTAIN", 100, true, 3, None)
+            .unwrap();
+        trail
+            .record("GET /health 404", "SUSTAIN", 100, true, 2, None)
+            .unwrap();
+        trail
+            .record("rm -rf /data", "KILL", 95, false, 300, None)
+            .unwrap();
+        trail
+            .record("withdraw 5 BTC", "SUSTAIN", 80, false, 100, None)
+            .unwrap();
+        trail
+            .record_entry(RecordInput {
+                input_log: "withdraw 9 BTC",
+                action: "SUSTAIN",
+                latency_ms: 5000,
+                error: Some("llm.timeout"),
+                ..Default::default()
+            })
+            .unwrap();
+        trail
+            .record_denial(&DenialEvent::new(
+                &Default::default(),
+                "drop table users",
+                "drop table users",
+            ))
+            .unwrap();
+        trail
+            .record_honeypot(&HoneypotEvent::new(
+                "/home/agent/.aws/credentials",
+                "opened",
+                Some(42),
+            ))
+            .unwrap();
+        let mut authorization = AuthorizationEvent::new(3, "https://risk.example.com", "c0ffee");
+        authorization.approved = true;
+        trail.record_authorization(&authorization).unwrap();
+        trail
+            .record_shutdown("SIGTERM", true, &serde_json::json!({"kills": 1}))
+            .unwrap();
+        drop(trail);
+
+        let report = Report::read(&path, 1, Some(b"secret")).unwrap();
+        assert_eq!((report.runs, report.decisions, report.kills), (1, 5, 1));
+        assert_eq!((report.kill_rate, report.filter_efficiency), (0.2, 0.4));
+        assert_eq!((report.denials, report.honeypots), (1, 1));
+        assert_eq!((report.authorized, report.unauthorized), (1, 0));
+        assert_eq!(report.per_hour.len(), 1);
+        assert_eq!(
+            report.latency_ms,
+            Some(Percentiles {
+                analyses: 3,
+                p50: 300,
+                p90: 5000,
+                p99: 5000,
+                max: 5000
+            })
+        );
+        assert_eq!(report.templates[0].template, "GET /health <num>");
+        assert_eq!(report.templates.len(), 1);
+        assert_eq!(report.models.values().sum::<u64>(), 5);
+        assert_eq!(report.errors["llm.timeout"], 1);
+        assert!(report.chain.verified, "{:?}", report.chain);
+        assert_eq!(report.chain.footers_verified, 1);
+        assert_eq!(report.chain.end, "shutdown");
+
+        // Without the key the footer cannot be checked, but is not failed
+        let report = Report::read(&path, 1, None).unwrap();
+        assert_eq!(report.chain.footers_unverified, 1);
+        assert!(report.chain.verified);
+
+        // A deleted record and a forged footer
+        let content = std::fs::read_to_string(&path).unwrap();
+        let tampered: String = content
+            .lines()
+            .filter(|l| !l.starts_with("{\"id\":2,"))
+            .map(|l| l.replace("\"kills\":1", "\"kills\":0") + "\n")
+            .collect();
+        std::fs::write(&path, tampered).unwrap();
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        writeln!(file, "{{\"id\":5,").unwrap();
+        drop(file);
+
+        let report = Report::read(&path, 1, Some(b"secret")).unwrap();
+        assert!(!report.chain.verified);
+        assert_eq!(report.chain.missing, [(2, 2)]);
+        assert_eq!(report.chain.footers_failed, 1);
+        assert_eq!(report.chain.unacknowledged, 1);
+    }
+
+    #[test]
+    fn test_checkpoints() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let open = |tx| {
+            let fp =
+                ModelFingerprint::new("test-model", "http://localhost", 30, &Sampling::default());
+            AuditTrail::new(path.clone(), fp, "test prompt")
+                .unwrap()
+                .with_checkpoints(2, Some(tx))
+        };
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let trail = open(tx);
+        for n in 1..=3 {
+            trail
+                .record(&format!("line {}", n), "SUSTAIN", 90, true, 1, None)
+                .unwrap();
+        }
+        let first = rx.try_recv().unwrap();
+        assert_eq!((first.first_id, first.last_id, first.records), (1, 2, 2));
+        assert!(rx.try_recv().is_err());
+        trail.close_checkpoints().unwrap();
+        let last = rx.try_recv().unwrap();
+        assert_eq!((last.first_id, last.records), (3, 1));
+        assert_eq!(last.previous.as_deref(), Some(first.root.as_str()));
+        // Closed: the anchorer's channel ends
+        assert!(rx.try_recv().is_err() && rx.is_closed());
+        drop(trail);
+
+        // The next run links to the last checkpoint in the file
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let trail = open(tx);
+        trail.record("line 4", "KILL", 90, true, 1, None).unwrap();
+        trail.record("line 5", "KILL", 90, true, 1, None).unwrap();
+        assert_eq!(rx.try_recv().unwrap().previous, Some(last.root));
+        trail.record("line 6", "KILL", 90, true, 1, None).unwrap();
+        drop(trail);
+
+        let report = Report::read(&path, 10, None).unwrap();
+        let chain = &report.chain;
+        assert_eq!((chain.checkpoints, chain.checkpoints_verified), (3, 3));
+        assert_eq!(chain.uncovered, 1);
+        assert!(chain.verified);
+
+        // An edited record no longer hashes to its checkpoint
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, content.replace("line 4", "line 0")).unwrap();
+        let report = Report::read(&path, 10, None).unwrap();
+        assert_eq!(report.chain.checkpoints_failed, 1);
+        assert!(!report.chain.verified);
+    }
+
+    #[test]
+    fn test_hour_label() {
+        assert_eq!(hour_label(0), "1970-01-01 00:00");
+        // 2024-02-29 13:00 UTC
+        assert_eq!(hour_label(1_709_211_600 / 3600), "2024-02-29 13:00");
+        assert_eq!(minute_label(1_709_212_500_000), "2024-02-29 13:15");
+    }
+}
@@ -0,0 +1,489 @@
+//! Resource Monitor - Runaway Processes That Never Log It
+//!
+//! A rogue agent that leaks memory, spins a core or forks workers says
+//! nothing about it in its own logs. With `[resource]` enabled, the kernel
+//! samples `--target-pid` every `interval_ms` - CPU time, resident memory,
+//! open handles (file descriptors on Linux) and direct child processes -
+//! and escalates a synthetic `RESOURCE` line when a threshold is crossed:
+//!
+//! - `max_rss_mb`: resident memory above the limit
+//! - `rss_growth`: memory grew that many times over within `window_ms`
+//! - `max_cpu_pct`: average CPU over `window_ms` (100 = one core)
+//! - `max_handles` / `max_children`: open handles / child processes
+//!
+//! Each threshold fires once, then again only after it has cleared. The
+//! lines are judged like rate anomalies (`action`: `analyze` asks the LLM,
+//! `kill` acts at once) in a session named `resource:<pid>`. Supported on
+//! Linux (`/proc`) and Windows (process and ToolHelp APIs).
+//!
+//! ```toml
+//! [resource]
+//! interval_ms = 5000
+//! window_ms = 60000
+//! rss_growth = 10.0
+//! max_rss_mb = 4096
+//! max_cpu_pct = 350
+//! max_handles = 10000
+//! max_children = 50
+//! action = "analyze"
+//! ```
+
+use crate::filter::RuleAction;
+use serde::Deserialize;
+use std::collections::{HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+const MB: u64 = 1024 * 1024;
+
+/// `[resource]` table
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ResourceConfig {
+    /// Sampling period (milliseconds); 0 disables the monitor
+    pub interval_ms: u64,
+    /// Window for `rss_growth` and `max_cpu_pct` (milliseconds)
+    pub window_ms: u64,
+    pub max_rss_mb: Option<u64>,
+    /// Growth factor of resident memory within the window
+    pub rss_growth: Option<f64>,
+    /// Average CPU percentage over the window (100 = one core)
+    pub max_cpu_pct: Option<f64>,
+    pub max_handles: Option<u64>,
+    pub max_children: Option<u64>,
+    /// `analyze` sends the synthetic line to the LLM, `kill` acts immediately
+    pub action: RuleAction,
+}
+
+impl Default for ResourceConfig {
+    fn default() -> Self {
+        Self {
+            interval_ms: 0,
+            window_ms: 60_000,
+            max_rss_mb: None,
+            rss_growth: None,
+            max_cpu_pct: None,
+            max_handles: None,
+            max_children: None,
+            action: RuleAction::Analyze,
+        }
+    }
+}
+
+impl ResourceConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.enabled() {
+            return Ok(());
+        }
+        let limits = [
+            self.max_rss_mb.is_some(),
+            self.rss_growth.is_some(),
+            self.max_cpu_pct.is_some(),
+            self.max_handles.is_some(),
+            self.max_children.is_some(),
+        ];
+        if !limits.contains(&true) {
+            return Err("resource: needs at least one threshold".to_string());
+        }
+        if self.window_ms < self.interval_ms {
+            return Err("resource: window_ms must be at least interval_ms".to_string());
+        }
+        if self.rss_growth.is_some_and(|g| g <= 1.0) {
+            return Err("resource: rss_growth must be > 1".to_string());
+        }
+        if self.max_cpu_pct.is_some_and(|c| c <= 0.0) {
+            return Err("resource: max_cpu_pct must be > 0".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.interval_ms > 0
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_millis(self.interval_ms)
+    }
+}
+
+/// One reading of the target process
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    /// User + kernel CPU time since the process started
+    pub cpu: Duration,
+    /// Resident memory (bytes)
+    pub rss: u64,
+    /// Open handles / file descriptors (`None` = not readable)
+    pub handles: Option<u64>,
+    /// Direct child processes
+    pub children: u64,
+}
+
+/// A crossed threshold
+#[derive(Debug, PartialEq)]
+pub struct ResourceHit {
+    /// `resource-rss`, `resource-rss-growth`, `resource-cpu`, ...
+    pub id: &'static str,
+    pub line: String,
+}
+
+/// Threshold state for one target process
+pub struct ResourceMonitor {
+    config: ResourceConfig,
+    pid: u32,
+    /// Samples within the window, oldest first
+    samples: VecDeque<(Instant, Sample)>,
+    /// Thresholds currently crossed (fire again once cleared)
+    active: HashSet<&'static str>,
+}
+
+impl ResourceMonitor {
+    pub fn new(config: &ResourceConfig, pid: u32) -> Self {
+        Self {
+            config: config.clone(),
+            pid,
+            samples: VecDeque::new(),
+            active: HashSet::new(),
+        }
+    }
+
+    /// Record a sample; returns the thresholds it newly crosses
+    pub fn observe(&mut self, sample: Sample, now: Instant) -> Vec<ResourceHit> {
+        let window = Duration::from_millis(self.config.window_ms);
+        self.samples.push_back((now, sample));
+        while self
+            .samples
+            .front()
+            .is_some_and(|(at, _)| now.duration_since(*at) > window)
+        {
+            self.samples.pop_front();
+        }
+
+        let pid = self.pid;
+        let mut crossed: Vec<(&'static str, String)> = Vec::new();
+        if let Some(max) = self.config.max_rss_mb {
+            if sample.rss > max * MB {
+                crossed.push((
+                    "resource-rss",
+                    format!(
+                        "RESOURCE pid {}: RSS {} MB above max {} MB",
+                        pid,
+                        sample.rss / MB,
+                        max
+                    ),
+                ));
+            }
+        }
+        if let Some(growth) = self.config.rss_growth {
+            let (at, low) = self
+                .samples
+                .iter()
+                .filter(|(_, s)| s.rss > 0)
+                .map(|(at, s)| (*at, s.rss))
+                .min_by_key(|(_, rss)| *rss)
+                .unwrap_or((now, sample.rss));
+            let factor = sample.rss as f64 / low.max(1) as f64;
+            if factor >= growth {
+                crossed.push((
+                    "resource-rss-growth",
+                    format!(
+                        "RESOURCE pid {}: RSS grew {:.1}x in {}s ({} MB -> {} MB)",
+                        pid,
+                        factor,
+                        now.duration_since(at).as_secs(),
+                        low / MB,
+                        sample.rss / MB
+                    ),
+                ));
+            }
+        }
+        if let Some(max) = self.config.max_cpu_pct {
+            // Judged once the samples span (nearly) the whole window
+            let (first_at, first) = self.samples[0];
+            let span = now.duration_since(first_at);
+            if span + self.config.interval() >= window && !span.is_zero() {
+                let pct =
+                    sample.cpu.saturating_sub(first.cpu).as_secs_f64() * 100.0 / span.as_secs_f64();
+                if pct > max {
+                    crossed.push((
+                        "resource-cpu",
+                        format!(
+                            "RESOURCE pid {}: CPU {:.0}% above max {:.0}% over {}s",
+                            pid,
+                            pct,
+                            max,
+                            span.as_secs()
+                        ),
+                    ));
+                }
+            }
+        }
+        if let (Some(max), Some(handles)) = (self.config.max_handles, sample.handles) {
+            if handles > max {
+                crossed.push((
+                    "resource-handles",
+                    format!(
+                        "RESOURCE pid {}: {} open handles above max {}",
+                        pid, handles, max
+                    ),
+                ));
+            }
+        }
+        if let Some(max) = self.config.max_children {
+            if sample.children > max {
+                crossed.push((
+                    "resource-children",
+                    format!(
+                        "RESOURCE pid {}: {} child processes above max {}",
+                        pid, sample.children, max
+                    ),
+                ));
+            }
+        }
+
+        let now_active: HashSet<&'static str> = crossed.iter().map(|(id, _)| *id).collect();
+        let hits = crossed
+            .into_iter()
+            .filter(|(id, _)| !self.active.contains(id))
+            .map(|(id, line)| ResourceHit { id, line })
+            .collect();
+        self.active = now_active;
+        hits
+    }
+}
+
+/// Read the target's counters from `/proc`
+#[cfg(target_os = "linux")]
+pub fn sample(pid: u32) -> std::io::Result<Sample> {
+    let dir = std::path::PathBuf::from(format!("/proc/{}", pid));
+    let stat = std::fs::read_to_string(dir.join("stat"))?;
+    let (_, ticks) = parse_stat(&stat).ok_or_else(|| invalid("stat"))?;
+    let status = std::fs::read_to_string(dir.join("status"))?;
+
+    Ok(Sample {
+        // USER_HZ is fixed at 100 in the /proc ABI
+        cpu: Duration::from_millis(ticks * 10),
+        rss: parse_rss(&status).unwrap_or(0),
+        handles: std::fs::read_dir(dir.join("fd"))
+            .ok()
+            .map(|fds| fds.count() as u64),
+        children: children(pid)?.len() as u64,
+    })
+}
+
+/// Direct child processes of `pid`
+#[cfg(target_os = "linux")]
+pub fn children(pid: u32) -> std::io::Result<Vec<u32>> {
+    let mut children = Vec::new();
+    for entry in std::fs::read_dir("/proc")?.flatten() {
+        let Ok(child) = entry.file_name().to_string_lossy().parse::<u32>() else {
+            continue;
+        };
+        // Processes may exit between listing and reading
+        let Ok(stat) = std::fs::read_to_string(entry.path().join("stat")) else {
+            continue;
+        };
+        if parse_stat(&stat).is_some_and(|(ppid, _)| ppid == pid) {
+            children.push(child);
+        }
+    }
+    Ok(children)
+}
+
+/// Parent PID and user + system ticks from `/proc/<pid>/stat`
+#[cfg(target_os = "linux")]
+fn parse_stat(stat: &str) -> Option<(u32, u64)> {
+    // The command name may contain spaces and parentheses: fields follow the last ')'
+    let fields: Vec<&str> = stat
+        .get(stat.rfind(')')? + 1..)?
+        .split_whitespace()
+        .collect();
+    let ppid = fields.get(1)?.parse().ok()?;
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+    Some((ppid, utime + stime))
+}
+
+/// `VmRSS` from `/proc/<pid>/status`, in bytes
+#[cfg(target_os = "linux")]
+fn parse_rss(status: &str) -> Option<u64> {
+    let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+#[cfg(target_os = "linux")]
+fn invalid(file: &str) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("unexpected /proc {} format", file),
+    )
+}
+
+/// Read the target's counters through the process and ToolHelp APIs
+#[cfg(windows)]
+pub fn sample(pid: u32) -> std::io::Result<Sample> {
+    use windows_sys::Win32::Foundation::{CloseHandle, FILETIME};
+    use windows_sys::Win32::System::ProcessStatus::{
+        K32GetProcessMemoryInfo, PROCESS_MEMORY_COUNTERS,
+    };
+    use windows_sys::Win32::System::Threading::{
+        GetProcessHandleCount, GetProcessTimes, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    let ticks = |t: FILETIME| (t.dwHighDateTime as u64) << 32 | t.dwLowDateTime as u64;
+
+    let process = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid) };
+    if process.is_null() {
+        return Err(std::io::Error::last_os_error());
+    }
+    let zero = FILETIME {
+        dwLowDateTime: 0,
+        dwHighDateTime: 0,
+    };
+    let (mut created, mut exited, mut kernel, mut user) = (zero, zero, zero, zero);
+    let mut memory: PROCESS_MEMORY_COUNTERS = unsafe { std::mem::zeroed() };
+    memory.cb = std::mem::size_of::<PROCESS_MEMORY_COUNTERS>() as u32;
+    let mut handles = 0u32;
+    let result = unsafe {
+        if GetProcessTimes(process, &mut created, &mut exited, &mut kernel, &mut user) == 0
+            || K32GetProcessMemoryInfo(process, &mut memory, memory.cb) == 0
+        {
+            Err(std::io::Error::last_os_error())
+        } else {
+            Ok(GetProcessHandleCount(process, &mut handles) != 0)
+        }
+    };
+    unsafe { CloseHandle(process) };
+    let handles_read = result?;
+
+    Ok(Sample {
+        // FILETIME durations count 100ns intervals
+        cpu: Duration::from_nanos((ticks(kernel) + ticks(user)) * 100),
+        rss: memory.WorkingSetSize as u64,
+        handles: handles_read.then_some(handles as u64),
+        children: children(pid)?.len() as u64,
+    })
+}
+
+/// Direct child processes of `pid`, from a ToolHelp snapshot
+#[cfg(windows)]
+pub fn children(pid: u32) -> std::io::Result<Vec<u32>> {
+    use windows_sys::Win32::Foundation::{CloseHandle, INVALID_HANDLE_VALUE};
+    use windows_sys::Win32::System::Diagnostics::ToolHelp::{
+        CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W,
+        TH32CS_SNAPPROCESS,
+    };
+
+    let snapshot = unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0) };
+    if snapshot == INVALID_HANDLE_VALUE {
+        return Err(std::io::Error::last_os_error());
+    }
+    let mut children = Vec::new();
+    let mut entry: PROCESSENTRY32W = unsafe { std::mem::zeroed() };
+    entry.dwSize = std::mem::size_of::<PROCESSENTRY32W>() as u32;
+    let mut more = unsafe { Process32FirstW(snapshot, &mut entry) } != 0;
+    while more {
+        if entry.th32ParentProcessID == pid && entry.th32ProcessID != pid {
+            children.push(entry.th32ProcessID);
+        }
+        more = unsafe { Process32NextW(snapshot, &mut entry) } != 0;
+    }
+    unsafe { CloseHandle(snapshot) };
+    Ok(children)
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+pub fn sample(_pid: u32) -> std::io::Result<Sample> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "resource sampling is supported on Linux and Windows",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(cpu_ms: u64, rss_mb: u64, handles: u64, children: u64) -> Sample {
+        Sample {
+            cpu: Duration::from_millis(cpu_ms),
+            rss: rss_mb * MB,
+            handles: Some(handles),
+            children,
+        }
+    }
+
+    #[test]
+    fn test_thresholds() {
+        let config: ResourceConfig = toml::from_str(
+            "interval_ms = 1000\nwindow_ms = 10000\nrss_growth = 10.0\nmax_rss_mb = 1000\n\
+             max_cpu_pct = 150\nmax_handles = 100\nmax_children = 5",
+        )
+        .unwrap();
+        config.validate().unwrap();
+        let mut monitor = ResourceMonitor::new(&config, 42);
+        let t0 = Instant::now();
+        let at = |s: u64| t0 + Duration::from_secs(s);
+
+        assert!(monitor.observe(reading(0, 40, 10, 0), at(0)).is_empty());
+        assert!(monitor.observe(reading(1000, 60, 10, 1), at(1)).is_empty());
+
+        // 40 MB -> 450 MB within the window; fires once
+        let hits = monitor.observe(reading(2000, 450, 10, 1), at(2));
+        assert_eq!(
+            hits,
+            vec![ResourceHit {
+                id: "resource-rss-growth",
+                line: "RESOURCE pid 42: RSS grew 11.2x in 2s (40 MB -> 450 MB)".to_string()
+            }]
+        );
+        assert!(monitor.observe(reading(3000, 460, 10, 1), at(3)).is_empty());
+
+        let hits = monitor.observe(reading(4000, 1200, 500, 9), at(4));
+        let ids: Vec<&str> = hits.iter().map(|h| h.id).collect();
+        assert_eq!(
+            ids,
+            ["resource-rss", "resource-handles", "resource-children"]
+        );
+        assert_eq!(
+            hits[2].line,
+            "RESOURCE pid 42: 9 child processes above max 5"
+        );
+
+        // The window ages out the 40 MB sample (growth clears) while two
+        // busy cores pull the window's average CPU over the limit
+        let mut hits = Vec::new();
+        for s in 5..=14 {
+            hits.extend(monitor.observe(reading(4000 + (s - 4) * 2000, 1200, 500, 9), at(s)));
+        }
+        assert_eq!(hits.len(), 1, "{:?}", hits);
+        assert_eq!(hits[0].id, "resource-cpu");
+        assert_eq!(
+            hits[0].line,
+            "RESOURCE pid 42: CPU 156% above max 150% over 9s"
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = |toml: &str| toml::from_str::<ResourceConfig>(toml).unwrap().validate();
+        assert!(config("").is_ok()); // disabled
+        assert!(config("interval_ms = 1000").is_err());
+        assert!(config("interval_ms = 1000\nmax_children = 5").is_ok());
+        assert!(config("interval_ms = 1000\nrss_growth = 0.5").is_err());
+        assert!(config("interval_ms = 5000\nwindow_ms = 1000\nmax_children = 5").is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_sample_self() {
+        let stat = "1234 (my (odd) bot) S 1 1234 1234 0 -1 4194560 100 0 0 0 250 50 0 0 20 0 1";
+        assert_eq!(parse_stat(stat), Some((1, 300)));
+        assert_eq!(parse_rss("Name:\tbot\nVmRSS:\t  2048 kB\n"), Some(2 * MB));
+
+        let own = sample(std::process::id()).unwrap();
+        assert!(own.rss > 0);
+        assert!(own.handles.is_some_and(|h| h > 0));
+        assert!(sample(u32::MAX / 2).is_err());
+    }
+}
@@ -0,0 +1,217 @@
+//! Restrict - Degrading the Agent's Capabilities Instead of Killing It
+//!
+//! A killed agent cannot be asked what it was doing. The `restrict` policy
+//! action keeps it running but takes away what made it dangerous, through
+//! one of two mechanisms configured in `[restrict]`:
+//!
+//! - `apparmor` - a tighter version of the AppArmor profile the agent
+//!   already runs under, loaded with `apparmor_parser --replace`: AppArmor
+//!   applies a replaced profile to running tasks at once (Linux)
+//! - `command` - a helper, e.g. one that re-execs the agent under a seccomp
+//!   filter or in a network namespace without routes; `{pid}`,
+//!   `{container}` and `{decision_id}` in its arguments are substituted
+//!
+//! ```toml
+//! [restrict]
+//! # Same profile name as the agent's, with network and writes denied
+//! apparmor = "/etc/apparmor.d/restricted/agent"
+//! timeout_ms = 5000
+//!
+//! [[policy.rule]]
+//! name = "restrict-exfil"
+//! when = 'decision.action == "KILL" && decision.rule == "exfil"'
+//! action = "restrict"
+//! ```
+//!
+//! The restriction is one-way: undoing it (reloading the original profile,
+//! restarting the agent) is left to the operator.
+
+use serde::Deserialize;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::process::Command;
+use tokio_util::task::TaskTracker;
+use tracing::{error, info};
+
+/// `[restrict]` table
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RestrictConfig {
+    /// Restricted AppArmor profile replacing the agent's
+    pub apparmor: Option<PathBuf>,
+    /// Helper run to restrict the agent (program and arguments)
+    pub command: Vec<String>,
+    /// Longest the restriction may take (milliseconds)
+    pub timeout_ms: u64,
+}
+
+impl Default for RestrictConfig {
+    fn default() -> Self {
+        Self {
+            apparmor: None,
+            command: Vec::new(),
+            timeout_ms: 5000,
+        }
+    }
+}
+
+impl RestrictConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.apparmor.is_some() && !self.command.is_empty() {
+            return Err("restrict: set one of apparmor or command".to_string());
+        }
+        if self.timeout_ms == 0 {
+            return Err("restrict: timeout_ms must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// What `restrict` applies
+#[derive(Debug, Clone)]
+enum Mechanism {
+    AppArmor(PathBuf),
+    Command(Vec<String>),
+}
+
+/// Applies `[restrict]` to RESTRICT targets
+pub struct Restrictor {
+    mechanism: Mechanism,
+    timeout: Duration,
+    tasks: TaskTracker,
+}
+
+impl Restrictor {
+    /// `None` unless `apparmor` or `command` is set
+    pub fn new(config: &RestrictConfig) -> Option<Self> {
+        let mechanism = match config.apparmor {
+            Some(ref profile) => Mechanism::AppArmor(profile.clone()),
+            None if !config.command.is_empty() => Mechanism::Command(config.command.clone()),
+            None => return None,
+        };
+        Some(Self {
+            mechanism,
+            timeout: Duration::from_millis(config.timeout_ms),
+            tasks: TaskTracker::new(),
+        })
+    }
+
+    /// What a RESTRICT does, for the startup log
+    pub fn describe(&self) -> String {
+        match self.mechanism {
+            Mechanism::AppArmor(ref profile) => format!("AppArmor profile {}", profile.display()),
+            Mechanism::Command(ref command) => format!("helper {}", command[0]),
+        }
+    }
+
+    /// Restrict the target of RESTRICT `decision_id` in the background
+    pub fn restrict(&self, decision_id: u64, pid: Option<u32>, container: Option<&str>) {
+        let mut command = match self.mechanism {
+            Mechanism::AppArmor(ref profile) => {
+                let mut command = Command::new("apparmor_parser");
+                command.arg("--replace").arg(profile);
+                command
+            }
+            Mechanism::Command(ref argv) => {
+                let args = substitute(&argv[1..], decision_id, pid, container);
+                let mut command = Command::new(&argv[0]);
+                command.args(args);
+                command
+            }
+        };
+        let what = self.describe();
+        let timeout = self.timeout;
+        self.tasks.spawn(async move {
+            let run = command.kill_on_drop(true).output();
+            let result = match tokio::time::timeout(timeout, run).await {
+                Ok(Ok(output)) if output.status.success() => Ok(()),
+                Ok(Ok(output)) => Err(io::Error::other(
+                    String::from_utf8_lossy(&output.stderr).trim().to_string(),
+                )),
+                Ok(Err(e)) => Err(e),
+                Err(_) => Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("timed out after {}ms", timeout.as_millis()),
+                )),
+            };
+            match result {
+                Ok(()) => info!("🪤 Restricted by {} (decision {})", what, decision_id),
+                Err(e) => error!("Failed to restrict by {}: {}", what, e),
+            }
+        });
+    }
+
+    /// Wait (up to `timeout`) for restrictions in progress; returns false
+    /// on timeout
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        self.tasks.close();
+        tokio::time::timeout(timeout, self.tasks.wait())
+            .await
+            .is_ok()
+    }
+}
+
+/// Helper arguments with `{pid}`, `{container}` and `{decision_id}` filled
+/// in (empty when there is no such target)
+fn substitute(
+    args: &[String],
+    decision_id: u64,
+    pid: Option<u32>,
+    container: Option<&str>,
+) -> Vec<String> {
+    let pid = pid.map(|pid| pid.to_string()).unwrap_or_default();
+    args.iter()
+        .map(|arg| {
+            arg.replace("{pid}", &pid)
+                .replace("{container}", container.unwrap_or_default())
+                .replace("{decision_id}", &decision_id.to_string())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config() {
+        let config: RestrictConfig = toml::from_str(
+            r#"command = ["/usr/local/bin/sandbox", "--no-net", "--pid={pid}", "{container}"]"#,
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+        let restrictor = Restrictor::new(&config).unwrap();
+        assert_eq!(restrictor.describe(), "helper /usr/local/bin/sandbox");
+        assert_eq!(
+            substitute(&config.command[1..], 7, Some(4242), None),
+            ["--no-net", "--pid=4242", ""]
+        );
+
+        assert!(Restrictor::new(&RestrictConfig::default()).is_none());
+        let both = RestrictConfig {
+            apparmor: Some(PathBuf::from("/etc/apparmor.d/restricted/agent")),
+            ..config
+        };
+        assert!(both.validate().is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_restrict() {
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("restricted");
+        let config = RestrictConfig {
+            command: vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                format!("echo {{decision_id}} {{pid}} > {}", marker.display()),
+            ],
+            ..Default::default()
+        };
+        let restrictor = Restrictor::new(&config).unwrap();
+        restrictor.restrict(9, Some(4242), None);
+        assert!(restrictor.drain(Duration::from_secs(5)).await);
+        assert_eq!(std::fs::read_to_string(&marker).unwrap(), "9 4242\n");
+    }
+}
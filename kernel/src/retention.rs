@@ -0,0 +1,451 @@
+//! Retention - Rotating and Pruning the Audit Trail
+//!
+//! With `[retention] keep`, the audit file is rotated every `rotate`
+//! (default one day): the trail checkpoints what is pending, writes a
+//! `rotate` event and renames the file to `<stem>.<YYYYMMDD-HHMMSS>.<ext>`
+//! (UTC rotation time) before starting a fresh one. Rotation alone
+//! (`rotate` without `keep`) keeps every segment.
+//!
+//! A background pruner removes segments rotated more than `keep` ago, or
+//! moves them to `archive_dir`. Each removal is recorded in the live file
+//! as a signed `prune` tombstone (signed like the shutdown footer): the
+//! segment's ID and time range, record count, file SHA-256, the Merkle root
+//! over its decision records and its last checkpoint root, plus the reason
+//! and what was done. With `[checkpoint]`, the tombstone is covered by a
+//! checkpoint right away, so the chain is re-anchored at every prune.
+//!
+//! ```toml
+//! [retention]
+//! keep = "90d"
+//! rotate = "1d"
+//! archive_dir = "/var/lib/tripwired/archive"
+//! ```
+
+use crate::audit::{AuditError, AuditTrail, PruneEvent};
+use crate::checkpoint::{leaf_hash, merkle_root, to_hex};
+use crate::learn::parse_duration;
+use crate::s3;
+use crate::schedule::{civil_from_days, days_from_civil};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+
+/// How often segments are checked for rotation and expiry
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Shortest rotation period
+const MIN_ROTATE: Duration = Duration::from_secs(60);
+
+/// `[retention]` table
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RetentionConfig {
+    /// How long rotated segments are kept (`90d`; none: forever)
+    #[serde(default)]
+    pub keep: Option<String>,
+    /// Segment length (default `1d` with `keep`)
+    #[serde(default)]
+    pub rotate: Option<String>,
+    /// Move expired segments here instead of deleting them
+    #[serde(default)]
+    pub archive_dir: Option<PathBuf>,
+    /// Keep expired segments until `[s3]` has uploaded them (set when
+    /// `[s3]` is configured)
+    #[serde(skip)]
+    pub hold_for_upload: bool,
+}
+
+impl RetentionConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(ref keep) = self.keep {
+            let keep = parse_duration(keep).map_err(|e| format!("retention: keep: {}", e))?;
+            if keep.is_zero() {
+                return Err("retention: keep must be greater than zero".to_string());
+            }
+        }
+        if let Some(ref rotate) = self.rotate {
+            let rotate = parse_duration(rotate).map_err(|e| format!("retention: rotate: {}", e))?;
+            if rotate < MIN_ROTATE {
+                return Err("retention: rotate must be at least 1m".to_string());
+            }
+        }
+        if self.archive_dir.is_some() && self.keep.is_none() {
+            return Err("retention: archive_dir needs keep".to_string());
+        }
+        Ok(())
+    }
+
+    /// The file is rotated at all
+    pub fn enabled(&self) -> bool {
+        self.rotate().is_some()
+    }
+
+    pub fn keep(&self) -> Option<Duration> {
+        self.keep.as_deref().and_then(|k| parse_duration(k).ok())
+    }
+
+    pub fn rotate(&self) -> Option<Duration> {
+        match self.rotate {
+            Some(ref rotate) => parse_duration(rotate).ok(),
+            None => self.keep.as_ref().map(|_| Duration::from_secs(86_400)),
+        }
+    }
+}
+
+/// Rotate and prune every `CHECK_INTERVAL` until shutdown
+pub async fn run(audit: Arc<AuditTrail>, config: RetentionConfig, shutdown: CancellationToken) {
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = shutdown.cancelled() => break,
+        }
+        let (audit, config) = (Arc::clone(&audit), config.clone());
+        // Hashing whole segments: off the runtime
+        match tokio::task::spawn_blocking(move || tick(&audit, &config, now_ms())).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => error!("Audit retention failed: {}", e),
+            Err(e) => error!("Audit retention task failed: {}", e),
+        }
+    }
+}
+
+/// Rotate the live file if it is due, then prune expired segments
+pub fn tick(audit: &AuditTrail, config: &RetentionConfig, now: u64) -> Result<(), AuditError> {
+    if let Some(rotate) = config.rotate() {
+        if now.saturating_sub(audit.segment_started()) >= rotate.as_millis() as u64 {
+            let segment = segment_path(audit.path(), now);
+            audit.rotate(&segment)?;
+            info!("🗂️  Audit trail rotated to {}", segment.display());
+        }
+    }
+    let (Some(keep), Some(label)) = (config.keep(), config.keep.as_deref()) else {
+        return Ok(());
+    };
+    for (rotated_ms, path) in segments(audit.path())? {
+        if config.hold_for_upload && !s3::uploaded(&path) {
+            continue;
+        }
+        if rotated_ms + keep.as_millis() as u64 <= now {
+            prune(audit, config, &path, &format!("retention {}", label))?;
+        }
+    }
+    Ok(())
+}
+
+/// Remove (or archive) one segment and record its tombstone
+fn prune(
+    audit: &AuditTrail,
+    config: &RetentionConfig,
+    path: &Path,
+    reason: &str,
+) -> Result<(), AuditError> {
+    let mut event = scan_segment(path)?;
+    event.reason = reason.to_string();
+    event.action = match config.archive_dir {
+        Some(ref dir) => {
+            std::fs::create_dir_all(dir)?;
+            let target = dir.join(&event.segment);
+            // A rename cannot cross filesystems
+            if std::fs::rename(path, &target).is_err() {
+                std::fs::copy(path, &target)?;
+                std::fs::remove_file(path)?;
+            }
+            format!("archived to {}", target.display())
+        }
+        None => {
+            std::fs::remove_file(path)?;
+            "deleted".to_string()
+        }
+    };
+    // The upload receipt (see `s3`) outlives nothing it described
+    if config.hold_for_upload {
+        std::fs::remove_file(s3::receipt_path(path))?;
+    }
+    audit.record_prune(&event)?;
+    info!(
+        "🗑️  Audit segment {} pruned ({} records): {}",
+        event.segment, event.records, event.action
+    );
+    Ok(())
+}
+
+/// Tombstone contents for a segment (reason and action left empty)
+pub fn scan_segment(path: &Path) -> std::io::Result<PruneEvent> {
+    let mut event = PruneEvent {
+        event: "prune".to_string(),
+        timestamp_ms: now_ms(),
+        segment: path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        reason: String::new(),
+        action: String::new(),
+        records: 0,
+        first_id: None,
+        last_id: None,
+        from_ms: None,
+        to_ms: None,
+        sha256: String::new(),
+        root: String::new(),
+        checkpoint: None,
+        signature: None,
+    };
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut hasher = Sha256::new();
+    let mut leaves = Vec::new();
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        hasher.update(&line);
+        let Ok(value) = serde_json::from_slice::<serde_json::Value>(&line) else {
+            continue;
+        };
+        match value.get("event").and_then(|e| e.as_str()) {
+            Some("checkpoint") => {
+                event.checkpoint = value
+                    .get("root")
+                    .and_then(|r| r.as_str())
+                    .map(str::to_string)
+            }
+            Some(_) => {}
+            None => {
+                let Some(id) = value.get("id").and_then(|i| i.as_u64()) else {
+                    continue;
+                };
+                event.records += 1;
+                event.first_id = Some(event.first_id.map_or(id, |f| f.min(id)));
+                event.last_id = Some(event.last_id.map_or(id, |l| l.max(id)));
+                if let Some(ms) = value.get("timestamp_ms").and_then(|t| t.as_u64()) {
+                    event.from_ms = Some(event.from_ms.map_or(ms, |f| f.min(ms)));
+                    event.to_ms = Some(event.to_ms.map_or(ms, |t| t.max(ms)));
+                }
+                leaves.push(leaf_hash(line.strip_suffix(b"\n").unwrap_or(&line)));
+            }
+        }
+    }
+    event.sha256 = to_hex(&hasher.finalize());
+    event.root = to_hex(&merkle_root(&leaves));
+    Ok(event)
+}
+
+/// `audit.jsonl` rotated at `ms` -> `audit.20261015-093000.jsonl`
+fn segment_path(path: &Path, ms: u64) -> PathBuf {
+    let secs = ms / 1000;
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let stamp = format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        secs % 86_400 / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    );
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{}.{}.{}", stem, stamp, ext.to_string_lossy()),
+        None => format!("{}.{}", stem, stamp),
+    };
+    path.with_file_name(name)
+}
+
+/// Rotation time (Unix ms) of a segment of `path`, from its name
+fn segment_time(path: &Path, name: &str) -> Option<u64> {
+    let stem = path.file_stem()?.to_string_lossy();
+    let stamp = name.strip_prefix(&*stem)?.strip_prefix('.')?;
+    let stamp = match path.extension() {
+        Some(ext) => stamp
+            .strip_suffix(&*ext.to_string_lossy())?
+            .strip_suffix('.')?,
+        None => stamp,
+    };
+    let (date, time) = stamp.split_once('-')?;
+    if date.len() != 8
+        || time.len() != 6
+        || !(date.to_owned() + time).bytes().all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+    let field = |s: &str| s.parse::<u32>().ok();
+    let days = days_from_civil(
+        i64::from(field(&date[..4])?),
+        field(&date[4..6])?,
+        field(&date[6..])?,
+    );
+    let secs = field(&time[..2])? * 3600 + field(&time[2..4])? * 60 + field(&time[4..])?;
+    u64::try_from(days)
+        .ok()
+        .map(|d| (d * 86_400 + u64::from(secs)) * 1000)
+}
+
+/// Rotated segments of `path`, oldest first
+pub fn segments(path: &Path) -> std::io::Result<Vec<(u64, PathBuf)>> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let mut segments = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if let Some(ms) = segment_time(path, &name.to_string_lossy()) {
+            segments.push((ms, entry.path()));
+        }
+    }
+    segments.sort();
+    Ok(segments)
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::ModelFingerprint;
+    use crate::llm::Sampling;
+    use crate::report::Report;
+
+    #[test]
+    fn test_retention_config() {
+        let config = RetentionConfig {
+            keep: Some("90d".to_string()),
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+        assert_eq!(config.rotate(), Some(Duration::from_secs(86_400)));
+
+        let bad = |keep: Option<&str>, rotate: Option<&str>, archive: bool| RetentionConfig {
+            keep: keep.map(str::to_string),
+            rotate: rotate.map(str::to_string),
+            archive_dir: archive.then(|| PathBuf::from("/archive")),
+            ..Default::default()
+        };
+        assert!(bad(Some("0d"), None, false).validate().is_err());
+        assert!(bad(Some("90"), None, false).validate().is_err());
+        assert!(bad(None, Some("30s"), false).validate().is_err());
+        assert!(bad(None, Some("1h"), true).validate().is_err());
+        assert!(bad(None, Some("1h"), false).validate().is_ok());
+        assert!(!RetentionConfig::default().enabled());
+    }
+
+    #[test]
+    fn test_segment_names() {
+        let path = Path::new("/var/log/tripwired-audit.jsonl");
+        // 2026-10-15 09:30:05 UTC
+        let ms = 1_792_056_605_000;
+        let segment = segment_path(path, ms);
+        assert_eq!(
+            segment,
+            Path::new("/var/log/tripwired-audit.20261015-093005.jsonl")
+        );
+        let name = segment.file_name().unwrap().to_string_lossy();
+        assert_eq!(segment_time(path, &name), Some(ms));
+        assert_eq!(segment_time(path, "tripwired-audit.jsonl"), None);
+        assert_eq!(segment_time(path, "tripwired-audit.2026-10-15.jsonl"), None);
+        assert_eq!(
+            segment_time(Path::new("audit"), "audit.20000229-000000"),
+            Some(951_782_400_000)
+        );
+    }
+
+    #[test]
+    fn test_rotate_and_prune() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let fp = ModelFingerprint::new("test-model", "http://localhost", 30, &Sampling::default());
+        let trail = AuditTrail::new(path.clone(), fp.clone(), "test prompt")
+            .unwrap()
+            .with_signing_key(b"secret".to_vec())
+            .with_checkpoints(2, None);
+        for n in 0..3 {
+            trail
+                .record(&format!("line {}", n), "SUSTAIN", 90, true, 1, None)
+                .unwrap();
+        }
+        let archive = dir.path().join("archive");
+        let config = RetentionConfig {
+            keep: Some("1d".to_string()),
+            rotate: None,
+            archive_dir: Some(archive.clone()),
+            ..Default::default()
+        };
+        let day = 86_400_000;
+        let start = trail.segment_started();
+
+        // Not due yet
+        tick(&trail, &config, start + 1000).unwrap();
+        assert!(segments(&path).unwrap().is_empty());
+
+        tick(&trail, &config, start + day).unwrap();
+        let rotated = segments(&path).unwrap();
+        assert_eq!(rotated.len(), 1);
+        let content = std::fs::read_to_string(&rotated[0].1).unwrap();
+        assert!(content
+            .lines()
+            .last()
+            .unwrap()
+            .contains("\"event\":\"rotate\""));
+        trail.record("line 3", "KILL", 99, false, 5, None).unwrap();
+
+        // The first segment expires; the second one does not
+        tick(&trail, &config, start + 2 * day + 1000).unwrap();
+        assert_eq!(segments(&path).unwrap().len(), 1);
+        let archived = archive.join(rotated[0].1.file_name().unwrap());
+        assert_eq!(std::fs::read_to_string(&archived).unwrap(), content);
+
+        let live = std::fs::read_to_string(&path).unwrap();
+        let line = live
+            .lines()
+            .find(|l| l.contains("\"event\":\"prune\""))
+            .unwrap();
+        let tombstone: PruneEvent = serde_json::from_str(line).unwrap();
+        assert_eq!((tombstone.first_id, tombstone.last_id), (Some(1), Some(3)));
+        assert_eq!(tombstone.records, 3);
+        assert_eq!(tombstone.reason, "retention 1d");
+        assert!(tombstone.action.starts_with("archived to "));
+        assert_eq!(
+            tombstone.sha256,
+            to_hex(&Sha256::digest(content.as_bytes()))
+        );
+        assert_eq!(tombstone.verify(Some(b"secret")), Some(true));
+        assert_eq!(tombstone.verify(Some(b"other")), Some(false));
+        // Re-anchored: the tombstone is the last leaf of a checkpoint
+        assert!(live
+            .lines()
+            .last()
+            .unwrap()
+            .contains("\"event\":\"checkpoint\""));
+
+        // The live file and the segment still verify on their own
+        let report = Report::read(&path, 10, Some(b"secret")).unwrap();
+        assert!(report.chain.verified);
+        assert_eq!(report.chain.tombstones_verified, 1);
+        assert_eq!(report.chain.checkpoints_failed, 0);
+        let report = Report::read(&archived, 10, Some(b"secret")).unwrap();
+        assert!(report.chain.verified);
+        assert_eq!(report.chain.end, "rotated");
+
+        // A restart continues the IDs from the live file alone
+        let restarted = AuditTrail::new(path.clone(), fp, "test prompt").unwrap();
+        assert_eq!(
+            restarted
+                .record("line 4", "SUSTAIN", 90, true, 1, None)
+                .unwrap(),
+            5
+        );
+    }
+}
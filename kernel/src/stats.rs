@@ -0,0 +1,787 @@
+//! Kernel Statistics - Runtime Counters and Prometheus Rendering
+//!
+//! Counters are updated by the pipeline; snapshots feed the admin API
+//! and the audit shutdown footer.
+
+use crate::agents::AgentStatus;
+use crate::backup::BackupStatus;
+use crate::filter::{ExcludeStat, Filter, RuleStat};
+use crate::ha::Role;
+use crate::shadow::ShadowStats;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Pipeline counters
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Stats {
+    pub filtered: u64,
+    pub analyzed: u64,
+    pub kills: u64,
+    /// KILL decisions that did not signal the target (cooldown / hourly cap)
+    pub kills_suppressed: u64,
+    /// Kills decided by a `kill` rule without asking the LLM
+    pub fast_path_kills: u64,
+    /// Escalated lines decided by the classifier without the LLM
+    pub classified: u64,
+    /// Targets suspended by a `pause` policy rule
+    pub pauses: u64,
+    /// Target cgroups frozen or clamped by a policy rule
+    pub clamps: u64,
+    /// Targets restricted by a `restrict` policy rule
+    pub restrictions: u64,
+    /// Verdicts changed by a policy rule
+    pub policy_overrides: u64,
+    /// LLM answers without a readable verdict (FAIL, see `--on-uncertain`)
+    pub uncertain: u64,
+    /// Lines whose judgment panicked (`PIPELINE_ERROR`)
+    pub pipeline_errors: u64,
+    /// Completed multi-line sequences
+    pub sequences: u64,
+    /// Rate / ratio thresholds crossed
+    pub rate_triggers: u64,
+    /// Numeric hard limits breached (killed on the fast path)
+    pub limit_breaches: u64,
+    /// Numeric rate-of-change limits crossed
+    pub limit_changes: u64,
+    /// `--target-pid` resource thresholds crossed (`[resource]`)
+    pub resource_triggers: u64,
+    /// `--target-pid` new destinations and volume thresholds (`[egress]`)
+    pub egress_triggers: u64,
+    /// Changes below `[fs]` sensitive paths
+    pub fs_triggers: u64,
+    /// Credential store / clipboard events of `--target-pid` (`[credential]`)
+    pub credential_triggers: u64,
+    /// Decoy accesses (`[[honeypot]]`), each a fast-path KILL
+    pub honeypot_trips: u64,
+    /// Lines cut off at `--max-line-bytes`
+    pub lines_truncated: u64,
+    /// Framed payloads rejected as invalid UTF-8
+    pub lines_rejected: u64,
+    /// TCP connections closed for a missing or wrong `HELLO token=`
+    pub auth_failures: u64,
+    /// Records assembled from several lines (`[multiline]`)
+    pub multiline_records: u64,
+    /// Acks dropped because the agent was not reading them (`--ack`)
+    pub acks_dropped: u64,
+    /// `CHECK` requests the guard allowed / denied
+    pub checks_allowed: u64,
+    pub checks_denied: u64,
+    /// Lines skipped by flow control (`sample` / `escalate`)
+    pub lines_dropped: u64,
+    /// Lines delayed by flow control (`queue`)
+    pub lines_throttled: u64,
+    /// Agents that went over their input limit (`escalate`)
+    pub floods: u64,
+    /// LLM decisions answered by a fallback model
+    pub llm_fallbacks: u64,
+    /// Escalations decided by the degraded-mode policy (LLM unhealthy or failing)
+    pub degraded: u64,
+    /// LLM decisions made in a batched request
+    pub batched: u64,
+    /// Lines from the LLM's blind window re-analyzed after recovery
+    pub backfilled: u64,
+    /// Backfilled verdicts that would have been KILL
+    pub backfill_kills: u64,
+    /// LLM analyses abandoned at the `[slo]` deadline (decided by `on_miss`)
+    pub deadline_misses: u64,
+    /// LLM verdicts that arrived after the deadline (not enforced)
+    pub late_verdicts: u64,
+    /// LLM analyses of `priority = "high"` lines
+    pub priority_lines: u64,
+    /// High-lane analyses abandoned at `[priority] timeout_ms`
+    pub priority_timeouts: u64,
+    /// Lines replayed from the persisted queue after a restart
+    pub recovered: u64,
+    pub total_latency_ms: u64,
+    /// Failures by category (`llm.timeout`, `audit.io`, ...)
+    pub errors: BTreeMap<String, u64>,
+}
+
+impl Stats {
+    /// Count a failure of `category`
+    pub fn error(&mut self, category: &str) {
+        *self.errors.entry(category.to_string()).or_default() += 1;
+    }
+}
+
+/// Point-in-time view of counters plus per-rule match counts
+#[derive(Debug, Clone, Serialize)]
+pub struct StatsSnapshot {
+    #[serde(flatten)]
+    pub counters: Stats,
+    /// Last canary probe passed
+    pub llm_healthy: bool,
+    /// Kill actions may signal the target
+    pub armed: bool,
+    /// End of an operator hold (Unix ms; `u64::MAX` = until re-armed)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disarmed_until_ms: Option<u64>,
+    /// Active schedule profile
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    /// The active profile audits kill actions without executing them
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub dry_run: bool,
+    /// HA instance id
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    /// HA role: only the leader serves agents and may kill
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<Role>,
+    /// Analyses waiting on the LLM
+    pub llm_pending: usize,
+    /// Shadow analyzer agreement with the live verdicts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shadow: Option<ShadowStats>,
+    pub rules: Vec<RuleStat>,
+    pub excludes: Vec<ExcludeStat>,
+    /// Connected agents, with their correlation IDs (see `agents`)
+    pub agents: Vec<AgentStatus>,
+    /// Backup audit sink health (`--audit-backup`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audit_backup: Option<BackupStatus>,
+}
+
+impl StatsSnapshot {
+    pub fn new(counters: &Stats, filter: &Filter) -> Self {
+        Self {
+            counters: counters.clone(),
+            llm_healthy: false,
+            armed: false,
+            disarmed_until_ms: None,
+            profile: None,
+            dry_run: false,
+            instance: None,
+            role: None,
+            llm_pending: 0,
+            shadow: None,
+            rules: filter.rule_stats(),
+            excludes: filter.exclude_stats(),
+            agents: Vec::new(),
+            audit_backup: None,
+        }
+    }
+
+    pub fn with_health(mut self, llm_healthy: bool, armed: bool) -> Self {
+        self.llm_healthy = llm_healthy;
+        self.armed = armed;
+        self
+    }
+
+    pub fn with_hold(mut self, disarmed_until_ms: Option<u64>) -> Self {
+        self.disarmed_until_ms = disarmed_until_ms;
+        self
+    }
+
+    pub fn with_profile(mut self, profile: Option<&str>, dry_run: bool) -> Self {
+        self.profile = profile.map(str::to_string);
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn with_ha(mut self, instance: &str, role: Role) -> Self {
+        self.instance = Some(instance.to_string());
+        self.role = Some(role);
+        self
+    }
+
+    pub fn with_llm_pending(mut self, pending: usize) -> Self {
+        self.llm_pending = pending;
+        self
+    }
+
+    pub fn with_shadow(mut self, shadow: Option<ShadowStats>) -> Self {
+        self.shadow = shadow;
+        self
+    }
+
+    pub fn with_agents(mut self, agents: Vec<AgentStatus>) -> Self {
+        self.agents = agents;
+        self
+    }
+
+    pub fn with_audit_backup(mut self, backup: Option<BackupStatus>) -> Self {
+        self.audit_backup = backup;
+        self
+    }
+
+    /// Add failures counted outside the pipeline (audit trail writes)
+    pub fn with_errors(mut self, errors: BTreeMap<String, u64>) -> Self {
+        for (category, n) in errors {
+            *self.counters.errors.entry(category).or_default() += n;
+        }
+        self
+    }
+
+    /// Prometheus text exposition format
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        let c = &self.counters;
+
+        counter(
+            &mut out,
+            "tripwired_lines_filtered_total",
+            "Lines passed by the pre-filter without LLM analysis",
+            c.filtered,
+        );
+        counter(
+            &mut out,
+            "tripwired_lines_analyzed_total",
+            "Lines analyzed by the LLM",
+            c.analyzed,
+        );
+        counter(&mut out, "tripwired_kills_total", "KILL decisions", c.kills);
+        counter(
+            &mut out,
+            "tripwired_kills_suppressed_total",
+            "KILL decisions suppressed by the kill cooldown or hourly cap",
+            c.kills_suppressed,
+        );
+        counter(
+            &mut out,
+            "tripwired_fast_path_kills_total",
+            "KILL decisions made by kill rules without the LLM",
+            c.fast_path_kills,
+        );
+        counter(
+            &mut out,
+            "tripwired_classified_total",
+            "Escalated lines decided by the classifier without the LLM",
+            c.classified,
+        );
+        counter(
+            &mut out,
+            "tripwired_pauses_total",
+            "Targets suspended by a pause policy rule",
+            c.pauses,
+        );
+        counter(
+            &mut out,
+            "tripwired_clamps_total",
+            "Target cgroups frozen or clamped by a policy rule",
+            c.clamps,
+        );
+        counter(
+            &mut out,
+            "tripwired_restrictions_total",
+            "Targets restricted by a restrict policy rule",
+            c.restrictions,
+        );
+        counter(
+            &mut out,
+            "tripwired_policy_overrides_total",
+            "Verdicts changed by a decision policy rule",
+            c.policy_overrides,
+        );
+        counter(
+            &mut out,
+            "tripwired_uncertain_total",
+            "LLM answers without a readable verdict (FAIL)",
+            c.uncertain,
+        );
+        counter(
+            &mut out,
+            "tripwired_pipeline_errors_total",
+            "Lines whose judgment panicked and was contained",
+            c.pipeline_errors,
+        );
+        counter(
+            &mut out,
+            "tripwired_sequences_total",
+            "Completed multi-line sequence rules",
+            c.sequences,
+        );
+        counter(
+            &mut out,
+            "tripwired_rate_triggers_total",
+            "Rate and ratio rule thresholds crossed",
+            c.rate_triggers,
+        );
+        counter(
+            &mut out,
+            "tripwired_limit_breaches_total",
+            "Numeric hard limits breached",
+            c.limit_breaches,
+        );
+        counter(
+            &mut out,
+            "tripwired_limit_changes_total",
+            "Numeric rate-of-change limits crossed",
+            c.limit_changes,
+        );
+        counter(
+            &mut out,
+            "tripwired_resource_triggers_total",
+            "Target process resource thresholds crossed",
+            c.resource_triggers,
+        );
+        counter(
+            &mut out,
+            "tripwired_egress_triggers_total",
+            "Target process new network destinations and volume thresholds crossed",
+            c.egress_triggers,
+        );
+        counter(
+            &mut out,
+            "tripwired_fs_triggers_total",
+            "Changes below sensitive file-system paths",
+            c.fs_triggers,
+        );
+        counter(
+            &mut out,
+            "tripwired_credential_triggers_total",
+            "Target process credential store and clipboard accesses",
+            c.credential_triggers,
+        );
+        counter(
+            &mut out,
+            "tripwired_honeypot_trips_total",
+            "Honeypot decoy accesses",
+            c.honeypot_trips,
+        );
+        counter(
+            &mut out,
+            "tripwired_lines_truncated_total",
+            "Lines cut off at the maximum line length",
+            c.lines_truncated,
+        );
+        counter(
+            &mut out,
+            "tripwired_lines_rejected_total",
+            "Framed payloads rejected as invalid UTF-8",
+            c.lines_rejected,
+        );
+        counter(
+            &mut out,
+            "tripwired_agent_auth_failures_total",
+            "TCP connections closed for a missing or wrong agent token",
+            c.auth_failures,
+        );
+        counter(
+            &mut out,
+            "tripwired_multiline_records_total",
+            "Records assembled from several lines",
+            c.multiline_records,
+        );
+        counter(
+            &mut out,
+            "tripwired_acks_dropped_total",
+            "Acks dropped because the agent was not reading them",
+            c.acks_dropped,
+        );
+        counter(
+            &mut out,
+            "tripwired_checks_allowed_total",
+            "CHECK requests the guard allowed",
+            c.checks_allowed,
+        );
+        counter(
+            &mut out,
+            "tripwired_checks_denied_total",
+            "CHECK requests the guard denied",
+            c.checks_denied,
+        );
+        counter(
+            &mut out,
+            "tripwired_lines_dropped_total",
+            "Lines skipped by flow control",
+            c.lines_dropped,
+        );
+        counter(
+            &mut out,
+            "tripwired_lines_throttled_total",
+            "Lines delayed by flow control",
+            c.lines_throttled,
+        );
+        counter(
+            &mut out,
+            "tripwired_floods_total",
+            "Agents escalated for exceeding their input limit",
+            c.floods,
+        );
+        counter(
+            &mut out,
+            "tripwired_llm_fallbacks_total",
+            "LLM decisions answered by a fallback model",
+            c.llm_fallbacks,
+        );
+        counter(
+            &mut out,
+            "tripwired_degraded_total",
+            "Escalations decided by the degraded-mode policy",
+            c.degraded,
+        );
+        counter(
+            &mut out,
+            "tripwired_batched_total",
+            "LLM decisions made in a batched request",
+            c.batched,
+        );
+        counter(
+            &mut out,
+            "tripwired_backfilled_total",
+            "Lines from the LLM's blind window re-analyzed after recovery",
+            c.backfilled,
+        );
+        counter(
+            &mut out,
+            "tripwired_backfill_kills_total",
+            "Backfilled verdicts that would have been KILL",
+            c.backfill_kills,
+        );
+        counter(
+            &mut out,
+            "tripwired_deadline_misses_total",
+            "LLM analyses abandoned at the decision deadline",
+            c.deadline_misses,
+        );
+        counter(
+            &mut out,
+            "tripwired_late_verdicts_total",
+            "LLM verdicts that arrived after the decision deadline",
+            c.late_verdicts,
+        );
+        counter(
+            &mut out,
+            "tripwired_priority_lines_total",
+            "LLM analyses of high-priority lines",
+            c.priority_lines,
+        );
+        counter(
+            &mut out,
+            "tripwired_priority_timeouts_total",
+            "High-priority analyses abandoned at the priority timeout",
+            c.priority_timeouts,
+        );
+        counter(
+            &mut out,
+            "tripwired_recovered_total",
+            "Lines replayed from the persisted queue after a restart",
+            c.recovered,
+        );
+        counter(
+            &mut out,
+            "tripwired_llm_latency_ms_total",
+            "Cumulative LLM analysis latency in milliseconds",
+            c.total_latency_ms,
+        );
+        gauge(
+            &mut out,
+            "tripwired_llm_healthy",
+            "1 if the last LLM canary probe passed",
+            self.llm_healthy as u64,
+        );
+        gauge(
+            &mut out,
+            "tripwired_armed",
+            "1 if kill actions may signal the target",
+            self.armed as u64,
+        );
+        if let Some(role) = self.role {
+            gauge(
+                &mut out,
+                "tripwired_ha_leader",
+                "1 if this instance is the HA leader",
+                (role == Role::Leader) as u64,
+            );
+        }
+        gauge(
+            &mut out,
+            "tripwired_llm_pending",
+            "Analyses waiting on the LLM",
+            self.llm_pending as u64,
+        );
+        if let Some(ref shadow) = self.shadow {
+            counter(
+                &mut out,
+                "tripwired_shadow_agreed_total",
+                "Shadow verdicts matching the live LLM verdict",
+                shadow.agreed,
+            );
+            counter(
+                &mut out,
+                "tripwired_shadow_disagreed_total",
+                "Shadow verdicts differing from the live LLM verdict",
+                shadow.disagreed,
+            );
+            counter(
+                &mut out,
+                "tripwired_shadow_errors_total",
+                "Shadow analyses that failed",
+                shadow.errors,
+            );
+            counter(
+                &mut out,
+                "tripwired_shadow_skipped_total",
+                "Lines not shadowed because the in-flight cap was reached",
+                shadow.skipped,
+            );
+        }
+        if let Some(ref backup) = self.audit_backup {
+            counter(
+                &mut out,
+                "tripwired_audit_backup_bytes_total",
+                "Bytes written to the backup audit sink",
+                backup.bytes,
+            );
+            counter(
+                &mut out,
+                "tripwired_audit_backup_missed_bytes_total",
+                "Audit bytes written to the primary trail but not the backup",
+                backup.missed_bytes,
+            );
+            counter(
+                &mut out,
+                "tripwired_audit_backup_failures_total",
+                "Failed backup audit sink writes, opens and renames",
+                backup.failures,
+            );
+            gauge(
+                &mut out,
+                "tripwired_audit_backup_diverged",
+                "1 if the backup audit file is missing bytes of the current segment",
+                backup.diverged as u64,
+            );
+        }
+
+        header(
+            &mut out,
+            "tripwired_errors_total",
+            "Failures by category",
+            "counter",
+        );
+        for (category, n) in &c.errors {
+            let _ = writeln!(
+                out,
+                "tripwired_errors_total{{category=\"{}\"}} {}",
+                escape(category),
+                n
+            );
+        }
+
+        header(
+            &mut out,
+            "tripwired_rule_matches_total",
+            "Filter rule matches",
+            "counter",
+        );
+        for r in &self.rules {
+            let tier = serde_json::to_value(r.tier).unwrap_or_default();
+            let _ = writeln!(
+                out,
+                "tripwired_rule_matches_total{{rule=\"{}\",tier=\"{}\"}} {}",
+                escape(&r.id),
+                tier.as_str().unwrap_or_default(),
+                r.matches
+            );
+        }
+
+        header(
+            &mut out,
+            "tripwired_rule_evaluations_total",
+            "Lines evaluated against user patterns",
+            "counter",
+        );
+        for r in &self.rules {
+            if let Some(evals) = r.evals {
+                let _ = writeln!(
+                    out,
+                    "tripwired_rule_evaluations_total{{rule=\"{}\"}} {}",
+                    escape(&r.id),
+                    evals
+                );
+            }
+        }
+
+        header(
+            &mut out,
+            "tripwired_rule_eval_seconds_total",
+            "Time spent evaluating user patterns",
+            "counter",
+        );
+        for r in &self.rules {
+            if let Some(eval_us) = r.eval_us {
+                let _ = writeln!(
+                    out,
+                    "tripwired_rule_eval_seconds_total{{rule=\"{}\"}} {:.6}",
+                    escape(&r.id),
+                    eval_us as f64 / 1e6
+                );
+            }
+        }
+
+        header(
+            &mut out,
+            "tripwired_exclude_matches_total",
+            "Lines whitelisted by exclude patterns",
+            "counter",
+        );
+        for e in &self.excludes {
+            let _ = writeln!(
+                out,
+                "tripwired_exclude_matches_total{{exclude=\"{}\"}} {}",
+                e.id, e.matches
+            );
+        }
+
+        // One series per connected agent: gone once it disconnects
+        agent_counter(
+            &mut out,
+            "tripwired_agent_lines_total",
+            "Lines received per agent connection",
+            &self.agents,
+            |a| a.lines,
+        );
+        agent_counter(
+            &mut out,
+            "tripwired_agent_escalations_total",
+            "Lines escalated to a rule or the LLM per agent connection",
+            &self.agents,
+            |a| a.escalations,
+        );
+        agent_counter(
+            &mut out,
+            "tripwired_agent_kills_total",
+            "KILL verdicts per agent connection",
+            &self.agents,
+            |a| a.kills,
+        );
+
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn counter(out: &mut String, name: &str, help: &str, value: u64) {
+    header(out, name, help, "counter");
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: u64) {
+    header(out, name, help, "gauge");
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+/// A counter labeled with each agent's correlation IDs
+fn agent_counter(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    agents: &[AgentStatus],
+    value: impl Fn(&AgentStatus) -> u64,
+) {
+    header(out, name, help, "counter");
+    for a in agents {
+        let _ = writeln!(
+            out,
+            "{}{{agent_id=\"{}\",session_id=\"{}\",connection_id=\"{}\"}} {}",
+            name,
+            escape(&a.agent_id),
+            escape(&a.session_id),
+            a.id,
+            value(a)
+        );
+    }
+}
+
+/// Escape a Prometheus label value
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::FilterConfig;
+
+    #[test]
+    fn test_prometheus_rendering() {
+        let filter = Filter::new(&FilterConfig::default()).unwrap();
+        filter.check("sudo reboot");
+
+        let mut counters = Stats {
+            filtered: 7,
+            kills: 1,
+            ..Default::default()
+        };
+        counters.error("llm.timeout");
+        counters.error("llm.timeout");
+        let agent = AgentStatus {
+            id: 3,
+            agent_id: "trader-7".to_string(),
+            session_id: "run-1".to_string(),
+            lines: 12,
+            kills: 1,
+            ..Default::default()
+        };
+        let text = StatsSnapshot::new(&counters, &filter)
+            .with_health(true, false)
+            .with_errors(BTreeMap::from([("audit.io".to_string(), 1)]))
+            .with_agents(vec![agent])
+            .to_prometheus();
+
+        assert!(text.contains("tripwired_lines_filtered_total 7\n"));
+        assert!(text.contains("tripwired_kills_total 1\n"));
+        assert!(text.contains("# TYPE tripwired_llm_healthy gauge\ntripwired_llm_healthy 1\n"));
+        assert!(text.contains("tripwired_armed 0\n"));
+        assert!(text.contains("tripwired_errors_total{category=\"llm.timeout\"} 2\n"));
+        assert!(text.contains("tripwired_errors_total{category=\"audit.io\"} 1\n"));
+        assert!(text.contains(
+            "tripwired_agent_lines_total{agent_id=\"trader-7\",session_id=\"run-1\",connection_id=\"3\"} 12\n"
+        ));
+        assert!(text.contains("tripwired_agent_kills_total{agent_id=\"trader-7\""));
+        assert!(text
+            .contains("tripwired_rule_matches_total{rule=\"essential#6\",tier=\"essential\"} 1\n"));
+        assert!(
+            text.contains("tripwired_rule_matches_total{rule=\"trading#0\",tier=\"domain\"} 0\n")
+        );
+        assert!(!text.contains("tripwired_shadow_"));
+        assert!(!text.contains("tripwired_rule_evaluations_total{"));
+
+        let config: FilterConfig = toml::from_str("patterns = ['(?i)invoice.*void']").unwrap();
+        let filter = Filter::new(&config).unwrap();
+        filter.check("invoice 7 void");
+        let text = StatsSnapshot::new(&counters, &filter).to_prometheus();
+        assert!(text.contains("tripwired_rule_evaluations_total{rule=\"custom#0\"} 1\n"));
+        assert!(text.contains("tripwired_rule_eval_seconds_total{rule=\"custom#0\"} 0."));
+
+        let shadow = ShadowStats {
+            agreed: 9,
+            disagreed: 1,
+            ..Default::default()
+        };
+        let text = StatsSnapshot::new(&counters, &filter)
+            .with_shadow(Some(shadow))
+            .to_prometheus();
+        assert!(text.contains("tripwired_shadow_agreed_total 9\n"));
+        assert!(text.contains("tripwired_shadow_disagreed_total 1\n"));
+
+        let backup = BackupStatus {
+            missed_bytes: 120,
+            diverged: true,
+            ..Default::default()
+        };
+        let text = StatsSnapshot::new(&counters, &filter)
+            .with_audit_backup(Some(backup))
+            .to_prometheus();
+        assert!(text.contains("tripwired_audit_backup_missed_bytes_total 120\n"));
+        assert!(text.contains("tripwired_audit_backup_diverged 1\n"));
+    }
+
+    #[test]
+    fn test_snapshot_json_is_flat() {
+        let filter = Filter::new(&FilterConfig::default()).unwrap();
+        let json = serde_json::to_value(StatsSnapshot::new(&Stats::default(), &filter)).unwrap();
+        assert_eq!(json["filtered"], 0);
+        assert_eq!(json["llm_healthy"], false);
+        assert!(json["rules"].as_array().unwrap().len() > 20);
+    }
+}
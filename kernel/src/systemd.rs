@@ -0,0 +1,145 @@
+//! systemd Integration - sd_notify, Socket Activation, Watchdog
+//!
+//! Native implementation of the sd_notify protocol (no libsystemd dependency).
+//! Every function is a silent no-op when the kernel is not started by systemd,
+//! so the same binary runs unchanged from a shell.
+
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::FromRawFd;
+use std::os::unix::net::{UnixDatagram, UnixListener};
+use std::time::Duration;
+
+/// First descriptor passed by socket activation (SD_LISTEN_FDS_START)
+const LISTEN_FDS_START: i32 = 3;
+
+/// Send a state string (e.g. "READY=1") to the service manager
+///
+/// Returns `Ok(false)` when `$NOTIFY_SOCKET` is not set.
+pub fn notify(state: &str) -> std::io::Result<bool> {
+    match std::env::var_os("NOTIFY_SOCKET") {
+        Some(path) => send_to(&path, state).map(|_| true),
+        None => Ok(false),
+    }
+}
+
+/// Signal readiness (Type=notify)
+pub fn notify_ready(status: &str) -> std::io::Result<bool> {
+    notify(&format!("READY=1\nSTATUS={}", status))
+}
+
+/// Signal that shutdown has begun
+pub fn notify_stopping() -> std::io::Result<bool> {
+    notify("STOPPING=1")
+}
+
+/// Keepalive ping for WatchdogSec=
+pub fn notify_watchdog() -> std::io::Result<bool> {
+    notify("WATCHDOG=1")
+}
+
+/// Watchdog ping interval (half of WatchdogSec=), if the watchdog is enabled for us
+pub fn watchdog_interval() -> Option<Duration> {
+    let usec = std::env::var("WATCHDOG_USEC").ok();
+    let pid = std::env::var("WATCHDOG_PID").ok();
+    parse_watchdog(usec.as_deref(), pid.as_deref(), std::process::id())
+}
+
+/// Take the socket-activated Unix listener passed by systemd (ListenStream=)
+///
+/// Clears the LISTEN_* variables so child processes don't inherit them.
+/// Call it from `main` before the async runtime starts: removing environment
+/// variables is only sound while no other thread can read them.
+pub fn take_listener() -> std::io::Result<Option<UnixListener>> {
+    let pid = std::env::var("LISTEN_PID").ok();
+    let fds = std::env::var("LISTEN_FDS").ok();
+    let count = parse_listen_fds(pid.as_deref(), fds.as_deref(), std::process::id());
+
+    std::env::remove_var("LISTEN_PID");
+    std::env::remove_var("LISTEN_FDS");
+    std::env::remove_var("LISTEN_FDNAMES");
+
+    if count == 0 {
+        return Ok(None);
+    }
+
+    // SAFETY: systemd guarantees descriptors LISTEN_FDS_START.. are open and
+    // owned by this process when LISTEN_PID matches our PID.
+    let listener = unsafe { UnixListener::from_raw_fd(LISTEN_FDS_START) };
+    listener.set_nonblocking(true)?;
+    Ok(Some(listener))
+}
+
+fn send_to(path: &OsStr, state: &str) -> std::io::Result<()> {
+    let socket = UnixDatagram::unbound()?;
+
+    // '@' prefix = Linux abstract namespace socket
+    if let Some(name) = path.as_bytes().strip_prefix(b"@") {
+        use std::os::linux::net::SocketAddrExt;
+        let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+        socket.send_to_addr(state.as_bytes(), &addr)?;
+    } else {
+        socket.send_to(state.as_bytes(), path)?;
+    }
+    Ok(())
+}
+
+fn parse_watchdog(usec: Option<&str>, pid: Option<&str>, own_pid: u32) -> Option<Duration> {
+    // WATCHDOG_PID is optional; when present it must be us
+    if let Some(pid) = pid {
+        if pid.parse::<u32>().ok() != Some(own_pid) {
+            return None;
+        }
+    }
+    let usec = usec?.parse::<u64>().ok().filter(|&u| u > 0)?;
+    Some(Duration::from_micros(usec / 2))
+}
+
+fn parse_listen_fds(pid: Option<&str>, fds: Option<&str>, own_pid: u32) -> u32 {
+    match (pid.and_then(|p| p.parse::<u32>().ok()), fds) {
+        (Some(p), Some(n)) if p == own_pid => n.parse().unwrap_or(0),
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_watchdog_interval() {
+        assert_eq!(
+            parse_watchdog(Some("10000000"), None, 42),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(
+            parse_watchdog(Some("10000000"), Some("42"), 42),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(parse_watchdog(Some("10000000"), Some("7"), 42), None); // not us
+        assert_eq!(parse_watchdog(Some("0"), None, 42), None);
+        assert_eq!(parse_watchdog(None, None, 42), None);
+    }
+
+    #[test]
+    fn test_listen_fds() {
+        assert_eq!(parse_listen_fds(Some("42"), Some("1"), 42), 1);
+        assert_eq!(parse_listen_fds(Some("7"), Some("1"), 42), 0); // not us
+        assert_eq!(parse_listen_fds(None, Some("1"), 42), 0);
+        assert_eq!(parse_listen_fds(Some("42"), Some("junk"), 42), 0);
+    }
+
+    #[test]
+    fn test_send_to_notify_socket() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("notify.sock");
+        let server = UnixDatagram::bind(&path).unwrap();
+
+        send_to(path.as_os_str(), "READY=1").unwrap();
+
+        let mut buf = [0u8; 64];
+        let n = server.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"READY=1");
+    }
+}
@@ -0,0 +1,429 @@
+//! Live Dashboard - `tripwired top`
+//!
+//! Terminal UI for a running kernel, polling its admin API
+//! (`--admin-port`): pipeline counters, LLM health and arming, an LLM
+//! latency sparkline, connected agents and a rolling decision feed.
+//!
+//! Keys: `q` / Esc quits, `f` toggles pre-filtered SUSTAINs in the feed.
+
+use crate::agents::AgentStatus;
+use crate::audit::DecisionRecord;
+use crate::error::KernelError;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, Paragraph, Row, Sparkline, Table};
+use ratatui::Frame;
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Decisions kept in the feed
+const FEED_LEN: usize = 500;
+
+/// LLM latencies kept for the sparkline
+const LATENCY_LEN: usize = 120;
+
+/// The `/stats` fields the dashboard shows
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Counters {
+    filtered: u64,
+    analyzed: u64,
+    kills: u64,
+    fast_path_kills: u64,
+    sequences: u64,
+    rate_triggers: u64,
+    llm_fallbacks: u64,
+    degraded: u64,
+    batched: u64,
+    total_latency_ms: u64,
+    llm_healthy: bool,
+    armed: bool,
+    disarmed_until_ms: Option<u64>,
+    llm_pending: usize,
+}
+
+/// Everything on screen, refreshed each poll
+#[derive(Default)]
+struct Dashboard {
+    url: String,
+    counters: Counters,
+    agents: Vec<AgentStatus>,
+    /// Newest last
+    feed: VecDeque<DecisionRecord>,
+    /// LLM latencies (ms), newest last
+    latencies: VecDeque<u64>,
+    last_id: u64,
+    show_filtered: bool,
+    /// Last poll failure, shown until a poll succeeds
+    error: Option<String>,
+}
+
+impl Dashboard {
+    fn absorb(&mut self, records: Vec<DecisionRecord>) {
+        for record in records {
+            self.last_id = self.last_id.max(record.id);
+            if !record.filtered {
+                push(&mut self.latencies, LATENCY_LEN, record.latency_ms);
+            }
+            push(&mut self.feed, FEED_LEN, record);
+        }
+    }
+
+    async fn poll(&mut self, client: &reqwest::Client) -> Result<(), reqwest::Error> {
+        self.counters = get(client, &format!("{}/stats", self.url)).await?;
+        self.agents = get(client, &format!("{}/agents", self.url)).await?;
+        let records = get(
+            client,
+            &format!("{}/decisions?after={}", self.url, self.last_id),
+        )
+        .await?;
+        self.absorb(records);
+        Ok(())
+    }
+}
+
+async fn get<T: serde::de::DeserializeOwned>(
+    client: &reqwest::Client,
+    url: &str,
+) -> Result<T, reqwest::Error> {
+    client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+}
+
+fn push<T>(buf: &mut VecDeque<T>, max: usize, item: T) {
+    if buf.len() == max {
+        buf.pop_front();
+    }
+    buf.push_back(item);
+}
+
+/// Run the dashboard until the user quits
+pub async fn run(admin_port: u16, interval: Duration) -> Result<(), KernelError> {
+    let client = reqwest::Client::builder()
+        .no_proxy()
+        .timeout(Duration::from_secs(2))
+        .build()?;
+    let mut dashboard = Dashboard {
+        url: format!("http://127.0.0.1:{}", admin_port),
+        ..Default::default()
+    };
+
+    let mut terminal = ratatui::try_init()?;
+    let result = async {
+        loop {
+            dashboard.error = dashboard.poll(&client).await.err().map(|e| e.to_string());
+            terminal.draw(|frame| draw(frame, &dashboard))?;
+
+            let next = Instant::now() + interval;
+            while let Some(wait) = next.checked_duration_since(Instant::now()) {
+                if !event::poll(wait)? {
+                    break;
+                }
+                if let Event::Key(key) = event::read()? {
+                    if key.kind != KeyEventKind::Press {
+                        continue;
+                    }
+                    match key.code {
+                        KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                        KeyCode::Char('f') => {
+                            dashboard.show_filtered = !dashboard.show_filtered;
+                            terminal.draw(|frame| draw(frame, &dashboard))?;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+    .await;
+    ratatui::try_restore()?;
+    result
+}
+
+fn draw(frame: &mut Frame, dashboard: &Dashboard) {
+    let agent_rows = dashboard.agents.len().clamp(1, 8) as u16;
+    let [title, top, agents, feed, help] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Length(7),
+        Constraint::Length(agent_rows + 3),
+        Constraint::Min(3),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    frame.render_widget(Paragraph::new(title_line(dashboard)), title);
+
+    let [counters, latency] =
+        Layout::horizontal([Constraint::Length(44), Constraint::Min(20)]).areas(top);
+    draw_counters(frame, counters, &dashboard.counters);
+    draw_latency(frame, latency, dashboard);
+    draw_agents(frame, agents, &dashboard.agents);
+    draw_feed(frame, feed, dashboard);
+
+    let filtered = if dashboard.show_filtered {
+        "hide"
+    } else {
+        "show"
+    };
+    frame.render_widget(
+        Paragraph::new(format!(" q quit · f {} filtered", filtered)).dim(),
+        help,
+    );
+}
+
+fn title_line(dashboard: &Dashboard) -> Line<'static> {
+    let c = &dashboard.counters;
+    let mut spans = vec![
+        Span::styled(" tripwired top ", Style::new().add_modifier(Modifier::BOLD)),
+        Span::raw(format!("{}  ", dashboard.url)),
+    ];
+    if let Some(ref error) = dashboard.error {
+        spans.push(Span::styled(
+            format!("disconnected: {}", error),
+            Style::new().fg(Color::Red),
+        ));
+        return Line::from(spans);
+    }
+    spans.push(if c.llm_healthy {
+        Span::styled("LLM healthy", Style::new().fg(Color::Green))
+    } else {
+        Span::styled("LLM UNHEALTHY", Style::new().fg(Color::Red).bold())
+    });
+    spans.push(Span::raw("  "));
+    spans.push(if c.armed {
+        Span::styled("ARMED", Style::new().fg(Color::Red).bold())
+    } else if c.disarmed_until_ms.is_some() {
+        Span::styled(
+            "disarmed by operator",
+            Style::new().fg(Color::Yellow).bold(),
+        )
+    } else {
+        Span::styled("disarmed", Style::new().fg(Color::Yellow))
+    });
+    spans.push(Span::raw(format!("  pending {}", c.llm_pending)));
+    Line::from(spans)
+}
+
+fn draw_counters(frame: &mut Frame, area: Rect, c: &Counters) {
+    let avg = c.total_latency_ms.checked_div(c.analyzed).unwrap_or(0);
+    let text = vec![
+        Line::from(format!(
+            "filtered {:>9}   analyzed {:>7}",
+            c.filtered, c.analyzed
+        )),
+        Line::from(vec![
+            Span::styled(
+                format!("kills {:>12}", c.kills),
+                Style::new().fg(Color::Red),
+            ),
+            Span::raw(format!("   fast path {:>6}", c.fast_path_kills)),
+        ]),
+        Line::from(format!(
+            "sequences {:>8}   rate {:>11}",
+            c.sequences, c.rate_triggers
+        )),
+        Line::from(format!(
+            "fallbacks {:>8}   degraded {:>7}",
+            c.llm_fallbacks, c.degraded
+        )),
+        Line::from(format!("batched {:>10}   avg {:>9}ms", c.batched, avg)),
+    ];
+    frame.render_widget(
+        Paragraph::new(text).block(Block::default().borders(Borders::ALL).title(" Counters ")),
+        area,
+    );
+}
+
+fn draw_latency(frame: &mut Frame, area: Rect, dashboard: &Dashboard) {
+    let latest = dashboard.latencies.back().copied().unwrap_or(0);
+    let max = dashboard.latencies.iter().copied().max().unwrap_or(0);
+    // Newest on the right edge
+    let width = area.width.saturating_sub(2) as usize;
+    let skip = dashboard.latencies.len().saturating_sub(width);
+    let data: Vec<u64> = dashboard.latencies.iter().skip(skip).copied().collect();
+    frame.render_widget(
+        Sparkline::default()
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!(" LLM latency  last {}ms  max {}ms ", latest, max)),
+            )
+            .data(&data)
+            .style(Style::new().fg(Color::Cyan)),
+        area,
+    );
+}
+
+fn draw_agents(frame: &mut Frame, area: Rect, agents: &[AgentStatus]) {
+    let rows = agents.iter().map(|a| {
+        let last = a.last_action.clone().unwrap_or_else(|| "-".to_string());
+        Row::new(vec![
+            a.id.to_string(),
+            // `agent_id` defaults to the peer (empty from older kernels)
+            match a.agent_id.is_empty() {
+                true => a.peer.clone(),
+                false => a.agent_id.clone(),
+            },
+            a.lines.to_string(),
+            a.escalations.to_string(),
+            a.kills.to_string(),
+            last,
+            clock(a.last_seen_ms),
+        ])
+        .style(action_style(a.last_action.as_deref().unwrap_or("")))
+    });
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(4),
+            Constraint::Min(16),
+            Constraint::Length(9),
+            Constraint::Length(9),
+            Constraint::Length(6),
+            Constraint::Length(8),
+            Constraint::Length(9),
+        ],
+    )
+    .header(Row::new(["ID", "Agent", "Lines", "Escal.", "Kills", "Last", "Seen"]).bold())
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(format!(" Agents ({}) ", agents.len())),
+    );
+    frame.render_widget(table, area);
+}
+
+fn draw_feed(frame: &mut Frame, area: Rect, dashboard: &Dashboard) {
+    let items: Vec<ListItem> = dashboard
+        .feed
+        .iter()
+        .rev()
+        .filter(|r| dashboard.show_filtered || !r.filtered)
+        .take(area.height as usize)
+        .map(|r| ListItem::new(feed_line(r)))
+        .collect();
+    frame.render_widget(
+        List::new(items).block(Block::default().borders(Borders::ALL).title(" Decisions ")),
+        area,
+    );
+}
+
+/// `12:00:01 #42 KILL  97% 180ms [rule] input — reason`
+fn feed_line(record: &DecisionRecord) -> Line<'static> {
+    let latency = if record.filtered {
+        format!("{}μs", record.latency_ms)
+    } else {
+        format!("{}ms", record.latency_ms)
+    };
+    let mut spans = vec![
+        Span::raw(format!("{} #{:<6} ", clock(record.timestamp_ms), record.id)),
+        Span::styled(
+            format!("{:<7}", record.action),
+            action_style(&record.action).bold(),
+        ),
+        Span::raw(format!("{:>4}% {:>8} ", record.confidence, latency)),
+    ];
+    if let Some(ref rule) = record.rule {
+        spans.push(Span::styled(format!("[{}] ", rule), Style::new().dim()));
+    }
+    spans.push(Span::raw(record.input_log.clone()));
+    if let Some(ref reason) = record.reason {
+        spans.push(Span::styled(
+            format!(" — {}", reason),
+            Style::new().italic(),
+        ));
+    }
+    let line = Line::from(spans);
+    if record.filtered {
+        line.dim()
+    } else {
+        line
+    }
+}
+
+fn action_style(action: &str) -> Style {
+    match action {
+        "KILL" => Style::new().fg(Color::Red),
+        "FAIL" => Style::new().fg(Color::Yellow),
+        "PAUSE" | "FREEZE" | "CLAMP_CPU" | "CLAMP_MEMORY" | "RESTRICT" => {
+            Style::new().fg(Color::Magenta)
+        }
+        "SUSTAIN" => Style::new().fg(Color::Green),
+        _ => Style::new(),
+    }
+}
+
+/// `HH:MM:SS` (UTC) of a Unix timestamp in milliseconds
+fn clock(timestamp_ms: u64) -> String {
+    let secs = timestamp_ms / 1000 % 86_400;
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    fn record(id: u64, action: &str, filtered: bool, latency_ms: u64) -> DecisionRecord {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "timestamp_ms": 3_723_000,
+            "input_log": format!("line {}", id),
+            "input_hash": "",
+            "action": action,
+            "confidence": 97,
+            "filtered": filtered,
+            "latency_ms": latency_ms,
+            "model_fingerprint": "",
+            "prompt_hash": "",
+            "raw_response": null,
+            "reason": "sequential orders",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_dashboard_render() {
+        let mut dashboard = Dashboard {
+            url: "http://127.0.0.1:9100".to_string(),
+            counters: Counters {
+                kills: 1,
+                llm_healthy: true,
+                armed: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        dashboard.absorb(vec![
+            record(1, "SUSTAIN", true, 12),
+            record(2, "KILL", false, 180),
+        ]);
+        assert_eq!(dashboard.last_id, 2);
+        assert_eq!(dashboard.latencies, [180]);
+
+        let mut terminal = Terminal::new(TestBackend::new(120, 30)).unwrap();
+        terminal.draw(|frame| draw(frame, &dashboard)).unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+
+        assert!(screen.contains("LLM healthy"));
+        assert!(screen.contains("01:02:03 #2      KILL"));
+        assert!(screen.contains("line 2 — sequential orders"));
+        // Pre-filtered lines are hidden until toggled
+        assert!(!screen.contains("line 1"));
+    }
+}
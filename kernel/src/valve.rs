@@ -0,0 +1,150 @@
+//! Kill Valve - Cooldown and Hourly Cap on Kill Actions
+//!
+//! After a KILL fires, the target is usually already dead: further KILL
+//! decisions from the same agent within `cooldown_ms` are audited but do
+//! not signal again. `max_per_hour` is a global safety valve - once that
+//! many kills fired in the last hour, every further KILL is suppressed,
+//! so a prompt regression cannot machine-gun every process it is pointed
+//! at. Suppressed kills are tagged in the audit record (`suppressed`).
+//!
+//! ```toml
+//! [kill_limits]
+//! cooldown_ms = 10000
+//! max_per_hour = 20
+//! ```
+
+use serde::Deserialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const HOUR: Duration = Duration::from_secs(3600);
+
+/// `[kill_limits]` table
+#[derive(Debug, Clone, Deserialize)]
+pub struct KillLimits {
+    /// Per-agent quiet period after a kill fires (milliseconds); 0 disables
+    #[serde(default = "default_cooldown_ms")]
+    pub cooldown_ms: u64,
+    /// Kills allowed per rolling hour across all agents; 0 = unlimited
+    #[serde(default)]
+    pub max_per_hour: usize,
+}
+
+fn default_cooldown_ms() -> u64 {
+    10_000
+}
+
+impl Default for KillLimits {
+    fn default() -> Self {
+        Self {
+            cooldown_ms: default_cooldown_ms(),
+            max_per_hour: 0,
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    /// Last kill fired per agent
+    last_kill: HashMap<u64, Instant>,
+    /// Kills fired within the last hour, oldest first
+    fired: VecDeque<Instant>,
+}
+
+/// Decides whether a KILL decision may signal the target
+pub struct KillValve {
+    cooldown: Duration,
+    max_per_hour: usize,
+    state: Mutex<State>,
+}
+
+impl KillValve {
+    pub fn new(limits: &KillLimits) -> Self {
+        Self {
+            cooldown: Duration::from_millis(limits.cooldown_ms),
+            max_per_hour: limits.max_per_hour,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// `None` if the kill may fire (and counts it), else why it is suppressed
+    pub fn check(&self, agent: u64) -> Option<String> {
+        self.check_at(agent, Instant::now())
+    }
+
+    fn check_at(&self, agent: u64, now: Instant) -> Option<String> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(last) = state.last_kill.get(&agent) {
+            let since = now.saturating_duration_since(*last);
+            if since < self.cooldown {
+                return Some(format!(
+                    "cooldown: agent {} killed {}ms ago ({}ms window)",
+                    agent,
+                    since.as_millis(),
+                    self.cooldown.as_millis()
+                ));
+            }
+        }
+        while state
+            .fired
+            .front()
+            .is_some_and(|t| now.saturating_duration_since(*t) >= HOUR)
+        {
+            state.fired.pop_front();
+        }
+        if self.max_per_hour > 0 && state.fired.len() >= self.max_per_hour {
+            return Some(format!(
+                "max_per_hour: {} kills in the last hour",
+                state.fired.len()
+            ));
+        }
+        state.fired.push_back(now);
+        state.last_kill.insert(agent, now);
+        None
+    }
+
+    /// Forget a disconnected agent's cooldown
+    pub fn forget(&self, agent: u64) {
+        self.state.lock().unwrap().last_kill.remove(&agent);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cooldown_and_cap() {
+        let valve = KillValve::new(&KillLimits {
+            cooldown_ms: 1000,
+            max_per_hour: 3,
+        });
+        let t0 = Instant::now();
+        let at = |ms| t0 + Duration::from_millis(ms);
+
+        assert_eq!(valve.check_at(1, at(0)), None);
+        // Same agent inside the window; another agent is unaffected
+        assert!(valve.check_at(1, at(500)).unwrap().starts_with("cooldown"));
+        assert_eq!(valve.check_at(2, at(500)), None);
+        assert_eq!(valve.check_at(1, at(1000)), None);
+
+        // Fourth kill within the hour trips the valve for everyone
+        let capped = valve.check_at(3, at(2000)).unwrap();
+        assert_eq!(capped, "max_per_hour: 3 kills in the last hour");
+        // Suppressed kills don't start a cooldown or count toward the cap
+        assert_eq!(valve.check_at(3, at(3_600_001)), None);
+    }
+
+    #[test]
+    fn test_disabled() {
+        let valve = KillValve::new(&KillLimits {
+            cooldown_ms: 0,
+            max_per_hour: 0,
+        });
+        let now = Instant::now();
+        for _ in 0..100 {
+            assert_eq!(valve.check_at(1, now), None);
+        }
+    }
+}
@@ -0,0 +1,406 @@
+//! Verdict Parser - Reading Untrusted Model Output
+//!
+//! The model's answer is untrusted text: it may be fenced, chatty,
+//! truncated by `max_tokens`, or quote the log it was asked about. It is
+//! read JSON-first, and a bare `KILL` or `SUSTAIN` word is never a verdict,
+//! so "I will not KILL this" decides nothing. In order of precedence:
+//!
+//! 1. The answer, with any number of enclosing code fences removed, is one
+//!    verdict object: that is the verdict. When the response schema was
+//!    enforced nothing else is accepted.
+//! 2. Otherwise the answer is tokenized like JSON and every
+//!    `"action": "..."` field counts, at any depth, in complete or
+//!    truncated objects or bare in prose. Text inside a string value (a
+//!    reason quoting `"action":"KILL"`, say) is never a field.
+//! 3. Every such field must name the same action, `KILL` or `SUSTAIN` in
+//!    any case. A field with another value, or fields that disagree, leave
+//!    the answer without a verdict - which the caller treats as FAIL.
+//! 4. `confidence` and `reason` are read from the object holding the first
+//!    `action` field.
+//!
+//! This module only depends on serde, so the fuzz target in `kernel/fuzz`
+//! builds it on its own.
+
+use serde::Deserialize;
+use std::borrow::Cow;
+
+/// Verdict as constrained by the response schema
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct LlmVerdict {
+    pub action: Verdict,
+    #[serde(default)]
+    pub confidence: Option<u32>,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Verdict {
+    #[serde(alias = "kill")]
+    Kill,
+    #[serde(alias = "sustain")]
+    Sustain,
+}
+
+impl Verdict {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Kill => "KILL",
+            Self::Sustain => "SUSTAIN",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        if name.eq_ignore_ascii_case("KILL") {
+            Some(Self::Kill)
+        } else if name.eq_ignore_ascii_case("SUSTAIN") {
+            Some(Self::Sustain)
+        } else {
+            None
+        }
+    }
+}
+
+/// Read the verdict from a model answer (`structured`: the schema was
+/// enforced); `None` if it has none
+pub fn parse(content: &str, structured: bool) -> Option<LlmVerdict> {
+    match serde_json::from_str::<LlmVerdict>(unfence(content)) {
+        Ok(verdict) => Some(verdict),
+        Err(_) if structured => None,
+        Err(_) => scan(content),
+    }
+}
+
+/// The text inside any number of enclosing code fences
+///
+/// An opening fence is three or more backticks and an optional language
+/// tag; a missing closing fence (a truncated answer) is fine.
+pub fn unfence(text: &str) -> &str {
+    let mut body = text.trim();
+    loop {
+        let inner = body.trim_start_matches('`');
+        if body.len() - inner.len() < 3 {
+            return body;
+        }
+        let inner = inner
+            .trim_start_matches(|c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '+'))
+            .trim_end();
+        let unclosed = inner.trim_end_matches('`');
+        body = match inner.len() - unclosed.len() >= 3 {
+            true => unclosed.trim(),
+            false => inner.trim(),
+        };
+    }
+}
+
+/// Tokens of JSON-ish text; anything else is `Other`
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+    Str(Cow<'a, str>),
+    Num(&'a str),
+    Open,
+    Close,
+    Colon,
+    Other,
+}
+
+/// Tokenize `text`, stopping at an unterminated string
+fn tokens(text: &str) -> Vec<Token<'_>> {
+    let bytes = text.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let start = i;
+        let token = match bytes[i] {
+            b'"' => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != b'"' {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+                if i >= bytes.len() {
+                    break;
+                }
+                i += 1;
+                let literal = &text[start..i];
+                match literal.contains('\\') {
+                    true => serde_json::from_str::<String>(literal)
+                        .map_or(Token::Other, |s| Token::Str(Cow::Owned(s))),
+                    false => Token::Str(Cow::Borrowed(&literal[1..literal.len() - 1])),
+                }
+            }
+            b'-' | b'0'..=b'9' => {
+                i += 1;
+                while i < bytes.len()
+                    && matches!(bytes[i], b'0'..=b'9' | b'.' | b'e' | b'E' | b'+' | b'-')
+                {
+                    i += 1;
+                }
+                Token::Num(&text[start..i])
+            }
+            b'{' | b'}' | b':' => {
+                i += 1;
+                match bytes[start] {
+                    b'{' => Token::Open,
+                    b'}' => Token::Close,
+                    _ => Token::Colon,
+                }
+            }
+            b if b.is_ascii_whitespace() => {
+                i += 1;
+                continue;
+            }
+            _ => {
+                // Whole characters, so later slices stay on boundaries
+                i += text[i..].chars().next().map_or(1, char::len_utf8);
+                Token::Other
+            }
+        };
+        tokens.push(token);
+    }
+    tokens
+}
+
+/// `"key": value`, and the object it was found in (`None`: top level)
+struct Field<'t, 'a> {
+    object: Option<usize>,
+    key: &'t str,
+    value: &'t Token<'a>,
+}
+
+fn fields<'t, 'a>(tokens: &'t [Token<'a>]) -> Vec<Field<'t, 'a>> {
+    let mut fields = Vec::new();
+    let mut open = Vec::new();
+    let mut objects = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Open => {
+                open.push(objects);
+                objects += 1;
+            }
+            Token::Close => {
+                open.pop();
+            }
+            // A key, not the value of an earlier field
+            Token::Str(key) if i == 0 || tokens[i - 1] != Token::Colon => {
+                if let (Some(Token::Colon), Some(value)) = (tokens.get(i + 1), tokens.get(i + 2)) {
+                    fields.push(Field {
+                        object: open.last().copied(),
+                        key,
+                        value,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    fields
+}
+
+/// Precedence rules 2-4 (see the module docs)
+fn scan(text: &str) -> Option<LlmVerdict> {
+    let tokens = tokens(text);
+    let fields = fields(&tokens);
+    let mut votes = fields
+        .iter()
+        .filter(|f| f.key.eq_ignore_ascii_case("action"))
+        .map(|f| match f.value {
+            Token::Str(name) => Verdict::from_name(name).map(|v| (v, f.object)),
+            _ => None,
+        });
+    let (action, object) = votes.next()??;
+    for vote in votes {
+        if vote?.0 != action {
+            return None;
+        }
+    }
+
+    let sibling = |name: &str| {
+        fields
+            .iter()
+            .find(|f| f.object == object && f.key.eq_ignore_ascii_case(name))
+            .map(|f| f.value)
+    };
+    Some(LlmVerdict {
+        action,
+        confidence: match sibling("confidence") {
+            Some(Token::Num(n)) => n.parse().ok(),
+            _ => None,
+        },
+        reason: match sibling("reason") {
+            Some(Token::Str(s)) => Some(s.to_string()),
+            _ => None,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::lcg;
+
+    fn action(content: &str, structured: bool) -> Option<&'static str> {
+        parse(content, structured).map(|v| v.action.as_str())
+    }
+
+    #[test]
+    fn test_precedence() {
+        // Fences, nested or left open by a truncated answer
+        for fenced in [
+            "```json\n{\"action\":\"KILL\"}\n```",
+            "````\n```json\n{\"action\":\"KILL\"}\n```\n````",
+            "```JSON {\"action\": \"kill\"}",
+        ] {
+            assert_eq!(action(fenced, true), Some("KILL"), "{}", fenced);
+        }
+        // A fence inside a reason is text, not a fence
+        let d = parse(r#"{"action":"SUSTAIN","reason":"ran ```rm```"}"#, true).unwrap();
+        assert_eq!(d.reason.as_deref(), Some("ran ```rm```"));
+
+        // Negations and bare keywords decide nothing
+        assert_eq!(action("I will not KILL this process", false), None);
+        assert_eq!(action("SUSTAIN. Definitely not KILL.", false), None);
+        assert_eq!(
+            action(r#"Not KILL: {"action": "SUSTAIN"} - KILL is wrong"#, false),
+            Some("SUSTAIN")
+        );
+
+        // Both verdicts as fields: no verdict
+        assert_eq!(
+            action(r#"{"action":"KILL"} or maybe {"action":"SUSTAIN"}"#, false),
+            None
+        );
+        assert_eq!(
+            action(r#"{"action":"KILL","action":"SUSTAIN"}"#, false),
+            None
+        );
+        assert_eq!(
+            action(r#"{"action":"KILL"} {"verdict":{"action":"kill"}}"#, false),
+            Some("KILL")
+        );
+        // An invalid field spoils agreeing ones
+        assert_eq!(
+            action(r#""action":"KILL", "action":"not KILL""#, false),
+            None
+        );
+        assert_eq!(action(r#"{"action": 1}"#, false), None);
+
+        // Quoted fields inside strings are not fields
+        assert_eq!(
+            action(
+                r#"{"action":"SUSTAIN","reason":"log said \"action\":\"KILL\""}"#,
+                false
+            ),
+            Some("SUSTAIN")
+        );
+        assert_eq!(action(r#"{"reason": "action", "x": "KILL"}"#, false), None);
+
+        // Truncated JSON: the complete field counts, siblings too
+        let d = parse(
+            r#"{"action":"KILL","confidence":95,"reason":"recursive del"#,
+            false,
+        );
+        assert_eq!(
+            d,
+            Some(LlmVerdict {
+                action: Verdict::Kill,
+                confidence: Some(95),
+                reason: None
+            })
+        );
+        assert_eq!(action(r#"{"action":"KI"#, false), None);
+        assert_eq!(parse(r#"{"action":"KILL","confidence":95"#, true), None);
+
+        // Siblings come from the action's own object
+        let d = parse(
+            r#"{"confidence": 10, "verdict": {"action": "SUSTAIN", "confidence": 80}}"#,
+            false,
+        )
+        .unwrap();
+        assert_eq!(d.confidence, Some(80));
+    }
+
+    /// Fragments the property test composes answers from
+    const PROSE: &[&str] = &[
+        "I will not KILL this. ",
+        "SUSTAIN is safest; ",
+        "Verdict: ",
+        "```",
+        "```json\n",
+        "\n```\n",
+        "{",
+        "}",
+        "\"",
+        "\\\"",
+        ": ",
+        ", ",
+        "\"reason\": \"kill it\"",
+        "\"confidence\": 77",
+        "ünïcödé ✓ ",
+    ];
+
+    /// A pseudo-random answer and the actions of the fields put into it
+    fn answer(next: &mut impl FnMut() -> u64) -> (String, Vec<&'static str>) {
+        let mut text = String::new();
+        let mut actions = Vec::new();
+        for _ in 0..next() % 8 {
+            match next() % 4 {
+                0 => {
+                    let a = ["KILL", "SUSTAIN", "kill", "MAYBE"][(next() % 4) as usize];
+                    text.push_str(&format!("{{\"action\": \"{}\"}}", a));
+                    actions.push(a);
+                }
+                _ => text.push_str(PROSE[(next() as usize) % PROSE.len()]),
+            }
+        }
+        (text, actions)
+    }
+
+    #[test]
+    fn test_parse_properties() {
+        let mut next = lcg(0xf0220);
+        for _ in 0..20_000 {
+            let (text, actions) = answer(&mut next);
+            let strict = parse(&text, true);
+            let loose = parse(&text, false);
+
+            // Strict answers read the same either way, fenced or not
+            if let Some(ref verdict) = strict {
+                assert_eq!(
+                    loose.as_ref().map(|v| v.action),
+                    Some(verdict.action),
+                    "{}",
+                    text
+                );
+                for fenced in [
+                    format!("```json\n{}\n```", text),
+                    format!("````\n```\n{}\n```\n````", text),
+                ] {
+                    assert_eq!(parse(&fenced, true).as_ref(), Some(verdict), "{}", fenced);
+                }
+            }
+            // Never a verdict without an action field naming it
+            if let Some(ref verdict) = loose {
+                let named = actions
+                    .iter()
+                    .any(|a| a.eq_ignore_ascii_case(verdict.action.as_str()));
+                assert!(named, "{}", text);
+            }
+            // Every truncation reads the same action or none
+            if let Some(ref verdict) = loose {
+                for cut in (0..text.len()).filter(|&i| text.is_char_boundary(i)) {
+                    if let Some(v) = parse(&text[..cut], false) {
+                        assert_eq!(v.action, verdict.action, "{}", &text[..cut]);
+                    }
+                }
+            }
+        }
+
+        // Arbitrary bytes never panic
+        for _ in 0..20_000 {
+            let bytes: Vec<u8> = (0..next() % 64).map(|_| next() as u8).collect();
+            let text = String::from_utf8_lossy(&bytes);
+            let _ = (parse(&text, true), parse(&text, false), unfence(&text));
+        }
+    }
+}
@@ -0,0 +1,309 @@
+//! VM - Stopping the Whole Agent Sandbox
+//!
+//! Killing the agent's process leaves whatever it started inside its
+//! sandbox running. When the agent has a VM of its own, `--target-vm` (or
+//! `target_vm` in a `[channel.<name>]` table) makes KILL power the VM off
+//! and PAUSE suspend it:
+//!
+//! - `libvirt:<domain>` (Unix) - `virsh destroy` / `virsh suspend`, against
+//!   `LIBVIRT_DEFAULT_URI` (default: the local hypervisor)
+//! - `qmp:<socket>` (Unix) - QEMU's QMP monitor socket: `quit` / `stop`,
+//!   for QEMU, microVMs and Cloud Hypervisor's QMP-compatible builds
+//! - `hyperv:<name>` (Windows) - `Stop-VM -TurnOff` / `Suspend-VM` through
+//!   PowerShell (needs the Hyper-V module and Hyper-V Administrators)
+//!
+//! ```bash
+//! tripwired --vsock-port 5000 --target-vm qmp:/run/sandbox/agent-7.qmp
+//! ```
+//!
+//! Power-off is immediate, with no guest shutdown: the agent gets no chance
+//! to react. The kernel's self-test checks the VM is running at startup.
+
+use serde_json::{json, Value};
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use tokio::process::Command;
+
+/// A VM the kernel can power off and suspend
+#[derive(Debug, Clone, PartialEq)]
+pub enum VmTarget {
+    /// libvirt domain name or UUID
+    Libvirt(String),
+    /// QMP monitor socket (`-qmp unix:<path>,server`)
+    Qmp(PathBuf),
+    /// Hyper-V VM name
+    HyperV(String),
+}
+
+/// Parse `--target-vm` (`libvirt:`, `qmp:` or `hyperv:` and a name)
+pub fn parse_target(spec: &str) -> Result<VmTarget, String> {
+    let (kind, name) = spec
+        .split_once(':')
+        .filter(|(_, name)| !name.is_empty())
+        .ok_or_else(|| format!("'{}': expected libvirt:, qmp: or hyperv: and a name", spec))?;
+    let target = match kind {
+        "libvirt" => VmTarget::Libvirt(name.to_string()),
+        "qmp" => VmTarget::Qmp(PathBuf::from(name)),
+        "hyperv" => VmTarget::HyperV(name.to_string()),
+        _ => return Err(format!("'{}': unknown VM kind '{}'", spec, kind)),
+    };
+    match (&target, cfg!(windows)) {
+        (VmTarget::HyperV(_), false) => Err("hyperv: VMs are only supported on Windows".into()),
+        (VmTarget::Libvirt(_) | VmTarget::Qmp(_), true) => {
+            Err(format!("{}: VMs are not supported on Windows", kind))
+        }
+        _ => Ok(target),
+    }
+}
+
+impl fmt::Display for VmTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VmTarget::Libvirt(domain) => write!(f, "libvirt:{}", domain),
+            VmTarget::Qmp(path) => write!(f, "qmp:{}", path.display()),
+            VmTarget::HyperV(name) => write!(f, "hyperv:{}", name),
+        }
+    }
+}
+
+impl VmTarget {
+    /// Power the VM off at once
+    pub async fn kill(&self) -> io::Result<()> {
+        match self {
+            VmTarget::Libvirt(domain) => virsh(&["destroy", domain]).await.map(drop),
+            VmTarget::Qmp(path) => qmp(path, "quit", json!({})).await.map(drop),
+            VmTarget::HyperV(name) => {
+                powershell(&format!("Stop-VM -Name {} -TurnOff -Force", quoted(name)))
+                    .await
+                    .map(drop)
+            }
+        }
+    }
+
+    /// Suspend the VM (resumable by an operator)
+    pub async fn pause(&self) -> io::Result<()> {
+        match self {
+            VmTarget::Libvirt(domain) => virsh(&["suspend", domain]).await.map(drop),
+            VmTarget::Qmp(path) => qmp(path, "stop", json!({})).await.map(drop),
+            VmTarget::HyperV(name) => powershell(&format!("Suspend-VM -Name {}", quoted(name)))
+                .await
+                .map(drop),
+        }
+    }
+
+    /// Save the VM's disks and memory as the snapshot `name`; where it is
+    pub async fn snapshot(&self, name: &str) -> io::Result<String> {
+        match self {
+            VmTarget::Libvirt(domain) => {
+                virsh(&["snapshot-create-as", domain, name, "--atomic"]).await?;
+                Ok(format!("libvirt snapshot {} of {}", name, domain))
+            }
+            VmTarget::Qmp(path) => {
+                let command = json!({ "command-line": format!("savevm {}", name) });
+                let output = qmp(path, "human-monitor-command", command).await?;
+                // savevm reports failure as monitor output, not a QMP error
+                match output.as_str().map(str::trim).unwrap_or_default() {
+                    "" => Ok(format!("QEMU snapshot {} (loadvm {})", name, name)),
+                    error => Err(io::Error::other(error.to_string())),
+                }
+            }
+            VmTarget::HyperV(vm) => {
+                powershell(&format!(
+                    "Checkpoint-VM -Name {} -SnapshotName {}",
+                    quoted(vm),
+                    quoted(name)
+                ))
+                .await?;
+                Ok(format!("Hyper-V checkpoint {} of {}", name, vm))
+            }
+        }
+    }
+
+    /// Whether the VM is running (for the startup self-test)
+    pub async fn running(&self) -> io::Result<bool> {
+        match self {
+            VmTarget::Libvirt(domain) => {
+                Ok(virsh(&["domstate", domain]).await?.trim() == "running")
+            }
+            VmTarget::Qmp(path) => {
+                let status = qmp(path, "query-status", json!({})).await?;
+                Ok(status["running"] == true)
+            }
+            VmTarget::HyperV(name) => {
+                let state = powershell(&format!("(Get-VM -Name {}).State", quoted(name))).await?;
+                Ok(state.trim() == "Running")
+            }
+        }
+    }
+}
+
+/// Run `virsh` and return its stdout
+async fn virsh(args: &[&str]) -> io::Result<String> {
+    output(Command::new("virsh").args(args)).await
+}
+
+/// Run a PowerShell `script` and return its stdout
+async fn powershell(script: &str) -> io::Result<String> {
+    output(Command::new("powershell").args(["-NoProfile", "-NonInteractive", "-Command", script]))
+        .await
+}
+
+/// PowerShell single-quoted string: nothing inside is expanded
+fn quoted(name: &str) -> String {
+    format!("'{}'", name.replace('\'', "''"))
+}
+
+async fn output(command: &mut Command) -> io::Result<String> {
+    // Abandoned (a snapshot past its deadline): stop the tool as well
+    let output = command.kill_on_drop(true).output().await?;
+    if !output.status.success() {
+        return Err(io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Run one QMP command on the monitor at `path` and return its `return`
+#[cfg(unix)]
+async fn qmp(path: &std::path::Path, command: &str, arguments: Value) -> io::Result<Value> {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let stream = tokio::net::UnixStream::connect(path).await?;
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    // Greeting, then capabilities negotiation before any command
+    reply(&mut lines).await?;
+    for request in [
+        json!({ "execute": "qmp_capabilities" }),
+        json!({ "execute": command, "arguments": arguments }),
+    ] {
+        writer
+            .write_all((request.to_string() + "\n").as_bytes())
+            .await?;
+    }
+    reply(&mut lines).await?;
+    reply(&mut lines).await
+}
+
+#[cfg(windows)]
+async fn qmp(_: &std::path::Path, _: &str, _: Value) -> io::Result<Value> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "QMP is not supported on Windows",
+    ))
+}
+
+/// Next reply on a QMP connection, skipping asynchronous events
+#[cfg(unix)]
+async fn reply<R: tokio::io::AsyncBufRead + Unpin>(
+    lines: &mut tokio::io::Lines<R>,
+) -> io::Result<Value> {
+    loop {
+        let line = lines
+            .next_line()
+            .await?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "QMP monitor closed"))?;
+        let message: Value = serde_json::from_str(&line).map_err(io::Error::other)?;
+        if let Some(error) = message.get("error") {
+            return Err(io::Error::other(format!(
+                "QMP: {}",
+                error["desc"].as_str().unwrap_or("command failed")
+            )));
+        }
+        if let Some(value) = message.get("return").or_else(|| message.get("QMP")) {
+            return Ok(value.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_target() {
+        #[cfg(unix)]
+        {
+            assert_eq!(
+                parse_target("libvirt:agent-7"),
+                Ok(VmTarget::Libvirt("agent-7".to_string()))
+            );
+            let qmp = parse_target("qmp:/run/vm.qmp").unwrap();
+            assert_eq!(qmp, VmTarget::Qmp(PathBuf::from("/run/vm.qmp")));
+            assert_eq!(qmp.to_string(), "qmp:/run/vm.qmp");
+            assert!(parse_target("hyperv:agent").is_err());
+        }
+        #[cfg(windows)]
+        {
+            assert_eq!(
+                parse_target("hyperv:Agent VM"),
+                Ok(VmTarget::HyperV("Agent VM".to_string()))
+            );
+            assert!(parse_target("libvirt:agent").is_err());
+        }
+        assert!(parse_target("agent-7").is_err());
+        assert!(parse_target("libvirt:").is_err());
+        assert!(parse_target("xen:agent").is_err());
+    }
+
+    /// A QMP monitor answering one connection as QEMU does
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_qmp() {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vm.qmp");
+        let listener = tokio::net::UnixListener::bind(&path).unwrap();
+        let monitor = tokio::spawn(async move {
+            let mut executed = Vec::new();
+            for _ in 0..3 {
+                let (stream, _) = listener.accept().await.unwrap();
+                let (reader, mut writer) = stream.into_split();
+                let mut lines = BufReader::new(reader).lines();
+                writer
+                    .write_all(b"{\"QMP\": {\"version\": {}, \"capabilities\": []}}\n")
+                    .await
+                    .unwrap();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    let request: Value = serde_json::from_str(&line).unwrap();
+                    let execute = request["execute"].as_str().unwrap().to_string();
+                    let answer = match execute.as_str() {
+                        "query-status" => {
+                            "{\"event\": \"RESUME\"}\n{\"return\": {\"running\": true, \"status\": \"running\"}}\n"
+                        }
+                        "stop" => "{\"error\": {\"class\": \"GenericError\", \"desc\": \"busy\"}}\n",
+                        "human-monitor-command" => {
+                            assert_eq!(request["arguments"]["command-line"], "savevm tripwired-7");
+                            "{\"return\": \"\"}\n"
+                        }
+                        _ => "{\"return\": {}}\n",
+                    };
+                    writer.write_all(answer.as_bytes()).await.unwrap();
+                    executed.push(execute);
+                }
+            }
+            executed
+        });
+
+        let target = VmTarget::Qmp(path);
+        assert!(target.running().await.unwrap());
+        let error = target.pause().await.unwrap_err();
+        assert_eq!(error.to_string(), "QMP: busy");
+        let saved = target.snapshot("tripwired-7").await.unwrap();
+        assert!(saved.contains("loadvm tripwired-7"));
+        assert_eq!(
+            monitor.await.unwrap(),
+            [
+                "qmp_capabilities",
+                "query-status",
+                "qmp_capabilities",
+                "stop",
+                "qmp_capabilities",
+                "human-monitor-command"
+            ]
+        );
+    }
+}
@@ -0,0 +1,273 @@
+//! Vsock - Agents in VM Sandboxes (Linux)
+//!
+//! An agent isolated in a microVM has no route to the host's loopback and
+//! no shared filesystem for a Unix socket. `--vsock-port` accepts its
+//! connections over AF_VSOCK instead: the guest connects to the host (CID
+//! 2) on that port, with no network configuration on either side.
+//!
+//! ```bash
+//! tripwired --vsock-port 5000 --agent-token-file /etc/tripwired/token
+//! # in the guest: socat - VSOCK-CONNECT:2:5000
+//! ```
+//!
+//! Each connection is the agent `vsock:<cid>`, the guest's context ID,
+//! which names the VM to stop on a KILL. Any guest on the host can reach
+//! the port: `--agent-token-file` holds guests to `HELLO token=` as it does
+//! TCP agents. Firecracker does not expose
+//! AF_VSOCK on the host: it forwards guest port N to the Unix socket
+//! `<uds_path>_N`, so serve that with `--socket-path` instead.
+
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::unix::AsyncFd;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Pending connections the kernel queues for `accept`
+const BACKLOG: i32 = 128;
+
+/// Listening AF_VSOCK socket
+pub struct VsockListener {
+    fd: AsyncFd<OwnedFd>,
+}
+
+impl VsockListener {
+    /// Listen on `port` for every context ID
+    pub fn bind(port: u32) -> io::Result<Self> {
+        let fd = socket()?;
+        let addr = sockaddr(libc::VMADDR_CID_ANY, port);
+        // SAFETY: `addr` is a valid sockaddr_vm of the length passed
+        let bound = unsafe {
+            libc::bind(
+                fd.as_raw_fd(),
+                &addr as *const libc::sockaddr_vm as *const libc::sockaddr,
+                std::mem::size_of::<libc::sockaddr_vm>() as libc::socklen_t,
+            )
+        };
+        if bound < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `fd` is an open, bound socket
+        if unsafe { libc::listen(fd.as_raw_fd(), BACKLOG) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            fd: AsyncFd::new(fd)?,
+        })
+    }
+
+    /// Next connection, and the context ID it came from
+    pub async fn accept(&self) -> io::Result<(VsockStream, u32)> {
+        loop {
+            let mut guard = self.fd.readable().await?;
+            let accepted = guard.try_io(|fd| {
+                let mut addr: libc::sockaddr_vm = unsafe { std::mem::zeroed() };
+                let mut len = std::mem::size_of::<libc::sockaddr_vm>() as libc::socklen_t;
+                // SAFETY: `addr` / `len` describe a writable sockaddr_vm
+                let conn = unsafe {
+                    libc::accept4(
+                        fd.as_raw_fd(),
+                        &mut addr as *mut libc::sockaddr_vm as *mut libc::sockaddr,
+                        &mut len,
+                        libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+                    )
+                };
+                if conn < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                // SAFETY: accept4 returned a new descriptor we now own
+                Ok((unsafe { OwnedFd::from_raw_fd(conn) }, addr.svm_cid))
+            });
+            if let Ok(result) = accepted {
+                let (fd, cid) = result?;
+                return Ok((VsockStream::new(fd)?, cid));
+            }
+        }
+    }
+}
+
+/// Connected AF_VSOCK stream
+pub struct VsockStream {
+    fd: AsyncFd<OwnedFd>,
+}
+
+impl VsockStream {
+    fn new(fd: OwnedFd) -> io::Result<Self> {
+        Ok(Self {
+            fd: AsyncFd::new(fd)?,
+        })
+    }
+
+    /// Connect to `port` on context `cid` (blocking connect, then async I/O)
+    #[cfg(test)]
+    pub fn connect(cid: u32, port: u32) -> io::Result<Self> {
+        // SAFETY: plain socket(2); the descriptor is owned at once
+        let raw =
+            unsafe { libc::socket(libc::AF_VSOCK, libc::SOCK_STREAM | libc::SOCK_CLOEXEC, 0) };
+        if raw < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: socket(2) returned a new descriptor
+        let fd = unsafe { OwnedFd::from_raw_fd(raw) };
+        let addr = sockaddr(cid, port);
+        // SAFETY: `addr` is a valid sockaddr_vm of the length passed
+        let connected = unsafe {
+            libc::connect(
+                fd.as_raw_fd(),
+                &addr as *const libc::sockaddr_vm as *const libc::sockaddr,
+                std::mem::size_of::<libc::sockaddr_vm>() as libc::socklen_t,
+            )
+        };
+        if connected < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `fd` is open; switch it to non-blocking for AsyncFd
+        unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETFL, libc::O_NONBLOCK) };
+        Self::new(fd)
+    }
+}
+
+impl AsyncRead for VsockStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        loop {
+            let mut guard = ready!(self.fd.poll_read_ready(cx))?;
+            let unfilled = buf.initialize_unfilled();
+            let read = guard.try_io(|fd| {
+                // SAFETY: `unfilled` is writable for its whole length
+                let n = unsafe {
+                    libc::read(
+                        fd.as_raw_fd(),
+                        unfilled.as_mut_ptr() as *mut libc::c_void,
+                        unfilled.len(),
+                    )
+                };
+                match n < 0 {
+                    true => Err(io::Error::last_os_error()),
+                    false => Ok(n as usize),
+                }
+            });
+            if let Ok(result) = read {
+                buf.advance(result?);
+                return Poll::Ready(Ok(()));
+            }
+        }
+    }
+}
+
+impl AsyncWrite for VsockStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        data: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            let mut guard = ready!(self.fd.poll_write_ready(cx))?;
+            let written = guard.try_io(|fd| {
+                // SAFETY: `data` is readable for its whole length
+                let n = unsafe {
+                    libc::write(
+                        fd.as_raw_fd(),
+                        data.as_ptr() as *const libc::c_void,
+                        data.len(),
+                    )
+                };
+                match n < 0 {
+                    true => Err(io::Error::last_os_error()),
+                    false => Ok(n as usize),
+                }
+            });
+            if let Ok(result) = written {
+                return Poll::Ready(result);
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        // SAFETY: `fd` is an open socket
+        match unsafe { libc::shutdown(self.fd.as_raw_fd(), libc::SHUT_WR) } < 0 {
+            true => Poll::Ready(Err(io::Error::last_os_error())),
+            false => Poll::Ready(Ok(())),
+        }
+    }
+}
+
+/// Non-blocking AF_VSOCK stream socket
+fn socket() -> io::Result<OwnedFd> {
+    // SAFETY: plain socket(2); the descriptor is owned at once
+    let raw = unsafe {
+        libc::socket(
+            libc::AF_VSOCK,
+            libc::SOCK_STREAM | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+            0,
+        )
+    };
+    if raw < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: socket(2) returned a new descriptor
+    Ok(unsafe { OwnedFd::from_raw_fd(raw) })
+}
+
+fn sockaddr(cid: u32, port: u32) -> libc::sockaddr_vm {
+    // SAFETY: sockaddr_vm is plain data; zero is valid for every field
+    let mut addr: libc::sockaddr_vm = unsafe { std::mem::zeroed() };
+    addr.svm_family = libc::AF_VSOCK as libc::sa_family_t;
+    addr.svm_cid = cid;
+    addr.svm_port = port;
+    addr
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    #[tokio::test]
+    async fn test_vsock_roundtrip() {
+        // Needs the vsock modules; without them there is nothing to test
+        let Ok(listener) = VsockListener::bind(libc::VMADDR_PORT_ANY) else {
+            return;
+        };
+        let mut addr: libc::sockaddr_vm = unsafe { std::mem::zeroed() };
+        let mut len = std::mem::size_of::<libc::sockaddr_vm>() as libc::socklen_t;
+        let named = unsafe {
+            libc::getsockname(
+                listener.fd.as_raw_fd(),
+                &mut addr as *mut libc::sockaddr_vm as *mut libc::sockaddr,
+                &mut len,
+            )
+        };
+        assert_eq!(named, 0);
+        assert_ne!(addr.svm_port, libc::VMADDR_PORT_ANY);
+
+        // Loopback (CID 1) needs vsock_loopback as well
+        let port = addr.svm_port;
+        let Ok(Ok(mut client)) =
+            tokio::task::spawn_blocking(move || VsockStream::connect(libc::VMADDR_CID_LOCAL, port))
+                .await
+        else {
+            return;
+        };
+        let (server, cid) = listener.accept().await.unwrap();
+        assert_eq!(cid, libc::VMADDR_CID_LOCAL);
+
+        client.write_all(b"order filled\n").await.unwrap();
+        let (reader, mut writer) = tokio::io::split(server);
+        let mut line = String::new();
+        BufReader::new(reader).read_line(&mut line).await.unwrap();
+        assert_eq!(line, "order filled\n");
+        writer.write_all(b"{\"id\":1}\n").await.unwrap();
+        let mut ack = String::new();
+        BufReader::new(client).read_line(&mut ack).await.unwrap();
+        assert_eq!(ack, "{\"id\":1}\n");
+    }
+}
@@ -0,0 +1,334 @@
+//! Self-Watchdog - Last Resort When the Kernel Itself Stalls
+//!
+//! A kill-switch that is alive but wedged is worse than a dead one: the
+//! supervisor sees a running process while the agent runs unprotected. A
+//! plain OS thread, independent of the async runtime, checks that:
+//!
+//! - a heartbeat task still gets scheduled (event-loop starvation)
+//! - no audit write has been in progress for longer than `stall_ms`
+//! - no more than `max_panics` kernel tasks have panicked
+//!
+//! When a check fails, `action` runs once, in order: `pause` suspends every
+//! target PID, `webhook` sends a `stall` event to the `[notify]` webhooks and
+//! email (from the watchdog thread, not the wedged runtime), and `exit` ends
+//! the process with status 70 so the supervisor restarts it.
+//!
+//! ```toml
+//! [watchdog]
+//! stall_ms = 10000
+//! max_panics = 3
+//! action = ["pause", "webhook", "exit"]
+//! ```
+
+use crate::notify::{Event, Notifier, NotifyConfig};
+use serde::Deserialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+/// Exit status after a stall (EX_SOFTWARE)
+pub const EXIT_CODE: i32 = 70;
+
+/// Panics anywhere in the process since the hook was installed
+static PANICS: AtomicU64 = AtomicU64::new(0);
+
+/// `[watchdog]` table
+#[derive(Debug, Clone, Deserialize)]
+pub struct WatchdogConfig {
+    /// How long the event loop or an audit write may stall (milliseconds;
+    /// 0 disables the watchdog)
+    #[serde(default = "default_stall_ms")]
+    pub stall_ms: u64,
+    /// Task panics tolerated before the kernel counts as wedged
+    #[serde(default = "default_max_panics")]
+    pub max_panics: u64,
+    /// Last-resort actions, run in order
+    #[serde(default = "default_action")]
+    pub action: Vec<WatchdogAction>,
+}
+
+fn default_stall_ms() -> u64 {
+    10_000
+}
+
+fn default_max_panics() -> u64 {
+    3
+}
+
+fn default_action() -> Vec<WatchdogAction> {
+    vec![WatchdogAction::Webhook, WatchdogAction::Exit]
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            stall_ms: default_stall_ms(),
+            max_panics: default_max_panics(),
+            action: default_action(),
+        }
+    }
+}
+
+impl WatchdogConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.stall_ms == 0 {
+            return Ok(());
+        }
+        if self.stall_ms < 1000 {
+            return Err("watchdog: stall_ms must be 0 (disabled) or at least 1000".to_string());
+        }
+        if self.action.is_empty() {
+            return Err("watchdog: action must not be empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchdogAction {
+    /// Suspend every target (SIGSTOP)
+    Pause,
+    /// Send a `stall` event to the `[notify]` webhooks and email
+    Webhook,
+    /// Exit with `EXIT_CODE`
+    Exit,
+}
+
+impl WatchdogAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Pause => "pause",
+            Self::Webhook => "webhook",
+            Self::Exit => "exit",
+        }
+    }
+}
+
+/// What the watchdog thread watches and how it reacts
+pub struct Watchdog {
+    config: WatchdogConfig,
+    notify: NotifyConfig,
+    /// Unix ms of the runtime heartbeat's last tick
+    heartbeat: Arc<AtomicU64>,
+    /// Audit file and the start of its write in progress (0 when idle)
+    writers: Vec<(String, Arc<AtomicU64>)>,
+    /// PIDs suspended by `pause`
+    targets: Vec<u32>,
+}
+
+impl Watchdog {
+    pub fn new(config: &WatchdogConfig, notify: &NotifyConfig) -> Self {
+        Self {
+            config: config.clone(),
+            notify: notify.clone(),
+            heartbeat: Arc::new(AtomicU64::new(now_ms())),
+            writers: Vec::new(),
+            targets: Vec::new(),
+        }
+    }
+
+    pub fn with_audit_writer(mut self, name: &str, busy_since: Arc<AtomicU64>) -> Self {
+        self.writers.push((name.to_string(), busy_since));
+        self
+    }
+
+    pub fn with_target(mut self, pid: u32) -> Self {
+        self.targets.push(pid);
+        self
+    }
+
+    /// Start the heartbeat task and the watchdog thread; both stop with
+    /// `shutdown`, since a drain may legitimately take a while
+    pub fn start(self, shutdown: CancellationToken) {
+        let period = Duration::from_millis(self.config.stall_ms / 4);
+
+        let heartbeat = Arc::clone(&self.heartbeat);
+        let stop = shutdown.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(period);
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => heartbeat.store(now_ms(), Ordering::Relaxed),
+                    _ = stop.cancelled() => break,
+                }
+            }
+        });
+
+        std::thread::Builder::new()
+            .name("tripwired-watchdog".to_string())
+            .spawn(move || self.watch(&shutdown, period))
+            .expect("Failed to start watchdog thread");
+    }
+
+    fn watch(&self, shutdown: &CancellationToken, period: Duration) {
+        let mut tripped = false;
+        loop {
+            std::thread::sleep(period);
+            if shutdown.is_cancelled() {
+                return;
+            }
+            match self.check(now_ms(), PANICS.load(Ordering::Relaxed)) {
+                Some(reason) if !tripped => {
+                    tripped = true;
+                    self.fire(&reason);
+                }
+                Some(_) => {}
+                None if tripped => {
+                    tripped = false;
+                    info!("🐕 Watchdog: kernel recovered");
+                }
+                None => {}
+            }
+        }
+    }
+
+    /// Why the kernel counts as wedged, if it does
+    fn check(&self, now: u64, panics: u64) -> Option<String> {
+        let stall_ms = self.config.stall_ms;
+        let stalled = now.saturating_sub(self.heartbeat.load(Ordering::Relaxed));
+        if stalled > stall_ms {
+            return Some(format!("event loop stalled for {}ms", stalled));
+        }
+        for (name, busy_since) in &self.writers {
+            let since = busy_since.load(Ordering::Relaxed);
+            if since != 0 && now.saturating_sub(since) > stall_ms {
+                return Some(format!(
+                    "audit writer {} stalled for {}ms",
+                    name,
+                    now - since
+                ));
+            }
+        }
+        if panics > self.config.max_panics {
+            return Some(format!("{} panics in kernel tasks", panics));
+        }
+        None
+    }
+
+    fn fire(&self, reason: &str) {
+        error!("💀 Watchdog: {} - the kernel is not protecting", reason);
+        for action in &self.config.action {
+            match action {
+                WatchdogAction::Pause => {
+                    for &pid in &self.targets {
+                        crate::pause_process(pid);
+                    }
+                }
+                WatchdogAction::Webhook => self.alert(reason),
+                WatchdogAction::Exit => {
+                    error!(
+                        "💀 Watchdog: exiting with status {} for the supervisor to restart",
+                        EXIT_CODE
+                    );
+                    std::process::exit(EXIT_CODE);
+                }
+            }
+        }
+    }
+
+    /// Deliver a `stall` event on a runtime of our own
+    fn alert(&self, reason: &str) {
+        let runtime = match tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+        {
+            Ok(runtime) => runtime,
+            Err(e) => {
+                warn!("📣 Watchdog alert not sent: {}", e);
+                return;
+            }
+        };
+        let timeout = Duration::from_millis(self.notify.timeout_ms);
+        runtime.block_on(async {
+            let notifier = Notifier::new(&self.notify);
+            notifier.send(Event::Stall {
+                reason: reason.to_string(),
+            });
+            if !notifier.drain(timeout).await {
+                warn!("📣 Watchdog alert not delivered within {:?}", timeout);
+            }
+        });
+    }
+}
+
+/// Panics counted so far (since [`install_panic_hook`])
+pub fn panics() -> u64 {
+    PANICS.load(Ordering::Relaxed)
+}
+
+/// Count panics on top of the default hook's report, for the watchdog and
+/// `/healthz` (contained panics included: the release profile unwinds)
+pub fn install_panic_hook() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        PANICS.fetch_add(1, Ordering::Relaxed);
+        previous(info);
+    }));
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_counts_contained_panics() {
+        install_panic_hook();
+        let before = panics();
+        let caught = crate::contain::catch_unwind(async { panic!("drill") }).await;
+        assert_eq!(caught, Err("drill".to_string()));
+        let task = tokio::spawn(async { panic!("drill") }).await;
+        assert!(task.unwrap_err().is_panic());
+        // Other tests may panic meanwhile
+        assert!(panics() >= before + 2);
+    }
+
+    #[test]
+    fn test_check() {
+        let busy = Arc::new(AtomicU64::new(0));
+        let watchdog = Watchdog::new(&WatchdogConfig::default(), &NotifyConfig::default())
+            .with_audit_writer("audit.jsonl", Arc::clone(&busy));
+        let t0 = watchdog.heartbeat.load(Ordering::Relaxed);
+
+        assert_eq!(watchdog.check(t0 + 10_000, 0), None);
+        assert_eq!(
+            watchdog.check(t0 + 10_001, 0).unwrap(),
+            "event loop stalled for 10001ms"
+        );
+
+        watchdog.heartbeat.store(t0 + 30_000, Ordering::Relaxed);
+        busy.store(t0 + 15_000, Ordering::Relaxed);
+        assert_eq!(
+            watchdog.check(t0 + 30_000, 0).unwrap(),
+            "audit writer audit.jsonl stalled for 15000ms"
+        );
+        busy.store(0, Ordering::Relaxed);
+        assert_eq!(watchdog.check(t0 + 30_000, 3), None);
+        assert_eq!(
+            watchdog.check(t0 + 30_000, 4).unwrap(),
+            "4 panics in kernel tasks"
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = |toml: &str| toml::from_str::<WatchdogConfig>(toml).unwrap();
+        assert!(config("").validate().is_ok());
+        assert!(config("stall_ms = 0\naction = []").validate().is_ok());
+        assert!(config("stall_ms = 500").validate().is_err());
+        assert!(config("action = []").validate().is_err());
+        assert_eq!(
+            config("action = ['pause', 'exit']").action,
+            [WatchdogAction::Pause, WatchdogAction::Exit]
+        );
+    }
+}
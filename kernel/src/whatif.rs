@@ -0,0 +1,416 @@
+//! What-If - Replaying Past Decisions Against a Proposed Config
+//!
+//! `tripwired what-if --config new.toml --audit old-audit.jsonl` replays
+//! every decision in an audit file through a proposed config and reports
+//! the ones that would come out differently, before a rule change reaches
+//! an armed kernel:
+//!
+//! ```text
+//! What-if: new.toml against old-audit.jsonl
+//!   replayed   1234 decisions (2 encrypted, 5 from other sources skipped)
+//!   unchanged  1220
+//!   changed    14
+//!     KILL → SUSTAIN          9
+//!     SUSTAIN → KILL          3
+//!     SUSTAIN → ANALYZE       2
+//!   #812 KILL → SUSTAIN  rule trading#3 → none  "sell 100% of position"
+//! ```
+//!
+//! Each input goes through the new filter (excludes and rules). A kill rule
+//! makes a KILL. An analyze rule reuses the verdict the LLM recorded for
+//! the line (before policy), or asks the LLM again with `--llm-url` (and
+//! `--prompt-file` for a new prompt; the agent history sent originally is
+//! not in the trail, so `{context}` is empty). The new `[[policy.rule]]`
+//! entries then run at the record's own time. An input with no recorded
+//! LLM verdict (it was not escalated, or judged in degraded mode) is
+//! reported as `ANALYZE` unless `--llm-url` is given.
+//!
+//! The current config (`--filter-config`) tells filter decisions apart from
+//! records of other sources (rate, limit, sequence and monitor rules),
+//! which are skipped, as are encrypted and backfilled records; redacted
+//! records replay their redacted text. `--fail-on-change` exits 1 when
+//! any decision changed, for CI.
+
+use crate::audit::DecisionRecord;
+use crate::budget::BUDGET_RULE;
+use crate::error::KernelError;
+use crate::filter::{Filter, FilterConfig, RuleAction};
+use crate::line::preview;
+use crate::llm::LlmClient;
+use crate::policy::{Policy, Verdict};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+/// Verdict of an escalated line with no recorded LLM answer
+const ANALYZE: &str = "ANALYZE";
+
+/// `tripwired what-if` options
+#[derive(Debug, Clone)]
+pub struct WhatIfOptions {
+    /// Proposed filter config
+    pub config: PathBuf,
+    pub audit: PathBuf,
+    /// Re-analyze escalations with this LLM instead of reusing verdicts
+    pub llm_url: Option<String>,
+    pub model: String,
+    pub max_tokens: u32,
+    pub prompt_file: Option<PathBuf>,
+    pub json: bool,
+    /// Changes listed (all are counted)
+    pub limit: usize,
+    pub fail_on_change: bool,
+}
+
+/// A decision that would come out differently
+#[derive(Debug, Clone, Serialize)]
+pub struct Change {
+    pub id: u64,
+    pub timestamp_ms: u64,
+    pub input_log: String,
+    pub old_action: String,
+    pub new_action: String,
+    pub old_rule: Option<String>,
+    pub new_rule: Option<String>,
+    /// New policy rule that decided it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub policy: Option<String>,
+}
+
+/// Outcome of the replay
+#[derive(Debug, Default, Serialize)]
+pub struct WhatIf {
+    pub config: PathBuf,
+    pub audit: PathBuf,
+    /// Decisions replayed
+    pub replayed: u64,
+    pub unchanged: u64,
+    /// Encrypted records (not readable)
+    pub encrypted: u64,
+    /// Records of other sources, and backfilled ones
+    pub skipped: u64,
+    /// `OLD → NEW` counts
+    pub transitions: BTreeMap<String, u64>,
+    /// Every change, oldest first
+    pub changes: Vec<Change>,
+}
+
+/// One input's fate under the proposed config
+#[derive(Debug, PartialEq)]
+struct Outcome {
+    action: String,
+    rule: Option<String>,
+    policy: Option<String>,
+}
+
+/// The proposed config, compiled
+pub struct Replayer {
+    filter: Filter,
+    policy: Policy,
+    /// Names of filter rules in either config
+    rules: HashSet<String>,
+    llm: Option<LlmClient>,
+}
+
+impl Replayer {
+    pub fn new(current: &FilterConfig, proposed: &FilterConfig) -> Result<Self, KernelError> {
+        let filter = Filter::new(proposed)?.without_prescreen();
+        let mut rules: HashSet<String> = filter.rule_stats().into_iter().map(|s| s.id).collect();
+        rules.extend(Filter::new(current)?.rule_stats().into_iter().map(|s| s.id));
+        rules.insert(BUDGET_RULE.to_string());
+        Ok(Self {
+            filter,
+            policy: Policy::new(&proposed.policy)?,
+            rules,
+            llm: None,
+        })
+    }
+
+    /// Ask this LLM instead of reusing recorded verdicts
+    pub fn with_llm(mut self, llm: LlmClient) -> Self {
+        self.llm = Some(llm);
+        self
+    }
+
+    /// `None`: not a filter decision
+    async fn replay(&self, record: &DecisionRecord) -> Result<Option<Outcome>, KernelError> {
+        if record
+            .rule
+            .as_ref()
+            .is_some_and(|r| !self.rules.contains(r))
+        {
+            return Ok(None);
+        }
+        let Some(rule) = self.filter.check(&record.input_log) else {
+            return Ok(Some(Outcome {
+                action: "SUSTAIN".to_string(),
+                rule: None,
+                policy: None,
+            }));
+        };
+        // The model's verdict: asked again, or as recorded
+        let recorded = !record.filtered && record.error.is_none();
+        let (action, confidence, reason) = match (rule.action, &self.llm) {
+            (RuleAction::Kill, _) => ("KILL".to_string(), 100, None),
+            (RuleAction::Analyze, Some(llm)) => {
+                let decision = llm.analyze(&record.input_log, "").await?;
+                (
+                    decision.action.to_string(),
+                    decision.confidence,
+                    decision.reason,
+                )
+            }
+            (RuleAction::Analyze, None) if recorded => (
+                record.verdict.clone().unwrap_or(record.action.clone()),
+                record.confidence,
+                record.reason.clone(),
+            ),
+            (RuleAction::Analyze, None) => (ANALYZE.to_string(), 0, None),
+        };
+        let mut outcome = Outcome {
+            action,
+            rule: Some(rule.name.clone()),
+            policy: None,
+        };
+        if outcome.action == ANALYZE {
+            return Ok(Some(outcome));
+        }
+        let verdict = Verdict {
+            action: &outcome.action,
+            confidence,
+            rule: Some(&rule.name),
+            reason: reason.as_deref(),
+            model: Some(&record.model_fingerprint),
+            filtered: rule.action == RuleAction::Kill,
+        };
+        let agent = crate::agents::AgentStatus {
+            agent_id: record.agent_id.clone().unwrap_or_default(),
+            session_id: record.session_id.clone().unwrap_or_default(),
+            ..Default::default()
+        };
+        if let Some(applied) = self
+            .policy
+            .decide_at(&verdict, &agent, &[], record.timestamp_ms)
+        {
+            outcome.action = applied.action.as_str().to_string();
+            outcome.policy = Some(applied.rule);
+        }
+        Ok(Some(outcome))
+    }
+
+    /// Replay every decision in `path`
+    pub async fn run(&self, path: &Path) -> Result<WhatIf, KernelError> {
+        let mut report = WhatIf {
+            audit: path.to_path_buf(),
+            ..Default::default()
+        };
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            // Headers and events have an `event` field; torn lines are skipped
+            let Ok(value) = serde_json::from_str::<serde_json::Value>(&line) else {
+                continue;
+            };
+            if value.get("event").is_some() {
+                continue;
+            }
+            let Ok(record) = serde_json::from_value::<DecisionRecord>(value) else {
+                continue;
+            };
+            if record.encrypted {
+                report.encrypted += 1;
+                continue;
+            }
+            if record.backfilled {
+                report.skipped += 1;
+                continue;
+            }
+            let Some(outcome) = self.replay(&record).await? else {
+                report.skipped += 1;
+                continue;
+            };
+            report.replayed += 1;
+            if outcome.action == record.action {
+                report.unchanged += 1;
+                continue;
+            }
+            *report
+                .transitions
+                .entry(format!("{} → {}", record.action, outcome.action))
+                .or_default() += 1;
+            report.changes.push(Change {
+                id: record.id,
+                timestamp_ms: record.timestamp_ms,
+                input_log: record.input_log,
+                old_action: record.action,
+                new_action: outcome.action,
+                old_rule: record.rule,
+                new_rule: outcome.rule,
+                policy: outcome.policy,
+            });
+        }
+        Ok(report)
+    }
+}
+
+/// `tripwired what-if`: `current` is the `--filter-config` in force
+pub async fn run(
+    options: &WhatIfOptions,
+    current: &FilterConfig,
+    proposed: &FilterConfig,
+) -> Result<(), KernelError> {
+    let mut replayer = Replayer::new(current, proposed)?;
+    if let Some(ref url) = options.llm_url {
+        let mut llm = LlmClient::new(url, &options.model, options.max_tokens)
+            .with_sampling(proposed.sampling(&options.model))
+            .with_examples(proposed.prompt.examples.clone());
+        if let Some(ref path) = options.prompt_file {
+            llm = llm.with_prompt(crate::llm::load_prompt(path)?);
+        }
+        replayer = replayer.with_llm(llm);
+    }
+    let mut report = replayer.run(&options.audit).await?;
+    report.config = options.config.clone();
+
+    match options.json {
+        true => println!("{}", serde_json::to_string_pretty(&report)?),
+        false => print(&report, options.limit),
+    }
+    if options.fail_on_change && !report.changes.is_empty() {
+        return Err(format!("{} decision(s) would change", report.changes.len()).into());
+    }
+    Ok(())
+}
+
+fn print(report: &WhatIf, limit: usize) {
+    println!(
+        "What-if: {} against {}",
+        report.config.display(),
+        report.audit.display()
+    );
+    println!(
+        "  replayed   {} decisions ({} encrypted, {} from other sources skipped)",
+        report.replayed, report.encrypted, report.skipped
+    );
+    println!("  unchanged  {}", report.unchanged);
+    println!("  changed    {}", report.changes.len());
+    for (transition, n) in &report.transitions {
+        println!("    {:<22}{:>6}", transition, n);
+    }
+    if report.transitions.keys().any(|t| t.ends_with(ANALYZE)) {
+        println!("    (ANALYZE: escalated, but no recorded LLM verdict; see --llm-url)");
+    }
+    for change in report.changes.iter().take(limit) {
+        println!(
+            "  #{} {} → {}  rule {} → {}{}  {:?}",
+            change.id,
+            change.old_action,
+            change.new_action,
+            change.old_rule.as_deref().unwrap_or("none"),
+            change.new_rule.as_deref().unwrap_or("none"),
+            match change.policy {
+                Some(ref policy) => format!(" (policy {})", policy),
+                None => String::new(),
+            },
+            preview(&change.input_log, 80)
+        );
+    }
+    if report.changes.len() > limit {
+        println!("  ... {} more", report.changes.len() - limit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::{AuditTrail, ModelFingerprint, RecordInput};
+    use crate::llm::Sampling;
+
+    #[tokio::test]
+    async fn test_what_if() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let fp = ModelFingerprint::new("test-model", "http://localhost", 30, &Sampling::default());
+        let trail = AuditTrail::new(path.clone(), fp, "p").unwrap();
+        let record = |input_log: &str, action: &str, rule: Option<&str>, filtered: bool| {
+            trail
+                .record_entry(RecordInput {
+                    input_log,
+                    action,
+                    confidence: 70,
+                    filtered,
+                    rule,
+                    ..Default::default()
+                })
+                .unwrap();
+        };
+        // LLM verdicts on an analyze rule, a kill rule, a pass and a rate rule
+        record("wire transfer 900", "KILL", Some("wire"), false);
+        record("wire transfer 5", "SUSTAIN", Some("wire"), false);
+        record("purge all backups", "KILL", Some("purge"), true);
+        record("heartbeat", "SUSTAIN", None, true);
+        record("rate orders 400/min", "KILL", Some("orders-rate"), false);
+
+        let current: FilterConfig = toml::from_str(
+            r#"
+            [[rule]]
+            id = "wire"
+            pattern = "wire transfer"
+            [[rule]]
+            id = "purge"
+            pattern = "purge"
+            action = "kill"
+            "#,
+        )
+        .unwrap();
+        // `purge` now needs the LLM, heartbeats escalate, and unsure kills pause
+        let proposed: FilterConfig = toml::from_str(
+            r#"
+            [[rule]]
+            id = "wire"
+            pattern = "wire transfer"
+            [[rule]]
+            id = "purge"
+            pattern = "purge"
+            [[rule]]
+            id = "beat"
+            pattern = "heartbeat"
+            [[policy.rule]]
+            name = "unsure-kill"
+            when = 'decision.action == "KILL" && decision.confidence < 80'
+            action = "pause"
+            "#,
+        )
+        .unwrap();
+
+        let report = Replayer::new(&current, &proposed)
+            .unwrap()
+            .run(&path)
+            .await
+            .unwrap();
+        assert_eq!((report.replayed, report.skipped), (4, 1));
+        assert_eq!(report.unchanged, 1);
+        let changes: Vec<(&str, &str, Option<&str>)> = report
+            .changes
+            .iter()
+            .map(|c| {
+                (
+                    c.input_log.as_str(),
+                    c.new_action.as_str(),
+                    c.policy.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                ("wire transfer 900", "PAUSE", Some("unsure-kill")),
+                ("purge all backups", ANALYZE, None),
+                ("heartbeat", ANALYZE, None),
+            ]
+        );
+        assert_eq!(report.transitions["KILL → PAUSE"], 1);
+        assert_eq!(report.transitions["SUSTAIN → ANALYZE"], 1);
+    }
+}